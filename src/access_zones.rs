@@ -0,0 +1,266 @@
+//! Coordinate-Space Access Control Zones
+//!
+//! Operators name zones, either a hyperbolic disk around a coordinate or an
+//! explicit set of nodes, and attach rules to them:
+//!
+//! - `no_transit`: packets from zone `from` may not be forwarded through
+//!   zone `through`. Delivering to a node inside `through` is not transit
+//!   and stays allowed.
+//! - `signed_control_only`: only signed control packets may cross into
+//!   `zone`, delivery included. Traffic already inside is not re-checked.
+//!
+//! `GPRouter` enforces the rules at forward time: a neighbor a rule denies
+//! is never chosen as next hop, in any routing mode, so packets route
+//! around a zone where the topology allows and are dropped where it does
+//! not. Each routing decision a rule changed is recorded in a bounded
+//! decision log naming the packet, the denied hops and the rule, so an
+//! operator can audit what the policy did.
+//!
+//! Disk zones place nodes by the routing coordinates the router knows; a
+//! node whose coordinate is unknown is outside every disk zone.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::PoincareDiskPoint;
+
+/// Region of a zone
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ZoneRegion {
+    /// Nodes within hyperbolic distance `radius` of `center`
+    Disk { center: PoincareDiskPoint, radius: f64 },
+    /// Exactly these nodes
+    Nodes { nodes: Vec<NodeId> },
+}
+
+/// A named region of the overlay
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Zone {
+    pub name: String,
+    pub region: ZoneRegion,
+}
+
+/// A restriction on traffic between zones
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ZoneRule {
+    /// Packets from `from` may not be forwarded through `through`
+    NoTransit { from: String, through: String },
+    /// Only signed control packets may enter `zone`
+    SignedControlOnly { zone: String },
+}
+
+/// Zones and their rules
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ZoneConfig {
+    pub zones: Vec<Zone>,
+    pub rules: Vec<ZoneRule>,
+    /// Decisions kept in the log; the oldest are dropped first
+    pub log_capacity: usize,
+}
+
+impl Default for ZoneConfig {
+    fn default() -> Self {
+        Self {
+            zones: Vec::new(),
+            rules: Vec::new(),
+            log_capacity: 256,
+        }
+    }
+}
+
+impl ZoneConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        let mut names = HashSet::new();
+        for zone in &self.zones {
+            if !names.insert(zone.name.as_str()) {
+                return Err(format!("Zone {} is defined twice", zone.name));
+            }
+            if let ZoneRegion::Disk { center, radius } = &zone.region {
+                if center.euclidean_norm_sq() >= 1.0 || !(radius.is_finite() && *radius > 0.0) {
+                    return Err(format!("Zone {} needs a center inside the disk and a positive radius", zone.name));
+                }
+            }
+        }
+        for rule in &self.rules {
+            let referenced: Vec<&String> = match rule {
+                ZoneRule::NoTransit { from, through } => vec![from, through],
+                ZoneRule::SignedControlOnly { zone } => vec![zone],
+            };
+            if let Some(unknown) = referenced.into_iter().find(|name| !names.contains(name.as_str())) {
+                return Err(format!("Zone rule refers to unknown zone {}", unknown));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What rules know about a packet beyond its addresses
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PacketAccess {
+    /// Carries a signature
+    pub signed: bool,
+    /// Protocol control traffic rather than application payload
+    pub control: bool,
+}
+
+/// Routing coordinate of a node, if known
+pub type CoordLookup<'a> = dyn Fn(&NodeId) -> Option<PoincareDiskPoint> + 'a;
+
+/// A routing decision changed by zone rules
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ZoneDecision {
+    pub source: NodeId,
+    pub destination: NodeId,
+    /// Node that made the decision
+    pub at: NodeId,
+    /// Neighbors denied as next hop, with the index of the denying rule
+    pub denied: Vec<(NodeId, usize)>,
+    /// Next hop chosen instead, or None if the packet was dropped
+    pub next_hop: Option<NodeId>,
+}
+
+/// Zone rules ready for evaluation, with their decision log
+#[derive(Debug, Default)]
+pub struct ZonePolicy {
+    config: ZoneConfig,
+    log: Mutex<VecDeque<ZoneDecision>>,
+}
+
+impl ZonePolicy {
+    pub fn new(config: ZoneConfig) -> Self {
+        Self { config, log: Mutex::new(VecDeque::new()) }
+    }
+
+    pub fn config(&self) -> &ZoneConfig {
+        &self.config
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.config.rules.is_empty()
+    }
+
+    fn contains(&self, zone: &str, node: &NodeId, coord_of: &CoordLookup) -> bool {
+        self.config.zones.iter().filter(|z| z.name == zone).any(|z| match &z.region {
+            ZoneRegion::Disk { center, radius } => {
+                coord_of(node).is_some_and(|p| p.hyperbolic_distance(center) <= *radius)
+            }
+            ZoneRegion::Nodes { nodes } => nodes.contains(node),
+        })
+    }
+
+    /// Index of the first rule that forbids forwarding from `at` to `hop`
+    pub fn denies(
+        &self,
+        coord_of: &CoordLookup,
+        source: &NodeId,
+        destination: &NodeId,
+        access: PacketAccess,
+        at: &NodeId,
+        hop: &NodeId,
+    ) -> Option<usize> {
+        self.config.rules.iter().position(|rule| match rule {
+            ZoneRule::NoTransit { from, through } => {
+                hop != destination && self.contains(through, hop, coord_of) && self.contains(from, source, coord_of)
+            }
+            ZoneRule::SignedControlOnly { zone } => {
+                !(access.signed && access.control)
+                    && self.contains(zone, hop, coord_of)
+                    && !self.contains(zone, at, coord_of)
+            }
+        })
+    }
+
+    /// Record a decision, dropping the oldest beyond the log capacity
+    pub fn record(&self, decision: ZoneDecision) {
+        let mut log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        log.push_back(decision);
+        while log.len() > self.config.log_capacity {
+            log.pop_front();
+        }
+    }
+
+    /// Logged decisions, oldest first
+    pub fn decisions(&self) -> Vec<ZoneDecision> {
+        self.log.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config() -> ZoneConfig {
+        ZoneConfig {
+            zones: vec![
+                Zone { name: "core".into(), region: ZoneRegion::Disk { center: PoincareDiskPoint::origin(), radius: 0.5 } },
+                Zone { name: "guests".into(), region: ZoneRegion::Nodes { nodes: vec![NodeId::new("guest")] } },
+            ],
+            rules: vec![
+                ZoneRule::NoTransit { from: "guests".into(), through: "core".into() },
+                ZoneRule::SignedControlOnly { zone: "core".into() },
+            ],
+            log_capacity: 2,
+        }
+    }
+
+    #[test]
+    fn test_rules_match_zones() {
+        let policy = ZonePolicy::new(config());
+        let coords: HashMap<NodeId, PoincareDiskPoint> = [("hub", 0.1), ("edge", 0.8), ("guest", 0.9)]
+            .iter()
+            .map(|(id, x)| (NodeId::new(*id), PoincareDiskPoint::new(*x, 0.0).unwrap()))
+            .collect();
+        let (hub, edge, guest, far) = (NodeId::new("hub"), NodeId::new("edge"), NodeId::new("guest"), NodeId::new("far"));
+        let coords = |id: &NodeId| coords.get(id).copied();
+        let data = PacketAccess::default();
+        let signed_control = PacketAccess { signed: true, control: true };
+
+        // Guests may not transit the core, but may deliver into it if allowed otherwise
+        assert_eq!(policy.denies(&coords, &guest, &far, signed_control, &edge, &hub), Some(0));
+        assert_eq!(policy.denies(&coords, &guest, &hub, signed_control, &edge, &hub), None);
+        // Unsigned or data traffic may not enter the core
+        assert_eq!(policy.denies(&coords, &edge, &far, data, &edge, &hub), Some(1));
+        assert_eq!(policy.denies(&coords, &edge, &far, PacketAccess { signed: true, control: false }, &edge, &hub), Some(1));
+        assert_eq!(policy.denies(&coords, &edge, &far, signed_control, &edge, &hub), None);
+        // Traffic already inside, and hops outside, are unaffected
+        assert_eq!(policy.denies(&coords, &hub, &far, data, &hub, &hub), None);
+        assert_eq!(policy.denies(&coords, &edge, &far, data, &hub, &edge), None);
+        // An unknown coordinate is outside every disk
+        assert_eq!(policy.denies(&coords, &edge, &far, data, &edge, &far), None);
+    }
+
+    #[test]
+    fn test_validation_and_log() {
+        assert!(config().validate().is_ok());
+        let mut unknown = config();
+        unknown.rules.push(ZoneRule::SignedControlOnly { zone: "nowhere".into() });
+        assert!(unknown.validate().unwrap_err().contains("nowhere"));
+        let mut bad_radius = config();
+        bad_radius.zones[0].region = ZoneRegion::Disk { center: PoincareDiskPoint::origin(), radius: 0.0 };
+        assert!(bad_radius.validate().is_err());
+
+        let policy = ZonePolicy::new(config());
+        for at in ["a", "b", "c"] {
+            policy.record(ZoneDecision {
+                source: NodeId::new("s"),
+                destination: NodeId::new("d"),
+                at: NodeId::new(at),
+                denied: vec![(NodeId::new("x"), 0)],
+                next_hop: None,
+            });
+        }
+        let kept: Vec<String> = policy.decisions().into_iter().map(|d| d.at.0).collect();
+        assert_eq!(kept, ["b", "c"]);
+
+        let json = serde_json::to_string(&config()).unwrap();
+        assert!(json.contains("\"kind\":\"no_transit\""));
+        assert_eq!(serde_json::from_str::<ZoneConfig>(&json).unwrap(), config());
+    }
+}
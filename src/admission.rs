@@ -0,0 +1,287 @@
+//! Join Admission Control
+//!
+//! When a region comes back after an outage, many nodes rediscover their old
+//! neighbors at once and each join triggers coordinate refinement and
+//! gossip. A token bucket limits how many previously unknown peers a node
+//! accepts from discovery per second. A rejected joiner gets a discovery
+//! reply carrying a backoff hint and retries that address only after the
+//! hinted delay plus random jitter, so retries do not arrive in lockstep.
+//!
+//! Admitted peers are onboarded in stages: for `onboarding_ms` they are
+//! routed to by their ID anchor and left out of the local Ricci flow, and
+//! only then does their advertised coordinate enter the embedding.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+
+/// Join admission settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdmissionConfig {
+    pub enabled: bool,
+    /// New peers accepted per second once the burst is used up
+    pub joins_per_sec: f64,
+    /// New peers accepted back to back
+    pub burst: u32,
+    /// How long an admitted peer is routed to by its anchor only
+    pub onboarding_ms: u64,
+    /// Shortest backoff hinted to a rejected joiner
+    pub min_backoff_ms: u64,
+    /// Longest backoff hinted to a rejected joiner
+    pub max_backoff_ms: u64,
+}
+
+impl Default for AdmissionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            joins_per_sec: 5.0,
+            burst: 10,
+            onboarding_ms: 10_000,
+            min_backoff_ms: 1_000,
+            max_backoff_ms: 30_000,
+        }
+    }
+}
+
+impl AdmissionConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.joins_per_sec.is_finite() || self.joins_per_sec <= 0.0 || self.burst == 0 {
+            return Err("Join admission rate and burst must be positive".to_string());
+        }
+        if self.min_backoff_ms == 0 || self.min_backoff_ms > self.max_backoff_ms {
+            return Err("Join backoff must satisfy 0 < min_backoff_ms <= max_backoff_ms".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Retry hint sent to a rejected joiner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JoinBackoff {
+    /// Do not send discovery to the rejecting node before this much time
+    pub retry_after_ms: u64,
+    /// Add a uniformly random delay of up to this much
+    pub jitter_ms: u64,
+}
+
+impl JoinBackoff {
+    /// Delay before retrying, with jitter drawn from `rng`
+    pub fn delay_ms(&self, rng: &mut impl Rng) -> u64 {
+        self.retry_after_ms + rng.gen_range(0..=self.jitter_ms)
+    }
+}
+
+/// Outcome of a discovery from a peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdmissionDecision {
+    /// The peer is already a neighbor
+    Known,
+    /// The peer is new and accepted
+    Admit,
+    /// The peer is new and must retry later
+    Reject(JoinBackoff),
+}
+
+/// Admission and backoff counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdmissionStats {
+    pub admitted: u64,
+    pub rejected: u64,
+    /// Peers currently routed to by anchor only
+    pub onboarding: usize,
+    /// Addresses this node is backing off from after being rejected
+    pub deferred: usize,
+}
+
+/// Token bucket over joins, onboarding stages, and backoffs toward others
+#[derive(Debug, Clone)]
+pub struct JoinAdmission {
+    config: AdmissionConfig,
+    tokens: f64,
+    last_ms: u64,
+    /// Rejections since the bucket was last full, scaling the backoff hint
+    pressure: u32,
+    /// Admitted peers still onboarding, with their admission time
+    onboarding: HashMap<NodeId, u64>,
+    /// Addresses that rejected this node, with the earliest retry time
+    deferred: HashMap<SocketAddr, u64>,
+    admitted: u64,
+    rejected: u64,
+}
+
+impl Default for JoinAdmission {
+    fn default() -> Self {
+        Self::new(AdmissionConfig::default())
+    }
+}
+
+impl JoinAdmission {
+    pub fn new(config: AdmissionConfig) -> Self {
+        Self {
+            tokens: config.burst as f64,
+            config,
+            last_ms: 0,
+            pressure: 0,
+            onboarding: HashMap::new(),
+            deferred: HashMap::new(),
+            admitted: 0,
+            rejected: 0,
+        }
+    }
+
+    pub fn config(&self) -> &AdmissionConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: AdmissionConfig) {
+        self.tokens = self.tokens.min(config.burst as f64);
+        if !config.enabled {
+            self.onboarding.clear();
+        }
+        self.config = config;
+    }
+
+    fn refill(&mut self, now_ms: u64) {
+        let elapsed = now_ms.saturating_sub(self.last_ms) as f64 / 1000.0;
+        let burst = self.config.burst as f64;
+        self.tokens = (self.tokens + elapsed * self.config.joins_per_sec).min(burst);
+        self.last_ms = self.last_ms.max(now_ms);
+        if self.tokens >= burst {
+            self.pressure = 0;
+        }
+    }
+
+    /// Decide whether to accept a discovery from `peer`
+    ///
+    /// `known` is whether the peer is already a neighbor; refreshing a known
+    /// neighbor never uses a token.
+    pub fn decide(&mut self, peer: &NodeId, known: bool, now_ms: u64) -> AdmissionDecision {
+        if known {
+            return AdmissionDecision::Known;
+        }
+        if !self.config.enabled {
+            self.admitted += 1;
+            return AdmissionDecision::Admit;
+        }
+        self.refill(now_ms);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            self.admitted += 1;
+            self.onboarding.insert(peer.clone(), now_ms);
+            return AdmissionDecision::Admit;
+        }
+        self.rejected += 1;
+        self.pressure = self.pressure.saturating_add(1);
+        AdmissionDecision::Reject(self.backoff_hint())
+    }
+
+    /// Backoff for the next rejected joiner
+    ///
+    /// Starts at the time until a token is free and grows with the number
+    /// of joiners rejected since the bucket was last full, so a storm
+    /// spreads its retries over a longer window.
+    fn backoff_hint(&self) -> JoinBackoff {
+        let refill_ms = ((1.0 - self.tokens) / self.config.joins_per_sec * 1000.0).ceil() as u64;
+        let waiting = self.pressure as f64 / self.config.burst as f64;
+        let retry_after_ms = (refill_ms.max(self.config.min_backoff_ms) as f64 * (1.0 + waiting)) as u64;
+        let retry_after_ms = retry_after_ms.min(self.config.max_backoff_ms);
+        JoinBackoff { retry_after_ms, jitter_ms: retry_after_ms / 2 }
+    }
+
+    /// Whether `peer` is still routed to by anchor only
+    pub fn is_onboarding(&self, peer: &NodeId, now_ms: u64) -> bool {
+        self.onboarding
+            .get(peer)
+            .is_some_and(|admitted| now_ms.saturating_sub(*admitted) < self.config.onboarding_ms)
+    }
+
+    /// Forget peers that finished onboarding or left
+    pub fn retain_onboarding(&mut self, mut keep: impl FnMut(&NodeId) -> bool, now_ms: u64) {
+        let onboarding_ms = self.config.onboarding_ms;
+        self.onboarding
+            .retain(|peer, admitted| now_ms.saturating_sub(*admitted) < onboarding_ms && keep(peer));
+    }
+
+    /// Back off from `addr` after it rejected this node
+    pub fn defer(&mut self, addr: SocketAddr, backoff: JoinBackoff, now_ms: u64) {
+        let retry_at = now_ms + backoff.delay_ms(&mut rand::thread_rng());
+        self.deferred.insert(addr, retry_at);
+    }
+
+    /// Whether discovery may be sent to `addr`
+    pub fn may_contact(&mut self, addr: &SocketAddr, now_ms: u64) -> bool {
+        self.deferred.retain(|_, retry_at| *retry_at > now_ms);
+        !self.deferred.contains_key(addr)
+    }
+
+    pub fn stats(&self, now_ms: u64) -> AdmissionStats {
+        AdmissionStats {
+            admitted: self.admitted,
+            rejected: self.rejected,
+            onboarding: self.onboarding.keys().filter(|peer| self.is_onboarding(peer, now_ms)).count(),
+            deferred: self.deferred.values().filter(|retry_at| **retry_at > now_ms).count(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AdmissionConfig {
+        AdmissionConfig { enabled: true, joins_per_sec: 2.0, burst: 2, onboarding_ms: 1_000, ..Default::default() }
+    }
+
+    #[test]
+    fn test_bucket_limits_new_joins() {
+        let mut admission = JoinAdmission::new(config());
+        let peer = |i: u32| NodeId::new(format!("peer{}", i));
+
+        assert_eq!(admission.decide(&peer(0), false, 0), AdmissionDecision::Admit);
+        assert_eq!(admission.decide(&peer(1), false, 0), AdmissionDecision::Admit);
+        // Known neighbors are never limited
+        assert_eq!(admission.decide(&peer(0), true, 0), AdmissionDecision::Known);
+        let AdmissionDecision::Reject(first) = admission.decide(&peer(2), false, 0) else {
+            panic!("third join should be rejected");
+        };
+        let AdmissionDecision::Reject(second) = admission.decide(&peer(3), false, 0) else {
+            panic!("fourth join should be rejected");
+        };
+        assert!(first.retry_after_ms >= 1_000);
+        assert!(second.retry_after_ms > first.retry_after_ms);
+        assert!(second.retry_after_ms <= 30_000);
+
+        // Half a second refills one token
+        assert_eq!(admission.decide(&peer(2), false, 500), AdmissionDecision::Admit);
+        assert_eq!((admission.stats(500).admitted, admission.stats(500).rejected), (3, 2));
+
+        assert!(admission.is_onboarding(&peer(0), 999));
+        assert!(!admission.is_onboarding(&peer(0), 1_000));
+        admission.retain_onboarding(|_| true, 1_000);
+        assert_eq!(admission.stats(1_000).onboarding, 1);
+    }
+
+    #[test]
+    fn test_rejected_joiner_defers_with_jitter() {
+        let mut admission = JoinAdmission::new(config());
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let backoff = JoinBackoff { retry_after_ms: 1_000, jitter_ms: 500 };
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let delay = backoff.delay_ms(&mut rng);
+            assert!((1_000..=1_500).contains(&delay));
+        }
+
+        admission.defer(addr, backoff, 0);
+        assert!(!admission.may_contact(&addr, 999));
+        assert!(admission.may_contact(&"127.0.0.1:9001".parse().unwrap(), 999));
+        assert!(admission.may_contact(&addr, 1_501));
+        assert_eq!(admission.stats(1_501).deferred, 0);
+    }
+}
@@ -2,14 +2,32 @@
 //!
 //! This module provides a REST API using axum for interacting with DRFE-R nodes.
 //! It exposes endpoints for packet sending, status queries, and topology inspection.
-
+//!
+//! GET requests are reads; all other methods are control requests and need
+//! the operator role. Clients present `Authorization: Bearer <token>`; see
+//! `api_access` for tokens, roles and per-client limits.
+
+use crate::access_zones::ZoneDecision;
+use crate::api_access::{AccessError, RequestClass};
+use crate::chaos::ExperimentStatus;
+use crate::config::{ConfigUpdate, NodeConfig};
+use crate::convergence::ConvergenceSummary;
+use crate::coordinate_control::CoordinateControlState;
+use crate::coordinate_history::{CoordinateSample, ReplayReport};
 use crate::coordinates::NodeId;
+use crate::dead_letter::{DeadLetter, DeadLetterStats};
+use crate::header_budget::HeaderStatsEntry;
+use crate::health::{HealthReport, HealthStatus};
 use crate::network::DistributedNode;
+use crate::latency_map::{LatencyMapSnapshot, RankedCandidate};
+use crate::memory::MemoryReport;
+use crate::path_query::PathEstimate;
+use crate::route_stats::RouteStatsSnapshot;
 use axum::{
-    extract::{Path, State, Request},
-    http::{StatusCode, HeaderMap},
+    extract::{ConnectInfo, Path, Query, State, Request},
+    http::{header, Method, StatusCode, HeaderMap},
     response::{IntoResponse, Response},
-    routing::{get, post},
+    routing::{delete, get, post},
     Json, Router,
     middleware::{self, Next},
 };
@@ -175,6 +193,76 @@ pub struct TopologyEdge {
     pub distance: f64,
 }
 
+/// Recorded coordinates of one node
+#[derive(Debug, Serialize)]
+pub struct CoordinateHistoryEntry {
+    /// Node ID
+    pub id: String,
+    /// Whether this is the local node
+    pub is_local: bool,
+    /// Samples, oldest first
+    pub samples: Vec<CoordinateSample>,
+}
+
+/// Request to replay a failed delivery against past coordinates
+#[derive(Debug, Deserialize)]
+pub struct ReplayRequest {
+    /// Destination node ID
+    pub destination: String,
+    /// When the delivery failed (Unix time in milliseconds)
+    pub failed_at_ms: u64,
+    /// TTL the packet was sent with (defaults to 64)
+    #[serde(default = "default_ttl")]
+    pub ttl: u32,
+}
+
+/// Undeliverable packet queued on the node
+#[derive(Debug, Serialize)]
+pub struct DeadLetterEntry {
+    pub id: u64,
+    pub destination: String,
+    /// Payload data (base64 encoded)
+    pub payload: String,
+    pub ttl: u32,
+    /// Error of the last failed attempt
+    pub reason: String,
+    pub first_failed_ms: u64,
+    pub last_failed_ms: u64,
+    /// Failed retries so far
+    pub redeliveries: u32,
+}
+
+impl From<DeadLetter> for DeadLetterEntry {
+    fn from(letter: DeadLetter) -> Self {
+        Self {
+            id: letter.id,
+            destination: letter.destination.0,
+            payload: base64::engine::general_purpose::STANDARD.encode(&letter.payload),
+            ttl: letter.ttl,
+            reason: letter.reason,
+            first_failed_ms: letter.first_failed_ms,
+            last_failed_ms: letter.last_failed_ms,
+            redeliveries: letter.redeliveries,
+        }
+    }
+}
+
+/// Dead-letter queue contents and counters
+#[derive(Debug, Serialize)]
+pub struct DeadLetterList {
+    pub letters: Vec<DeadLetterEntry>,
+    pub stats: DeadLetterStats,
+}
+
+/// Result of retrying dead letters
+#[derive(Debug, Serialize)]
+pub struct DeadLetterRetryResponse {
+    /// Letters that were sent
+    pub sent: usize,
+    /// Letters still queued after the retry
+    pub remaining: usize,
+}
+
 /// API error type
 #[derive(Debug)]
 pub enum ApiError {
@@ -186,6 +274,8 @@ pub enum ApiError {
     Internal(String),
     /// Unauthorized (authentication failed)
     Unauthorized(String),
+    /// Authenticated, but the role does not allow the request
+    Forbidden(String),
     /// Rate limit exceeded
     TooManyRequests(String),
 }
@@ -197,6 +287,7 @@ impl IntoResponse for ApiError {
             ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
             ApiError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
             ApiError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
+            ApiError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
             ApiError::TooManyRequests(msg) => (StatusCode::TOO_MANY_REQUESTS, msg),
         };
 
@@ -208,6 +299,20 @@ impl IntoResponse for ApiError {
     }
 }
 
+impl From<AccessError> for ApiError {
+    fn from(error: AccessError) -> Self {
+        match error {
+            AccessError::Unauthenticated(msg) => ApiError::Unauthorized(msg),
+            AccessError::Forbidden(msg) => ApiError::Forbidden(msg),
+            AccessError::RateLimited(_) => ApiError::TooManyRequests(error.to_string()),
+        }
+    }
+}
+
+/// Node ID whose signature on a request was verified
+#[derive(Debug, Clone)]
+struct SignedBy(String);
+
 /// Authentication middleware
 ///
 /// Verifies Ed25519 signatures on requests when authentication is enabled.
@@ -218,7 +323,7 @@ impl IntoResponse for ApiError {
 async fn auth_middleware(
     State(state): State<ApiState>,
     headers: HeaderMap,
-    request: Request,
+    mut request: Request,
     next: Next,
 ) -> Result<Response, ApiError> {
     // Skip authentication if not required
@@ -289,6 +394,39 @@ async fn auth_middleware(
         .verify(message.as_bytes(), &signature)
         .map_err(|_| ApiError::Unauthorized("Signature verification failed".to_string()))?;
 
+    let signed_by = SignedBy(node_id.to_string());
+    drop(auth_keys);
+    request.extensions_mut().insert(signed_by);
+    Ok(next.run(request).await)
+}
+
+/// Access control middleware
+///
+/// Authenticates the bearer token, checks that the client's role allows
+/// the request and applies the node's per-client read or control limit.
+async fn access_middleware(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let class = match *request.method() {
+        Method::GET | Method::HEAD | Method::OPTIONS => RequestClass::Read,
+        _ => RequestClass::Control,
+    };
+    let bearer = match headers.get(header::AUTHORIZATION) {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|v| v.strip_prefix("Bearer "))
+                .ok_or_else(|| ApiError::Unauthorized("Malformed Authorization header".to_string()))?,
+        ),
+        None => None,
+    };
+    let signed_by = request.extensions().get::<SignedBy>().map(|s| s.0.as_str());
+    let client = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|c| c.0.ip());
+    state.node.authorize_api(bearer, signed_by, client, class).await?;
     Ok(next.run(request).await)
 }
 
@@ -327,10 +465,32 @@ pub fn create_router(state: ApiState) -> Router {
         .route("/api/v1/nodes/:id", get(get_node_info))
         .route("/api/v1/nodes/:id/neighbors", get(get_node_neighbors))
         .route("/api/v1/topology", get(get_topology))
+        .route("/api/v1/health", get(get_health))
+        .route("/api/v1/config", get(get_config).put(update_config))
+        .route("/api/v1/coordinates/history", get(get_coordinate_history))
+        .route("/api/v1/debug/replay", post(replay_delivery))
+        .route("/api/v1/debug/coordinate-control", get(get_coordinate_control))
+        .route("/api/v1/dead-letters", get(get_dead_letters))
+        .route("/api/v1/dead-letters/retry", post(retry_dead_letters))
+        .route("/api/v1/dead-letters/:id", delete(delete_dead_letter))
+        .route("/api/v1/dead-letters/:id/retry", post(retry_dead_letter))
+        .route("/api/v1/route-stats", get(get_route_stats))
+        .route("/api/v1/header-stats", get(get_header_stats))
+        .route("/api/v1/memory", get(get_memory))
+        .route("/api/v1/convergence", get(get_convergence))
+        .route("/api/v1/paths/:id", get(get_path))
+        .route("/api/v1/latency", get(get_latency_map))
+        .route("/api/v1/latency/rank", get(rank_by_latency))
+        .route("/api/v1/zones/decisions", get(get_zone_decisions))
+        .route("/api/v1/chaos/experiments", get(get_chaos_experiments))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             rate_limit_middleware,
         ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            access_middleware,
+        ))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             auth_middleware,
@@ -552,6 +712,209 @@ async fn get_topology(
     Ok(Json(TopologyResponse { nodes, edges }))
 }
 
+/// GET /api/v1/health - Get node health
+///
+/// Responds with 503 Service Unavailable when the node is unhealthy.
+async fn get_health(State(state): State<ApiState>) -> (StatusCode, Json<HealthReport>) {
+    let report = state.node.health_report().await;
+    let status = match report.status {
+        HealthStatus::Unhealthy => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::OK,
+    };
+    (status, Json(report))
+}
+
+/// GET /api/v1/config - Get the node's runtime configuration
+async fn get_config(State(state): State<ApiState>) -> Json<NodeConfig> {
+    Json(state.node.config().await)
+}
+
+/// PUT /api/v1/config - Change runtime tunables without restarting the node
+///
+/// Only the fields present in the request body are changed.
+async fn update_config(
+    State(state): State<ApiState>,
+    Json(update): Json<ConfigUpdate>,
+) -> Result<Json<NodeConfig>, ApiError> {
+    let config = state.node.apply_config(&update).await.map_err(ApiError::BadRequest)?;
+    Ok(Json(config))
+}
+
+/// GET /api/v1/coordinates/history - Past coordinates of this node and its neighbors
+async fn get_coordinate_history(State(state): State<ApiState>) -> Json<Vec<CoordinateHistoryEntry>> {
+    let local = state.node.id().clone();
+    let mut ids = state.node.coordinate_history_nodes().await;
+    ids.sort_by(|a, b| a.0.cmp(&b.0));
+    ids.insert(0, local.clone());
+
+    let mut entries = Vec::with_capacity(ids.len());
+    for id in ids {
+        entries.push(CoordinateHistoryEntry {
+            samples: state.node.coordinate_history(&id).await,
+            is_local: id == local,
+            id: id.0,
+        });
+    }
+    Json(entries)
+}
+
+/// POST /api/v1/debug/replay - Check whether stale coordinates caused a failed delivery
+async fn replay_delivery(
+    State(state): State<ApiState>,
+    Json(request): Json<ReplayRequest>,
+) -> Result<Json<ReplayReport>, ApiError> {
+    if request.destination.is_empty() {
+        return Err(ApiError::BadRequest("Destination cannot be empty".to_string()));
+    }
+    let dest = NodeId::new(&request.destination);
+    let report = state.node.replay_failed_delivery(&dest, request.failed_at_ms, request.ttl).await;
+    Ok(Json(report))
+}
+
+/// GET /api/v1/debug/coordinate-control - State of the coordinate update controller
+async fn get_coordinate_control(State(state): State<ApiState>) -> Json<CoordinateControlState> {
+    Json(state.node.coordinate_control_state().await)
+}
+
+/// GET /api/v1/dead-letters - Undeliverable packets, oldest first
+async fn get_dead_letters(State(state): State<ApiState>) -> Json<DeadLetterList> {
+    let letters = state.node.dead_letters().await.into_iter().map(DeadLetterEntry::from).collect();
+    Json(DeadLetterList {
+        letters,
+        stats: state.node.dead_letter_stats().await,
+    })
+}
+
+/// POST /api/v1/dead-letters/retry - Retry every dead letter once
+async fn retry_dead_letters(State(state): State<ApiState>) -> Json<DeadLetterRetryResponse> {
+    let sent = state.node.retry_dead_letters().await;
+    Json(DeadLetterRetryResponse {
+        sent,
+        remaining: state.node.dead_letters().await.len(),
+    })
+}
+
+fn parse_dead_letter_id(id: &str) -> Result<u64, ApiError> {
+    id.parse()
+        .map_err(|_| ApiError::BadRequest(format!("Invalid dead letter ID: {}", id)))
+}
+
+/// POST /api/v1/dead-letters/:id/retry - Retry one dead letter
+async fn retry_dead_letter(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> Result<Json<DeadLetterRetryResponse>, ApiError> {
+    let id = parse_dead_letter_id(&id)?;
+    let outcome = state
+        .node
+        .retry_dead_letter(id)
+        .await
+        .ok_or_else(|| ApiError::NotFound(format!("Dead letter {} not found", id)))?;
+    Ok(Json(DeadLetterRetryResponse {
+        sent: usize::from(outcome.is_ok()),
+        remaining: state.node.dead_letters().await.len(),
+    }))
+}
+
+/// DELETE /api/v1/dead-letters/:id - Discard a dead letter
+async fn delete_dead_letter(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> Result<Json<DeadLetterEntry>, ApiError> {
+    let id = parse_dead_letter_id(&id)?;
+    state
+        .node
+        .remove_dead_letter(id)
+        .await
+        .map(|letter| Json(letter.into()))
+        .ok_or_else(|| ApiError::NotFound(format!("Dead letter {} not found", id)))
+}
+
+/// GET /api/v1/route-stats - Delivery, stretch and link statistics for export
+async fn get_route_stats(State(state): State<ApiState>) -> Json<RouteStatsSnapshot> {
+    Json(state.node.route_stats().await)
+}
+
+/// GET /api/v1/header-stats - Header size and overhead per packet type
+async fn get_header_stats(State(state): State<ApiState>) -> Json<Vec<HeaderStatsEntry>> {
+    Json(state.node.header_stats().await)
+}
+
+/// GET /api/v1/memory - Approximate memory held by the major tables, per subsystem
+async fn get_memory(State(state): State<ApiState>) -> Json<MemoryReport> {
+    Json(state.node.memory_report().await)
+}
+
+/// GET /api/v1/convergence - Cluster-wide Ricci flow stress, displacement and convergence status
+async fn get_convergence(State(state): State<ApiState>) -> Json<ConvergenceSummary> {
+    Json(state.node.convergence().await)
+}
+
+/// GET /api/v1/paths/:id - Predicted path and latency to a destination, without sending
+async fn get_path(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> Result<Json<PathEstimate>, ApiError> {
+    if id.is_empty() {
+        return Err(ApiError::BadRequest("Destination cannot be empty".to_string()));
+    }
+    state
+        .node
+        .compute_path(&NodeId::new(&id))
+        .await
+        .map(Json)
+        .map_err(|e| ApiError::NotFound(e.to_string()))
+}
+
+/// GET /api/v1/latency - Estimated RTTs between all known nodes
+async fn get_latency_map(State(state): State<ApiState>) -> Json<LatencyMapSnapshot> {
+    Json(state.node.latency_map().await.snapshot())
+}
+
+/// GET /api/v1/zones/decisions - Routing decisions affected by access control zones
+async fn get_zone_decisions(State(state): State<ApiState>) -> Json<Vec<ZoneDecision>> {
+    Json(state.node.zone_decisions().await)
+}
+
+/// GET /api/v1/chaos/experiments - Progress of the configured chaos experiments
+async fn get_chaos_experiments(State(state): State<ApiState>) -> Json<Vec<ExperimentStatus>> {
+    Json(state.node.chaos_experiments())
+}
+
+/// Query for ranking candidates by latency
+#[derive(Debug, Deserialize)]
+pub struct RankQuery {
+    /// Comma-separated candidate node IDs
+    pub candidates: String,
+    /// Node to measure from; defaults to this node
+    pub from: Option<String>,
+}
+
+/// GET /api/v1/latency/rank?candidates=a,b,c - Candidates nearest first by expected RTT
+///
+/// Candidates whose coordinates are unknown here are left out.
+async fn rank_by_latency(
+    State(state): State<ApiState>,
+    Query(query): Query<RankQuery>,
+) -> Result<Json<Vec<RankedCandidate>>, ApiError> {
+    let candidates: Vec<NodeId> = query
+        .candidates
+        .split(',')
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(NodeId::new)
+        .collect();
+    if candidates.is_empty() {
+        return Err(ApiError::BadRequest("Candidates cannot be empty".to_string()));
+    }
+    let from = query.from.map(NodeId::new).unwrap_or_else(|| state.node.id().clone());
+    let map = state.node.latency_map().await;
+    if map.estimate(&from, &from).is_none() {
+        return Err(ApiError::NotFound(format!("No coordinate known for {}", from)));
+    }
+    Ok(Json(map.rank(&from, &candidates)))
+}
+
 /// Start the API server
 ///
 /// # Arguments
@@ -597,7 +960,7 @@ pub async fn start_api_server(
 
     // Start server
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
@@ -759,6 +1122,185 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_get_health() {
+        let node = create_test_node().await;
+        let state = create_test_state(node);
+
+        // An isolated node has no neighbors and reports unhealthy
+        let (status, report) = get_health(State(state)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(report.0.status, HealthStatus::Unhealthy);
+        assert!(report.0.checks.iter().any(|c| c.name == "neighbors"));
+    }
+
+    #[tokio::test]
+    async fn test_update_config() {
+        let node = create_test_node().await;
+        let state = create_test_state(node);
+
+        let update = ConfigUpdate {
+            max_neighbors: Some(4),
+            heartbeat_interval_ms: Some(250),
+            ..ConfigUpdate::default()
+        };
+        let config = update_config(State(state.clone()), Json(update)).await.unwrap();
+        assert_eq!(config.0.max_neighbors, 4);
+        assert_eq!(config.0.heartbeat_interval_ms, 250);
+
+        let current = get_config(State(state.clone())).await;
+        assert_eq!(current.0, config.0);
+
+        // Invalid updates are rejected and leave the config unchanged
+        let update = ConfigUpdate {
+            failure_timeout_ms: Some(100),
+            ..ConfigUpdate::default()
+        };
+        assert!(update_config(State(state.clone()), Json(update)).await.is_err());
+        assert_eq!(get_config(State(state)).await.0, config.0);
+    }
+
+    #[tokio::test]
+    async fn test_coordinate_history_and_replay() {
+        let node = create_test_node().await;
+        let state = create_test_state(Arc::clone(&node));
+        node.update_coordinates(crate::PoincareDiskPoint::new(0.2, 0.1).unwrap()).await.unwrap();
+
+        let history = get_coordinate_history(State(state.clone())).await.0;
+        assert_eq!(history.len(), 1);
+        assert!(history[0].is_local);
+        assert_eq!(history[0].samples.len(), 2);
+        assert_eq!(history[0].samples[1].version, 1);
+
+        // No neighbors: unreachable then and now, so not a staleness problem
+        let request = ReplayRequest { destination: "other".to_string(), failed_at_ms: 0, ttl: 8 };
+        let report = replay_delivery(State(state), Json(request)).await.unwrap().0;
+        assert_eq!(report.verdict, crate::coordinate_history::StalenessVerdict::NotStale);
+    }
+
+    #[tokio::test]
+    async fn test_get_coordinate_control() {
+        let node = create_test_node().await;
+        let state = create_test_state(Arc::clone(&node));
+        // Sending with no neighbors is a failed greedy decision
+        let _ = node.send_packet(NodeId::new("other"), b"x".to_vec(), 8).await;
+
+        let control = get_coordinate_control(State(state)).await.0;
+        assert_eq!(control.greedy_success, Some(0.0));
+        assert_eq!(control.updates, 0);
+        assert_eq!(control.next_update_ms, 0);
+    }
+
+    #[tokio::test]
+    async fn test_dead_letter_endpoints() {
+        let node = create_test_node().await;
+        let state = create_test_state(Arc::clone(&node));
+        let update = ConfigUpdate {
+            dead_letter: Some(crate::dead_letter::DeadLetterConfig { max_attempts: 1, ..Default::default() }),
+            ..ConfigUpdate::default()
+        };
+        node.apply_config(&update).await.unwrap();
+        for _ in 0..2 {
+            assert!(node.send_packet(NodeId::new("unreachable"), b"hi".to_vec(), 8).await.is_err());
+        }
+
+        let list = get_dead_letters(State(state.clone())).await.0;
+        assert_eq!(list.letters.len(), 2);
+        assert_eq!(list.letters[0].payload, "aGk=");
+        assert_eq!(list.stats.queued, 2);
+
+        let first = list.letters[0].id.to_string();
+        let retried = retry_dead_letter(State(state.clone()), Path(first.clone())).await.unwrap().0;
+        assert_eq!((retried.sent, retried.remaining), (0, 2));
+
+        assert_eq!(delete_dead_letter(State(state.clone()), Path(first.clone())).await.unwrap().0.destination, "unreachable");
+        assert!(matches!(delete_dead_letter(State(state.clone()), Path(first)).await, Err(ApiError::NotFound(_))));
+        assert!(matches!(retry_dead_letter(State(state.clone()), Path("x".to_string())).await, Err(ApiError::BadRequest(_))));
+
+        let retried = retry_dead_letters(State(state)).await.0;
+        assert_eq!((retried.sent, retried.remaining), (0, 1));
+    }
+
+    #[tokio::test]
+    async fn test_get_route_stats() {
+        let node = create_test_node().await;
+        let state = create_test_state(Arc::clone(&node));
+        let update = ConfigUpdate {
+            dead_letter: Some(crate::dead_letter::DeadLetterConfig { max_attempts: 1, ..Default::default() }),
+            route_stats: Some(crate::route_stats::RouteStatsConfig { enabled: true, ..Default::default() }),
+            ..ConfigUpdate::default()
+        };
+        node.apply_config(&update).await.unwrap();
+        // Nothing listens on the neighbor's port, so the send fails on the link
+        let neighbor = NodeId::new("closed");
+        let coord = crate::PoincareDiskPoint::new(0.3, 0.0).unwrap();
+        node.add_neighbor(crate::network::NeighborInfo::new(neighbor.clone(), coord, "127.0.0.1:1".parse().unwrap()))
+            .await;
+        assert!(node.send_packet(neighbor.clone(), b"x".to_vec(), 8).await.is_err());
+
+        let stats = get_route_stats(State(state)).await.0;
+        assert_eq!(stats.neighbors[&neighbor].send_failures, 1);
+        assert!(stats.destinations.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_header_stats() {
+        let node = create_test_node().await;
+        let state = create_test_state(Arc::clone(&node));
+        let neighbor = NodeId::new("closed");
+        let coord = crate::PoincareDiskPoint::new(0.3, 0.0).unwrap();
+        node.add_neighbor(crate::network::NeighborInfo::new(neighbor.clone(), coord, "127.0.0.1:1".parse().unwrap()))
+            .await;
+        let _ = node.send_packet(neighbor, vec![0; 100], 8).await;
+
+        let stats = get_header_stats(State(state)).await.0;
+        let data = stats.iter().find(|e| e.packet_type == crate::network::PacketType::Data).unwrap();
+        assert!(data.counters.packets >= 1);
+        assert!(data.mean_header_bytes > 0.0 && data.overhead_ratio > 0.0);
+        assert_eq!(data.counters.over_budget, 0);
+    }
+
+    #[tokio::test]
+    async fn test_get_memory() {
+        let node = create_test_node().await;
+        let state = create_test_state(Arc::clone(&node));
+        let empty = get_memory(State(state.clone())).await.0;
+        assert_eq!(empty.get("neighbors").entries, 0);
+
+        let neighbor = NodeId::new("closed");
+        let coord = crate::PoincareDiskPoint::new(0.3, 0.0).unwrap();
+        node.add_neighbor(crate::network::NeighborInfo::new(neighbor.clone(), coord, "127.0.0.1:1".parse().unwrap()))
+            .await;
+        let _ = node.send_packet(neighbor, vec![0; 100], 8).await;
+
+        let report = get_memory(State(state)).await.0;
+        assert_eq!(report.get("neighbors").entries, 1);
+        assert_eq!(report.get("dead_letters").entries, 1);
+        assert!(report.get("dead_letters").bytes >= 100);
+        for subsystem in ["router", "tz_table", "seen_packets", "pending_acks"] {
+            assert!(report.subsystems.contains_key(subsystem), "{}", subsystem);
+        }
+        assert_eq!(report.total_bytes, report.subsystems.values().map(|u| u.bytes).sum::<usize>());
+        assert!(report.total_bytes > empty.total_bytes);
+    }
+
+    #[tokio::test]
+    async fn test_get_path() {
+        let node = create_test_node().await;
+        let state = create_test_state(Arc::clone(&node));
+        // Without neighbors there is no first hop
+        let result = get_path(State(state.clone()), Path("elsewhere".to_string())).await;
+        assert!(matches!(result, Err(ApiError::NotFound(_))));
+
+        let neighbor = NodeId::new("next");
+        let coord = crate::PoincareDiskPoint::new(0.3, 0.0).unwrap();
+        node.add_neighbor(crate::network::NeighborInfo::new(neighbor.clone(), coord, "127.0.0.1:1".parse().unwrap()))
+            .await;
+        let path = get_path(State(state), Path(neighbor.0.clone())).await.unwrap().0;
+        assert!(path.complete);
+        assert_eq!(path.nodes(), vec![neighbor]);
+    }
+
     #[tokio::test]
     async fn test_default_ttl() {
         assert_eq!(default_ttl(), 64);
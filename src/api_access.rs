@@ -0,0 +1,287 @@
+//! Access Control for the Control APIs
+//!
+//! The REST and gRPC endpoints expose both introspection and control of the
+//! node. Clients authenticate with a bearer token; the node stores only the
+//! SHA-256 of each token together with its role. `ReadOnly` clients may use
+//! endpoints that observe the node, `Operator` clients may also send
+//! packets and change its configuration. Requests without a token get
+//! `anonymous_role`, which defaults to `ReadOnly` so an exposed port never
+//! grants control of the node by accident; set it to null to reject them,
+//! or to `operator` on a port only trusted clients can reach.
+//!
+//! Reads and control requests can be rate limited separately, per client:
+//! the token name when authenticated, otherwise the client's IP address.
+//! This comes on top of the per-node limit of signed requests in `api`.
+
+use std::net::IpAddr;
+use std::num::NonZeroU32;
+use std::sync::Mutex;
+
+use governor::{DefaultKeyedRateLimiter, Quota, RateLimiter};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+/// Keyed limiter entries kept before idle clients are dropped
+const MAX_TRACKED_CLIENTS: usize = 4096;
+
+/// What a client may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiRole {
+    /// Observe the node: status, topology, statistics
+    ReadOnly,
+    /// Also send packets and change the node's configuration
+    Operator,
+}
+
+/// Kind of request, deciding the role it needs and the limit it counts against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestClass {
+    Read,
+    Control,
+}
+
+impl RequestClass {
+    pub fn required_role(&self) -> ApiRole {
+        match self {
+            RequestClass::Read => ApiRole::ReadOnly,
+            RequestClass::Control => ApiRole::Operator,
+        }
+    }
+}
+
+/// A token clients may present
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiToken {
+    /// Client name, which keys its rate limits
+    pub name: String,
+    /// Hex SHA-256 of the token
+    pub sha256: String,
+    pub role: ApiRole,
+}
+
+impl ApiToken {
+    /// Entry for `token`, for writing configs
+    pub fn new(name: impl Into<String>, token: &str, role: ApiRole) -> Self {
+        Self { name: name.into(), sha256: hash_token(token), role }
+    }
+}
+
+/// Hex SHA-256 of a bearer token
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// API authentication, authorization and rate limit settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiAccessConfig {
+    pub tokens: Vec<ApiToken>,
+    /// Role of requests without a token; None rejects them
+    pub anonymous_role: Option<ApiRole>,
+    /// Read requests per client per minute; None for no limit
+    pub read_requests_per_minute: Option<u32>,
+    /// Control requests per client per minute; None for no limit
+    pub control_requests_per_minute: Option<u32>,
+}
+
+impl Default for ApiAccessConfig {
+    fn default() -> Self {
+        Self {
+            tokens: Vec::new(),
+            anonymous_role: Some(ApiRole::ReadOnly),
+            read_requests_per_minute: None,
+            control_requests_per_minute: None,
+        }
+    }
+}
+
+impl ApiAccessConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.read_requests_per_minute == Some(0) || self.control_requests_per_minute == Some(0) {
+            return Err("API rate limits must be positive".to_string());
+        }
+        let mut names = std::collections::HashSet::new();
+        for token in &self.tokens {
+            if token.name.is_empty() || !names.insert(token.name.as_str()) {
+                return Err(format!("API token names must be unique and non-empty: {:?}", token.name));
+            }
+            if token.sha256.len() != 64 || !token.sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("API token {} must be a hex SHA-256", token.name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Client behind a request with an optional bearer token
+    ///
+    /// Every configured hash is compared in constant time, so response
+    /// timing reveals neither which token matched nor how much of it.
+    pub fn authenticate(&self, bearer: Option<&str>) -> Result<Principal, AccessError> {
+        let Some(token) = bearer else {
+            return Ok(Principal { name: None, role: self.anonymous_role });
+        };
+        let hash = hash_token(token);
+        let mut matched = None;
+        for t in &self.tokens {
+            let stored = t.sha256.to_ascii_lowercase();
+            if bool::from(stored.as_bytes().ct_eq(hash.as_bytes())) {
+                matched = matched.or(Some(t));
+            }
+        }
+        matched
+            .map(|t| Principal { name: Some(t.name.clone()), role: Some(t.role) })
+            .ok_or(AccessError::Unauthenticated("Unknown API token".to_string()))
+    }
+}
+
+/// An authenticated client, or an anonymous one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    /// Token name; None for anonymous clients
+    pub name: Option<String>,
+    /// None when anonymous requests are not allowed
+    pub role: Option<ApiRole>,
+}
+
+impl Principal {
+    /// A client authenticated by other means, such as a request signature
+    pub fn named(name: impl Into<String>, role: ApiRole) -> Self {
+        Self { name: Some(name.into()), role: Some(role) }
+    }
+}
+
+/// Why a request was refused
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AccessError {
+    #[error("{0}")]
+    Unauthenticated(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("Rate limit exceeded for {0}")]
+    RateLimited(String),
+}
+
+type Quotas = (Option<u32>, Option<u32>);
+
+#[derive(Default)]
+struct Limiters {
+    quotas: Quotas,
+    read: Option<DefaultKeyedRateLimiter<String>>,
+    control: Option<DefaultKeyedRateLimiter<String>>,
+}
+
+impl Limiters {
+    fn new(quotas: Quotas) -> Self {
+        let keyed = |per_minute: Option<u32>| {
+            let per_minute = NonZeroU32::new(per_minute?)?;
+            Some(RateLimiter::keyed(Quota::per_minute(per_minute)))
+        };
+        Self { quotas, read: keyed(quotas.0), control: keyed(quotas.1) }
+    }
+}
+
+/// Per-client rate limits of a node's API servers
+#[derive(Default)]
+pub struct ApiAccess {
+    limiters: Mutex<Limiters>,
+}
+
+impl ApiAccess {
+    /// Authorize a request of `class` by `principal` and count it against its limit
+    ///
+    /// `client` keys the limit of anonymous requests.
+    pub fn admit(
+        &self,
+        config: &ApiAccessConfig,
+        principal: &Principal,
+        client: Option<IpAddr>,
+        class: RequestClass,
+    ) -> Result<(), AccessError> {
+        let role = principal
+            .role
+            .ok_or(AccessError::Unauthenticated("An API token is required".to_string()))?;
+        if role < class.required_role() {
+            return Err(AccessError::Forbidden(format!("{:?} role cannot make {:?} requests", role, class)));
+        }
+
+        let key = match (&principal.name, client) {
+            (Some(name), _) => format!("client:{}", name),
+            (None, Some(ip)) => format!("ip:{}", ip),
+            (None, None) => "anonymous".to_string(),
+        };
+        let mut limiters = self.limiters.lock().unwrap_or_else(|e| e.into_inner());
+        let quotas = (config.read_requests_per_minute, config.control_requests_per_minute);
+        if limiters.quotas != quotas {
+            *limiters = Limiters::new(quotas);
+        }
+        let limiter = match class {
+            RequestClass::Read => &limiters.read,
+            RequestClass::Control => &limiters.control,
+        };
+        let Some(limiter) = limiter else {
+            return Ok(());
+        };
+        if limiter.len() > MAX_TRACKED_CLIENTS {
+            limiter.retain_recent();
+        }
+        limiter.check_key(&key).map_err(|_| AccessError::RateLimited(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roles_and_limits() {
+        let config = ApiAccessConfig {
+            tokens: vec![ApiToken::new("ops", "s3cret", ApiRole::Operator), ApiToken::new("dash", "view", ApiRole::ReadOnly)],
+            anonymous_role: None,
+            read_requests_per_minute: Some(2),
+            control_requests_per_minute: Some(1),
+        };
+        config.validate().unwrap();
+        let access = ApiAccess::default();
+
+        assert!(matches!(config.authenticate(Some("wrong")), Err(AccessError::Unauthenticated(_))));
+        let anonymous = config.authenticate(None).unwrap();
+        assert!(matches!(
+            access.admit(&config, &anonymous, None, RequestClass::Read),
+            Err(AccessError::Unauthenticated(_))
+        ));
+
+        let dash = config.authenticate(Some("view")).unwrap();
+        assert_eq!(dash.role, Some(ApiRole::ReadOnly));
+        assert!(matches!(access.admit(&config, &dash, None, RequestClass::Control), Err(AccessError::Forbidden(_))));
+
+        let ops = config.authenticate(Some("s3cret")).unwrap();
+        assert!(access.admit(&config, &ops, None, RequestClass::Control).is_ok());
+        assert!(matches!(access.admit(&config, &ops, None, RequestClass::Control), Err(AccessError::RateLimited(_))));
+        // Reads have their own budget, per client
+        assert!(access.admit(&config, &ops, None, RequestClass::Read).is_ok());
+        assert!(access.admit(&config, &dash, None, RequestClass::Read).is_ok());
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let mut config = ApiAccessConfig { tokens: vec![ApiToken::new("a", "x", ApiRole::Operator)], ..Default::default() };
+        assert!(config.validate().is_ok());
+        config.tokens.push(ApiToken::new("a", "y", ApiRole::ReadOnly));
+        assert!(config.validate().is_err());
+        config.tokens[1] = ApiToken { name: "b".to_string(), sha256: "plain".to_string(), role: ApiRole::ReadOnly };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_default_denies_anonymous_control() {
+        let config = ApiAccessConfig {
+            tokens: vec![ApiToken { sha256: hash_token("x").to_uppercase(), ..ApiToken::new("a", "x", ApiRole::Operator) }],
+            ..Default::default()
+        };
+        assert_eq!(config.authenticate(None).unwrap().role, Some(ApiRole::ReadOnly));
+        assert_eq!(config.authenticate(Some("x")).unwrap().name.as_deref(), Some("a"));
+        assert!(config.authenticate(Some("y")).is_err());
+    }
+}
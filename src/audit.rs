@@ -1,7 +1,9 @@
 //! Audit Logging for DRFE-R Security Events
 //!
 //! This module provides structured audit logging for all security-relevant events
-//! in the DRFE-R system using the tracing crate.
+//! in the DRFE-R system using the tracing crate. Every entry carries an `hlc`
+//! field, a hybrid logical clock reading that orders entries correctly even
+//! when the wall clock steps back.
 
 use serde::{Deserialize, Serialize};
 use std::fmt;
@@ -82,6 +84,7 @@ impl AuditLogger {
             AuditOutcome::Success => {
                 info!(
                     event_type = %SecurityEventType::Authentication,
+                    hlc = %crate::hlc::now(),
                     outcome = %outcome,
                     node_id = %node_id,
                     "Authentication successful"
@@ -90,6 +93,7 @@ impl AuditLogger {
             AuditOutcome::Failure | AuditOutcome::Denied => {
                 warn!(
                     event_type = %SecurityEventType::Authentication,
+                    hlc = %crate::hlc::now(),
                     outcome = %outcome,
                     node_id = %node_id,
                     reason = ?reason,
@@ -116,6 +120,7 @@ impl AuditLogger {
             AuditOutcome::Success => {
                 info!(
                     event_type = %SecurityEventType::SignatureVerification,
+                    hlc = %crate::hlc::now(),
                     outcome = %outcome,
                     packet_id = %packet_id,
                     source_node = %source_node,
@@ -125,6 +130,7 @@ impl AuditLogger {
             AuditOutcome::Failure | AuditOutcome::Denied => {
                 warn!(
                     event_type = %SecurityEventType::SignatureVerification,
+                    hlc = %crate::hlc::now(),
                     outcome = %outcome,
                     packet_id = %packet_id,
                     source_node = %source_node,
@@ -146,6 +152,7 @@ impl AuditLogger {
             AuditOutcome::Denied => {
                 warn!(
                     event_type = %SecurityEventType::RateLimit,
+                    hlc = %crate::hlc::now(),
                     outcome = %outcome,
                     client_id = %client_id,
                     endpoint = %endpoint,
@@ -155,6 +162,7 @@ impl AuditLogger {
             AuditOutcome::Success => {
                 info!(
                     event_type = %SecurityEventType::RateLimit,
+                    hlc = %crate::hlc::now(),
                     outcome = %outcome,
                     client_id = %client_id,
                     endpoint = %endpoint,
@@ -176,6 +184,7 @@ impl AuditLogger {
             AuditOutcome::Success => {
                 info!(
                     event_type = %SecurityEventType::TlsConnection,
+                    hlc = %crate::hlc::now(),
                     outcome = %outcome,
                     peer_addr = %peer_addr,
                     "TLS connection established"
@@ -184,6 +193,7 @@ impl AuditLogger {
             AuditOutcome::Failure => {
                 warn!(
                     event_type = %SecurityEventType::TlsConnection,
+                    hlc = %crate::hlc::now(),
                     outcome = %outcome,
                     peer_addr = %peer_addr,
                     reason = ?reason,
@@ -209,6 +219,7 @@ impl AuditLogger {
     ) {
         error!(
             event_type = %SecurityEventType::MaliciousPacket,
+            hlc = %crate::hlc::now(),
             outcome = %AuditOutcome::Denied,
             packet_id = %packet_id,
             source_node = %source_node,
@@ -229,6 +240,7 @@ impl AuditLogger {
             AuditOutcome::Success => {
                 info!(
                     event_type = %SecurityEventType::NodeLifecycle,
+                    hlc = %crate::hlc::now(),
                     outcome = %outcome,
                     node_id = %node_id,
                     event = %event,
@@ -238,6 +250,7 @@ impl AuditLogger {
             AuditOutcome::Failure => {
                 warn!(
                     event_type = %SecurityEventType::NodeLifecycle,
+                    hlc = %crate::hlc::now(),
                     outcome = %outcome,
                     node_id = %node_id,
                     event = %event,
@@ -263,6 +276,7 @@ impl AuditLogger {
     ) {
         info!(
             event_type = %SecurityEventType::CoordinateUpdate,
+            hlc = %crate::hlc::now(),
             outcome = %outcome,
             node_id = %node_id,
             old_version = %old_version,
@@ -290,6 +304,7 @@ impl AuditLogger {
             AuditOutcome::Success => {
                 info!(
                     event_type = %SecurityEventType::ApiAccess,
+                    hlc = %crate::hlc::now(),
                     outcome = %outcome,
                     client_id = %client_id,
                     method = %method,
@@ -301,6 +316,7 @@ impl AuditLogger {
             AuditOutcome::Failure | AuditOutcome::Denied => {
                 warn!(
                     event_type = %SecurityEventType::ApiAccess,
+                    hlc = %crate::hlc::now(),
                     outcome = %outcome,
                     client_id = %client_id,
                     method = %method,
@@ -327,6 +343,7 @@ impl AuditLogger {
     ) {
         info!(
             event_type = %SecurityEventType::ConfigurationChange,
+            hlc = %crate::hlc::now(),
             outcome = %AuditOutcome::Success,
             changed_by = %changed_by,
             setting = %setting,
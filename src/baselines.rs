@@ -4,9 +4,10 @@
 //! for performance comparison purposes.
 
 use crate::coordinates::NodeId;
+use crate::graph::{BitSet, CsrGraph};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
 /// Trait for DHT routing protocols
 pub trait DHTRouter {
@@ -35,10 +36,14 @@ pub struct DHTRoutingResult {
 /// Each node maintains a finger table with O(log N) entries pointing to
 /// nodes at exponentially increasing distances around the ring.
 pub struct ChordDHT {
-    /// All nodes in the ring, sorted by ID
+    /// All nodes in the ring, sorted by hash once finger tables are built
     nodes: Vec<ChordNode>,
-    /// Map from NodeId to index in nodes vector
-    node_map: HashMap<NodeId, usize>,
+    /// Finger tables as an overlay graph, in finger order
+    fingers: CsrGraph,
+    /// Ring hash by `fingers` index
+    hashes: Vec<u64>,
+    /// Ring successor by `fingers` index
+    successors: Vec<u32>,
     /// Number of bits in the identifier space (m)
     m: usize,
 }
@@ -48,8 +53,6 @@ struct ChordNode {
     id: NodeId,
     /// Hash value in the ring (0 to 2^m - 1)
     hash: u64,
-    /// Finger table: finger[i] points to successor of (n + 2^i) mod 2^m
-    finger_table: Vec<NodeId>,
 }
 
 impl ChordDHT {
@@ -57,22 +60,19 @@ impl ChordDHT {
     pub fn new(m: usize) -> Self {
         Self {
             nodes: Vec::new(),
-            node_map: HashMap::new(),
+            fingers: CsrGraph::from_adjacency(&HashMap::new()),
+            hashes: Vec::new(),
+            successors: Vec::new(),
             m,
         }
     }
     
     /// Add a node to the Chord ring
+    ///
+    /// Routing only sees the node once `build_finger_tables` has run.
     pub fn add_node(&mut self, id: NodeId) {
         let hash = self.hash_node_id(&id);
-        let node = ChordNode {
-            id: id.clone(),
-            hash,
-            finger_table: Vec::new(),
-        };
-        
-        self.nodes.push(node);
-        self.node_map.insert(id, self.nodes.len() - 1);
+        self.nodes.push(ChordNode { id, hash });
     }
     
     /// Build finger tables for all nodes
@@ -80,25 +80,25 @@ impl ChordDHT {
         // Sort nodes by hash value
         self.nodes.sort_by_key(|n| n.hash);
         
-        // Rebuild node_map after sorting
-        self.node_map.clear();
-        for (idx, node) in self.nodes.iter().enumerate() {
-            self.node_map.insert(node.id.clone(), idx);
-        }
-        
-        // Build finger table for each node
-        for i in 0..self.nodes.len() {
-            let node_hash = self.nodes[i].hash;
-            let mut finger_table = Vec::new();
-            
-            for k in 0..self.m {
-                let target = (node_hash + (1u64 << k)) % (1u64 << self.m);
-                let successor = self.find_successor(target);
-                finger_table.push(successor);
-            }
-            
-            self.nodes[i].finger_table = finger_table;
+        // finger[k] points to successor of (n + 2^k) mod 2^m
+        let mut tables = HashMap::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let table = (0..self.m)
+                .map(|k| self.find_successor((node.hash + (1u64 << k)) % (1u64 << self.m)))
+                .collect();
+            tables.insert(node.id.clone(), table);
         }
+        self.fingers = CsrGraph::from_adjacency(&tables);
+
+        let ring: HashMap<&NodeId, usize> = self.nodes.iter().enumerate().map(|(i, n)| (&n.id, i)).collect();
+        let position = |i: u32| ring[self.fingers.id(i)];
+        self.hashes = (0..self.fingers.len() as u32).map(|i| self.nodes[position(i)].hash).collect();
+        self.successors = (0..self.fingers.len() as u32)
+            .map(|i| {
+                let successor = &self.nodes[(position(i) + 1) % self.nodes.len()].id;
+                self.fingers.index_of(successor).expect("ring node in finger graph")
+            })
+            .collect();
     }
     
     /// Hash a node ID to the ring space
@@ -129,22 +129,16 @@ impl ChordDHT {
     }
     
     /// Find the closest preceding node in the finger table
-    fn closest_preceding_finger(&self, node_idx: usize, target_hash: u64) -> Option<NodeId> {
-        let node = &self.nodes[node_idx];
+    fn closest_preceding_finger(&self, node: u32, target_hash: u64) -> Option<u32> {
+        let node_hash = self.hashes[node as usize];
         
         // Search finger table in reverse order for closest preceding node
-        for finger_id in node.finger_table.iter().rev() {
-            if let Some(&finger_idx) = self.node_map.get(finger_id) {
-                let finger_hash = self.nodes[finger_idx].hash;
-                
-                // Check if finger is between node and target (circular)
-                if self.is_between(node.hash, finger_hash, target_hash) {
-                    return Some(finger_id.clone());
-                }
-            }
-        }
-        
-        None
+        self.fingers
+            .neighbors(node)
+            .iter()
+            .rev()
+            .copied()
+            .find(|&finger| self.is_between(node_hash, self.hashes[finger as usize], target_hash))
     }
     
     /// Check if value is between start and end in circular space
@@ -161,13 +155,21 @@ impl DHTRouter for ChordDHT {
     fn route(&self, source: &NodeId, destination: &NodeId, max_hops: u32) -> DHTRoutingResult {
         let dest_hash = self.hash_node_id(destination);
         
-        let mut current_id = source.clone();
-        let mut path = vec![current_id.clone()];
+        let mut path = vec![source.clone()];
         let mut hops = 0;
+
+        let Some(mut current) = self.fingers.index_of(source) else {
+            return DHTRoutingResult {
+                success: false,
+                hops,
+                path,
+                failure_reason: Some("Current node not found".to_string()),
+            };
+        };
         
         while hops < max_hops {
             // Check if we've reached the destination
-            if &current_id == destination {
+            if self.fingers.id(current) == destination {
                 return DHTRoutingResult {
                     success: true,
                     hops,
@@ -176,52 +178,21 @@ impl DHTRouter for ChordDHT {
                 };
             }
             
-            // Get current node index
-            let current_idx = match self.node_map.get(&current_id) {
-                Some(&idx) => idx,
-                None => {
-                    return DHTRoutingResult {
-                        success: false,
-                        hops,
-                        path,
-                        failure_reason: Some("Current node not found".to_string()),
-                    };
-                }
-            };
-            
-            let current_hash = self.nodes[current_idx].hash;
-            
-            // Check if destination is between current and successor
-            let successor_id = if current_idx + 1 < self.nodes.len() {
-                self.nodes[current_idx + 1].id.clone()
-            } else {
-                self.nodes[0].id.clone()
-            };
-            
-            let successor_idx = self.node_map[&successor_id];
-            let successor_hash = self.nodes[successor_idx].hash;
+            let current_hash = self.hashes[current as usize];
+            let successor = self.successors[current as usize];
+            let successor_hash = self.hashes[successor as usize];
             
-            if dest_hash == current_hash || 
+            current = if dest_hash == current_hash || 
                self.is_between(current_hash, dest_hash, successor_hash) ||
                dest_hash == successor_hash {
                 // Destination is our successor
-                current_id = successor_id;
-                path.push(current_id.clone());
-                hops += 1;
-                continue;
-            }
-            
-            // Find closest preceding finger
-            if let Some(next_id) = self.closest_preceding_finger(current_idx, dest_hash) {
-                current_id = next_id;
-                path.push(current_id.clone());
-                hops += 1;
+                successor
             } else {
-                // No better finger, go to successor
-                current_id = successor_id;
-                path.push(current_id.clone());
-                hops += 1;
-            }
+                // Closest preceding finger, or the successor if no finger is better
+                self.closest_preceding_finger(current, dest_hash).unwrap_or(successor)
+            };
+            path.push(self.fingers.id(current).clone());
+            hops += 1;
         }
         
         DHTRoutingResult {
@@ -246,67 +217,65 @@ impl DHTRouter for ChordDHT {
 /// Kademlia uses XOR metric for distance and maintains k-buckets
 /// for routing. Each node maintains O(log N) contacts.
 pub struct KademliaDHT {
-    /// All nodes in the network
-    nodes: HashMap<NodeId, KademliaNode>,
+    /// Hash value of every node, for XOR distance calculation
+    nodes: HashMap<NodeId, Vec<u8>>,
+    /// K-bucket contacts as an overlay graph, bucket by bucket
+    contacts: CsrGraph,
+    /// Hash by `contacts` index
+    hashes: Vec<Vec<u8>>,
     /// Number of bits in node ID
     id_bits: usize,
     /// Bucket size (k parameter)
     k: usize,
 }
 
-#[derive(Debug, Clone)]
-struct KademliaNode {
-    /// Hash value for XOR distance calculation
-    hash: Vec<u8>,
-    /// K-buckets: buckets[i] contains nodes at distance 2^i to 2^(i+1)
-    buckets: Vec<Vec<NodeId>>,
-}
-
 impl KademliaDHT {
     /// Create a new Kademlia DHT
     pub fn new(id_bits: usize, k: usize) -> Self {
         Self {
             nodes: HashMap::new(),
+            contacts: CsrGraph::from_adjacency(&HashMap::new()),
+            hashes: Vec::new(),
             id_bits,
             k,
         }
     }
     
     /// Add a node to the network
+    ///
+    /// Routing only sees the node once `build_routing_tables` has run.
     pub fn add_node(&mut self, id: NodeId) {
         let hash = self.hash_node_id(&id);
-        let node = KademliaNode {
-            hash,
-            buckets: vec![Vec::new(); self.id_bits],
-        };
-        
-        self.nodes.insert(id, node);
+        self.nodes.insert(id, hash);
     }
     
     /// Build k-buckets for all nodes
     pub fn build_routing_tables(&mut self) {
-        let all_ids: Vec<NodeId> = self.nodes.keys().cloned().collect();
+        let mut all_ids: Vec<&NodeId> = self.nodes.keys().collect();
+        all_ids.sort();
         
-        for node_id in &all_ids {
-            let node_hash = self.nodes[node_id].hash.clone();
+        // buckets[i] contains nodes at distance 2^i to 2^(i+1)
+        let mut tables = HashMap::with_capacity(all_ids.len());
+        for &node_id in &all_ids {
+            let node_hash = &self.nodes[node_id];
+            let mut buckets: Vec<Vec<NodeId>> = vec![Vec::new(); self.id_bits];
             
-            for other_id in &all_ids {
+            for &other_id in &all_ids {
                 if node_id == other_id {
                     continue;
                 }
                 
-                let other_hash = &self.nodes[other_id].hash;
-                let distance = self.xor_distance(&node_hash, other_hash);
+                let distance = self.xor_distance(node_hash, &self.nodes[other_id]);
                 let bucket_idx = self.get_bucket_index(distance);
                 
-                if bucket_idx < self.id_bits {
-                    let node = self.nodes.get_mut(node_id).unwrap();
-                    if node.buckets[bucket_idx].len() < self.k {
-                        node.buckets[bucket_idx].push(other_id.clone());
-                    }
+                if bucket_idx < self.id_bits && buckets[bucket_idx].len() < self.k {
+                    buckets[bucket_idx].push(other_id.clone());
                 }
             }
+            tables.insert(node_id.clone(), buckets.concat());
         }
+        self.contacts = CsrGraph::from_adjacency(&tables);
+        self.hashes = (0..self.contacts.len() as u32).map(|i| self.nodes[self.contacts.id(i)].clone()).collect();
     }
     
     /// Hash a node ID
@@ -333,30 +302,20 @@ impl KademliaDHT {
         self.id_bits // All zeros (same node)
     }
     
-    /// Find k closest nodes to a target
-    fn find_closest_nodes(&self, current_id: &NodeId, target_hash: &[u8], k: usize) -> Vec<NodeId> {
-        let current_node = match self.nodes.get(current_id) {
-            Some(n) => n,
-            None => return Vec::new(),
-        };
-        
-        let mut candidates: Vec<(NodeId, Vec<u8>)> = Vec::new();
-        
-        // Collect all nodes from all buckets
-        for bucket in &current_node.buckets {
-            for node_id in bucket {
-                if let Some(node) = self.nodes.get(node_id) {
-                    let distance = self.xor_distance(&node.hash, target_hash);
-                    candidates.push((node_id.clone(), distance));
-                }
-            }
-        }
+    /// Find k closest contacts to a target
+    fn find_closest_nodes(&self, current: u32, target_hash: &[u8], k: usize) -> Vec<u32> {
+        let mut candidates: Vec<(u32, Vec<u8>)> = self
+            .contacts
+            .neighbors(current)
+            .iter()
+            .map(|&contact| (contact, self.xor_distance(&self.hashes[contact as usize], target_hash)))
+            .collect();
         
         // Sort by distance
         candidates.sort_by(|a, b| a.1.cmp(&b.1));
         
         // Return k closest
-        candidates.into_iter().take(k).map(|(id, _)| id).collect()
+        candidates.into_iter().take(k).map(|(contact, _)| contact).collect()
     }
 }
 
@@ -364,15 +323,23 @@ impl DHTRouter for KademliaDHT {
     fn route(&self, source: &NodeId, destination: &NodeId, max_hops: u32) -> DHTRoutingResult {
         let dest_hash = self.hash_node_id(destination);
         
-        let mut current_id = source.clone();
-        let mut path = vec![current_id.clone()];
-        let mut visited = HashSet::new();
-        visited.insert(current_id.clone());
+        let mut path = vec![source.clone()];
         let mut hops = 0;
+
+        let Some(mut current) = self.contacts.index_of(source) else {
+            return DHTRoutingResult {
+                success: false,
+                hops,
+                path,
+                failure_reason: Some("Current node not found".to_string()),
+            };
+        };
+        let mut visited = BitSet::new(self.contacts.len());
+        visited.insert(current);
         
         while hops < max_hops {
             // Check if we've reached the destination
-            if &current_id == destination {
+            if self.contacts.id(current) == destination {
                 return DHTRoutingResult {
                     success: true,
                     hops,
@@ -382,33 +349,26 @@ impl DHTRouter for KademliaDHT {
             }
             
             // Find closest unvisited node to destination
-            let closest_nodes = self.find_closest_nodes(&current_id, &dest_hash, self.k);
-            
-            let mut next_id = None;
-            let mut best_distance = self.xor_distance(
-                &self.nodes[&current_id].hash,
-                &dest_hash
-            );
+            let mut next = None;
+            let mut best_distance = self.xor_distance(&self.hashes[current as usize], &dest_hash);
             
-            for candidate_id in closest_nodes {
-                if visited.contains(&candidate_id) {
+            for candidate in self.find_closest_nodes(current, &dest_hash, self.k) {
+                if visited.contains(candidate) {
                     continue;
                 }
                 
-                if let Some(candidate) = self.nodes.get(&candidate_id) {
-                    let distance = self.xor_distance(&candidate.hash, &dest_hash);
-                    if distance < best_distance {
-                        best_distance = distance;
-                        next_id = Some(candidate_id);
-                    }
+                let distance = self.xor_distance(&self.hashes[candidate as usize], &dest_hash);
+                if distance < best_distance {
+                    best_distance = distance;
+                    next = Some(candidate);
                 }
             }
             
-            match next_id {
-                Some(id) => {
-                    current_id = id;
-                    path.push(current_id.clone());
-                    visited.insert(current_id.clone());
+            match next {
+                Some(candidate) => {
+                    current = candidate;
+                    path.push(self.contacts.id(current).clone());
+                    visited.insert(current);
                     hops += 1;
                 }
                 None => {
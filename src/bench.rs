@@ -0,0 +1,658 @@
+//! Benchmark Harness
+//!
+//! Building blocks shared by the evaluation binaries: synthetic topologies
+//! (Barabási–Albert, Erdős–Rényi, Watts–Strogatz, grids and random trees),
+//! routers built from a PIE embedding, runners that route sampled pairs with
+//! and without a TZ fallback, and the statistics reported alongside them:
+//! stretch against BFS shortest paths, sampled diameter and 95% confidence
+//! intervals over seeds. Everything is deterministic for a given seed, so a
+//! binary, an integration test and a paper's scripts that use the same
+//! settings see the same networks and the same pairs.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Instant;
+
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::{NodeId, RoutingCoordinate};
+use crate::graph::{BfsScratch, CsrGraph};
+use crate::greedy_embedding::{EmbeddingError, GreedyEmbedding};
+use crate::routing::{GPRouter, RoutingNode};
+use crate::simulation::{BatchConfig, Workload};
+use crate::tz_routing::TZRoutingTable;
+use crate::PoincareDiskPoint;
+
+/// A generated network, by index and by node ID
+#[derive(Debug, Clone)]
+pub struct Network {
+    pub nodes: Vec<NodeId>,
+    /// Neighbors of `nodes[i]` as indices into `nodes`
+    pub neighbors: Vec<Vec<usize>>,
+    pub adjacency: HashMap<NodeId, Vec<NodeId>>,
+}
+
+impl Network {
+    /// Network over nodes named `{prefix}_{i}`
+    pub fn from_indices(prefix: &str, neighbors: Vec<Vec<usize>>) -> Self {
+        let nodes: Vec<NodeId> = (0..neighbors.len()).map(|i| NodeId::new(format!("{}_{}", prefix, i))).collect();
+        let adjacency = nodes
+            .iter()
+            .zip(&neighbors)
+            .map(|(id, adj)| (id.clone(), adj.iter().map(|&j| nodes[j].clone()).collect()))
+            .collect();
+        Self { nodes, neighbors, adjacency }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.neighbors.iter().map(Vec::len).sum::<usize>() / 2
+    }
+
+    pub fn avg_degree(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        self.edge_count() as f64 * 2.0 / self.len() as f64
+    }
+
+    /// Largest BFS eccentricity over `samples` random sources
+    pub fn diameter(&self, samples: usize) -> u32 {
+        sampled_diameter(&self.adjacency, samples)
+    }
+}
+
+/// Synthetic topology families
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Topology {
+    /// Preferential attachment, `m` links per new node
+    BarabasiAlbert { m: usize },
+    /// Each pair linked with probability `p`, then made connected
+    ErdosRenyi { p: f64 },
+    /// Ring lattice of degree `k` with links rewired with probability `beta`
+    WattsStrogatz { k: usize, beta: f64 },
+    /// Square grid of `⌊√n⌋²` nodes
+    Grid,
+    /// Random tree whose first `branching` nodes hang off the root
+    Tree { branching: usize },
+}
+
+impl Topology {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Topology::BarabasiAlbert { .. } => "Barabasi-Albert",
+            Topology::ErdosRenyi { .. } => "Erdos-Renyi",
+            Topology::WattsStrogatz { .. } => "Watts-Strogatz",
+            Topology::Grid => "Grid",
+            Topology::Tree { .. } => "Tree",
+        }
+    }
+
+    /// Generate a network of about `n` nodes
+    pub fn generate(&self, n: usize, seed: u64) -> Network {
+        match *self {
+            Topology::BarabasiAlbert { m } => barabasi_albert(n, m, seed),
+            Topology::ErdosRenyi { p } => erdos_renyi(n, p, seed),
+            Topology::WattsStrogatz { k, beta } => watts_strogatz(n, k, beta, seed),
+            Topology::Grid => grid((n as f64).sqrt() as usize),
+            Topology::Tree { branching } => random_tree(n, branching, seed),
+        }
+    }
+}
+
+fn link(neighbors: &mut [Vec<usize>], a: usize, b: usize) {
+    neighbors[a].push(b);
+    neighbors[b].push(a);
+}
+
+/// Barabási–Albert graph: a clique of `m` nodes, then each node links to `m`
+/// earlier ones with probability proportional to their degree
+pub fn barabasi_albert(n: usize, m: usize, seed: u64) -> Network {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut degrees = vec![0usize; n];
+    let mut neighbors = vec![Vec::new(); n];
+
+    for i in 0..m.min(n) {
+        for j in (i + 1)..m.min(n) {
+            link(&mut neighbors, i, j);
+            degrees[i] += 1;
+            degrees[j] += 1;
+        }
+    }
+
+    for i in m..n {
+        let total: usize = degrees[..i].iter().sum();
+        if total == 0 {
+            // No degrees to weight by yet (m <= 1): hang the node off the first
+            link(&mut neighbors, i, 0);
+            degrees[i] += 1;
+            degrees[0] += 1;
+            continue;
+        }
+        let mut connected = HashSet::new();
+        while connected.len() < m.min(i) {
+            let r = rng.gen::<f64>() * total as f64;
+            let mut cumsum = 0.0;
+            for j in 0..i {
+                cumsum += degrees[j] as f64;
+                if cumsum >= r && !connected.contains(&j) {
+                    link(&mut neighbors, i, j);
+                    degrees[i] += 1;
+                    degrees[j] += 1;
+                    connected.insert(j);
+                    break;
+                }
+            }
+        }
+    }
+
+    Network::from_indices("node", neighbors)
+}
+
+/// Erdős–Rényi graph with nodes outside the first one's component attached
+/// to random earlier nodes
+pub fn erdos_renyi(n: usize, p: f64, seed: u64) -> Network {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut neighbors = vec![Vec::new(); n];
+    for i in 0..n {
+        for j in (i + 1)..n {
+            if rng.gen::<f64>() < p {
+                link(&mut neighbors, i, j);
+            }
+        }
+    }
+    attach_unreachable(&mut neighbors, &mut rng);
+    Network::from_indices("er", neighbors)
+}
+
+fn attach_unreachable(neighbors: &mut [Vec<usize>], rng: &mut StdRng) {
+    let n = neighbors.len();
+    if n == 0 {
+        return;
+    }
+    let mut visited = vec![false; n];
+    let mut queue = VecDeque::from([0]);
+    visited[0] = true;
+    while let Some(u) = queue.pop_front() {
+        for &v in &neighbors[u] {
+            if !visited[v] {
+                visited[v] = true;
+                queue.push_back(v);
+            }
+        }
+    }
+    let unreachable: Vec<usize> = (1..n).filter(|&i| !visited[i]).collect();
+    for i in unreachable {
+        let earlier = rng.gen_range(0..i);
+        link(neighbors, i, earlier);
+    }
+}
+
+/// Watts–Strogatz small-world graph
+pub fn watts_strogatz(n: usize, k: usize, beta: f64, seed: u64) -> Network {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut neighbors: Vec<Vec<usize>> = vec![Vec::new(); n];
+
+    for i in 0..n {
+        for j in 1..=k / 2 {
+            let neighbor = (i + j) % n;
+            if !neighbors[i].contains(&neighbor) {
+                link(&mut neighbors, i, neighbor);
+            }
+        }
+    }
+
+    for i in 0..n {
+        for j in neighbors[i].clone() {
+            if j > i && rng.gen::<f64>() < beta {
+                neighbors[i].retain(|&x| x != j);
+                neighbors[j].retain(|&x| x != i);
+                let mut new_j = rng.gen_range(0..n);
+                while new_j == i || neighbors[i].contains(&new_j) {
+                    new_j = rng.gen_range(0..n);
+                }
+                link(&mut neighbors, i, new_j);
+            }
+        }
+    }
+
+    Network::from_indices("ws", neighbors)
+}
+
+/// Square grid with `side` nodes per side
+pub fn grid(side: usize) -> Network {
+    grid_rows(side * side, side)
+}
+
+/// Grid of `n` nodes laid out in rows of `width`; the last row may be partial
+pub fn grid_rows(n: usize, width: usize) -> Network {
+    let mut neighbors = vec![Vec::new(); n];
+    for idx in 0..n {
+        if (idx + 1) % width != 0 && idx + 1 < n {
+            link(&mut neighbors, idx, idx + 1);
+        }
+        if idx + width < n {
+            link(&mut neighbors, idx, idx + width);
+        }
+    }
+    Network::from_indices("grid", neighbors)
+}
+
+/// Path of `n` nodes
+pub fn line(n: usize) -> Network {
+    let mut neighbors = vec![Vec::new(); n];
+    for i in 1..n {
+        link(&mut neighbors, i - 1, i);
+    }
+    Network::from_indices("line", neighbors)
+}
+
+/// Clique of `head_ratio · n` nodes (at least 3) with the rest as a path
+/// hanging off its last node
+pub fn lollipop(n: usize, head_ratio: f64) -> Network {
+    let head = ((n as f64 * head_ratio) as usize).max(3).min(n.saturating_sub(1));
+    let mut neighbors = vec![Vec::new(); n];
+    for i in 0..head {
+        for j in (i + 1)..head {
+            link(&mut neighbors, i, j);
+        }
+    }
+    for i in head.max(1)..n {
+        link(&mut neighbors, i - 1, i);
+    }
+    Network::from_indices("lollipop", neighbors)
+}
+
+/// Random tree: the first `branching` nodes are children of the root, every
+/// later node a child of a uniformly random earlier one
+pub fn random_tree(n: usize, branching: usize, seed: u64) -> Network {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut neighbors = vec![Vec::new(); n];
+    for i in 1..n {
+        let parent = if i <= branching { 0 } else { rng.gen_range(0..i) };
+        link(&mut neighbors, i, parent);
+    }
+    Network::from_indices("tree", neighbors)
+}
+
+/// Router over `network` with PIE coordinates and spanning-tree information
+pub fn build_pie_router(network: &Network) -> Result<GPRouter, EmbeddingError> {
+    let result = GreedyEmbedding::new().embed(&network.adjacency)?;
+
+    let mut tree_parent: HashMap<NodeId, Option<NodeId>> = HashMap::new();
+    tree_parent.insert(result.root.clone(), None);
+    for (parent, children) in &result.tree_children {
+        for child in children {
+            tree_parent.insert(child.clone(), Some(parent.clone()));
+        }
+    }
+
+    let mut router = GPRouter::new();
+    for node_id in &network.nodes {
+        let point = result.coordinates.get(node_id).copied().unwrap_or_else(PoincareDiskPoint::origin);
+        let mut node = RoutingNode::new(node_id.clone(), RoutingCoordinate::new(point, 0));
+        node.set_tree_info(
+            tree_parent.get(node_id).cloned().flatten(),
+            result.tree_children.get(node_id).cloned().unwrap_or_default(),
+        );
+        router.add_node(node);
+    }
+    for (i, adjacent) in network.neighbors.iter().enumerate() {
+        for &j in adjacent.iter().filter(|&&j| i < j) {
+            router.add_edge(&network.nodes[i], &network.nodes[j]);
+        }
+    }
+    router.refresh_tree_metrics();
+
+    Ok(router)
+}
+
+/// Routing test settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoutingTestConfig {
+    /// Source–destination pairs sampled
+    pub pairs: usize,
+    pub seed: u64,
+}
+
+impl Default for RoutingTestConfig {
+    fn default() -> Self {
+        Self { pairs: 500, seed: 42 }
+    }
+}
+
+/// Outcome of routing the sampled pairs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoutingTestResult {
+    pub success_rate: f64,
+    /// Average hops of delivered pairs
+    pub avg_hops: f64,
+    /// Ratio-of-sums stretch over delivered pairs
+    pub stretch: f64,
+    pub max_stretch: f64,
+    /// Share of hops taken in each mode, in percent
+    pub gravity_pct: f64,
+    pub pressure_pct: f64,
+    pub tree_pct: f64,
+    pub tz_pct: f64,
+    /// Wall-clock routing time per pair
+    pub routing_time_us: f64,
+}
+
+/// Distinct random pairs; the same seed gives the same pairs in every runner
+fn sample_pairs(nodes: &[NodeId], count: usize, rng: &mut StdRng) -> Vec<(usize, usize)> {
+    let n = nodes.len();
+    if n < 2 {
+        return Vec::new();
+    }
+    (0..count)
+        .map(|_| {
+            let src = rng.gen_range(0..n);
+            let mut dst = rng.gen_range(0..n);
+            while dst == src {
+                dst = rng.gen_range(0..n);
+            }
+            (src, dst)
+        })
+        .collect()
+}
+
+/// Route pairs with the router's own modes (gravity, pressure, tree)
+pub fn run_routing_tests(router: &GPRouter, nodes: &[NodeId], config: &RoutingTestConfig) -> RoutingTestResult {
+    let mut rng = StdRng::seed_from_u64(config.seed + 1000);
+    let pairs = sample_pairs(nodes, config.pairs, &mut rng)
+        .into_iter()
+        .map(|(src, dst)| (nodes[src].clone(), nodes[dst].clone()))
+        .collect();
+
+    let start = Instant::now();
+    let summary = router.simulate_batch(&Workload::Pairs(pairs), &BatchConfig::default()).summary;
+    let elapsed_us = start.elapsed().as_micros() as f64;
+
+    RoutingTestResult {
+        success_rate: summary.success_rate,
+        avg_hops: summary.avg_hops,
+        stretch: summary.stretch,
+        max_stretch: summary.max_stretch,
+        gravity_pct: summary.gravity_pct,
+        pressure_pct: summary.pressure_pct,
+        tree_pct: summary.tree_pct,
+        tz_pct: 0.0,
+        routing_time_us: elapsed_us / config.pairs.max(1) as f64,
+    }
+}
+
+/// Sums over delivered pairs, turned into a result
+#[derive(Default)]
+struct Tally {
+    successes: u32,
+    hops: u32,
+    optimal: u32,
+    gravity_hops: u32,
+    tz_hops: u32,
+    max_stretch: f64,
+}
+
+impl Tally {
+    fn delivered(&mut self, hops: u32, gravity_hops: u32, optimal: Option<u32>) {
+        self.successes += 1;
+        self.hops += hops;
+        self.gravity_hops += gravity_hops;
+        self.tz_hops += hops - gravity_hops;
+        if let Some(optimal) = optimal {
+            self.optimal += optimal;
+            if optimal > 0 {
+                self.max_stretch = self.max_stretch.max(hops as f64 / optimal as f64);
+            }
+        }
+    }
+
+    fn result(&self, pairs: usize, routing_time_us: f64) -> RoutingTestResult {
+        let ratio = |num: u32, den: u32| if den > 0 { num as f64 / den as f64 } else { 0.0 };
+        RoutingTestResult {
+            success_rate: self.successes as f64 / pairs.max(1) as f64,
+            avg_hops: ratio(self.hops, self.successes),
+            stretch: ratio(self.hops, self.optimal),
+            max_stretch: self.max_stretch,
+            gravity_pct: ratio(self.gravity_hops, self.hops) * 100.0,
+            pressure_pct: 0.0,
+            tree_pct: 0.0,
+            tz_pct: ratio(self.tz_hops, self.hops) * 100.0,
+            routing_time_us,
+        }
+    }
+}
+
+/// Route pairs greedily and finish failed walks along the TZ path from where they stopped
+pub fn run_routing_tests_with_tz(
+    router: &GPRouter,
+    tz_table: &TZRoutingTable,
+    nodes: &[NodeId],
+    config: &RoutingTestConfig,
+) -> RoutingTestResult {
+    let mut rng = StdRng::seed_from_u64(config.seed + 1000);
+    let mut shortest = ShortestPaths::new(router, nodes);
+    let mut tally = Tally::default();
+
+    let start = Instant::now();
+    for (src, dst) in sample_pairs(nodes, config.pairs, &mut rng) {
+        let walk = greedy_walk(router, &nodes[src], &nodes[dst], nodes.len() as u32);
+        let hops = if walk.delivered {
+            Some(walk.hops)
+        } else {
+            tz_table.compute_path(&walk.last, &nodes[dst]).map(|path| walk.hops + (path.len() - 1) as u32)
+        };
+        if let Some(hops) = hops {
+            tally.delivered(hops, walk.hops, shortest.distance(&nodes[src], &nodes[dst]));
+        }
+    }
+    let elapsed_us = start.elapsed().as_micros() as f64;
+
+    tally.result(config.pairs, elapsed_us / config.pairs.max(1) as f64)
+}
+
+/// Route pairs greedily with no fallback
+pub fn run_gravity_only_tests(router: &GPRouter, nodes: &[NodeId], config: &RoutingTestConfig) -> RoutingTestResult {
+    let mut rng = StdRng::seed_from_u64(config.seed + 2000);
+    let mut shortest = ShortestPaths::new(router, nodes);
+    let mut tally = Tally::default();
+
+    for (src, dst) in sample_pairs(nodes, config.pairs, &mut rng) {
+        let walk = greedy_walk(router, &nodes[src], &nodes[dst], nodes.len() as u32);
+        if walk.delivered {
+            tally.delivered(walk.hops, walk.hops, shortest.distance(&nodes[src], &nodes[dst]));
+        }
+    }
+
+    RoutingTestResult { gravity_pct: 100.0, routing_time_us: 0.0, ..tally.result(config.pairs, 0.0) }
+}
+
+/// Where a greedy walk ended
+#[derive(Debug, Clone, PartialEq)]
+pub struct GreedyWalk {
+    pub delivered: bool,
+    pub hops: u32,
+    /// The destination, or the node where no unvisited neighbor was closer
+    pub last: NodeId,
+}
+
+/// Walk toward `dst` by hyperbolic distance, never revisiting a node, for at most `max_hops`
+pub fn greedy_walk(router: &GPRouter, src: &NodeId, dst: &NodeId, max_hops: u32) -> GreedyWalk {
+    let Some(dest_coord) = router.get_node(dst).map(|n| n.coord.point) else {
+        return GreedyWalk { delivered: src == dst, hops: 0, last: src.clone() };
+    };
+
+    let mut current = src.clone();
+    let mut hops = 0;
+    let mut visited = HashSet::from([src.clone()]);
+    while hops < max_hops && &current != dst {
+        let Some(node) = router.get_node(&current) else {
+            break;
+        };
+        let mut best: Option<&NodeId> = None;
+        let mut best_dist = node.coord.point.hyperbolic_distance(&dest_coord);
+        for neighbor in node.neighbors.iter().filter(|id| !visited.contains(*id)) {
+            if let Some(n) = router.get_node(neighbor) {
+                let d = n.coord.point.hyperbolic_distance(&dest_coord);
+                if d < best_dist {
+                    best_dist = d;
+                    best = Some(neighbor);
+                }
+            }
+        }
+        let Some(next) = best.cloned() else {
+            break;
+        };
+        visited.insert(next.clone());
+        current = next;
+        hops += 1;
+    }
+
+    GreedyWalk { delivered: &current == dst, hops, last: current }
+}
+
+/// Shortest-path baseline for stretch: reuses one CSR graph and BFS buffer across queries
+pub struct ShortestPaths {
+    graph: CsrGraph,
+    scratch: BfsScratch,
+}
+
+impl ShortestPaths {
+    pub fn new(router: &GPRouter, nodes: &[NodeId]) -> Self {
+        let adjacency: HashMap<NodeId, Vec<NodeId>> = nodes
+            .iter()
+            .filter_map(|id| router.get_node(id).map(|node| (id.clone(), node.neighbors.clone())))
+            .collect();
+        let graph = CsrGraph::from_adjacency(&adjacency);
+        let scratch = BfsScratch::new(graph.len());
+        Self { graph, scratch }
+    }
+
+    pub fn distance(&mut self, start: &NodeId, end: &NodeId) -> Option<u32> {
+        let (s, e) = (self.graph.index_of(start)?, self.graph.index_of(end)?);
+        self.graph.distance(s, e, &mut self.scratch)
+    }
+}
+
+/// Largest BFS eccentricity over `samples` random sources
+pub fn sampled_diameter(adjacency: &HashMap<NodeId, Vec<NodeId>>, samples: usize) -> u32 {
+    let nodes: Vec<&NodeId> = adjacency.keys().collect();
+    if nodes.is_empty() {
+        return 0;
+    }
+    let mut rng = StdRng::seed_from_u64(12345);
+    let graph = CsrGraph::from_adjacency(adjacency);
+    let mut scratch = BfsScratch::new(graph.len());
+
+    let mut max_dist = 0u32;
+    for _ in 0..samples.min(nodes.len()) {
+        let start = nodes[rng.gen_range(0..nodes.len())];
+        if let Some(source) = graph.index_of(start) {
+            graph.bfs(source, &mut scratch);
+            if let Some(&last) = scratch.order().last() {
+                max_dist = max_dist.max(scratch.distance(last).unwrap_or(0));
+            }
+        }
+    }
+    max_dist
+}
+
+/// Mean and half-width of its 95% confidence interval (Student's t)
+pub fn mean_ci(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    if values.len() < 2 {
+        return (values.first().copied().unwrap_or(0.0), 0.0);
+    }
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    let stderr = (variance / n).sqrt();
+    let t = match values.len() {
+        2 => 12.706,
+        3 => 4.303,
+        4 => 3.182,
+        5 => 2.776,
+        6 => 2.571,
+        7 => 2.447,
+        8 => 2.365,
+        9 => 2.306,
+        10 => 2.262,
+        _ => 1.96,
+    };
+    (mean, t * stderr)
+}
+
+/// Human-readable byte count
+pub fn format_bytes(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MB", bytes as f64 / 1024.0 / 1024.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tz_routing::TZConfig;
+
+    #[test]
+    fn test_generators_are_connected() {
+        let topologies = [
+            Topology::BarabasiAlbert { m: 3 },
+            Topology::ErdosRenyi { p: 0.05 },
+            Topology::WattsStrogatz { k: 4, beta: 0.3 },
+            Topology::Grid,
+            Topology::Tree { branching: 3 },
+        ];
+        for topology in topologies {
+            let network = topology.generate(100, 7);
+            let graph = CsrGraph::from_adjacency(&network.adjacency);
+            let mut scratch = BfsScratch::new(graph.len());
+            graph.bfs(0, &mut scratch);
+            assert_eq!(scratch.order().len(), network.len(), "{} is disconnected", topology.name());
+            assert_eq!(network.adjacency.values().map(Vec::len).sum::<usize>(), network.edge_count() * 2);
+        }
+        let grid = Topology::Grid.generate(100, 0);
+        assert_eq!((grid.len(), grid.edge_count(), grid.diameter(100)), (100, 180, 18));
+        assert_eq!(random_tree(50, 3, 1).edge_count(), 49);
+        // 23 nodes in rows of 5: 4 full rows and a row of 3
+        assert_eq!(grid_rows(23, 5).edge_count(), (4 * 4 + 2) + 18);
+        assert_eq!(line(10).diameter(10), 9);
+        let lollipop = lollipop(20, 0.25);
+        assert_eq!(lollipop.edge_count(), 10 + 15);
+        assert_eq!(lollipop.diameter(20), 16);
+    }
+
+    #[test]
+    fn test_runners_and_statistics() {
+        let network = barabasi_albert(200, 3, 42);
+        let router = build_pie_router(&network).unwrap();
+        let tz = TZRoutingTable::build(&network.adjacency, TZConfig::default()).unwrap();
+        let config = RoutingTestConfig { pairs: 100, seed: 1 };
+
+        let with_tz = run_routing_tests_with_tz(&router, &tz, &network.nodes, &config);
+        assert_eq!(with_tz.success_rate, 1.0);
+        assert!(with_tz.stretch >= 1.0);
+        assert!((with_tz.gravity_pct + with_tz.tz_pct - 100.0).abs() < 1e-9);
+        let gravity = run_gravity_only_tests(&router, &network.nodes, &config);
+        assert!(gravity.success_rate <= with_tz.success_rate);
+        // Deterministic for a seed
+        assert_eq!(run_gravity_only_tests(&router, &network.nodes, &config), gravity);
+
+        let (mean, ci) = mean_ci(&[1.0, 2.0, 3.0]);
+        assert_eq!(mean, 2.0);
+        assert!((ci - 4.303 / 3f64.sqrt()).abs() < 1e-9);
+        assert_eq!(mean_ci(&[5.0]), (5.0, 0.0));
+        assert_eq!(format_bytes(2048), "2.0 KB");
+    }
+}
@@ -9,14 +9,13 @@
 //! - Scalability (performance vs network size)
 
 use drfe_r::baselines::{ChordDHT, DHTRouter, KademliaDHT};
-use drfe_r::coordinates::{NodeId, RoutingCoordinate};
-use drfe_r::greedy_embedding::GreedyEmbedding;
-use drfe_r::routing::{GPRouter, RoutingNode};
-use drfe_r::PoincareDiskPoint;
+use drfe_r::bench::{barabasi_albert, build_pie_router, erdos_renyi, grid_rows, Network};
+use drfe_r::coordinates::NodeId;
+use drfe_r::routing::GPRouter;
+use drfe_r::simulation::{BatchConfig, Workload};
 use rand::seq::SliceRandom;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;
 use std::time::Instant;
@@ -39,152 +38,19 @@ struct ExperimentSummary {
     results: Vec<ComparisonResult>,
 }
 
-/// Generate a random graph topology
-fn generate_topology(n: usize, topology_type: &str) -> Vec<(usize, usize)> {
-    let mut rng = rand::thread_rng();
-    let mut edges = Vec::new();
-
+/// Generate a connected graph topology
+fn generate_topology(n: usize, topology_type: &str) -> Network {
+    let seed = n as u64;
     match topology_type {
-        "ba" => {
-            // Barabási-Albert (scale-free)
-            let m = 3; // edges per new node
-            
-            // Start with complete graph of m nodes
-            for i in 0..m {
-                for j in (i + 1)..m {
-                    edges.push((i, j));
-                }
-            }
-            
-            // Add remaining nodes with preferential attachment
-            for i in m..n {
-                let mut degrees: HashMap<usize, usize> = HashMap::new();
-                for &(a, b) in &edges {
-                    *degrees.entry(a).or_insert(0) += 1;
-                    *degrees.entry(b).or_insert(0) += 1;
-                }
-                
-                let total_degree: usize = degrees.values().sum();
-                let mut targets = Vec::new();
-                
-                while targets.len() < m && targets.len() < i {
-                    let r: f64 = rng.gen();
-                    let mut cumulative = 0.0;
-                    
-                    for node in 0..i {
-                        if targets.contains(&node) {
-                            continue;
-                        }
-                        let prob = *degrees.get(&node).unwrap_or(&1) as f64 / total_degree as f64;
-                        cumulative += prob;
-                        
-                        if r <= cumulative {
-                            targets.push(node);
-                            break;
-                        }
-                    }
-                }
-                
-                for &target in &targets {
-                    edges.push((i, target));
-                }
-            }
-        }
-        "random" => {
-            // Erdős-Rényi random graph
-            let p = 4.0 / n as f64; // Average degree ~4
-            for i in 0..n {
-                for j in (i + 1)..n {
-                    if rng.gen::<f64>() < p {
-                        edges.push((i, j));
-                    }
-                }
-            }
-        }
-        "grid" => {
-            // 2D grid
-            let side = (n as f64).sqrt().ceil() as usize;
-            for i in 0..n {
-                let row = i / side;
-                let col = i % side;
-                
-                // Right neighbor
-                if col + 1 < side && i + 1 < n {
-                    edges.push((i, i + 1));
-                }
-                
-                // Bottom neighbor
-                if row + 1 < side && i + side < n {
-                    edges.push((i, i + side));
-                }
-            }
-        }
+        "ba" => barabasi_albert(n, 3, seed),               // Scale-free, 3 edges per new node
+        "random" => erdos_renyi(n, 4.0 / n as f64, seed), // Average degree ~4
+        "grid" => grid_rows(n, (n as f64).sqrt().ceil() as usize),
         _ => panic!("Unknown topology type: {}", topology_type),
     }
-
-    edges
 }
 
-/// Build DRFE-R router from topology
-fn build_drfer_router(n: usize, edges: &[(usize, usize)]) -> GPRouter {
-    // Create adjacency list
-    let mut adjacency: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
-    
-    for i in 0..n {
-        adjacency.insert(NodeId::new(&format!("node{}", i)), Vec::new());
-    }
-    
-    for &(i, j) in edges {
-        let id_i = NodeId::new(&format!("node{}", i));
-        let id_j = NodeId::new(&format!("node{}", j));
-        
-        adjacency.get_mut(&id_i).unwrap().push(id_j.clone());
-        adjacency.get_mut(&id_j).unwrap().push(id_i.clone());
-    }
-    
-    // Create greedy embedding
-    let embedding = GreedyEmbedding::new();
-    let result = embedding.embed(&adjacency).expect("Failed to create embedding");
-    
-    // Build router
-    let mut router = GPRouter::new();
-    
-    for i in 0..n {
-        let node_id = NodeId::new(&format!("node{}", i));
-        let coord_point = result.coordinates.get(&node_id).cloned().unwrap_or_else(|| {
-            PoincareDiskPoint::origin()
-        });
-        let coord = RoutingCoordinate::new(coord_point, 0);
-        
-        let mut node = RoutingNode::new(node_id.clone(), coord);
-        
-        // Set tree structure from embedding result
-        let children = result.tree_children.get(&node_id).cloned().unwrap_or_default();
-        node.tree_children = children;
-        
-        // Find parent by checking which node has this node as a child
-        for (parent_id, parent_children) in &result.tree_children {
-            if parent_children.contains(&node_id) {
-                node.tree_parent = Some(parent_id.clone());
-                break;
-            }
-        }
-        
-        router.add_node(node);
-    }
-    
-    // Add edges
-    for &(i, j) in edges {
-        let id_i = NodeId::new(&format!("node{}", i));
-        let id_j = NodeId::new(&format!("node{}", j));
-        router.add_edge(&id_i, &id_j);
-    }
-    
-    router
-}
-
-/// Build Chord DHT from topology
-fn build_chord_dht(n: usize, _edges: &[(usize, usize)]) -> ChordDHT {
+/// Build Chord DHT over `n` nodes
+fn build_chord_dht(n: usize) -> ChordDHT {
     let m = (n as f64).log2().ceil() as usize + 1;
     let mut chord = ChordDHT::new(m);
     
@@ -196,8 +62,8 @@ fn build_chord_dht(n: usize, _edges: &[(usize, usize)]) -> ChordDHT {
     chord
 }
 
-/// Build Kademlia DHT from topology
-fn build_kademlia_dht(n: usize, _edges: &[(usize, usize)]) -> KademliaDHT {
+/// Build Kademlia DHT over `n` nodes
+fn build_kademlia_dht(n: usize) -> KademliaDHT {
     let k = 20.min(n / 2);
     let mut kad = KademliaDHT::new(160, k);
     
@@ -211,47 +77,20 @@ fn build_kademlia_dht(n: usize, _edges: &[(usize, usize)]) -> KademliaDHT {
 
 /// Run routing tests for DRFE-R
 fn test_drfer(router: &GPRouter, num_tests: usize) -> (f64, f64, f64) {
-    let mut rng = rand::thread_rng();
-    let node_ids = router.node_ids();
-    
-    let mut successes = 0;
-    let mut total_hops = 0;
-    let mut total_time_us = 0.0;
-    
-    for _ in 0..num_tests {
-        let source = node_ids.choose(&mut rng).unwrap();
-        let dest = node_ids.choose(&mut rng).unwrap();
-        
-        if source == dest {
-            continue;
-        }
-        
-        let dest_coord = router.get_node(dest).unwrap().coord.point;
-        
-        let start = Instant::now();
-        let result = router.simulate_delivery(source, dest, dest_coord, 1000);
-        let elapsed = start.elapsed();
-        
-        if result.success {
-            successes += 1;
-            total_hops += result.hops;
-            total_time_us += elapsed.as_micros() as f64;
-        }
-    }
-    
-    let success_rate = successes as f64 / num_tests as f64;
-    let avg_hops = if successes > 0 {
-        total_hops as f64 / successes as f64
-    } else {
-        0.0
-    };
-    let avg_latency = if successes > 0 {
-        total_time_us / successes as f64
+    let workload = Workload::Random { count: num_tests, seed: rand::thread_rng().gen() };
+    let config = BatchConfig { max_ttl: Some(1000), compute_stretch: false };
+
+    let start = Instant::now();
+    let summary = router.simulate_batch(&workload, &config).summary;
+    let total_time_us = start.elapsed().as_micros() as f64;
+
+    let avg_latency = if summary.delivered > 0 {
+        total_time_us / summary.delivered as f64
     } else {
         0.0
     };
-    
-    (success_rate, avg_hops, avg_latency)
+
+    (summary.success_rate, summary.avg_hops, avg_latency)
 }
 
 /// Run routing tests for DHT
@@ -313,22 +152,12 @@ fn main() {
             println!("Testing N={}, Topology={}", n, topology);
             
             // Generate topology
-            let edges = generate_topology(n, topology);
-            
-            // Ensure connectivity (add edges if needed)
-            let mut edge_set: std::collections::HashSet<_> = edges.iter().cloned().collect();
-            for i in 1..n {
-                if !edge_set.contains(&(i - 1, i)) && !edge_set.contains(&(i, i - 1)) {
-                    edge_set.insert((i - 1, i));
-                }
-            }
-            let edges: Vec<_> = edge_set.into_iter().collect();
-            
-            println!("  Generated {} edges", edges.len());
+            let network = generate_topology(n, topology);
+            println!("  Generated {} edges", network.edge_count());
             
             // Test DRFE-R
             println!("  Testing DRFE-R...");
-            let drfer = build_drfer_router(n, &edges);
+            let drfer = build_pie_router(&network).expect("Failed to create embedding");
             let (sr, ah, al) = test_drfer(&drfer, num_tests);
             
             all_results.push(ComparisonResult {
@@ -348,7 +177,7 @@ fn main() {
             
             // Test Chord
             println!("  Testing Chord...");
-            let chord = build_chord_dht(n, &edges);
+            let chord = build_chord_dht(n);
             let (sr, ah, al) = test_dht(&chord, num_tests);
             
             all_results.push(ComparisonResult {
@@ -368,7 +197,7 @@ fn main() {
             
             // Test Kademlia
             println!("  Testing Kademlia...");
-            let kad = build_kademlia_dht(n, &edges);
+            let kad = build_kademlia_dht(n);
             let (sr, ah, al) = test_dht(&kad, num_tests);
             
             all_results.push(ComparisonResult {
@@ -2,9 +2,12 @@
 //!
 //! Removes nodes or edges without recomputing coordinates or TZ tables.
 
+use drfe_r::bench::{barabasi_albert, Network};
 use drfe_r::coordinates::{NodeId, RoutingCoordinate};
 use drfe_r::greedy_embedding::GreedyEmbedding;
+use drfe_r::path_search::PathSearch;
 use drfe_r::routing::{GPRouter, RoutingNode};
+use drfe_r::simulation::{BatchConfig, Workload};
 use drfe_r::tz_routing::{TZConfig, TZRoutingTable};
 use drfe_r::PoincareDiskPoint;
 use rand::prelude::*;
@@ -81,65 +84,6 @@ fn edge_key(a: &NodeId, b: &NodeId) -> EdgeKey {
     }
 }
 
-fn generate_ba_network(
-    n: usize,
-    m: usize,
-    seed: u64,
-) -> (Vec<NodeId>, Vec<Vec<usize>>, HashMap<NodeId, Vec<NodeId>>) {
-    let mut rng = StdRng::seed_from_u64(seed);
-    let mut degrees: Vec<usize> = vec![0; n];
-    let mut adjacency_idx: Vec<Vec<usize>> = vec![Vec::new(); n];
-    let nodes: Vec<NodeId> = (0..n)
-        .map(|i| NodeId::new(format!("node_{}", i)))
-        .collect();
-
-    for i in 0..m.min(n) {
-        for j in (i + 1)..m.min(n) {
-            adjacency_idx[i].push(j);
-            adjacency_idx[j].push(i);
-            degrees[i] += 1;
-            degrees[j] += 1;
-        }
-    }
-
-    for i in m..n {
-        let total: usize = degrees.iter().take(i).sum();
-        if total == 0 {
-            continue;
-        }
-
-        let mut connected = HashSet::new();
-        while connected.len() < m.min(i) {
-            let r = rng.gen::<f64>() * total as f64;
-            let mut cumsum = 0.0;
-            for j in 0..i {
-                cumsum += degrees[j] as f64;
-                if cumsum >= r && !connected.contains(&j) {
-                    adjacency_idx[i].push(j);
-                    adjacency_idx[j].push(i);
-                    degrees[i] += 1;
-                    degrees[j] += 1;
-                    connected.insert(j);
-                    break;
-                }
-            }
-        }
-    }
-
-    let mut adjacency: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
-    for i in 0..n {
-        adjacency.insert(
-            nodes[i].clone(),
-            adjacency_idx[i]
-                .iter()
-                .map(|&j| nodes[j].clone())
-                .collect(),
-        );
-    }
-
-    (nodes, adjacency_idx, adjacency)
-}
-
 fn build_router_pie(
     nodes: &[NodeId],
     adjacency_idx: &[Vec<usize>],
@@ -183,28 +127,6 @@ fn build_router_pie(
     router
 }
 
-fn bfs_distance(router: &GPRouter, start: &NodeId, end: &NodeId) -> Option<u32> {
-    let mut visited = HashSet::new();
-    let mut queue = VecDeque::new();
-    queue.push_back((start.clone(), 0u32));
-    visited.insert(start.clone());
-
-    while let Some((cur, dist)) = queue.pop_front() {
-        if &cur == end {
-            return Some(dist);
-        }
-        if let Some(node) = router.get_node(&cur) {
-            for neighbor in &node.neighbors {
-                if !visited.contains(neighbor) {
-                    visited.insert(neighbor.clone());
-                    queue.push_back((neighbor.clone(), dist + 1));
-                }
-            }
-        }
-    }
-    None
-}
-
 fn gravity_path_limited(
     router: &GPRouter,
     src: &NodeId,
@@ -633,8 +555,8 @@ fn main() {
             println!("Seed {}", current_seed);
         }
 
-        let (nodes, adjacency_idx, adjacency) =
-            generate_ba_network(num_nodes, 3, *current_seed);
+        let Network { nodes, neighbors: adjacency_idx, adjacency } =
+            barabasi_albert(num_nodes, 3, *current_seed);
         let base_router = build_router_pie(&nodes, &adjacency_idx, &adjacency);
         let tz_table =
             TZRoutingTable::build(&adjacency, TZConfig::default()).expect("TZ build failed");
@@ -709,6 +631,7 @@ fn main() {
 
                 let router = build_pruned_router(&base_router, &alive_nodes, &removed_edges);
                 let edges_remaining = router.edge_count();
+                let mut search = PathSearch::from_router(&router);
 
                 let mut pairs = Vec::with_capacity(num_tests);
                 for _ in 0..num_tests {
@@ -719,6 +642,16 @@ fn main() {
                     }
                     pairs.push((alive_vec[src_idx].clone(), alive_vec[dst_idx].clone()));
                 }
+                let pie_config = BatchConfig {
+                    max_ttl: Some((alive_vec.len() * 20) as u32),
+                    compute_stretch: false,
+                };
+                let pie_paths: Vec<Option<(Vec<NodeId>, u64)>> = router
+                    .simulate_batch(&Workload::Pairs(pairs.clone()), &pie_config)
+                    .pairs
+                    .into_iter()
+                    .map(|pair| pair.delivery.success.then_some((pair.delivery.path, 0u64)))
+                    .collect();
 
                 for strategy in ["pie", "pie_tz", "pie_tz_rebuild"] {
                     let mut successes = 0u64;
@@ -752,19 +685,9 @@ fn main() {
                         &tz_table
                     };
 
-                    for (src, dst) in &pairs {
+                    for ((src, dst), pie_path) in pairs.iter().zip(&pie_paths) {
                         let result = if strategy == "pie" {
-                            let dest_coord = match router.get_node(dst) {
-                                Some(n) => n.coord.point,
-                                None => continue,
-                            };
-                            let delivery =
-                                router.simulate_delivery(src, dst, dest_coord, (alive_vec.len() * 20) as u32);
-                            if delivery.success {
-                                Some((delivery.path, 0u64))
-                            } else {
-                                None
-                            }
+                            pie_path.clone()
                         } else {
                             let max_gravity = alive_vec.len() as u32;
                             pie_tz_path_pruned(
@@ -789,7 +712,7 @@ fn main() {
                             tz_hops_total += tz_hops;
                             all_hops += hops;
 
-                            if let Some(opt) = bfs_distance(&router, src, dst) {
+                            if let Some(opt) = search.distance(src, dst) {
                                 total_optimal += opt as u64;
                                 if opt > 0 {
                                     let stretch = hops as f64 / opt as f64;
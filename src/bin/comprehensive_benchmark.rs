@@ -8,14 +8,15 @@
 //! 5. Ablation study
 //! 6. Stress tests
 
-use drfe_r::coordinates::{NodeId, RoutingCoordinate};
-use drfe_r::greedy_embedding::GreedyEmbedding;
-use drfe_r::routing::{GPRouter, RoutingNode, RoutingMode, PacketHeader, DeliveryResult};
-use drfe_r::tz_routing::{TZRoutingTable, TZConfig};
-use drfe_r::PoincareDiskPoint;
+use drfe_r::bench::{
+    barabasi_albert, build_pie_router, format_bytes, mean_ci, run_gravity_only_tests, run_routing_tests,
+    run_routing_tests_with_tz, RoutingTestConfig, Topology,
+};
+use drfe_r::robustness::{evaluate_failures, DegradationCurve, FailureMode, RobustnessConfig};
+use drfe_r::routing::PacketHeader;
+use drfe_r::tz_routing::{TZRoutingTable, TZConfig, TZMemoryBudget};
 use rand::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs::File;
 use std::io::Write;
 use std::time::Instant;
@@ -63,6 +64,18 @@ struct MemoryResult {
     avg_bunch_size: f64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MemoryBudgetResult {
+    nodes: usize,
+    budget_bytes_per_node: Option<usize>,
+    tz_table_entries: usize,
+    tz_bytes_estimated: usize,
+    tz_bytes_compact: usize,
+    nodes_over_budget: usize,
+    avg_stretch: f64,
+    max_stretch: f64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct LatencyResult {
     nodes: usize,
@@ -133,6 +146,10 @@ fn main() {
     let memory_results = run_memory_tests(seed);
     save_json(&memory_results, "paper_data/comprehensive/memory_results.json");
 
+    println!("\n--- Memory budget trade-off ---");
+    let budget_results = run_memory_budget_tests(seed);
+    save_json(&budget_results, "paper_data/comprehensive/memory_budget_results.json");
+
     // 4. Latency Tests
     println!("\n═══════════════════════════════════════════════════════════════");
     println!("                    4. LATENCY TESTS                           ");
@@ -147,6 +164,13 @@ fn main() {
     let ablation_results = run_ablation_study(num_tests, seed);
     save_json(&ablation_results, "paper_data/comprehensive/ablation_results.json");
 
+    // 6. Failure Robustness
+    println!("\n═══════════════════════════════════════════════════════════════");
+    println!("                    6. FAILURE ROBUSTNESS                      ");
+    println!("═══════════════════════════════════════════════════════════════\n");
+    let robustness_results = run_robustness_tests(num_tests, seed);
+    save_json(&robustness_results, "paper_data/comprehensive/robustness_results.json");
+
     // Generate Summary Report
     println!("\n═══════════════════════════════════════════════════════════════");
     println!("                    GENERATING SUMMARY                         ");
@@ -169,27 +193,30 @@ fn main() {
 fn run_topology_tests(n: usize, num_tests: usize, seed: u64) -> Vec<TopologyResult> {
     let mut results = Vec::new();
 
-    let topologies = vec![
-        ("Barabasi-Albert", generate_ba_network(n, 3, seed)),
-        ("Erdos-Renyi", generate_er_network(n, 0.006, seed)),
-        ("Watts-Strogatz", generate_ws_network(n, 6, 0.3, seed)),
-        ("Grid", generate_grid_network((n as f64).sqrt() as usize)),
-        ("Tree", generate_tree_network(n, 3, seed)),
+    let config = RoutingTestConfig { pairs: num_tests, seed };
+    let topologies = [
+        Topology::BarabasiAlbert { m: 3 },
+        Topology::ErdosRenyi { p: 0.006 },
+        Topology::WattsStrogatz { k: 6, beta: 0.3 },
+        Topology::Grid,
+        Topology::Tree { branching: 3 },
     ];
 
     println!("{:<20} {:<10} {:<10} {:<10} {:<10} {:<10}",
              "Topology", "Nodes", "Edges", "PIE-Str", "TZ-Str", "Improve");
     println!("{}", "-".repeat(70));
 
-    for (name, (nodes, adj_idx, adjacency)) in topologies {
-        let n_actual = nodes.len();
-        let edges: usize = adj_idx.iter().map(|v| v.len()).sum::<usize>() / 2;
-        let avg_degree = if n_actual > 0 { edges as f64 * 2.0 / n_actual as f64 } else { 0.0 };
-        let diameter = compute_diameter(&adjacency, 50);
+    for topology in topologies {
+        let name = topology.name();
+        let network = topology.generate(n, seed);
+        let n_actual = network.len();
+        let edges = network.edge_count();
+        let avg_degree = network.avg_degree();
+        let diameter = network.diameter(50);
 
         // PIE-DFS
-        let (router_pie, _) = build_router_pie(&nodes, &adj_idx, &adjacency);
-        let pie_result = run_routing_tests(&router_pie, None, &nodes, num_tests, seed);
+        let router_pie = build_pie_router(&network).expect("PIE embedding failed");
+        let pie_result = run_routing_tests(&router_pie, &network.nodes, &config);
 
         results.push(TopologyResult {
             topology: name.to_string(),
@@ -207,8 +234,8 @@ fn run_topology_tests(n: usize, num_tests: usize, seed: u64) -> Vec<TopologyResu
         });
 
         // PIE+TZ
-        let tz_table = TZRoutingTable::build(&adjacency, TZConfig::default()).unwrap();
-        let tz_result = run_routing_tests_with_tz(&router_pie, &tz_table, &nodes, num_tests, seed);
+        let tz_table = TZRoutingTable::build(&network.adjacency, TZConfig::default()).unwrap();
+        let tz_result = run_routing_tests_with_tz(&router_pie, &tz_table, &network.nodes, &config);
 
         results.push(TopologyResult {
             topology: name.to_string(),
@@ -247,23 +274,24 @@ fn run_scalability_tests(num_tests: usize, seed: u64) -> Vec<ScalabilityResult>
 
     for &n in &sizes {
         let start_total = Instant::now();
-        let (nodes, adj_idx, adjacency) = generate_ba_network(n, 3, seed);
-        let edges = adj_idx.iter().map(|v| v.len()).sum::<usize>() / 2;
+        let network = barabasi_albert(n, 3, seed);
+        let edges = network.edge_count();
 
         // Build PIE
         let pie_start = Instant::now();
-        let (router_pie, _) = build_router_pie(&nodes, &adj_idx, &adjacency);
+        let router_pie = build_pie_router(&network).expect("PIE embedding failed");
         let pie_time = pie_start.elapsed().as_millis();
 
         // Build TZ
         let tz_start = Instant::now();
-        let tz_table = TZRoutingTable::build(&adjacency, TZConfig::default()).unwrap();
+        let tz_table = TZRoutingTable::build(&network.adjacency, TZConfig::default()).unwrap();
         let tz_build_time = tz_start.elapsed().as_millis();
 
         let preprocess_total = start_total.elapsed().as_millis();
 
         // PIE-DFS test
-        let pie_result = run_routing_tests(&router_pie, None, &nodes, num_tests.min(n*n/4), seed);
+        let config = RoutingTestConfig { pairs: num_tests.min(n * n / 4), seed };
+        let pie_result = run_routing_tests(&router_pie, &network.nodes, &config);
         results.push(ScalabilityResult {
             nodes: n,
             edges,
@@ -277,7 +305,7 @@ fn run_scalability_tests(num_tests: usize, seed: u64) -> Vec<ScalabilityResult>
         });
 
         // PIE+TZ test
-        let tz_result = run_routing_tests_with_tz(&router_pie, &tz_table, &nodes, num_tests.min(n*n/4), seed);
+        let tz_result = run_routing_tests_with_tz(&router_pie, &tz_table, &network.nodes, &config);
         results.push(ScalabilityResult {
             nodes: n,
             edges,
@@ -311,8 +339,8 @@ fn run_memory_tests(seed: u64) -> Vec<MemoryResult> {
     println!("{}", "-".repeat(75));
 
     for &n in &sizes {
-        let (_, _, adjacency) = generate_ba_network(n, 3, seed);
-        let tz_table = TZRoutingTable::build(&adjacency, TZConfig::default()).unwrap();
+        let network = barabasi_albert(n, 3, seed);
+        let tz_table = TZRoutingTable::build(&network.adjacency, TZConfig::default()).unwrap();
 
         let entries = tz_table.memory_usage();
         let landmarks = tz_table.landmarks.len();
@@ -341,6 +369,48 @@ fn run_memory_tests(seed: u64) -> Vec<MemoryResult> {
     results
 }
 
+fn run_memory_budget_tests(seed: u64) -> Vec<MemoryBudgetResult> {
+    let mut results = Vec::new();
+    let n = 2000;
+    let budgets = [None, Some(16 * 1024), Some(4 * 1024), Some(1024)];
+
+    println!("{:<10} {:<12} {:<12} {:<12} {:<8} {:<10} {:<10}",
+             "Budget", "TZ Entries", "Est. Bytes", "Compact", "Over", "Stretch", "Max");
+    println!("{}", "-".repeat(80));
+
+    let adjacency = barabasi_albert(n, 3, seed).adjacency;
+    let base_table = TZRoutingTable::build(&adjacency, TZConfig::default()).unwrap();
+
+    for budget in budgets {
+        let mut tz_table = base_table.clone();
+        let nodes_over_budget = match budget {
+            Some(bytes) => tz_table
+                .enforce_memory_budget(&TZMemoryBudget { per_node_bytes: bytes, ..TZMemoryBudget::default() })
+                .nodes_over_budget,
+            None => 0,
+        };
+        let report = tz_table.memory_report(&adjacency, 2000);
+
+        println!("{:<10} {:<12} {:<12} {:<12} {:<8} {:<10.3} {:<10.2}",
+                 budget.map(format_bytes).unwrap_or_else(|| "none".to_string()),
+                 report.entries, format_bytes(report.estimated_bytes), format_bytes(report.compact_bytes),
+                 nodes_over_budget, report.avg_stretch, report.max_stretch);
+
+        results.push(MemoryBudgetResult {
+            nodes: n,
+            budget_bytes_per_node: budget,
+            tz_table_entries: report.entries,
+            tz_bytes_estimated: report.estimated_bytes,
+            tz_bytes_compact: report.compact_bytes,
+            nodes_over_budget,
+            avg_stretch: report.avg_stretch,
+            max_stretch: report.max_stretch,
+        });
+    }
+
+    results
+}
+
 // ============================================================================
 // 4. Latency Tests
 // ============================================================================
@@ -356,16 +426,17 @@ fn run_latency_tests(seed: u64) -> Vec<LatencyResult> {
     println!("{}", "-".repeat(65));
 
     for &n in &sizes {
-        let (nodes, adj_idx, adjacency) = generate_ba_network(n, 3, seed);
+        let network = barabasi_albert(n, 3, seed);
+        let nodes = &network.nodes;
 
         // PIE embedding time
         let pie_start = Instant::now();
-        let (router, _) = build_router_pie(&nodes, &adj_idx, &adjacency);
+        let router = build_pie_router(&network).expect("PIE embedding failed");
         let pie_ms = pie_start.elapsed().as_millis();
 
         // TZ build time
         let tz_start = Instant::now();
-        let tz_table = TZRoutingTable::build(&adjacency, TZConfig::default()).unwrap();
+        let tz_table = TZRoutingTable::build(&network.adjacency, TZConfig::default()).unwrap();
         let tz_ms = tz_start.elapsed().as_millis();
 
         // Routing decision latency
@@ -439,12 +510,14 @@ fn run_ablation_study(num_tests: usize, seed: u64) -> Vec<AblationResult> {
     println!("{}", "-".repeat(70));
 
     for &n in &sizes {
-        let (nodes, adj_idx, adjacency) = generate_ba_network(n, 3, seed);
-        let (router, _) = build_router_pie(&nodes, &adj_idx, &adjacency);
-        let tz_table = TZRoutingTable::build(&adjacency, TZConfig::default()).unwrap();
+        let network = barabasi_albert(n, 3, seed);
+        let nodes = &network.nodes;
+        let router = build_pie_router(&network).expect("PIE embedding failed");
+        let tz_table = TZRoutingTable::build(&network.adjacency, TZConfig::default()).unwrap();
+        let config = RoutingTestConfig { pairs: num_tests, seed };
 
         // PIE only (Gravity only, no fallback)
-        let gravity_only = run_gravity_only_tests(&router, &nodes, num_tests, seed);
+        let gravity_only = run_gravity_only_tests(&router, nodes, &config);
         results.push(AblationResult {
             nodes: n,
             configuration: "Gravity-only".to_string(),
@@ -457,7 +530,7 @@ fn run_ablation_study(num_tests: usize, seed: u64) -> Vec<AblationResult> {
         });
 
         // PIE + DFS (current baseline)
-        let pie_dfs = run_routing_tests(&router, None, &nodes, num_tests, seed);
+        let pie_dfs = run_routing_tests(&router, nodes, &config);
         results.push(AblationResult {
             nodes: n,
             configuration: "PIE+DFS".to_string(),
@@ -470,7 +543,7 @@ fn run_ablation_study(num_tests: usize, seed: u64) -> Vec<AblationResult> {
         });
 
         // PIE + TZ (proposed)
-        let pie_tz = run_routing_tests_with_tz(&router, &tz_table, &nodes, num_tests, seed);
+        let pie_tz = run_routing_tests_with_tz(&router, &tz_table, nodes, &config);
         results.push(AblationResult {
             nodes: n,
             configuration: "PIE+TZ".to_string(),
@@ -483,7 +556,7 @@ fn run_ablation_study(num_tests: usize, seed: u64) -> Vec<AblationResult> {
         });
 
         // TZ only
-        let (tz_avg, tz_max, _) = tz_table.verify_stretch(&adjacency, num_tests);
+        let (tz_avg, tz_max, _) = tz_table.verify_stretch(&network.adjacency, num_tests);
         results.push(AblationResult {
             nodes: n,
             configuration: "TZ-only".to_string(),
@@ -510,567 +583,51 @@ fn run_ablation_study(num_tests: usize, seed: u64) -> Vec<AblationResult> {
 }
 
 // ============================================================================
-// Network Generators
-// ============================================================================
-
-fn generate_ba_network(n: usize, m: usize, seed: u64) 
-    -> (Vec<NodeId>, Vec<Vec<usize>>, HashMap<NodeId, Vec<NodeId>>) 
-{
-    let mut rng = StdRng::seed_from_u64(seed);
-    let mut degrees: Vec<usize> = vec![0; n];
-    let mut adjacency_idx: Vec<Vec<usize>> = vec![Vec::new(); n];
-    let nodes: Vec<NodeId> = (0..n).map(|i| NodeId::new(format!("node_{}", i))).collect();
-
-    for i in 0..m.min(n) {
-        for j in (i+1)..m.min(n) {
-            adjacency_idx[i].push(j);
-            adjacency_idx[j].push(i);
-            degrees[i] += 1;
-            degrees[j] += 1;
-        }
-    }
-
-    for i in m..n {
-        let total: usize = degrees.iter().take(i).sum();
-        if total == 0 { continue; }
-
-        let mut connected = HashSet::new();
-        while connected.len() < m.min(i) {
-            let r = rng.gen::<f64>() * total as f64;
-            let mut cumsum = 0.0;
-            for j in 0..i {
-                cumsum += degrees[j] as f64;
-                if cumsum >= r && !connected.contains(&j) {
-                    adjacency_idx[i].push(j);
-                    adjacency_idx[j].push(i);
-                    degrees[i] += 1;
-                    degrees[j] += 1;
-                    connected.insert(j);
-                    break;
-                }
-            }
-        }
-    }
-
-    let mut adjacency: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
-    for i in 0..n {
-        adjacency.insert(nodes[i].clone(), 
-            adjacency_idx[i].iter().map(|&j| nodes[j].clone()).collect());
-    }
-
-    (nodes, adjacency_idx, adjacency)
-}
-
-fn generate_er_network(n: usize, p: f64, seed: u64) 
-    -> (Vec<NodeId>, Vec<Vec<usize>>, HashMap<NodeId, Vec<NodeId>>) 
-{
-    let mut rng = StdRng::seed_from_u64(seed);
-    let mut adjacency_idx: Vec<Vec<usize>> = vec![Vec::new(); n];
-    let nodes: Vec<NodeId> = (0..n).map(|i| NodeId::new(format!("er_{}", i))).collect();
-
-    for i in 0..n {
-        for j in (i+1)..n {
-            if rng.gen::<f64>() < p {
-                adjacency_idx[i].push(j);
-                adjacency_idx[j].push(i);
-            }
-        }
-    }
-
-    // Ensure connected
-    ensure_connected(&mut adjacency_idx, n, &mut rng);
-
-    let mut adjacency: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
-    for i in 0..n {
-        adjacency.insert(nodes[i].clone(),
-            adjacency_idx[i].iter().map(|&j| nodes[j].clone()).collect());
-    }
-
-    (nodes, adjacency_idx, adjacency)
-}
-
-fn generate_ws_network(n: usize, k: usize, beta: f64, seed: u64) 
-    -> (Vec<NodeId>, Vec<Vec<usize>>, HashMap<NodeId, Vec<NodeId>>) 
-{
-    let mut rng = StdRng::seed_from_u64(seed);
-    let mut adjacency_idx: Vec<Vec<usize>> = vec![Vec::new(); n];
-    let nodes: Vec<NodeId> = (0..n).map(|i| NodeId::new(format!("ws_{}", i))).collect();
-
-    // Ring lattice
-    for i in 0..n {
-        for j in 1..=k/2 {
-            let neighbor = (i + j) % n;
-            if !adjacency_idx[i].contains(&neighbor) {
-                adjacency_idx[i].push(neighbor);
-                adjacency_idx[neighbor].push(i);
-            }
-        }
-    }
-
-    // Rewire with probability beta
-    for i in 0..n {
-        let neighbors: Vec<usize> = adjacency_idx[i].clone();
-        for &j in &neighbors {
-            if j > i && rng.gen::<f64>() < beta {
-                // Remove edge (i,j)
-                adjacency_idx[i].retain(|&x| x != j);
-                adjacency_idx[j].retain(|&x| x != i);
-                
-                // Add random edge
-                let mut new_j = rng.gen_range(0..n);
-                while new_j == i || adjacency_idx[i].contains(&new_j) {
-                    new_j = rng.gen_range(0..n);
-                }
-                adjacency_idx[i].push(new_j);
-                adjacency_idx[new_j].push(i);
-            }
-        }
-    }
-
-    let mut adjacency: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
-    for i in 0..n {
-        adjacency.insert(nodes[i].clone(),
-            adjacency_idx[i].iter().map(|&j| nodes[j].clone()).collect());
-    }
-
-    (nodes, adjacency_idx, adjacency)
-}
-
-fn generate_grid_network(side: usize) 
-    -> (Vec<NodeId>, Vec<Vec<usize>>, HashMap<NodeId, Vec<NodeId>>) 
-{
-    let n = side * side;
-    let mut adjacency_idx: Vec<Vec<usize>> = vec![Vec::new(); n];
-    let nodes: Vec<NodeId> = (0..n).map(|i| NodeId::new(format!("grid_{}", i))).collect();
-
-    for i in 0..side {
-        for j in 0..side {
-            let idx = i * side + j;
-            if j + 1 < side {
-                adjacency_idx[idx].push(idx + 1);
-                adjacency_idx[idx + 1].push(idx);
-            }
-            if i + 1 < side {
-                adjacency_idx[idx].push(idx + side);
-                adjacency_idx[idx + side].push(idx);
-            }
-        }
-    }
-
-    let mut adjacency: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
-    for i in 0..n {
-        adjacency.insert(nodes[i].clone(),
-            adjacency_idx[i].iter().map(|&j| nodes[j].clone()).collect());
-    }
-
-    (nodes, adjacency_idx, adjacency)
-}
-
-fn generate_tree_network(n: usize, branching: usize, seed: u64) 
-    -> (Vec<NodeId>, Vec<Vec<usize>>, HashMap<NodeId, Vec<NodeId>>) 
-{
-    let mut rng = StdRng::seed_from_u64(seed);
-    let mut adjacency_idx: Vec<Vec<usize>> = vec![Vec::new(); n];
-    let nodes: Vec<NodeId> = (0..n).map(|i| NodeId::new(format!("tree_{}", i))).collect();
-
-    for i in 1..n {
-        let parent = if i <= branching { 0 } else { rng.gen_range(0..i) };
-        adjacency_idx[i].push(parent);
-        adjacency_idx[parent].push(i);
-    }
-
-    let mut adjacency: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
-    for i in 0..n {
-        adjacency.insert(nodes[i].clone(),
-            adjacency_idx[i].iter().map(|&j| nodes[j].clone()).collect());
-    }
-
-    (nodes, adjacency_idx, adjacency)
-}
-
-fn ensure_connected(adj: &mut Vec<Vec<usize>>, n: usize, rng: &mut StdRng) {
-    let mut visited = vec![false; n];
-    let mut queue = VecDeque::new();
-    queue.push_back(0);
-    visited[0] = true;
-    let mut count = 1;
-
-    while let Some(u) = queue.pop_front() {
-        for &v in &adj[u] {
-            if !visited[v] {
-                visited[v] = true;
-                count += 1;
-                queue.push_back(v);
-            }
-        }
-    }
-
-    // Connect isolated components
-    for i in 0..n {
-        if !visited[i] {
-            let connected = rng.gen_range(0..i);
-            adj[i].push(connected);
-            adj[connected].push(i);
-        }
-    }
-}
-
-// ============================================================================
-// Router Building
-// ============================================================================
-
-fn build_router_pie(
-    nodes: &[NodeId], 
-    adjacency_idx: &[Vec<usize>], 
-    adjacency: &HashMap<NodeId, Vec<NodeId>>
-) -> (GPRouter, u128) {
-    let start = Instant::now();
-    let embedder = GreedyEmbedding::new();
-    let result = embedder.embed(adjacency).expect("PIE embedding failed");
-
-    let mut tree_parent: HashMap<NodeId, Option<NodeId>> = HashMap::new();
-    tree_parent.insert(result.root.clone(), None);
-    for (parent, children) in &result.tree_children {
-        for child in children {
-            tree_parent.insert(child.clone(), Some(parent.clone()));
-        }
-    }
-
-    let mut router = GPRouter::new();
-    for node_id in nodes.iter() {
-        let point = result.coordinates.get(node_id).copied().unwrap_or_else(PoincareDiskPoint::origin);
-        let coord = RoutingCoordinate::new(point, 0);
-        let mut rn = RoutingNode::new(node_id.clone(), coord);
-        rn.set_tree_info(
-            tree_parent.get(node_id).cloned().flatten(),
-            result.tree_children.get(node_id).cloned().unwrap_or_default()
-        );
-        router.add_node(rn);
-    }
-
-    for i in 0..nodes.len() {
-        for &j in &adjacency_idx[i] {
-            if i < j {
-                router.add_edge(&nodes[i], &nodes[j]);
-            }
-        }
-    }
-
-    (router, start.elapsed().as_millis())
-}
-
-// ============================================================================
-// Test Runners
+// 6. Failure Robustness
 // ============================================================================
 
-struct TestResult {
-    success_rate: f64,
-    avg_hops: f64,
-    stretch: f64,
-    max_stretch: f64,
-    gravity_pct: f64,
-    pressure_pct: f64,
-    tree_pct: f64,
-    tz_pct: f64,
-    routing_time_us: f64,
-}
-
-fn run_routing_tests(
-    router: &GPRouter, 
-    _tz: Option<&TZRoutingTable>,
-    nodes: &[NodeId], 
-    num_tests: usize, 
-    seed: u64
-) -> TestResult {
-    let mut rng = StdRng::seed_from_u64(seed + 1000);
-    let n = nodes.len();
-    let max_ttl = (n * 20) as u32;
-
-    let mut successes = 0u32;
-    let mut total_hops = 0u32;
-    let mut total_optimal = 0u32;
-    let mut gravity = 0u32;
-    let mut pressure = 0u32;
-    let mut tree = 0u32;
-    let mut all_hops = 0u32;
-    let mut max_stretch = 0.0f64;
-
-    let start = Instant::now();
-
-    for _ in 0..num_tests {
-        let src = rng.gen_range(0..n);
-        let mut dst = rng.gen_range(0..n);
-        while dst == src { dst = rng.gen_range(0..n); }
-
-        if let Some(dest_node) = router.get_node(&nodes[dst]) {
-            let result = router.simulate_delivery(&nodes[src], &nodes[dst], dest_node.coord.point, max_ttl);
-            if result.success {
-                successes += 1;
-                total_hops += result.hops;
-                gravity += result.gravity_hops;
-                pressure += result.pressure_hops;
-                tree += result.tree_hops;
-                all_hops += result.hops;
-
-                if let Some(opt) = bfs_distance(router, &nodes[src], &nodes[dst]) {
-                    total_optimal += opt;
-                    if opt > 0 {
-                        let s = result.hops as f64 / opt as f64;
-                        max_stretch = f64::max(max_stretch, s);
-                    }
-                }
-            }
-        }
-    }
-
-    let elapsed_us = start.elapsed().as_micros() as f64;
-
-    TestResult {
-        success_rate: successes as f64 / num_tests as f64,
-        avg_hops: if successes > 0 { total_hops as f64 / successes as f64 } else { 0.0 },
-        stretch: if total_optimal > 0 { total_hops as f64 / total_optimal as f64 } else { 0.0 },
-        max_stretch,
-        gravity_pct: if all_hops > 0 { gravity as f64 / all_hops as f64 * 100.0 } else { 0.0 },
-        pressure_pct: if all_hops > 0 { pressure as f64 / all_hops as f64 * 100.0 } else { 0.0 },
-        tree_pct: if all_hops > 0 { tree as f64 / all_hops as f64 * 100.0 } else { 0.0 },
-        tz_pct: 0.0,
-        routing_time_us: elapsed_us / num_tests as f64,
-    }
-}
-
-fn run_routing_tests_with_tz(
-    router: &GPRouter, 
-    tz_table: &TZRoutingTable,
-    nodes: &[NodeId], 
-    num_tests: usize, 
-    seed: u64
-) -> TestResult {
-    let mut rng = StdRng::seed_from_u64(seed + 1000);
-    let n = nodes.len();
-    let max_gravity = n as u32;
-
-    let mut successes = 0u32;
-    let mut total_hops = 0u32;
-    let mut total_optimal = 0u32;
-    let mut gravity = 0u32;
-    let mut tz = 0u32;
-    let mut all_hops = 0u32;
-    let mut max_stretch = 0.0f64;
-
-    let start = Instant::now();
-
-    for _ in 0..num_tests {
-        let src = rng.gen_range(0..n);
-        let mut dst = rng.gen_range(0..n);
-        while dst == src { dst = rng.gen_range(0..n); }
-
-        let (success, g_hops, final_node) = try_gravity_only_limited(router, &nodes[src], &nodes[dst], max_gravity);
-        
-        let (ok, hops, g, t) = if success {
-            (true, g_hops, g_hops, 0)
-        } else {
-            if let Some(path) = tz_table.compute_path(&final_node, &nodes[dst]) {
-                let tz_hops = (path.len() - 1) as u32;
-                (true, g_hops + tz_hops, g_hops, tz_hops)
-            } else {
-                (false, 0, 0, 0)
-            }
-        };
-
-        if ok {
-            successes += 1;
-            total_hops += hops;
-            gravity += g;
-            tz += t;
-            all_hops += hops;
-
-            if let Some(opt) = bfs_distance(router, &nodes[src], &nodes[dst]) {
-                total_optimal += opt;
-                if opt > 0 {
-                    let s = hops as f64 / opt as f64;
-                    max_stretch = f64::max(max_stretch, s);
-                }
-            }
-        }
-    }
-
-    let elapsed_us = start.elapsed().as_micros() as f64;
-
-    TestResult {
-        success_rate: successes as f64 / num_tests as f64,
-        avg_hops: if successes > 0 { total_hops as f64 / successes as f64 } else { 0.0 },
-        stretch: if total_optimal > 0 { total_hops as f64 / total_optimal as f64 } else { 0.0 },
-        max_stretch,
-        gravity_pct: if all_hops > 0 { gravity as f64 / all_hops as f64 * 100.0 } else { 0.0 },
-        pressure_pct: 0.0,
-        tree_pct: 0.0,
-        tz_pct: if all_hops > 0 { tz as f64 / all_hops as f64 * 100.0 } else { 0.0 },
-        routing_time_us: elapsed_us / num_tests as f64,
-    }
-}
-
-fn run_gravity_only_tests(
-    router: &GPRouter, 
-    nodes: &[NodeId], 
-    num_tests: usize, 
-    seed: u64
-) -> TestResult {
-    let mut rng = StdRng::seed_from_u64(seed + 2000);
-    let n = nodes.len();
-    let max_hops = n as u32;
-
-    let mut successes = 0u32;
-    let mut total_hops = 0u32;
-    let mut total_optimal = 0u32;
-    let mut max_stretch = 0.0f64;
-
-    for _ in 0..num_tests {
-        let src = rng.gen_range(0..n);
-        let mut dst = rng.gen_range(0..n);
-        while dst == src { dst = rng.gen_range(0..n); }
-
-        let (success, hops, _) = try_gravity_only_limited(router, &nodes[src], &nodes[dst], max_hops);
-        
-        if success {
-            successes += 1;
-            total_hops += hops;
-
-            if let Some(opt) = bfs_distance(router, &nodes[src], &nodes[dst]) {
-                total_optimal += opt;
-                if opt > 0 {
-                    let s = hops as f64 / opt as f64;
-                    max_stretch = f64::max(max_stretch, s);
-                }
-            }
-        }
-    }
-
-    TestResult {
-        success_rate: successes as f64 / num_tests as f64,
-        avg_hops: if successes > 0 { total_hops as f64 / successes as f64 } else { 0.0 },
-        stretch: if total_optimal > 0 { total_hops as f64 / total_optimal as f64 } else { 0.0 },
-        max_stretch,
-        gravity_pct: 100.0,
-        pressure_pct: 0.0,
-        tree_pct: 0.0,
-        tz_pct: 0.0,
-        routing_time_us: 0.0,
-    }
-}
-
-fn try_gravity_only_limited(router: &GPRouter, src: &NodeId, dst: &NodeId, max: u32) -> (bool, u32, NodeId) {
-    if src == dst { return (true, 0, src.clone()); }
-
-    let dest_coord = match router.get_node(dst) {
-        Some(n) => n.coord.point,
-        None => return (false, 0, src.clone()),
-    };
-
-    let mut current = src.clone();
-    let mut hops = 0;
-    let mut visited = HashSet::new();
-    visited.insert(src.clone());
-
-    for _ in 0..max {
-        if &current == dst { return (true, hops, current); }
-
-        let node = match router.get_node(&current) {
-            Some(n) => n,
-            None => break,
-        };
+fn run_robustness_tests(num_tests: usize, seed: u64) -> Vec<DegradationCurve> {
+    let n = 1000;
+    let network = barabasi_albert(n, 3, seed);
+    let mut router = build_pie_router(&network).expect("PIE embedding failed");
+    router.set_tz_table(TZRoutingTable::build(&network.adjacency, TZConfig::default()).unwrap());
+
+    let modes = [
+        FailureMode::RandomNodes,
+        FailureMode::HighDegreeNodes,
+        FailureMode::HighBetweennessNodes,
+        FailureMode::RandomEdges,
+        FailureMode::HighBetweennessEdges,
+    ];
 
-        let cur_dist = node.coord.point.hyperbolic_distance(&dest_coord);
-        let mut best: Option<NodeId> = None;
-        let mut best_dist = cur_dist;
-
-        for neighbor in &node.neighbors {
-            if visited.contains(neighbor) { continue; }
-            if let Some(n) = router.get_node(neighbor) {
-                let d = n.coord.point.hyperbolic_distance(&dest_coord);
-                if d < best_dist {
-                    best_dist = d;
-                    best = Some(neighbor.clone());
-                }
-            }
-        }
+    println!("{:<22} {:<8} {:<10} {:<12} {:<10} {:<10}",
+             "Failure Mode", "Removed", "Success%", "Connected%", "Stretch", "MaxStr");
+    println!("{}", "-".repeat(75));
 
-        match best {
-            Some(next) => {
-                visited.insert(next.clone());
-                current = next;
-                hops += 1;
-            }
-            None => break,
+    let mut curves = Vec::new();
+    for mode in modes {
+        let curve = evaluate_failures(&router, &RobustnessConfig {
+            mode,
+            fractions: vec![0.0, 0.01, 0.05, 0.1, 0.2],
+            pairs_per_step: num_tests,
+            seed,
+            ..RobustnessConfig::default()
+        });
+        for p in &curve.points {
+            println!("{:<22} {:<8} {:<10.1} {:<12.1} {:<10.3} {:<10.2}",
+                     format!("{:?}", mode), format!("{:.0}%", p.fraction * 100.0), p.success_rate * 100.0,
+                     p.connected_success_rate * 100.0, p.stretch, p.max_stretch);
         }
+        curves.push(curve);
     }
 
-    (&current == dst, hops, current)
+    curves
 }
 
 // ============================================================================
 // Utilities
 // ============================================================================
 
-fn bfs_distance(router: &GPRouter, start: &NodeId, end: &NodeId) -> Option<u32> {
-    let mut visited = HashSet::new();
-    let mut queue = VecDeque::new();
-    queue.push_back((start.clone(), 0u32));
-    visited.insert(start.clone());
-
-    while let Some((cur, dist)) = queue.pop_front() {
-        if &cur == end { return Some(dist); }
-        if let Some(node) = router.get_node(&cur) {
-            for neighbor in &node.neighbors {
-                if !visited.contains(neighbor) {
-                    visited.insert(neighbor.clone());
-                    queue.push_back((neighbor.clone(), dist + 1));
-                }
-            }
-        }
-    }
-    None
-}
-
-fn compute_diameter(adj: &HashMap<NodeId, Vec<NodeId>>, samples: usize) -> u32 {
-    let nodes: Vec<&NodeId> = adj.keys().collect();
-    if nodes.is_empty() { return 0; }
-
-    let mut max_dist = 0u32;
-    let mut rng = StdRng::seed_from_u64(12345);
-
-    for _ in 0..samples.min(nodes.len()) {
-        let start = nodes[rng.gen_range(0..nodes.len())];
-        let mut visited = HashSet::new();
-        let mut queue = VecDeque::new();
-        queue.push_back((start.clone(), 0u32));
-        visited.insert(start.clone());
-
-        while let Some((cur, dist)) = queue.pop_front() {
-            max_dist = max_dist.max(dist);
-            if let Some(neighbors) = adj.get(&cur) {
-                for n in neighbors {
-                    if !visited.contains(n) {
-                        visited.insert(n.clone());
-                        queue.push_back((n.clone(), dist + 1));
-                    }
-                }
-            }
-        }
-    }
-
-    max_dist
-}
-
-fn format_bytes(bytes: usize) -> String {
-    if bytes < 1024 {
-        format!("{} B", bytes)
-    } else if bytes < 1024 * 1024 {
-        format!("{:.1} KB", bytes as f64 / 1024.0)
-    } else {
-        format!("{:.1} MB", bytes as f64 / 1024.0 / 1024.0)
-    }
-}
-
 /// Generate confidence interval report for multi-seed scalability results
 fn generate_scalability_ci_report(all_results: &[Vec<ScalabilityResult>]) {
     let path = "paper_data/comprehensive/scalability_ci_report.md";
@@ -1117,31 +674,6 @@ fn generate_scalability_ci_report(all_results: &[Vec<ScalabilityResult>]) {
     println!("  ✓ CI report saved to {}", path);
 }
 
-/// Compute mean and 95% confidence interval
-fn mean_ci(values: &[f64]) -> (f64, f64) {
-    let n = values.len() as f64;
-    if n < 2.0 {
-        return (values.first().copied().unwrap_or(0.0), 0.0);
-    }
-    let mean = values.iter().sum::<f64>() / n;
-    let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
-    let stderr = (variance / n).sqrt();
-    // t-value for 95% CI (approximate for small n)
-    let t = match values.len() {
-        2 => 12.706,
-        3 => 4.303,
-        4 => 3.182,
-        5 => 2.776,
-        6 => 2.571,
-        7 => 2.447,
-        8 => 2.365,
-        9 => 2.306,
-        10 => 2.262,
-        _ => 1.96,
-    };
-    (mean, t * stderr)
-}
-
 fn save_json<T: Serialize>(data: &T, path: &str) {
     if let Ok(mut file) = File::create(path) {
         let json = serde_json::to_string_pretty(data).unwrap();
@@ -2,12 +2,13 @@
 //! 
 //! Identifies specific node pairs that fail and why
 
+use drfe_r::bench::{barabasi_albert, Network};
 use drfe_r::coordinates::{NodeId, RoutingCoordinate};
 use drfe_r::greedy_embedding::GreedyEmbedding;
 use drfe_r::routing::{GPRouter, RoutingNode};
+use drfe_r::simulation::{BatchConfig, Workload};
 use drfe_r::PoincareDiskPoint;
-use rand::prelude::*;
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{HashMap, VecDeque};
 
 fn main() {
     println!("DRFE-R Failure Analysis");
@@ -18,58 +19,7 @@ fn main() {
     let m = 3; // BA parameter
 
     // Generate BA network
-    let mut rng = StdRng::seed_from_u64(seed);
-    let mut degrees: Vec<usize> = Vec::new();
-    let mut nodes: Vec<NodeId> = Vec::new();
-    let mut adjacency_idx: Vec<Vec<usize>> = Vec::new();
-
-    for i in 0..num_nodes {
-        let id = NodeId::new(format!("node_{}", i));
-        nodes.push(id);
-        degrees.push(0);
-        adjacency_idx.push(Vec::new());
-    }
-
-    // Build initial complete graph of m nodes
-    for i in 0..m.min(num_nodes) {
-        for j in (i + 1)..m.min(num_nodes) {
-            adjacency_idx[i].push(j);
-            adjacency_idx[j].push(i);
-            degrees[i] += 1;
-            degrees[j] += 1;
-        }
-    }
-
-    // Preferential attachment
-    for i in m..num_nodes {
-        let total_degree: usize = degrees.iter().take(i).sum();
-        if total_degree == 0 {
-            adjacency_idx[i].push(0);
-            adjacency_idx[0].push(i);
-            degrees[i] += 1;
-            degrees[0] += 1;
-            continue;
-        }
-
-        let mut connected = HashSet::new();
-        let mut attempts = 0;
-        while connected.len() < m.min(i) && attempts < 1000 {
-            attempts += 1;
-            let r_val = rng.gen::<f64>() * total_degree as f64;
-            let mut cumsum = 0.0;
-            for j in 0..i {
-                cumsum += degrees[j] as f64;
-                if cumsum >= r_val && !connected.contains(&j) {
-                    adjacency_idx[i].push(j);
-                    adjacency_idx[j].push(i);
-                    degrees[i] += 1;
-                    degrees[j] += 1;
-                    connected.insert(j);
-                    break;
-                }
-            }
-        }
-    }
+    let Network { nodes, neighbors: adjacency_idx, adjacency } = barabasi_albert(num_nodes, m, seed);
 
     // Check connectivity using BFS
     let mut visited = vec![false; num_nodes];
@@ -104,15 +54,6 @@ fn main() {
     }
 
     // Now create router with embedding
-    let mut adjacency: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
-    for i in 0..num_nodes {
-        let neighbor_ids: Vec<NodeId> = adjacency_idx[i]
-            .iter()
-            .map(|&j| nodes[j].clone())
-            .collect();
-        adjacency.insert(nodes[i].clone(), neighbor_ids);
-    }
-
     let embedder = GreedyEmbedding::new();
     let embedding_result = embedder.embed(&adjacency).expect("Embedding should succeed");
 
@@ -169,66 +110,43 @@ fn main() {
         (250, 400),
     ];
 
-    for (src_idx, dst_idx) in test_pairs {
-        let source = &nodes[src_idx];
-        let dest = &nodes[dst_idx];
-
-        if let Some(dest_node) = router.get_node(dest) {
-            let result = router.simulate_delivery(
-                source,
-                dest,
-                dest_node.coord.point,
-                10000, // Very high TTL
-            );
-
-            println!("  {} -> {}: {} (hops: {}, reason: {:?})",
-                source.0, dest.0,
-                if result.success { "SUCCESS" } else { "FAILED" },
-                result.hops,
-                result.failure_reason
-            );
-        }
+    let config = BatchConfig {
+        max_ttl: Some(10000), // Very high TTL
+        compute_stretch: false,
+    };
+    let workload = Workload::Pairs(
+        test_pairs
+            .iter()
+            .map(|&(src_idx, dst_idx)| (nodes[src_idx].clone(), nodes[dst_idx].clone()))
+            .collect(),
+    );
+    for pair in router.simulate_batch(&workload, &config).pairs {
+        let result = &pair.delivery;
+        println!("  {} -> {}: {} (hops: {}, reason: {:?})",
+            pair.source.0, pair.destination.0,
+            if result.success { "SUCCESS" } else { "FAILED" },
+            result.hops,
+            result.failure_reason
+        );
     }
 
     // Run bulk test and collect failure statistics
     println!("\nBulk routing test (200 pairs):");
-    let mut successes = 0;
-    let mut failures = 0;
     let mut failure_reasons: HashMap<String, usize> = HashMap::new();
     let mut failed_pairs: Vec<(usize, usize)> = Vec::new();
 
-    let mut test_rng = StdRng::seed_from_u64(seed + 1000);
-    for _ in 0..200 {
-        let src_idx = test_rng.gen_range(0..num_nodes);
-        let mut dst_idx = test_rng.gen_range(0..num_nodes);
-        while dst_idx == src_idx {
-            dst_idx = test_rng.gen_range(0..num_nodes);
+    let index = |id: &NodeId| nodes.iter().position(|n| n == id).expect("node from the network");
+    let bulk = router.simulate_batch(&Workload::Random { count: 200, seed: seed + 1000 }, &config);
+    for pair in bulk.pairs.iter().filter(|p| !p.delivery.success) {
+        if let Some(reason) = &pair.delivery.failure_reason {
+            *failure_reasons.entry(reason.clone()).or_insert(0) += 1;
         }
-
-        let source = &nodes[src_idx];
-        let dest = &nodes[dst_idx];
-
-        if let Some(dest_node) = router.get_node(dest) {
-            let result = router.simulate_delivery(
-                source,
-                dest,
-                dest_node.coord.point,
-                10000,
-            );
-
-            if result.success {
-                successes += 1;
-            } else {
-                failures += 1;
-                if let Some(reason) = &result.failure_reason {
-                    *failure_reasons.entry(reason.clone()).or_insert(0) += 1;
-                }
-                if failed_pairs.len() < 5 {
-                    failed_pairs.push((src_idx, dst_idx));
-                }
-            }
+        if failed_pairs.len() < 5 {
+            failed_pairs.push((index(&pair.source), index(&pair.destination)));
         }
     }
+    let successes = bulk.summary.delivered;
+    let failures = bulk.summary.packets - bulk.summary.delivered;
 
     println!("  Successes: {}", successes);
     println!("  Failures: {}", failures);
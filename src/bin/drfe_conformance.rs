@@ -0,0 +1,119 @@
+//! Conformance suite for DRFE-R implementations
+//!
+//! Writes the canonical test vectors, checks a vector file against this
+//! implementation, or probes a remote node over UDP and scores how it
+//! answers. Exits non-zero on any mismatch or failed check.
+//!
+//! Usage:
+//!   drfe-conformance vectors [--out <file>]
+//!   drfe-conformance verify <file>
+//!   drfe-conformance probe <addr> [--id <probe id>] [--timeout-ms <ms>] [--heartbeat-ms <ms>] [--json]
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use drfe_r::conformance::{probe, CheckOutcome, ProbeConfig, TestVectors};
+use drfe_r::coordinates::NodeId;
+
+fn exit_with(message: String) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(2);
+}
+
+fn usage() -> ! {
+    exit_with(
+        "Usage: drfe-conformance vectors [--out <file>]\n       drfe-conformance verify <file>\n       drfe-conformance probe <addr> [--id <probe id>] [--timeout-ms <ms>] [--heartbeat-ms <ms>] [--json]"
+            .to_string(),
+    )
+}
+
+fn millis(value: Option<&String>) -> Duration {
+    match value.and_then(|v| v.parse().ok()) {
+        Some(ms) => Duration::from_millis(ms),
+        None => usage(),
+    }
+}
+
+fn write_vectors(args: &[String]) {
+    let json = TestVectors::generate()
+        .to_json()
+        .unwrap_or_else(|e| exit_with(format!("Failed to encode vectors: {}", e)));
+    match args {
+        [] => println!("{}", json),
+        [flag, path] if flag == "--out" => {
+            std::fs::write(path, json + "\n").unwrap_or_else(|e| exit_with(format!("Failed to write {}: {}", path, e)));
+        }
+        _ => usage(),
+    }
+}
+
+fn verify_vectors(path: &str) {
+    let json = std::fs::read_to_string(path).unwrap_or_else(|e| exit_with(format!("Failed to read {}: {}", path, e)));
+    let vectors = TestVectors::from_json(&json).unwrap_or_else(|e| exit_with(format!("Invalid vectors: {}", e)));
+    let mismatches = vectors.verify();
+    for mismatch in &mismatches {
+        println!("MISMATCH {}", mismatch);
+    }
+    println!("{} mismatches", mismatches.len());
+    if !mismatches.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+async fn probe_remote(args: &[String]) {
+    let Some(target) = args.first().and_then(|a| a.parse::<SocketAddr>().ok()) else {
+        usage();
+    };
+    let mut config = ProbeConfig::default();
+    let mut json = false;
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--id" if i + 1 < args.len() => {
+                config.probe_id = NodeId::new(args[i + 1].as_str());
+                i += 1;
+            }
+            "--timeout-ms" => {
+                config.timeout = millis(args.get(i + 1));
+                i += 1;
+            }
+            "--heartbeat-ms" => {
+                config.heartbeat_wait = millis(args.get(i + 1));
+                i += 1;
+            }
+            "--json" => json = true,
+            _ => usage(),
+        }
+        i += 1;
+    }
+
+    let report = probe(target, &config).await.unwrap_or_else(|e| exit_with(format!("Probe failed: {}", e)));
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+    } else {
+        let remote = report.remote_id.as_ref().map_or("unknown".to_string(), |id| id.0.clone());
+        println!("Target {} ({})", report.target, remote);
+        for check in &report.checks {
+            match &check.outcome {
+                CheckOutcome::Pass => println!("  PASS {}", check.name),
+                CheckOutcome::Fail(reason) => println!("  FAIL {}: {}", check.name, reason),
+                CheckOutcome::Skipped(reason) => println!("  SKIP {}: {}", check.name, reason),
+            }
+        }
+        println!("Score: {}/{} ({:.0}%)", report.passed(), report.checks.len(), report.score() * 100.0);
+    }
+    if report.passed() < report.checks.len() {
+        std::process::exit(1);
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("vectors") => write_vectors(&args[1..]),
+        Some("verify") if args.len() == 2 => verify_vectors(&args[1]),
+        Some("probe") => probe_remote(&args[1..]).await,
+        _ => usage(),
+    }
+}
@@ -0,0 +1,123 @@
+//! Mesh VPN over the DRFE-R overlay
+//!
+//! Starts a node, creates a TUN device and tunnels IP packets between the
+//! device and the overlay. Routes come from the tunnel config, a JSON
+//! `TunConfig`:
+//!
+//! ```json
+//! { "device_name": "drfe0", "mtu": 1400,
+//!   "routes": { "10.1.0.0/16": "site-a", "10.2.0.0/16": "site-b" } }
+//! ```
+//!
+//! Assign the device an address afterwards, e.g. `ip addr add 10.1.0.1/8 dev drfe0`.
+//!
+//! Node tunables can come from a JSON `ConfigUpdate` given with
+//! `--node-config`; the node reloads that file on SIGHUP.
+//!
+//! Usage: drfe-tun --id <node> --udp <addr> --tcp <addr> [--config <file>] [--node-config <file>] [--bootstrap <addr>]...
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use drfe_r::coordinates::NodeId;
+use drfe_r::network::DistributedNode;
+use drfe_r::tun::{run, TunConfig, TunDevice, TunTunnel};
+use tokio::sync::Mutex;
+
+fn exit_with(message: String) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut id = String::from("tun-node");
+    let mut udp_addr = String::from("0.0.0.0:7777");
+    let mut tcp_addr = String::from("0.0.0.0:7778");
+    let mut config_path: Option<String> = None;
+    let mut node_config_path: Option<String> = None;
+    let mut bootstrap: Vec<SocketAddr> = Vec::new();
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--id" if i + 1 < args.len() => {
+                id = args[i + 1].clone();
+                i += 1;
+            }
+            "--udp" if i + 1 < args.len() => {
+                udp_addr = args[i + 1].clone();
+                i += 1;
+            }
+            "--tcp" if i + 1 < args.len() => {
+                tcp_addr = args[i + 1].clone();
+                i += 1;
+            }
+            "--config" | "-c" if i + 1 < args.len() => {
+                config_path = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "--node-config" if i + 1 < args.len() => {
+                node_config_path = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "--bootstrap" | "-b" if i + 1 < args.len() => {
+                match args[i + 1].parse() {
+                    Ok(addr) => bootstrap.push(addr),
+                    Err(e) => exit_with(format!("Invalid bootstrap address {}: {}", args[i + 1], e)),
+                }
+                i += 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let config = match &config_path {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .unwrap_or_else(|e| exit_with(format!("Failed to read {}: {}", path, e)));
+            serde_json::from_str::<TunConfig>(&text)
+                .unwrap_or_else(|e| exit_with(format!("Invalid config {}: {}", path, e)))
+        }
+        None => TunConfig::default(),
+    };
+    if let Err(e) = config.validate() {
+        exit_with(format!("Invalid config: {}", e));
+    }
+
+    let node = DistributedNode::new(NodeId::new(id.as_str()), &udp_addr, &tcp_addr)
+        .await
+        .unwrap_or_else(|e| exit_with(format!("Failed to start node: {}", e)));
+    if let Some(path) = &node_config_path {
+        if let Err(e) = node.set_config_file(path.into()).await {
+            exit_with(format!("Invalid node config {}: {}", path, e));
+        }
+    }
+    let node = Arc::new(node);
+    let runner = Arc::clone(&node);
+    tokio::spawn(async move { runner.start(Vec::new()).await });
+
+    if !bootstrap.is_empty() {
+        match node.join_network(&bootstrap, Duration::from_secs(5)).await {
+            Ok(neighbors) => println!("Joined with {} neighbors", neighbors),
+            Err(e) => eprintln!("Join failed, continuing alone: {}", e),
+        }
+    }
+
+    let device = TunDevice::open(&config.device_name, config.mtu)
+        .unwrap_or_else(|e| exit_with(format!("Failed to open {}: {}", config.device_name, e)));
+    println!("Node {} tunneling via {} (mtu {})", id, device.name(), config.mtu);
+
+    let tunnel = TunTunnel::new(config).unwrap_or_else(|e| exit_with(format!("Invalid routes: {}", e)));
+    for route in tunnel.routes().routes() {
+        println!("  {}/{} -> {}", route.network, route.prefix_len, route.node.0);
+    }
+
+    if let Err(e) = run(node, device, Arc::new(Mutex::new(tunnel))).await {
+        exit_with(format!("Tunnel stopped: {}", e));
+    }
+}
@@ -13,8 +13,10 @@
 //! Usage:
 //!   dynamic_network_experiment [--base-size 500] [--churn-rounds 20] [--seed 42]
 
+use drfe_r::bench::{barabasi_albert, Network};
 use drfe_r::coordinates::{NodeId, RoutingCoordinate};
 use drfe_r::greedy_embedding::GreedyEmbedding;
+use drfe_r::path_search::PathSearch;
 use drfe_r::routing::{GPRouter, RoutingNode};
 use drfe_r::tz_routing::{TZConfig, TZRoutingTable};
 use drfe_r::PoincareDiskPoint;
@@ -89,7 +91,7 @@ fn main() {
     let mut results: Vec<DynamicRoundResult> = Vec::new();
 
     // Build initial BA network
-    let (mut adjacency, mut node_list) = generate_initial_ba(base_size, 3, seed);
+    let Network { nodes: mut node_list, mut adjacency, .. } = barabasi_albert(base_size, 3, seed);
     let edges_count = count_edges(&adjacency);
     println!("Initial network: {} nodes, {} edges", node_list.len(), edges_count);
 
@@ -245,54 +247,6 @@ fn main() {
 // Network mutation operations
 // ============================================================================
 
-fn generate_initial_ba(n: usize, m: usize, seed: u64) -> (HashMap<NodeId, Vec<NodeId>>, Vec<NodeId>) {
-    let mut rng = StdRng::seed_from_u64(seed);
-    let mut adjacency: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
-    let mut node_list: Vec<NodeId> = Vec::new();
-    let mut degrees: Vec<usize> = Vec::new();
-
-    for i in 0..n {
-        let id = NodeId::new(format!("node_{}", i));
-        adjacency.insert(id.clone(), Vec::new());
-        node_list.push(id);
-        degrees.push(0);
-    }
-
-    // Initial clique
-    for i in 0..m.min(n) {
-        for j in (i+1)..m.min(n) {
-            adjacency.get_mut(&node_list[i]).unwrap().push(node_list[j].clone());
-            adjacency.get_mut(&node_list[j]).unwrap().push(node_list[i].clone());
-            degrees[i] += 1;
-            degrees[j] += 1;
-        }
-    }
-
-    for new in m..n {
-        let total_deg: usize = degrees.iter().take(new).sum();
-        let mut targets = HashSet::new();
-        while targets.len() < m.min(new) {
-            let r = rng.gen_range(0..total_deg.max(1));
-            let mut cumul = 0;
-            for j in 0..new {
-                cumul += degrees[j];
-                if cumul > r {
-                    targets.insert(j);
-                    break;
-                }
-            }
-        }
-        for &t in &targets {
-            adjacency.get_mut(&node_list[new]).unwrap().push(node_list[t].clone());
-            adjacency.get_mut(&node_list[t]).unwrap().push(node_list[new].clone());
-            degrees[new] += 1;
-            degrees[t] += 1;
-        }
-    }
-
-    (adjacency, node_list)
-}
-
 fn add_nodes(
     adjacency: &mut HashMap<NodeId, Vec<NodeId>>,
     node_list: &mut Vec<NodeId>,
@@ -507,6 +461,7 @@ fn measure_routing(
     let n = node_list.len();
     if n < 2 { return (0.0, 0.0, 0.0, 0.0); }
 
+    let mut search = PathSearch::from_router(router);
     let max_hops = n as u32;
     let mut tz_success = 0u32;
     let mut tz_total_h = 0u64;
@@ -523,7 +478,7 @@ fn measure_routing(
         let src = &node_list[src_idx];
         let dst = &node_list[dst_idx];
 
-        let opt = bfs_distance(router, src, dst);
+        let opt = search.distance(src, dst);
 
         // Gravity-only
         let (g_ok, g_hops, stuck_at) = try_gravity(router, src, dst, max_hops);
@@ -601,21 +556,3 @@ fn try_gravity(router: &GPRouter, src: &NodeId, dst: &NodeId, max: u32) -> (bool
     (&current == dst, hops, current)
 }
 
-fn bfs_distance(router: &GPRouter, start: &NodeId, end: &NodeId) -> Option<u32> {
-    let mut visited = HashSet::new();
-    let mut queue = VecDeque::new();
-    queue.push_back((start.clone(), 0u32));
-    visited.insert(start.clone());
-    while let Some((cur, dist)) = queue.pop_front() {
-        if &cur == end { return Some(dist); }
-        if let Some(node) = router.get_node(&cur) {
-            for neighbor in &node.neighbors {
-                if !visited.contains(neighbor) {
-                    visited.insert(neighbor.clone());
-                    queue.push_back((neighbor.clone(), dist + 1));
-                }
-            }
-        }
-    }
-    None
-}
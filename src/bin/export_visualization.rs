@@ -1,17 +1,30 @@
 //! Export visualization data for Poincaré disk frontend
 //!
 //! Generates JSON coordinate files for visualization comparison.
+//!
+//! The `render` subcommand draws a checkpoint or an embedding file instead:
+//!
+//! Usage: export_visualization render --checkpoint <file> | --embedding <file>
+//!        [--svg <out.svg>] [--json <out.json>] [--traffic <traffic.json>]
+//!        [--size <px>] [--labels]
+//!
+//! The traffic file maps node IDs to packet or byte counts and turns on the
+//! heatmap.
 
+use drfe_r::bench::barabasi_albert;
 use drfe_r::coordinates::{NodeId, RoutingCoordinate};
 use drfe_r::greedy_embedding::GreedyEmbedding;
+use drfe_r::network::NodeCheckpoint;
 use drfe_r::ricci::{GraphNode, RicciFlow, RicciGraph};
 use drfe_r::routing::{GPRouter, RoutingNode};
+use drfe_r::visualization::{Scene, SvgOptions};
 use drfe_r::PoincareDiskPoint;
 use rand::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::Write;
+use std::path::PathBuf;
 
 #[derive(Serialize, Deserialize)]
 struct NetworkVisualization {
@@ -36,55 +49,6 @@ struct EdgeViz {
     target: String,
 }
 
-fn build_ba_topology(n: usize, m: usize, seed: u64) -> HashMap<usize, HashSet<usize>> {
-    let mut adj: HashMap<usize, HashSet<usize>> = HashMap::new();
-    for i in 0..n {
-        adj.insert(i, HashSet::new());
-    }
-
-    let mut rng = StdRng::seed_from_u64(seed);
-    let mut degrees: Vec<usize> = vec![0; n];
-
-    for i in 0..m.min(n) {
-        for j in (i + 1)..m.min(n) {
-            adj.get_mut(&i).unwrap().insert(j);
-            adj.get_mut(&j).unwrap().insert(i);
-            degrees[i] += 1;
-            degrees[j] += 1;
-        }
-    }
-
-    for i in m..n {
-        let total_degree: usize = degrees.iter().take(i).sum();
-        if total_degree == 0 {
-            adj.get_mut(&i).unwrap().insert(0);
-            adj.get_mut(&0).unwrap().insert(i);
-            degrees[i] += 1;
-            degrees[0] += 1;
-            continue;
-        }
-
-        let mut connected = HashSet::new();
-        while connected.len() < m.min(i) {
-            let r = rng.gen::<f64>() * total_degree as f64;
-            let mut cumsum = 0.0;
-            for j in 0..i {
-                cumsum += degrees[j] as f64;
-                if cumsum >= r && !connected.contains(&j) {
-                    adj.get_mut(&i).unwrap().insert(j);
-                    adj.get_mut(&j).unwrap().insert(i);
-                    degrees[i] += 1;
-                    degrees[j] += 1;
-                    connected.insert(j);
-                    break;
-                }
-            }
-        }
-    }
-
-    adj
-}
-
 fn export_pie_embedding(adj: &HashMap<usize, HashSet<usize>>, n: usize) -> NetworkVisualization {
     let nodes: Vec<NodeId> = (0..n).map(|i| NodeId::new(format!("node_{}", i))).collect();
 
@@ -239,7 +203,83 @@ fn export_ricci_embedding(adj: &HashMap<usize, HashSet<usize>>, n: usize) -> Net
     }
 }
 
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+fn render(args: &[String]) {
+    let mut checkpoint: Option<PathBuf> = None;
+    let mut embedding: Option<PathBuf> = None;
+    let mut svg_out: Option<PathBuf> = None;
+    let mut json_out: Option<PathBuf> = None;
+    let mut traffic: Option<PathBuf> = None;
+    let mut options = SvgOptions::default();
+
+    let mut i = 0;
+    while i < args.len() {
+        let value = args.get(i + 1);
+        match (args[i].as_str(), value) {
+            ("--checkpoint" | "-c", Some(v)) => checkpoint = Some(PathBuf::from(v)),
+            ("--embedding" | "-e", Some(v)) => embedding = Some(PathBuf::from(v)),
+            ("--svg", Some(v)) => svg_out = Some(PathBuf::from(v)),
+            ("--json", Some(v)) => json_out = Some(PathBuf::from(v)),
+            ("--traffic", Some(v)) => traffic = Some(PathBuf::from(v)),
+            ("--size", Some(v)) => options.size = v.parse().unwrap_or_else(|_| fail(format!("Invalid size: {}", v))),
+            ("--labels", _) => {
+                options.labels = true;
+                i += 1;
+                continue;
+            }
+            (flag, _) => fail(format!("Unknown or incomplete option: {}", flag)),
+        }
+        i += 2;
+    }
+
+    let mut scene = match (checkpoint, embedding) {
+        (Some(path), None) => {
+            let checkpoint = NodeCheckpoint::load_from_file(&path).unwrap_or_else(|e| fail(e.to_string()));
+            Scene::from_checkpoint(&checkpoint)
+        }
+        (None, Some(path)) => {
+            let json = std::fs::read_to_string(&path).unwrap_or_else(|e| fail(format!("Failed to read {:?}: {}", path, e)));
+            Scene::from_json(&json).unwrap_or_else(|e| fail(e))
+        }
+        _ => fail("Pass exactly one of --checkpoint or --embedding".to_string()),
+    };
+
+    if let Some(path) = traffic {
+        let json = std::fs::read_to_string(&path).unwrap_or_else(|e| fail(format!("Failed to read {:?}: {}", path, e)));
+        let counts: HashMap<String, f64> =
+            serde_json::from_str(&json).unwrap_or_else(|e| fail(format!("Invalid traffic file: {}", e)));
+        let counts = counts.into_iter().map(|(id, count)| (NodeId::new(id), count)).collect();
+        scene = scene.with_traffic(&counts);
+        options.heatmap = true;
+    }
+
+    println!("Scene: {} nodes, {} edges", scene.nodes.len(), scene.edges.len());
+    let svg_out = match (&svg_out, &json_out) {
+        (None, None) => Some(PathBuf::from("disk.svg")),
+        _ => svg_out,
+    };
+    if let Some(path) = svg_out {
+        std::fs::write(&path, scene.render_svg(&options)).unwrap_or_else(|e| fail(format!("Failed to write {:?}: {}", path, e)));
+        println!("✓ Saved {:?}", path);
+    }
+    if let Some(path) = json_out {
+        let json = scene.to_json().unwrap_or_else(|e| fail(e));
+        std::fs::write(&path, json).unwrap_or_else(|e| fail(format!("Failed to write {:?}: {}", path, e)));
+        println!("✓ Saved {:?}", path);
+    }
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("render") {
+        render(&args[2..]);
+        return;
+    }
+
     println!("DRFE-R Visualization Export");
     println!("============================\n");
 
@@ -247,7 +287,12 @@ fn main() {
     let seed = 12345u64;
 
     println!("Generating BA topology with {} nodes...", n);
-    let adj = build_ba_topology(n, 3, seed);
+    let adj: HashMap<usize, HashSet<usize>> = barabasi_albert(n, 3, seed)
+        .neighbors
+        .into_iter()
+        .enumerate()
+        .map(|(i, neighbors)| (i, neighbors.into_iter().collect()))
+        .collect();
     println!("Topology has {} edges", adj.values().map(|s| s.len()).sum::<usize>() / 2);
 
     println!("\nExporting PIE embedding...");
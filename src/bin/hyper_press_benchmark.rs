@@ -3,62 +3,11 @@
 //! Compare HYPER-PRESS with PIE-DFS and PIE+TZ on various topologies
 
 use std::collections::{HashMap, HashSet};
+use drfe_r::bench::barabasi_albert;
 use drfe_r::coordinates::NodeId;
 use drfe_r::hyper_press::HyperPress;
 use rand::prelude::*;
 
-/// Generate Barabasi-Albert graph
-fn generate_ba_graph(n: usize, m: usize) -> HashMap<NodeId, Vec<NodeId>> {
-    let mut adj: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
-    let mut rng = rand::thread_rng();
-    
-    // Start with a clique of m+1 nodes
-    for i in 0..=m {
-        let id = NodeId(format!("n{}", i));
-        adj.entry(id.clone()).or_insert_with(Vec::new);
-        for j in 0..i {
-            let other = NodeId(format!("n{}", j));
-            adj.get_mut(&id).unwrap().push(other.clone());
-            adj.get_mut(&other).unwrap().push(id.clone());
-        }
-    }
-    
-    // Preferential attachment for remaining nodes
-    for i in (m + 1)..n {
-        let new_node = NodeId(format!("n{}", i));
-        adj.insert(new_node.clone(), Vec::new());
-        
-        // Calculate degree sum for preferential attachment
-        let degrees: Vec<(NodeId, usize)> = adj.iter()
-            .filter(|(k, _)| **k != new_node)
-            .map(|(k, v)| (k.clone(), v.len()))
-            .collect();
-        let total_degree: usize = degrees.iter().map(|(_, d)| d).sum();
-        
-        // Select m distinct targets
-        let mut targets = HashSet::new();
-        while targets.len() < m && targets.len() < degrees.len() {
-            let r: f64 = rng.gen();
-            let mut cumsum = 0.0;
-            for (node, deg) in &degrees {
-                cumsum += (*deg as f64) / (total_degree as f64);
-                if r < cumsum && !targets.contains(node) {
-                    targets.insert(node.clone());
-                    break;
-                }
-            }
-        }
-        
-        // Add edges
-        for target in targets {
-            adj.get_mut(&new_node).unwrap().push(target.clone());
-            adj.get_mut(&target).unwrap().push(new_node.clone());
-        }
-    }
-    
-    adj
-}
-
 /// Test HYPER-PRESS routing success rate
 fn test_hyper_press_routing(
     hp: &HyperPress,
@@ -153,7 +102,7 @@ fn main() {
         println!("\n▶ Network size: {} nodes", n);
         
         // Generate BA graph
-        let adj = generate_ba_graph(n, 3);
+        let adj = barabasi_albert(n, 3, n as u64).adjacency;
         
         // Test with different lambda values
         for &lambda in &[0.0, 0.3, 1.0] {
@@ -7,8 +7,10 @@
 //! Usage:
 //!   large_topology_benchmark [--sizes 1000,2000,5000] [--seeds 42,43,44] [--edge-list FILE]
 
+use drfe_r::bench::{barabasi_albert, watts_strogatz, Network};
 use drfe_r::coordinates::{NodeId, RoutingCoordinate};
 use drfe_r::greedy_embedding::GreedyEmbedding;
+use drfe_r::path_search::PathSearch;
 use drfe_r::routing::{GPRouter, RoutingNode};
 use drfe_r::tz_routing::{TZConfig, TZRoutingTable};
 use drfe_r::PoincareDiskPoint;
@@ -130,8 +132,8 @@ fn main() {
                 let start = Instant::now();
 
                 let (nodes, adj_idx, adjacency) = match *topo {
-                    "ba" => generate_ba_network(n, 3, seed),
-                    "ws" => generate_ws_network(n, 6, 0.3, seed),
+                    "ba" => network_parts(barabasi_albert(n, 3, seed)),
+                    "ws" => network_parts(watts_strogatz(n, 6, 0.3, seed)),
                     "community" => generate_community_network(n, seed),
                     "powerlaw_cluster" => generate_powerlaw_cluster(n, seed),
                     _ => continue,
@@ -254,6 +256,7 @@ fn run_pie_tz_tests(
     let mut rng = StdRng::seed_from_u64(seed + 1000);
     let n = nodes.len();
     let max_gravity = n as u32;
+    let mut search = PathSearch::from_router(router);
 
     let mut successes = 0u32;
     let mut total_hops = 0u64;
@@ -287,7 +290,7 @@ fn run_pie_tz_tests(
             gravity_hops += g;
             tz_hops += t;
 
-            if let Some(opt) = bfs_distance(router, &nodes[src], &nodes[dst]) {
+            if let Some(opt) = search.distance(&nodes[src], &nodes[dst]) {
                 total_optimal += opt as u64;
                 if opt > 0 {
                     let s = hops as f64 / opt as f64;
@@ -364,124 +367,15 @@ fn try_gravity_limited(router: &GPRouter, src: &NodeId, dst: &NodeId, max: u32)
     (&current == dst, hops, current)
 }
 
-fn bfs_distance(router: &GPRouter, start: &NodeId, end: &NodeId) -> Option<u32> {
-    let mut visited = HashSet::new();
-    let mut queue = VecDeque::new();
-    queue.push_back((start.clone(), 0u32));
-    visited.insert(start.clone());
-
-    while let Some((cur, dist)) = queue.pop_front() {
-        if &cur == end { return Some(dist); }
-        if let Some(node) = router.get_node(&cur) {
-            for neighbor in &node.neighbors {
-                if !visited.contains(neighbor) {
-                    visited.insert(neighbor.clone());
-                    queue.push_back((neighbor.clone(), dist + 1));
-                }
-            }
-        }
-    }
-    None
-}
-
 // ============================================================================
 // Network Generators
 // ============================================================================
 
-fn generate_ba_network(n: usize, m: usize, seed: u64)
-    -> (Vec<NodeId>, Vec<Vec<usize>>, HashMap<NodeId, Vec<NodeId>>)
-{
-    let mut rng = StdRng::seed_from_u64(seed);
-    let mut degrees: Vec<usize> = vec![0; n];
-    let mut adjacency_idx: Vec<Vec<usize>> = vec![Vec::new(); n];
-    let nodes: Vec<NodeId> = (0..n).map(|i| NodeId::new(format!("node_{}", i))).collect();
-
-    for i in 0..m.min(n) {
-        for j in (i + 1)..m.min(n) {
-            adjacency_idx[i].push(j);
-            adjacency_idx[j].push(i);
-            degrees[i] += 1;
-            degrees[j] += 1;
-        }
-    }
-
-    for new_node in m..n {
-        let total_degree: usize = degrees.iter().take(new_node).sum();
-        let mut targets = HashSet::new();
-
-        while targets.len() < m.min(new_node) {
-            let r = rng.gen_range(0..total_degree.max(1));
-            let mut cumul = 0;
-            for j in 0..new_node {
-                cumul += degrees[j];
-                if cumul > r {
-                    targets.insert(j);
-                    break;
-                }
-            }
-        }
-
-        for &target in &targets {
-            adjacency_idx[new_node].push(target);
-            adjacency_idx[target].push(new_node);
-            degrees[new_node] += 1;
-            degrees[target] += 1;
-        }
-    }
-
-    let mut adjacency = HashMap::new();
-    for (i, neighbors) in adjacency_idx.iter().enumerate() {
-        adjacency.insert(nodes[i].clone(), neighbors.iter().map(|&j| nodes[j].clone()).collect());
-    }
-
-    (nodes, adjacency_idx, adjacency)
-}
-
-fn generate_ws_network(n: usize, k: usize, beta: f64, seed: u64)
+fn network_parts(network: Network)
     -> (Vec<NodeId>, Vec<Vec<usize>>, HashMap<NodeId, Vec<NodeId>>)
 {
-    let mut rng = StdRng::seed_from_u64(seed);
-    let nodes: Vec<NodeId> = (0..n).map(|i| NodeId::new(format!("node_{}", i))).collect();
-    let mut adjacency_idx: Vec<Vec<usize>> = vec![Vec::new(); n];
-
-    // Ring lattice
-    for i in 0..n {
-        for j in 1..=(k / 2) {
-            let neighbor = (i + j) % n;
-            if !adjacency_idx[i].contains(&neighbor) {
-                adjacency_idx[i].push(neighbor);
-                adjacency_idx[neighbor].push(i);
-            }
-        }
-    }
-
-    // Rewire
-    for i in 0..n {
-        for j in 1..=(k / 2) {
-            if rng.gen::<f64>() < beta {
-                let old = (i + j) % n;
-                let mut new_target = rng.gen_range(0..n);
-                let mut tries = 0;
-                while (new_target == i || adjacency_idx[i].contains(&new_target)) && tries < n {
-                    new_target = rng.gen_range(0..n);
-                    tries += 1;
-                }
-                if tries < n {
-                    adjacency_idx[i].retain(|&x| x != old);
-                    adjacency_idx[old].retain(|&x| x != i);
-                    adjacency_idx[i].push(new_target);
-                    adjacency_idx[new_target].push(i);
-                }
-            }
-        }
-    }
-
-    let mut adjacency = HashMap::new();
-    for (i, neighbors) in adjacency_idx.iter().enumerate() {
-        adjacency.insert(nodes[i].clone(), neighbors.iter().map(|&j| nodes[j].clone()).collect());
-    }
-
-    (nodes, adjacency_idx, adjacency)
+    let Network { nodes, neighbors, adjacency } = network;
+    (nodes, neighbors, adjacency)
 }
 
 /// Community-structure network mimicking real-world AS topologies
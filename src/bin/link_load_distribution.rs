@@ -2,14 +2,17 @@
 //!
 //! Measures edge load concentration under different routing strategies.
 
+use drfe_r::bench::{barabasi_albert, Network};
 use drfe_r::coordinates::{NodeId, RoutingCoordinate};
 use drfe_r::greedy_embedding::GreedyEmbedding;
+use drfe_r::path_search::{PathSearch, SearchStrategy};
 use drfe_r::routing::{GPRouter, RoutingNode};
+use drfe_r::simulation::{BatchConfig, Workload};
 use drfe_r::tz_routing::{TZConfig, TZRoutingTable};
 use drfe_r::PoincareDiskPoint;
 use rand::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
@@ -97,65 +100,6 @@ fn edge_key(a: &NodeId, b: &NodeId) -> EdgeKey {
     }
 }
 
-fn generate_ba_network(
-    n: usize,
-    m: usize,
-    seed: u64,
-) -> (Vec<NodeId>, Vec<Vec<usize>>, HashMap<NodeId, Vec<NodeId>>) {
-    let mut rng = StdRng::seed_from_u64(seed);
-    let mut degrees: Vec<usize> = vec![0; n];
-    let mut adjacency_idx: Vec<Vec<usize>> = vec![Vec::new(); n];
-    let nodes: Vec<NodeId> = (0..n)
-        .map(|i| NodeId::new(format!("node_{}", i)))
-        .collect();
-
-    for i in 0..m.min(n) {
-        for j in (i + 1)..m.min(n) {
-            adjacency_idx[i].push(j);
-            adjacency_idx[j].push(i);
-            degrees[i] += 1;
-            degrees[j] += 1;
-        }
-    }
-
-    for i in m..n {
-        let total: usize = degrees.iter().take(i).sum();
-        if total == 0 {
-            continue;
-        }
-
-        let mut connected = HashSet::new();
-        while connected.len() < m.min(i) {
-            let r = rng.gen::<f64>() * total as f64;
-            let mut cumsum = 0.0;
-            for j in 0..i {
-                cumsum += degrees[j] as f64;
-                if cumsum >= r && !connected.contains(&j) {
-                    adjacency_idx[i].push(j);
-                    adjacency_idx[j].push(i);
-                    degrees[i] += 1;
-                    degrees[j] += 1;
-                    connected.insert(j);
-                    break;
-                }
-            }
-        }
-    }
-
-    let mut adjacency: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
-    for i in 0..n {
-        adjacency.insert(
-            nodes[i].clone(),
-            adjacency_idx[i]
-                .iter()
-                .map(|&j| nodes[j].clone())
-                .collect(),
-        );
-    }
-
-    (nodes, adjacency_idx, adjacency)
-}
-
 fn build_router_pie(
     nodes: &[NodeId],
     adjacency_idx: &[Vec<usize>],
@@ -199,66 +143,6 @@ fn build_router_pie(
     router
 }
 
-fn bfs_distance(router: &GPRouter, start: &NodeId, end: &NodeId) -> Option<u32> {
-    let mut visited = HashSet::new();
-    let mut queue = VecDeque::new();
-    queue.push_back((start.clone(), 0u32));
-    visited.insert(start.clone());
-
-    while let Some((cur, dist)) = queue.pop_front() {
-        if &cur == end {
-            return Some(dist);
-        }
-        if let Some(node) = router.get_node(&cur) {
-            for neighbor in &node.neighbors {
-                if !visited.contains(neighbor) {
-                    visited.insert(neighbor.clone());
-                    queue.push_back((neighbor.clone(), dist + 1));
-                }
-            }
-        }
-    }
-    None
-}
-
-fn bfs_path(router: &GPRouter, start: &NodeId, end: &NodeId) -> Option<Vec<NodeId>> {
-    let mut visited = HashSet::new();
-    let mut queue = VecDeque::new();
-    let mut parent: HashMap<NodeId, NodeId> = HashMap::new();
-
-    visited.insert(start.clone());
-    queue.push_back(start.clone());
-
-    while let Some(cur) = queue.pop_front() {
-        if &cur == end {
-            break;
-        }
-        if let Some(node) = router.get_node(&cur) {
-            for neighbor in &node.neighbors {
-                if !visited.contains(neighbor) {
-                    visited.insert(neighbor.clone());
-                    parent.insert(neighbor.clone(), cur.clone());
-                    queue.push_back(neighbor.clone());
-                }
-            }
-        }
-    }
-
-    if !visited.contains(end) {
-        return None;
-    }
-
-    let mut path = Vec::new();
-    let mut current = end.clone();
-    path.push(current.clone());
-    while &current != start {
-        current = parent.get(&current)?.clone();
-        path.push(current.clone());
-    }
-    path.reverse();
-    Some(path)
-}
-
 fn gravity_path_limited(
     router: &GPRouter,
     src: &NodeId,
@@ -665,8 +549,8 @@ fn main() {
             println!("Seed {}", current_seed);
         }
 
-        let (nodes, adjacency_idx, adjacency) =
-            generate_ba_network(num_nodes, 3, *current_seed);
+        let Network { nodes, neighbors: adjacency_idx, adjacency } =
+            barabasi_albert(num_nodes, 3, *current_seed);
         let router = build_router_pie(&nodes, &adjacency_idx, &adjacency);
         let tz_table =
             TZRoutingTable::build(&adjacency, TZConfig::default()).expect("TZ build failed");
@@ -682,11 +566,24 @@ fn main() {
             pairs.push((nodes[src].clone(), nodes[dst].clone()));
         }
 
+        // Plain BFS keeps the same shortest path among ties as before
+        let mut search = PathSearch::from_router(&router).with_strategy(SearchStrategy::Bfs).unwrap();
         let mut opt_distances = Vec::with_capacity(num_pairs);
         for (src, dst) in &pairs {
-            opt_distances.push(bfs_distance(&router, src, dst));
+            opt_distances.push(search.distance(src, dst));
         }
 
+        let pie_config = BatchConfig {
+            max_ttl: Some((num_nodes * 20) as u32),
+            compute_stretch: false,
+        };
+        let pie_paths: Vec<Option<Vec<NodeId>>> = router
+            .simulate_batch(&Workload::Pairs(pairs.clone()), &pie_config)
+            .pairs
+            .into_iter()
+            .map(|pair| pair.delivery.success.then_some(pair.delivery.path))
+            .collect();
+
         let mut results = Vec::new();
         let max_gravity = nodes.len() as u32;
 
@@ -700,22 +597,10 @@ fn main() {
 
             for (idx, (src, dst)) in pairs.iter().enumerate() {
                 let path = match strategy.as_str() {
-                    "pie" => {
-                        let dest_coord = match router.get_node(dst) {
-                            Some(n) => n.coord.point,
-                            None => continue,
-                        };
-                        let result =
-                            router.simulate_delivery(src, dst, dest_coord, (num_nodes * 20) as u32);
-                        if result.success {
-                            Some(result.path)
-                        } else {
-                            None
-                        }
-                    }
+                    "pie" => pie_paths[idx].clone(),
                     "pie_tz" => pie_tz_path(&router, &tz_table, src, dst, max_gravity),
                     "tz_only" => tz_table.compute_path(src, dst),
-                    "shortest" => bfs_path(&router, src, dst),
+                    "shortest" => search.shortest_path(src, dst),
                     _ => None,
                 };
 
@@ -7,15 +7,16 @@
 //!
 //! Results are saved to paper_data/lmh_tz/
 
+use drfe_r::bench::{barabasi_albert, Network};
 use drfe_r::coordinates::{NodeId, RoutingCoordinate};
 use drfe_r::greedy_embedding::GreedyEmbedding;
 use drfe_r::landmark_embedding::{LandmarkEmbedding, LandmarkConfig};
 use drfe_r::routing::{GPRouter, RoutingNode};
+use drfe_r::simulation::{BatchConfig, Workload};
 use drfe_r::tz_routing::{TZRoutingTable, TZConfig};
 use drfe_r::PoincareDiskPoint;
-use rand::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;
 use std::time::Instant;
@@ -58,11 +59,11 @@ fn main() {
         println!("\n▶ Testing network size: {} nodes", n);
         
         // Generate BA network adjacency
-        let (nodes, adjacency_idx, adjacency) = generate_ba_adjacency(n, 3, seed);
+        let Network { nodes, neighbors: adjacency_idx, adjacency } = barabasi_albert(n, 3, seed);
 
         // Strategy 1: PIE only (baseline)
         let (router_pie, time_pie) = build_router_pie(&nodes, &adjacency_idx, &adjacency);
-        let results_pie = run_routing_tests(&router_pie, &nodes, n, num_tests, seed);
+        let results_pie = run_routing_tests(&router_pie, num_tests, seed);
         let result_pie = BenchmarkResult {
             network_size: n,
             embedding_strategy: "PIE".to_string(),
@@ -83,7 +84,7 @@ fn main() {
 
         // Strategy 2: LMH (Landmark-MDS Hyperbolic)
         let (router_lmh, time_lmh) = build_router_lmh(&nodes, &adjacency_idx, &adjacency);
-        let results_lmh = run_routing_tests(&router_lmh, &nodes, n, num_tests, seed);
+        let results_lmh = run_routing_tests(&router_lmh, num_tests, seed);
         let result_lmh = BenchmarkResult {
             network_size: n,
             embedding_strategy: "LMH".to_string(),
@@ -201,64 +202,6 @@ struct TestResults {
     routing_time_ms: u128,
 }
 
-fn generate_ba_adjacency(n: usize, m: usize, seed: u64) 
-    -> (Vec<NodeId>, Vec<Vec<usize>>, HashMap<NodeId, Vec<NodeId>>) 
-{
-    let mut rng = StdRng::seed_from_u64(seed);
-    let mut degrees: Vec<usize> = vec![0; n];
-    let mut adjacency_idx: Vec<Vec<usize>> = vec![Vec::new(); n];
-    let nodes: Vec<NodeId> = (0..n).map(|i| NodeId::new(format!("node_{}", i))).collect();
-
-    // Initial complete graph of m nodes
-    for i in 0..m.min(n) {
-        for j in (i+1)..m.min(n) {
-            adjacency_idx[i].push(j);
-            adjacency_idx[j].push(i);
-            degrees[i] += 1;
-            degrees[j] += 1;
-        }
-    }
-
-    // Preferential attachment
-    for i in m..n {
-        let total: usize = degrees.iter().take(i).sum();
-        if total == 0 {
-            adjacency_idx[i].push(0);
-            adjacency_idx[0].push(i);
-            degrees[i] += 1;
-            degrees[0] += 1;
-            continue;
-        }
-
-        let mut connected = HashSet::new();
-        let mut attempts = 0;
-        while connected.len() < m.min(i) && attempts < 1000 {
-            attempts += 1;
-            let r = rng.gen::<f64>() * total as f64;
-            let mut cumsum = 0.0;
-            for j in 0..i {
-                cumsum += degrees[j] as f64;
-                if cumsum >= r && !connected.contains(&j) {
-                    adjacency_idx[i].push(j);
-                    adjacency_idx[j].push(i);
-                    degrees[i] += 1;
-                    degrees[j] += 1;
-                    connected.insert(j);
-                    break;
-                }
-            }
-        }
-    }
-
-    let mut adjacency: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
-    for i in 0..n {
-        let neighbors: Vec<NodeId> = adjacency_idx[i].iter().map(|&j| nodes[j].clone()).collect();
-        adjacency.insert(nodes[i].clone(), neighbors);
-    }
-
-    (nodes, adjacency_idx, adjacency)
-}
-
 fn build_router_pie(
     nodes: &[NodeId], 
     adjacency_idx: &[Vec<usize>], 
@@ -330,96 +273,21 @@ fn build_router_lmh(
     (router, start.elapsed().as_millis())
 }
 
-fn run_routing_tests(
-    router: &GPRouter, 
-    nodes: &[NodeId], 
-    n: usize, 
-    num_tests: usize, 
-    seed: u64
-) -> TestResults {
-    let mut rng = StdRng::seed_from_u64(seed + 1000);
-    let max_ttl = (n * 20) as u32;
-
-    let mut successes = 0;
-    let mut total_hops = 0u32;
-    let mut total_optimal = 0u32;
-    let mut gravity_hops = 0u32;
-    let mut pressure_hops = 0u32;
-    let mut tree_hops = 0u32;
-    let mut total_all_hops = 0u32;
-    let mut max_stretch = 0.0f64;
+fn run_routing_tests(router: &GPRouter, num_tests: usize, seed: u64) -> TestResults {
+    let workload = Workload::Random { count: num_tests, seed: seed + 1000 };
 
     let routing_start = Instant::now();
-
-    for _ in 0..num_tests {
-        let src_idx = rng.gen_range(0..n);
-        let mut dst_idx = rng.gen_range(0..n);
-        while dst_idx == src_idx {
-            dst_idx = rng.gen_range(0..n);
-        }
-
-        let source = &nodes[src_idx];
-        let dest = &nodes[dst_idx];
-
-        if let Some(dest_node) = router.get_node(dest) {
-            let result = router.simulate_delivery(source, dest, dest_node.coord.point, max_ttl);
-            if result.success {
-                successes += 1;
-                total_hops += result.hops;
-                gravity_hops += result.gravity_hops;
-                pressure_hops += result.pressure_hops;
-                tree_hops += result.tree_hops;
-                total_all_hops += result.hops;
-
-                // BFS for optimal
-                if let Some(opt) = bfs_shortest_path(router, source, dest) {
-                    total_optimal += opt;
-                    if opt > 0 {
-                        let stretch = result.hops as f64 / opt as f64;
-                        max_stretch = f64::max(max_stretch, stretch);
-                    }
-                }
-            }
-        }
-    }
-
+    let summary = router.simulate_batch(&workload, &BatchConfig::default()).summary;
     let routing_time_ms = routing_start.elapsed().as_millis();
 
-    let success_rate = successes as f64 / num_tests as f64;
-    let avg_hops = if successes > 0 { total_hops as f64 / successes as f64 } else { 0.0 };
-    let stretch = if total_optimal > 0 { total_hops as f64 / total_optimal as f64 } else { 0.0 };
-    let gravity_pct = if total_all_hops > 0 { gravity_hops as f64 / total_all_hops as f64 * 100.0 } else { 0.0 };
-    let pressure_pct = if total_all_hops > 0 { pressure_hops as f64 / total_all_hops as f64 * 100.0 } else { 0.0 };
-    let tree_pct = if total_all_hops > 0 { tree_hops as f64 / total_all_hops as f64 * 100.0 } else { 0.0 };
-
     TestResults { 
-        success_rate, 
-        avg_hops, 
-        stretch, 
-        max_stretch,
-        gravity_pct, 
-        pressure_pct, 
-        tree_pct,
+        success_rate: summary.success_rate, 
+        avg_hops: summary.avg_hops, 
+        stretch: summary.stretch, 
+        max_stretch: summary.max_stretch,
+        gravity_pct: summary.gravity_pct, 
+        pressure_pct: summary.pressure_pct, 
+        tree_pct: summary.tree_pct,
         routing_time_ms,
     }
 }
-
-fn bfs_shortest_path(router: &GPRouter, start: &NodeId, end: &NodeId) -> Option<u32> {
-    let mut visited = HashSet::new();
-    let mut queue = VecDeque::new();
-    queue.push_back((start.clone(), 0));
-    visited.insert(start.clone());
-
-    while let Some((current, dist)) = queue.pop_front() {
-        if &current == end { return Some(dist); }
-        if let Some(node) = router.get_node(&current) {
-            for neighbor in &node.neighbors {
-                if !visited.contains(neighbor) {
-                    visited.insert(neighbor.clone());
-                    queue.push_back((neighbor.clone(), dist + 1));
-                }
-            }
-        }
-    }
-    None
-}
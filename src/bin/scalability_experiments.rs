@@ -6,13 +6,12 @@
 //!
 //! Requirements: 10.1, 16.1, 16.2, 16.3, 16.4
 
-use drfe_r::coordinates::{NodeId, RoutingCoordinate};
-use drfe_r::greedy_embedding::GreedyEmbedding;
-use drfe_r::routing::{GPRouter, RoutingNode};
-use drfe_r::PoincareDiskPoint;
+use drfe_r::bench::{barabasi_albert, build_pie_router};
+use drfe_r::coordinates::NodeId;
+use drfe_r::routing::GPRouter;
+use drfe_r::simulation::{BatchConfig, Workload};
 use rand::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::Write;
 use std::time::Instant;
@@ -148,144 +147,9 @@ pub struct ScalabilityConfigSummary {
 /// Generate a Barabási-Albert scale-free network with PIE embedding
 fn generate_ba_network(n: usize, m: usize, seed: u64) -> (GPRouter, u128) {
     let start = Instant::now();
-    let mut rng = StdRng::seed_from_u64(seed);
-    let mut degrees: Vec<usize> = Vec::new();
-    let mut nodes: Vec<NodeId> = Vec::new();
-    let mut adjacency_idx: Vec<Vec<usize>> = Vec::new();
-
-    // Create nodes
-    for i in 0..n {
-        let id = NodeId::new(format!("node_{}", i));
-        nodes.push(id);
-        degrees.push(0);
-        adjacency_idx.push(Vec::new());
-    }
-
-    // Build initial complete graph of m nodes
-    for i in 0..m.min(n) {
-        for j in (i + 1)..m.min(n) {
-            adjacency_idx[i].push(j);
-            adjacency_idx[j].push(i);
-            degrees[i] += 1;
-            degrees[j] += 1;
-        }
-    }
-
-    // Add remaining nodes with preferential attachment
-    for i in m..n {
-        let total_degree: usize = degrees.iter().take(i).sum();
-        if total_degree == 0 {
-            adjacency_idx[i].push(0);
-            adjacency_idx[0].push(i);
-            degrees[i] += 1;
-            degrees[0] += 1;
-            continue;
-        }
-
-        let mut connected = HashSet::new();
-        let mut attempts = 0;
-        while connected.len() < m.min(i) && attempts < 1000 {
-            attempts += 1;
-            let r_val = rng.gen::<f64>() * total_degree as f64;
-            let mut cumsum = 0.0;
-            for j in 0..i {
-                cumsum += degrees[j] as f64;
-                if cumsum >= r_val && !connected.contains(&j) {
-                    adjacency_idx[i].push(j);
-                    adjacency_idx[j].push(i);
-                    degrees[i] += 1;
-                    degrees[j] += 1;
-                    connected.insert(j);
-                    break;
-                }
-            }
-        }
-    }
-
-    let generation_time = start.elapsed().as_millis();
-
-    // Convert to NodeId-based adjacency
-    let mut adjacency: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
-    for i in 0..n {
-        let neighbors: Vec<NodeId> = adjacency_idx[i]
-            .iter()
-            .map(|&j| nodes[j].clone())
-            .collect();
-        adjacency.insert(nodes[i].clone(), neighbors);
-    }
-
-    // Compute PIE embedding
-    let embed_start = Instant::now();
-    let embedder = GreedyEmbedding::new();
-    let embedding_result = embedder.embed(&adjacency).expect("Embedding should succeed");
-    let embedding_time = embed_start.elapsed().as_millis();
-
-    // Build tree parent map
-    let mut tree_parent: HashMap<NodeId, Option<NodeId>> = HashMap::new();
-    tree_parent.insert(embedding_result.root.clone(), None);
-    for (parent_id, children) in &embedding_result.tree_children {
-        for child_id in children {
-            tree_parent.insert(child_id.clone(), Some(parent_id.clone()));
-        }
-    }
-
-    // Create router
-    let mut router = GPRouter::new();
-    for i in 0..n {
-        let node_id = &nodes[i];
-        let point = embedding_result
-            .coordinates
-            .get(node_id)
-            .copied()
-            .unwrap_or_else(PoincareDiskPoint::origin);
-        let coord = RoutingCoordinate::new(point, 0);
-        let mut routing_node = RoutingNode::new(node_id.clone(), coord);
-        
-        let parent = tree_parent.get(node_id).cloned().flatten();
-        let children = embedding_result
-            .tree_children
-            .get(node_id)
-            .cloned()
-            .unwrap_or_default();
-        routing_node.set_tree_info(parent, children);
-        
-        router.add_node(routing_node);
-    }
-
-    // Add edges
-    for i in 0..n {
-        for &j in &adjacency_idx[i] {
-            if i < j {
-                router.add_edge(&nodes[i], &nodes[j]);
-            }
-        }
-    }
-
-    (router, generation_time + embedding_time)
-}
-
-/// Calculate shortest path using BFS
-fn bfs_shortest_path(router: &GPRouter, start: &NodeId, end: &NodeId) -> Option<u32> {
-    let mut visited = HashSet::new();
-    let mut queue = std::collections::VecDeque::new();
-    queue.push_back((start.clone(), 0));
-    visited.insert(start.clone());
-
-    while let Some((current, dist)) = queue.pop_front() {
-        if &current == end {
-            return Some(dist);
-        }
-
-        if let Some(node) = router.get_node(&current) {
-            for neighbor in &node.neighbors {
-                if !visited.contains(neighbor) {
-                    visited.insert(neighbor.clone());
-                    queue.push_back((neighbor.clone(), dist + 1));
-                }
-            }
-        }
-    }
-    None
+    let network = barabasi_albert(n, m, seed);
+    let router = build_pie_router(&network).expect("Embedding should succeed");
+    (router, start.elapsed().as_millis())
 }
 
 /// Run scalability experiment for a single network size
@@ -318,72 +182,48 @@ fn run_scalability_experiment(
         .map(|i| NodeId::new(format!("node_{}", i)))
         .collect();
     
-    let routing_start = Instant::now();
-    let mut successful = 0;
-    let mut hop_counts = Vec::new();
-    let mut optimal_hop_counts = Vec::new();
-    let mut total_gravity_hops = 0u32;
-    let mut total_pressure_hops = 0u32;
-    let mut total_tree_hops = 0u32;
-    
+    let mut pairs = Vec::with_capacity(config.num_routing_tests);
     for _ in 0..config.num_routing_tests {
         let src_idx = rng.gen_range(0..network_size);
         let mut dst_idx = rng.gen_range(0..network_size);
         while dst_idx == src_idx {
             dst_idx = rng.gen_range(0..network_size);
         }
-        
-        let source = &node_ids[src_idx];
-        let dest = &node_ids[dst_idx];
-        
-        if let Some(dest_node) = router.get_node(dest) {
-            let result = router.simulate_delivery(
-                source,
-                dest,
-                dest_node.coord.point,
-                config.max_ttl,
-            );
-            
-            if result.success {
-                successful += 1;
-                hop_counts.push(result.hops);
-                total_gravity_hops += result.gravity_hops;
-                total_pressure_hops += result.pressure_hops;
-                total_tree_hops += result.tree_hops;
-                
-                if let Some(opt) = bfs_shortest_path(&router, source, dest) {
-                    optimal_hop_counts.push(opt);
-                }
-            }
-        }
+        pairs.push((node_ids[src_idx].clone(), node_ids[dst_idx].clone()));
     }
-    
+
+    let routing_start = Instant::now();
+    let batch = router.simulate_batch(
+        &Workload::Pairs(pairs),
+        &BatchConfig {
+            max_ttl: Some(config.max_ttl),
+            compute_stretch: true,
+        },
+    );
     let routing_time_ms = routing_start.elapsed().as_millis();
     println!("done ({} ms)", routing_time_ms);
-    
+
     // Calculate statistics
-    let success_rate = successful as f64 / config.num_routing_tests as f64;
-    
-    let total_hops: u32 = hop_counts.iter().sum();
-    let avg_hops = if successful > 0 {
-        total_hops as f64 / successful as f64
-    } else {
-        0.0
-    };
-    
+    let summary = &batch.summary;
+    let successful = summary.delivered as usize;
+    let success_rate = summary.success_rate;
+    let avg_hops = summary.avg_hops;
+    let avg_stretch = summary.stretch;
+
+    let delivered: Vec<_> = batch.pairs.iter().filter(|p| p.delivery.success).collect();
+    let mut hop_counts: Vec<u32> = delivered.iter().map(|p| p.delivery.hops).collect();
+    let optimal_hop_counts: Vec<u32> = delivered.iter().filter_map(|p| p.optimal_hops).collect();
+    let total_gravity_hops: u32 = delivered.iter().map(|p| p.delivery.gravity_hops).sum();
+    let total_pressure_hops: u32 = delivered.iter().map(|p| p.delivery.pressure_hops).sum();
+    let total_tree_hops: u32 = delivered.iter().map(|p| p.delivery.tree_hops).sum();
+
     let total_optimal: u32 = optimal_hop_counts.iter().sum();
     let avg_optimal_hops = if !optimal_hop_counts.is_empty() {
         total_optimal as f64 / optimal_hop_counts.len() as f64
     } else {
         0.0
     };
-    
-    let avg_stretch = if total_optimal > 0 {
-        total_hops as f64 / total_optimal as f64
-    } else {
-        0.0
-    };
-    
+
     // Calculate percentiles
     hop_counts.sort_unstable();
     let median_hops = if !hop_counts.is_empty() {
@@ -401,11 +241,7 @@ fn run_scalability_experiment(
     
     let max_hops = hop_counts.iter().max().copied().unwrap_or(0);
     
-    let gravity_percentage = if total_hops > 0 {
-        (total_gravity_hops as f64 / total_hops as f64) * 100.0
-    } else {
-        0.0
-    };
+    let gravity_percentage = summary.gravity_pct;
     
     let avg_routing_time_us = if config.num_routing_tests > 0 {
         (routing_time_ms as f64 * 1000.0) / config.num_routing_tests as f64
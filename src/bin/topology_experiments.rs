@@ -13,9 +13,11 @@
 //! - Stretch ratio (actual hops / shortest path hops)
 
 
+use drfe_r::bench::{barabasi_albert, erdos_renyi, grid_rows, watts_strogatz, Network};
 use drfe_r::coordinates::{NodeId, RoutingCoordinate};
 use drfe_r::greedy_embedding::GreedyEmbedding;
 use drfe_r::routing::{GPRouter, RoutingNode};
+use drfe_r::simulation::{BatchConfig, Workload};
 use drfe_r::tz_routing::{TZConfig, TZRoutingTable};
 use drfe_r::PoincareDiskPoint;
 use rand::prelude::*;
@@ -178,181 +180,6 @@ impl std::fmt::Display for ExperimentResult {
     }
 }
 
-/// Generate Barabási-Albert scale-free network
-fn generate_barabasi_albert(config: &TopologyConfig) -> GPRouter {
-    let mut rng = StdRng::seed_from_u64(config.seed);
-    let mut degrees: Vec<usize> = vec![0; config.num_nodes];
-    let mut adjacency_idx: Vec<Vec<usize>> = vec![Vec::new(); config.num_nodes];
-    let nodes: Vec<NodeId> = (0..config.num_nodes)
-        .map(|i| NodeId::new(format!("node_{}", i)))
-        .collect();
-
-    let m = config.ba_m.min(config.num_nodes - 1);
-
-    // Initial complete graph
-    for i in 0..m {
-        for j in (i + 1)..m {
-            adjacency_idx[i].push(j);
-            adjacency_idx[j].push(i);
-            degrees[i] += 1;
-            degrees[j] += 1;
-        }
-    }
-
-    // Preferential attachment
-    for i in m..config.num_nodes {
-        let total_degree: usize = degrees.iter().take(i).sum();
-        if total_degree == 0 {
-            adjacency_idx[i].push(0);
-            adjacency_idx[0].push(i);
-            degrees[i] += 1;
-            degrees[0] += 1;
-            continue;
-        }
-
-        let mut connected = HashSet::new();
-        let mut attempts = 0;
-        while connected.len() < m && attempts < 1000 {
-            attempts += 1;
-            let r_val = rng.gen::<f64>() * total_degree as f64;
-            let mut cumsum = 0.0;
-            for j in 0..i {
-                cumsum += degrees[j] as f64;
-                if cumsum >= r_val && !connected.contains(&j) {
-                    adjacency_idx[i].push(j);
-                    adjacency_idx[j].push(i);
-                    degrees[i] += 1;
-                    degrees[j] += 1;
-                    connected.insert(j);
-                    break;
-                }
-            }
-        }
-    }
-
-    build_router_from_adjacency(config, &nodes, &adjacency_idx)
-}
-
-/// Generate Watts-Strogatz small-world network
-fn generate_watts_strogatz(config: &TopologyConfig) -> GPRouter {
-    let mut rng = StdRng::seed_from_u64(config.seed);
-    let mut adjacency: HashMap<usize, HashSet<usize>> = HashMap::new();
-    
-    for i in 0..config.num_nodes {
-        adjacency.insert(i, HashSet::new());
-    }
-
-    let k = config.ws_k.min(config.num_nodes - 1);
-    
-    // Ring lattice
-    for i in 0..config.num_nodes {
-        for j in 1..=(k / 2) {
-            let neighbor = (i + j) % config.num_nodes;
-            adjacency.get_mut(&i).unwrap().insert(neighbor);
-            adjacency.get_mut(&neighbor).unwrap().insert(i);
-        }
-    }
-
-    // Rewire with probability beta
-    for i in 0..config.num_nodes {
-        for j in 1..=(k / 2) {
-            let original_neighbor = (i + j) % config.num_nodes;
-            
-            if rng.gen::<f64>() < config.ws_beta {
-                if adjacency.get(&i).unwrap().contains(&original_neighbor) {
-                    adjacency.get_mut(&i).unwrap().remove(&original_neighbor);
-                    adjacency.get_mut(&original_neighbor).unwrap().remove(&i);
-
-                    let mut new_neighbor = rng.gen_range(0..config.num_nodes);
-                    while new_neighbor == i || adjacency.get(&i).unwrap().contains(&new_neighbor) {
-                        new_neighbor = rng.gen_range(0..config.num_nodes);
-                    }
-
-                    adjacency.get_mut(&i).unwrap().insert(new_neighbor);
-                    adjacency.get_mut(&new_neighbor).unwrap().insert(i);
-                }
-            }
-        }
-    }
-
-    let nodes: Vec<NodeId> = (0..config.num_nodes)
-        .map(|i| NodeId::new(format!("node_{}", i)))
-        .collect();
-    
-    let adjacency_idx: Vec<Vec<usize>> = (0..config.num_nodes)
-        .map(|i| adjacency.get(&i).unwrap().iter().copied().collect())
-        .collect();
-
-    build_router_from_adjacency(config, &nodes, &adjacency_idx)
-}
-
-/// Generate 2D Grid network
-fn generate_grid(config: &TopologyConfig) -> GPRouter {
-    let width = (config.num_nodes as f64).sqrt().ceil() as usize;
-    let mut adjacency_idx: Vec<Vec<usize>> = vec![Vec::new(); config.num_nodes];
-
-    for i in 0..config.num_nodes {
-        let x = i % width;
-
-        // Right neighbor
-        if x + 1 < width {
-            let neighbor = i + 1;
-            if neighbor < config.num_nodes {
-                adjacency_idx[i].push(neighbor);
-                adjacency_idx[neighbor].push(i);
-            }
-        }
-
-        // Bottom neighbor
-        let neighbor = i + width;
-        if neighbor < config.num_nodes {
-            adjacency_idx[i].push(neighbor);
-            adjacency_idx[neighbor].push(i);
-        }
-    }
-
-    let nodes: Vec<NodeId> = (0..config.num_nodes)
-        .map(|i| NodeId::new(format!("node_{}", i)))
-        .collect();
-
-    build_router_from_adjacency(config, &nodes, &adjacency_idx)
-}
-
-/// Generate Random (Erdős-Rényi) network
-fn generate_random(config: &TopologyConfig) -> GPRouter {
-    let mut rng = StdRng::seed_from_u64(config.seed);
-    let mut adjacency_idx: Vec<Vec<usize>> = vec![Vec::new(); config.num_nodes];
-
-    // Generate edges with probability p
-    for i in 0..config.num_nodes {
-        for j in (i + 1)..config.num_nodes {
-            if rng.gen::<f64>() < config.random_p {
-                adjacency_idx[i].push(j);
-                adjacency_idx[j].push(i);
-            }
-        }
-    }
-
-    // Ensure connectivity
-    let mut connected: HashSet<usize> = HashSet::new();
-    connected.insert(0);
-    
-    for i in 1..config.num_nodes {
-        if adjacency_idx[i].is_empty() {
-            let connected_idx = *connected.iter().nth(rng.gen_range(0..connected.len())).unwrap();
-            adjacency_idx[i].push(connected_idx);
-            adjacency_idx[connected_idx].push(i);
-        }
-        connected.insert(i);
-    }
-
-    let nodes: Vec<NodeId> = (0..config.num_nodes)
-        .map(|i| NodeId::new(format!("node_{}", i)))
-        .collect();
-
-    build_router_from_adjacency(config, &nodes, &adjacency_idx)
-}
-
 /// Generate Real-World inspired topology (combination of BA + community structure)
 fn generate_real_world(config: &TopologyConfig) -> GPRouter {
     if let Some(path) = &config.real_world_path {
@@ -486,6 +313,11 @@ fn load_edge_list(path: &str) -> Result<(Vec<NodeId>, Vec<Vec<usize>>), String>
     Ok((nodes, adjacency_idx))
 }
 
+/// Build GPRouter from a generated topology using PIE embedding
+fn build_router_from_network(config: &TopologyConfig, network: &Network) -> GPRouter {
+    build_router_from_adjacency(config, &network.nodes, &network.neighbors)
+}
+
 /// Build GPRouter from adjacency list using PIE embedding
 fn build_router_from_adjacency(
     config: &TopologyConfig,
@@ -572,40 +404,21 @@ fn build_router_from_adjacency(
     router
 }
 
-/// Calculate shortest path using BFS
-fn bfs_shortest_path(router: &GPRouter, start: &NodeId, end: &NodeId) -> Option<u32> {
-    let mut visited = HashSet::new();
-    let mut queue = std::collections::VecDeque::new();
-    queue.push_back((start.clone(), 0));
-    visited.insert(start.clone());
-
-    while let Some((current, dist)) = queue.pop_front() {
-        if &current == end {
-            return Some(dist);
-        }
-
-        if let Some(node) = router.get_node(&current) {
-            for neighbor in &node.neighbors {
-                if !visited.contains(neighbor) {
-                    visited.insert(neighbor.clone());
-                    queue.push_back((neighbor.clone(), dist + 1));
-                }
-            }
-        }
-    }
-    None
-}
-
 /// Run routing experiments on a given topology
 fn run_experiment(config: &TopologyConfig) -> ExperimentResult {
     println!("Generating {} topology with {} nodes...", config.topology_type, config.num_nodes);
     let gen_start = Instant::now();
     
+    let n = config.num_nodes;
     let router = match config.topology_type {
-        TopologyType::BarabasiAlbert => generate_barabasi_albert(config),
-        TopologyType::WattsStrogatz => generate_watts_strogatz(config),
-        TopologyType::Grid => generate_grid(config),
-        TopologyType::Random => generate_random(config),
+        TopologyType::BarabasiAlbert => {
+            build_router_from_network(config, &barabasi_albert(n, config.ba_m.min(n - 1), config.seed))
+        }
+        TopologyType::WattsStrogatz => {
+            build_router_from_network(config, &watts_strogatz(n, config.ws_k.min(n - 1), config.ws_beta, config.seed))
+        }
+        TopologyType::Grid => build_router_from_network(config, &grid_rows(n, (n as f64).sqrt().ceil() as usize)),
+        TopologyType::Random => build_router_from_network(config, &erdos_renyi(n, config.random_p, config.seed)),
         TopologyType::RealWorld => generate_real_world(config),
     };
     
@@ -613,8 +426,7 @@ fn run_experiment(config: &TopologyConfig) -> ExperimentResult {
     println!("  Generated in {} ms", gen_time);
     println!("  Nodes: {}, Edges: {}", router.node_count(), router.edge_count());
 
-    let node_ids = router.node_ids();
-    let actual_nodes = node_ids.len();
+    let actual_nodes = router.node_count();
 
     // Dynamic TTL: scale with network size for better exploration
     // Use at least 10*N to ensure enough room for Pressure + Tree/TZ exploration
@@ -623,7 +435,9 @@ fn run_experiment(config: &TopologyConfig) -> ExperimentResult {
     println!("Running {} routing tests (TTL={})...", config.num_tests, effective_ttl);
     let test_start = Instant::now();
 
-    let mut rng = StdRng::seed_from_u64(config.seed + 1000);
+    let workload = Workload::Random { count: config.num_tests, seed: config.seed + 1000 };
+    let batch_config = BatchConfig { max_ttl: Some(effective_ttl), ..BatchConfig::default() };
+    let batch = router.simulate_batch(&workload, &batch_config);
 
     let mut successful = 0;
     let mut failed = 0;
@@ -634,67 +448,30 @@ fn run_experiment(config: &TopologyConfig) -> ExperimentResult {
     let mut total_optimal_hops = 0u32;
     let mut ttl_failures = 0;
     let mut no_path_failures = 0;
-    let mut stretch_samples: Vec<f64> = Vec::with_capacity(config.num_tests);
-    let mut max_stretch = 0.0f64;
-
-    for _ in 0..config.num_tests {
-        let src_idx = rng.gen_range(0..actual_nodes);
-        let mut dst_idx = rng.gen_range(0..actual_nodes);
-        while dst_idx == src_idx {
-            dst_idx = rng.gen_range(0..actual_nodes);
-        }
 
-        let source = &node_ids[src_idx];
-        let dest = &node_ids[dst_idx];
-
-        if let Some(dest_node) = router.get_node(dest) {
-            let result = router.simulate_delivery(
-                source,
-                dest,
-                dest_node.coord.point,
-                effective_ttl,
-            );
-
-            if result.success {
-                successful += 1;
-                total_hops += result.hops;
-                gravity_hops += result.gravity_hops;
-                pressure_hops += result.pressure_hops;
-                tree_hops += result.tree_hops;
-
-                if let Some(opt) = bfs_shortest_path(&router, source, dest) {
-                    total_optimal_hops += opt;
-                    if opt > 0 {
-                        let stretch = result.hops as f64 / opt as f64;
-                        stretch_samples.push(stretch);
-                        if stretch > max_stretch {
-                            max_stretch = stretch;
-                        }
-                    }
-                }
-            } else {
-                failed += 1;
-                if let Some(ref reason) = result.failure_reason {
-                    if reason.contains("TTL") {
-                        ttl_failures += 1;
-                    } else {
-                        no_path_failures += 1;
-                    }
-                }
-            }
+    for pair in &batch.pairs {
+        let result = &pair.delivery;
+        if result.success {
+            successful += 1;
+            total_hops += result.hops;
+            gravity_hops += result.gravity_hops;
+            pressure_hops += result.pressure_hops;
+            tree_hops += result.tree_hops;
+            total_optimal_hops += pair.optimal_hops.unwrap_or(0);
         } else {
             failed += 1;
-            no_path_failures += 1;
+            match result.failure_reason.as_deref() {
+                Some(reason) if reason.contains("TTL") => ttl_failures += 1,
+                Some(_) => no_path_failures += 1,
+                None => {}
+            }
         }
     }
+    let stretch_samples: Vec<f64> = batch.pairs.iter().filter_map(|p| p.stretch()).collect();
 
     let elapsed = test_start.elapsed().as_millis();
-    
-    let avg_hops = if successful > 0 {
-        total_hops as f64 / successful as f64
-    } else {
-        0.0
-    };
+    let summary = batch.summary;
+    let avg_hops = summary.avg_hops;
 
     let avg_optimal_hops = if successful > 0 {
         total_optimal_hops as f64 / successful as f64
@@ -702,11 +479,8 @@ fn run_experiment(config: &TopologyConfig) -> ExperimentResult {
         0.0
     };
 
-    let stretch_ratio = if total_optimal_hops > 0 {
-        total_hops as f64 / total_optimal_hops as f64
-    } else {
-        0.0
-    };
+    let stretch_ratio = summary.stretch;
+    let max_stretch = summary.max_stretch;
     let (p95_stretch, p99_stretch) = stretch_percentiles(&stretch_samples);
 
     ExperimentResult {
@@ -2,8 +2,10 @@
 //!
 //! Varies the number of TZ landmarks to measure stretch vs memory tradeoffs.
 
+use drfe_r::bench::{barabasi_albert, Network};
 use drfe_r::coordinates::{NodeId, RoutingCoordinate};
 use drfe_r::greedy_embedding::GreedyEmbedding;
+use drfe_r::path_search::PathSearch;
 use drfe_r::routing::{GPRouter, RoutingNode};
 use drfe_r::tz_routing::{TZConfig, TZRoutingTable};
 use drfe_r::PoincareDiskPoint;
@@ -98,65 +100,6 @@ struct SensitivityReport {
     summary: Vec<SensitivitySummary>,
 }
 
-fn generate_ba_network(
-    n: usize,
-    m: usize,
-    seed: u64,
-) -> (Vec<NodeId>, Vec<Vec<usize>>, HashMap<NodeId, Vec<NodeId>>) {
-    let mut rng = StdRng::seed_from_u64(seed);
-    let mut degrees: Vec<usize> = vec![0; n];
-    let mut adjacency_idx: Vec<Vec<usize>> = vec![Vec::new(); n];
-    let nodes: Vec<NodeId> = (0..n)
-        .map(|i| NodeId::new(format!("node_{}", i)))
-        .collect();
-
-    for i in 0..m.min(n) {
-        for j in (i + 1)..m.min(n) {
-            adjacency_idx[i].push(j);
-            adjacency_idx[j].push(i);
-            degrees[i] += 1;
-            degrees[j] += 1;
-        }
-    }
-
-    for i in m..n {
-        let total: usize = degrees.iter().take(i).sum();
-        if total == 0 {
-            continue;
-        }
-
-        let mut connected = HashSet::new();
-        while connected.len() < m.min(i) {
-            let r = rng.gen::<f64>() * total as f64;
-            let mut cumsum = 0.0;
-            for j in 0..i {
-                cumsum += degrees[j] as f64;
-                if cumsum >= r && !connected.contains(&j) {
-                    adjacency_idx[i].push(j);
-                    adjacency_idx[j].push(i);
-                    degrees[i] += 1;
-                    degrees[j] += 1;
-                    connected.insert(j);
-                    break;
-                }
-            }
-        }
-    }
-
-    let mut adjacency: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
-    for i in 0..n {
-        adjacency.insert(
-            nodes[i].clone(),
-            adjacency_idx[i]
-                .iter()
-                .map(|&j| nodes[j].clone())
-                .collect(),
-        );
-    }
-
-    (nodes, adjacency_idx, adjacency)
-}
-
 fn build_router_pie(
     nodes: &[NodeId],
     adjacency_idx: &[Vec<usize>],
@@ -200,28 +143,6 @@ fn build_router_pie(
     router
 }
 
-fn bfs_distance(router: &GPRouter, start: &NodeId, end: &NodeId) -> Option<u32> {
-    let mut visited = HashSet::new();
-    let mut queue = VecDeque::new();
-    queue.push_back((start.clone(), 0u32));
-    visited.insert(start.clone());
-
-    while let Some((cur, dist)) = queue.pop_front() {
-        if &cur == end {
-            return Some(dist);
-        }
-        if let Some(node) = router.get_node(&cur) {
-            for neighbor in &node.neighbors {
-                if !visited.contains(neighbor) {
-                    visited.insert(neighbor.clone());
-                    queue.push_back((neighbor.clone(), dist + 1));
-                }
-            }
-        }
-    }
-    None
-}
-
 fn try_gravity_only_limited(
     router: &GPRouter,
     src: &NodeId,
@@ -292,6 +213,7 @@ fn run_pie_tz_tests(
     let mut rng = StdRng::seed_from_u64(seed + 1000);
     let n = nodes.len();
     let max_gravity = n as u32;
+    let mut search = PathSearch::from_router(router);
 
     let mut successes = 0u32;
     let mut total_hops = 0u32;
@@ -326,7 +248,7 @@ fn run_pie_tz_tests(
             tz_hops_total += tz_hops;
             all_hops += hops;
 
-            if let Some(opt) = bfs_distance(router, &nodes[src], &nodes[dst]) {
+            if let Some(opt) = search.distance(&nodes[src], &nodes[dst]) {
                 total_optimal += opt;
                 if opt > 0 {
                     let s = hops as f64 / opt as f64;
@@ -454,6 +376,7 @@ fn tz_stretch_stats(
 ) -> StretchStats {
     let max_pairs = nodes.len().saturating_mul(nodes.len());
     let sample_count = num_samples.min(max_pairs);
+    let mut search = PathSearch::from_router(router);
     let mut stretches: Vec<f64> = Vec::new();
     let mut max_stretch = 0.0;
     let mut violations = 0;
@@ -471,7 +394,7 @@ fn tz_stretch_stats(
                 continue;
             }
             let tz_len = path.len() as u32 - 1;
-            if let Some(opt) = bfs_distance(router, source, destination) {
+            if let Some(opt) = search.distance(source, destination) {
                 if opt > 0 {
                     let stretch = tz_len as f64 / opt as f64;
                     stretches.push(stretch);
@@ -661,8 +584,8 @@ fn main() {
             println!("Seed {}", current_seed);
         }
 
-        let (nodes, adjacency_idx, adjacency) =
-            generate_ba_network(num_nodes, 3, *current_seed);
+        let Network { nodes, neighbors: adjacency_idx, adjacency } =
+            barabasi_albert(num_nodes, 3, *current_seed);
         let router = build_router_pie(&nodes, &adjacency_idx, &adjacency);
 
         let mut results = Vec::new();
@@ -0,0 +1,433 @@
+//! Overlay-Wide Broadcast
+//!
+//! Delivers a payload to every node of the overlay. Each node forwards a
+//! broadcast it has not seen before to its neighbors, except the one it
+//! came from, until the message has travelled `hop_limit` hops. Recently
+//! seen message IDs are remembered, so loops in the overlay cost one
+//! duplicate per link rather than an unbounded storm.
+//!
+//! Flooding sends every message over every link. `Tree` mode bounds that
+//! with epidemic broadcast trees (Plumtree): a node that receives a
+//! duplicate prunes the link it came over, so after a few broadcasts the
+//! links still pushing payloads form a spanning tree. Pruned links carry
+//! only an `IHave` announcement. A node that hears of a message but does
+//! not receive it within `graft_timeout_ms` grafts the announcing link back
+//! onto the tree and gets the message resent, which repairs the tree after
+//! churn or after concurrent broadcasts pruned too much.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::memory::{self, MemoryUsage};
+
+/// How broadcasts are disseminated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BroadcastMode {
+    /// Push every message over every link
+    #[default]
+    Flood,
+    /// Push over spanning tree links and announce over the others
+    Tree,
+}
+
+/// Broadcast settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BroadcastConfig {
+    pub mode: BroadcastMode,
+    /// Hops a broadcast travels from its origin
+    pub hop_limit: u32,
+    /// Recently seen message IDs kept for duplicate suppression
+    pub dedup_capacity: usize,
+    /// Undelivered broadcasts kept for the application
+    pub inbox_capacity: usize,
+    /// Recent messages kept to answer grafts
+    pub cache_capacity: usize,
+    /// Wait for an announced message before grafting
+    pub graft_timeout_ms: u64,
+}
+
+impl Default for BroadcastConfig {
+    fn default() -> Self {
+        Self {
+            mode: BroadcastMode::Flood,
+            hop_limit: 16,
+            dedup_capacity: 4096,
+            inbox_capacity: 1024,
+            cache_capacity: 256,
+            graft_timeout_ms: 500,
+        }
+    }
+}
+
+impl BroadcastConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.hop_limit == 0 {
+            return Err("Broadcast hop_limit must be positive".to_string());
+        }
+        if self.dedup_capacity == 0 || self.inbox_capacity == 0 {
+            return Err("Broadcast dedup_capacity and inbox_capacity must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A broadcast payload
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BroadcastMessage {
+    pub message_id: String,
+    pub origin: NodeId,
+    /// Hops travelled when received
+    pub hops: u32,
+    /// Hop limit chosen by the origin
+    pub hop_limit: u32,
+    pub payload: Vec<u8>,
+}
+
+/// Broadcast messages exchanged between neighbors
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BroadcastWire {
+    Message(BroadcastMessage),
+    /// The sender got a duplicate over this link; announce instead of pushing
+    Prune,
+    /// The sender has a message, announced over a pruned link
+    IHave { message_id: String },
+    /// The sender missed an announced message; resend it and push again
+    Graft { message_id: String },
+}
+
+/// Broadcast counters since startup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BroadcastStats {
+    pub originated: u64,
+    pub delivered: u64,
+    /// Copies dropped as already seen
+    pub duplicates: u64,
+    /// Payload copies sent to neighbors
+    pub forwarded: u64,
+    /// Announcements sent over pruned links
+    pub announced: u64,
+    /// Links pruned after a duplicate
+    pub pruned: u64,
+    /// Links grafted back for a missing message
+    pub grafted: u64,
+}
+
+/// Outgoing broadcast messages as (neighbor, message)
+pub type BroadcastActions = Vec<(NodeId, BroadcastWire)>;
+
+/// Duplicate suppression, tree and delivery state of one node
+#[derive(Debug, Clone, Default)]
+pub struct BroadcastManager {
+    config: BroadcastConfig,
+    seen: HashSet<String>,
+    seen_order: VecDeque<String>,
+    inbox: VecDeque<BroadcastMessage>,
+    /// Recent messages, for grafts
+    cache: VecDeque<BroadcastMessage>,
+    /// Links that only get announcements
+    lazy: HashSet<NodeId>,
+    /// Announced but unseen messages: ID -> (announcer, first announced)
+    missing: HashMap<String, (NodeId, u64)>,
+    stats: BroadcastStats,
+}
+
+impl BroadcastManager {
+    pub fn new(config: BroadcastConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    pub fn config(&self) -> &BroadcastConfig {
+        &self.config
+    }
+
+    /// Replace the settings; switching to flooding forgets the tree
+    pub fn set_config(&mut self, config: BroadcastConfig) {
+        self.config = config;
+        if self.config.mode == BroadcastMode::Flood {
+            self.lazy.clear();
+            self.missing.clear();
+        }
+        while self.seen_order.len() > self.config.dedup_capacity {
+            if let Some(old) = self.seen_order.pop_front() {
+                self.seen.remove(&old);
+            }
+        }
+        while self.inbox.len() > self.config.inbox_capacity {
+            self.inbox.pop_front();
+        }
+        while self.cache.len() > self.config.cache_capacity {
+            self.cache.pop_front();
+        }
+    }
+
+    pub fn stats(&self) -> BroadcastStats {
+        self.stats
+    }
+
+    /// Memory held by the seen message IDs
+    pub fn seen_memory(&self) -> MemoryUsage {
+        memory::seen_cache_usage(&self.seen, &self.seen_order)
+    }
+
+    /// Links currently pruned from the tree
+    pub fn lazy_links(&self) -> Vec<NodeId> {
+        let mut links: Vec<NodeId> = self.lazy.iter().cloned().collect();
+        links.sort_by(|a, b| a.0.cmp(&b.0));
+        links
+    }
+
+    /// Start a broadcast from this node
+    pub fn originate(
+        &mut self,
+        origin: &NodeId,
+        message_id: String,
+        payload: Vec<u8>,
+        neighbors: &[NodeId],
+    ) -> BroadcastActions {
+        self.mark_seen(&message_id);
+        self.stats.originated += 1;
+        let message = BroadcastMessage {
+            message_id,
+            origin: origin.clone(),
+            hops: 0,
+            hop_limit: self.config.hop_limit,
+            payload,
+        };
+        self.remember(message.clone());
+        self.fan_out(None, message, neighbors)
+    }
+
+    /// Handle a message received from neighbor `from`
+    ///
+    /// `neighbors` are the current usable neighbors; `now_ms` times grafts.
+    pub fn on_receive(
+        &mut self,
+        from: &NodeId,
+        wire: BroadcastWire,
+        neighbors: &[NodeId],
+        now_ms: u64,
+    ) -> BroadcastActions {
+        match wire {
+            BroadcastWire::Message(message) => self.on_message(from, message, neighbors),
+            BroadcastWire::Prune => {
+                if self.config.mode == BroadcastMode::Tree {
+                    self.lazy.insert(from.clone());
+                }
+                Vec::new()
+            }
+            BroadcastWire::IHave { message_id } => {
+                if !self.seen.contains(&message_id) {
+                    self.missing.entry(message_id).or_insert((from.clone(), now_ms));
+                }
+                Vec::new()
+            }
+            BroadcastWire::Graft { message_id } => {
+                self.lazy.remove(from);
+                self.cache
+                    .iter()
+                    .find(|m| m.message_id == message_id)
+                    .map(|m| (from.clone(), BroadcastWire::Message(m.clone())))
+                    .into_iter()
+                    .collect()
+            }
+        }
+    }
+
+    /// Graft links whose announced messages have not arrived in time
+    pub fn poll_grafts(&mut self, now_ms: u64) -> BroadcastActions {
+        let timeout = self.config.graft_timeout_ms;
+        let overdue: Vec<String> = self
+            .missing
+            .iter()
+            .filter(|(_, (_, since))| now_ms.saturating_sub(*since) >= timeout)
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        let mut actions = Vec::new();
+        for message_id in overdue {
+            if let Some((announcer, _)) = self.missing.remove(&message_id) {
+                self.lazy.remove(&announcer);
+                self.stats.grafted += 1;
+                actions.push((announcer, BroadcastWire::Graft { message_id }));
+            }
+        }
+        actions
+    }
+
+    /// Take the broadcasts delivered so far, oldest first
+    pub fn take_messages(&mut self) -> Vec<BroadcastMessage> {
+        self.inbox.drain(..).collect()
+    }
+
+    fn on_message(&mut self, from: &NodeId, mut message: BroadcastMessage, neighbors: &[NodeId]) -> BroadcastActions {
+        if !self.mark_seen(&message.message_id) {
+            self.stats.duplicates += 1;
+            if self.config.mode == BroadcastMode::Tree && self.lazy.insert(from.clone()) {
+                self.stats.pruned += 1;
+                return vec![(from.clone(), BroadcastWire::Prune)];
+            }
+            return Vec::new();
+        }
+        self.missing.remove(&message.message_id);
+        message.hops += 1;
+        self.stats.delivered += 1;
+        if self.inbox.len() >= self.config.inbox_capacity {
+            self.inbox.pop_front();
+        }
+        self.inbox.push_back(message.clone());
+        self.remember(message.clone());
+
+        // Honor the smaller of the origin's limit and our own
+        if message.hops >= message.hop_limit.min(self.config.hop_limit) {
+            return Vec::new();
+        }
+        self.fan_out(Some(from), message, neighbors)
+    }
+
+    fn fan_out(&mut self, from: Option<&NodeId>, message: BroadcastMessage, neighbors: &[NodeId]) -> BroadcastActions {
+        // Departed neighbors rejoin as tree links if they come back
+        self.lazy.retain(|id| neighbors.contains(id));
+
+        let mut actions = BroadcastActions::new();
+        for hop in neighbors.iter().filter(|hop| Some(*hop) != from && **hop != message.origin) {
+            if self.config.mode == BroadcastMode::Tree && self.lazy.contains(hop) {
+                self.stats.announced += 1;
+                actions.push((hop.clone(), BroadcastWire::IHave { message_id: message.message_id.clone() }));
+            } else {
+                self.stats.forwarded += 1;
+                actions.push((hop.clone(), BroadcastWire::Message(message.clone())));
+            }
+        }
+        actions
+    }
+
+    fn remember(&mut self, message: BroadcastMessage) {
+        if self.config.mode != BroadcastMode::Tree || self.config.cache_capacity == 0 {
+            return;
+        }
+        if self.cache.len() >= self.config.cache_capacity {
+            self.cache.pop_front();
+        }
+        self.cache.push_back(message);
+    }
+
+    /// Remember a message ID; false if it was already seen
+    fn mark_seen(&mut self, message_id: &str) -> bool {
+        if !self.seen.insert(message_id.to_string()) {
+            return false;
+        }
+        self.seen_order.push_back(message_id.to_string());
+        if self.seen_order.len() > self.config.dedup_capacity {
+            if let Some(old) = self.seen_order.pop_front() {
+                self.seen.remove(&old);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(names: &[&str]) -> Vec<NodeId> {
+        names.iter().map(|n| NodeId::new(*n)).collect()
+    }
+
+    fn message(id: &str, hops: u32, hop_limit: u32) -> BroadcastWire {
+        BroadcastWire::Message(BroadcastMessage {
+            message_id: id.to_string(),
+            origin: NodeId::new("o"),
+            hops,
+            hop_limit,
+            payload: b"x".to_vec(),
+        })
+    }
+
+    fn targets(actions: &BroadcastActions) -> Vec<&str> {
+        actions.iter().map(|(id, _)| id.0.as_str()).collect()
+    }
+
+    #[test]
+    fn test_flood_suppresses_duplicates_and_skips_sender() {
+        let mut manager = BroadcastManager::new(BroadcastConfig::default());
+        let neighbors = ids(&["a", "b", "c"]);
+
+        let actions = manager.on_receive(&NodeId::new("a"), message("m1", 0, 16), &neighbors, 0);
+        assert_eq!(targets(&actions), vec!["b", "c"]);
+        assert!(actions
+            .iter()
+            .all(|(_, wire)| matches!(wire, BroadcastWire::Message(m) if m.hops == 1)));
+
+        assert!(manager.on_receive(&NodeId::new("b"), message("m1", 0, 16), &neighbors, 0).is_empty());
+        let stats = manager.stats();
+        assert_eq!((stats.delivered, stats.duplicates, stats.forwarded), (1, 1, 2));
+        assert_eq!(manager.take_messages().len(), 1);
+        assert!(manager.take_messages().is_empty());
+    }
+
+    #[test]
+    fn test_hop_limit_stops_forwarding() {
+        let mut manager = BroadcastManager::new(BroadcastConfig::default());
+        let actions = manager.on_receive(&NodeId::new("a"), message("m1", 1, 2), &ids(&["a", "b"]), 0);
+        assert!(actions.is_empty());
+        assert_eq!(manager.stats().delivered, 1);
+    }
+
+    #[test]
+    fn test_tree_prunes_duplicate_links_and_announces_over_them() {
+        let config = BroadcastConfig { mode: BroadcastMode::Tree, ..BroadcastConfig::default() };
+        let mut manager = BroadcastManager::new(config);
+        let neighbors = ids(&["a", "b", "c"]);
+
+        manager.on_receive(&NodeId::new("a"), message("m1", 0, 16), &neighbors, 0);
+        let actions = manager.on_receive(&NodeId::new("b"), message("m1", 0, 16), &neighbors, 0);
+        assert_eq!(actions, vec![(NodeId::new("b"), BroadcastWire::Prune)]);
+        assert_eq!(manager.lazy_links(), ids(&["b"]));
+
+        let actions = manager.originate(&NodeId::new("s"), "m2".to_string(), Vec::new(), &neighbors);
+        assert!(matches!(actions[0], (ref id, BroadcastWire::Message(_)) if id.0 == "a"));
+        assert!(matches!(actions[1], (ref id, BroadcastWire::IHave { .. }) if id.0 == "b"));
+        assert!(matches!(actions[2], (ref id, BroadcastWire::Message(_)) if id.0 == "c"));
+    }
+
+    #[test]
+    fn test_missing_announcement_grafts_and_resends() {
+        let config = BroadcastConfig { mode: BroadcastMode::Tree, ..BroadcastConfig::default() };
+        let mut receiver = BroadcastManager::new(config.clone());
+        let mut announcer = BroadcastManager::new(config);
+        let (r, a) = (NodeId::new("r"), NodeId::new("a"));
+
+        announcer.on_receive(&r, BroadcastWire::Prune, std::slice::from_ref(&r), 0);
+        let actions = announcer.originate(&a, "m1".to_string(), b"late".to_vec(), std::slice::from_ref(&r));
+        assert_eq!(actions, vec![(r.clone(), BroadcastWire::IHave { message_id: "m1".to_string() })]);
+
+        receiver.on_receive(&a, actions[0].1.clone(), std::slice::from_ref(&a), 1_000);
+        assert!(receiver.poll_grafts(1_100).is_empty());
+        let grafts = receiver.poll_grafts(1_600);
+        assert_eq!(grafts, vec![(a.clone(), BroadcastWire::Graft { message_id: "m1".to_string() })]);
+
+        let resent = announcer.on_receive(&r, grafts[0].1.clone(), std::slice::from_ref(&r), 1_600);
+        assert!(announcer.lazy_links().is_empty());
+        receiver.on_receive(&a, resent[0].1.clone(), std::slice::from_ref(&a), 1_700);
+        assert_eq!(receiver.take_messages()[0].payload, b"late");
+    }
+
+    #[test]
+    fn test_seen_ids_are_bounded() {
+        let config = BroadcastConfig { dedup_capacity: 2, ..BroadcastConfig::default() };
+        let mut manager = BroadcastManager::new(config);
+        for id in ["m1", "m2", "m3"] {
+            manager.originate(&NodeId::new("o"), id.to_string(), Vec::new(), &[]);
+        }
+        assert!(manager.mark_seen("m1"));
+        assert!(!manager.mark_seen("m3"));
+    }
+}
@@ -0,0 +1,380 @@
+//! Coordinate Certificates
+//!
+//! Anyone can compute a node's anchor coordinate from its ID, but its
+//! routing coordinate is only known from what the node advertises. A
+//! coordinate certificate binds a node's current coordinate and epoch to its
+//! identity key, optionally counter-signed by neighbors that observed the
+//! same coordinate. Certificates travel with rendezvous registrations and
+//! through gossip; verifiers check them before trusting an advertised
+//! coordinate in the routing path.
+//!
+//! The subject and its endorsers sign the same certificate body under
+//! different domain tags, so an endorsement can never stand in for the
+//! subject's own signature. Times are in the verifier's clock units.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::network::SerializablePoincareDiskPoint;
+use crate::PoincareDiskPoint;
+
+const SUBJECT_TAG: &str = "drfe-r/coordinate-certificate";
+const ENDORSEMENT_TAG: &str = "drfe-r/coordinate-endorsement";
+
+/// Signed contents of a certificate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertificateBody {
+    pub node_id: NodeId,
+    pub coord: SerializablePoincareDiskPoint,
+    /// Coordinate version; newer epochs supersede older ones
+    pub epoch: u64,
+    pub issued_at: u64,
+    pub expires_at: u64,
+}
+
+/// A neighbor's counter-signature over a certificate body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Endorsement {
+    pub endorser: NodeId,
+    pub signature: Vec<u8>,
+}
+
+/// A node's coordinate attested by its identity key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoordinateCertificate {
+    pub body: CertificateBody,
+    /// Subject's Ed25519 signature over the body
+    pub signature: Option<Vec<u8>>,
+    pub endorsements: Vec<Endorsement>,
+}
+
+impl CoordinateCertificate {
+    /// Unsigned certificate valid from `issued_at` for `validity`
+    pub fn new(node_id: NodeId, coord: PoincareDiskPoint, epoch: u64, issued_at: u64, validity: u64) -> Self {
+        Self {
+            body: CertificateBody {
+                node_id,
+                coord: SerializablePoincareDiskPoint::from(coord),
+                epoch,
+                issued_at,
+                expires_at: issued_at.saturating_add(validity),
+            },
+            signature: None,
+            endorsements: Vec::new(),
+        }
+    }
+
+    pub fn node_id(&self) -> &NodeId {
+        &self.body.node_id
+    }
+
+    pub fn coord(&self) -> PoincareDiskPoint {
+        PoincareDiskPoint::from(self.body.coord)
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.body.epoch
+    }
+
+    /// Sign as the subject with a 32-byte Ed25519 private key
+    pub fn sign(&mut self, private_key: &[u8]) -> Result<(), String> {
+        self.signature = Some(sign_tagged(SUBJECT_TAG, &self.body, private_key)?);
+        Ok(())
+    }
+
+    /// Verify the subject's signature with its 32-byte public key
+    pub fn verify_signature(&self, public_key: &[u8]) -> bool {
+        self.signature
+            .as_ref()
+            .is_some_and(|sig| verify_tagged(SUBJECT_TAG, &self.body, sig, public_key))
+    }
+
+    /// Counter-sign as a neighbor
+    ///
+    /// Endorsers should first check the coordinate against what they observe
+    /// with `should_endorse`. A repeated endorsement replaces the earlier one.
+    pub fn endorse(&mut self, endorser: NodeId, private_key: &[u8]) -> Result<(), String> {
+        if endorser == self.body.node_id {
+            return Err("A node cannot endorse its own certificate".to_string());
+        }
+        let signature = sign_tagged(ENDORSEMENT_TAG, &self.body, private_key)?;
+        self.endorsements.retain(|e| e.endorser != endorser);
+        self.endorsements.push(Endorsement { endorser, signature });
+        Ok(())
+    }
+
+    /// Number of distinct endorsers whose signatures verify
+    pub fn valid_endorsements(&self, keys: &HashMap<NodeId, Vec<u8>>) -> usize {
+        let mut endorsers = HashSet::new();
+        for e in &self.endorsements {
+            if e.endorser == self.body.node_id {
+                continue;
+            }
+            let Some(key) = keys.get(&e.endorser) else {
+                continue;
+            };
+            if verify_tagged(ENDORSEMENT_TAG, &self.body, &e.signature, key) {
+                endorsers.insert(&e.endorser);
+            }
+        }
+        endorsers.len()
+    }
+
+    /// Whether the certificate attests `point` within `tolerance` (hyperbolic distance)
+    pub fn matches(&self, point: &PoincareDiskPoint, tolerance: f64) -> bool {
+        self.coord().hyperbolic_distance(point) <= tolerance
+    }
+}
+
+/// Whether a neighbor that observes `observed` should endorse `cert`
+pub fn should_endorse(cert: &CoordinateCertificate, observed: &PoincareDiskPoint, tolerance: f64) -> bool {
+    cert.matches(observed, tolerance)
+}
+
+fn sign_tagged(tag: &str, body: &CertificateBody, private_key: &[u8]) -> Result<Vec<u8>, String> {
+    use ed25519_dalek::{Signer, SigningKey};
+
+    let key: [u8; 32] = private_key
+        .try_into()
+        .map_err(|_| format!("Invalid private key length: {} (expected 32 bytes)", private_key.len()))?;
+    let message = rmp_serde::to_vec(&(tag, body))
+        .map_err(|e| format!("Failed to serialize certificate for signing: {}", e))?;
+    Ok(SigningKey::from_bytes(&key).sign(&message).to_bytes().to_vec())
+}
+
+fn verify_tagged(tag: &str, body: &CertificateBody, signature: &[u8], public_key: &[u8]) -> bool {
+    use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+    let Ok(key) = <[u8; 32]>::try_from(public_key) else {
+        return false;
+    };
+    let (Ok(key), Ok(signature), Ok(message)) = (
+        VerifyingKey::from_bytes(&key),
+        Signature::from_slice(signature),
+        rmp_serde::to_vec(&(tag, body)),
+    ) else {
+        return false;
+    };
+    key.verify(&message, &signature).is_ok()
+}
+
+/// Checks certificates against known identity keys
+#[derive(Debug, Clone)]
+pub struct CertificateVerifier {
+    /// Identity public keys by node
+    pub keys: HashMap<NodeId, Vec<u8>>,
+    /// Required endorsements from distinct neighbors
+    pub min_endorsements: usize,
+    /// Accepted distance between certified and advertised coordinates
+    pub coord_tolerance: f64,
+    /// Accepted clock skew for certificates issued in the future
+    pub max_clock_skew: u64,
+}
+
+impl Default for CertificateVerifier {
+    fn default() -> Self {
+        Self {
+            keys: HashMap::new(),
+            min_endorsements: 0,
+            coord_tolerance: 1e-6,
+            max_clock_skew: 5_000,
+        }
+    }
+}
+
+impl CertificateVerifier {
+    pub fn new(min_endorsements: usize) -> Self {
+        Self {
+            min_endorsements,
+            ..Self::default()
+        }
+    }
+
+    /// Register a node's identity key
+    pub fn add_key(&mut self, node_id: NodeId, public_key: Vec<u8>) {
+        self.keys.insert(node_id, public_key);
+    }
+
+    /// Identity key registered for a node
+    pub fn key(&self, node_id: &NodeId) -> Option<&[u8]> {
+        self.keys.get(node_id).map(Vec::as_slice)
+    }
+
+    /// Check signature, validity window and endorsements
+    pub fn verify(&self, cert: &CoordinateCertificate, now: u64) -> Result<(), String> {
+        let subject = cert.node_id();
+        let key = self
+            .keys
+            .get(subject)
+            .ok_or_else(|| format!("No identity key for {}", subject))?;
+        if !cert.verify_signature(key) {
+            return Err(format!("Invalid certificate signature for {}", subject));
+        }
+        if now >= cert.body.expires_at {
+            return Err(format!("Certificate for {} expired", subject));
+        }
+        if cert.body.issued_at > now.saturating_add(self.max_clock_skew) {
+            return Err(format!("Certificate for {} issued in the future", subject));
+        }
+        let endorsements = cert.valid_endorsements(&self.keys);
+        if endorsements < self.min_endorsements {
+            return Err(format!(
+                "Certificate for {} has {} of {} required endorsements",
+                subject, endorsements, self.min_endorsements
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check that a certificate is valid and attests `advertised`
+    ///
+    /// Use before trusting a destination's advertised coordinate.
+    pub fn verify_coordinate(
+        &self,
+        cert: &CoordinateCertificate,
+        advertised: &PoincareDiskPoint,
+        now: u64,
+    ) -> Result<(), String> {
+        self.verify(cert, now)?;
+        if !cert.matches(advertised, self.coord_tolerance) {
+            return Err(format!("Advertised coordinate of {} does not match its certificate", cert.node_id()));
+        }
+        Ok(())
+    }
+}
+
+/// Latest verified certificate per node, exchanged by gossip
+#[derive(Debug, Clone, Default)]
+pub struct CertificateStore {
+    certs: HashMap<NodeId, CoordinateCertificate>,
+}
+
+impl CertificateStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Verify and store a certificate
+    ///
+    /// Returns true if it replaced an older epoch (or was new), false if an
+    /// equal or newer epoch is already stored.
+    pub fn insert(&mut self, cert: CoordinateCertificate, verifier: &CertificateVerifier, now: u64) -> Result<bool, String> {
+        verifier.verify(&cert, now)?;
+        if self.certs.get(cert.node_id()).is_some_and(|c| c.epoch() >= cert.epoch()) {
+            return Ok(false);
+        }
+        self.certs.insert(cert.node_id().clone(), cert);
+        Ok(true)
+    }
+
+    pub fn get(&self, node_id: &NodeId) -> Option<&CoordinateCertificate> {
+        self.certs.get(node_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.certs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.certs.is_empty()
+    }
+
+    /// (node, epoch) of every stored certificate, sent to gossip peers
+    pub fn digest(&self) -> Vec<(NodeId, u64)> {
+        self.certs.iter().map(|(id, c)| (id.clone(), c.epoch())).collect()
+    }
+
+    /// Certificates a peer with `digest` is missing or has older epochs of
+    pub fn updates_for(&self, digest: &[(NodeId, u64)]) -> Vec<CoordinateCertificate> {
+        let known: HashMap<&NodeId, u64> = digest.iter().map(|(id, epoch)| (id, *epoch)).collect();
+        self.certs
+            .values()
+            .filter(|c| known.get(c.node_id()).is_none_or(|&epoch| epoch < c.epoch()))
+            .cloned()
+            .collect()
+    }
+
+    /// Drop expired certificates, returning how many were removed
+    pub fn expire(&mut self, now: u64) -> usize {
+        let before = self.certs.len();
+        self.certs.retain(|_, c| now < c.body.expires_at);
+        before - self.certs.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+
+    fn keypair(seed: u8) -> ([u8; 32], Vec<u8>) {
+        let private = [seed; 32];
+        let public = SigningKey::from_bytes(&private).verifying_key().to_bytes().to_vec();
+        (private, public)
+    }
+
+    #[test]
+    fn test_signed_and_endorsed_certificate() {
+        let (alice_sk, alice_pk) = keypair(1);
+        let (bob_sk, bob_pk) = keypair(2);
+        let (carol_sk, _) = keypair(3);
+        let point = PoincareDiskPoint::new(0.3, -0.2).unwrap();
+
+        let mut verifier = CertificateVerifier::new(1);
+        verifier.add_key(NodeId::new("alice"), alice_pk);
+        verifier.add_key(NodeId::new("bob"), bob_pk);
+
+        let mut cert = CoordinateCertificate::new(NodeId::new("alice"), point, 3, 100, 1000);
+        cert.sign(&alice_sk).unwrap();
+        assert!(verifier.verify(&cert, 200).unwrap_err().contains("0 of 1"));
+
+        // Unknown endorsers and self-endorsements do not count
+        cert.endorse(NodeId::new("carol"), &carol_sk).unwrap();
+        assert!(cert.endorse(NodeId::new("alice"), &alice_sk).is_err());
+        assert!(verifier.verify(&cert, 200).is_err());
+
+        assert!(should_endorse(&cert, &point, 1e-6));
+        cert.endorse(NodeId::new("bob"), &bob_sk).unwrap();
+        assert!(verifier.verify_coordinate(&cert, &point, 200).is_ok());
+        assert!(verifier.verify(&cert, 1100).is_err());
+
+        let elsewhere = PoincareDiskPoint::new(0.1, 0.1).unwrap();
+        assert!(verifier.verify_coordinate(&cert, &elsewhere, 200).is_err());
+
+        // Rewriting the coordinate invalidates both signatures
+        cert.body.coord = SerializablePoincareDiskPoint::from(elsewhere);
+        assert!(verifier.verify(&cert, 200).is_err());
+        assert_eq!(cert.valid_endorsements(&verifier.keys), 0);
+    }
+
+    #[test]
+    fn test_store_keeps_newest_epoch_and_gossips_updates() {
+        let (sk, pk) = keypair(7);
+        let mut verifier = CertificateVerifier::default();
+        verifier.add_key(NodeId::new("n"), pk);
+        let point = PoincareDiskPoint::new(0.2, 0.2).unwrap();
+        let cert = |epoch| {
+            let mut c = CoordinateCertificate::new(NodeId::new("n"), point, epoch, 0, 100);
+            c.sign(&sk).unwrap();
+            c
+        };
+
+        let mut store = CertificateStore::new();
+        assert_eq!(store.insert(cert(2), &verifier, 10), Ok(true));
+        assert_eq!(store.insert(cert(1), &verifier, 10), Ok(false));
+        assert!(store.insert(CoordinateCertificate::new(NodeId::new("n"), point, 5, 0, 100), &verifier, 10).is_err());
+        assert_eq!(store.get(&NodeId::new("n")).unwrap().epoch(), 2);
+
+        let mut peer = CertificateStore::new();
+        assert_eq!(store.updates_for(&peer.digest()).len(), 1);
+        for update in store.updates_for(&peer.digest()) {
+            peer.insert(update, &verifier, 10).unwrap();
+        }
+        assert!(store.updates_for(&peer.digest()).is_empty());
+
+        assert_eq!(store.expire(100), 1);
+        assert!(store.is_empty());
+    }
+}
@@ -6,11 +6,21 @@
 //! - Delays and jitter
 //! - Clock drift
 //! - Node crashes
+//!
+//! Experiments inject a fault on the links touching a few target nodes for a
+//! bounded time. They are defined in the node configuration, so they can be
+//! loaded through the API or a config file reload, and are checked against
+//! blast-radius limits before they are accepted. While one runs, its abort
+//! conditions are evaluated against the node's telemetry; when one trips, the
+//! fault is rolled back at once and the remaining experiments are cancelled.
 
 use std::collections::HashSet;
 use std::sync::RwLock;
 
+use serde::{Deserialize, Serialize};
+
 use crate::coordinates::NodeId;
+use crate::telemetry::MetricSample;
 
 /// Random number generator for chaos
 fn random_f64() -> f64 {
@@ -40,6 +50,23 @@ pub struct ChaosEngine {
     pub enabled: bool,
     /// Statistics
     stats: RwLock<ChaosStats>,
+    /// Fault injected by a running experiment
+    scoped: RwLock<Option<ScopedFault>>,
+}
+
+/// Fault on the links to and from a set of nodes, applied whether or not
+/// chaos injection is otherwise enabled
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScopedFault {
+    pub targets: HashSet<NodeId>,
+    pub drop_rate: f64,
+    pub delay_range_ms: (u64, u64),
+}
+
+impl ScopedFault {
+    fn covers(&self, from: &NodeId, to: &NodeId) -> bool {
+        self.targets.contains(from) || self.targets.contains(to)
+    }
 }
 
 /// Chaos injection statistics
@@ -63,6 +90,7 @@ impl ChaosEngine {
             crashed_nodes: RwLock::new(HashSet::new()),
             enabled: false,
             stats: RwLock::new(ChaosStats::default()),
+            scoped: RwLock::new(None),
         }
     }
 
@@ -77,16 +105,19 @@ impl ChaosEngine {
             crashed_nodes: RwLock::new(HashSet::new()),
             enabled: true,
             stats: RwLock::new(ChaosStats::default()),
+            scoped: RwLock::new(None),
         }
     }
 
     /// Should this packet be dropped?
-    pub fn should_drop_packet(&self, _from: &NodeId, _to: &NodeId) -> bool {
-        if !self.enabled {
-            return false;
-        }
-        
-        if random_f64() < self.packet_drop_rate {
+    pub fn should_drop_packet(&self, from: &NodeId, to: &NodeId) -> bool {
+        let rate = match self.scoped.read().unwrap().as_ref() {
+            Some(fault) if fault.covers(from, to) => fault.drop_rate,
+            _ if self.enabled => self.packet_drop_rate,
+            _ => return false,
+        };
+
+        if random_f64() < rate {
             self.stats.write().unwrap().packets_dropped += 1;
             return true;
         }
@@ -94,13 +125,17 @@ impl ChaosEngine {
     }
 
     /// Get delay for a packet in milliseconds
-    pub fn get_delay_ms(&self, _from: &NodeId, _to: &NodeId) -> u64 {
-        if !self.enabled || self.delay_range_ms.1 == 0 {
+    pub fn get_delay_ms(&self, from: &NodeId, to: &NodeId) -> u64 {
+        let (min, max) = match self.scoped.read().unwrap().as_ref() {
+            Some(fault) if fault.covers(from, to) => fault.delay_range_ms,
+            _ if self.enabled => self.delay_range_ms,
+            _ => return 0,
+        };
+        if max == 0 {
             return 0;
         }
-        
-        let range = self.delay_range_ms.1 - self.delay_range_ms.0;
-        let delay = self.delay_range_ms.0 + (random_f64() * range as f64) as u64;
+
+        let delay = min + (random_f64() * (max - min) as f64) as u64;
         
         if delay > 0 {
             self.stats.write().unwrap().packets_delayed += 1;
@@ -204,9 +239,25 @@ impl ChaosEngine {
     pub fn reset(&self) {
         self.partitions.write().unwrap().clear();
         self.crashed_nodes.write().unwrap().clear();
+        self.clear_fault();
         self.reset_stats();
     }
 
+    /// Inject a fault on the links touching its targets, replacing any other
+    pub fn inject(&self, fault: ScopedFault) {
+        *self.scoped.write().unwrap() = Some(fault);
+    }
+
+    /// Remove the injected fault
+    pub fn clear_fault(&self) {
+        *self.scoped.write().unwrap() = None;
+    }
+
+    /// Currently injected fault
+    pub fn scoped_fault(&self) -> Option<ScopedFault> {
+        self.scoped.read().unwrap().clone()
+    }
+
     /// Enable or disable chaos injection
     pub fn set_enabled(&mut self, enabled: bool) {
         self.enabled = enabled;
@@ -340,6 +391,284 @@ impl ChaosScenarios {
     }
 }
 
+/// Fault an experiment injects
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ChaosFault {
+    /// Drop this fraction of packets
+    Drop { rate: f64 },
+    /// Delay packets by a uniform time in the range
+    Delay { min_ms: u64, max_ms: u64 },
+    /// Drop every packet, cutting the targets off
+    Isolate,
+}
+
+impl ChaosFault {
+    fn scoped(&self, targets: &[NodeId]) -> ScopedFault {
+        let (drop_rate, delay_range_ms) = match *self {
+            ChaosFault::Drop { rate } => (rate, (0, 0)),
+            ChaosFault::Delay { min_ms, max_ms } => (0.0, (min_ms, max_ms)),
+            ChaosFault::Isolate => (1.0, (0, 0)),
+        };
+        ScopedFault { targets: targets.iter().cloned().collect(), drop_rate, delay_range_ms }
+    }
+}
+
+/// Telemetry threshold that stops an experiment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AbortCondition {
+    /// Acked fraction of the Data packets sent since the experiment started
+    /// falls below `threshold`, once at least `min_packets` were sent
+    DeliveryRatioBelow { threshold: f64, min_packets: u64 },
+    /// The sum of a metric's samples rises above `threshold`
+    MetricAbove { metric: String, threshold: f64 },
+    /// The sum of a metric's samples falls below `threshold`
+    MetricBelow { metric: String, threshold: f64 },
+}
+
+impl AbortCondition {
+    /// Why the condition trips on `telemetry`, if it does
+    fn check(&self, telemetry: &ChaosTelemetry, baseline: (u64, u64)) -> Option<String> {
+        match self {
+            AbortCondition::DeliveryRatioBelow { threshold, min_packets } => {
+                let sent = telemetry.sent.saturating_sub(baseline.0);
+                let acked = telemetry.acked.saturating_sub(baseline.1);
+                let ratio = acked as f64 / sent as f64;
+                (sent >= *min_packets.max(&1) && ratio < *threshold)
+                    .then(|| format!("delivery ratio {:.3} below {}", ratio, threshold))
+            }
+            AbortCondition::MetricAbove { metric, threshold } => telemetry
+                .metric(metric)
+                .filter(|value| value > threshold)
+                .map(|value| format!("{} at {} above {}", metric, value, threshold)),
+            AbortCondition::MetricBelow { metric, threshold } => telemetry
+                .metric(metric)
+                .filter(|value| value < threshold)
+                .map(|value| format!("{} at {} below {}", metric, value, threshold)),
+        }
+    }
+}
+
+/// A fault on some targets for a bounded time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChaosExperiment {
+    pub name: String,
+    /// Nodes whose links are faulted, in either direction
+    pub targets: Vec<NodeId>,
+    pub fault: ChaosFault,
+    pub duration_ms: u64,
+    /// Any one of these rolls the fault back early
+    #[serde(default)]
+    pub abort: Vec<AbortCondition>,
+}
+
+/// Experiments to run in order, and the limits they must stay within
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChaosExperimentConfig {
+    pub experiments: Vec<ChaosExperiment>,
+    /// Most targets one experiment may fault
+    pub max_targets: usize,
+    /// Longest an experiment may run
+    pub max_duration_ms: u64,
+    /// Highest drop rate a fault may inject; isolation counts as 1.0
+    pub max_drop_rate: f64,
+    /// Longest delay a fault may inject
+    pub max_delay_ms: u64,
+}
+
+impl Default for ChaosExperimentConfig {
+    fn default() -> Self {
+        Self {
+            experiments: Vec::new(),
+            max_targets: 3,
+            max_duration_ms: 600_000,
+            max_drop_rate: 1.0,
+            max_delay_ms: 5_000,
+        }
+    }
+}
+
+impl ChaosExperimentConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.max_drop_rate) {
+            return Err("Chaos experiment max_drop_rate must be in [0, 1]".to_string());
+        }
+        let mut names = HashSet::new();
+        for experiment in &self.experiments {
+            let name = &experiment.name;
+            if !names.insert(name.as_str()) {
+                return Err(format!("Chaos experiment {} is defined twice", name));
+            }
+            if experiment.targets.is_empty() || experiment.targets.len() > self.max_targets {
+                return Err(format!("Chaos experiment {} needs 1 to {} targets", name, self.max_targets));
+            }
+            if experiment.duration_ms == 0 || experiment.duration_ms > self.max_duration_ms {
+                return Err(format!("Chaos experiment {} must run 1 to {} ms", name, self.max_duration_ms));
+            }
+            let fault = experiment.fault.scoped(&experiment.targets);
+            if !(0.0..=self.max_drop_rate).contains(&fault.drop_rate) {
+                return Err(format!("Chaos experiment {} exceeds drop rate {}", name, self.max_drop_rate));
+            }
+            let (min, max) = fault.delay_range_ms;
+            if min > max || max > self.max_delay_ms {
+                return Err(format!("Chaos experiment {} needs a delay range within {} ms", name, self.max_delay_ms));
+            }
+            for condition in &experiment.abort {
+                if let AbortCondition::DeliveryRatioBelow { threshold, .. } = condition {
+                    if !(0.0..=1.0).contains(threshold) {
+                        return Err(format!("Chaos experiment {} has a delivery ratio outside [0, 1]", name));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether any experiment aborts on the delivery ratio
+    pub fn uses_delivery_ratio(&self) -> bool {
+        self.experiments
+            .iter()
+            .flat_map(|e| &e.abort)
+            .any(|c| matches!(c, AbortCondition::DeliveryRatioBelow { .. }))
+    }
+}
+
+/// Node telemetry that abort conditions are evaluated against
+#[derive(Debug, Clone, Default)]
+pub struct ChaosTelemetry {
+    /// Data packets sent, since startup
+    pub sent: u64,
+    /// Of those, packets acked
+    pub acked: u64,
+    pub metrics: Vec<MetricSample>,
+}
+
+impl ChaosTelemetry {
+    /// Sum of the samples of `metric`, if any were taken
+    fn metric(&self, metric: &str) -> Option<f64> {
+        self.metrics
+            .iter()
+            .filter(|s| s.name == metric)
+            .map(|s| s.value)
+            .reduce(|a, b| a + b)
+    }
+}
+
+/// Where an experiment is in its life
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ExperimentState {
+    Pending,
+    Running,
+    /// Ran for its full duration
+    Completed,
+    /// Rolled back because an abort condition tripped
+    Aborted { reason: String },
+    /// Not run because an earlier experiment aborted
+    Cancelled,
+}
+
+/// Progress of one configured experiment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExperimentStatus {
+    pub name: String,
+    #[serde(flatten)]
+    pub state: ExperimentState,
+    pub started_ms: Option<u64>,
+    pub ended_ms: Option<u64>,
+}
+
+#[derive(Debug)]
+struct ActiveExperiment {
+    index: usize,
+    /// Sent and acked counters when the fault was injected
+    baseline: (u64, u64),
+}
+
+/// Runs the configured experiments one at a time against a chaos engine
+#[derive(Debug, Default)]
+pub struct ChaosExperiments {
+    config: ChaosExperimentConfig,
+    statuses: Vec<ExperimentStatus>,
+    active: Option<ActiveExperiment>,
+}
+
+impl ChaosExperiments {
+    pub fn new(config: ChaosExperimentConfig) -> Self {
+        let statuses = config
+            .experiments
+            .iter()
+            .map(|e| ExperimentStatus { name: e.name.clone(), state: ExperimentState::Pending, started_ms: None, ended_ms: None })
+            .collect();
+        Self { config, statuses, active: None }
+    }
+
+    /// Replace the experiments, rolling back a running one first
+    pub fn set_config(&mut self, config: ChaosExperimentConfig, engine: &ChaosEngine) {
+        if self.active.is_some() {
+            engine.clear_fault();
+        }
+        *self = Self::new(config);
+    }
+
+    /// Nothing is running and nothing is left to run
+    pub fn is_idle(&self) -> bool {
+        self.active.is_none() && !self.statuses.iter().any(|s| s.state == ExperimentState::Pending)
+    }
+
+    /// Check the running experiment, ending it when its time is up or an
+    /// abort condition trips, and start the next one
+    ///
+    /// # Returns
+    /// Whether any status changed
+    pub fn tick(&mut self, engine: &ChaosEngine, telemetry: &ChaosTelemetry, now_ms: u64) -> bool {
+        let mut changed = false;
+        if let Some(active) = &self.active {
+            let experiment = &self.config.experiments[active.index];
+            let started = self.statuses[active.index].started_ms.unwrap_or(now_ms);
+            let reason = experiment.abort.iter().find_map(|c| c.check(telemetry, active.baseline));
+            let ended = match reason {
+                Some(reason) => Some(ExperimentState::Aborted { reason }),
+                None if now_ms.saturating_sub(started) >= experiment.duration_ms => Some(ExperimentState::Completed),
+                None => None,
+            };
+            if let Some(state) = ended {
+                engine.clear_fault();
+                let aborted = matches!(state, ExperimentState::Aborted { .. });
+                let status = &mut self.statuses[active.index];
+                status.state = state;
+                status.ended_ms = Some(now_ms);
+                if aborted {
+                    for status in self.statuses.iter_mut().filter(|s| s.state == ExperimentState::Pending) {
+                        status.state = ExperimentState::Cancelled;
+                    }
+                }
+                self.active = None;
+                changed = true;
+            }
+        }
+
+        if self.active.is_none() {
+            if let Some(index) = self.statuses.iter().position(|s| s.state == ExperimentState::Pending) {
+                let experiment = &self.config.experiments[index];
+                engine.inject(experiment.fault.scoped(&experiment.targets));
+                let status = &mut self.statuses[index];
+                status.state = ExperimentState::Running;
+                status.started_ms = Some(now_ms);
+                self.active = Some(ActiveExperiment { index, baseline: (telemetry.sent, telemetry.acked) });
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    pub fn statuses(&self) -> Vec<ExperimentStatus> {
+        self.statuses.clone()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -383,4 +712,71 @@ mod tests {
         chaos.heal_partitions();
         // After healing, should be able to communicate
     }
+
+    fn experiment(name: &str, fault: ChaosFault, abort: Vec<AbortCondition>) -> ChaosExperiment {
+        ChaosExperiment { name: name.into(), targets: vec![NodeId::new("t")], fault, duration_ms: 1_000, abort }
+    }
+
+    #[test]
+    fn test_experiment_blast_radius() {
+        let mut config = ChaosExperimentConfig {
+            experiments: vec![experiment("drop", ChaosFault::Drop { rate: 0.2 }, Vec::new())],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+
+        let too_wide = ChaosExperimentConfig { max_targets: 0, ..config.clone() };
+        assert!(too_wide.validate().unwrap_err().contains("targets"));
+        let too_long = ChaosExperimentConfig { max_duration_ms: 500, ..config.clone() };
+        assert!(too_long.validate().is_err());
+        config.max_drop_rate = 0.5;
+        config.experiments.push(experiment("cut", ChaosFault::Isolate, Vec::new()));
+        assert!(config.validate().unwrap_err().contains("cut"));
+        config.experiments[1] = experiment("slow", ChaosFault::Delay { min_ms: 10, max_ms: 9_000 }, Vec::new());
+        assert!(config.validate().is_err());
+
+        // The fault only touches links to and from the targets
+        let engine = ChaosEngine::new();
+        engine.inject(ChaosFault::Isolate.scoped(&[NodeId::new("t")]));
+        assert!(engine.should_drop_packet(&NodeId::new("a"), &NodeId::new("t")));
+        assert!(!engine.should_drop_packet(&NodeId::new("a"), &NodeId::new("b")));
+    }
+
+    #[test]
+    fn test_experiments_abort_and_roll_back() {
+        let config = ChaosExperimentConfig {
+            experiments: vec![
+                experiment("slow", ChaosFault::Delay { min_ms: 5, max_ms: 5 }, Vec::new()),
+                experiment(
+                    "cut",
+                    ChaosFault::Isolate,
+                    vec![AbortCondition::DeliveryRatioBelow { threshold: 0.5, min_packets: 4 }],
+                ),
+                experiment("drop", ChaosFault::Drop { rate: 0.1 }, Vec::new()),
+            ],
+            ..Default::default()
+        };
+        let engine = ChaosEngine::new();
+        let mut runner = ChaosExperiments::new(config);
+        let (a, t) = (NodeId::new("a"), NodeId::new("t"));
+        let telemetry = |sent, acked| ChaosTelemetry { sent, acked, metrics: Vec::new() };
+
+        assert!(runner.tick(&engine, &telemetry(10, 10), 0));
+        assert_eq!(engine.get_delay_ms(&a, &t), 5);
+        assert!(!runner.tick(&engine, &telemetry(10, 10), 500));
+        // The first completes and the second starts from the current counters
+        assert!(runner.tick(&engine, &telemetry(10, 10), 1_000));
+        assert_eq!(runner.statuses()[0].state, ExperimentState::Completed);
+        assert!(engine.should_drop_packet(&a, &t));
+        assert!(!runner.tick(&engine, &telemetry(13, 10), 1_100));
+        assert!(runner.tick(&engine, &telemetry(14, 11), 1_200));
+
+        let statuses = runner.statuses();
+        assert!(matches!(&statuses[1].state, ExperimentState::Aborted { reason } if reason.contains("0.250")));
+        assert_eq!(statuses[1].ended_ms, Some(1_200));
+        assert_eq!(statuses[2].state, ExperimentState::Cancelled);
+        assert_eq!(engine.scoped_fault(), None);
+        assert!(!engine.should_drop_packet(&a, &t));
+        assert!(runner.is_idle());
+    }
 }
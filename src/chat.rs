@@ -5,10 +5,13 @@
 //! - WebSocket server with axum
 //! - Message routing via DRFE-R protocol
 //! - Chat room management
+//! - Peer availability from the overlay's presence service, with messages
+//!   to offline peers held until they come back
 //!
 //! Requirements: 13.1, 13.2, 13.3
 
 use crate::coordinates::{NodeId, RoutingCoordinate, AnchorCoordinate};
+use crate::presence::{PresenceStatus, PresenceUpdate};
 use crate::routing::{GPRouter, RoutingNode, DeliveryResult};
 use crate::PoincareDiskPoint;
 use axum::{
@@ -47,6 +50,8 @@ pub enum ChatMessageType {
     Error { message: String },
     /// System message
     System { message: String },
+    /// Peer came online or went offline
+    Presence { user_id: String, online: bool },
 }
 
 /// Chat message structure
@@ -154,6 +159,24 @@ impl ChatMessage {
             room_id: None,
         }
     }
+
+    /// Create a presence message
+    pub fn new_presence(recipient: &str, user_id: &str, online: bool) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            sender: "system".to_string(),
+            recipient: recipient.to_string(),
+            message_type: ChatMessageType::Presence {
+                user_id: user_id.to_string(),
+                online,
+            },
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64,
+            room_id: None,
+        }
+    }
 }
 
 /// Chat room structure
@@ -264,6 +287,10 @@ pub struct ChatServerState {
     pub broadcast_tx: broadcast::Sender<ChatMessage>,
     /// User-specific channels (user_id -> sender)
     pub user_channels: Arc<RwLock<HashMap<String, broadcast::Sender<ChatMessage>>>>,
+    /// Peer availability reported by the presence service (user_id -> status)
+    pub presence: Arc<RwLock<HashMap<String, PresenceStatus>>>,
+    /// Messages held for offline users (user_id -> messages)
+    pub offline_queue: Arc<RwLock<HashMap<String, Vec<ChatMessage>>>>,
 }
 
 impl ChatServerState {
@@ -277,6 +304,8 @@ impl ChatServerState {
             router: Arc::new(RwLock::new(GPRouter::new())),
             broadcast_tx,
             user_channels: Arc::new(RwLock::new(HashMap::new())),
+            presence: Arc::new(RwLock::new(HashMap::new())),
+            offline_queue: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
@@ -301,6 +330,8 @@ impl ChatServerState {
         let (tx, _) = broadcast::channel(100);
         let mut channels = self.user_channels.write().await;
         channels.insert(user_id.to_string(), tx);
+        drop(channels);
+        self.flush_offline_queue(user_id).await;
         
         Ok(user)
     }
@@ -411,7 +442,17 @@ impl ChatServerState {
     }
 
     /// Send a message to a user
+    ///
+    /// Messages to a user the presence service reports offline are held
+    /// until the user comes back.
     pub async fn send_message(&self, message: ChatMessage) -> Result<(), String> {
+        let connected = self.user_channels.read().await.contains_key(&message.recipient);
+        if !connected && self.availability(&message.recipient).await == Some(PresenceStatus::Offline) {
+            let mut queue = self.offline_queue.write().await;
+            queue.entry(message.recipient.clone()).or_default().push(message);
+            return Ok(());
+        }
+
         // Route the message
         let routing_result = self.route_message(&message).await?;
         
@@ -472,6 +513,45 @@ impl ChatServerState {
         Ok(())
     }
 
+    /// Record a peer's presence and tell connected users about it
+    ///
+    /// A peer coming online receives the messages held for it, if it is
+    /// connected here. Returns the number of messages delivered.
+    pub async fn apply_presence(&self, update: &PresenceUpdate) -> usize {
+        let user_id = update.node.0.as_str();
+        let previous = self.presence.write().await.insert(user_id.to_string(), update.status);
+        let online = update.status == PresenceStatus::Online;
+        if previous != Some(update.status) {
+            let channels = self.user_channels.read().await;
+            for (recipient, tx) in channels.iter().filter(|(id, _)| id.as_str() != user_id) {
+                let _ = tx.send(ChatMessage::new_presence(recipient, user_id, online));
+            }
+        }
+        if online {
+            self.flush_offline_queue(user_id).await
+        } else {
+            0
+        }
+    }
+
+    /// Last known availability of a peer
+    pub async fn availability(&self, user_id: &str) -> Option<PresenceStatus> {
+        self.presence.read().await.get(user_id).copied()
+    }
+
+    /// Deliver the messages held for a user, if it is connected
+    async fn flush_offline_queue(&self, user_id: &str) -> usize {
+        let channels = self.user_channels.read().await;
+        let Some(tx) = channels.get(user_id) else {
+            return 0;
+        };
+        let held = self.offline_queue.write().await.remove(user_id).unwrap_or_default();
+        for message in &held {
+            let _ = tx.send(message.clone());
+        }
+        held.len()
+    }
+
     /// Get user by ID
     pub async fn get_user(&self, user_id: &str) -> Option<ConnectedUser> {
         let users = self.users.read().await;
@@ -881,6 +961,30 @@ mod tests {
         assert!(result.success);
     }
 
+    #[tokio::test]
+    async fn test_presence_holds_messages_for_offline_peers() {
+        let state = ChatServerState::new();
+        state.register_user("alice").await.unwrap();
+        let mut alice_rx = state.user_channels.read().await["alice"].subscribe();
+
+        let update = |status| PresenceUpdate { node: NodeId::new("bob"), status, version: 1, expired: false };
+        assert_eq!(state.apply_presence(&update(PresenceStatus::Offline)).await, 0);
+        assert_eq!(state.availability("bob").await, Some(PresenceStatus::Offline));
+        let notice = alice_rx.try_recv().unwrap();
+        assert!(matches!(notice.message_type, ChatMessageType::Presence { online: false, .. }));
+
+        // Bob is offline, so the message waits for him
+        state.send_message(ChatMessage::new_text("alice", "bob", "Ping")).await.unwrap();
+        assert_eq!(state.offline_queue.read().await["bob"].len(), 1);
+
+        // Bob reconnects and gets it
+        state.register_user("bob").await.unwrap();
+        assert!(state.offline_queue.read().await.is_empty());
+        assert_eq!(state.apply_presence(&update(PresenceStatus::Online)).await, 0);
+        let notice = alice_rx.try_recv().unwrap();
+        assert!(matches!(notice.message_type, ChatMessageType::Presence { online: true, .. }));
+    }
+
     #[test]
     fn test_chat_message_creation() {
         let msg = ChatMessage::new_text("alice", "bob", "Hello!");
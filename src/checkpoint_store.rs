@@ -0,0 +1,486 @@
+//! Checkpoint Storage Backends
+//!
+//! Node checkpoints and coordinated snapshot parts go through the async
+//! `CheckpointStore` trait, so a node can keep them in a local directory or,
+//! with the `s3` feature, in any S3-compatible object store. Everything is
+//! stored under plain keys:
+//!
+//! - `checkpoints/<node>/<millis>.json` for periodic node checkpoints, or
+//!   `<millis>.ckpt` for checkpoints in the compact encoding of
+//!   `checkpoint_codec`
+//! - `snapshots/<snapshot id>/<node>.json` for parts of a coordinated snapshot
+//!
+//! Millisecond timestamps are zero-padded so keys sort by age. On top of the
+//! trait, this module applies retention policies and finds the newest
+//! snapshot that every participant completed and uploaded, reconciled into
+//! a cluster restore plan.
+
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::checkpoint_codec::CompactEncoding;
+use crate::failpoint::fail_point;
+use crate::network::{CheckpointError, NodeCheckpoint};
+use crate::snapshot::{reconcile, ClusterRestorePlan, NodeSnapshot};
+
+const CHECKPOINT_PREFIX: &str = "checkpoints/";
+const SNAPSHOT_PREFIX: &str = "snapshots/";
+
+fn storage_error(key: &str, message: impl ToString) -> CheckpointError {
+    CheckpointError::Storage { key: key.to_string(), message: message.to_string() }
+}
+
+/// Key-value storage for serialized checkpoints
+#[async_trait]
+pub trait CheckpointStore: Send + Sync {
+    /// Store `data` under `key`, replacing any previous value
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<(), CheckpointError>;
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, CheckpointError>;
+
+    /// Keys starting with `prefix`, in ascending order
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, CheckpointError>;
+
+    /// Remove `key`; removing a missing key is not an error
+    async fn delete(&self, key: &str) -> Result<(), CheckpointError>;
+}
+
+/// Checkpoints in a local directory, one file per key
+#[derive(Debug, Clone)]
+pub struct FsCheckpointStore {
+    root: PathBuf,
+}
+
+impl FsCheckpointStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, CheckpointError> {
+        let relative = Path::new(key);
+        if key.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(storage_error(key, "key must be a relative path without '..'"));
+        }
+        Ok(self.root.join(relative))
+    }
+
+    fn io_error(path: PathBuf) -> impl FnOnce(std::io::Error) -> CheckpointError {
+        move |source| CheckpointError::Io { path, source }
+    }
+}
+
+#[async_trait]
+impl CheckpointStore for FsCheckpointStore {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<(), CheckpointError> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(Self::io_error(parent.to_path_buf()))?;
+        }
+        // Write then rename, so readers never see a partial checkpoint
+        let partial = path.with_extension("partial");
+        fail_point!("checkpoint.before_write", |error| Err(Self::io_error(partial)(error)));
+        tokio::fs::write(&partial, data).await.map_err(Self::io_error(partial.clone()))?;
+        fail_point!("checkpoint.after_write", |error| Err(Self::io_error(partial)(error)));
+        tokio::fs::rename(&partial, &path).await.map_err(Self::io_error(path))
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, CheckpointError> {
+        let path = self.path(key)?;
+        tokio::fs::read(&path).await.map_err(Self::io_error(path))
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, CheckpointError> {
+        let mut keys = Vec::new();
+        let mut pending = vec![self.root.clone()];
+        while let Some(dir) = pending.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(Self::io_error(dir)(e)),
+            };
+            while let Some(entry) = entries.next_entry().await.map_err(Self::io_error(dir.clone()))? {
+                let path = entry.path();
+                if entry.file_type().await.map_err(Self::io_error(path.clone()))?.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                let Ok(relative) = path.strip_prefix(&self.root) else {
+                    continue;
+                };
+                let key = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+                if key.starts_with(prefix) && !key.ends_with(".partial") {
+                    keys.push(key);
+                }
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), CheckpointError> {
+        let path = self.path(key)?;
+        match tokio::fs::remove_file(&path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(Self::io_error(path)(e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Connection settings for an S3-compatible bucket
+#[cfg(feature = "s3")]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct S3Config {
+    pub bucket: String,
+    pub region: String,
+    /// Custom endpoint for S3-compatible services such as MinIO
+    pub endpoint: Option<String>,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// Prepended to every key, e.g. `cluster-a/`
+    pub prefix: String,
+    /// Allow plain HTTP endpoints
+    pub allow_http: bool,
+}
+
+/// Checkpoints in an object store, such as an S3 bucket
+#[cfg(feature = "s3")]
+#[derive(Debug, Clone)]
+pub struct ObjectCheckpointStore {
+    store: std::sync::Arc<dyn object_store::ObjectStore>,
+    prefix: String,
+}
+
+#[cfg(feature = "s3")]
+impl ObjectCheckpointStore {
+    /// Wrap any object store; `prefix` is prepended to every key
+    pub fn new(store: std::sync::Arc<dyn object_store::ObjectStore>, prefix: impl Into<String>) -> Self {
+        Self { store, prefix: prefix.into() }
+    }
+
+    /// Connect to an S3-compatible bucket
+    pub fn s3(config: &S3Config) -> Result<Self, CheckpointError> {
+        let mut builder = object_store::aws::AmazonS3Builder::new()
+            .with_bucket_name(&config.bucket)
+            .with_region(&config.region)
+            .with_access_key_id(&config.access_key_id)
+            .with_secret_access_key(&config.secret_access_key)
+            .with_allow_http(config.allow_http);
+        if let Some(endpoint) = &config.endpoint {
+            builder = builder.with_endpoint(endpoint);
+        }
+        let store = builder.build().map_err(|e| storage_error(&config.bucket, e))?;
+        Ok(Self::new(std::sync::Arc::new(store), config.prefix.clone()))
+    }
+
+    fn path(&self, key: &str) -> object_store::path::Path {
+        object_store::path::Path::from(format!("{}{}", self.prefix, key))
+    }
+}
+
+#[cfg(feature = "s3")]
+#[async_trait]
+impl CheckpointStore for ObjectCheckpointStore {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<(), CheckpointError> {
+        self.store
+            .put(&self.path(key), data.into())
+            .await
+            .map(|_| ())
+            .map_err(|e| storage_error(key, e))
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, CheckpointError> {
+        let result = self.store.get(&self.path(key)).await.map_err(|e| storage_error(key, e))?;
+        let bytes = result.bytes().await.map_err(|e| storage_error(key, e))?;
+        Ok(bytes.to_vec())
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, CheckpointError> {
+        use futures_util::TryStreamExt;
+
+        // Object store listings are by directory; filter the rest of the prefix
+        let full = format!("{}{}", self.prefix, prefix);
+        let dir = full.rsplit_once('/').map(|(dir, _)| object_store::path::Path::from(dir));
+        let objects: Vec<_> = self.store.list(dir.as_ref()).try_collect().await.map_err(|e| storage_error(prefix, e))?;
+        let mut keys: Vec<String> = objects
+            .into_iter()
+            .filter_map(|meta| meta.location.as_ref().strip_prefix(&self.prefix).map(str::to_string))
+            .filter(|key| key.starts_with(prefix))
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), CheckpointError> {
+        match self.store.delete(&self.path(key)).await {
+            Err(object_store::Error::NotFound { .. }) | Ok(()) => Ok(()),
+            Err(e) => Err(storage_error(key, e)),
+        }
+    }
+}
+
+/// Which stored checkpoints or snapshots to keep
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    /// Newest entries always kept
+    pub keep_last: usize,
+    /// Older entries beyond `keep_last` are removed; all are if unset
+    pub max_age_secs: Option<u64>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self { keep_last: 5, max_age_secs: None }
+    }
+}
+
+impl RetentionPolicy {
+    /// Whether the `index`-th newest entry, `age_secs` old, is kept
+    fn keeps(&self, index: usize, age_secs: u64) -> bool {
+        index < self.keep_last || self.max_age_secs.is_some_and(|max| age_secs <= max)
+    }
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn checkpoint_prefix(node_id: &str) -> String {
+    format!("{}{}/", CHECKPOINT_PREFIX, node_id)
+}
+
+/// Millisecond timestamp in a checkpoint key
+fn checkpoint_key_ms(key: &str) -> Option<u64> {
+    let name = key.rsplit('/').next()?;
+    name.strip_suffix(".json").or_else(|| name.strip_suffix(".ckpt"))?.parse().ok()
+}
+
+/// Store a node checkpoint, returning its key
+pub async fn save_checkpoint(store: &dyn CheckpointStore, checkpoint: &NodeCheckpoint) -> Result<String, CheckpointError> {
+    let key = format!("{}{:020}.json", checkpoint_prefix(&checkpoint.node_id), now_ms());
+    store.put(&key, checkpoint.to_json()?.into_bytes()).await?;
+    Ok(key)
+}
+
+/// Store a node checkpoint in the compact encoding, returning its key
+pub async fn save_compact_checkpoint(
+    store: &dyn CheckpointStore,
+    checkpoint: &NodeCheckpoint,
+    encoding: &CompactEncoding,
+) -> Result<String, CheckpointError> {
+    let key = format!("{}{:020}.ckpt", checkpoint_prefix(&checkpoint.node_id), now_ms());
+    store.put(&key, checkpoint.to_compact(encoding)?).await?;
+    Ok(key)
+}
+
+/// Newest stored checkpoint of a node
+pub async fn latest_checkpoint(store: &dyn CheckpointStore, node_id: &str) -> Result<Option<NodeCheckpoint>, CheckpointError> {
+    let keys = store.list(&checkpoint_prefix(node_id)).await?;
+    let Some(key) = keys.iter().rev().find(|key| checkpoint_key_ms(key).is_some()) else {
+        return Ok(None);
+    };
+    let data = store.get(key).await?;
+    NodeCheckpoint::from_bytes(&data).map(Some)
+}
+
+/// Remove a node's checkpoints that `policy` does not keep
+///
+/// # Returns
+/// Number of checkpoints removed
+pub async fn apply_retention(
+    store: &dyn CheckpointStore,
+    node_id: &str,
+    policy: &RetentionPolicy,
+) -> Result<usize, CheckpointError> {
+    let now = now_ms();
+    let mut keys: Vec<(String, u64)> = store
+        .list(&checkpoint_prefix(node_id))
+        .await?
+        .into_iter()
+        .filter_map(|key| checkpoint_key_ms(&key).map(|ms| (key, ms)))
+        .collect();
+    keys.sort_by_key(|(_, ms)| std::cmp::Reverse(*ms));
+
+    let mut removed = 0;
+    for (index, (key, ms)) in keys.iter().enumerate() {
+        if !policy.keeps(index, now.saturating_sub(*ms) / 1000) {
+            store.delete(key).await?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Store one node's part of a coordinated snapshot
+pub async fn save_snapshot(store: &dyn CheckpointStore, snapshot: &NodeSnapshot) -> Result<String, CheckpointError> {
+    let key = format!("{}{}/{}.json", SNAPSHOT_PREFIX, snapshot.snapshot_id, snapshot.checkpoint.node_id);
+    let json = snapshot.to_json().map_err(|e| storage_error(&key, e))?;
+    store.put(&key, json.into_bytes()).await?;
+    Ok(key)
+}
+
+/// Stored snapshot parts by snapshot ID
+async fn load_snapshots(store: &dyn CheckpointStore) -> Result<BTreeMap<String, Vec<NodeSnapshot>>, CheckpointError> {
+    let mut snapshots: BTreeMap<String, Vec<NodeSnapshot>> = BTreeMap::new();
+    for key in store.list(SNAPSHOT_PREFIX).await? {
+        let Some((snapshot_id, _)) = key[SNAPSHOT_PREFIX.len()..].split_once('/') else {
+            continue;
+        };
+        let data = store.get(&key).await?;
+        let part = NodeSnapshot::from_json(&String::from_utf8_lossy(&data)).map_err(|e| storage_error(&key, e))?;
+        snapshots.entry(snapshot_id.to_string()).or_default().push(part);
+    }
+    Ok(snapshots)
+}
+
+/// When a snapshot was taken: the newest checkpoint among its parts
+fn snapshot_time(parts: &[NodeSnapshot]) -> u64 {
+    parts.iter().map(|p| p.checkpoint.timestamp).max().unwrap_or(0)
+}
+
+/// Whether every participant completed its part and uploaded it
+///
+/// Markers reach every neighbor, so each neighbor named in a part must
+/// have a part of its own.
+fn is_cluster_consistent(parts: &[NodeSnapshot]) -> bool {
+    let participants: std::collections::HashSet<&str> = parts.iter().map(|p| p.checkpoint.node_id.as_str()).collect();
+    parts.iter().all(|p| p.complete && p.checkpoint.neighbors.iter().all(|n| participants.contains(n.id.as_str())))
+}
+
+/// IDs of stored snapshots with their part counts, newest first
+pub async fn list_snapshots(store: &dyn CheckpointStore) -> Result<Vec<(String, usize)>, CheckpointError> {
+    let mut snapshots: Vec<_> = load_snapshots(store).await?.into_iter().collect();
+    snapshots.sort_by_key(|(_, parts)| std::cmp::Reverse(snapshot_time(parts)));
+    Ok(snapshots.into_iter().map(|(id, parts)| (id, parts.len())).collect())
+}
+
+/// Restore plan from the newest cluster-consistent snapshot in the store
+pub async fn latest_consistent_snapshot(store: &dyn CheckpointStore) -> Result<Option<ClusterRestorePlan>, CheckpointError> {
+    let mut snapshots: Vec<_> = load_snapshots(store).await?.into_values().collect();
+    snapshots.sort_by_key(|parts| std::cmp::Reverse(snapshot_time(parts)));
+    Ok(snapshots
+        .into_iter()
+        .filter(|parts| is_cluster_consistent(parts))
+        .find_map(|parts| reconcile(parts).ok()))
+}
+
+/// Remove snapshots that `policy` does not keep
+///
+/// # Returns
+/// Number of snapshots removed
+pub async fn apply_snapshot_retention(store: &dyn CheckpointStore, policy: &RetentionPolicy) -> Result<usize, CheckpointError> {
+    let now_secs = now_ms() / 1000;
+    let mut snapshots: Vec<_> = load_snapshots(store).await?.into_iter().collect();
+    snapshots.sort_by_key(|(_, parts)| std::cmp::Reverse(snapshot_time(parts)));
+
+    let mut removed = 0;
+    for (index, (snapshot_id, parts)) in snapshots.iter().enumerate() {
+        if policy.keeps(index, now_secs.saturating_sub(snapshot_time(parts))) {
+            continue;
+        }
+        for part in parts {
+            store.delete(&format!("{}{}/{}.json", SNAPSHOT_PREFIX, snapshot_id, part.checkpoint.node_id)).await?;
+        }
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::CheckpointNeighbor;
+    use crate::PoincareDiskPoint;
+
+    fn checkpoint(node: &str, neighbors: &[&str], timestamp: u64) -> NodeCheckpoint {
+        let mut checkpoint = NodeCheckpoint::new(node.to_string(), PoincareDiskPoint::origin(), 1, Vec::new());
+        checkpoint.timestamp = timestamp;
+        checkpoint.neighbors = neighbors
+            .iter()
+            .map(|id| CheckpointNeighbor {
+                id: id.to_string(),
+                coord: PoincareDiskPoint::origin().into(),
+                addr: "127.0.0.1:1".to_string(),
+                version: 1,
+            })
+            .collect();
+        checkpoint
+    }
+
+    fn part(snapshot_id: &str, node: &str, neighbors: &[&str], timestamp: u64, complete: bool) -> NodeSnapshot {
+        NodeSnapshot {
+            snapshot_id: snapshot_id.to_string(),
+            initiator: "a".to_string(),
+            initiated_ms: 0,
+            addr: "127.0.0.1:1".to_string(),
+            checkpoint: checkpoint(node, neighbors, timestamp),
+            channel_messages: Vec::new(),
+            complete,
+            timed_out: false,
+        }
+    }
+
+    async fn exercise(store: &dyn CheckpointStore) {
+        assert!(latest_checkpoint(store, "a").await.unwrap().is_none());
+        for timestamp in 1..=3 {
+            save_checkpoint(store, &checkpoint("a", &[], timestamp)).await.unwrap();
+            // Keys are per millisecond
+            tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+        }
+        save_checkpoint(store, &checkpoint("ab", &[], 9)).await.unwrap();
+        assert_eq!(latest_checkpoint(store, "a").await.unwrap().unwrap().timestamp, 3);
+
+        let policy = RetentionPolicy { keep_last: 2, max_age_secs: None };
+        assert_eq!(apply_retention(store, "a", &policy).await.unwrap(), 1);
+        assert_eq!(store.list(&checkpoint_prefix("a")).await.unwrap().len(), 2);
+        assert_eq!(latest_checkpoint(store, "ab").await.unwrap().unwrap().timestamp, 9);
+
+        // s1 is consistent; the newer s2 is missing b's part
+        for snapshot in [
+            part("s1", "a", &["b"], 10, true),
+            part("s1", "b", &["a"], 10, true),
+            part("s2", "a", &["b"], 20, true),
+        ] {
+            save_snapshot(store, &snapshot).await.unwrap();
+        }
+        assert_eq!(list_snapshots(store).await.unwrap(), vec![("s2".to_string(), 1), ("s1".to_string(), 2)]);
+        let plan = latest_consistent_snapshot(store).await.unwrap().unwrap();
+        assert_eq!(plan.snapshot_id, "s1");
+        assert_eq!(plan.checkpoints.len(), 2);
+
+        assert_eq!(apply_snapshot_retention(store, &RetentionPolicy { keep_last: 1, max_age_secs: None }).await.unwrap(), 1);
+        assert!(latest_consistent_snapshot(store).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_filesystem_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FsCheckpointStore::new(dir.path());
+        exercise(&store).await;
+
+        // Compact checkpoints sort and load alongside JSON ones
+        save_checkpoint(&store, &checkpoint("c", &["a"], 1)).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+        let key = save_compact_checkpoint(&store, &checkpoint("c", &["a", "b"], 2), &CompactEncoding::default()).await.unwrap();
+        assert!(key.ends_with(".ckpt"));
+        let latest = latest_checkpoint(&store, "c").await.unwrap().unwrap();
+        assert_eq!((latest.timestamp, latest.neighbors.len()), (2, 2));
+
+        assert!(store.put("../escape.json", Vec::new()).await.is_err());
+        assert_eq!(store.get("missing.json").await.unwrap_err().code(), "checkpoint.io");
+        store.delete("missing.json").await.unwrap();
+    }
+
+    #[cfg(feature = "s3")]
+    #[tokio::test]
+    async fn test_object_store() {
+        let memory = std::sync::Arc::new(object_store::memory::InMemory::new());
+        exercise(&ObjectCheckpointStore::new(memory, "cluster/")).await;
+    }
+}
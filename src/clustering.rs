@@ -0,0 +1,350 @@
+//! Hyperbolic Clustering
+//!
+//! Groups nodes by hyperbolic proximity with k-medoids. Medoids are actual
+//! nodes, so clusters stay meaningful in the Poincaré disk where Euclidean
+//! averages of coordinates drift toward the origin. Initialization is
+//! farthest-first from the node nearest the origin with ties broken by node
+//! ID, so the same input always gives the same clusters. `relabel` carries
+//! cluster IDs over from a previous run by member overlap, which keeps IDs
+//! stable while the network changes.
+//!
+//! Clusterings are used to bootstrap hierarchical routing clusters, to check
+//! whether an observed network partition follows geometric clusters, and to
+//! report load per cluster when the clusters are used as shards.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::hierarchical::ClusterId;
+use crate::PoincareDiskPoint;
+
+/// Assignment rounds before k-medoids stops even if it has not converged
+pub const DEFAULT_MAX_ITERATIONS: usize = 50;
+
+/// One cluster of a clustering
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cluster {
+    pub id: ClusterId,
+    /// Member minimizing the total distance to all other members
+    pub medoid: NodeId,
+    pub medoid_coord: PoincareDiskPoint,
+    /// Members in node ID order, including the medoid
+    pub members: Vec<NodeId>,
+    /// Largest hyperbolic distance from the medoid to a member
+    pub radius: f64,
+}
+
+/// Nodes grouped into clusters
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Clustering {
+    /// Clusters in ID order
+    clusters: Vec<Cluster>,
+    assignment: HashMap<NodeId, ClusterId>,
+    /// Sum of member distances to their medoid
+    cost: f64,
+}
+
+impl Clustering {
+    pub fn clusters(&self) -> &[Cluster] {
+        &self.clusters
+    }
+
+    pub fn cluster_of(&self, node: &NodeId) -> Option<&ClusterId> {
+        self.assignment.get(node)
+    }
+
+    pub fn cost(&self) -> f64 {
+        self.cost
+    }
+
+    /// Members of each cluster, keyed by cluster ID
+    pub fn partition(&self) -> HashMap<ClusterId, HashSet<NodeId>> {
+        self.clusters
+            .iter()
+            .map(|cluster| (cluster.id.clone(), cluster.members.iter().cloned().collect()))
+            .collect()
+    }
+
+    /// Reuse cluster IDs from `previous` for clusters that share members
+    ///
+    /// Cluster pairs are matched greedily by the number of shared members,
+    /// largest overlap first. Clusters without a match get IDs not used by
+    /// either clustering.
+    pub fn relabel(&mut self, previous: &Clustering) {
+        let mut overlaps: Vec<(usize, usize, usize)> = Vec::new();
+        for (i, cluster) in self.clusters.iter().enumerate() {
+            let mut shared: BTreeMap<usize, usize> = BTreeMap::new();
+            for member in &cluster.members {
+                if let Some(id) = previous.cluster_of(member) {
+                    if let Some(j) = previous.clusters.iter().position(|c| &c.id == id) {
+                        *shared.entry(j).or_default() += 1;
+                    }
+                }
+            }
+            overlaps.extend(shared.into_iter().map(|(j, count)| (count, i, j)));
+        }
+        overlaps.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+
+        let mut labels: Vec<Option<ClusterId>> = vec![None; self.clusters.len()];
+        let mut taken: HashSet<usize> = HashSet::new();
+        for (_, i, j) in overlaps {
+            if labels[i].is_none() && taken.insert(j) {
+                labels[i] = Some(previous.clusters[j].id.clone());
+            }
+        }
+        let used: HashSet<ClusterId> = previous.clusters.iter().map(|c| c.id.clone()).collect();
+        let mut fresh = (0..).map(cluster_id).filter(|id| !used.contains(id));
+        for (cluster, label) in self.clusters.iter_mut().zip(labels) {
+            cluster.id = label.unwrap_or_else(|| fresh.next().unwrap());
+        }
+        self.clusters.sort_by(|a, b| a.id.cmp(&b.id));
+        self.assignment = self
+            .clusters
+            .iter()
+            .flat_map(|cluster| cluster.members.iter().map(|member| (member.clone(), cluster.id.clone())))
+            .collect();
+    }
+
+    /// How well observed partitions line up with the clusters
+    ///
+    /// Each partition is a set of nodes that can reach each other. Nodes
+    /// not in this clustering are ignored.
+    pub fn partition_alignment(&self, partitions: &[Vec<NodeId>]) -> PartitionAlignment {
+        let mut spread: HashMap<&ClusterId, HashMap<usize, usize>> = HashMap::new();
+        for (index, partition) in partitions.iter().enumerate() {
+            for node in partition {
+                if let Some(id) = self.cluster_of(node) {
+                    *spread.entry(id).or_default().entry(index).or_default() += 1;
+                }
+            }
+        }
+        let total: usize = spread.values().flat_map(|counts| counts.values()).sum();
+        let dominant: usize = spread.values().filter_map(|counts| counts.values().max()).sum();
+        let mut split_clusters: Vec<ClusterId> =
+            spread.iter().filter(|(_, counts)| counts.len() > 1).map(|(id, _)| (*id).clone()).collect();
+        split_clusters.sort();
+        PartitionAlignment {
+            partitions: partitions.len(),
+            purity: if total == 0 { 1.0 } else { dominant as f64 / total as f64 },
+            split_clusters,
+        }
+    }
+
+    /// Load per cluster when clusters are used as shards
+    ///
+    /// Nodes missing from `load` count as zero load.
+    pub fn shard_report(&self, load: &HashMap<NodeId, f64>) -> ShardReport {
+        let shards: Vec<ShardLoad> = self
+            .clusters
+            .iter()
+            .map(|cluster| ShardLoad {
+                cluster: cluster.id.clone(),
+                nodes: cluster.members.len(),
+                load: cluster.members.iter().filter_map(|member| load.get(member)).sum(),
+                radius: cluster.radius,
+            })
+            .collect();
+        let mean = if shards.is_empty() { 0.0 } else { shards.iter().map(|s| s.load).sum::<f64>() / shards.len() as f64 };
+        let max = shards.iter().map(|s| s.load).fold(0.0, f64::max);
+        ShardReport { imbalance: if mean > 0.0 { max / mean } else { 1.0 }, shards }
+    }
+}
+
+/// Result of `Clustering::partition_alignment`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartitionAlignment {
+    pub partitions: usize,
+    /// Fraction of nodes in the partition holding most of their cluster;
+    /// 1.0 when no cluster is split across partitions
+    pub purity: f64,
+    /// Clusters whose members ended up in more than one partition
+    pub split_clusters: Vec<ClusterId>,
+}
+
+/// Load carried by one cluster
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShardLoad {
+    pub cluster: ClusterId,
+    pub nodes: usize,
+    pub load: f64,
+    pub radius: f64,
+}
+
+/// Result of `Clustering::shard_report`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShardReport {
+    pub shards: Vec<ShardLoad>,
+    /// Largest shard load divided by the mean shard load
+    pub imbalance: f64,
+}
+
+fn cluster_id(index: usize) -> ClusterId {
+    ClusterId::new(format!("cluster_{}", index))
+}
+
+/// Cluster `points` into at most `k` groups by hyperbolic distance
+///
+/// Duplicate node IDs keep their first coordinate. Cluster IDs are
+/// assigned in order of the medoids' node IDs; use `Clustering::relabel`
+/// to carry IDs over from an earlier clustering.
+pub fn k_medoids(points: &[(NodeId, PoincareDiskPoint)], k: usize, max_iterations: usize) -> Clustering {
+    let mut nodes: Vec<(NodeId, PoincareDiskPoint)> = Vec::new();
+    let mut seen: HashSet<&NodeId> = HashSet::new();
+    for (id, coord) in points {
+        if seen.insert(id) {
+            nodes.push((id.clone(), *coord));
+        }
+    }
+    nodes.sort_by(|a, b| a.0.cmp(&b.0));
+    let k = k.min(nodes.len());
+    if k == 0 {
+        return Clustering::default();
+    }
+    let distance = |a: usize, b: usize| nodes[a].1.hyperbolic_distance(&nodes[b].1);
+
+    // Farthest-first initialization from the node nearest the origin
+    let origin = PoincareDiskPoint::origin();
+    let first = (0..nodes.len())
+        .min_by(|&a, &b| nodes[a].1.hyperbolic_distance(&origin).total_cmp(&nodes[b].1.hyperbolic_distance(&origin)))
+        .unwrap();
+    let mut medoids = vec![first];
+    let mut nearest: Vec<f64> = (0..nodes.len()).map(|i| distance(i, first)).collect();
+    while medoids.len() < k {
+        let next = (0..nodes.len())
+            .filter(|i| !medoids.contains(i))
+            .max_by(|&a, &b| nearest[a].total_cmp(&nearest[b]).then(b.cmp(&a)))
+            .unwrap();
+        medoids.push(next);
+        for (i, d) in nearest.iter_mut().enumerate() {
+            *d = d.min(distance(i, next));
+        }
+    }
+
+    let assign = |medoids: &[usize]| -> Vec<usize> {
+        (0..nodes.len())
+            .map(|i| {
+                (0..medoids.len())
+                    .min_by(|&a, &b| distance(i, medoids[a]).total_cmp(&distance(i, medoids[b])))
+                    .unwrap()
+            })
+            .collect()
+    };
+    let mut assignment = assign(&medoids);
+    for _ in 0..max_iterations {
+        let mut changed = false;
+        for (c, medoid) in medoids.iter_mut().enumerate() {
+            let members: Vec<usize> = (0..nodes.len()).filter(|&i| assignment[i] == c).collect();
+            let cost = |candidate: usize| members.iter().map(|&m| distance(candidate, m)).sum::<f64>();
+            let best = members
+                .iter()
+                .copied()
+                .min_by(|&a, &b| cost(a).total_cmp(&cost(b)).then(a.cmp(&b)))
+                .unwrap_or(*medoid);
+            if cost(best) < cost(*medoid) - 1e-12 {
+                *medoid = best;
+                changed = true;
+            }
+        }
+        let next = assign(&medoids);
+        if !changed && next == assignment {
+            break;
+        }
+        assignment = next;
+    }
+
+    let mut order: Vec<usize> = (0..medoids.len()).collect();
+    order.sort_by(|&a, &b| nodes[medoids[a]].0.cmp(&nodes[medoids[b]].0));
+    let mut clustering = Clustering::default();
+    for (rank, &c) in order.iter().enumerate() {
+        let medoid = medoids[c];
+        let members: Vec<usize> = (0..nodes.len()).filter(|&i| assignment[i] == c).collect();
+        let id = cluster_id(rank);
+        for &m in &members {
+            clustering.assignment.insert(nodes[m].0.clone(), id.clone());
+            clustering.cost += distance(medoid, m);
+        }
+        clustering.clusters.push(Cluster {
+            id,
+            medoid: nodes[medoid].0.clone(),
+            medoid_coord: nodes[medoid].1,
+            radius: members.iter().map(|&m| distance(medoid, m)).fold(0.0, f64::max),
+            members: members.iter().map(|&m| nodes[m].0.clone()).collect(),
+        });
+    }
+    clustering
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Three tight groups of four nodes near the boundary, 120° apart
+    fn groups() -> Vec<(NodeId, PoincareDiskPoint)> {
+        let mut points = Vec::new();
+        for group in 0..3 {
+            for i in 0..4 {
+                let angle = group as f64 * 2.0 * std::f64::consts::PI / 3.0 + i as f64 * 0.02;
+                let point = PoincareDiskPoint::from_polar(0.6, angle).unwrap();
+                points.push((NodeId::new(format!("g{}n{}", group, i)), point));
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn test_k_medoids_finds_groups() {
+        let points = groups();
+        let clustering = k_medoids(&points, 3, DEFAULT_MAX_ITERATIONS);
+        assert_eq!(clustering.clusters().len(), 3);
+        for cluster in clustering.clusters() {
+            let group = &cluster.members[0].0[..2];
+            assert_eq!(cluster.members.len(), 4);
+            assert!(cluster.members.iter().all(|m| m.0.starts_with(group)));
+        }
+        // Same input, same clusters
+        let mut shuffled = points.clone();
+        shuffled.reverse();
+        assert_eq!(k_medoids(&shuffled, 3, DEFAULT_MAX_ITERATIONS), clustering);
+
+        // A partition along group lines is aligned; one that cuts a group is not
+        let members = |g: &str| points.iter().filter(|(n, _)| n.0.starts_with(g)).map(|(n, _)| n.clone()).collect::<Vec<_>>();
+        let aligned = clustering.partition_alignment(&[members("g0"), [members("g1"), members("g2")].concat()]);
+        assert_eq!((aligned.purity, aligned.split_clusters.len()), (1.0, 0));
+        let mut cut = members("g0");
+        let moved = cut.pop().unwrap();
+        let rest = [members("g1"), members("g2"), vec![moved.clone()]].concat();
+        let misaligned = clustering.partition_alignment(&[cut, rest]);
+        assert!(misaligned.purity < 1.0);
+        assert_eq!(misaligned.split_clusters, vec![clustering.cluster_of(&moved).unwrap().clone()]);
+
+        let load: HashMap<NodeId, f64> = members("g0").into_iter().map(|n| (n, 3.0)).collect();
+        let report = clustering.shard_report(&load);
+        assert_eq!(report.shards.iter().map(|s| s.load).sum::<f64>(), 12.0);
+        assert!((report.imbalance - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_relabel_keeps_ids_stable() {
+        let points = groups();
+        let previous = k_medoids(&points, 3, DEFAULT_MAX_ITERATIONS);
+
+        // Drop group 0 and add a new group near the origin; surviving
+        // groups keep their IDs and the new group gets an unused one
+        let mut changed: Vec<_> = points.iter().filter(|(n, _)| !n.0.starts_with("g0")).cloned().collect();
+        for i in 0..4 {
+            let point = PoincareDiskPoint::from_polar(0.1, i as f64 * 0.05).unwrap();
+            changed.push((NodeId::new(format!("a{}", i)), point));
+        }
+        let mut next = k_medoids(&changed, 3, DEFAULT_MAX_ITERATIONS);
+        next.relabel(&previous);
+        for group in ["g1", "g2"] {
+            let node = NodeId::new(format!("{}n0", group));
+            assert_eq!(next.cluster_of(&node), previous.cluster_of(&node));
+        }
+        let fresh = next.cluster_of(&NodeId::new("a0")).unwrap();
+        assert!(previous.clusters().iter().all(|c| &c.id != fresh));
+        assert_eq!(fresh, &ClusterId::new("cluster_3"));
+    }
+}
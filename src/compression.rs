@@ -0,0 +1,200 @@
+//! Payload Compression
+//!
+//! Packet payloads can be compressed per overlay link. Each node advertises
+//! the algorithms it can decode in its discovery packets; a sender compresses
+//! toward a neighbor only with an algorithm that neighbor advertised, so old
+//! nodes keep receiving plain payloads. The receiving node decompresses
+//! before handling, and a forwarder re-encodes for its own next hop.
+//!
+//! Payloads below `min_payload_bytes` are sent as-is: heartbeats, acks and
+//! other small control packets gain nothing from compression.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Compression applied to a packet payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompressionAlgorithm {
+    #[default]
+    None,
+    Lz4,
+    Zstd,
+}
+
+/// Algorithms this build can decode, advertised to peers
+pub fn supported() -> Vec<CompressionAlgorithm> {
+    vec![CompressionAlgorithm::Zstd, CompressionAlgorithm::Lz4]
+}
+
+/// Compression failures
+#[derive(Error, Debug)]
+pub enum CompressionError {
+    #[error("Compression failed: {0}")]
+    Compress(#[source] std::io::Error),
+
+    #[error("Decompression failed: {0}")]
+    Decompress(String),
+
+    #[error("Decompressed payload exceeds {max} bytes")]
+    TooLarge { max: usize },
+}
+
+impl CompressionError {
+    /// Stable identifier for programmatic handling
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Compress(_) => "compression.compress",
+            Self::Decompress(_) => "compression.decompress",
+            Self::TooLarge { .. } => "compression.too_large",
+        }
+    }
+}
+
+/// Compression settings of a node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressionConfig {
+    /// Compress outgoing payloads (decoding is always available)
+    pub enabled: bool,
+    /// Algorithms in order of preference
+    pub preferred: Vec<CompressionAlgorithm>,
+    /// Smaller payloads are sent uncompressed
+    pub min_payload_bytes: usize,
+    /// zstd compression level (1-22)
+    pub zstd_level: i32,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            preferred: supported(),
+            min_payload_bytes: 1024,
+            zstd_level: 3,
+        }
+    }
+}
+
+impl CompressionConfig {
+    /// First preferred algorithm the peer can decode
+    pub fn negotiate(&self, peer_supports: &[CompressionAlgorithm]) -> CompressionAlgorithm {
+        if !self.enabled {
+            return CompressionAlgorithm::None;
+        }
+        self.preferred
+            .iter()
+            .copied()
+            .find(|alg| *alg != CompressionAlgorithm::None && peer_supports.contains(alg))
+            .unwrap_or(CompressionAlgorithm::None)
+    }
+
+    /// Algorithm to use for a payload of `len` bytes toward a peer
+    pub fn choose(&self, len: usize, peer_supports: &[CompressionAlgorithm]) -> CompressionAlgorithm {
+        if len < self.min_payload_bytes {
+            return CompressionAlgorithm::None;
+        }
+        self.negotiate(peer_supports)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=22).contains(&self.zstd_level) {
+            return Err("zstd_level must be between 1 and 22".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Compress `data` with `algorithm`
+pub fn compress(algorithm: CompressionAlgorithm, data: &[u8], zstd_level: i32) -> Result<Vec<u8>, CompressionError> {
+    match algorithm {
+        CompressionAlgorithm::None => Ok(data.to_vec()),
+        CompressionAlgorithm::Lz4 => Ok(lz4_flex::compress_prepend_size(data)),
+        CompressionAlgorithm::Zstd => zstd::bulk::compress(data, zstd_level).map_err(CompressionError::Compress),
+    }
+}
+
+/// Decompress `data`, refusing output larger than `max_size`
+pub fn decompress(algorithm: CompressionAlgorithm, data: &[u8], max_size: usize) -> Result<Vec<u8>, CompressionError> {
+    match algorithm {
+        CompressionAlgorithm::None => Ok(data.to_vec()),
+        CompressionAlgorithm::Lz4 => {
+            // The size prefix is checked before allocating
+            let declared = data
+                .get(..4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+                .ok_or_else(|| CompressionError::Decompress("Truncated lz4 payload".to_string()))?;
+            if declared > max_size {
+                return Err(CompressionError::TooLarge { max: max_size });
+            }
+            lz4_flex::decompress_size_prepended(data).map_err(|e| CompressionError::Decompress(e.to_string()))
+        }
+        CompressionAlgorithm::Zstd => {
+            if zstd::zstd_safe::get_frame_content_size(data).ok().flatten().is_some_and(|size| size > max_size as u64) {
+                return Err(CompressionError::TooLarge { max: max_size });
+            }
+            zstd::bulk::decompress(data, max_size).map_err(|e| CompressionError::Decompress(e.to_string()))
+        }
+    }
+}
+
+/// Compression counters of a node
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressionStats {
+    /// Payloads sent compressed
+    pub compressed: u64,
+    /// Payloads sent uncompressed (too small, unsupported, or incompressible)
+    pub skipped: u64,
+    /// Original size of compressed payloads
+    pub bytes_in: u64,
+    /// Size of compressed payloads on the wire
+    pub bytes_out: u64,
+    /// Received payloads that failed to decompress
+    pub decode_failures: u64,
+}
+
+impl CompressionStats {
+    /// Wire bytes per original byte over compressed payloads
+    pub fn ratio(&self) -> f64 {
+        if self.bytes_in > 0 {
+            self.bytes_out as f64 / self.bytes_in as f64
+        } else {
+            1.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_limits() {
+        let data = b"hyperbolic ".repeat(500);
+        for alg in [CompressionAlgorithm::Lz4, CompressionAlgorithm::Zstd] {
+            let compressed = compress(alg, &data, 3).unwrap();
+            assert!(compressed.len() < data.len() / 4);
+            assert_eq!(decompress(alg, &compressed, data.len()).unwrap(), data);
+            assert!(matches!(
+                decompress(alg, &compressed, data.len() - 1),
+                Err(CompressionError::TooLarge { .. }) | Err(CompressionError::Decompress(_))
+            ));
+        }
+        assert!(decompress(CompressionAlgorithm::Lz4, &[1, 2], 100).is_err());
+    }
+
+    #[test]
+    fn test_negotiation_and_threshold() {
+        let config = CompressionConfig::default();
+        assert_eq!(config.choose(4096, &supported()), CompressionAlgorithm::Zstd);
+        assert_eq!(config.choose(4096, &[CompressionAlgorithm::Lz4]), CompressionAlgorithm::Lz4);
+        // Peer advertised nothing (older node)
+        assert_eq!(config.choose(4096, &[]), CompressionAlgorithm::None);
+        // Small payloads skip compression
+        assert_eq!(config.choose(64, &supported()), CompressionAlgorithm::None);
+
+        let disabled = CompressionConfig { enabled: false, ..CompressionConfig::default() };
+        assert_eq!(disabled.choose(4096, &supported()), CompressionAlgorithm::None);
+        assert!(CompressionConfig { zstd_level: 0, ..CompressionConfig::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,664 @@
+//! Runtime Configuration for DRFE-R Nodes
+//!
+//! Tunables that can be changed on a running node without restarting it,
+//! dropping connections or losing neighbor state. Changes arrive as a partial
+//! `ConfigUpdate` (only fields that are present are applied), either through
+//! the REST API or by re-reading a JSON config file on SIGHUP.
+
+use crate::access_zones::ZoneConfig;
+use crate::admission::AdmissionConfig;
+use crate::api_access::ApiAccessConfig;
+use crate::broadcast::BroadcastConfig;
+use crate::chaos::{ChaosEngine, ChaosExperimentConfig};
+use crate::compression::CompressionConfig;
+use crate::content::ContentConfig;
+use crate::convergence::ConvergenceConfig;
+use crate::coordinate_control::CoordinateControlConfig;
+use crate::coordinate_precision::CoordinatePrecision;
+use crate::coordinate_tree::CoordinateTreeConfig;
+use crate::coordinates::{AnchorConfig, GeoBootstrapConfig};
+use crate::coordination::ElectionConfig;
+use crate::dead_letter::DeadLetterConfig;
+use crate::deadline::DeadlineConfig;
+use crate::degradation::DegradationConfig;
+use crate::e2e_encryption::E2eConfig;
+use crate::fec::FecConfig;
+use crate::header_budget::HeaderBudgetConfig;
+use crate::healing::HealingConfig;
+use crate::heartbeat::AdaptiveHeartbeatConfig;
+use crate::hlc::ClockConfig;
+use crate::mode_switch::ModeSwitchKind;
+use crate::multihoming::MultihomingConfig;
+use crate::nat::NatConfig;
+use crate::neighbor_cap::NeighborCapConfig;
+use crate::neighbor_exchange::NeighborExchangeConfig;
+use crate::neighbor_policy::NeighborPolicyKind;
+use crate::onion::OnionConfig;
+use crate::path_cache::PathCacheConfig;
+use crate::presence::PresenceConfig;
+use crate::probing::ProbeConfig;
+use crate::reputation::ReputationConfig;
+use crate::route_cache::RouteCacheConfig;
+use crate::route_stats::RouteStatsConfig;
+use crate::shaping::ShapingConfig;
+use crate::traffic_matrix::TrafficMatrixConfig;
+use crate::tree_repair::TreeRepairConfig;
+use crate::ttl_policy::TtlPolicy;
+use serde::{Deserialize, Serialize};
+
+/// Chaos injection settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChaosSettings {
+    /// Whether chaos injection is active
+    pub enabled: bool,
+    /// Probability of dropping an outgoing packet (0.0 to 1.0)
+    pub packet_drop_rate: f64,
+    /// Delay range for outgoing packets in milliseconds (min, max)
+    pub delay_range_ms: (u64, u64),
+    /// Probability of network partition (0.0 to 1.0)
+    pub partition_probability: f64,
+    /// Clock drift in milliseconds
+    pub clock_drift_ms: i64,
+}
+
+impl ChaosSettings {
+    /// Copy these settings into a chaos engine
+    pub fn apply_to(&self, engine: &mut ChaosEngine) {
+        engine.enabled = self.enabled;
+        engine.packet_drop_rate = self.packet_drop_rate;
+        engine.delay_range_ms = self.delay_range_ms;
+        engine.partition_probability = self.partition_probability;
+        engine.clock_drift_ms = self.clock_drift_ms;
+    }
+}
+
+impl From<&ChaosEngine> for ChaosSettings {
+    fn from(engine: &ChaosEngine) -> Self {
+        Self {
+            enabled: engine.enabled,
+            packet_drop_rate: engine.packet_drop_rate,
+            delay_range_ms: engine.delay_range_ms,
+            partition_probability: engine.partition_probability,
+            clock_drift_ms: engine.clock_drift_ms,
+        }
+    }
+}
+
+impl Default for ChaosSettings {
+    fn default() -> Self {
+        Self::from(&ChaosEngine::new())
+    }
+}
+
+/// Quality-of-service limits
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QosLimits {
+    /// Incoming packets are shed while this many are already being handled
+    pub max_inflight_packets: usize,
+    /// Forwarded Data packets are marked congestion-experienced at this depth
+    /// (capped at `max_inflight_packets`, so marking precedes shedding)
+    pub congestion_mark_packets: usize,
+}
+
+impl Default for QosLimits {
+    fn default() -> Self {
+        Self {
+            max_inflight_packets: 10_000,
+            congestion_mark_packets: 256,
+        }
+    }
+}
+
+/// Keepalives and socket options of overlay TCP connections
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeepaliveConfig {
+    /// Send an empty frame on pooled connections idle this long; 0 disables
+    pub idle_ms: u64,
+    /// Set TCP_NODELAY
+    pub nodelay: bool,
+    /// Idle time before the kernel's SO_KEEPALIVE probes start; 0 disables them
+    pub tcp_keepalive_ms: u64,
+    /// A write blocked this long marks the connection dead
+    pub write_timeout_ms: u64,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            idle_ms: 10_000,
+            nodelay: true,
+            tcp_keepalive_ms: 30_000,
+            write_timeout_ms: 5_000,
+        }
+    }
+}
+
+/// Effective runtime configuration of a node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeConfig {
+    /// Heartbeat interval in milliseconds
+    pub heartbeat_interval_ms: u64,
+    /// Neighbor failure timeout in milliseconds
+    pub failure_timeout_ms: u64,
+    /// Discovery broadcast interval in milliseconds
+    pub discovery_interval_ms: u64,
+    /// Maximum number of neighbors to maintain; the starting cap when
+    /// `neighbor_cap` adapts it
+    pub max_neighbors: usize,
+    /// Chaos injection settings
+    pub chaos: ChaosSettings,
+    /// QoS limits
+    pub qos: QosLimits,
+    /// TTL profiles per packet type and QoS class
+    #[serde(default)]
+    pub ttl: TtlPolicy,
+    /// Which peers to keep when more than `max_neighbors` are known
+    #[serde(default)]
+    pub neighbor_policy: NeighborPolicyKind,
+    /// Payload compression on overlay links
+    #[serde(default)]
+    pub compression: CompressionConfig,
+    /// Churn- and RTT-driven heartbeat intervals; enable on every node of
+    /// an overlay, since failure deadlines follow the advertised intervals
+    #[serde(default)]
+    pub adaptive_heartbeat: AdaptiveHeartbeatConfig,
+    /// Per-region traffic counting and hot-region routing costs
+    #[serde(default)]
+    pub traffic_matrix: TrafficMatrixConfig,
+    /// Send retries and the dead-letter queue for undeliverable packets
+    #[serde(default)]
+    pub dead_letter: DeadLetterConfig,
+    /// Set-points and gains of the coordinate update controller
+    #[serde(default)]
+    pub coordinate_control: CoordinateControlConfig,
+    /// Hop limit, duplicate suppression and mode of overlay-wide broadcasts
+    #[serde(default)]
+    pub broadcast: BroadcastConfig,
+    /// Sizes of the anchor coordinate and next-hop caches
+    #[serde(default)]
+    pub route_cache: RouteCacheConfig,
+    /// Lease timing of the leader election for maintenance tasks
+    #[serde(default)]
+    pub election: ElectionConfig,
+    /// Per-destination and per-neighbor statistics kept across restarts
+    #[serde(default)]
+    pub route_stats: RouteStatsConfig,
+    /// Keepalive frames and socket options for pooled TCP connections
+    #[serde(default)]
+    pub keepalive: KeepaliveConfig,
+    /// Per-type routing header budgets, compaction and trimming
+    #[serde(default)]
+    pub header_budget: HeaderBudgetConfig,
+    /// Reed-Solomon parity on UDP links toward peers that support it
+    #[serde(default)]
+    pub fec: FecConfig,
+    /// Egress bandwidth caps per neighbor and QoS class
+    #[serde(default)]
+    pub shaping: ShapingConfig,
+    /// Rate of new neighbors accepted from discovery and their onboarding
+    #[serde(default)]
+    pub admission: AdmissionConfig,
+    /// Digest-based exchange of neighbor lists with neighbors
+    #[serde(default)]
+    pub neighbor_exchange: NeighborExchangeConfig,
+    /// Next hops toward target regions validated by acknowledgements
+    #[serde(default)]
+    pub path_cache: PathCacheConfig,
+    /// Gossip of Ricci flow stress and displacement for cluster-wide convergence
+    #[serde(default)]
+    pub convergence: ConvergenceConfig,
+    /// Tokens, roles and rate limits of the REST and gRPC APIs; left out
+    /// of serialized configs, which read-only clients can fetch
+    #[serde(default, skip_serializing)]
+    pub api_access: ApiAccessConfig,
+    /// Endpoints advertised to neighbors and transport choice per QoS class
+    #[serde(default)]
+    pub multihoming: MultihomingConfig,
+    /// Encoding of coordinates in coordinate updates
+    #[serde(default)]
+    pub coordinate_precision: CoordinatePrecision,
+    /// When routed packets escalate from Gravity to Pressure and fallback modes
+    #[serde(default)]
+    pub mode_switch: ModeSwitchKind,
+    /// Sealing of Data payloads for their destination
+    #[serde(default)]
+    pub e2e: E2eConfig,
+    /// Onion relaying and the relays on onion routes we build
+    #[serde(default)]
+    pub onion: OnionConfig,
+    /// Chunking and fetching of payloads too large for one packet
+    #[serde(default)]
+    pub content: ContentConfig,
+    /// Access control zones enforced when forwarding
+    #[serde(default)]
+    pub zones: ZoneConfig,
+    /// Chaos experiments and their blast-radius limits
+    #[serde(default)]
+    pub chaos_experiments: ChaosExperimentConfig,
+    /// Local repair of the routing spanning tree
+    #[serde(default)]
+    pub tree_repair: TreeRepairConfig,
+    /// Geographic hint for the initial routing coordinate
+    #[serde(default)]
+    pub geo: GeoBootstrapConfig,
+    /// NAT and firewall reachability self-test
+    #[serde(default)]
+    pub nat: NatConfig,
+    /// Anchor derivation of this node and of the peers it accepts
+    #[serde(default)]
+    pub anchor: AnchorConfig,
+    /// Re-embedding after this node's partition heals
+    #[serde(default)]
+    pub healing: HealingConfig,
+    /// Tolerance of clock skew between this node and its peers
+    #[serde(default)]
+    pub clock: ClockConfig,
+    /// Measures taken as memory, CPU or file descriptors run short
+    #[serde(default)]
+    pub degradation: DegradationConfig,
+    /// Publication of this node's presence and records held for others
+    #[serde(default)]
+    pub presence: PresenceConfig,
+    /// Relay of coordinate updates along the spanning tree
+    #[serde(default)]
+    pub coordinate_tree: CoordinateTreeConfig,
+    /// Dropping of packets that cannot meet their deadline
+    #[serde(default)]
+    pub deadline: DeadlineConfig,
+    /// Neighbor cap derived from resources and local degree
+    #[serde(default)]
+    pub neighbor_cap: NeighborCapConfig,
+    /// Neighbor reputation from acks and signed reports of trusted peers
+    #[serde(default)]
+    pub reputation: ReputationConfig,
+    /// Black- and gray-hole detection by signed probes through each neighbor
+    #[serde(default)]
+    pub probing: ProbeConfig,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval_ms: 1000,
+            failure_timeout_ms: 5000,
+            discovery_interval_ms: 5000,
+            max_neighbors: 10,
+            chaos: ChaosSettings::default(),
+            qos: QosLimits::default(),
+            ttl: TtlPolicy::default(),
+            neighbor_policy: NeighborPolicyKind::default(),
+            compression: CompressionConfig::default(),
+            adaptive_heartbeat: AdaptiveHeartbeatConfig::default(),
+            traffic_matrix: TrafficMatrixConfig::default(),
+            dead_letter: DeadLetterConfig::default(),
+            coordinate_control: CoordinateControlConfig::default(),
+            broadcast: BroadcastConfig::default(),
+            route_cache: RouteCacheConfig::default(),
+            election: ElectionConfig::default(),
+            route_stats: RouteStatsConfig::default(),
+            keepalive: KeepaliveConfig::default(),
+            header_budget: HeaderBudgetConfig::default(),
+            fec: FecConfig::default(),
+            shaping: ShapingConfig::default(),
+            admission: AdmissionConfig::default(),
+            neighbor_exchange: NeighborExchangeConfig::default(),
+            path_cache: PathCacheConfig::default(),
+            convergence: ConvergenceConfig::default(),
+            api_access: ApiAccessConfig::default(),
+            multihoming: MultihomingConfig::default(),
+            coordinate_precision: CoordinatePrecision::default(),
+            mode_switch: ModeSwitchKind::default(),
+            e2e: E2eConfig::default(),
+            onion: OnionConfig::default(),
+            content: ContentConfig::default(),
+            zones: ZoneConfig::default(),
+            chaos_experiments: ChaosExperimentConfig::default(),
+            tree_repair: TreeRepairConfig::default(),
+            geo: GeoBootstrapConfig::default(),
+            nat: NatConfig::default(),
+            anchor: AnchorConfig::default(),
+            healing: HealingConfig::default(),
+            clock: ClockConfig::default(),
+            degradation: DegradationConfig::default(),
+            presence: PresenceConfig::default(),
+            coordinate_tree: CoordinateTreeConfig::default(),
+            deadline: DeadlineConfig::default(),
+            neighbor_cap: NeighborCapConfig::default(),
+            reputation: ReputationConfig::default(),
+            probing: ProbeConfig::default(),
+        }
+    }
+}
+
+impl NodeConfig {
+    /// Return a new config with `update` applied, or an error if the result is invalid
+    pub fn apply(&self, update: &ConfigUpdate) -> Result<NodeConfig, String> {
+        let mut config = self.clone();
+        if let Some(v) = update.heartbeat_interval_ms {
+            config.heartbeat_interval_ms = v;
+        }
+        if let Some(v) = update.failure_timeout_ms {
+            config.failure_timeout_ms = v;
+        }
+        if let Some(v) = update.discovery_interval_ms {
+            config.discovery_interval_ms = v;
+        }
+        if let Some(v) = update.max_neighbors {
+            config.max_neighbors = v;
+        }
+        if let Some(chaos) = &update.chaos {
+            config.chaos = chaos.clone();
+        }
+        if let Some(ttl) = &update.ttl {
+            config.ttl = ttl.clone();
+        }
+        if let Some(qos) = &update.qos {
+            config.qos = qos.clone();
+        }
+        if let Some(policy) = &update.neighbor_policy {
+            config.neighbor_policy = policy.clone();
+        }
+        if let Some(compression) = &update.compression {
+            config.compression = compression.clone();
+        }
+        if let Some(adaptive) = &update.adaptive_heartbeat {
+            config.adaptive_heartbeat = adaptive.clone();
+        }
+        if let Some(traffic) = &update.traffic_matrix {
+            config.traffic_matrix = traffic.clone();
+        }
+        if let Some(dead_letter) = &update.dead_letter {
+            config.dead_letter = dead_letter.clone();
+        }
+        if let Some(control) = &update.coordinate_control {
+            config.coordinate_control = control.clone();
+        }
+        if let Some(broadcast) = &update.broadcast {
+            config.broadcast = broadcast.clone();
+        }
+        if let Some(route_cache) = &update.route_cache {
+            config.route_cache = route_cache.clone();
+        }
+        if let Some(election) = &update.election {
+            config.election = election.clone();
+        }
+        if let Some(route_stats) = &update.route_stats {
+            config.route_stats = route_stats.clone();
+        }
+        if let Some(keepalive) = &update.keepalive {
+            config.keepalive = keepalive.clone();
+        }
+        if let Some(header_budget) = &update.header_budget {
+            config.header_budget = header_budget.clone();
+        }
+        if let Some(fec) = &update.fec {
+            config.fec = fec.clone();
+        }
+        if let Some(shaping) = &update.shaping {
+            config.shaping = shaping.clone();
+        }
+        if let Some(admission) = &update.admission {
+            config.admission = admission.clone();
+        }
+        if let Some(neighbor_exchange) = &update.neighbor_exchange {
+            config.neighbor_exchange = neighbor_exchange.clone();
+        }
+        if let Some(path_cache) = &update.path_cache {
+            config.path_cache = path_cache.clone();
+        }
+        if let Some(convergence) = &update.convergence {
+            config.convergence = convergence.clone();
+        }
+        if let Some(api_access) = &update.api_access {
+            config.api_access = api_access.clone();
+        }
+        if let Some(multihoming) = &update.multihoming {
+            config.multihoming = multihoming.clone();
+        }
+        if let Some(precision) = update.coordinate_precision {
+            config.coordinate_precision = precision;
+        }
+        if let Some(mode_switch) = &update.mode_switch {
+            config.mode_switch = mode_switch.clone();
+        }
+        if let Some(e2e) = &update.e2e {
+            config.e2e = e2e.clone();
+        }
+        if let Some(onion) = &update.onion {
+            config.onion = onion.clone();
+        }
+        if let Some(content) = &update.content {
+            config.content = content.clone();
+        }
+        if let Some(zones) = &update.zones {
+            config.zones = zones.clone();
+        }
+        if let Some(experiments) = &update.chaos_experiments {
+            config.chaos_experiments = experiments.clone();
+        }
+        if let Some(tree_repair) = &update.tree_repair {
+            config.tree_repair = tree_repair.clone();
+        }
+        if let Some(geo) = &update.geo {
+            config.geo = geo.clone();
+        }
+        if let Some(nat) = &update.nat {
+            config.nat = nat.clone();
+        }
+        if let Some(anchor) = &update.anchor {
+            config.anchor = anchor.clone();
+        }
+        if let Some(healing) = &update.healing {
+            config.healing = healing.clone();
+        }
+        if let Some(clock) = &update.clock {
+            config.clock = clock.clone();
+        }
+        if let Some(degradation) = &update.degradation {
+            config.degradation = degradation.clone();
+        }
+        if let Some(presence) = &update.presence {
+            config.presence = presence.clone();
+        }
+        if let Some(coordinate_tree) = &update.coordinate_tree {
+            config.coordinate_tree = coordinate_tree.clone();
+        }
+        if let Some(deadline) = &update.deadline {
+            config.deadline = deadline.clone();
+        }
+        if let Some(neighbor_cap) = &update.neighbor_cap {
+            config.neighbor_cap = neighbor_cap.clone();
+        }
+        if let Some(reputation) = &update.reputation {
+            config.reputation = reputation.clone();
+        }
+        if let Some(probing) = &update.probing {
+            config.probing = probing.clone();
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check that all values are usable
+    pub fn validate(&self) -> Result<(), String> {
+        if self.heartbeat_interval_ms == 0 || self.discovery_interval_ms == 0 {
+            return Err("Intervals must be positive".to_string());
+        }
+        if self.failure_timeout_ms <= self.heartbeat_interval_ms {
+            return Err("failure_timeout_ms must exceed heartbeat_interval_ms".to_string());
+        }
+        if self.max_neighbors == 0 {
+            return Err("max_neighbors must be positive".to_string());
+        }
+        if self.qos.max_inflight_packets == 0 {
+            return Err("max_inflight_packets must be positive".to_string());
+        }
+        if self.qos.congestion_mark_packets == 0 {
+            return Err("congestion_mark_packets must be positive".to_string());
+        }
+        if self.keepalive.write_timeout_ms == 0 {
+            return Err("keepalive write_timeout_ms must be positive".to_string());
+        }
+        self.ttl.validate()?;
+        self.neighbor_policy.validate()?;
+        self.compression.validate()?;
+        self.adaptive_heartbeat.validate()?;
+        self.traffic_matrix.validate()?;
+        self.dead_letter.validate()?;
+        self.coordinate_control.validate()?;
+        self.broadcast.validate()?;
+        self.route_cache.validate()?;
+        self.election.validate()?;
+        self.route_stats.validate()?;
+        self.header_budget.validate()?;
+        self.fec.validate()?;
+        self.shaping.validate()?;
+        self.admission.validate()?;
+        self.neighbor_exchange.validate()?;
+        self.path_cache.validate()?;
+        self.convergence.validate()?;
+        self.api_access.validate()?;
+        self.multihoming.validate()?;
+        self.mode_switch.validate()?;
+        self.e2e.validate()?;
+        self.onion.validate()?;
+        self.content.validate()?;
+        self.zones.validate()?;
+        self.chaos_experiments.validate()?;
+        if self.chaos_experiments.uses_delivery_ratio() && !self.route_stats.enabled {
+            return Err("Aborting chaos experiments on delivery ratio needs route_stats enabled".to_string());
+        }
+        self.tree_repair.validate()?;
+        self.geo.validate()?;
+        self.nat.validate()?;
+        self.anchor.validate()?;
+        self.healing.validate()?;
+        self.clock.validate()?;
+        self.degradation.validate()?;
+        self.presence.validate()?;
+        self.coordinate_tree.validate()?;
+        self.deadline.validate()?;
+        self.neighbor_cap.validate()?;
+        self.reputation.validate()?;
+        self.probing.validate()?;
+        let chaos = &self.chaos;
+        if !(0.0..=1.0).contains(&chaos.packet_drop_rate)
+            || !(0.0..=1.0).contains(&chaos.partition_probability)
+        {
+            return Err("Chaos probabilities must be in [0, 1]".to_string());
+        }
+        if chaos.delay_range_ms.0 > chaos.delay_range_ms.1 {
+            return Err("Chaos delay range min exceeds max".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Partial configuration change; absent fields keep their current value
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfigUpdate {
+    pub heartbeat_interval_ms: Option<u64>,
+    pub failure_timeout_ms: Option<u64>,
+    pub discovery_interval_ms: Option<u64>,
+    pub max_neighbors: Option<usize>,
+    pub chaos: Option<ChaosSettings>,
+    pub qos: Option<QosLimits>,
+    pub ttl: Option<TtlPolicy>,
+    pub neighbor_policy: Option<NeighborPolicyKind>,
+    pub compression: Option<CompressionConfig>,
+    pub adaptive_heartbeat: Option<AdaptiveHeartbeatConfig>,
+    pub traffic_matrix: Option<TrafficMatrixConfig>,
+    pub dead_letter: Option<DeadLetterConfig>,
+    pub coordinate_control: Option<CoordinateControlConfig>,
+    pub broadcast: Option<BroadcastConfig>,
+    pub route_cache: Option<RouteCacheConfig>,
+    pub election: Option<ElectionConfig>,
+    pub route_stats: Option<RouteStatsConfig>,
+    pub keepalive: Option<KeepaliveConfig>,
+    pub header_budget: Option<HeaderBudgetConfig>,
+    pub fec: Option<FecConfig>,
+    pub shaping: Option<ShapingConfig>,
+    pub admission: Option<AdmissionConfig>,
+    pub neighbor_exchange: Option<NeighborExchangeConfig>,
+    pub path_cache: Option<PathCacheConfig>,
+    pub convergence: Option<ConvergenceConfig>,
+    pub api_access: Option<ApiAccessConfig>,
+    pub multihoming: Option<MultihomingConfig>,
+    pub coordinate_precision: Option<CoordinatePrecision>,
+    pub mode_switch: Option<ModeSwitchKind>,
+    pub e2e: Option<E2eConfig>,
+    pub onion: Option<OnionConfig>,
+    pub content: Option<ContentConfig>,
+    pub zones: Option<ZoneConfig>,
+    pub chaos_experiments: Option<ChaosExperimentConfig>,
+    pub tree_repair: Option<TreeRepairConfig>,
+    pub geo: Option<GeoBootstrapConfig>,
+    pub nat: Option<NatConfig>,
+    pub anchor: Option<AnchorConfig>,
+    pub healing: Option<HealingConfig>,
+    pub clock: Option<ClockConfig>,
+    pub degradation: Option<DegradationConfig>,
+    pub presence: Option<PresenceConfig>,
+    pub coordinate_tree: Option<CoordinateTreeConfig>,
+    pub deadline: Option<DeadlineConfig>,
+    pub neighbor_cap: Option<NeighborCapConfig>,
+    pub reputation: Option<ReputationConfig>,
+    pub probing: Option<ProbeConfig>,
+}
+
+impl ConfigUpdate {
+    /// Parse an update from JSON
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse config: {}", e))
+    }
+
+    /// Load an update from a JSON config file
+    pub fn load_from_file(path: &std::path::Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config file: {}", e))?;
+        Self::from_json(&json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_update() {
+        let config = NodeConfig::default();
+        let update =
+            ConfigUpdate::from_json(r#"{"max_neighbors": 20, "qos": {"max_inflight_packets": 64}}"#)
+                .unwrap();
+
+        let updated = config.apply(&update).unwrap();
+        assert_eq!(updated.max_neighbors, 20);
+        assert_eq!(updated.qos.max_inflight_packets, 64);
+        assert_eq!(updated.heartbeat_interval_ms, config.heartbeat_interval_ms);
+        assert_eq!(updated.chaos, config.chaos);
+    }
+
+    #[test]
+    fn test_invalid_update_rejected() {
+        let config = NodeConfig::default();
+
+        let update = ConfigUpdate {
+            failure_timeout_ms: Some(500),
+            ..ConfigUpdate::default()
+        };
+        assert!(config.apply(&update).is_err());
+
+        let chaos = ChaosSettings {
+            packet_drop_rate: 1.5,
+            ..ChaosSettings::default()
+        };
+        let update = ConfigUpdate {
+            chaos: Some(chaos),
+            ..ConfigUpdate::default()
+        };
+        assert!(config.apply(&update).is_err());
+    }
+}
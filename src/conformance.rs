@@ -0,0 +1,700 @@
+//! Conformance Test Vectors and Remote Compliance Checks
+//!
+//! Implementations in other languages join the overlay by matching this one
+//! bit for bit where it matters: the geometry every routing decision rests
+//! on, the anchors nodes derive from IDs, the MessagePack wire format and
+//! Ed25519 packet signatures. `TestVectors` collects canonical inputs and
+//! the outputs this implementation produces for them, together with greedy
+//! routing paths on small fixture graphs, as one JSON document that a third
+//! party can check its own code against. `TestVectors::verify` checks the
+//! document against this implementation.
+//!
+//! Vectors only cover computations. `probe` checks an implementation over
+//! the wire instead: it joins the remote node as a neighbor, sends it
+//! discovery, data and malformed packets, and scores what it answers. The `drfe-conformance` binary runs both.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ed25519_dalek::{Signer, SigningKey};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::compression::CompressionAlgorithm;
+use crate::coordinate_precision::CoordinatePrecision;
+use crate::coordinates::{AnchorAlgorithm, AnchorCoordinate, NodeId, RoutingCoordinate};
+use crate::fec::FecScheme;
+use crate::network::{Packet, PacketType, MAX_PACKET_SIZE, PROTOCOL_VERSION};
+use crate::routing::{GPRouter, RoutingNode};
+use crate::PoincareDiskPoint;
+
+/// Version of the vector document format
+pub const VECTORS_VERSION: u32 = 1;
+
+/// Absolute tolerance on floating-point outputs
+pub const TOLERANCE: f64 = 1e-12;
+
+/// Hyperbolic distance between two points
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DistanceVector {
+    pub a: PoincareDiskPoint,
+    pub b: PoincareDiskPoint,
+    pub distance: f64,
+}
+
+/// Möbius sum `a ⊕ b`, absent where it leaves the disk or is undefined
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MobiusVector {
+    pub a: PoincareDiskPoint,
+    pub b: PoincareDiskPoint,
+    pub sum: Option<PoincareDiskPoint>,
+}
+
+/// Anchor of a node ID under an anchor algorithm
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchorVector {
+    pub id: String,
+    pub algorithm: AnchorAlgorithm,
+    /// Overlay anchor key of the keyed algorithm, hex
+    pub key: Option<String>,
+    pub point: PoincareDiskPoint,
+}
+
+/// A packet, as fields and as its MessagePack encoding
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PacketVector {
+    pub name: String,
+    pub packet: serde_json::Value,
+    /// MessagePack encoding, hex
+    pub msgpack: String,
+}
+
+/// An Ed25519 packet signature, all byte strings hex
+///
+/// The message is the MessagePack encoding of the packet without its
+/// signature. An invalid vector carries a signature that must be rejected.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignatureVector {
+    pub name: String,
+    pub seed: String,
+    pub public_key: String,
+    pub message: String,
+    pub signature: String,
+    pub valid: bool,
+}
+
+/// Greedy routing from `source` to `destination`, aimed at the destination's coordinate
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteVector {
+    pub source: String,
+    pub destination: String,
+    pub ttl: u32,
+    pub delivered: bool,
+    pub path: Vec<String>,
+    pub gravity_hops: u32,
+    pub pressure_hops: u32,
+    pub tree_hops: u32,
+}
+
+/// A small overlay and the routes taken between every pair of its nodes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoutingGraph {
+    pub name: String,
+    pub nodes: Vec<(String, PoincareDiskPoint)>,
+    pub edges: Vec<(String, String)>,
+    pub routes: Vec<RouteVector>,
+}
+
+impl RoutingGraph {
+    fn router(&self) -> GPRouter {
+        let mut router = GPRouter::new();
+        for (id, point) in &self.nodes {
+            router.add_node(RoutingNode::new(NodeId::new(id.as_str()), RoutingCoordinate::new(*point, 0)));
+        }
+        for (a, b) in &self.edges {
+            router.add_edge(&NodeId::new(a.as_str()), &NodeId::new(b.as_str()));
+        }
+        router
+    }
+
+    fn route(router: &GPRouter, source: &str, destination: &str, ttl: u32) -> Option<RouteVector> {
+        let destination_id = NodeId::new(destination);
+        let target = router.get_node(&destination_id)?.coord.point;
+        let result = router.simulate_delivery(&NodeId::new(source), &destination_id, target, ttl);
+        Some(RouteVector {
+            source: source.to_string(),
+            destination: destination.to_string(),
+            ttl,
+            delivered: result.success,
+            path: result.path.into_iter().map(|id| id.0).collect(),
+            gravity_hops: result.gravity_hops,
+            pressure_hops: result.pressure_hops,
+            tree_hops: result.tree_hops,
+        })
+    }
+
+    /// The graph with routes between every ordered pair of its nodes
+    fn with_routes(name: &str, nodes: &[(&str, f64, f64)], edges: &[(&str, &str)]) -> Self {
+        let mut graph = Self {
+            name: name.to_string(),
+            nodes: nodes
+                .iter()
+                .map(|(id, x, y)| (id.to_string(), PoincareDiskPoint::new(*x, *y).expect("fixture point inside the disk")))
+                .collect(),
+            edges: edges.iter().map(|(a, b)| (a.to_string(), b.to_string())).collect(),
+            routes: Vec::new(),
+        };
+        let router = graph.router();
+        for (source, ..) in nodes {
+            for (destination, ..) in nodes.iter().filter(|(d, ..)| d != source) {
+                graph.routes.extend(Self::route(&router, source, destination, 16));
+            }
+        }
+        graph
+    }
+}
+
+/// A vector whose recorded output this implementation does not reproduce
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub category: &'static str,
+    pub vector: String,
+    pub detail: String,
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}: {}", self.category, self.vector, self.detail)
+    }
+}
+
+/// The conformance test vectors
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestVectors {
+    pub version: u32,
+    pub protocol_version: u8,
+    pub tolerance: f64,
+    pub distance: Vec<DistanceVector>,
+    pub mobius: Vec<MobiusVector>,
+    pub anchors: Vec<AnchorVector>,
+    pub packets: Vec<PacketVector>,
+    pub signatures: Vec<SignatureVector>,
+    pub routing: Vec<RoutingGraph>,
+}
+
+fn point(x: f64, y: f64) -> PoincareDiskPoint {
+    PoincareDiskPoint::new(x, y).expect("vector point inside the disk")
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+fn close(a: f64, b: f64) -> bool {
+    a == b || (a - b).abs() <= TOLERANCE
+}
+
+fn points_close(a: &PoincareDiskPoint, b: &PoincareDiskPoint) -> bool {
+    close(a.x, b.x) && close(a.y, b.y)
+}
+
+/// Fix the header fields that vary between runs
+fn canonical(mut packet: Packet) -> Packet {
+    packet.header.timestamp = 1_700_000_000_000;
+    packet.header.packet_id = format!("{}-{}-vector", packet.header.source.0, packet.header.destination.0);
+    packet.header.sequence = 7;
+    packet
+}
+
+fn data_packet() -> Packet {
+    canonical(Packet::new_data(NodeId::new("alice"), NodeId::new("bob"), point(0.3, -0.2), b"hello".to_vec(), 64))
+}
+
+impl TestVectors {
+    /// Vectors for the canonical inputs, computed by this implementation
+    pub fn generate() -> Self {
+        let pairs = [
+            (point(0.0, 0.0), point(0.5, 0.0)),
+            (point(0.1, 0.2), point(-0.3, 0.4)),
+            (point(0.9, 0.0), point(-0.9, 0.0)),
+            (point(0.25, -0.125), point(0.25, -0.125)),
+            (point(0.6, 0.7), point(0.61, 0.7)),
+            (point(-0.05, 0.99), point(0.05, -0.99)),
+        ];
+        let distance = pairs
+            .iter()
+            .map(|(a, b)| DistanceVector { a: *a, b: *b, distance: a.hyperbolic_distance(b) })
+            .collect();
+        let mobius = pairs
+            .iter()
+            .chain(&[(point(0.5, 0.5), point(-0.5, -0.5)), (point(0.3, -0.4), point(0.0, 0.0))])
+            .map(|(a, b)| MobiusVector { a: *a, b: *b, sum: a.mobius_add(b) })
+            .collect();
+
+        let key = b"overlay-secret".as_slice();
+        let mut anchors = Vec::new();
+        for id in ["alice", "bob", "node_1", "", "ノード"] {
+            for algorithm in [AnchorAlgorithm::Sha256, AnchorAlgorithm::Sha512, AnchorAlgorithm::KeyedSha256] {
+                let key = (algorithm == AnchorAlgorithm::KeyedSha256).then_some(key);
+                anchors.push(AnchorVector {
+                    id: id.to_string(),
+                    algorithm,
+                    key: key.map(to_hex),
+                    point: AnchorCoordinate::derive(&NodeId::new(id), algorithm, key).point,
+                });
+            }
+        }
+
+        let seed = [7u8; 32];
+        let mut signed = data_packet();
+        signed.sign(&seed).expect("32-byte seed");
+        let ack = canonical(Packet::new_ack(NodeId::new("bob"), &data_packet().header));
+        let update = canonical(Packet::new_coordinate_update_at(
+            NodeId::new("alice"),
+            point(-0.45, 0.6),
+            42,
+            CoordinatePrecision::Fixed32,
+        ));
+        let heartbeat = canonical(Packet::new_heartbeat(NodeId::new("alice"), NodeId::new("bob")));
+        let packets = [("data", data_packet()), ("signed_data", signed.clone()), ("ack", ack), ("coordinate_update", update), ("heartbeat", heartbeat)]
+            .into_iter()
+            .map(|(name, packet)| PacketVector {
+                name: name.to_string(),
+                packet: serde_json::to_value(&packet).expect("packets serialize to JSON"),
+                msgpack: to_hex(&packet.to_msgpack().expect("packets serialize to MessagePack")),
+            })
+            .collect();
+
+        let signing_key = SigningKey::from_bytes(&seed);
+        let message = data_packet().to_msgpack().expect("packets serialize to MessagePack");
+        let signature = signed.signature.clone().unwrap_or_default();
+        let mut tampered = signature.clone();
+        tampered[0] ^= 1;
+        let signatures = [("data", signature, true), ("data_tampered", tampered, false)]
+            .into_iter()
+            .map(|(name, signature, valid)| SignatureVector {
+                name: name.to_string(),
+                seed: to_hex(&seed),
+                public_key: to_hex(signing_key.verifying_key().as_bytes()),
+                message: to_hex(&message),
+                signature: to_hex(&signature),
+                valid,
+            })
+            .collect();
+
+        // A diamond where greedy forwarding always succeeds, and a dead end
+        // in front of the destination that forces pressure recovery
+        let routing = vec![
+            RoutingGraph::with_routes(
+                "diamond",
+                &[("0", 0.0, 0.0), ("1", 0.0, 0.3), ("2", 0.3, 0.0), ("3", 0.5, 0.0), ("4", 0.15, -0.2)],
+                &[("0", "1"), ("0", "2"), ("0", "4"), ("1", "2"), ("2", "3"), ("2", "4")],
+            ),
+            RoutingGraph::with_routes(
+                "dead_end",
+                &[("s", -0.6, 0.0), ("w", 0.0, 0.0), ("b", -0.5, 0.6), ("c", 0.3, 0.7), ("d", 0.6, 0.0)],
+                &[("s", "w"), ("s", "b"), ("b", "c"), ("c", "d")],
+            ),
+        ];
+
+        Self {
+            version: VECTORS_VERSION,
+            protocol_version: PROTOCOL_VERSION,
+            tolerance: TOLERANCE,
+            distance,
+            mobius,
+            anchors,
+            packets,
+            signatures,
+            routing,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Vectors whose outputs this implementation does not reproduce
+    pub fn verify(&self) -> Vec<Mismatch> {
+        let mut mismatches = Vec::new();
+        let mut fail = |category: &'static str, vector: String, detail: String| {
+            mismatches.push(Mismatch { category, vector, detail });
+        };
+
+        for (i, v) in self.distance.iter().enumerate() {
+            let distance = v.a.hyperbolic_distance(&v.b);
+            if !close(distance, v.distance) {
+                fail("distance", i.to_string(), format!("expected {}, got {}", v.distance, distance));
+            }
+        }
+        for (i, v) in self.mobius.iter().enumerate() {
+            let sum = v.a.mobius_add(&v.b);
+            let matches = match (&sum, &v.sum) {
+                (Some(got), Some(expected)) => points_close(got, expected),
+                (got, expected) => got == expected,
+            };
+            if !matches {
+                fail("mobius", i.to_string(), format!("expected {:?}, got {:?}", v.sum, sum));
+            }
+        }
+        for v in &self.anchors {
+            let key = v.key.as_deref().map(from_hex);
+            if key.as_ref().is_some_and(Option::is_none) {
+                fail("anchor", v.id.clone(), "key is not hex".to_string());
+                continue;
+            }
+            let point = AnchorCoordinate::derive(&NodeId::new(v.id.as_str()), v.algorithm, key.flatten().as_deref()).point;
+            if !points_close(&point, &v.point) {
+                fail("anchor", format!("{} {:?}", v.id, v.algorithm), format!("expected {}, got {}", v.point, point));
+            }
+        }
+        for v in &self.packets {
+            let name = v.name.clone();
+            let Some(bytes) = from_hex(&v.msgpack) else {
+                fail("packet", name, "msgpack is not hex".to_string());
+                continue;
+            };
+            let packet = match Packet::from_msgpack(&bytes) {
+                Ok(packet) => packet,
+                Err(e) => {
+                    fail("packet", name, format!("does not decode: {}", e));
+                    continue;
+                }
+            };
+            if packet.to_msgpack().ok().as_deref() != Some(bytes.as_slice()) {
+                fail("packet", name, "does not re-encode to the same bytes".to_string());
+            } else if serde_json::to_value(&packet).ok().as_ref() != Some(&v.packet) {
+                fail("packet", name, "decodes to different fields".to_string());
+            }
+        }
+        for v in &self.signatures {
+            let name = v.name.clone();
+            let (Some(seed), Some(public_key), Some(message), Some(signature)) =
+                (from_hex(&v.seed), from_hex(&v.public_key), from_hex(&v.message), from_hex(&v.signature))
+            else {
+                fail("signature", name, "a field is not hex".to_string());
+                continue;
+            };
+            let Ok(seed) = <[u8; 32]>::try_from(seed) else {
+                fail("signature", name, "seed is not 32 bytes".to_string());
+                continue;
+            };
+            let key = SigningKey::from_bytes(&seed);
+            if key.verifying_key().as_bytes().as_slice() != public_key {
+                fail("signature", name, "public key does not match the seed".to_string());
+                continue;
+            }
+            if v.valid && key.sign(&message).to_bytes().as_slice() != signature {
+                fail("signature", name, "signing the message gives another signature".to_string());
+                continue;
+            }
+            let verified = Packet::from_msgpack(&message).is_ok_and(|mut packet| {
+                packet.signature = Some(signature);
+                packet.verify_signature(&public_key)
+            });
+            if verified != v.valid {
+                fail("signature", name, format!("expected valid = {}, got {}", v.valid, verified));
+            }
+        }
+        for graph in &self.routing {
+            let router = graph.router();
+            for v in &graph.routes {
+                let name = format!("{} {}->{}", graph.name, v.source, v.destination);
+                match RoutingGraph::route(&router, &v.source, &v.destination, v.ttl) {
+                    Some(route) if route == *v => {}
+                    Some(route) => fail("routing", name, format!("expected path {:?}, got {:?}", v.path, route.path)),
+                    None => fail("routing", name, "destination is not in the graph".to_string()),
+                }
+            }
+        }
+        mismatches
+    }
+}
+
+/// Settings of a remote conformance probe
+#[derive(Debug, Clone)]
+pub struct ProbeConfig {
+    /// Node ID the probe joins as
+    pub probe_id: NodeId,
+    /// Longest wait for each answer
+    pub timeout: Duration,
+    /// Longest wait for a heartbeat; zero skips the heartbeat check
+    pub heartbeat_wait: Duration,
+}
+
+impl Default for ProbeConfig {
+    fn default() -> Self {
+        Self {
+            probe_id: NodeId::new("drfe-conformance"),
+            timeout: Duration::from_secs(2),
+            heartbeat_wait: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Outcome of one remote check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "outcome", content = "detail")]
+pub enum CheckOutcome {
+    Pass,
+    Fail(String),
+    /// Not run, as when an earlier check it depends on failed
+    Skipped(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckResult {
+    pub name: String,
+    #[serde(flatten)]
+    pub outcome: CheckOutcome,
+}
+
+/// Results of probing a remote implementation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConformanceReport {
+    pub target: SocketAddr,
+    /// Node ID the remote answered discovery with
+    pub remote_id: Option<NodeId>,
+    pub checks: Vec<CheckResult>,
+}
+
+impl ConformanceReport {
+    fn record(&mut self, name: &str, outcome: CheckOutcome) {
+        self.checks.push(CheckResult { name: name.to_string(), outcome });
+    }
+
+    pub fn passed(&self) -> usize {
+        self.checks.iter().filter(|c| c.outcome == CheckOutcome::Pass).count()
+    }
+
+    /// Fraction of the checks run that passed; skipped checks count as failed
+    pub fn score(&self) -> f64 {
+        if self.checks.is_empty() {
+            return 0.0;
+        }
+        self.passed() as f64 / self.checks.len() as f64
+    }
+}
+
+/// The probe's end of its link to the remote
+///
+/// Nodes send heartbeats and discovery answers over UDP but other traffic
+/// over TCP, to the address they discovered a neighbor from, so the probe
+/// listens for TCP on the port of its UDP socket. Both feed one queue.
+struct ProbePeer {
+    socket: Arc<UdpSocket>,
+    target: SocketAddr,
+    received: mpsc::UnboundedReceiver<Result<Packet, String>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl ProbePeer {
+    async fn bind(target: SocketAddr) -> std::io::Result<Self> {
+        let bind = if target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = Arc::new(UdpSocket::bind(bind).await?);
+        let listener = TcpListener::bind(socket.local_addr()?).await?;
+        let (sender, received) = mpsc::unbounded_channel();
+        let decode = |bytes: &[u8]| Packet::from_msgpack(bytes).map_err(|e| format!("undecodable packet: {}", e));
+
+        let udp = {
+            let (socket, sender) = (Arc::clone(&socket), sender.clone());
+            tokio::spawn(async move {
+                let mut buffer = vec![0u8; MAX_PACKET_SIZE];
+                while let Ok((len, from)) = socket.recv_from(&mut buffer).await {
+                    if from == target && sender.send(decode(&buffer[..len])).is_err() {
+                        break;
+                    }
+                }
+            })
+        };
+        let tcp = tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    // Frames are a big-endian length and a packet; empty ones are keepalives
+                    while let Ok(len) = stream.read_u32().await {
+                        let len = len as usize;
+                        if len > MAX_PACKET_SIZE {
+                            let _ = sender.send(Err(format!("TCP frame of {} bytes", len)));
+                            break;
+                        }
+                        let mut frame = vec![0u8; len];
+                        if stream.read_exact(&mut frame).await.is_err() {
+                            break;
+                        }
+                        if len > 0 && sender.send(decode(&frame)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        Ok(Self { socket, target, received, tasks: vec![udp, tcp] })
+    }
+
+    async fn send(&self, packet: &Packet) -> std::io::Result<()> {
+        let bytes = packet.to_msgpack().map_err(std::io::Error::other)?;
+        self.socket.send_to(&bytes, self.target).await.map(|_| ())
+    }
+
+    /// First packet from the target within `wait` that `expected` accepts
+    ///
+    /// Packets that do not decode are errors: a conforming node only sends
+    /// valid packets, and the probe advertises no FEC or compression.
+    async fn expect(&mut self, wait: Duration, expected: impl Fn(&Packet) -> bool) -> Result<Packet, String> {
+        let deadline = Instant::now() + wait;
+        loop {
+            match tokio::time::timeout_at(deadline, self.received.recv()).await {
+                Ok(Some(Ok(packet))) if expected(&packet) => return Ok(packet),
+                Ok(Some(Ok(_))) => {}
+                Ok(Some(Err(e))) => return Err(e),
+                Ok(None) => return Err("probe sockets closed".to_string()),
+                Err(_) => return Err(format!("no answer within {} ms", wait.as_millis())),
+            }
+        }
+    }
+}
+
+impl Drop for ProbePeer {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// A discovery advertising the probe with no FEC or compression support
+fn probe_discovery(config: &ProbeConfig) -> Packet {
+    let coord = AnchorCoordinate::from_id(&config.probe_id).point;
+    let mut packet = Packet::new_discovery(config.probe_id.clone(), coord);
+    packet.payload = bincode::serialize(&(coord, Vec::<CompressionAlgorithm>::new(), Vec::<FecScheme>::new())).unwrap_or_default();
+    packet
+}
+
+/// Check a discovery answer, returning the remote's ID and coordinate
+fn check_discovery(packet: &Packet) -> Result<(NodeId, PoincareDiskPoint), String> {
+    if packet.header.version != PROTOCOL_VERSION {
+        return Err(format!("protocol version {}, expected {}", packet.header.version, PROTOCOL_VERSION));
+    }
+    // Every discovery payload starts with the sender's coordinate
+    let coord: PoincareDiskPoint =
+        bincode::deserialize(&packet.payload).map_err(|e| format!("payload has no coordinate: {}", e))?;
+    if PoincareDiskPoint::new(coord.x, coord.y).is_none() {
+        return Err(format!("coordinate {} outside the disk", coord));
+    }
+    Ok((packet.header.source.clone(), coord))
+}
+
+/// Probe the implementation listening on UDP `target` and score its answers
+///
+/// Checks, in order: a discovery is answered with a well-formed discovery,
+/// a data packet addressed to the remote is acknowledged, the remote sends
+/// its new neighbor heartbeats, and garbage or a packet from a future
+/// protocol version leaves it answering discovery.
+pub async fn probe(target: SocketAddr, config: &ProbeConfig) -> std::io::Result<ConformanceReport> {
+    let mut peer = ProbePeer::bind(target).await?;
+    let mut report = ConformanceReport { target, remote_id: None, checks: Vec::new() };
+    let is_discovery = |p: &Packet| p.header.packet_type == PacketType::Discovery;
+
+    peer.send(&probe_discovery(config)).await?;
+    let discovered = peer.expect(config.timeout, is_discovery).await.and_then(|p| check_discovery(&p));
+    let (remote, coord) = match discovered {
+        Ok(found) => found,
+        Err(e) => {
+            report.record("discovery", CheckOutcome::Fail(e));
+            for name in ["data_ack", "heartbeat", "malformed_input"] {
+                report.record(name, CheckOutcome::Skipped("no discovery answer".to_string()));
+            }
+            return Ok(report);
+        }
+    };
+    report.remote_id = Some(remote.clone());
+    report.record("discovery", CheckOutcome::Pass);
+
+    let data = Packet::new_data(config.probe_id.clone(), remote.clone(), coord, b"drfe-conformance".to_vec(), 8);
+    peer.send(&data).await?;
+    let packet_id = data.header.packet_id.clone();
+    let acked = peer
+        .expect(config.timeout, |p| p.ack_info().is_some_and(|(id, _)| id == packet_id))
+        .await
+        .and_then(|ack| match ack.header.destination == config.probe_id {
+            true => Ok(()),
+            false => Err(format!("ack addressed to {}", ack.header.destination)),
+        });
+    report.record("data_ack", acked.map_or_else(CheckOutcome::Fail, |_| CheckOutcome::Pass));
+
+    if config.heartbeat_wait.is_zero() {
+        report.record("heartbeat", CheckOutcome::Skipped("disabled".to_string()));
+    } else {
+        let heartbeat = peer
+            .expect(config.heartbeat_wait, |p| p.header.packet_type == PacketType::Heartbeat)
+            .await;
+        report.record("heartbeat", heartbeat.map_or_else(CheckOutcome::Fail, |_| CheckOutcome::Pass));
+    }
+
+    peer.socket.send_to(b"\x00not a packet\xff", target).await?;
+    let mut future = probe_discovery(config);
+    future.header.version = PROTOCOL_VERSION + 1;
+    peer.send(&future).await?;
+    peer.send(&probe_discovery(config)).await?;
+    let survived = peer.expect(config.timeout, |p| is_discovery(p) && p.header.version == PROTOCOL_VERSION).await;
+    report.record("malformed_input", survived.map_or_else(CheckOutcome::Fail, |_| CheckOutcome::Pass));
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_vectors_verify() {
+        let vectors = TestVectors::generate();
+        assert_eq!(vectors.verify(), Vec::new());
+        assert!(vectors.signatures.iter().any(|v| !v.valid));
+        // The dead end needs pressure recovery at least once
+        let dead_end = &vectors.routing[1];
+        assert!(dead_end.routes.iter().all(|r| r.delivered));
+        assert!(dead_end.routes.iter().any(|r| r.pressure_hops > 0));
+
+        let parsed = TestVectors::from_json(&vectors.to_json().unwrap()).unwrap();
+        assert_eq!(parsed.verify(), Vec::new());
+        assert_eq!(from_hex(&to_hex(&[0, 1, 0xab, 0xff])), Some(vec![0, 1, 0xab, 0xff]));
+        assert_eq!(from_hex("abc"), None);
+    }
+
+    #[test]
+    fn test_tampered_vectors_are_reported() {
+        let mut vectors = TestVectors::generate();
+        vectors.distance[1].distance += 1e-9;
+        vectors.anchors[0].point = point(0.0, 0.95);
+        vectors.packets[0].msgpack.replace_range(0..2, "00");
+        vectors.signatures[1].valid = true;
+        vectors.routing[0].routes[0].path.reverse();
+
+        let categories: Vec<&str> = vectors.verify().iter().map(|m| m.category).collect();
+        assert_eq!(categories, ["distance", "anchor", "packet", "signature", "routing"]);
+
+        let mut report = ConformanceReport { target: "127.0.0.1:1".parse().unwrap(), remote_id: None, checks: Vec::new() };
+        assert_eq!(report.score(), 0.0);
+        report.record("discovery", CheckOutcome::Pass);
+        report.record("data_ack", CheckOutcome::Fail("no answer".to_string()));
+        assert_eq!((report.passed(), report.score()), (1, 0.5));
+    }
+}
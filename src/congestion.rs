@@ -0,0 +1,261 @@
+//! ECN-Style Congestion Control
+//!
+//! Overloaded forwarders set a congestion-experienced bit on Data packets
+//! instead of dropping them, destinations echo the bit in their Acks, and
+//! sources keep an AIMD congestion window per destination. A source only
+//! sends while fewer packets than the window are unacknowledged, so senders
+//! back off before forwarders have to shed load.
+
+use std::collections::HashMap;
+
+use crate::coordinates::NodeId;
+use crate::memory::{self, MemoryFootprint, MemoryUsage};
+
+/// Congestion window settings
+#[derive(Debug, Clone)]
+pub struct CongestionConfig {
+    /// Window for a destination we have not heard from yet (packets)
+    pub initial_window: f64,
+    /// Window never shrinks below this
+    pub min_window: f64,
+    /// Window never grows beyond this
+    pub max_window: f64,
+    /// Unacknowledged packets older than this count as lost
+    pub ack_timeout_ms: u64,
+}
+
+impl Default for CongestionConfig {
+    fn default() -> Self {
+        Self {
+            initial_window: 10.0,
+            min_window: 1.0,
+            max_window: 1000.0,
+            ack_timeout_ms: 3000,
+        }
+    }
+}
+
+/// Snapshot of one destination's window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowStats {
+    /// Congestion window in packets
+    pub cwnd: f64,
+    /// Slow-start threshold in packets
+    pub ssthresh: f64,
+    /// Unacknowledged packets
+    pub inflight: usize,
+    /// Smoothed RTT from acknowledgments
+    pub srtt_ms: Option<f64>,
+    /// Acks received with the congestion bit set
+    pub congestion_marks: u64,
+    /// Packets that timed out without an ack
+    pub losses: u64,
+}
+
+#[derive(Debug, Clone)]
+struct CongestionWindow {
+    cwnd: f64,
+    ssthresh: f64,
+    /// (packet ID, sent at) in send order
+    inflight: Vec<(String, u64)>,
+    srtt_ms: Option<f64>,
+    last_decrease_ms: Option<u64>,
+    congestion_marks: u64,
+    losses: u64,
+}
+
+impl CongestionWindow {
+    fn new(config: &CongestionConfig) -> Self {
+        Self {
+            cwnd: config.initial_window,
+            ssthresh: config.max_window,
+            inflight: Vec::new(),
+            srtt_ms: None,
+            last_decrease_ms: None,
+            congestion_marks: 0,
+            losses: 0,
+        }
+    }
+
+    /// Multiplicative decrease, at most once per smoothed RTT
+    fn decrease(&mut self, config: &CongestionConfig, now_ms: u64) {
+        let recent = match (self.last_decrease_ms, self.srtt_ms) {
+            (Some(last), Some(srtt)) => (now_ms.saturating_sub(last) as f64) < srtt,
+            _ => false,
+        };
+        if recent {
+            return;
+        }
+        self.ssthresh = (self.cwnd / 2.0).max(config.min_window);
+        self.cwnd = self.ssthresh;
+        self.last_decrease_ms = Some(now_ms);
+    }
+
+    /// Slow start below ssthresh, then additive increase
+    fn increase(&mut self, config: &CongestionConfig) {
+        self.cwnd += if self.cwnd < self.ssthresh { 1.0 } else { 1.0 / self.cwnd };
+        self.cwnd = self.cwnd.min(config.max_window);
+    }
+
+    fn expire(&mut self, config: &CongestionConfig, now_ms: u64) {
+        let before = self.inflight.len();
+        self.inflight
+            .retain(|(_, sent_at)| now_ms.saturating_sub(*sent_at) <= config.ack_timeout_ms);
+        let lost = before - self.inflight.len();
+        if lost > 0 {
+            self.losses += lost as u64;
+            self.decrease(config, now_ms);
+        }
+    }
+}
+
+/// Per-destination congestion windows of a sending node
+#[derive(Debug, Clone, Default)]
+pub struct CongestionController {
+    pub config: CongestionConfig,
+    windows: HashMap<NodeId, CongestionWindow>,
+}
+
+impl CongestionController {
+    pub fn new(config: CongestionConfig) -> Self {
+        Self {
+            config,
+            windows: HashMap::new(),
+        }
+    }
+
+    /// Reserve a window slot for a packet to `destination`
+    ///
+    /// Returns false if the window is full; the packet should not be sent.
+    pub fn try_send(&mut self, destination: &NodeId, packet_id: &str, now_ms: u64) -> bool {
+        let config = &self.config;
+        let window = self
+            .windows
+            .entry(destination.clone())
+            .or_insert_with(|| CongestionWindow::new(config));
+        window.expire(config, now_ms);
+        if window.inflight.len() as f64 >= window.cwnd.floor().max(1.0) {
+            return false;
+        }
+        window.inflight.push((packet_id.to_string(), now_ms));
+        true
+    }
+
+    /// Release a reserved slot for a packet that was never sent
+    pub fn cancel(&mut self, destination: &NodeId, packet_id: &str) {
+        if let Some(window) = self.windows.get_mut(destination) {
+            if let Some(pos) = window.inflight.iter().position(|(id, _)| id == packet_id) {
+                window.inflight.remove(pos);
+            }
+        }
+    }
+
+    /// Process an Ack from `destination`, echoing the congestion bit
+    ///
+    /// Returns false for acks that match no outstanding packet.
+    pub fn on_ack(&mut self, destination: &NodeId, packet_id: &str, congestion_experienced: bool, now_ms: u64) -> bool {
+        let Some(window) = self.windows.get_mut(destination) else {
+            return false;
+        };
+        let Some(pos) = window.inflight.iter().position(|(id, _)| id == packet_id) else {
+            return false;
+        };
+        let (_, sent_at) = window.inflight.remove(pos);
+
+        let sample = now_ms.saturating_sub(sent_at) as f64;
+        window.srtt_ms = Some(window.srtt_ms.map_or(sample, |srtt| 0.875 * srtt + 0.125 * sample));
+
+        if congestion_experienced {
+            window.congestion_marks += 1;
+            window.decrease(&self.config, now_ms);
+        } else {
+            window.increase(&self.config);
+        }
+        true
+    }
+
+    /// Window state for a destination
+    pub fn window(&self, destination: &NodeId) -> Option<WindowStats> {
+        self.windows.get(destination).map(|w| WindowStats {
+            cwnd: w.cwnd,
+            ssthresh: w.ssthresh,
+            inflight: w.inflight.len(),
+            srtt_ms: w.srtt_ms,
+            congestion_marks: w.congestion_marks,
+            losses: w.losses,
+        })
+    }
+}
+
+/// Entries are packets awaiting an ack
+impl MemoryFootprint for CongestionController {
+    fn memory(&self) -> MemoryUsage {
+        let mut usage = MemoryUsage::new(0, memory::table_bytes::<NodeId, CongestionWindow>(self.windows.capacity()));
+        for (destination, window) in &self.windows {
+            let packet_ids: usize = window.inflight.iter().map(|(id, _)| id.capacity()).sum();
+            usage.entries += window.inflight.len();
+            usage.bytes += memory::id_bytes(destination)
+                + memory::vec_bytes::<(String, u64)>(window.inflight.capacity())
+                + packet_ids;
+        }
+        usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_limits_and_grows() {
+        let mut cc = CongestionController::default();
+        let dest = NodeId::new("d");
+
+        for i in 0..10 {
+            assert!(cc.try_send(&dest, &format!("p{}", i), 0));
+        }
+        assert!(!cc.try_send(&dest, "p10", 0));
+
+        // Slow start: each clean ack opens the window by one
+        assert!(cc.on_ack(&dest, "p0", false, 20));
+        assert!(!cc.on_ack(&dest, "p0", false, 20));
+        let stats = cc.window(&dest).unwrap();
+        assert_eq!(stats.cwnd, 11.0);
+        assert_eq!(stats.inflight, 9);
+        assert_eq!(stats.srtt_ms, Some(20.0));
+        assert!(cc.try_send(&dest, "p10", 20));
+        assert!(cc.try_send(&dest, "p11", 20));
+        assert!(!cc.try_send(&dest, "p12", 20));
+    }
+
+    #[test]
+    fn test_congestion_mark_halves_once_per_rtt() {
+        let mut cc = CongestionController::default();
+        let dest = NodeId::new("d");
+        for i in 0..4 {
+            cc.try_send(&dest, &format!("p{}", i), 0);
+        }
+
+        cc.on_ack(&dest, "p0", true, 100);
+        assert_eq!(cc.window(&dest).unwrap().cwnd, 5.0);
+        // Marks within the same RTT belong to the same congestion event
+        cc.on_ack(&dest, "p1", true, 110);
+        assert_eq!(cc.window(&dest).unwrap().cwnd, 5.0);
+        cc.on_ack(&dest, "p2", true, 300);
+        assert_eq!(cc.window(&dest).unwrap().cwnd, 2.5);
+        assert_eq!(cc.window(&dest).unwrap().congestion_marks, 3);
+
+        // Congestion avoidance grows by 1/cwnd per ack
+        cc.on_ack(&dest, "p3", false, 310);
+        assert!((cc.window(&dest).unwrap().cwnd - 2.9).abs() < 1e-9);
+
+        // Timeouts count as losses and free their slots
+        cc.try_send(&dest, "p4", 400);
+        cc.try_send(&dest, "p5", 400);
+        assert!(!cc.try_send(&dest, "p6", 400));
+        assert!(cc.try_send(&dest, "p6", 400 + cc.config.ack_timeout_ms + 1));
+        let stats = cc.window(&dest).unwrap();
+        assert_eq!(stats.losses, 2);
+        assert_eq!(stats.cwnd, 1.45);
+    }
+}
@@ -0,0 +1,691 @@
+//! Content-Addressed Chunked Transfer
+//!
+//! Payloads above `ContentConfig::threshold` do not travel as one packet.
+//! The sender splits them into chunks named by their SHA-256 hash, keeps
+//! the chunks in its chunk store and routes a small manifest to the
+//! destination instead: the hash of the whole payload, its size, the chunk
+//! hashes in order and the nodes holding the chunks. A manifest can also be
+//! handed around out of band, such as posted in a chat room, and fetched
+//! by anyone who receives it.
+//!
+//! The fetching node requests up to `parallel` chunks at a time, spread
+//! over the holders. A holder answers a request by naming a reliable stream
+//! and sending the chunk over it, so lost segments are retransmitted by the
+//! stream layer rather than by re-requesting the chunk. Every chunk is
+//! checked against its hash before it is stored and the reassembled payload
+//! against the manifest, so a faulty holder cannot substitute data; a chunk
+//! that fails verification, is missing or times out is requested from the
+//! next holder. Fetched chunks stay in the store, so a node that fetched
+//! content can serve it too. A draining node hands its chunks to a
+//! neighbor before leaving, so they stay in the overlay.
+//!
+//! Nothing a peer sends can grow this state without bound: a pushed
+//! manifest must name its sender as a holder and counts against
+//! `max_downloads`, a chunk stream is only accepted from the holder it was
+//! requested from, and handovers are capped by `max_replica_streams`.
+//!
+//! The state machines here are transport-agnostic: `DistributedNode` moves
+//! the messages and streams they produce.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::coordinates::NodeId;
+
+/// Chunked transfer settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContentConfig {
+    /// Payloads larger than this many bytes are chunked
+    pub threshold: usize,
+    /// Bytes per chunk
+    pub chunk_size: usize,
+    /// Chunk requests outstanding per transfer
+    pub parallel: usize,
+    /// Time a holder has to finish sending a chunk
+    pub request_timeout_ms: u64,
+    /// Bytes of chunks kept for serving; the oldest are evicted first
+    pub store_bytes: usize,
+    /// Transfers fetched at once; further manifests are refused
+    pub max_downloads: usize,
+    /// Chunk handovers from draining neighbors accepted at once
+    pub max_replica_streams: usize,
+}
+
+impl Default for ContentConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 64 * 1024,
+            chunk_size: 32 * 1024,
+            parallel: 4,
+            request_timeout_ms: 30_000,
+            store_bytes: 64 * 1024 * 1024,
+            max_downloads: 16,
+            max_replica_streams: 64,
+        }
+    }
+}
+
+impl ContentConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.chunk_size == 0 || self.chunk_size > self.threshold {
+            return Err("Content chunk_size must be between 1 and threshold".to_string());
+        }
+        if self.parallel == 0 {
+            return Err("Content parallel must be at least 1".to_string());
+        }
+        if self.request_timeout_ms == 0 {
+            return Err("Content request_timeout_ms must be positive".to_string());
+        }
+        if self.max_downloads == 0 || self.max_replica_streams == 0 {
+            return Err("Content max_downloads and max_replica_streams must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// SHA-256 hash naming a chunk or a whole payload
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ContentId(pub [u8; 32]);
+
+impl ContentId {
+    pub fn of(data: &[u8]) -> Self {
+        Self(Sha256::digest(data).into())
+    }
+}
+
+impl fmt::Display for ContentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{:02x}", b))
+    }
+}
+
+impl fmt::Debug for ContentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ContentId({})", self)
+    }
+}
+
+/// Everything needed to fetch and verify a chunked payload
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Hash of the whole payload
+    pub content_id: ContentId,
+    pub size: u64,
+    pub chunk_size: u32,
+    /// Chunk hashes in payload order
+    pub chunks: Vec<ContentId>,
+    /// Nodes holding every chunk
+    pub holders: Vec<NodeId>,
+}
+
+impl Manifest {
+    /// Split `data` into chunks held by `holder`
+    pub fn split(data: &[u8], chunk_size: usize, holder: NodeId) -> (Self, Vec<Vec<u8>>) {
+        let pieces: Vec<Vec<u8>> = data.chunks(chunk_size.max(1)).map(<[u8]>::to_vec).collect();
+        let manifest = Self {
+            content_id: ContentId::of(data),
+            size: data.len() as u64,
+            chunk_size: chunk_size as u32,
+            chunks: pieces.iter().map(|p| ContentId::of(p)).collect(),
+            holders: vec![holder],
+        };
+        (manifest, pieces)
+    }
+
+    /// Check that the chunk list can describe the payload size
+    pub fn validate(&self) -> Result<(), ContentError> {
+        let expected = self.size.div_ceil(u64::from(self.chunk_size.max(1)));
+        if self.chunk_size == 0 || self.chunks.len() as u64 != expected {
+            return Err(ContentError::BadManifest(format!(
+                "{} chunks of {} bytes cannot hold {} bytes",
+                self.chunks.len(),
+                self.chunk_size,
+                self.size
+            )));
+        }
+        if self.holders.is_empty() {
+            return Err(ContentError::BadManifest("no holders".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Transfer control messages, routed like Data
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ContentMessage {
+    /// A payload the destination should fetch
+    Manifest(Manifest),
+    /// Ask a holder for a chunk
+    Request { chunk: ContentId },
+    /// The holder is sending `chunk` on the stream `stream_id` it opened
+    Serving { chunk: ContentId, stream_id: u64 },
+    /// The holder does not have `chunk`
+    Missing { chunk: ContentId },
+    /// A draining holder is handing `chunk` over on the stream `stream_id`
+    Replica { chunk: ContentId, stream_id: u64 },
+}
+
+/// Chunked transfer failures
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ContentError {
+    #[error("Malformed manifest: {0}")]
+    BadManifest(String),
+
+    #[error("Reassembled content {0} failed verification")]
+    ContentMismatch(ContentId),
+
+    #[error("No holder could supply chunk {0}")]
+    NoHolder(ContentId),
+
+    #[error("Too many transfers in progress")]
+    Busy,
+}
+
+impl ContentError {
+    /// Stable identifier for programmatic handling
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::BadManifest(_) => "content.bad_manifest",
+            Self::ContentMismatch(_) => "content.content_mismatch",
+            Self::NoHolder(_) => "content.no_holder",
+            Self::Busy => "content.busy",
+        }
+    }
+}
+
+/// Chunked transfer counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentStats {
+    pub published: u64,
+    pub chunks_served: u64,
+    pub chunks_fetched: u64,
+    /// Chunks that failed verification
+    pub chunks_rejected: u64,
+    pub completed: u64,
+    pub failed: u64,
+}
+
+/// Chunks this node can serve, evicted oldest first
+#[derive(Debug, Clone, Default)]
+pub struct ChunkStore {
+    chunks: HashMap<ContentId, Vec<u8>>,
+    order: VecDeque<ContentId>,
+    bytes: usize,
+    capacity: usize,
+}
+
+impl ChunkStore {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, ..Self::default() }
+    }
+
+    /// Store a chunk under its hash
+    pub fn insert(&mut self, data: Vec<u8>) -> ContentId {
+        let id = ContentId::of(&data);
+        if self.chunks.contains_key(&id) {
+            return id;
+        }
+        self.bytes += data.len();
+        self.chunks.insert(id, data);
+        self.order.push_back(id);
+        while self.bytes > self.capacity && self.order.len() > 1 {
+            if let Some(old) = self.order.pop_front() {
+                self.bytes -= self.chunks.remove(&old).map_or(0, |c| c.len());
+            }
+        }
+        id
+    }
+
+    pub fn get(&self, id: &ContentId) -> Option<&Vec<u8>> {
+        self.chunks.get(id)
+    }
+
+    pub fn contains(&self, id: &ContentId) -> bool {
+        self.chunks.contains_key(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Bytes of chunk data held
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Every chunk held, oldest first
+    pub fn chunks(&self) -> impl Iterator<Item = (&ContentId, &Vec<u8>)> + '_ {
+        self.order.iter().filter_map(|id| self.chunks.get_key_value(id))
+    }
+}
+
+/// A chunk request in flight
+#[derive(Debug, Clone)]
+struct PendingChunk {
+    holder: NodeId,
+    requested_at: u64,
+}
+
+/// One payload being fetched
+#[derive(Debug, Clone)]
+struct Download {
+    manifest: Manifest,
+    /// Node the manifest came from
+    source: NodeId,
+    pending: HashMap<ContentId, PendingChunk>,
+    /// Holders already asked for each chunk
+    tried: HashMap<ContentId, HashSet<NodeId>>,
+}
+
+impl Download {
+    fn missing<'a>(&'a self, store: &'a ChunkStore) -> impl Iterator<Item = &'a ContentId> + 'a {
+        let mut seen = HashSet::new();
+        self.manifest
+            .chunks
+            .iter()
+            .filter(move |c| !store.contains(c) && seen.insert(**c))
+    }
+
+    /// Next holder to ask for a chunk, spreading chunks over holders
+    fn holder_for(&self, chunk: &ContentId) -> Option<NodeId> {
+        let tried = self.tried.get(chunk);
+        let index = self.manifest.chunks.iter().position(|c| c == chunk).unwrap_or(0);
+        let holders = &self.manifest.holders;
+        (0..holders.len())
+            .map(|offset| &holders[(index + offset) % holders.len()])
+            .find(|h| tried.is_none_or(|t| !t.contains(*h)))
+            .cloned()
+    }
+}
+
+/// What to do after a transfer event
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Progress {
+    /// Chunk requests to send, as (holder, chunk)
+    pub requests: Vec<(NodeId, ContentId)>,
+    /// Payloads fetched and verified, with the node their manifest came from
+    pub completed: Vec<(NodeId, ContentId, Vec<u8>)>,
+    /// Transfers abandoned
+    pub failed: Vec<(ContentId, ContentError)>,
+}
+
+/// Chunk store and transfers of one node
+#[derive(Debug, Clone)]
+pub struct ContentTransfers {
+    pub config: ContentConfig,
+    store: ChunkStore,
+    downloads: HashMap<ContentId, Download>,
+    /// Incoming streams announced by holders: (holder, stream ID) -> chunk
+    streams: HashMap<(NodeId, u64), ContentId>,
+    /// Incoming handovers: (holder, stream ID) -> (chunk, announced at)
+    replicas: HashMap<(NodeId, u64), (ContentId, u64)>,
+    stats: ContentStats,
+}
+
+impl Default for ContentTransfers {
+    fn default() -> Self {
+        Self::new(ContentConfig::default())
+    }
+}
+
+impl ContentTransfers {
+    pub fn new(config: ContentConfig) -> Self {
+        Self {
+            store: ChunkStore::new(config.store_bytes),
+            config,
+            downloads: HashMap::new(),
+            streams: HashMap::new(),
+            replicas: HashMap::new(),
+            stats: ContentStats::default(),
+        }
+    }
+
+    /// Apply new settings; a smaller store shrinks on the next insert
+    pub fn set_config(&mut self, config: ContentConfig) {
+        self.store.capacity = config.store_bytes;
+        self.config = config;
+    }
+
+    /// Store `data` as chunks held by `holder` and describe it
+    pub fn publish(&mut self, data: &[u8], holder: NodeId) -> Manifest {
+        let (manifest, pieces) = Manifest::split(data, self.config.chunk_size, holder);
+        for piece in pieces {
+            self.store.insert(piece);
+        }
+        self.stats.published += 1;
+        manifest
+    }
+
+    /// Start fetching the payload a manifest describes
+    pub fn fetch(&mut self, manifest: Manifest, source: NodeId, now_ms: u64) -> Result<Progress, ContentError> {
+        manifest.validate()?;
+        let content_id = manifest.content_id;
+        if !self.downloads.contains_key(&content_id) && self.downloads.len() >= self.config.max_downloads {
+            return Err(ContentError::Busy);
+        }
+        self.downloads.entry(content_id).or_insert_with(|| Download {
+            manifest,
+            source,
+            pending: HashMap::new(),
+            tried: HashMap::new(),
+        });
+        Ok(self.advance(now_ms))
+    }
+
+    /// Start fetching a manifest pushed by `source`, which must hold the chunks
+    pub fn offer(&mut self, manifest: Manifest, source: NodeId, now_ms: u64) -> Result<Progress, ContentError> {
+        if !manifest.holders.contains(&source) {
+            return Err(ContentError::BadManifest("not held by its sender".to_string()));
+        }
+        self.fetch(manifest, source, now_ms)
+    }
+
+    /// A chunk this node can serve
+    pub fn serve(&mut self, chunk: &ContentId) -> Option<Vec<u8>> {
+        let data = self.store.get(chunk).cloned();
+        if data.is_some() {
+            self.stats.chunks_served += 1;
+        }
+        data
+    }
+
+    /// Note that `holder` is sending `chunk` on its stream `stream_id`
+    ///
+    /// Ignored unless the chunk was requested from `holder` and no stream
+    /// of its carries it yet.
+    pub fn on_serving(&mut self, holder: &NodeId, chunk: ContentId, stream_id: u64) -> bool {
+        let requested = self
+            .downloads
+            .values()
+            .any(|d| d.pending.get(&chunk).is_some_and(|p| &p.holder == holder));
+        let announced = self.streams.iter().any(|((h, _), c)| h == holder && *c == chunk);
+        if !requested || announced {
+            return false;
+        }
+        self.streams.insert((holder.clone(), stream_id), chunk);
+        true
+    }
+
+    /// Note that a draining `holder` is handing `chunk` over on its stream
+    /// `stream_id`; the chunk is stored like a fetched one once verified
+    ///
+    /// Ignored for chunks already stored or on their way, and beyond
+    /// `max_replica_streams` handovers at once.
+    pub fn on_replica(&mut self, holder: &NodeId, chunk: ContentId, stream_id: u64, now_ms: u64) -> bool {
+        let incoming = self.replicas.values().any(|(c, _)| *c == chunk);
+        if self.store.contains(&chunk) || incoming || self.replicas.len() >= self.config.max_replica_streams {
+            return false;
+        }
+        self.replicas.insert((holder.clone(), stream_id), (chunk, now_ms));
+        true
+    }
+
+    /// Whether an incoming stream carries a chunk
+    pub fn is_chunk_stream(&self, holder: &NodeId, stream_id: u64) -> bool {
+        let key = (holder.clone(), stream_id);
+        self.streams.contains_key(&key) || self.replicas.contains_key(&key)
+    }
+
+    /// Handle the full contents of a finished chunk stream
+    pub fn on_stream_finished(&mut self, holder: &NodeId, stream_id: u64, data: Vec<u8>, now_ms: u64) -> Progress {
+        let key = (holder.clone(), stream_id);
+        let Some(chunk) = self.streams.remove(&key).or_else(|| self.replicas.remove(&key).map(|(c, _)| c)) else {
+            return Progress::default();
+        };
+        if ContentId::of(&data) != chunk {
+            self.stats.chunks_rejected += 1;
+            return self.retry(holder, &chunk, now_ms);
+        }
+        self.store.insert(data);
+        self.stats.chunks_fetched += 1;
+        for download in self.downloads.values_mut() {
+            download.pending.remove(&chunk);
+        }
+        self.advance(now_ms)
+    }
+
+    /// A holder reported that it does not have `chunk`
+    pub fn on_missing(&mut self, holder: &NodeId, chunk: &ContentId, now_ms: u64) -> Progress {
+        self.retry(holder, chunk, now_ms)
+    }
+
+    /// Give requests that timed out to the next holder and forget stalled
+    /// handovers
+    pub fn poll(&mut self, now_ms: u64) -> Progress {
+        let timeout = self.config.request_timeout_ms;
+        self.replicas.retain(|_, (_, at)| now_ms.saturating_sub(*at) < timeout);
+        let expired: Vec<(NodeId, ContentId)> = self
+            .downloads
+            .values()
+            .flat_map(|d| d.pending.iter())
+            .filter(|(_, p)| now_ms.saturating_sub(p.requested_at) >= timeout)
+            .map(|(chunk, p)| (p.holder.clone(), *chunk))
+            .collect();
+        let mut progress = Progress::default();
+        for (holder, chunk) in expired {
+            let next = self.retry(&holder, &chunk, now_ms);
+            progress.requests.extend(next.requests);
+            progress.completed.extend(next.completed);
+            progress.failed.extend(next.failed);
+        }
+        progress
+    }
+
+    /// Drop a failed request so `advance` asks the next holder
+    fn retry(&mut self, holder: &NodeId, chunk: &ContentId, now_ms: u64) -> Progress {
+        for download in self.downloads.values_mut() {
+            if download.pending.get(chunk).is_some_and(|p| &p.holder == holder) {
+                download.pending.remove(chunk);
+            }
+        }
+        self.advance(now_ms)
+    }
+
+    /// Issue requests up to the parallel limit and finish complete downloads
+    fn advance(&mut self, now_ms: u64) -> Progress {
+        let mut progress = Progress::default();
+        let mut finished = Vec::new();
+        for (content_id, download) in self.downloads.iter_mut() {
+            let missing: Vec<ContentId> = download.missing(&self.store).copied().collect();
+            if missing.is_empty() {
+                finished.push(*content_id);
+                continue;
+            }
+            for chunk in missing {
+                if download.pending.len() >= self.config.parallel {
+                    break;
+                }
+                if download.pending.contains_key(&chunk) {
+                    continue;
+                }
+                let Some(holder) = download.holder_for(&chunk) else {
+                    progress.failed.push((*content_id, ContentError::NoHolder(chunk)));
+                    break;
+                };
+                download.tried.entry(chunk).or_default().insert(holder.clone());
+                download.pending.insert(chunk, PendingChunk { holder: holder.clone(), requested_at: now_ms });
+                progress.requests.push((holder, chunk));
+            }
+        }
+
+        for (content_id, _) in &progress.failed {
+            self.downloads.remove(content_id);
+            self.stats.failed += 1;
+        }
+        // Streams for requests that were given up on are no longer expected
+        let downloads = &self.downloads;
+        self.streams
+            .retain(|(holder, _), chunk| downloads.values().any(|d| d.pending.get(chunk).is_some_and(|p| &p.holder == holder)));
+        for content_id in finished {
+            let Some(download) = self.downloads.remove(&content_id) else {
+                continue;
+            };
+            match self.assemble(&download.manifest) {
+                Ok(data) => {
+                    self.stats.completed += 1;
+                    progress.completed.push((download.source, content_id, data));
+                }
+                Err(e) => {
+                    self.stats.failed += 1;
+                    progress.failed.push((content_id, e));
+                }
+            }
+        }
+        progress
+    }
+
+    fn assemble(&self, manifest: &Manifest) -> Result<Vec<u8>, ContentError> {
+        let mut data = Vec::with_capacity(manifest.size as usize);
+        for chunk in &manifest.chunks {
+            // Evicted between arrival and assembly; the store is too small for the payload
+            let piece = self.store.get(chunk).ok_or(ContentError::NoHolder(*chunk))?;
+            data.extend_from_slice(piece);
+        }
+        if data.len() as u64 != manifest.size || ContentId::of(&data) != manifest.content_id {
+            return Err(ContentError::ContentMismatch(manifest.content_id));
+        }
+        Ok(data)
+    }
+
+    /// Transfers still in progress
+    pub fn active(&self) -> usize {
+        self.downloads.len()
+    }
+
+    pub fn store(&self) -> &ChunkStore {
+        &self.store
+    }
+
+    pub fn stats(&self) -> ContentStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ContentConfig {
+        ContentConfig {
+            threshold: 8,
+            chunk_size: 4,
+            parallel: 2,
+            request_timeout_ms: 100,
+            store_bytes: 1024,
+            max_downloads: 2,
+            max_replica_streams: 1,
+        }
+    }
+
+    /// Serve every request in `progress` from `holder`, returning the next progress
+    fn serve_all(holder: &mut ContentTransfers, fetcher: &mut ContentTransfers, progress: Progress) -> Progress {
+        let mut next = Progress::default();
+        for (stream_id, (node, chunk)) in progress.requests.into_iter().enumerate() {
+            let data = holder.serve(&chunk).unwrap();
+            fetcher.on_serving(&node, chunk, stream_id as u64);
+            let step = fetcher.on_stream_finished(&node, stream_id as u64, data, 0);
+            next.requests.extend(step.requests);
+            next.completed.extend(step.completed);
+            next.failed.extend(step.failed);
+        }
+        next
+    }
+
+    #[test]
+    fn test_chunked_fetch_round_trip() {
+        let payload = b"content addressed chunks!".to_vec();
+        let mut holder = ContentTransfers::new(config());
+        let manifest = holder.publish(&payload, NodeId::new("holder"));
+        assert_eq!(manifest.chunks.len(), 7);
+        assert_eq!(manifest.content_id, ContentId::of(&payload));
+
+        let mut fetcher = ContentTransfers::new(config());
+        let mut progress = fetcher.fetch(manifest, NodeId::new("holder"), 0).unwrap();
+        let mut rounds = 0;
+        while progress.completed.is_empty() {
+            // Never more than `parallel` requests in flight
+            assert!(!progress.requests.is_empty() && progress.requests.len() <= 2);
+            progress = serve_all(&mut holder, &mut fetcher, progress);
+            rounds += 1;
+        }
+        assert_eq!(progress.completed, vec![(NodeId::new("holder"), ContentId::of(&payload), payload)]);
+        assert!(rounds >= 4);
+        assert_eq!(fetcher.stats().completed, 1);
+        assert_eq!(fetcher.active(), 0);
+        // The fetcher can now serve the chunks itself
+        assert_eq!(fetcher.store().len(), 7);
+    }
+
+    #[test]
+    fn test_corrupt_and_missing_chunks_fall_back() {
+        let payload = b"12345678".to_vec();
+        let mut good = ContentTransfers::new(config());
+        let mut manifest = good.publish(&payload, NodeId::new("good"));
+        manifest.holders.insert(0, NodeId::new("bad"));
+
+        let mut fetcher = ContentTransfers::new(config());
+        let progress = fetcher.fetch(manifest.clone(), NodeId::new("good"), 0).unwrap();
+        let first = manifest.chunks[0];
+        assert_eq!(progress.requests[0], (NodeId::new("bad"), first));
+
+        // A substituted chunk is rejected and requested from the other holder
+        fetcher.on_serving(&NodeId::new("bad"), first, 9);
+        let retry = fetcher.on_stream_finished(&NodeId::new("bad"), 9, b"evil".to_vec(), 0);
+        assert_eq!(retry.requests, vec![(NodeId::new("good"), first)]);
+        assert_eq!(fetcher.stats().chunks_rejected, 1);
+
+        // A missing chunk moves to the next holder; once every holder timed out the transfer fails
+        let second = manifest.chunks[1];
+        let moved = fetcher.on_missing(&NodeId::new("good"), &second, 0);
+        assert_eq!(moved.requests, vec![(NodeId::new("bad"), second)]);
+        assert!(fetcher.poll(50).requests.is_empty());
+        let failed = fetcher.poll(100);
+        assert!(failed.failed.iter().any(|(_, e)| e.code() == "content.no_holder"));
+        assert_eq!(fetcher.stats().failed, 1);
+        assert_eq!(fetcher.active(), 0);
+
+        let mut broken = manifest;
+        broken.chunks.pop();
+        assert!(matches!(fetcher.fetch(broken, NodeId::new("good"), 0), Err(ContentError::BadManifest(_))));
+        assert!(ContentConfig { chunk_size: 16, ..config() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_unsolicited_messages_ignored() {
+        let mut holder = ContentTransfers::new(config());
+        let manifest = holder.publish(b"12345678", NodeId::new("holder"));
+        let mut fetcher = ContentTransfers::new(config());
+
+        // Pushed manifests must come from a holder, and only so many run at once
+        let stranger = NodeId::new("stranger");
+        assert!(matches!(fetcher.offer(manifest.clone(), stranger.clone(), 0), Err(ContentError::BadManifest(_))));
+        let progress = fetcher.offer(manifest.clone(), NodeId::new("holder"), 0).unwrap();
+        let (_, first) = progress.requests[0];
+        let other = |payload: &[u8]| Manifest::split(payload, 4, NodeId::new("holder")).0;
+        assert!(fetcher.fetch(other(b"abcdefghi"), NodeId::new("holder"), 0).is_ok());
+        assert_eq!(fetcher.fetch(other(b"jklmnopqr"), NodeId::new("holder"), 0), Err(ContentError::Busy));
+        assert_eq!(fetcher.active(), 2);
+
+        // Chunk streams only from the holder asked, once per chunk
+        assert!(!fetcher.on_serving(&stranger, first, 1));
+        assert!(!fetcher.on_serving(&NodeId::new("holder"), ContentId::of(b"other"), 2));
+        assert!(fetcher.on_serving(&NodeId::new("holder"), first, 3));
+        assert!(!fetcher.on_serving(&NodeId::new("holder"), first, 4));
+        assert!(!fetcher.is_chunk_stream(&stranger, 1));
+
+        // Handovers are capped and forgotten once stalled
+        assert!(fetcher.on_replica(&stranger, ContentId::of(b"r1"), 5, 0));
+        assert!(!fetcher.on_replica(&stranger, ContentId::of(b"r2"), 6, 0));
+        fetcher.poll(100);
+        assert!(!fetcher.is_chunk_stream(&stranger, 5));
+    }
+}
@@ -0,0 +1,336 @@
+//! Cluster-Wide Embedding Convergence
+//!
+//! After a large topology change, every node's Ricci flow keeps moving its
+//! coordinate for a while, and greedy routes have high stretch until the
+//! embedding settles. A single node only sees its own stress and movement.
+//! Nodes therefore gossip a small report with both to their neighbors, who
+//! merge it into their own table and pass it on, so every node can compute
+//! cluster-wide percentiles and tell operators when the embedding has
+//! converged.
+//!
+//! Each report carries a per-origin sequence number, so merges keep the
+//! newest report without comparing clocks. Its age grows as it is relayed,
+//! and reports older than `max_age_ms` are dropped, which removes nodes
+//! that left. A node's displacement is the hyperbolic distance its
+//! coordinate moved over the last `window_ms`, so a node whose flow has
+//! stopped reports zero even if its last update was a large one.
+
+use std::collections::{HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+
+/// Convergence gossip settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConvergenceConfig {
+    pub enabled: bool,
+    /// Time between gossip rounds
+    pub interval_ms: u64,
+    /// Window over which a node's displacement is summed
+    pub window_ms: u64,
+    /// Reports not refreshed by their origin for this long are dropped
+    pub max_age_ms: u64,
+    /// Reports held and gossiped, including this node's own
+    pub max_reports: usize,
+    /// p90 displacement at or below which the embedding counts as still
+    pub displacement_threshold: f64,
+    /// How long the embedding must stay still before it is converged
+    pub settle_ms: u64,
+}
+
+impl Default for ConvergenceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: 2_000,
+            window_ms: 10_000,
+            max_age_ms: 30_000,
+            max_reports: 1024,
+            displacement_threshold: 0.01,
+            settle_ms: 5_000,
+        }
+    }
+}
+
+impl ConvergenceConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_ms == 0 || self.window_ms == 0 || self.max_age_ms == 0 || self.max_reports == 0 {
+            return Err("Convergence interval, window, age and report limit must be positive".to_string());
+        }
+        if !self.displacement_threshold.is_finite() || self.displacement_threshold < 0.0 {
+            return Err("Convergence displacement threshold must be non-negative".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Latest Ricci flow state of one node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConvergenceReport {
+    pub node: NodeId,
+    /// Increased by the origin for every new report
+    pub seq: u64,
+    /// Stress of the origin's last Ricci flow run
+    pub stress: f64,
+    /// Distance the origin's coordinate moved over its window
+    pub displacement: f64,
+    /// Time since the origin produced the report, as of sending
+    pub age_ms: u64,
+}
+
+/// Percentiles of one quantity over the reporting nodes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Percentiles {
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl Percentiles {
+    /// Nearest-rank percentiles; zero for no values
+    pub fn of(mut values: Vec<f64>) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        values.sort_by(|a, b| a.total_cmp(b));
+        let rank = |p: f64| values[((p * values.len() as f64).ceil() as usize).clamp(1, values.len()) - 1];
+        Self { p50: rank(0.5), p90: rank(0.9), p99: rank(0.99), max: values[values.len() - 1] }
+    }
+}
+
+/// Whether low-stretch routing can be relied on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConvergenceStatus {
+    /// No reports yet, or gossip is disabled
+    #[default]
+    Unknown,
+    /// Coordinates are still moving, or stopped less than `settle_ms` ago
+    Converging,
+    /// Coordinates have been still for at least `settle_ms`
+    Converged,
+}
+
+/// Cluster-wide convergence as seen by this node
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConvergenceSummary {
+    pub status: ConvergenceStatus,
+    /// Nodes with a fresh report, including this one
+    pub nodes: usize,
+    pub stress: Percentiles,
+    pub displacement: Percentiles,
+    /// How long p90 displacement has been at or below the threshold
+    pub still_for_ms: u64,
+}
+
+#[derive(Debug, Clone)]
+struct HeldReport {
+    seq: u64,
+    stress: f64,
+    displacement: f64,
+    /// Local time the origin produced the report, estimated from its age
+    produced_ms: u64,
+}
+
+/// This node's report and the latest reports of other nodes
+#[derive(Debug, Clone)]
+pub struct ConvergenceTracker {
+    config: ConvergenceConfig,
+    id: NodeId,
+    seq: u64,
+    stress: f64,
+    /// Recent coordinate moves of this node
+    moves: VecDeque<(u64, f64)>,
+    reports: HashMap<NodeId, HeldReport>,
+    last_round_ms: Option<u64>,
+    still_since_ms: Option<u64>,
+}
+
+impl ConvergenceTracker {
+    pub fn new(id: NodeId, config: ConvergenceConfig) -> Self {
+        Self {
+            config,
+            id,
+            seq: 0,
+            stress: 0.0,
+            moves: VecDeque::new(),
+            reports: HashMap::new(),
+            last_round_ms: None,
+            still_since_ms: None,
+        }
+    }
+
+    pub fn config(&self) -> &ConvergenceConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: ConvergenceConfig) {
+        if !config.enabled {
+            self.reports.clear();
+            self.still_since_ms = None;
+        }
+        self.config = config;
+    }
+
+    /// Record a local Ricci flow run that moved this node by `displacement`
+    pub fn record_local(&mut self, stress: f64, displacement: f64, now_ms: u64) {
+        self.stress = stress;
+        self.moves.push_back((now_ms, displacement));
+        self.displacement(now_ms);
+    }
+
+    fn displacement(&mut self, now_ms: u64) -> f64 {
+        let window = self.config.window_ms;
+        while self.moves.front().is_some_and(|(at, _)| now_ms.saturating_sub(*at) >= window) {
+            self.moves.pop_front();
+        }
+        self.moves.iter().map(|(_, moved)| moved).sum()
+    }
+
+    /// Whether a gossip round is due; starts it if so
+    pub fn start_round(&mut self, now_ms: u64) -> bool {
+        let due = self.config.enabled
+            && self.last_round_ms.is_none_or(|last| now_ms.saturating_sub(last) >= self.config.interval_ms);
+        if due {
+            self.last_round_ms = Some(now_ms);
+        }
+        due
+    }
+
+    /// Reports to send to neighbors: a fresh one of our own and the held ones
+    pub fn gossip(&mut self, now_ms: u64) -> Vec<ConvergenceReport> {
+        self.expire(now_ms);
+        // Never below the clock, so a restarted node is not taken for its old self
+        self.seq = (self.seq + 1).max(now_ms);
+        let own = ConvergenceReport {
+            node: self.id.clone(),
+            seq: self.seq,
+            stress: self.stress,
+            displacement: self.displacement(now_ms),
+            age_ms: 0,
+        };
+        let mut reports = vec![own];
+        reports.extend(self.reports.iter().map(|(node, held)| ConvergenceReport {
+            node: node.clone(),
+            seq: held.seq,
+            stress: held.stress,
+            displacement: held.displacement,
+            age_ms: now_ms.saturating_sub(held.produced_ms),
+        }));
+        reports.truncate(self.config.max_reports);
+        reports
+    }
+
+    /// Merge reports from a neighbor, keeping the newest per origin
+    ///
+    /// Returns how many reports were new.
+    pub fn merge(&mut self, reports: Vec<ConvergenceReport>, now_ms: u64) -> usize {
+        if !self.config.enabled {
+            return 0;
+        }
+        let mut merged = 0;
+        for report in reports {
+            if report.node == self.id || report.age_ms >= self.config.max_age_ms || !report.stress.is_finite() {
+                continue;
+            }
+            if self.reports.get(&report.node).is_some_and(|held| held.seq >= report.seq) {
+                continue;
+            }
+            if !self.reports.contains_key(&report.node) && self.reports.len() + 1 >= self.config.max_reports {
+                continue;
+            }
+            self.reports.insert(
+                report.node,
+                HeldReport {
+                    seq: report.seq,
+                    stress: report.stress,
+                    displacement: report.displacement,
+                    produced_ms: now_ms.saturating_sub(report.age_ms),
+                },
+            );
+            merged += 1;
+        }
+        merged
+    }
+
+    fn expire(&mut self, now_ms: u64) {
+        let max_age = self.config.max_age_ms;
+        self.reports.retain(|_, held| now_ms.saturating_sub(held.produced_ms) < max_age);
+    }
+
+    /// Percentiles over this node and all fresh reports, and the status they imply
+    pub fn summary(&mut self, now_ms: u64) -> ConvergenceSummary {
+        if !self.config.enabled {
+            return ConvergenceSummary::default();
+        }
+        self.expire(now_ms);
+        let own = self.displacement(now_ms);
+        let stress = Percentiles::of(self.reports.values().map(|r| r.stress).chain([self.stress]).collect());
+        let displacement = Percentiles::of(self.reports.values().map(|r| r.displacement).chain([own]).collect());
+
+        let still_for_ms = if displacement.p90 <= self.config.displacement_threshold {
+            now_ms.saturating_sub(*self.still_since_ms.get_or_insert(now_ms))
+        } else {
+            self.still_since_ms = None;
+            0
+        };
+        let status = if self.seq == 0 && self.moves.is_empty() && self.reports.is_empty() {
+            ConvergenceStatus::Unknown
+        } else if still_for_ms >= self.config.settle_ms {
+            ConvergenceStatus::Converged
+        } else {
+            ConvergenceStatus::Converging
+        };
+        ConvergenceSummary { status, nodes: self.reports.len() + 1, stress, displacement, still_for_ms }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ConvergenceConfig {
+        ConvergenceConfig { enabled: true, window_ms: 1_000, max_age_ms: 5_000, settle_ms: 2_000, ..Default::default() }
+    }
+
+    #[test]
+    fn test_percentiles_nearest_rank() {
+        let p = Percentiles::of((1..=100).map(f64::from).collect());
+        assert_eq!((p.p50, p.p90, p.p99, p.max), (50.0, 90.0, 99.0, 100.0));
+        assert_eq!(Percentiles::of(vec![]), Percentiles::default());
+        assert_eq!(Percentiles::of(vec![3.0]).p50, 3.0);
+    }
+
+    #[test]
+    fn test_gossip_merges_and_settles() {
+        let mut a = ConvergenceTracker::new(NodeId::new("a"), config());
+        let mut b = ConvergenceTracker::new(NodeId::new("b"), config());
+        let mut c = ConvergenceTracker::new(NodeId::new("c"), config());
+        assert_eq!(a.summary(0).status, ConvergenceStatus::Unknown);
+
+        a.record_local(2.0, 0.5, 0);
+        b.record_local(1.0, 0.0, 0);
+        // a's report reaches c through b
+        assert_eq!(b.merge(a.gossip(0), 100), 1);
+        assert_eq!(c.merge(b.gossip(100), 200), 2);
+        // Older or equal sequence numbers are ignored
+        assert_eq!(c.merge(b.gossip(100), 200), 1);
+
+        let summary = c.summary(200);
+        assert_eq!((summary.nodes, summary.status), (3, ConvergenceStatus::Converging));
+        assert_eq!(summary.displacement.max, 0.5);
+        assert_eq!(summary.stress.max, 2.0);
+
+        // a stops moving; once its window passes the cluster settles
+        assert_eq!(c.merge(a.gossip(1_000), 1_000), 1);
+        assert_eq!(c.summary(1_000).still_for_ms, 0);
+        assert_eq!(c.summary(3_000).status, ConvergenceStatus::Converged);
+
+        // Reports not refreshed by their origin expire
+        let summary = c.summary(6_500);
+        assert_eq!(summary.nodes, 1);
+    }
+}
@@ -0,0 +1,210 @@
+//! Batched Coordinate Updates
+//!
+//! After a Ricci flow round many nodes move at once, and one
+//! `CoordinateUpdate` per node per neighbor turns convergence into a packet
+//! storm. Updates are instead queued as entries and flushed together: one
+//! `CoordinateBatch` packet per neighbor carries this node's own update plus
+//! every fresh entry learned from other neighbors, which is forwarded for a
+//! limited number of hops. Receivers apply each entry on its own and only if
+//! its version is newer than what they already know, so duplicates arriving
+//! over several paths are dropped rather than forwarded again.
+//!
+//! Entries may be signed by the node they describe. The hop budget is not
+//! covered by the signature because forwarders decrement it.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::network::SerializablePoincareDiskPoint;
+use crate::PoincareDiskPoint;
+
+const ENTRY_TAG: &str = "drfe-r/coordinate-entry";
+
+/// Hops an entry travels beyond the originating node's neighbors
+pub const DEFAULT_GOSSIP_HOPS: u8 = 2;
+
+/// Entries per batch packet, keeping batches well below the packet size limit
+pub const MAX_BATCH_ENTRIES: usize = 64;
+
+/// One node's coordinate at a version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoordinateEntry {
+    pub node: NodeId,
+    pub coord: SerializablePoincareDiskPoint,
+    pub version: u64,
+    /// Further hops this entry may be forwarded
+    pub hops_left: u8,
+    /// `node`'s Ed25519 signature over node, coordinate and version
+    pub signature: Option<Vec<u8>>,
+}
+
+impl CoordinateEntry {
+    pub fn new(node: NodeId, coord: PoincareDiskPoint, version: u64, hops_left: u8) -> Self {
+        Self {
+            node,
+            coord: SerializablePoincareDiskPoint::from(coord),
+            version,
+            hops_left,
+            signature: None,
+        }
+    }
+
+    pub fn point(&self) -> PoincareDiskPoint {
+        PoincareDiskPoint::from(self.coord)
+    }
+
+    fn signed_message(&self) -> Result<Vec<u8>, String> {
+        rmp_serde::to_vec(&(ENTRY_TAG, &self.node, &self.coord, self.version))
+            .map_err(|e| format!("Failed to serialize coordinate entry for signing: {}", e))
+    }
+
+    /// Sign as the described node with a 32-byte Ed25519 private key
+    pub fn sign(&mut self, private_key: &[u8]) -> Result<(), String> {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let key: [u8; 32] = private_key
+            .try_into()
+            .map_err(|_| format!("Invalid private key length: {} (expected 32 bytes)", private_key.len()))?;
+        let message = self.signed_message()?;
+        self.signature = Some(SigningKey::from_bytes(&key).sign(&message).to_bytes().to_vec());
+        Ok(())
+    }
+
+    /// Verify the signature with the described node's 32-byte public key
+    pub fn verify_signature(&self, public_key: &[u8]) -> bool {
+        use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+        let (Some(signature), Ok(key)) = (&self.signature, <[u8; 32]>::try_from(public_key)) else {
+            return false;
+        };
+        let (Ok(key), Ok(signature), Ok(message)) = (
+            VerifyingKey::from_bytes(&key),
+            Signature::from_slice(signature),
+            self.signed_message(),
+        ) else {
+            return false;
+        };
+        key.verify(&message, &signature).is_ok()
+    }
+}
+
+/// Newest known version per node plus the entries waiting to be sent
+#[derive(Debug, Clone, Default)]
+pub struct CoordinateBatcher {
+    known: HashMap<NodeId, (PoincareDiskPoint, u64)>,
+    pending: HashMap<NodeId, CoordinateEntry>,
+}
+
+impl CoordinateBatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an entry if it is newer than the known version
+    ///
+    /// Fresh entries with hops left are queued for forwarding with one hop
+    /// fewer; a queued older entry for the same node is replaced.
+    /// Returns whether the entry was fresh.
+    pub fn offer(&mut self, entry: CoordinateEntry) -> bool {
+        if self.known.get(&entry.node).is_some_and(|(_, v)| *v >= entry.version) {
+            return false;
+        }
+        self.known.insert(entry.node.clone(), (entry.point(), entry.version));
+        if entry.hops_left > 0 {
+            let mut forward = entry;
+            forward.hops_left -= 1;
+            self.pending.insert(forward.node.clone(), forward);
+        } else {
+            self.pending.remove(&entry.node);
+        }
+        true
+    }
+
+    /// Queue an entry originating at this node
+    ///
+    /// Own entries are sent to our neighbors, so they start with one more
+    /// hop than forwarded ones keep.
+    pub fn queue_local(&mut self, mut entry: CoordinateEntry) -> bool {
+        entry.hops_left = entry.hops_left.saturating_add(1);
+        self.offer(entry)
+    }
+
+    /// Take all pending entries, in batches of at most `MAX_BATCH_ENTRIES`
+    pub fn drain_batches(&mut self) -> Vec<Vec<CoordinateEntry>> {
+        let mut entries: Vec<CoordinateEntry> = self.pending.drain().map(|(_, e)| e).collect();
+        entries.sort_by(|a, b| a.node.0.cmp(&b.node.0));
+        entries.chunks(MAX_BATCH_ENTRIES).map(|chunk| chunk.to_vec()).collect()
+    }
+
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Newest coordinate and version heard for `node`
+    pub fn known(&self, node: &NodeId) -> Option<(PoincareDiskPoint, u64)> {
+        self.known.get(node).copied()
+    }
+
+    /// Newest coordinate and version heard for every node
+    pub fn known_entries(&self) -> impl Iterator<Item = (&NodeId, PoincareDiskPoint, u64)> {
+        self.known.iter().map(|(node, (point, version))| (node, *point, *version))
+    }
+
+    /// Newest coordinate heard for every node
+    pub fn known_points(&self) -> Vec<(NodeId, PoincareDiskPoint)> {
+        self.known.iter().map(|(node, (point, _))| (node.clone(), *point)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+
+    fn entry(node: &str, x: f64, version: u64, hops: u8) -> CoordinateEntry {
+        CoordinateEntry::new(NodeId::new(node), PoincareDiskPoint::new(x, 0.0).unwrap(), version, hops)
+    }
+
+    #[test]
+    fn test_batcher_applies_newest_versions_and_limits_hops() {
+        let mut batcher = CoordinateBatcher::new();
+        assert!(batcher.offer(entry("a", 0.1, 2, 1)));
+        // Stale and duplicate entries are dropped
+        assert!(!batcher.offer(entry("a", 0.2, 1, 1)));
+        assert!(!batcher.offer(entry("a", 0.2, 2, 1)));
+        assert!(batcher.offer(entry("a", 0.3, 3, 1)));
+        // Out of hops: applied but not forwarded
+        assert!(batcher.offer(entry("b", 0.4, 1, 0)));
+        assert!(batcher.queue_local(entry("self", 0.5, 1, 0)));
+
+        assert_eq!(batcher.known(&NodeId::new("a")).unwrap().1, 3);
+        let batches = batcher.drain_batches();
+        assert_eq!(batches.len(), 1);
+        let nodes: Vec<(&str, u8)> = batches[0].iter().map(|e| (e.node.0.as_str(), e.hops_left)).collect();
+        assert_eq!(nodes, vec![("a", 0), ("self", 0)]);
+        assert_eq!(batcher.pending_len(), 0);
+
+        for i in 0..(MAX_BATCH_ENTRIES + 1) {
+            batcher.offer(entry(&format!("n{}", i), 0.1, 1, 1));
+        }
+        assert_eq!(batcher.drain_batches().len(), 2);
+    }
+
+    #[test]
+    fn test_entry_signature_ignores_hop_budget() {
+        let private = [7u8; 32];
+        let public = SigningKey::from_bytes(&private).verifying_key().to_bytes();
+
+        let mut signed = entry("a", 0.1, 4, 2);
+        signed.sign(&private).unwrap();
+        assert!(signed.verify_signature(&public));
+        signed.hops_left = 0;
+        assert!(signed.verify_signature(&public));
+
+        signed.version = 5;
+        assert!(!signed.verify_signature(&public));
+        assert!(!entry("a", 0.1, 4, 2).verify_signature(&public));
+    }
+}
@@ -0,0 +1,285 @@
+//! Coordinate Update Control
+//!
+//! Ricci flow coordinate updates cost CPU and gossip traffic. Updates are
+//! needed when routing quality drops, not on a fixed clock. A
+//! proportional-integral controller sets the interval between updates from
+//! recent routing quality:
+//!
+//! - Greedy success rate: the share of Data packets this node forwarded in
+//!   gravity mode. Fallback modes mean the coordinates no longer fit the
+//!   topology.
+//! - Stretch: hops taken by packets delivered here, divided by the hop
+//!   count the hyperbolic distance predicts.
+//!
+//! The error is the shortfall against the set-points, with stretch weighted
+//! by `stretch_weight`. Its output `u` scales the base interval by `2^-u`.
+//! When quality degrades, updates come more often. When quality holds
+//! steady above the targets, the integral term backs the interval off
+//! toward `max_interval_ms`.
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+/// Controller settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoordinateControlConfig {
+    /// Greedy success rate to hold (0.0 to 1.0)
+    pub target_greedy_success: f64,
+    /// Stretch to hold (>= 1.0)
+    pub target_stretch: f64,
+    /// Weight of the relative stretch error against the greedy error
+    pub stretch_weight: f64,
+    /// Proportional gain
+    pub kp: f64,
+    /// Integral gain, per second of accumulated error
+    pub ki: f64,
+    /// Bound on the integral term's contribution to the output
+    pub integral_limit: f64,
+    /// Interval at zero error
+    pub base_interval_ms: u64,
+    pub min_interval_ms: u64,
+    pub max_interval_ms: u64,
+    /// Recent routing and delivery observations kept for each measurement
+    pub window: usize,
+}
+
+impl Default for CoordinateControlConfig {
+    fn default() -> Self {
+        Self {
+            target_greedy_success: 0.95,
+            target_stretch: 1.5,
+            stretch_weight: 0.5,
+            kp: 4.0,
+            ki: 0.02,
+            integral_limit: 3.0,
+            base_interval_ms: 60_000,
+            min_interval_ms: 5_000,
+            max_interval_ms: 600_000,
+            window: 256,
+        }
+    }
+}
+
+impl CoordinateControlConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.target_greedy_success) {
+            return Err("target_greedy_success must be in [0, 1]".to_string());
+        }
+        if self.target_stretch.is_nan() || self.target_stretch < 1.0 {
+            return Err("target_stretch must be at least 1".to_string());
+        }
+        if [self.stretch_weight, self.kp, self.ki, self.integral_limit].iter().any(|v| v.is_nan() || *v < 0.0) {
+            return Err("Controller gains and limits must be non-negative".to_string());
+        }
+        if self.min_interval_ms == 0
+            || self.min_interval_ms > self.base_interval_ms
+            || self.base_interval_ms > self.max_interval_ms
+        {
+            return Err("Intervals must satisfy 0 < min <= base <= max".to_string());
+        }
+        if self.window == 0 {
+            return Err("window must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Controller state, for debugging
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CoordinateControlState {
+    /// Measured greedy success rate; None without routing observations
+    pub greedy_success: Option<f64>,
+    /// Measured mean stretch; None without delivery observations
+    pub stretch: Option<f64>,
+    /// Weighted shortfall against the set-points (positive: worse)
+    pub error: f64,
+    /// Accumulated error, in error-seconds
+    pub integral: f64,
+    /// Controller output; the interval is `base * 2^-output`
+    pub output: f64,
+    pub interval_ms: u64,
+    /// Unix time of the last coordinate update in milliseconds
+    pub last_update_ms: Option<u64>,
+    pub next_update_ms: u64,
+    pub updates: u64,
+}
+
+/// PI controller for the coordinate update interval
+#[derive(Debug, Clone)]
+pub struct CoordinateUpdateController {
+    config: CoordinateControlConfig,
+    greedy: VecDeque<bool>,
+    stretch: VecDeque<f64>,
+    last_evaluated_ms: Option<u64>,
+    state: CoordinateControlState,
+}
+
+impl CoordinateUpdateController {
+    pub fn new(config: CoordinateControlConfig) -> Self {
+        let state = CoordinateControlState {
+            interval_ms: config.base_interval_ms,
+            ..Default::default()
+        };
+        Self {
+            config,
+            greedy: VecDeque::new(),
+            stretch: VecDeque::new(),
+            last_evaluated_ms: None,
+            state,
+        }
+    }
+
+    pub fn config(&self) -> &CoordinateControlConfig {
+        &self.config
+    }
+
+    /// Replace the settings, keeping observations and the integral
+    pub fn set_config(&mut self, config: CoordinateControlConfig) {
+        self.config = config;
+        while self.greedy.len() > self.config.window {
+            self.greedy.pop_front();
+        }
+        while self.stretch.len() > self.config.window {
+            self.stretch.pop_front();
+        }
+    }
+
+    /// Record a routing decision for a Data packet
+    pub fn observe_route(&mut self, greedy: bool) {
+        self.greedy.push_back(greedy);
+        if self.greedy.len() > self.config.window {
+            self.greedy.pop_front();
+        }
+    }
+
+    /// Record the stretch of a Data packet delivered here
+    pub fn observe_stretch(&mut self, stretch: f64) {
+        if !stretch.is_finite() {
+            return;
+        }
+        self.stretch.push_back(stretch.max(1.0));
+        if self.stretch.len() > self.config.window {
+            self.stretch.pop_front();
+        }
+    }
+
+    /// Update the controller from the current observations
+    pub fn evaluate(&mut self, now_ms: u64) -> &CoordinateControlState {
+        let config = &self.config;
+        let greedy_success = (!self.greedy.is_empty())
+            .then(|| self.greedy.iter().filter(|g| **g).count() as f64 / self.greedy.len() as f64);
+        let stretch = (!self.stretch.is_empty()).then(|| self.stretch.iter().sum::<f64>() / self.stretch.len() as f64);
+
+        let mut error = 0.0;
+        if let Some(success) = greedy_success {
+            error += config.target_greedy_success - success;
+        }
+        if let Some(stretch) = stretch {
+            error += config.stretch_weight * (stretch - config.target_stretch) / config.target_stretch;
+        }
+
+        // Anti-windup: the integral term alone never exceeds integral_limit
+        let dt = self.last_evaluated_ms.map_or(0.0, |last| now_ms.saturating_sub(last) as f64 / 1000.0);
+        self.last_evaluated_ms = Some(now_ms);
+        let bound = if config.ki > 0.0 { config.integral_limit / config.ki } else { 0.0 };
+        let integral = (self.state.integral + error * dt).clamp(-bound, bound);
+
+        let output = config.kp * error + config.ki * integral;
+        let interval_ms = (config.base_interval_ms as f64 * (-output).exp2())
+            .clamp(config.min_interval_ms as f64, config.max_interval_ms as f64) as u64;
+
+        let state = &mut self.state;
+        state.greedy_success = greedy_success;
+        state.stretch = stretch;
+        state.error = error;
+        state.integral = integral;
+        state.output = output;
+        state.interval_ms = interval_ms;
+        // Before the first update there is nothing to wait for
+        state.next_update_ms = state.last_update_ms.map_or(0, |last| last + interval_ms);
+        &self.state
+    }
+
+    /// Whether an update is due at `now_ms`
+    pub fn due(&mut self, now_ms: u64) -> bool {
+        now_ms >= self.evaluate(now_ms).next_update_ms
+    }
+
+    /// Record a completed coordinate update
+    ///
+    /// Observations made under the old coordinates are discarded.
+    pub fn updated(&mut self, now_ms: u64) {
+        self.greedy.clear();
+        self.stretch.clear();
+        self.state.updates += 1;
+        self.state.last_update_ms = Some(now_ms);
+        self.state.next_update_ms = now_ms + self.state.interval_ms;
+    }
+
+    pub fn state(&self) -> &CoordinateControlState {
+        &self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller() -> CoordinateUpdateController {
+        CoordinateUpdateController::new(CoordinateControlConfig::default())
+    }
+
+    #[test]
+    fn test_degraded_quality_shortens_interval() {
+        let mut c = controller();
+        assert!(c.due(0), "first update is due immediately");
+        c.updated(0);
+        assert!(!c.due(1_000));
+
+        for i in 0..100 {
+            c.observe_route(i % 2 == 0);
+        }
+        c.observe_stretch(3.0);
+        let state = c.evaluate(2_000).clone();
+        assert_eq!(state.greedy_success, Some(0.5));
+        assert!(state.error > 0.0);
+        assert_eq!(state.interval_ms, 5_000);
+        assert!(c.due(5_000));
+    }
+
+    #[test]
+    fn test_stable_quality_backs_off() {
+        let mut c = controller();
+        c.updated(0);
+        let mut now = 0;
+        let mut intervals = Vec::new();
+        for _ in 0..20 {
+            for _ in 0..50 {
+                c.observe_route(true);
+            }
+            c.observe_stretch(1.0);
+            now += 60_000;
+            intervals.push(c.evaluate(now).interval_ms);
+        }
+        // Proportional term lengthens at once; the integral keeps backing off
+        assert!(intervals[0] > 60_000);
+        assert!(intervals.windows(2).all(|w| w[1] >= w[0]));
+        assert_eq!(*intervals.last().unwrap(), 600_000);
+
+        // A quality drop pulls the interval down despite the accumulated integral
+        for _ in 0..256 {
+            c.observe_route(false);
+        }
+        assert!(c.evaluate(now + 1_000).interval_ms < 60_000);
+    }
+
+    #[test]
+    fn test_config_validation() {
+        assert!(CoordinateControlConfig::default().validate().is_ok());
+        let invalid = CoordinateControlConfig { min_interval_ms: 70_000, ..Default::default() };
+        assert!(invalid.validate().is_err());
+        assert!(CoordinateControlConfig { target_stretch: 0.5, ..Default::default() }.validate().is_err());
+    }
+}
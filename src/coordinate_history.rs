@@ -0,0 +1,246 @@
+//! Coordinate History
+//!
+//! Coordinates drift as Ricci flow rounds and gossip converge, so a route
+//! that failed an hour ago may have failed against coordinates that no
+//! longer exist. Nodes keep a bounded history of their own coordinate and
+//! of every coordinate observed for their neighbors. `replay_delivery`
+//! routes a failed delivery again, once against the coordinates known at
+//! the time of the failure and once against the current ones. If only the
+//! historical run fails, stale coordinates caused the failure.
+
+use std::collections::{HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::{NodeId, RoutingCoordinate};
+use crate::routing::{DeliveryResult, GPRouter};
+use crate::PoincareDiskPoint;
+
+/// A coordinate as observed at some time
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CoordinateSample {
+    /// Unix time of the observation in milliseconds
+    pub at_ms: u64,
+    pub version: u64,
+    pub coord: PoincareDiskPoint,
+}
+
+/// Bounded per-node coordinate histories
+#[derive(Debug, Clone)]
+pub struct CoordinateHistory {
+    local: NodeId,
+    /// Samples kept per node, oldest dropped first
+    capacity: usize,
+    /// Remote nodes tracked; the one heard from least recently is dropped
+    max_nodes: usize,
+    own: VecDeque<CoordinateSample>,
+    remote: HashMap<NodeId, VecDeque<CoordinateSample>>,
+}
+
+impl CoordinateHistory {
+    pub fn new(local: NodeId, capacity: usize, max_nodes: usize) -> Self {
+        Self {
+            local,
+            capacity: capacity.max(1),
+            max_nodes: max_nodes.max(1),
+            own: VecDeque::new(),
+            remote: HashMap::new(),
+        }
+    }
+
+    /// Record a coordinate of `node`, which may be the local node
+    ///
+    /// Observations that repeat the latest sample are ignored.
+    pub fn record(&mut self, node: &NodeId, sample: CoordinateSample) {
+        if *node != self.local && !self.remote.contains_key(node) && self.remote.len() >= self.max_nodes {
+            let stalest = self
+                .remote
+                .iter()
+                .min_by_key(|(_, samples)| samples.back().map_or(0, |s| s.at_ms))
+                .map(|(id, _)| id.clone());
+            if let Some(id) = stalest {
+                self.remote.remove(&id);
+            }
+        }
+
+        let samples = if *node == self.local {
+            &mut self.own
+        } else {
+            self.remote.entry(node.clone()).or_default()
+        };
+        if samples.back().is_some_and(|last| last.version == sample.version && last.coord == sample.coord) {
+            return;
+        }
+        samples.push_back(sample);
+        while samples.len() > self.capacity {
+            samples.pop_front();
+        }
+    }
+
+    /// Samples of `node`, oldest first
+    pub fn samples(&self, node: &NodeId) -> Option<&VecDeque<CoordinateSample>> {
+        if *node == self.local {
+            Some(&self.own)
+        } else {
+            self.remote.get(node)
+        }
+    }
+
+    /// Remote nodes with a history
+    pub fn nodes(&self) -> impl Iterator<Item = &NodeId> {
+        self.remote.keys()
+    }
+
+    /// Latest sample of `node` taken at or before `at_ms`
+    pub fn at(&self, node: &NodeId, at_ms: u64) -> Option<CoordinateSample> {
+        self.samples(node)?.iter().rev().find(|s| s.at_ms <= at_ms).copied()
+    }
+}
+
+/// Outcome of one replayed delivery
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayOutcome {
+    pub success: bool,
+    pub hops: u32,
+    pub path: Vec<NodeId>,
+    pub failure_reason: Option<String>,
+}
+
+impl From<DeliveryResult> for ReplayOutcome {
+    fn from(result: DeliveryResult) -> Self {
+        Self {
+            success: result.success,
+            hops: result.hops,
+            path: result.path,
+            failure_reason: result.failure_reason,
+        }
+    }
+}
+
+/// Whether stale coordinates explain a failed delivery
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StalenessVerdict {
+    /// Fails with the historical coordinates, succeeds with the current ones
+    Stale,
+    /// Fails with both: look for topology or embedding problems instead
+    NotStale,
+    /// Succeeds with the historical coordinates, so the failure came from
+    /// state the history does not capture (e.g. a neighbor that was down)
+    NotReproduced,
+}
+
+/// Result of `replay_delivery`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayReport {
+    pub at_ms: u64,
+    pub historical: ReplayOutcome,
+    pub current: ReplayOutcome,
+    /// Nodes whose coordinate at `at_ms` differs from the current one
+    pub stale_nodes: Vec<NodeId>,
+    pub verdict: StalenessVerdict,
+}
+
+/// Replay a delivery from `source` at `at_ms` and now
+///
+/// `router` holds the current coordinates; the historical run rewinds every
+/// node with a sample at or before `at_ms` to that sample. Nodes without
+/// such a sample keep their current coordinate.
+pub fn replay_delivery(
+    router: &GPRouter,
+    history: &CoordinateHistory,
+    source: &NodeId,
+    destination: &NodeId,
+    target: PoincareDiskPoint,
+    ttl: u32,
+    at_ms: u64,
+) -> ReplayReport {
+    let mut historical = router.clone();
+    let mut stale_nodes = Vec::new();
+    for id in router.node_ids() {
+        let (Some(sample), Some(node)) = (history.at(&id, at_ms), router.get_node(&id)) else {
+            continue;
+        };
+        if sample.coord != node.coord.point {
+            historical.set_coordinate(&id, RoutingCoordinate::new(sample.coord, sample.version));
+            stale_nodes.push(id);
+        }
+    }
+    stale_nodes.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let historical = ReplayOutcome::from(historical.simulate_delivery(source, destination, target, ttl));
+    let current = ReplayOutcome::from(router.simulate_delivery(source, destination, target, ttl));
+    let verdict = match (historical.success, current.success) {
+        (true, _) => StalenessVerdict::NotReproduced,
+        (false, true) => StalenessVerdict::Stale,
+        (false, false) => StalenessVerdict::NotStale,
+    };
+    ReplayReport {
+        at_ms,
+        historical,
+        current,
+        stale_nodes,
+        verdict,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routing::RoutingNode;
+
+    fn sample(at_ms: u64, version: u64, x: f64, y: f64) -> CoordinateSample {
+        CoordinateSample { at_ms, version, coord: PoincareDiskPoint::new(x, y).unwrap() }
+    }
+
+    #[test]
+    fn test_history_is_bounded_and_queryable_by_time() {
+        let mut history = CoordinateHistory::new(NodeId::new("local"), 3, 2);
+        let a = NodeId::new("a");
+        for v in 0..5 {
+            history.record(&a, sample(v * 10, v, 0.1 * v as f64, 0.0));
+        }
+        history.record(&a, sample(60, 4, 0.4, 0.0)); // repeat, ignored
+        assert_eq!(history.samples(&a).unwrap().len(), 3);
+        assert_eq!(history.at(&a, 35).unwrap().version, 3);
+        assert!(history.at(&a, 5).is_none());
+
+        // The least recently heard remote node is dropped, never the local one
+        history.record(&NodeId::new("local"), sample(0, 0, 0.0, 0.0));
+        history.record(&NodeId::new("b"), sample(100, 0, 0.0, 0.1));
+        history.record(&NodeId::new("c"), sample(200, 0, 0.0, 0.2));
+        let mut nodes: Vec<&str> = history.nodes().map(|n| n.0.as_str()).collect();
+        nodes.sort();
+        assert_eq!(nodes, vec!["b", "c"]);
+        assert_eq!(history.samples(&NodeId::new("local")).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_replay_detects_stale_coordinates() {
+        // s reaches d through r in two hops, or through q and x in three;
+        // at t=100 r's coordinate pointed away from d
+        let mut router = GPRouter::new();
+        for (id, x, y) in [("s", 0.0, 0.0), ("r", 0.3, 0.0), ("d", 0.6, 0.0), ("q", 0.2, 0.3), ("x", 0.5, 0.4)] {
+            let coord = RoutingCoordinate::new(PoincareDiskPoint::new(x, y).unwrap(), 1);
+            router.add_node(RoutingNode::new(NodeId::new(id), coord));
+        }
+        for (a, b) in [("s", "r"), ("r", "d"), ("s", "q"), ("q", "x"), ("x", "d")] {
+            router.add_edge(&NodeId::new(a), &NodeId::new(b));
+        }
+
+        let mut history = CoordinateHistory::new(NodeId::new("s"), 8, 8);
+        history.record(&NodeId::new("r"), sample(100, 0, -0.5, 0.0));
+        history.record(&NodeId::new("r"), sample(200, 1, 0.3, 0.0));
+
+        let target = PoincareDiskPoint::new(0.6, 0.0).unwrap();
+        let (s, d) = (NodeId::new("s"), NodeId::new("d"));
+        let report = replay_delivery(&router, &history, &s, &d, target, 2, 150);
+        assert_eq!(report.verdict, StalenessVerdict::Stale);
+        assert_eq!(report.stale_nodes, vec![NodeId::new("r")]);
+        assert!(report.current.success);
+
+        let report = replay_delivery(&router, &history, &s, &d, target, 2, 250);
+        assert_eq!(report.verdict, StalenessVerdict::NotReproduced);
+        assert!(report.stale_nodes.is_empty());
+    }
+}
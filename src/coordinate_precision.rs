@@ -0,0 +1,314 @@
+//! Coordinate Wire Precision
+//!
+//! Coordinate updates carry the new position as two f64s. Smaller encodings
+//! halve or quarter that, but naive quantization of `(x, y)` breaks down near
+//! the boundary: points crowd toward `|z| = 1`, where a rounding error of
+//! 1e-7 is a large hyperbolic distance and can even push a point out of the
+//! disk. The compact encodings therefore send the hyperbolic radius
+//! `ρ = 2 atanh |z|`, which grows like `-ln(1 - |z|)`, and the angle. A
+//! decoded radius is capped at `MAX_RADIUS`, so every decoded point lies
+//! inside the disk.
+//!
+//! Angular error still scales with `sinh ρ`, so peripheral nodes lose more
+//! than central ones. `analyze` quantizes every coordinate of a router and
+//! reports the displacement and the change in routing stretch per precision,
+//! to pick a level for a given topology.
+//!
+//! `F64` keeps the original payload, so nodes that predate this module read
+//! those updates. The compact payloads are understood by every node that has
+//! this module, whatever precision it sends with.
+
+use std::f64::consts::TAU;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bench::{run_routing_tests, RoutingTestConfig};
+use crate::coordinates::RoutingCoordinate;
+use crate::routing::GPRouter;
+use crate::PoincareDiskPoint;
+
+/// Largest hyperbolic radius a compact encoding can carry
+pub const MAX_RADIUS: f64 = 32.0;
+
+/// Length of the original `(PoincareDiskPoint, u64)` update payload
+const LEGACY_UPDATE_LEN: usize = 24;
+
+/// How coordinates are encoded on the wire
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoordinatePrecision {
+    /// Cartesian f64 pair, lossless
+    #[default]
+    F64,
+    /// Hyperbolic radius and angle as f32
+    F32,
+    /// Hyperbolic radius and angle as 32-bit fixed point
+    Fixed32,
+    /// Hyperbolic radius and angle as 16-bit fixed point
+    Fixed16,
+}
+
+impl CoordinatePrecision {
+    pub const ALL: [CoordinatePrecision; 4] =
+        [CoordinatePrecision::F64, CoordinatePrecision::F32, CoordinatePrecision::Fixed32, CoordinatePrecision::Fixed16];
+
+    /// Bytes of one encoded coordinate, excluding its tag
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            CoordinatePrecision::F64 => 16,
+            CoordinatePrecision::F32 | CoordinatePrecision::Fixed32 => 8,
+            CoordinatePrecision::Fixed16 => 4,
+        }
+    }
+
+    fn tag(&self) -> u8 {
+        match self {
+            CoordinatePrecision::F64 => 0,
+            CoordinatePrecision::F32 => 1,
+            CoordinatePrecision::Fixed32 => 2,
+            CoordinatePrecision::Fixed16 => 3,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.tag() == tag)
+    }
+}
+
+/// Why an encoded coordinate could not be read
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PrecisionError {
+    #[error("Encoded coordinate is truncated")]
+    Truncated,
+    #[error("Unknown coordinate encoding {0}")]
+    UnknownEncoding(u8),
+    #[error("Encoded coordinate is not in the disk")]
+    OutsideDisk,
+}
+
+/// Hyperbolic radius (capped) and angle in `[0, 2π)`
+fn to_polar(point: &PoincareDiskPoint) -> (f64, f64) {
+    let radius = (2.0 * point.euclidean_norm().atanh()).min(MAX_RADIUS);
+    (radius, point.angle().rem_euclid(TAU))
+}
+
+fn from_polar(radius: f64, angle: f64) -> Result<PoincareDiskPoint, PrecisionError> {
+    if !radius.is_finite() || !angle.is_finite() || radius < 0.0 {
+        return Err(PrecisionError::OutsideDisk);
+    }
+    let r = (radius.min(MAX_RADIUS) / 2.0).tanh();
+    PoincareDiskPoint::from_polar(r, angle).ok_or(PrecisionError::OutsideDisk)
+}
+
+fn fixed(value: f64, range: f64, max: u32) -> u32 {
+    ((value / range).clamp(0.0, 1.0) * max as f64).round() as u32
+}
+
+fn unfixed(value: u32, range: f64, max: u32) -> f64 {
+    value as f64 / max as f64 * range
+}
+
+/// Encode a coordinate as a tag byte and its body
+pub fn encode(point: &PoincareDiskPoint, precision: CoordinatePrecision) -> Vec<u8> {
+    let mut bytes = vec![precision.tag()];
+    let (radius, angle) = to_polar(point);
+    match precision {
+        CoordinatePrecision::F64 => {
+            bytes.extend_from_slice(&point.x.to_le_bytes());
+            bytes.extend_from_slice(&point.y.to_le_bytes());
+        }
+        CoordinatePrecision::F32 => {
+            bytes.extend_from_slice(&(radius as f32).to_le_bytes());
+            bytes.extend_from_slice(&(angle as f32).to_le_bytes());
+        }
+        CoordinatePrecision::Fixed32 => {
+            bytes.extend_from_slice(&fixed(radius, MAX_RADIUS, u32::MAX).to_le_bytes());
+            // 2^32 steps per turn, so a full turn wraps to zero
+            bytes.extend_from_slice(&((angle / TAU * 4_294_967_296.0).round() as u64 as u32).to_le_bytes());
+        }
+        CoordinatePrecision::Fixed16 => {
+            bytes.extend_from_slice(&(fixed(radius, MAX_RADIUS, u16::MAX as u32) as u16).to_le_bytes());
+            bytes.extend_from_slice(&((angle / TAU * 65_536.0).round() as u32 as u16).to_le_bytes());
+        }
+    }
+    bytes
+}
+
+/// Decode a coordinate written by `encode`, returning it and the bytes read
+pub fn decode(bytes: &[u8]) -> Result<(PoincareDiskPoint, usize), PrecisionError> {
+    let (&tag, body) = bytes.split_first().ok_or(PrecisionError::Truncated)?;
+    let precision = CoordinatePrecision::from_tag(tag).ok_or(PrecisionError::UnknownEncoding(tag))?;
+    let len = precision.encoded_len();
+    let body = body.get(..len).ok_or(PrecisionError::Truncated)?;
+    let half = |i: usize| &body[i * len / 2..(i + 1) * len / 2];
+
+    let point = match precision {
+        CoordinatePrecision::F64 => {
+            let x = f64::from_le_bytes(half(0).try_into().unwrap_or_default());
+            let y = f64::from_le_bytes(half(1).try_into().unwrap_or_default());
+            PoincareDiskPoint::try_new(x, y).map_err(|_| PrecisionError::OutsideDisk)?
+        }
+        CoordinatePrecision::F32 => {
+            let radius = f32::from_le_bytes(half(0).try_into().unwrap_or_default());
+            let angle = f32::from_le_bytes(half(1).try_into().unwrap_or_default());
+            from_polar(radius as f64, angle as f64)?
+        }
+        CoordinatePrecision::Fixed32 => {
+            let radius = u32::from_le_bytes(half(0).try_into().unwrap_or_default());
+            let angle = u32::from_le_bytes(half(1).try_into().unwrap_or_default());
+            from_polar(unfixed(radius, MAX_RADIUS, u32::MAX), angle as f64 / 4_294_967_296.0 * TAU)?
+        }
+        CoordinatePrecision::Fixed16 => {
+            let radius = u16::from_le_bytes(half(0).try_into().unwrap_or_default());
+            let angle = u16::from_le_bytes(half(1).try_into().unwrap_or_default());
+            from_polar(unfixed(radius as u32, MAX_RADIUS, u16::MAX as u32), angle as f64 / 65_536.0 * TAU)?
+        }
+    };
+    Ok((point, 1 + len))
+}
+
+/// The coordinate a receiver sees after a round trip at `precision`
+pub fn quantize(point: &PoincareDiskPoint, precision: CoordinatePrecision) -> PoincareDiskPoint {
+    if precision == CoordinatePrecision::F64 {
+        return *point;
+    }
+    decode(&encode(point, precision)).map(|(p, _)| p).unwrap_or(*point)
+}
+
+/// Payload of a coordinate update
+///
+/// `F64` writes the original bincode `(coordinate, version)` payload; the
+/// others write the encoded coordinate followed by the version, which is
+/// always shorter than the original.
+pub fn encode_update(point: &PoincareDiskPoint, version: u64, precision: CoordinatePrecision) -> Vec<u8> {
+    if precision == CoordinatePrecision::F64 {
+        return bincode::serialize(&(point, version)).unwrap_or_default();
+    }
+    let mut bytes = encode(point, precision);
+    bytes.extend_from_slice(&version.to_le_bytes());
+    bytes
+}
+
+/// Read a coordinate update payload in either form
+pub fn decode_update(payload: &[u8]) -> Result<(PoincareDiskPoint, u64), PrecisionError> {
+    if payload.len() == LEGACY_UPDATE_LEN {
+        let (point, version): (PoincareDiskPoint, u64) =
+            bincode::deserialize(payload).map_err(|_| PrecisionError::Truncated)?;
+        let point = PoincareDiskPoint::try_new(point.x, point.y).map_err(|_| PrecisionError::OutsideDisk)?;
+        return Ok((point, version));
+    }
+    let (point, read) = decode(payload)?;
+    let version = payload
+        .get(read..read + 8)
+        .and_then(|b| b.try_into().ok())
+        .map(u64::from_le_bytes)
+        .ok_or(PrecisionError::Truncated)?;
+    Ok((point, version))
+}
+
+/// Effect of one precision on a router's coordinates and routes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PrecisionReport {
+    pub precision: CoordinatePrecision,
+    /// Coordinate update payload size
+    pub update_bytes: usize,
+    /// Mean hyperbolic distance between original and quantized coordinates
+    pub mean_error: f64,
+    pub max_error: f64,
+    /// Hyperbolic radius of the node with the largest error
+    pub worst_radius: f64,
+    pub success_rate: f64,
+    pub stretch: f64,
+    pub max_stretch: f64,
+    /// Stretch minus the stretch with exact coordinates
+    pub stretch_change: f64,
+}
+
+/// Quantize every coordinate of `router` at each precision and route sampled pairs
+pub fn analyze(router: &GPRouter, precisions: &[CoordinatePrecision], config: &RoutingTestConfig) -> Vec<PrecisionReport> {
+    let nodes = router.node_ids();
+    let exact = run_routing_tests(router, &nodes, config);
+
+    precisions
+        .iter()
+        .map(|&precision| {
+            let mut quantized = router.clone();
+            let (mut total, mut max_error, mut worst_radius) = (0.0, 0.0, 0.0);
+            for id in &nodes {
+                let Some(coord) = router.get_node(id).map(|n| n.coord) else {
+                    continue;
+                };
+                let point = quantize(&coord.point, precision);
+                let error = coord.point.hyperbolic_distance(&point);
+                total += error;
+                if error > max_error {
+                    max_error = error;
+                    worst_radius = to_polar(&coord.point).0;
+                }
+                quantized.set_coordinate(id, RoutingCoordinate { point, ..coord });
+            }
+            let result = run_routing_tests(&quantized, &nodes, config);
+            PrecisionReport {
+                precision,
+                update_bytes: encode_update(&PoincareDiskPoint::origin(), 0, precision).len(),
+                mean_error: total / nodes.len().max(1) as f64,
+                max_error,
+                worst_radius,
+                success_rate: result.success_rate,
+                stretch: result.stretch,
+                max_stretch: result.max_stretch,
+                stretch_change: result.stretch - exact.stretch,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::{barabasi_albert, build_pie_router};
+
+    #[test]
+    fn test_round_trip_stays_in_disk() {
+        let points = [
+            PoincareDiskPoint::origin(),
+            PoincareDiskPoint::new(0.3, -0.4).unwrap(),
+            PoincareDiskPoint::from_polar(0.999_999, 2.0).unwrap(),
+            PoincareDiskPoint::from_polar(1.0 - 1e-16, -0.5).unwrap(),
+        ];
+        for precision in CoordinatePrecision::ALL {
+            for point in &points {
+                let payload = encode_update(point, 7, precision);
+                let (decoded, version) = decode_update(&payload).unwrap();
+                assert_eq!(version, 7);
+                assert!(decoded.euclidean_norm() < 1.0);
+                if point.euclidean_norm() < 0.9 {
+                    let error = decoded.hyperbolic_distance(point);
+                    assert!(error < 1e-2, "{:?} moved {} by {}", precision, point.x, error);
+                }
+                if precision != CoordinatePrecision::F64 {
+                    assert_eq!(payload.len(), 1 + precision.encoded_len() + 8);
+                    assert!(payload.len() < LEGACY_UPDATE_LEN);
+                }
+            }
+        }
+        assert_eq!(quantize(&points[1], CoordinatePrecision::F64), points[1]);
+        assert_eq!(decode(&[9, 0, 0]), Err(PrecisionError::UnknownEncoding(9)));
+        assert_eq!(decode(&[1, 0, 0]), Err(PrecisionError::Truncated));
+    }
+
+    #[test]
+    fn test_analysis_orders_precisions() {
+        let router = build_pie_router(&barabasi_albert(150, 3, 5)).unwrap();
+        let config = RoutingTestConfig { pairs: 100, seed: 3 };
+        let reports = analyze(&router, &CoordinatePrecision::ALL, &config);
+
+        let exact = &reports[0];
+        assert_eq!((exact.max_error, exact.stretch_change), (0.0, 0.0));
+        assert_eq!(exact.update_bytes, LEGACY_UPDATE_LEN);
+        let coarse = &reports[3];
+        assert_eq!(coarse.update_bytes, 13);
+        assert!(coarse.max_error > reports[2].max_error);
+        assert!(coarse.worst_radius > 0.0);
+    }
+}
@@ -0,0 +1,348 @@
+//! Ground-Truth Coordinate Recovery
+//!
+//! Measures how well an embedding recovers the latent geometry of a graph
+//! generated with known coordinates (see `graph_generators`). An embedding
+//! is only defined up to isometry, and its curvature scale is arbitrary, so
+//! the inferred coordinates are first aligned to the truth (hyperbolic
+//! Procrustes):
+//!
+//! ```text
+//! aligned(p) = T_t0( R_θ( S_s( T_-a( reflect(p) ) ) ) )
+//! ```
+//!
+//! `T_c` is the Möbius translation moving the origin to `c`, `R_θ` a
+//! rotation and `S_s` scales hyperbolic distances from the origin by `s`.
+//! `t0` is the true position of the most central node, whose inferred
+//! position seeds `a`. Rotation and scale start from closed-form estimates,
+//! then a compass search over `(a, θ, ln s)` minimises the mean squared
+//! hyperbolic distance between aligned and true positions, once with and
+//! once without reflection. Scaling about one point only approximates a
+//! change of curvature, so embeddings at a different curvature keep some
+//! residual error away from the anchor.
+//!
+//! The report gives error distributions for angle, radius (both in the
+//! native polar coordinates of the truth) and position.
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+use num_complex::Complex64;
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::graph_generators::{native_distance, HyperbolicGraph};
+use crate::PoincareDiskPoint;
+
+/// Compass search stops once every step is below this
+const MIN_STEP: f64 = 1e-10;
+/// Bound on compass search iterations per reflection
+const MAX_ITERATIONS: usize = 4000;
+/// Disk points are kept this far inside the boundary
+const MAX_NORM: f64 = 1.0 - 1e-15;
+
+/// Isometry plus scale mapping inferred coordinates onto the truth
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Alignment {
+    /// Inferred coordinates are mirrored (complex conjugate) first
+    pub reflect: bool,
+    /// Inferred point moved to the origin
+    pub center: (f64, f64),
+    /// Scale applied to hyperbolic distances from `center`
+    pub scale: f64,
+    pub rotation: f64,
+    /// True point the origin is moved to
+    pub target: (f64, f64),
+}
+
+impl Alignment {
+    /// Aligned position in native polar coordinates (radius, angle)
+    pub fn apply(&self, p: &PoincareDiskPoint) -> (f64, f64) {
+        let mut z = Complex64::new(p.x, p.y);
+        if self.reflect {
+            z = z.conj();
+        }
+        let z = translate(-Complex64::new(self.center.0, self.center.1), z);
+        let z = scale(z, self.scale) * Complex64::from_polar(1.0, self.rotation);
+        let z = translate(Complex64::new(self.target.0, self.target.1), z);
+        (2.0 * z.norm().min(MAX_NORM).atanh(), z.arg().rem_euclid(2.0 * PI))
+    }
+
+    fn with_params(&self, params: &[f64; 4]) -> Option<Self> {
+        let center = Complex64::new(params[0], params[1]);
+        if center.norm() >= MAX_NORM {
+            return None;
+        }
+        Some(Self {
+            center: (params[0], params[1]),
+            rotation: params[2],
+            scale: params[3].exp(),
+            ..*self
+        })
+    }
+
+    fn params(&self) -> [f64; 4] {
+        [self.center.0, self.center.1, self.rotation, self.scale.ln()]
+    }
+}
+
+/// Möbius translation moving the origin to `c`
+fn translate(c: Complex64, z: Complex64) -> Complex64 {
+    let w = (z + c) / (1.0 + c.conj() * z);
+    if w.norm() >= MAX_NORM {
+        w * (MAX_NORM / w.norm())
+    } else {
+        w
+    }
+}
+
+/// Scale the hyperbolic distance of `z` from the origin by `s`
+fn scale(z: Complex64, s: f64) -> Complex64 {
+    let norm = z.norm();
+    if norm == 0.0 {
+        return z;
+    }
+    z * ((s * norm.min(MAX_NORM).atanh()).tanh().min(MAX_NORM) / norm)
+}
+
+/// Summary of an error sample
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ErrorDistribution {
+    pub mean: f64,
+    pub median: f64,
+    pub p90: f64,
+    pub max: f64,
+}
+
+impl ErrorDistribution {
+    pub fn from_samples(samples: &[f64]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let quantile = |q: f64| sorted[((sorted.len() - 1) as f64 * q).round() as usize];
+        Self {
+            mean: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            median: quantile(0.5),
+            p90: quantile(0.9),
+            max: sorted[sorted.len() - 1],
+        }
+    }
+}
+
+/// Recovery quality of an embedding against ground truth
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RecoveryReport {
+    /// Nodes with both true and inferred coordinates
+    pub nodes: usize,
+    pub alignment: Alignment,
+    /// Angle between aligned and true positions, in radians (0 to π)
+    pub angular_error: ErrorDistribution,
+    /// Difference of aligned and true radius, in hyperbolic units
+    pub radial_error: ErrorDistribution,
+    /// Hyperbolic distance between aligned and true positions
+    pub position_error: ErrorDistribution,
+}
+
+/// Nodes present in both the truth and the embedding
+struct Samples<'a> {
+    truth: Vec<(f64, f64)>,
+    inferred: Vec<&'a PoincareDiskPoint>,
+}
+
+impl Samples<'_> {
+    fn cost(&self, alignment: &Alignment) -> f64 {
+        let total: f64 = self
+            .truth
+            .iter()
+            .zip(&self.inferred)
+            .map(|(&(r, theta), p)| {
+                let (ar, atheta) = alignment.apply(p);
+                native_distance(ar, atheta, r, theta).powi(2)
+            })
+            .sum();
+        total / self.truth.len() as f64
+    }
+}
+
+/// Fit an alignment of `inferred` onto the true coordinates of `graph`
+///
+/// Returns None with fewer than three common nodes.
+pub fn align(graph: &HyperbolicGraph, inferred: &HashMap<NodeId, PoincareDiskPoint>) -> Option<Alignment> {
+    let (samples, anchor) = samples(graph, inferred)?;
+    [false, true]
+        .into_iter()
+        .map(|reflect| fit(&samples, anchor, reflect))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(alignment, _)| alignment)
+}
+
+/// Align `inferred` to the truth and measure the remaining error
+pub fn evaluate_recovery(
+    graph: &HyperbolicGraph,
+    inferred: &HashMap<NodeId, PoincareDiskPoint>,
+) -> Option<RecoveryReport> {
+    let alignment = align(graph, inferred)?;
+    let (samples, _) = samples(graph, inferred)?;
+
+    let mut angular = Vec::with_capacity(samples.truth.len());
+    let mut radial = Vec::with_capacity(samples.truth.len());
+    let mut position = Vec::with_capacity(samples.truth.len());
+    for (&(r, theta), p) in samples.truth.iter().zip(&samples.inferred) {
+        let (ar, atheta) = alignment.apply(p);
+        angular.push(PI - (PI - (atheta - theta).abs()).abs());
+        radial.push((ar - r).abs());
+        position.push(native_distance(ar, atheta, r, theta));
+    }
+    Some(RecoveryReport {
+        nodes: samples.truth.len(),
+        alignment,
+        angular_error: ErrorDistribution::from_samples(&angular),
+        radial_error: ErrorDistribution::from_samples(&radial),
+        position_error: ErrorDistribution::from_samples(&position),
+    })
+}
+
+/// Common nodes, and the index among them of the most central true node
+fn samples<'a>(
+    graph: &HyperbolicGraph,
+    inferred: &'a HashMap<NodeId, PoincareDiskPoint>,
+) -> Option<(Samples<'a>, usize)> {
+    let mut samples = Samples { truth: Vec::new(), inferred: Vec::new() };
+    for (i, id) in graph.nodes.iter().enumerate() {
+        if let Some(p) = inferred.get(id) {
+            samples.truth.push((graph.radii[i], graph.angles[i]));
+            samples.inferred.push(p);
+        }
+    }
+    if samples.truth.len() < 3 {
+        return None;
+    }
+    let anchor = (0..samples.truth.len()).min_by(|&a, &b| samples.truth[a].0.total_cmp(&samples.truth[b].0))?;
+    Some((samples, anchor))
+}
+
+/// Closed-form starting point, refined by compass search
+fn fit(samples: &Samples, anchor: usize, reflect: bool) -> (Alignment, f64) {
+    let (r0, theta0) = samples.truth[anchor];
+    let target = Complex64::from_polar((r0 / 2.0).tanh(), theta0);
+    let mirror = |p: &PoincareDiskPoint| {
+        let z = Complex64::new(p.x, p.y);
+        if reflect { z.conj() } else { z }
+    };
+    let center = mirror(samples.inferred[anchor]);
+
+    // Both point sets as seen from their anchor
+    let seen: Vec<(Complex64, Complex64)> = samples
+        .truth
+        .iter()
+        .zip(&samples.inferred)
+        .map(|(&(r, theta), p)| {
+            let truth = translate(-target, Complex64::from_polar((r / 2.0).tanh(), theta));
+            (translate(-center, mirror(p)), truth)
+        })
+        .collect();
+
+    // Scale: least squares on distances from the anchor
+    let (mut cross, mut square) = (0.0, 0.0);
+    for (inferred, truth) in &seen {
+        let (di, dt) = (2.0 * inferred.norm().min(MAX_NORM).atanh(), 2.0 * truth.norm().min(MAX_NORM).atanh());
+        cross += di * dt;
+        square += di * di;
+    }
+    let s = if square > 0.0 && cross > 0.0 { cross / square } else { 1.0 };
+
+    // Rotation: circular mean of the angle differences, weighted by distance
+    let turn: Complex64 = seen
+        .iter()
+        .filter(|(inferred, truth)| inferred.norm() > 0.0 && truth.norm() > 0.0)
+        .map(|(inferred, truth)| {
+            let weight = inferred.norm().min(truth.norm());
+            Complex64::from_polar(weight, truth.arg() - inferred.arg())
+        })
+        .sum();
+
+    let mut best = Alignment {
+        reflect,
+        center: (center.re, center.im),
+        scale: s,
+        rotation: turn.arg(),
+        target: (target.re, target.im),
+    };
+    let mut best_cost = samples.cost(&best);
+    let mut steps = [0.05, 0.05, 0.1, 0.1];
+    for _ in 0..MAX_ITERATIONS {
+        if steps.iter().all(|s| *s < MIN_STEP) {
+            break;
+        }
+        let mut improved = false;
+        for k in 0..4 {
+            for direction in [1.0, -1.0] {
+                let mut params = best.params();
+                params[k] += direction * steps[k];
+                let Some(candidate) = best.with_params(&params) else { continue };
+                let cost = samples.cost(&candidate);
+                if cost < best_cost {
+                    best = candidate;
+                    best_cost = cost;
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            steps.iter_mut().for_each(|s| *s /= 2.0);
+        }
+    }
+    (best, best_cost)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph_generators::{hyperbolic_random_graph, HrgConfig};
+
+    fn graph() -> HyperbolicGraph {
+        hyperbolic_random_graph(&HrgConfig { nodes: 200, avg_degree: 6.0, ..Default::default() }).unwrap()
+    }
+
+    /// Scale about the most central node, then mirror, rotate and translate
+    fn transformed(graph: &HyperbolicGraph, s: f64, noise: f64) -> HashMap<NodeId, PoincareDiskPoint> {
+        let shift = Complex64::new(0.2, -0.1);
+        let central = (0..graph.len()).min_by(|&a, &b| graph.radii[a].total_cmp(&graph.radii[b])).unwrap();
+        let t0 = Complex64::from_polar((graph.radii[central] / 2.0).tanh(), graph.angles[central]);
+        (0..graph.len())
+            .map(|i| {
+                let jitter = if i % 2 == 0 { noise } else { -noise };
+                let z = Complex64::from_polar((graph.radii[i] / 2.0).tanh(), graph.angles[i] + jitter);
+                let z = scale(translate(-t0, z), s).conj();
+                let z = translate(shift, z * Complex64::from_polar(1.0, 0.7));
+                (graph.nodes[i].clone(), PoincareDiskPoint::new(z.re, z.im).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_recovers_isometric_copy() {
+        let graph = graph();
+        let report = evaluate_recovery(&graph, &transformed(&graph, 0.5, 0.0)).unwrap();
+        assert_eq!(report.nodes, 200);
+        assert!(report.alignment.reflect);
+        assert!((report.alignment.scale - 2.0).abs() < 1e-3, "scale {}", report.alignment.scale);
+        assert!(report.position_error.p90 < 1e-2, "{:?}", report.position_error);
+        assert!(report.angular_error.median < 1e-3);
+        assert!(report.radial_error.median < 1e-3);
+    }
+
+    #[test]
+    fn test_noise_shows_in_error_distribution() {
+        let graph = graph();
+        let report = evaluate_recovery(&graph, &transformed(&graph, 1.0, 0.05)).unwrap();
+        assert!(report.angular_error.mean > 0.01 && report.angular_error.mean < 0.1, "{:?}", report.angular_error);
+        assert!(report.angular_error.max >= report.angular_error.p90);
+        assert!(report.angular_error.p90 >= report.angular_error.median);
+
+        assert!(evaluate_recovery(&graph, &HashMap::new()).is_none());
+        let summary = ErrorDistribution::from_samples(&[3.0, 1.0, 2.0]);
+        assert_eq!((summary.mean, summary.median, summary.max), (2.0, 2.0, 3.0));
+    }
+}
@@ -0,0 +1,323 @@
+//! Spanning-Tree Dissemination of Coordinate Updates
+//!
+//! Coordinate batches used to go to every neighbor, so in a dense graph an
+//! entry relayed a few hops crosses most edges of its neighborhood, often
+//! in both directions. Relayed entries now follow a spanning tree instead:
+//! each node's parent is its neighbor closest to the disk origin, provided
+//! it is closer than the node itself, which rebuilds the embedding tree the
+//! coordinates were assigned from. A neighbor is a child when, according to
+//! the copy of its neighbor list held through `neighbor_exchange`, we are
+//! its parent.
+//!
+//! An entry about ourselves still goes to every neighbor, since neighbors
+//! route with our coordinate. Relayed entries go only to the parent, the
+//! children, and neighbors whose list copy is missing or does not list us;
+//! their tree edge is unknown, so they are flooded. The whole tree is
+//! treated as stale, and every neighbor flooded, until links have been
+//! computed within `max_age_ms`.
+//!
+//! Each edge remembers the newest version of each node it has carried in
+//! either direction, so an entry is never sent back to the neighbor it came
+//! from, nor to the node it describes, nor twice over the same edge.
+//!
+//! Neighbors whose views of each other's coordinates lag can briefly
+//! disagree about a tree edge and miss relayed entries; the view digests on
+//! heartbeats notice the gap and ask for a resync.
+
+use std::collections::{BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinate_batch::CoordinateEntry;
+use crate::coordinates::NodeId;
+use crate::neighbor_exchange::NeighborEntry;
+use crate::PoincareDiskPoint;
+
+/// Spanning-tree dissemination settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoordinateTreeConfig {
+    /// Relay coordinate entries along the tree; off floods every neighbor
+    pub enabled: bool,
+    /// Age after which computed tree links no longer count
+    pub max_age_ms: u64,
+}
+
+impl Default for CoordinateTreeConfig {
+    fn default() -> Self {
+        Self { enabled: true, max_age_ms: 15_000 }
+    }
+}
+
+impl CoordinateTreeConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_age_ms == 0 {
+            return Err("Coordinate tree max age must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Neighbor closest to the origin, if closer than `coord`
+///
+/// Ties are broken by node ID so that both ends of an edge agree.
+pub fn parent_of<'a>(
+    coord: &PoincareDiskPoint,
+    neighbors: impl IntoIterator<Item = (&'a NodeId, PoincareDiskPoint)>,
+) -> Option<NodeId> {
+    let own = coord.euclidean_norm_sq();
+    neighbors
+        .into_iter()
+        .map(|(id, point)| (point.euclidean_norm_sq(), id))
+        .filter(|(norm, _)| *norm < own)
+        .min_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1 .0.cmp(&b.1 .0)))
+        .map(|(_, id)| id.clone())
+}
+
+/// This node's edges in the tree
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TreeLinks {
+    pub parent: Option<NodeId>,
+    pub children: BTreeSet<NodeId>,
+    /// Neighbors whose parent is not known
+    pub unknown: BTreeSet<NodeId>,
+    pub computed_ms: u64,
+}
+
+impl TreeLinks {
+    /// Derive the links from our neighbors and the lists they shared
+    pub fn compute(
+        local: &NodeId,
+        coord: &PoincareDiskPoint,
+        neighbors: &[(NodeId, PoincareDiskPoint)],
+        list_of: impl Fn(&NodeId) -> Option<Vec<NeighborEntry>>,
+        now: u64,
+    ) -> Self {
+        let mut links = Self {
+            parent: parent_of(coord, neighbors.iter().map(|(id, point)| (id, *point))),
+            computed_ms: now,
+            ..Self::default()
+        };
+        for (id, point) in neighbors {
+            if links.parent.as_ref() == Some(id) {
+                continue;
+            }
+            let list = list_of(id).unwrap_or_default();
+            if !list.iter().any(|entry| &entry.node == local) {
+                links.unknown.insert(id.clone());
+                continue;
+            }
+            let theirs: Vec<(NodeId, PoincareDiskPoint)> =
+                list.into_iter().map(|entry| (entry.node, entry.coord.into())).collect();
+            if parent_of(point, theirs.iter().map(|(id, point)| (id, *point))).as_ref() == Some(local) {
+                links.children.insert(id.clone());
+            }
+        }
+        links
+    }
+
+    /// Whether relayed entries go to `peer`
+    pub fn relays_to(&self, peer: &NodeId) -> bool {
+        self.parent.as_ref() == Some(peer) || self.children.contains(peer) || self.unknown.contains(peer)
+    }
+}
+
+/// Entries sent along tree edges, flooded, and withheld
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoordinateTreeStats {
+    pub tree_entries: u64,
+    pub flooded_entries: u64,
+    pub suppressed_entries: u64,
+}
+
+/// Tree links and per-edge history of one node
+#[derive(Debug, Clone, Default)]
+pub struct CoordinateTree {
+    config: CoordinateTreeConfig,
+    links: Option<TreeLinks>,
+    /// Newest version of each node carried over the edge to each neighbor
+    edges: HashMap<NodeId, HashMap<NodeId, u64>>,
+    stats: CoordinateTreeStats,
+}
+
+impl CoordinateTree {
+    pub fn new(config: CoordinateTreeConfig) -> Self {
+        Self { config, ..Self::default() }
+    }
+
+    pub fn config(&self) -> &CoordinateTreeConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: CoordinateTreeConfig) {
+        self.config = config;
+    }
+
+    pub fn links(&self) -> Option<&TreeLinks> {
+        self.links.as_ref()
+    }
+
+    pub fn set_links(&mut self, links: TreeLinks) {
+        self.links = Some(links);
+    }
+
+    pub fn stats(&self) -> CoordinateTreeStats {
+        self.stats
+    }
+
+    /// Links computed recently enough to relay along
+    pub fn fresh_links(&self, now: u64) -> Option<&TreeLinks> {
+        self.links.as_ref().filter(|links| now.saturating_sub(links.computed_ms) <= self.config.max_age_ms)
+    }
+
+    /// Record an entry received from `peer`
+    pub fn observe(&mut self, peer: &NodeId, node: &NodeId, version: u64) {
+        let carried = self.edges.entry(peer.clone()).or_default().entry(node.clone()).or_insert(version);
+        *carried = (*carried).max(version);
+    }
+
+    /// Forget the edges to nodes that are no longer neighbors
+    pub fn retain_peers(&mut self, keep: impl Fn(&NodeId) -> bool) {
+        self.edges.retain(|peer, _| keep(peer));
+    }
+
+    /// Entries to send to each neighbor
+    ///
+    /// With the tree disabled every neighbor gets every entry, as before.
+    pub fn plan(
+        &mut self,
+        local: &NodeId,
+        entries: &[CoordinateEntry],
+        neighbors: &[NodeId],
+        now: u64,
+    ) -> Vec<(NodeId, Vec<CoordinateEntry>)> {
+        if !self.config.enabled {
+            return neighbors.iter().map(|peer| (peer.clone(), entries.to_vec())).collect();
+        }
+        let links = self.fresh_links(now).cloned();
+        let mut plan = Vec::new();
+        for peer in neighbors {
+            let relays = links.as_ref().is_none_or(|links| links.relays_to(peer));
+            let carried = self.edges.entry(peer.clone()).or_default();
+            let mut selected = Vec::new();
+            for entry in entries {
+                let wanted = &entry.node == local || (relays && &entry.node != peer);
+                if !wanted || carried.get(&entry.node).is_some_and(|v| *v >= entry.version) {
+                    self.stats.suppressed_entries += 1;
+                    continue;
+                }
+                carried.insert(entry.node.clone(), entry.version);
+                selected.push(entry.clone());
+            }
+            if links.is_some() {
+                self.stats.tree_entries += selected.len() as u64;
+            } else {
+                self.stats.flooded_entries += selected.len() as u64;
+            }
+            if !selected.is_empty() {
+                plan.push((peer.clone(), selected));
+            }
+        }
+        plan
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::SerializablePoincareDiskPoint;
+
+    fn point(x: f64) -> PoincareDiskPoint {
+        PoincareDiskPoint::new(x, 0.0).unwrap()
+    }
+
+    fn listed(nodes: &[(&str, f64)]) -> Option<Vec<NeighborEntry>> {
+        Some(
+            nodes
+                .iter()
+                .map(|(node, x)| NeighborEntry {
+                    node: NodeId::new(*node),
+                    coord: SerializablePoincareDiskPoint::from(point(*x)),
+                    version: 1,
+                })
+                .collect(),
+        )
+    }
+
+    fn entry(node: &str, version: u64) -> CoordinateEntry {
+        CoordinateEntry::new(NodeId::new(node), point(0.5), version, 1)
+    }
+
+    #[test]
+    fn test_links_follow_the_embedding_tree() {
+        // root (0.0) - me (0.3) - {child (0.6), cousin (0.6), newcomer (0.7)}
+        // cousin also neighbors uncle (0.2), its closer parent
+        let me = NodeId::new("me");
+        let neighbors = vec![
+            (NodeId::new("root"), point(0.0)),
+            (NodeId::new("child"), point(0.6)),
+            (NodeId::new("cousin"), point(-0.6)),
+            (NodeId::new("newcomer"), point(0.7)),
+        ];
+        let links = TreeLinks::compute(
+            &me,
+            &point(0.3),
+            &neighbors,
+            |peer| match peer.0.as_str() {
+                "child" => listed(&[("me", 0.3)]),
+                "cousin" => listed(&[("me", 0.3), ("uncle", -0.2)]),
+                // Its list has not been exchanged yet
+                _ => None,
+            },
+            100,
+        );
+        assert_eq!(links.parent, Some(NodeId::new("root")));
+        assert_eq!(links.children.iter().map(|n| n.0.as_str()).collect::<Vec<_>>(), vec!["child"]);
+        assert_eq!(links.unknown.iter().map(|n| n.0.as_str()).collect::<Vec<_>>(), vec!["newcomer"]);
+        assert!(!links.relays_to(&NodeId::new("cousin")));
+        assert_eq!(parent_of(&point(0.0), neighbors.iter().map(|(id, p)| (id, *p))), None);
+    }
+
+    #[test]
+    fn test_plan_relays_along_tree_edges_without_repeats() {
+        let me = NodeId::new("me");
+        let peers: Vec<NodeId> = ["root", "child", "cousin"].iter().map(|p| NodeId::new(*p)).collect();
+        let mut tree = CoordinateTree::new(CoordinateTreeConfig::default());
+        let entries = vec![entry("me", 2), entry("far", 5), entry("root", 3)];
+
+        // No links yet: stale, so everything floods except what a peer is about
+        let plan = tree.plan(&me, &entries, &peers, 0);
+        assert_eq!(plan.iter().map(|(_, e)| e.len()).sum::<usize>(), 8);
+        assert_eq!(tree.stats().flooded_entries, 8);
+
+        tree.set_links(TreeLinks {
+            parent: Some(NodeId::new("root")),
+            children: [NodeId::new("child")].into(),
+            computed_ms: 1_000,
+            ..TreeLinks::default()
+        });
+        tree.observe(&NodeId::new("child"), &NodeId::new("far"), 6);
+        let entries = vec![entry("me", 3), entry("far", 6), entry("root", 4)];
+        let plan: HashMap<String, Vec<String>> = tree
+            .plan(&me, &entries, &peers, 2_000)
+            .into_iter()
+            .map(|(peer, sent)| (peer.0, sent.into_iter().map(|e| e.node.0).collect()))
+            .collect();
+        assert_eq!(plan["root"], vec!["me", "far"]);
+        // "far" came from the child, so it is not sent back
+        assert_eq!(plan["child"], vec!["me", "root"]);
+        // Not a tree edge: only our own entry
+        assert_eq!(plan["cousin"], vec!["me"]);
+        assert_eq!(tree.stats().tree_entries, 5);
+
+        // Already carried over every edge
+        assert!(tree.plan(&me, &entries, &peers, 2_000).is_empty());
+
+        // Links too old: flood again
+        let plan = tree.plan(&me, &[entry("far", 7)], &peers, 20_000);
+        assert_eq!(plan.len(), 3);
+
+        tree.set_config(CoordinateTreeConfig { enabled: false, ..CoordinateTreeConfig::default() });
+        assert_eq!(tree.plan(&me, &entries, &peers, 0).len(), 3);
+    }
+}
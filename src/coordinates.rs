@@ -3,14 +3,28 @@
 //! This module implements the core solution to the Coordinate-ID Paradox:
 //! - Anchor Coordinate: Topology-independent, derived deterministically from ID
 //! - Routing Coordinate: Topology-dependent, updated dynamically via Ricci flow
+//!
+//! The hash from IDs to anchors is versioned by `AnchorAlgorithm`, since
+//! changing it moves every node's address at once. A node uses one
+//! algorithm for itself and may accept others, so an overlay migrates by
+//! first accepting the new algorithm everywhere, then switching nodes over
+//! one at a time, then dropping the old one. The keyed algorithm mixes in an
+//! overlay secret, so outsiders cannot tell where an ID is addressed.
+//!
+//! Before the embedding converges a node's routing coordinate is its anchor,
+//! which says nothing about where the node is. An operator can instead give
+//! a geographic hint, a latitude and longitude or a datacenter region, and
+//! the node starts from the hint's place on the disk, pulled slightly toward
+//! its anchor so nodes sharing a region do not start on the same point.
 
 use crate::PoincareDiskPoint;
+use hmac::{Hmac, Mac};
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::{BTreeMap, HashMap};
 
 /// Node identifier (could be IP address, UUID, etc.)
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct NodeId(pub String);
 
 impl NodeId {
@@ -43,18 +57,15 @@ impl AnchorCoordinate {
     /// Compute anchor coordinate from node ID using SHA-256 hash.
     /// The hash determines the angle θ on the disk boundary.
     pub fn from_id(id: &NodeId) -> Self {
-        let mut hasher = Sha256::new();
-        hasher.update(id.0.as_bytes());
-        let hash = hasher.finalize();
-
-        // Use first 8 bytes of hash to determine angle
-        let hash_value = u64::from_be_bytes([
-            hash[0], hash[1], hash[2], hash[3], hash[4], hash[5], hash[6], hash[7],
-        ]);
-
-        let theta = (hash_value as f64 / u64::MAX as f64) * 2.0 * std::f64::consts::PI;
+        Self::derive(id, AnchorAlgorithm::Sha256, None)
+    }
 
-        let point = PoincareDiskPoint::from_polar(Self::DEFAULT_RADIUS, theta)
+    /// Compute anchor coordinate from node ID under `algorithm`
+    ///
+    /// `key` is the overlay secret of the keyed algorithm and unused by the
+    /// others; without one the keyed algorithm uses an empty key.
+    pub fn derive(id: &NodeId, algorithm: AnchorAlgorithm, key: Option<&[u8]>) -> Self {
+        let point = PoincareDiskPoint::from_polar(Self::DEFAULT_RADIUS, anchor_angle(id, algorithm, key))
             .expect("DEFAULT_RADIUS should always be valid");
 
         Self { point }
@@ -66,21 +77,99 @@ impl AnchorCoordinate {
             return None;
         }
 
-        let mut hasher = Sha256::new();
-        hasher.update(id.0.as_bytes());
-        let hash = hasher.finalize();
-
-        let hash_value = u64::from_be_bytes([
-            hash[0], hash[1], hash[2], hash[3], hash[4], hash[5], hash[6], hash[7],
-        ]);
-
-        let theta = (hash_value as f64 / u64::MAX as f64) * 2.0 * std::f64::consts::PI;
+        let theta = anchor_angle(id, AnchorAlgorithm::Sha256, None);
         let point = PoincareDiskPoint::from_polar(radius, theta)?;
 
         Some(Self { point })
     }
 }
 
+/// Angle of the anchor of `id`, from the first 8 bytes of its hash
+fn anchor_angle(id: &NodeId, algorithm: AnchorAlgorithm, key: Option<&[u8]>) -> f64 {
+    let hash: Vec<u8> = match algorithm {
+        AnchorAlgorithm::Sha256 => Sha256::digest(id.0.as_bytes()).to_vec(),
+        AnchorAlgorithm::Sha512 => Sha512::digest(id.0.as_bytes()).to_vec(),
+        AnchorAlgorithm::KeyedSha256 => {
+            let mut mac = Hmac::<Sha256>::new_from_slice(key.unwrap_or_default()).expect("HMAC takes keys of any length");
+            mac.update(ANCHOR_KEY_CONTEXT);
+            mac.update(id.0.as_bytes());
+            mac.finalize().into_bytes().to_vec()
+        }
+    };
+    let hash_value = u64::from_be_bytes([
+        hash[0], hash[1], hash[2], hash[3], hash[4], hash[5], hash[6], hash[7],
+    ]);
+
+    (hash_value as f64 / u64::MAX as f64) * 2.0 * std::f64::consts::PI
+}
+
+const ANCHOR_KEY_CONTEXT: &[u8] = b"drfe-r/anchor";
+
+/// Hash from node IDs to anchor coordinates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnchorAlgorithm {
+    /// SHA-256 of the ID, used by every node before anchors were versioned
+    #[default]
+    Sha256,
+    Sha512,
+    /// HMAC-SHA256 of the ID under the overlay's anchor key
+    KeyedSha256,
+}
+
+impl AnchorAlgorithm {
+    /// Identifier on the wire
+    pub fn id(&self) -> u8 {
+        match self {
+            AnchorAlgorithm::Sha256 => 0,
+            AnchorAlgorithm::Sha512 => 1,
+            AnchorAlgorithm::KeyedSha256 => 2,
+        }
+    }
+
+    pub fn from_wire(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(AnchorAlgorithm::Sha256),
+            1 => Some(AnchorAlgorithm::Sha512),
+            2 => Some(AnchorAlgorithm::KeyedSha256),
+            _ => None,
+        }
+    }
+}
+
+/// Anchor derivation of this node and the ones it accepts from others
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnchorConfig {
+    /// Algorithm this node is addressed under
+    pub algorithm: AnchorAlgorithm,
+    /// Further algorithms accepted from peers, while an overlay migrates
+    pub accepted: Vec<AnchorAlgorithm>,
+    /// Overlay secret of the keyed algorithm; read from config files but
+    /// never written back out, so config views do not leak it
+    #[serde(skip_serializing)]
+    pub key: Option<Vec<u8>>,
+}
+
+impl AnchorConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.accepts(AnchorAlgorithm::KeyedSha256) && self.key.as_ref().is_none_or(|k| k.is_empty()) {
+            return Err("Keyed anchor derivation needs a key".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether peers addressed under `algorithm` can be reached
+    pub fn accepts(&self, algorithm: AnchorAlgorithm) -> bool {
+        self.algorithm == algorithm || self.accepted.contains(&algorithm)
+    }
+
+    /// Anchor of `id` under `algorithm`, with this overlay's key
+    pub fn anchor(&self, id: &NodeId, algorithm: AnchorAlgorithm) -> AnchorCoordinate {
+        AnchorCoordinate::derive(id, algorithm, self.key.as_deref())
+    }
+}
+
 /// Routing Coordinate: Dynamic, topology-dependent coordinate.
 ///
 /// Properties:
@@ -111,6 +200,111 @@ impl RoutingCoordinate {
     }
 }
 
+/// A place on the globe, in degrees
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GeoPoint {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl GeoPoint {
+    pub fn is_valid(&self) -> bool {
+        (-90.0..=90.0).contains(&self.lat) && (-180.0..=180.0).contains(&self.lon)
+    }
+
+    /// Deterministic stand-in for a region with no known location
+    ///
+    /// Uniform over the globe, so unrelated regions spread out while nodes
+    /// naming the same one still start together.
+    fn from_name(name: &str) -> Self {
+        let hash = Sha256::digest(format!("region:{}", name).as_bytes());
+        let unit = |bytes: &[u8]| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64 / u32::MAX as f64;
+        Self {
+            lat: (2.0 * unit(&hash[0..4]) - 1.0).asin().to_degrees(),
+            lon: unit(&hash[4..8]) * 360.0 - 180.0,
+        }
+    }
+
+    /// Place on the disk by azimuthal equidistant projection from the North Pole
+    ///
+    /// Longitude becomes the angle and distance from the pole the radius,
+    /// scaled so the South Pole lands on the anchor radius. Nearby places
+    /// stay nearby, though the far south is stretched around the rim.
+    pub fn to_disk(&self) -> PoincareDiskPoint {
+        let r = AnchorCoordinate::DEFAULT_RADIUS * (90.0 - self.lat.clamp(-90.0, 90.0)) / 180.0;
+        PoincareDiskPoint::from_polar(r, self.lon.to_radians()).expect("radius is below the anchor radius")
+    }
+}
+
+/// Where an operator says a node is
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum GeoHint {
+    LatLon { lat: f64, lon: f64 },
+    /// A datacenter region, located through `GeoBootstrapConfig::regions`
+    Region { name: String },
+}
+
+/// Initial routing coordinate from a geographic hint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeoBootstrapConfig {
+    /// This node's location; without one the node starts at its anchor
+    pub hint: Option<GeoHint>,
+    /// Locations of region names; unknown regions are placed by a hash of the name
+    pub regions: BTreeMap<String, GeoPoint>,
+    /// Fraction of the way from the geographic point to the anchor where the node starts
+    pub anchor_weight: f64,
+}
+
+impl Default for GeoBootstrapConfig {
+    fn default() -> Self {
+        Self {
+            hint: None,
+            regions: BTreeMap::new(),
+            anchor_weight: 0.1,
+        }
+    }
+}
+
+impl GeoBootstrapConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.anchor_weight) {
+            return Err("Geo anchor_weight must be within [0, 1]".to_string());
+        }
+        if let Some((name, _)) = self.regions.iter().find(|(_, point)| !point.is_valid()) {
+            return Err(format!("Region {} has an invalid latitude or longitude", name));
+        }
+        if let Some(GeoHint::LatLon { lat, lon }) = &self.hint {
+            if !(GeoPoint { lat: *lat, lon: *lon }).is_valid() {
+                return Err("Geo hint has an invalid latitude or longitude".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Location of the hint, if there is one
+    pub fn locate(&self) -> Option<GeoPoint> {
+        match self.hint.as_ref()? {
+            GeoHint::LatLon { lat, lon } => Some(GeoPoint { lat: *lat, lon: *lon }),
+            GeoHint::Region { name } => Some(self.regions.get(name).copied().unwrap_or_else(|| GeoPoint::from_name(name))),
+        }
+    }
+
+    /// Starting coordinate of node `id`: its hint blended with its anchor,
+    /// or just the anchor without a hint
+    pub fn initial_point(&self, id: &NodeId) -> PoincareDiskPoint {
+        let anchor = AnchorCoordinate::from_id(id).point;
+        match self.locate() {
+            Some(geo) => {
+                let geo = geo.to_disk();
+                geo.geodesic_interpolate(&anchor, self.anchor_weight).unwrap_or(geo)
+            }
+            None => anchor,
+        }
+    }
+}
+
 /// Home Node: The node whose routing coordinate is closest to a given anchor coordinate.
 ///
 /// h(t) = argmin_{v ∈ V} d_H(z_v, a(ID_t))
@@ -122,6 +316,10 @@ pub struct HomeNodeRegistry {
     routing_coords: HashMap<NodeId, RoutingCoordinate>,
     /// Soft-state registration: Map from NodeId to registered routing coordinate (for rendezvous)
     registrations: HashMap<NodeId, (RoutingCoordinate, u64)>, // (coord, expiry_time)
+    /// Registrations held by a stand-in while the home node is partitioned away
+    temporary: HashMap<NodeId, (NodeId, RoutingCoordinate, u64)>, // (temporary home, coord, expiry_time)
+    /// Routing coordinates indexed for home node lookups
+    index: SpatialIndex,
 }
 
 impl HomeNodeRegistry {
@@ -130,6 +328,8 @@ impl HomeNodeRegistry {
             anchor_coords: HashMap::new(),
             routing_coords: HashMap::new(),
             registrations: HashMap::new(),
+            temporary: HashMap::new(),
+            index: SpatialIndex::new(),
         }
     }
 
@@ -137,6 +337,7 @@ impl HomeNodeRegistry {
     pub fn register_node(&mut self, id: NodeId, routing_coord: RoutingCoordinate) {
         let anchor = AnchorCoordinate::from_id(&id);
         self.anchor_coords.insert(id.clone(), anchor);
+        self.index.insert(id.clone(), routing_coord.point);
         self.routing_coords.insert(id, routing_coord);
     }
 
@@ -152,6 +353,7 @@ impl HomeNodeRegistry {
 
     /// Update routing coordinate for a node
     pub fn update_routing(&mut self, id: &NodeId, coord: RoutingCoordinate) {
+        self.index.insert(id.clone(), coord.point);
         self.routing_coords.insert(id.clone(), coord);
     }
 
@@ -159,19 +361,30 @@ impl HomeNodeRegistry {
     /// Returns the NodeId of the node whose routing coordinate is closest to the target's anchor.
     pub fn find_home_node(&self, target_id: &NodeId) -> Option<NodeId> {
         let target_anchor = AnchorCoordinate::from_id(target_id);
+        self.index.nearest(&target_anchor.point).map(|(id, _)| id.clone())
+    }
 
-        self.routing_coords
-            .iter()
-            .min_by(|(_, coord_a), (_, coord_b)| {
-                let dist_a = coord_a.point.hyperbolic_distance(&target_anchor.point);
-                let dist_b = coord_b.point.hyperbolic_distance(&target_anchor.point);
-                dist_a
-                    .partial_cmp(&dist_b)
-                    .unwrap_or(std::cmp::Ordering::Equal)
-            })
+    /// Home node for `target_id` among the nodes accepted by `eligible`,
+    /// e.g. those reachable during a partition
+    pub fn find_home_node_where(&self, target_id: &NodeId, eligible: impl Fn(&NodeId) -> bool) -> Option<NodeId> {
+        let target_anchor = AnchorCoordinate::from_id(target_id);
+        self.index
+            .knn(&target_anchor.point, self.index.len())
+            .into_iter()
+            .find(|(id, _)| eligible(id))
             .map(|(id, _)| id.clone())
     }
 
+    /// The `k` nodes whose routing coordinates are closest to `point`, with
+    /// their distances, nearest first
+    pub fn nodes_near(&self, point: &PoincareDiskPoint, k: usize) -> Vec<(NodeId, f64)> {
+        self.index
+            .knn(point, k)
+            .into_iter()
+            .map(|(id, distance)| (id.clone(), distance))
+            .collect()
+    }
+
     /// Register destination info at home node (soft-state with TTL)
     pub fn register_at_home(
         &mut self,
@@ -200,10 +413,94 @@ impl HomeNodeRegistry {
         })
     }
 
+    /// Register destination info at a stand-in for an unreachable home node
+    pub fn register_at_temporary_home(
+        &mut self,
+        target_id: &NodeId,
+        home: &NodeId,
+        routing_coord: RoutingCoordinate,
+        ttl: u64,
+        current_time: u64,
+    ) {
+        self.temporary
+            .insert(target_id.clone(), (home.clone(), routing_coord, current_time + ttl));
+    }
+
+    /// Lookup a temporary registration held by a node accepted by `reachable`
+    pub fn lookup_temporary(
+        &self,
+        target_id: &NodeId,
+        current_time: u64,
+        reachable: impl Fn(&NodeId) -> bool,
+    ) -> Option<&RoutingCoordinate> {
+        self.temporary
+            .get(target_id)
+            .filter(|(home, _, expiry)| current_time < *expiry && reachable(home))
+            .map(|(_, coord, _)| coord)
+    }
+
+    /// (target, temporary home) of every temporary registration
+    pub fn temporary_registrations(&self) -> Vec<(NodeId, NodeId)> {
+        self.temporary
+            .iter()
+            .map(|(target, (home, _, _))| (target.clone(), home.clone()))
+            .collect()
+    }
+
+    /// Move a temporary registration to the regular ones
+    ///
+    /// When both exist, the one with the newer coordinate wins, then the
+    /// one that expires later. Returns false if there was nothing to merge.
+    pub fn merge_temporary(&mut self, target_id: &NodeId) -> bool {
+        let Some((_, coord, expiry)) = self.temporary.remove(target_id) else {
+            return false;
+        };
+        let newer = match self.registrations.get(target_id) {
+            Some((current, current_expiry)) => {
+                (coord.updated_at, expiry) > (current.updated_at, *current_expiry)
+            }
+            None => true,
+        };
+        if newer {
+            self.registrations.insert(target_id.clone(), (coord, expiry));
+        }
+        true
+    }
+
+    /// Hand off a departing node's home-node responsibilities.
+    ///
+    /// Removes `node` from the registry so that every target it was home for
+    /// resolves to the next-closest node. Registrations are keyed by target and
+    /// survive the handoff.
+    ///
+    /// Returns (target, new home) for each registration that moved.
+    pub fn handoff(&mut self, node: &NodeId) -> Vec<(NodeId, NodeId)> {
+        let homed: Vec<NodeId> = self
+            .registrations
+            .keys()
+            .filter(|target| self.find_home_node(target).as_ref() == Some(node))
+            .cloned()
+            .collect();
+
+        self.routing_coords.remove(node);
+        self.anchor_coords.remove(node);
+        self.index.remove(node);
+
+        homed
+            .into_iter()
+            .filter_map(|target| {
+                let home = self.find_home_node(&target)?;
+                Some((target, home))
+            })
+            .collect()
+    }
+
     /// Clean up expired registrations
     pub fn cleanup_expired(&mut self, current_time: u64) {
         self.registrations
             .retain(|_, (_, expiry)| current_time < *expiry);
+        self.temporary
+            .retain(|_, (_, _, expiry)| current_time < *expiry);
     }
 
     /// Get all registered nodes
@@ -223,6 +520,186 @@ impl Default for HomeNodeRegistry {
     }
 }
 
+/// Default hyperbolic width of a `SpatialIndex` ring
+const DEFAULT_RING_WIDTH: f64 = 0.5;
+
+/// Default number of angular sectors per `SpatialIndex` ring
+const DEFAULT_SECTORS: usize = 32;
+
+/// Slack for rounding differences between bucket bounds and point distances
+const BOUND_EPSILON: f64 = 1e-9;
+
+/// Spatial index over node coordinates for nearest-node queries
+///
+/// Points are bucketed on a polar grid: rings of fixed hyperbolic width
+/// around the origin, each split into equal angular sectors. A query visits
+/// buckets in order of the smallest distance any point in them could have
+/// and stops once no unvisited bucket can beat the current result, so the
+/// answer always matches a linear scan. Ties are broken by node ID.
+#[derive(Debug, Clone)]
+pub struct SpatialIndex {
+    ring_width: f64,
+    sectors: usize,
+    points: HashMap<NodeId, PoincareDiskPoint>,
+    buckets: HashMap<(usize, usize), Vec<NodeId>>,
+}
+
+impl SpatialIndex {
+    pub fn new() -> Self {
+        Self::with_grid(DEFAULT_RING_WIDTH, DEFAULT_SECTORS)
+    }
+
+    /// Index with `sectors` sectors per ring of hyperbolic width `ring_width`
+    pub fn with_grid(ring_width: f64, sectors: usize) -> Self {
+        Self {
+            ring_width: if ring_width > 0.0 { ring_width } else { DEFAULT_RING_WIDTH },
+            sectors: sectors.max(1),
+            points: HashMap::new(),
+            buckets: HashMap::new(),
+        }
+    }
+
+    fn sector_width(&self) -> f64 {
+        2.0 * std::f64::consts::PI / self.sectors as f64
+    }
+
+    fn bucket_of(&self, point: &PoincareDiskPoint) -> (usize, usize) {
+        let rho = 2.0 * point.euclidean_norm().atanh();
+        let ring = (rho / self.ring_width) as usize;
+        let sector = (crate::normalize_angle(point.angle()) / self.sector_width()) as usize;
+        (ring, sector.min(self.sectors - 1))
+    }
+
+    /// Smallest distance from a point at polar (`rho`, `theta`) to any point of a bucket
+    ///
+    /// With the angular gap fixed, distance (law of cosines) is minimized at
+    /// the radius where tanh r = tanh ρ · cos Δθ, clamped to the ring.
+    fn bucket_bound(&self, rho: f64, theta: f64, (ring, sector): (usize, usize)) -> f64 {
+        let width = self.sector_width();
+        let offset = crate::normalize_angle(theta - sector as f64 * width);
+        let gap = if offset <= width {
+            0.0
+        } else {
+            (offset - width).min(2.0 * std::f64::consts::PI - offset)
+        };
+
+        let inner = ring as f64 * self.ring_width;
+        let best = if gap >= std::f64::consts::FRAC_PI_2 {
+            0.0
+        } else {
+            (rho.tanh() * gap.cos()).atanh()
+        };
+        let r = best.clamp(inner, inner + self.ring_width);
+
+        let cosh_d = rho.cosh() * r.cosh() - rho.sinh() * r.sinh() * gap.cos();
+        cosh_d.max(1.0).acosh()
+    }
+
+    /// Non-empty buckets ordered by their distance bound to `point`
+    fn ranked_buckets(&self, point: &PoincareDiskPoint) -> Vec<(f64, &Vec<NodeId>)> {
+        let rho = 2.0 * point.euclidean_norm().atanh();
+        let theta = point.angle();
+        let mut ranked: Vec<(f64, &Vec<NodeId>)> = self
+            .buckets
+            .iter()
+            .map(|(bucket, ids)| (self.bucket_bound(rho, theta, *bucket) - BOUND_EPSILON, ids))
+            .collect();
+        ranked.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        ranked
+    }
+
+    fn sort_results(results: &mut [(&NodeId, f64)]) {
+        results.sort_by(|a, b| {
+            a.1.partial_cmp(&b.1)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.0 .0.cmp(&b.0 .0))
+        });
+    }
+
+    /// Insert or move a node, returning its previous coordinate
+    pub fn insert(&mut self, id: NodeId, point: PoincareDiskPoint) -> Option<PoincareDiskPoint> {
+        let previous = self.remove(&id);
+        self.buckets.entry(self.bucket_of(&point)).or_default().push(id.clone());
+        self.points.insert(id, point);
+        previous
+    }
+
+    pub fn remove(&mut self, id: &NodeId) -> Option<PoincareDiskPoint> {
+        let point = self.points.remove(id)?;
+        let bucket = self.bucket_of(&point);
+        if let Some(ids) = self.buckets.get_mut(&bucket) {
+            ids.retain(|other| other != id);
+            if ids.is_empty() {
+                self.buckets.remove(&bucket);
+            }
+        }
+        Some(point)
+    }
+
+    pub fn get(&self, id: &NodeId) -> Option<&PoincareDiskPoint> {
+        self.points.get(id)
+    }
+
+    pub fn contains(&self, id: &NodeId) -> bool {
+        self.points.contains_key(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&NodeId, &PoincareDiskPoint)> {
+        self.points.iter()
+    }
+
+    /// The `k` nodes closest to `point` with their distances, nearest first
+    pub fn knn(&self, point: &PoincareDiskPoint, k: usize) -> Vec<(&NodeId, f64)> {
+        let mut found: Vec<(&NodeId, f64)> = Vec::new();
+        if k == 0 {
+            return found;
+        }
+
+        for (bound, ids) in self.ranked_buckets(point) {
+            if found.len() >= k && bound > found[k - 1].1 {
+                break;
+            }
+            found.extend(ids.iter().map(|id| (id, self.points[id].hyperbolic_distance(point))));
+            Self::sort_results(&mut found);
+            found.truncate(k);
+        }
+        found
+    }
+
+    /// Closest node to `point`
+    pub fn nearest(&self, point: &PoincareDiskPoint) -> Option<(&NodeId, f64)> {
+        self.knn(point, 1).pop()
+    }
+
+    /// All nodes within hyperbolic distance `radius` of `point`, nearest first
+    pub fn range(&self, point: &PoincareDiskPoint, radius: f64) -> Vec<(&NodeId, f64)> {
+        let mut found: Vec<(&NodeId, f64)> = self
+            .ranked_buckets(point)
+            .into_iter()
+            .take_while(|(bound, _)| *bound <= radius)
+            .flat_map(|(_, ids)| ids.iter())
+            .map(|id| (id, self.points[id].hyperbolic_distance(point)))
+            .filter(|(_, d)| *d <= radius)
+            .collect();
+        Self::sort_results(&mut found);
+        found
+    }
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -260,6 +737,57 @@ mod tests {
         assert!((r - 0.95).abs() < 1e-10);
     }
 
+    #[test]
+    fn test_anchor_algorithms() {
+        let id = NodeId::new("node_42");
+        // The default derivation is the one used before versioning
+        assert_eq!(AnchorConfig::default().anchor(&id, AnchorAlgorithm::Sha256).point, AnchorCoordinate::from_id(&id).point);
+        let sha512 = AnchorCoordinate::derive(&id, AnchorAlgorithm::Sha512, None).point;
+        assert_ne!(sha512, AnchorCoordinate::from_id(&id).point);
+        assert!((sha512.euclidean_norm() - 0.95).abs() < 1e-9);
+
+        // Keyed anchors depend on the key
+        let keyed = |key: &[u8]| AnchorCoordinate::derive(&id, AnchorAlgorithm::KeyedSha256, Some(key)).point;
+        assert_eq!(keyed(b"secret"), keyed(b"secret"));
+        assert_ne!(keyed(b"secret"), keyed(b"other"));
+
+        let migrating = AnchorConfig { algorithm: AnchorAlgorithm::Sha512, accepted: vec![AnchorAlgorithm::Sha256], key: None };
+        assert!(migrating.accepts(AnchorAlgorithm::Sha256) && !migrating.accepts(AnchorAlgorithm::KeyedSha256));
+        assert!(migrating.validate().is_ok());
+        assert!(AnchorConfig { algorithm: AnchorAlgorithm::KeyedSha256, ..Default::default() }.validate().is_err());
+        let keyed_config: AnchorConfig = serde_json::from_str(r#"{"algorithm":"keyed_sha256","key":[1,2,3]}"#).unwrap();
+        assert_eq!(keyed_config.key, Some(vec![1, 2, 3]));
+        assert!(serde_json::to_value(&keyed_config).unwrap().get("key").is_none());
+        for algorithm in [AnchorAlgorithm::Sha256, AnchorAlgorithm::Sha512, AnchorAlgorithm::KeyedSha256] {
+            assert_eq!(AnchorAlgorithm::from_wire(algorithm.id()), Some(algorithm));
+        }
+        assert_eq!(AnchorAlgorithm::from_wire(9), None);
+    }
+
+    #[test]
+    fn test_geo_hints_preserve_geography() {
+        let at = |lat: f64, lon: f64| GeoBootstrapConfig { hint: Some(GeoHint::LatLon { lat, lon }), ..Default::default() };
+        let id = NodeId::new("node");
+        let (paris, london, sydney) = (at(48.9, 2.4), at(51.5, -0.1), at(-33.9, 151.2));
+        let d = |a: &GeoBootstrapConfig, b: &GeoBootstrapConfig| a.initial_point(&id).hyperbolic_distance(&b.initial_point(&id));
+        assert!(d(&paris, &london) < d(&paris, &sydney));
+        assert!(GeoPoint { lat: -90.0, lon: 0.0 }.to_disk().euclidean_norm() <= 0.95 + 1e-12);
+
+        // Nodes in one region start near each other but not on the same point
+        let mut region = GeoBootstrapConfig { hint: Some(GeoHint::Region { name: "eu-west".into() }), ..Default::default() };
+        region.regions.insert("eu-west".into(), GeoPoint { lat: 53.3, lon: -6.3 });
+        let (a, b) = (region.initial_point(&NodeId::new("a")), region.initial_point(&NodeId::new("b")));
+        assert!(a != b && a.hyperbolic_distance(&b) < d(&paris, &sydney));
+        let unknown = GeoBootstrapConfig { hint: Some(GeoHint::Region { name: "mars-1".into() }), ..Default::default() };
+        assert_eq!(unknown.locate(), unknown.clone().locate());
+        assert!(unknown.locate().unwrap().is_valid());
+
+        // Without a hint the anchor stays; bad input is refused
+        assert_eq!(GeoBootstrapConfig::default().initial_point(&id), AnchorCoordinate::from_id(&id).point);
+        assert!(at(95.0, 0.0).validate().is_err());
+        assert!(GeoBootstrapConfig { anchor_weight: 1.5, ..Default::default() }.validate().is_err());
+    }
+
     #[test]
     fn test_home_node_selection() {
         let mut registry = HomeNodeRegistry::new();
@@ -298,4 +826,63 @@ mod tests {
         // After expiry
         assert!(registry.lookup_registration(&target, 150).is_none());
     }
+
+    #[test]
+    fn test_handoff_moves_registrations() {
+        let mut registry = HomeNodeRegistry::new();
+        for (i, (x, y)) in [(0.5, 0.0), (-0.5, 0.0), (0.0, 0.5), (0.0, -0.5)].iter().enumerate() {
+            let coord = RoutingCoordinate::new(PoincareDiskPoint::new(*x, *y).unwrap(), 0);
+            registry.register_node(NodeId::new(format!("node_{}", i)), coord);
+        }
+
+        let target = NodeId::new("target");
+        let coord = RoutingCoordinate::new(PoincareDiskPoint::new(0.1, 0.1).unwrap(), 0);
+        registry.register_at_home(&target, coord, 100, 0);
+
+        let home = registry.find_home_node(&target).unwrap();
+        let moved = registry.handoff(&home);
+
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].0, target);
+        assert_ne!(moved[0].1, home);
+        assert_eq!(registry.find_home_node(&target), Some(moved[0].1.clone()));
+        assert!(registry.lookup_registration(&target, 50).is_some());
+    }
+
+    #[test]
+    fn test_spatial_index_matches_linear_scan() {
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut index = SpatialIndex::with_grid(0.4, 16);
+        let mut points = Vec::new();
+        for i in 0..300 {
+            let p = PoincareDiskPoint::from_polar(rng.gen_range(0.0..0.99), rng.gen_range(0.0..6.3)).unwrap();
+            index.insert(NodeId::new(format!("n{}", i)), p);
+            points.push((NodeId::new(format!("n{}", i)), p));
+        }
+        // Moving and removing keep the buckets consistent
+        let moved = PoincareDiskPoint::new(-0.3, 0.2).unwrap();
+        index.insert(points[0].0.clone(), moved);
+        points[0].1 = moved;
+        index.remove(&points[1].0);
+        points.remove(1);
+        assert_eq!(index.len(), points.len());
+
+        for _ in 0..50 {
+            let q = PoincareDiskPoint::from_polar(rng.gen_range(0.0..0.995), rng.gen_range(0.0..6.3)).unwrap();
+            let mut expected: Vec<(f64, &NodeId)> = points.iter().map(|(id, p)| (p.hyperbolic_distance(&q), id)).collect();
+            expected.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap().then_with(|| a.1 .0.cmp(&b.1 .0)));
+
+            let knn: Vec<&NodeId> = index.knn(&q, 7).into_iter().map(|(id, _)| id).collect();
+            let scan: Vec<&NodeId> = expected.iter().take(7).map(|(_, id)| *id).collect();
+            assert_eq!(knn, scan);
+
+            let radius = expected[20].0;
+            let within: Vec<&NodeId> = index.range(&q, radius).into_iter().map(|(id, _)| id).collect();
+            let scan: Vec<&NodeId> = expected.iter().take_while(|(d, _)| *d <= radius).map(|(_, id)| *id).collect();
+            assert_eq!(within, scan);
+        }
+        assert!(SpatialIndex::new().nearest(&PoincareDiskPoint::origin()).is_none());
+    }
 }
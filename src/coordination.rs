@@ -0,0 +1,316 @@
+//! Leader Election
+//!
+//! Network-wide maintenance such as electing landmarks, picking the
+//! spanning tree root or triggering a global re-embedding needs one node to
+//! act for everyone. The leader is the stable node with the lowest ID,
+//! where stable means up for at least `stable_after_ms`.
+//!
+//! The leader holds a lease and renews it every `renew_interval_ms` by
+//! flooding a `LeaderLease` to its neighbors. Nodes forward a lease only if
+//! it is newer than the one they hold, so each renewal crosses every link
+//! at most twice and needs no duplicate cache. When the lease runs out, or
+//! a stable node sees a lease held by a higher ID, it claims leadership
+//! under a new term. Concurrent claims for a term resolve to the lowest ID.
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+
+/// Leader election settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ElectionConfig {
+    pub enabled: bool,
+    /// How long a lease stays valid without renewal
+    pub lease_ms: u64,
+    pub renew_interval_ms: u64,
+    /// Uptime before a node may become leader
+    pub stable_after_ms: u64,
+}
+
+impl Default for ElectionConfig {
+    fn default() -> Self {
+        Self { enabled: true, lease_ms: 10_000, renew_interval_ms: 3_000, stable_after_ms: 10_000 }
+    }
+}
+
+impl ElectionConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.renew_interval_ms == 0 || self.lease_ms <= self.renew_interval_ms {
+            return Err("Election lease_ms must exceed a positive renew_interval_ms".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A leader's claim, as flooded over the overlay
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeaderLease {
+    pub leader: NodeId,
+    pub term: u64,
+    /// Renewals within the term
+    pub renewal: u64,
+    /// Validity left when the lease was sent; relative, so clocks need not agree
+    pub remaining_ms: u64,
+}
+
+impl LeaderLease {
+    /// Whether this lease supersedes `other`: a later term, then the lower
+    /// leader ID, then the later renewal
+    pub fn supersedes(&self, other: &LeaderLease) -> bool {
+        (self.term, std::cmp::Reverse(&self.leader), self.renewal)
+            > (other.term, std::cmp::Reverse(&other.leader), other.renewal)
+    }
+}
+
+/// Election messages sent to a neighbor
+pub type ElectionActions = Vec<(NodeId, LeaderLease)>;
+
+/// Election counters since startup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ElectionStats {
+    /// Terms this node started by claiming leadership
+    pub claims: u64,
+    /// Times the known leader changed
+    pub leader_changes: u64,
+    pub leases_received: u64,
+}
+
+/// One node's view of the election
+#[derive(Debug, Clone)]
+pub struct LeaderElection {
+    id: NodeId,
+    config: ElectionConfig,
+    started_ms: u64,
+    /// Best lease seen and its local expiry
+    lease: Option<(LeaderLease, u64)>,
+    last_renewal_ms: u64,
+    /// Whether this node stands for election
+    candidate: bool,
+    stats: ElectionStats,
+}
+
+impl LeaderElection {
+    pub fn new(id: NodeId, config: ElectionConfig, now_ms: u64) -> Self {
+        Self {
+            id,
+            config,
+            started_ms: now_ms,
+            lease: None,
+            last_renewal_ms: 0,
+            candidate: true,
+            stats: ElectionStats::default(),
+        }
+    }
+
+    pub fn config(&self) -> &ElectionConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: ElectionConfig) {
+        self.config = config;
+    }
+
+    pub fn stats(&self) -> ElectionStats {
+        self.stats
+    }
+
+    /// Current lease with its remaining validity, if it has not expired
+    pub fn lease(&self, now_ms: u64) -> Option<LeaderLease> {
+        let (lease, expires_ms) = self.lease.as_ref().filter(|(_, expires)| *expires > now_ms)?;
+        Some(LeaderLease { remaining_ms: expires_ms - now_ms, ..lease.clone() })
+    }
+
+    /// Leader holding a valid lease
+    pub fn leader(&self, now_ms: u64) -> Option<NodeId> {
+        self.lease(now_ms).map(|lease| lease.leader)
+    }
+
+    pub fn is_leader(&self, now_ms: u64) -> bool {
+        self.leader(now_ms).as_ref() == Some(&self.id)
+    }
+
+    /// Highest term seen
+    pub fn term(&self) -> u64 {
+        self.lease.as_ref().map_or(0, |(lease, _)| lease.term)
+    }
+
+    fn is_stable(&self, now_ms: u64) -> bool {
+        now_ms.saturating_sub(self.started_ms) >= self.config.stable_after_ms
+    }
+
+    /// Renew our lease when due, or claim leadership if it is vacant or
+    /// held by a higher ID
+    pub fn tick(&mut self, now_ms: u64, neighbors: &[NodeId]) -> ElectionActions {
+        if !self.config.enabled {
+            return Vec::new();
+        }
+        if self.is_leader(now_ms) {
+            if now_ms.saturating_sub(self.last_renewal_ms) < self.config.renew_interval_ms {
+                return Vec::new();
+            }
+            let (lease, _) = self.lease.as_ref().expect("leader holds a lease");
+            let renewed = LeaderLease { renewal: lease.renewal + 1, ..lease.clone() };
+            return self.hold(renewed, now_ms, neighbors);
+        }
+        if self.should_claim(now_ms) {
+            return self.claim(now_ms, neighbors);
+        }
+        Vec::new()
+    }
+
+    /// Handle a lease from neighbor `from`
+    pub fn on_lease(&mut self, from: &NodeId, lease: LeaderLease, now_ms: u64, neighbors: &[NodeId]) -> ElectionActions {
+        self.stats.leases_received += 1;
+        let current = self.lease.as_ref().map(|(current, _)| current);
+        if current.is_some_and(|current| !lease.supersedes(current)) {
+            // Tell a neighbor with an older view about ours
+            return match self.lease(now_ms) {
+                Some(ours) if ours != lease && ours.supersedes(&lease) => vec![(from.clone(), ours)],
+                _ => Vec::new(),
+            };
+        }
+
+        self.adopt(lease.clone(), now_ms + lease.remaining_ms);
+        if self.should_claim(now_ms) {
+            return self.claim(now_ms, neighbors);
+        }
+        neighbors.iter().filter(|n| *n != from).map(|n| (n.clone(), lease.clone())).collect()
+    }
+
+    /// Stop standing for election, e.g. before draining
+    ///
+    /// A leader floods its lease as expired so others take over at once.
+    pub fn resign(&mut self, now_ms: u64, neighbors: &[NodeId]) -> ElectionActions {
+        self.candidate = false;
+        if !self.is_leader(now_ms) {
+            return Vec::new();
+        }
+        let (lease, _) = self.lease.as_ref().expect("leader holds a lease");
+        let resigned = LeaderLease { renewal: lease.renewal + 1, remaining_ms: 0, ..lease.clone() };
+        self.adopt(resigned.clone(), now_ms);
+        neighbors.iter().map(|n| (n.clone(), resigned.clone())).collect()
+    }
+
+    /// Stand for election again after `resign`
+    pub fn stand(&mut self) {
+        self.candidate = true;
+    }
+
+    fn should_claim(&self, now_ms: u64) -> bool {
+        self.config.enabled
+            && self.candidate
+            && self.is_stable(now_ms) && self.leader(now_ms).is_none_or(|leader| self.id < leader)
+    }
+
+    fn claim(&mut self, now_ms: u64, neighbors: &[NodeId]) -> ElectionActions {
+        self.stats.claims += 1;
+        let lease = LeaderLease { leader: self.id.clone(), term: self.term() + 1, renewal: 0, remaining_ms: self.config.lease_ms };
+        self.hold(lease, now_ms, neighbors)
+    }
+
+    fn hold(&mut self, lease: LeaderLease, now_ms: u64, neighbors: &[NodeId]) -> ElectionActions {
+        let lease = LeaderLease { remaining_ms: self.config.lease_ms, ..lease };
+        self.last_renewal_ms = now_ms;
+        self.adopt(lease.clone(), now_ms + self.config.lease_ms);
+        neighbors.iter().map(|n| (n.clone(), lease.clone())).collect()
+    }
+
+    fn adopt(&mut self, lease: LeaderLease, expires_ms: u64) {
+        if self.lease.as_ref().is_none_or(|(current, _)| current.leader != lease.leader) {
+            self.stats.leader_changes += 1;
+        }
+        self.lease = Some((lease, expires_ms));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Deliver election messages until none are left
+    fn settle(nodes: &mut HashMap<NodeId, LeaderElection>, links: &HashMap<NodeId, Vec<NodeId>>, mut queue: Vec<(NodeId, NodeId, LeaderLease)>, now: u64) {
+        while let Some((from, to, lease)) = queue.pop() {
+            let Some(node) = nodes.get_mut(&to) else { continue };
+            for (next, lease) in node.on_lease(&from, lease, now, &links[&to]) {
+                queue.push((to.clone(), next, lease));
+            }
+        }
+    }
+
+    fn tick_all(nodes: &mut HashMap<NodeId, LeaderElection>, links: &HashMap<NodeId, Vec<NodeId>>, now: u64) {
+        let mut ids: Vec<NodeId> = nodes.keys().cloned().collect();
+        ids.sort();
+        ids.reverse();
+        for id in ids {
+            let actions = nodes.get_mut(&id).unwrap().tick(now, &links[&id]);
+            let queue = actions.into_iter().map(|(to, lease)| (id.clone(), to, lease)).collect();
+            settle(nodes, links, queue, now);
+        }
+    }
+
+    #[test]
+    fn test_lowest_stable_node_leads_and_fails_over() {
+        let config = ElectionConfig::default();
+        let ids: Vec<NodeId> = ["a", "b", "c", "d"].into_iter().map(NodeId::new).collect();
+        let mut links: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+        for pair in ids.windows(2) {
+            links.entry(pair[0].clone()).or_default().push(pair[1].clone());
+            links.entry(pair[1].clone()).or_default().push(pair[0].clone());
+        }
+        // "a" starts late, so "b" leads first
+        let mut nodes: HashMap<NodeId, LeaderElection> = ids
+            .iter()
+            .map(|id| (id.clone(), LeaderElection::new(id.clone(), config.clone(), if id.0 == "a" { 15_000 } else { 0 })))
+            .collect();
+        let leader_everywhere = |nodes: &HashMap<NodeId, LeaderElection>, leader: &str, now| {
+            nodes.values().all(|n| n.leader(now) == Some(NodeId::new(leader)))
+        };
+
+        tick_all(&mut nodes, &links, 1_000);
+        assert!(nodes.values().all(|n| n.leader(1_000).is_none()));
+        tick_all(&mut nodes, &links, 10_000);
+        assert!(leader_everywhere(&nodes, "b", 10_000));
+        let term = nodes[&ids[1]].term();
+
+        // Renewals keep the lease alive past its original expiry
+        for now in [13_000, 16_000, 19_000, 22_000] {
+            tick_all(&mut nodes, &links, now);
+        }
+        assert!(leader_everywhere(&nodes, "b", 22_000));
+        assert!(nodes[&ids[3]].lease(22_000).unwrap().renewal >= 3);
+
+        // Once stable, "a" preempts with a new term
+        tick_all(&mut nodes, &links, 25_000);
+        assert!(leader_everywhere(&nodes, "a", 25_000));
+        assert!(nodes.values().all(|n| n.term() == term + 1));
+
+        // "a" fails; "b" takes over once its lease has run out
+        nodes.remove(&ids[0]);
+        tick_all(&mut nodes, &links, 30_000);
+        assert!(leader_everywhere(&nodes, "a", 30_000));
+        tick_all(&mut nodes, &links, 36_000);
+        assert!(leader_everywhere(&nodes, "b", 36_000));
+        // Higher IDs may claim first; their terms are superseded, not reused
+        let failover_term = nodes[&ids[1]].term();
+        assert!(failover_term > term + 1 && nodes.values().all(|n| n.term() == failover_term));
+
+        // Resigning hands over immediately
+        let actions = nodes.get_mut(&ids[1]).unwrap().resign(36_000, &links[&ids[1]]);
+        let queue = actions.into_iter().map(|(to, lease)| (ids[1].clone(), to, lease)).collect();
+        settle(&mut nodes, &links, queue, 36_000);
+        assert!(leader_everywhere(&nodes, "c", 36_000));
+        assert!(nodes[&ids[2]].stats().claims >= 1);
+    }
+
+    #[test]
+    fn test_lease_order() {
+        let lease = |leader: &str, term, renewal| LeaderLease { leader: NodeId::new(leader), term, renewal, remaining_ms: 0 };
+        assert!(lease("z", 2, 0).supersedes(&lease("a", 1, 9)));
+        assert!(lease("a", 1, 0).supersedes(&lease("b", 1, 9)));
+        assert!(lease("a", 1, 1).supersedes(&lease("a", 1, 0)));
+        assert!(!lease("a", 1, 1).supersedes(&lease("a", 1, 1)));
+        assert!(ElectionConfig { lease_ms: 1_000, renew_interval_ms: 1_000, ..Default::default() }.validate().is_err());
+    }
+}
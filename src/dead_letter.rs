@@ -0,0 +1,310 @@
+//! Retry Policy and Dead-Letter Queue
+//!
+//! A Data packet this node originates is sent up to `max_attempts` times,
+//! with `retry_backoff_ms` between attempts. If every attempt fails, the
+//! payload goes to a bounded dead-letter queue instead of vanishing. Letters
+//! expire after `ttl_ms`. When the queue is full, the oldest letter is
+//! evicted. Letters can be listed, deleted and retried over the API. With
+//! `auto_retry`, the node also retries them when the topology gains
+//! neighbors, such as a neighbor joining or a partition healing, since that
+//! may have restored reachability. After `max_redeliveries` failed retries
+//! a letter is abandoned.
+//!
+//! Congestion (a full window) is flow control rather than a routing failure,
+//! so it is neither retried nor dead-lettered.
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::memory::{self, MemoryFootprint, MemoryUsage};
+use crate::ttl_policy::QosClass;
+
+/// Retry and dead-letter settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeadLetterConfig {
+    /// Keep undeliverable payloads (retries apply either way)
+    pub enabled: bool,
+    /// Send attempts per packet before it is dead-lettered
+    pub max_attempts: u32,
+    /// Wait between attempts
+    pub retry_backoff_ms: u64,
+    /// Letters kept; the oldest is evicted first
+    pub capacity: usize,
+    /// Letters older than this are dropped
+    pub ttl_ms: u64,
+    /// Retry letters when neighbors join or a partition heals
+    pub auto_retry: bool,
+    /// Failed retries before a letter is abandoned
+    pub max_redeliveries: u32,
+}
+
+impl Default for DeadLetterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_attempts: 2,
+            retry_backoff_ms: 100,
+            capacity: 1024,
+            ttl_ms: 300_000,
+            auto_retry: true,
+            max_redeliveries: 3,
+        }
+    }
+}
+
+impl DeadLetterConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_attempts == 0 {
+            return Err("max_attempts must be positive".to_string());
+        }
+        if self.capacity == 0 || self.ttl_ms == 0 {
+            return Err("Dead-letter capacity and ttl_ms must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// An undeliverable packet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub id: u64,
+    pub destination: NodeId,
+    pub payload: Vec<u8>,
+    pub ttl: u32,
+    pub qos_class: QosClass,
+    /// Error of the last failed attempt
+    pub reason: String,
+    pub first_failed_ms: u64,
+    pub last_failed_ms: u64,
+    /// Failed retries since the letter was queued
+    pub redeliveries: u32,
+}
+
+/// Dead-letter counters since startup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeadLetterStats {
+    pub queued: u64,
+    pub expired: u64,
+    pub evicted: u64,
+    /// Letters sent successfully on retry
+    pub redelivered: u64,
+    /// Letters dropped after `max_redeliveries` failed retries
+    pub abandoned: u64,
+}
+
+/// Bounded queue of undeliverable packets, oldest first
+#[derive(Debug, Clone, Default)]
+pub struct DeadLetterQueue {
+    config: DeadLetterConfig,
+    letters: VecDeque<DeadLetter>,
+    next_id: u64,
+    stats: DeadLetterStats,
+}
+
+impl DeadLetterQueue {
+    pub fn new(config: DeadLetterConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn config(&self) -> &DeadLetterConfig {
+        &self.config
+    }
+
+    /// Replace the settings; letters beyond the new capacity are evicted
+    pub fn set_config(&mut self, config: DeadLetterConfig) {
+        self.config = config;
+        while self.letters.len() > self.config.capacity {
+            self.letters.pop_front();
+            self.stats.evicted += 1;
+        }
+    }
+
+    /// Queue a payload whose sends all failed; returns the letter ID, or
+    /// None if dead-lettering is disabled
+    pub fn push(
+        &mut self,
+        destination: NodeId,
+        payload: Vec<u8>,
+        ttl: u32,
+        qos_class: QosClass,
+        reason: String,
+        now_ms: u64,
+    ) -> Option<u64> {
+        if !self.config.enabled {
+            return None;
+        }
+        self.expire(now_ms);
+        if self.letters.len() >= self.config.capacity {
+            self.letters.pop_front();
+            self.stats.evicted += 1;
+        }
+        self.next_id += 1;
+        self.letters.push_back(DeadLetter {
+            id: self.next_id,
+            destination,
+            payload,
+            ttl,
+            qos_class,
+            reason,
+            first_failed_ms: now_ms,
+            last_failed_ms: now_ms,
+            redeliveries: 0,
+        });
+        self.stats.queued += 1;
+        Some(self.next_id)
+    }
+
+    /// Drop letters older than `ttl_ms`
+    pub fn expire(&mut self, now_ms: u64) {
+        let ttl = self.config.ttl_ms;
+        let before = self.letters.len();
+        self.letters.retain(|l| now_ms.saturating_sub(l.first_failed_ms) < ttl);
+        self.stats.expired += (before - self.letters.len()) as u64;
+    }
+
+    /// Letters still queued at `now_ms`, oldest first
+    pub fn list(&mut self, now_ms: u64) -> Vec<DeadLetter> {
+        self.expire(now_ms);
+        self.letters.iter().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.letters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.letters.is_empty()
+    }
+
+    /// Delete a letter
+    pub fn remove(&mut self, id: u64) -> Option<DeadLetter> {
+        let index = self.letters.iter().position(|l| l.id == id)?;
+        self.letters.remove(index)
+    }
+
+    /// Take every unexpired letter out for a retry
+    ///
+    /// Hand each one back to `retry_failed` or `retry_succeeded`.
+    pub fn take_all(&mut self, now_ms: u64) -> Vec<DeadLetter> {
+        self.expire(now_ms);
+        self.letters.drain(..).collect()
+    }
+
+    /// Take one letter out for a retry
+    pub fn take(&mut self, id: u64, now_ms: u64) -> Option<DeadLetter> {
+        self.expire(now_ms);
+        self.remove(id)
+    }
+
+    /// Take the letters for one destination out for a retry
+    pub fn take_for(&mut self, destination: &NodeId, now_ms: u64) -> Vec<DeadLetter> {
+        self.expire(now_ms);
+        let (taken, kept): (Vec<_>, Vec<_>) = self.letters.drain(..).partition(|l| &l.destination == destination);
+        self.letters = kept.into();
+        taken
+    }
+
+    /// Count a letter that was sent on retry
+    pub fn retry_succeeded(&mut self, _letter: DeadLetter) {
+        self.stats.redelivered += 1;
+    }
+
+    /// Requeue a letter whose retry failed, unless it is out of retries
+    ///
+    /// Returns false if the letter was abandoned.
+    pub fn retry_failed(&mut self, mut letter: DeadLetter, reason: String, now_ms: u64) -> bool {
+        letter.redeliveries += 1;
+        letter.reason = reason;
+        letter.last_failed_ms = now_ms;
+        if letter.redeliveries > self.config.max_redeliveries {
+            self.stats.abandoned += 1;
+            return false;
+        }
+        // Keep the queue ordered by first failure so eviction drops the oldest
+        let index = self.letters.partition_point(|l| l.first_failed_ms <= letter.first_failed_ms);
+        self.letters.insert(index, letter);
+        if self.letters.len() > self.config.capacity {
+            self.letters.pop_front();
+            self.stats.evicted += 1;
+        }
+        true
+    }
+
+    pub fn stats(&self) -> DeadLetterStats {
+        self.stats
+    }
+}
+
+impl MemoryFootprint for DeadLetterQueue {
+    fn memory(&self) -> MemoryUsage {
+        let owned: usize = self
+            .letters
+            .iter()
+            .map(|letter| memory::id_bytes(&letter.destination) + letter.payload.capacity() + letter.reason.capacity())
+            .sum();
+        MemoryUsage::new(self.letters.len(), memory::vec_bytes::<DeadLetter>(self.letters.capacity()) + owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(capacity: usize) -> DeadLetterQueue {
+        DeadLetterQueue::new(DeadLetterConfig { capacity, ttl_ms: 1000, max_redeliveries: 1, ..Default::default() })
+    }
+
+    fn push(q: &mut DeadLetterQueue, dest: &str, now_ms: u64) -> Option<u64> {
+        q.push(NodeId::new(dest), b"x".to_vec(), 8, QosClass::default(), "Routing failed".to_string(), now_ms)
+    }
+
+    #[test]
+    fn test_queue_is_bounded_and_expires() {
+        let mut q = queue(2);
+        let a = push(&mut q, "a", 0).unwrap();
+        push(&mut q, "b", 100);
+        push(&mut q, "c", 200);
+        let ids: Vec<String> = q.list(300).into_iter().map(|l| l.destination.0).collect();
+        assert_eq!(ids, vec!["b", "c"]);
+        assert!(q.remove(a).is_none());
+
+        assert_eq!(q.list(1150).len(), 1);
+        let stats = q.stats();
+        assert_eq!((stats.queued, stats.evicted, stats.expired), (3, 1, 1));
+
+        let mut disabled = DeadLetterQueue::new(DeadLetterConfig { enabled: false, ..Default::default() });
+        assert!(push(&mut disabled, "a", 0).is_none());
+        assert!(DeadLetterConfig { max_attempts: 0, ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_retries_requeue_until_abandoned() {
+        let mut q = queue(4);
+        push(&mut q, "a", 0);
+        push(&mut q, "b", 10);
+
+        let mut letters = q.take_all(20);
+        assert!(q.is_empty());
+        let b = letters.pop().unwrap();
+        let a = letters.pop().unwrap();
+        q.retry_succeeded(b);
+        assert!(q.retry_failed(a, "still unreachable".to_string(), 30));
+        assert_eq!(q.list(30)[0].redeliveries, 1);
+        push(&mut q, "c", 30);
+        assert_eq!(q.take_for(&NodeId::new("c"), 30).len(), 1);
+        assert_eq!(q.len(), 1);
+
+        // Out of redeliveries on the second failure
+        let id = q.list(40)[0].id;
+        let a = q.take(id, 40).unwrap();
+        assert!(!q.retry_failed(a, "still unreachable".to_string(), 40));
+        assert!(q.is_empty());
+        assert_eq!((q.stats().redelivered, q.stats().abandoned), (1, 1));
+    }
+}
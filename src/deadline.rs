@@ -0,0 +1,129 @@
+//! Per-Packet Delivery Deadlines
+//!
+//! A Data packet may carry a deadline: the Unix time in milliseconds by
+//! which it is of any use to the destination, as for audio frames or game
+//! state. Before handing such a packet to the next hop, every node on the
+//! path, the source included, estimates how long the rest of the path will
+//! take: the link to the next hop, at half its measured RTT, plus the hops
+//! expected from there to the target (its hyperbolic distance over the mean
+//! link length, as for TTLs) at the mean half-RTT to this node's neighbors.
+//! If that overruns the time left, the packet is dropped rather than
+//! carried further, and a forwarder sends the source an Error packet with a
+//! `DeadlineNotice` saying where and by how much it fell short.
+//!
+//! Deadlines are compared against each node's wall clock, so clocks are
+//! assumed to agree to well within the deadlines used. Packets without a
+//! deadline, and nodes with `enabled` off, are unaffected.
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+
+/// Deadline enforcement settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeadlineConfig {
+    /// Drop packets that cannot meet their deadline when forwarding them
+    pub enabled: bool,
+    /// One-way latency assumed for a hop while no neighbor RTT is measured
+    pub default_hop_latency_ms: f64,
+}
+
+impl Default for DeadlineConfig {
+    fn default() -> Self {
+        Self { enabled: true, default_hop_latency_ms: 10.0 }
+    }
+}
+
+impl DeadlineConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.default_hop_latency_ms.is_finite() && self.default_hop_latency_ms > 0.0) {
+            return Err("Default hop latency must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// How a packet would have missed its deadline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeadlineMiss {
+    /// Time left until the deadline when the packet was dropped; negative
+    /// if it had already passed
+    pub left_ms: i64,
+    /// Estimated latency of the rest of the path, rounded up
+    pub estimated_ms: u64,
+}
+
+/// Error packet payload: a packet of the source dropped on the way
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeadlineNotice {
+    pub packet_id: String,
+    /// Destination of the dropped packet
+    pub destination: NodeId,
+    /// Node that dropped the packet
+    pub dropped_at: NodeId,
+    pub miss: DeadlineMiss,
+}
+
+/// Mean one-way latency of the measured links, or `fallback` if none is
+pub fn hop_latency_ms(half_rtts_ms: impl IntoIterator<Item = f64>, fallback: f64) -> f64 {
+    let (sum, count) = half_rtts_ms.into_iter().fold((0.0, 0usize), |(sum, count), ms| (sum + ms, count + 1));
+    if count == 0 {
+        fallback
+    } else {
+        sum / count as f64
+    }
+}
+
+/// Latency of the rest of the path, from the link to the next hop and the
+/// hops expected after it
+///
+/// Without an estimate of the hops after it (no usable link length), the
+/// next hop is taken to be the last.
+pub fn remaining_latency_ms(next_link_ms: f64, hops_after: Option<f64>, hop_latency_ms: f64) -> f64 {
+    next_link_ms + hops_after.unwrap_or(0.0).max(0.0) * hop_latency_ms
+}
+
+/// The miss, if a packet due at `deadline_ms` cannot make it by taking
+/// `estimated_ms` more from `now_ms`
+pub fn check(deadline_ms: u64, now_ms: u64, estimated_ms: f64) -> Option<DeadlineMiss> {
+    let left_ms = deadline_ms as i64 - now_ms as i64;
+    (estimated_ms > left_ms as f64).then(|| DeadlineMiss { left_ms, estimated_ms: estimated_ms.ceil() as u64 })
+}
+
+/// Deadline counters since startup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeadlineStats {
+    /// Packets dropped here, as source or forwarder, for missing their deadline
+    pub dropped: u64,
+    /// Error packets sent to the sources of those packets
+    pub notices_sent: u64,
+    /// Error packets received about packets this node sent
+    pub notices_received: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remaining_latency_from_telemetry() {
+        assert_eq!(hop_latency_ms([4.0, 8.0], 10.0), 6.0);
+        assert_eq!(hop_latency_ms([], 10.0), 10.0);
+        // 4 ms to the next hop, then 2.5 hops of 6 ms
+        assert_eq!(remaining_latency_ms(4.0, Some(2.5), 6.0), 19.0);
+        assert_eq!(remaining_latency_ms(4.0, None, 6.0), 4.0);
+        assert_eq!(remaining_latency_ms(4.0, Some(-1.0), 6.0), 4.0);
+    }
+
+    #[test]
+    fn test_check_against_time_left() {
+        assert_eq!(check(1_100, 1_000, 99.0), None);
+        assert_eq!(check(1_100, 1_000, 100.0), None);
+        assert_eq!(check(1_100, 1_000, 100.5), Some(DeadlineMiss { left_ms: 100, estimated_ms: 101 }));
+        // Already late: dropped however close the destination
+        assert_eq!(check(1_000, 1_020, 0.0), Some(DeadlineMiss { left_ms: -20, estimated_ms: 0 }));
+        assert!(DeadlineConfig::default().validate().is_ok());
+        assert!(DeadlineConfig { default_hop_latency_ms: 0.0, ..DeadlineConfig::default() }.validate().is_err());
+    }
+}
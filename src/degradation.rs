@@ -0,0 +1,356 @@
+//! Graceful Degradation Under Resource Pressure
+//!
+//! A relay that runs short of memory, CPU or file descriptors otherwise
+//! keeps growing its tables until the kernel kills it, taking every route
+//! through it along. `ResourceMonitor` instead turns the node's resource use
+//! into a pressure, the highest fraction of any limit in use, and steps
+//! through a degradation profile as it rises:
+//!
+//! 1. `ShedBulk`: drop `Bulk` traffic at egress, the lowest QoS class
+//! 2. `ShrinkCaches`: cut route, path and dead-letter caches to a fraction
+//! 3. `SlowHeartbeats`: lengthen the heartbeat interval
+//! 4. `RefuseNeighbors`: turn away new neighbors and stop seeking them
+//! 5. `ForwardingOnly`: forward transit traffic and nothing else; no local
+//!    traffic but control is originated and coordinates stop updating
+//!
+//! Each level keeps the measures of the ones below it. A level is entered
+//! once the pressure reaches its threshold and left only once the pressure
+//! falls `hysteresis` below it, so a node hovering at a threshold does not
+//! flap between levels.
+//!
+//! Resource use is read from `/proc` where available. A limit of zero is
+//! taken from the system: total memory, and the soft limit on open files.
+
+use serde::{Deserialize, Serialize};
+
+/// Measures a node takes against resource pressure, mildest first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DegradationLevel {
+    #[default]
+    Normal,
+    ShedBulk,
+    ShrinkCaches,
+    SlowHeartbeats,
+    RefuseNeighbors,
+    ForwardingOnly,
+}
+
+impl DegradationLevel {
+    /// Levels above `Normal`, mildest first
+    pub const DEGRADED: [DegradationLevel; 5] = [
+        DegradationLevel::ShedBulk,
+        DegradationLevel::ShrinkCaches,
+        DegradationLevel::SlowHeartbeats,
+        DegradationLevel::RefuseNeighbors,
+        DegradationLevel::ForwardingOnly,
+    ];
+}
+
+/// Resource limits and the pressure at which each level is entered
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DegradationConfig {
+    pub enabled: bool,
+    /// Resident memory the node may use; zero for the system's total
+    pub memory_limit_bytes: u64,
+    /// Open file descriptors the node may hold; zero for its soft limit
+    pub fd_limit: u64,
+    /// Share of all cores the node may keep busy; None leaves CPU out,
+    /// since a busy node only slows down where a full one fails
+    pub cpu_limit: Option<f64>,
+    /// Pressure entering each level above `Normal`, mildest first
+    pub thresholds: [f64; 5],
+    /// How far below its threshold the pressure must fall to leave a level
+    pub hysteresis: f64,
+    /// Share of their configured capacity caches keep from `ShrinkCaches` on
+    pub cache_fraction: f64,
+    /// Factor on the heartbeat interval from `SlowHeartbeats` on
+    pub heartbeat_factor: u32,
+    /// Backoff hinted to peers turned away from `RefuseNeighbors` on
+    pub refuse_backoff_ms: u64,
+}
+
+impl Default for DegradationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            memory_limit_bytes: 0,
+            fd_limit: 0,
+            cpu_limit: None,
+            thresholds: [0.70, 0.80, 0.85, 0.90, 0.95],
+            hysteresis: 0.05,
+            cache_fraction: 0.25,
+            heartbeat_factor: 2,
+            refuse_backoff_ms: 30_000,
+        }
+    }
+}
+
+impl DegradationConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.cpu_limit.is_some_and(|limit| !(limit > 0.0 && limit <= 1.0)) {
+            return Err("degradation cpu_limit must be in (0, 1]".to_string());
+        }
+        if self.thresholds.iter().any(|t| !(*t > 0.0 && *t <= 1.0)) || self.thresholds.windows(2).any(|w| w[0] > w[1]) {
+            return Err("degradation thresholds must be in (0, 1] and non-decreasing".to_string());
+        }
+        if !(0.0..1.0).contains(&self.hysteresis) {
+            return Err("degradation hysteresis must be in [0, 1)".to_string());
+        }
+        if !(self.cache_fraction > 0.0 && self.cache_fraction <= 1.0) {
+            return Err("degradation cache_fraction must be in (0, 1]".to_string());
+        }
+        if self.heartbeat_factor == 0 {
+            return Err("degradation heartbeat_factor must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    fn threshold(&self, level: DegradationLevel) -> f64 {
+        match level {
+            DegradationLevel::Normal => 0.0,
+            level => self.thresholds[DegradationLevel::DEGRADED.iter().position(|l| *l == level).unwrap_or(0)],
+        }
+    }
+
+    /// `capacity` as kept under `level`, never below one entry
+    pub fn cache_capacity(&self, capacity: usize, level: DegradationLevel) -> usize {
+        if level < DegradationLevel::ShrinkCaches {
+            return capacity;
+        }
+        ((capacity as f64 * self.cache_fraction) as usize).clamp(1.min(capacity), capacity)
+    }
+}
+
+/// Resources in use by the process, and its limits where known
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    pub memory_bytes: u64,
+    pub open_fds: u64,
+    /// Share of all cores busy with the process since the last sample
+    pub cpu: f64,
+    /// Total system memory, if known
+    pub system_memory_bytes: Option<u64>,
+    /// Soft limit on open files, if known
+    pub fd_soft_limit: Option<u64>,
+}
+
+impl ResourceUsage {
+    /// Highest fraction of any limit in use
+    ///
+    /// A resource whose limit is neither configured nor known does not count.
+    pub fn pressure(&self, config: &DegradationConfig) -> f64 {
+        let fraction = |used: u64, limit: u64, known: Option<u64>| {
+            let limit = if limit > 0 { Some(limit) } else { known };
+            limit.filter(|l| *l > 0).map_or(0.0, |l| used as f64 / l as f64)
+        };
+        let memory = fraction(self.memory_bytes, config.memory_limit_bytes, self.system_memory_bytes);
+        let fds = fraction(self.open_fds, config.fd_limit, self.fd_soft_limit);
+        let cpu = config.cpu_limit.map_or(0.0, |limit| self.cpu / limit);
+        memory.max(fds).max(cpu)
+    }
+}
+
+/// Reads the process's resource use from `/proc`
+#[derive(Debug, Default)]
+pub struct ProcessProbe {
+    /// CPU ticks used by the process when last sampled, and when
+    last_cpu: Option<(u64, std::time::Instant)>,
+}
+
+/// Clock ticks per second of `/proc` CPU times on Linux
+const CLOCK_TICKS: f64 = 100.0;
+/// Page size assumed for `/proc/self/statm`
+const PAGE_SIZE: u64 = 4096;
+
+impl ProcessProbe {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current use; resources `/proc` cannot tell about read as zero
+    pub fn sample(&mut self) -> ResourceUsage {
+        let read = |path: &str| std::fs::read_to_string(path).ok();
+        let memory_bytes = read("/proc/self/statm")
+            .and_then(|s| s.split_whitespace().nth(1)?.parse::<u64>().ok())
+            .map_or(0, |pages| pages * PAGE_SIZE);
+        let open_fds = std::fs::read_dir("/proc/self/fd").map_or(0, |dir| dir.count() as u64);
+        let system_memory_bytes = read("/proc/meminfo").and_then(|s| {
+            let line = s.lines().find(|l| l.starts_with("MemTotal:"))?;
+            Some(line.split_whitespace().nth(1)?.parse::<u64>().ok()? * 1024)
+        });
+        let fd_soft_limit = read("/proc/self/limits").and_then(|s| {
+            let line = s.lines().find(|l| l.starts_with("Max open files"))?;
+            line.split_whitespace().nth(3)?.parse().ok()
+        });
+
+        let now = std::time::Instant::now();
+        let ticks = read("/proc/self/stat").and_then(|s| {
+            // Fields after the parenthesized command name; utime and stime are the 12th and 13th
+            let fields: Vec<&str> = s.rsplit_once(')')?.1.split_whitespace().collect();
+            Some(fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?)
+        });
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get()) as f64;
+        let cpu = match (ticks, self.last_cpu) {
+            (Some(ticks), Some((last, at))) if now > at => {
+                let busy = ticks.saturating_sub(last) as f64 / CLOCK_TICKS;
+                (busy / now.duration_since(at).as_secs_f64() / cores).min(1.0)
+            }
+            _ => 0.0,
+        };
+        if let Some(ticks) = ticks {
+            self.last_cpu = Some((ticks, now));
+        }
+
+        ResourceUsage { memory_bytes, open_fds, cpu, system_memory_bytes, fd_soft_limit }
+    }
+}
+
+/// Current level of a node and how it got there
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct DegradationStatus {
+    pub level: DegradationLevel,
+    /// Pressure at the last sample
+    pub pressure: f64,
+    pub usage: ResourceUsage,
+    /// Level changes since startup
+    pub transitions: u64,
+    /// Packets dropped at egress for their QoS class
+    pub shed: u64,
+}
+
+/// Steps a node through the degradation profile as its resource pressure changes
+#[derive(Debug, Default)]
+pub struct ResourceMonitor {
+    config: DegradationConfig,
+    status: DegradationStatus,
+}
+
+impl ResourceMonitor {
+    pub fn new(config: DegradationConfig) -> Self {
+        Self { config, status: DegradationStatus::default() }
+    }
+
+    pub fn config(&self) -> &DegradationConfig {
+        &self.config
+    }
+
+    /// Replace the configuration; disabling returns the node to `Normal`
+    pub fn set_config(&mut self, config: DegradationConfig) {
+        if !config.enabled {
+            self.status.level = DegradationLevel::Normal;
+        }
+        self.config = config;
+    }
+
+    pub fn status(&self) -> DegradationStatus {
+        self.status
+    }
+
+    pub fn level(&self) -> DegradationLevel {
+        self.status.level
+    }
+
+    /// Take in a sample, returning the new level if it changed
+    ///
+    /// The level rises straight to the highest one whose threshold the
+    /// pressure reached, and falls to the highest one whose threshold less
+    /// the hysteresis it still reaches.
+    pub fn observe(&mut self, usage: ResourceUsage) -> Option<DegradationLevel> {
+        let pressure = usage.pressure(&self.config);
+        self.status.pressure = pressure;
+        self.status.usage = usage;
+        if !self.config.enabled {
+            return None;
+        }
+
+        let highest = |margin: f64| {
+            DegradationLevel::DEGRADED
+                .into_iter()
+                .filter(|level| pressure >= self.config.threshold(*level) - margin)
+                .max()
+                .unwrap_or_default()
+        };
+        let current = self.status.level;
+        let entered = highest(0.0);
+        let next = if entered > current { entered } else { current.min(highest(self.config.hysteresis)) };
+        if next == current {
+            return None;
+        }
+        self.status.level = next;
+        self.status.transitions += 1;
+        Some(next)
+    }
+
+    /// Whether egress drops traffic of `qos_class` at the current level
+    pub fn sheds(&self, qos_class: crate::ttl_policy::QosClass) -> bool {
+        qos_class == crate::ttl_policy::QosClass::Bulk && self.status.level >= DegradationLevel::ShedBulk
+    }
+
+    /// Count a packet dropped for its QoS class
+    pub fn record_shed(&mut self) {
+        self.status.shed += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ttl_policy::QosClass;
+
+    fn usage(memory_bytes: u64) -> ResourceUsage {
+        ResourceUsage { memory_bytes, ..Default::default() }
+    }
+
+    #[test]
+    fn test_levels_follow_pressure_with_hysteresis() {
+        let config = DegradationConfig { memory_limit_bytes: 1_000, ..Default::default() };
+        assert!(config.validate().is_ok());
+        let mut monitor = ResourceMonitor::new(config);
+
+        assert_eq!(monitor.observe(usage(500)), None);
+        assert_eq!(monitor.observe(usage(720)), Some(DegradationLevel::ShedBulk));
+        assert!(monitor.sheds(QosClass::Bulk) && !monitor.sheds(QosClass::Standard));
+        // A jump passes through every level at once
+        assert_eq!(monitor.observe(usage(960)), Some(DegradationLevel::ForwardingOnly));
+        // Just under a threshold is not enough to leave it
+        assert_eq!(monitor.observe(usage(930)), None);
+        assert_eq!(monitor.observe(usage(880)), Some(DegradationLevel::RefuseNeighbors));
+        assert_eq!(monitor.observe(usage(600)), Some(DegradationLevel::Normal));
+        assert_eq!(monitor.status().transitions, 4);
+        assert!(!monitor.sheds(QosClass::Bulk));
+
+        monitor.observe(usage(999));
+        monitor.set_config(DegradationConfig { enabled: false, ..DegradationConfig::default() });
+        assert_eq!(monitor.level(), DegradationLevel::Normal);
+        assert_eq!(monitor.observe(usage(999)), None);
+    }
+
+    #[test]
+    fn test_pressure_and_cache_capacity() {
+        let config = DegradationConfig { fd_limit: 100, cpu_limit: Some(0.5), ..Default::default() };
+        let sample = ResourceUsage {
+            memory_bytes: 300,
+            open_fds: 40,
+            cpu: 0.3,
+            system_memory_bytes: Some(1_000),
+            fd_soft_limit: Some(1_024),
+        };
+        // CPU at 0.3 of a 0.5 share outweighs memory and descriptors
+        assert!((sample.pressure(&config) - 0.6).abs() < 1e-9);
+        // Unknown limits, and CPU by default, do not count
+        assert_eq!(usage(1 << 40).pressure(&DegradationConfig::default()), 0.0);
+        assert_eq!(ResourceUsage { cpu: 1.0, ..sample }.pressure(&DegradationConfig::default()), 0.3);
+
+        assert_eq!(config.cache_capacity(1024, DegradationLevel::ShedBulk), 1024);
+        assert_eq!(config.cache_capacity(1024, DegradationLevel::ShrinkCaches), 256);
+        assert_eq!(config.cache_capacity(2, DegradationLevel::ForwardingOnly), 1);
+        assert_eq!(config.cache_capacity(0, DegradationLevel::ForwardingOnly), 0);
+
+        let mut bad = config;
+        bad.thresholds = [0.9, 0.8, 0.85, 0.9, 0.95];
+        assert!(bad.validate().is_err());
+        assert!(ProcessProbe::new().sample().pressure(&DegradationConfig::default()) >= 0.0);
+    }
+}
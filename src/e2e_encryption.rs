@@ -0,0 +1,561 @@
+//! End-to-End Payload Encryption
+//!
+//! TLS protects each overlay link, but every relay on the path still sees
+//! Data payloads in the clear. With end-to-end encryption the source seals
+//! a payload for its destination and relays only forward ciphertext.
+//!
+//! Keys come from the nodes' Ed25519 identity keys, the same keys that sign
+//! packets and coordinate certificates, so there is nothing extra to
+//! distribute: the destination's key is looked up in a `KeyDirectory`,
+//! filled from a rendezvous controller's verifier or by the operator. A
+//! session to a destination starts with a fresh ephemeral X25519 key; its
+//! AEAD key is HKDF-SHA256 over two Diffie-Hellman results, ephemeral with
+//! the destination's static key and the source's static key with the
+//! destination's, so only the destination can read the payload and only the
+//! source could have sealed it. Payloads are sealed with
+//! ChaCha20-Poly1305 under a per-session counter, and the destination keeps
+//! a sliding window of counters per session to drop replays. Sessions are
+//! rekeyed after `rekey_after_messages` payloads; a destination keeps the
+//! current and the previous session of each source and refuses sessions
+//! started before those, so evicted sessions cannot be replayed either.
+//!
+//! `seal_anonymous` seals a single payload without a sender: the key comes
+//! from the ephemeral key alone, so the recipient learns nothing about who
+//! sealed it. Onion layers use this; each carries a fresh ephemeral key and
+//! recipients drop repeated ones.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use thiserror::Error;
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+
+use crate::coordinates::NodeId;
+use crate::rendezvous::RendezvousController;
+
+/// Counters behind the highest one seen that are still accepted once
+pub const REPLAY_WINDOW: u64 = 64;
+/// Sessions kept per source: the current one and its predecessor
+const SESSIONS_PER_SOURCE: usize = 2;
+/// Ephemeral keys of anonymous payloads remembered to drop replays
+const ANONYMOUS_REPLAY_CACHE: usize = 4096;
+const ENVELOPE_VERSION: u8 = 1;
+const KDF_INFO: &[u8] = b"drfe-r/e2e-session";
+const KDF_INFO_ANONYMOUS: &[u8] = b"drfe-r/e2e-anonymous";
+
+/// End-to-end encryption settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct E2eConfig {
+    /// Seal Data payloads this node originates
+    pub enabled: bool,
+    /// Drop Data payloads that arrive unencrypted
+    pub require_encrypted: bool,
+    /// Payloads sealed under one session before a new one is started
+    pub rekey_after_messages: u64,
+}
+
+impl Default for E2eConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            require_encrypted: false,
+            rekey_after_messages: 1 << 20,
+        }
+    }
+}
+
+impl E2eConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.rekey_after_messages == 0 {
+            return Err("rekey_after_messages must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Payloads that could not be sealed or opened
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum EncryptionError {
+    #[error("Node has no identity key")]
+    NoIdentity,
+
+    #[error("No identity key known for {0}")]
+    UnknownKey(NodeId),
+
+    #[error("Invalid identity key for {0}")]
+    InvalidKey(NodeId),
+
+    #[error("Malformed encrypted payload")]
+    Malformed,
+
+    #[error("Encrypted payload from {0} failed authentication")]
+    Forged(NodeId),
+
+    #[error("Replayed payload from {0}")]
+    Replayed(NodeId),
+
+    #[error("Payload from {0} belongs to an expired session")]
+    StaleSession(NodeId),
+
+    #[error("Unencrypted payload from {0}")]
+    Plaintext(NodeId),
+
+    #[error("Onion route needs {needed} relays, found {found}")]
+    TooFewRelays { needed: usize, found: usize },
+}
+
+impl EncryptionError {
+    /// Stable identifier for programmatic handling
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NoIdentity => "encryption.no_identity",
+            Self::UnknownKey(_) => "encryption.unknown_key",
+            Self::InvalidKey(_) => "encryption.invalid_key",
+            Self::Malformed => "encryption.malformed",
+            Self::Forged(_) => "encryption.forged",
+            Self::Replayed(_) => "encryption.replayed",
+            Self::StaleSession(_) => "encryption.stale_session",
+            Self::Plaintext(_) => "encryption.plaintext",
+            Self::TooFewRelays { .. } => "encryption.too_few_relays",
+        }
+    }
+}
+
+/// Ed25519 identity keys of other nodes
+#[derive(Debug, Clone, Default)]
+pub struct KeyDirectory {
+    keys: HashMap<NodeId, VerifyingKey>,
+}
+
+impl KeyDirectory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a node's 32-byte identity key
+    pub fn insert(&mut self, node_id: NodeId, public_key: &[u8]) -> Result<(), EncryptionError> {
+        let key = <[u8; 32]>::try_from(public_key)
+            .ok()
+            .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+            .ok_or_else(|| EncryptionError::InvalidKey(node_id.clone()))?;
+        self.keys.insert(node_id, key);
+        Ok(())
+    }
+
+    /// Look up a key, asking the rendezvous controller for unknown nodes
+    pub fn fetch(&mut self, node_id: &NodeId, rendezvous: &RendezvousController) -> Result<VerifyingKey, EncryptionError> {
+        if !self.keys.contains_key(node_id) {
+            let key = rendezvous
+                .lookup_identity_key(node_id)
+                .ok_or_else(|| EncryptionError::UnknownKey(node_id.clone()))?;
+            self.insert(node_id.clone(), key)?;
+        }
+        self.get(node_id)
+    }
+
+    pub fn get(&self, node_id: &NodeId) -> Result<VerifyingKey, EncryptionError> {
+        self.keys.get(node_id).copied().ok_or_else(|| EncryptionError::UnknownKey(node_id.clone()))
+    }
+
+    /// Nodes with a known key
+    pub fn node_ids(&self) -> Vec<NodeId> {
+        self.keys.keys().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// What travels in place of the plaintext payload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Envelope {
+    version: u8,
+    /// When the sender started the session, in its clock; orders sessions
+    session_started_ms: u64,
+    ephemeral: [u8; 32],
+    counter: u64,
+    ciphertext: Vec<u8>,
+}
+
+impl Envelope {
+    fn nonce(&self) -> Nonce {
+        let mut nonce = [0u8; 12];
+        nonce[4..].copy_from_slice(&self.counter.to_le_bytes());
+        Nonce::from(nonce)
+    }
+
+    /// Authenticated, unencrypted header fields
+    fn aad(&self) -> Vec<u8> {
+        let mut aad = vec![self.version];
+        aad.extend_from_slice(&self.session_started_ms.to_le_bytes());
+        aad.extend_from_slice(&self.ephemeral);
+        aad.extend_from_slice(&self.counter.to_le_bytes());
+        aad
+    }
+}
+
+/// Counters seen in a session, as a bitmap behind the highest
+#[derive(Debug, Clone, Copy, Default)]
+struct ReplayWindow {
+    highest: Option<u64>,
+    seen: u64,
+}
+
+impl ReplayWindow {
+    fn is_fresh(&self, counter: u64) -> bool {
+        match self.highest {
+            None => true,
+            Some(highest) if counter > highest => true,
+            Some(highest) => highest - counter < REPLAY_WINDOW && self.seen & (1 << (highest - counter)) == 0,
+        }
+    }
+
+    fn mark(&mut self, counter: u64) {
+        match self.highest {
+            Some(highest) if counter <= highest => self.seen |= 1 << (highest - counter),
+            Some(highest) => {
+                let shift = counter - highest;
+                self.seen = if shift >= REPLAY_WINDOW { 0 } else { self.seen << shift };
+                self.seen |= 1;
+                self.highest = Some(counter);
+            }
+            None => {
+                self.seen = 1;
+                self.highest = Some(counter);
+            }
+        }
+    }
+}
+
+struct OutboundSession {
+    started_ms: u64,
+    ephemeral: [u8; 32],
+    cipher: ChaCha20Poly1305,
+    next_counter: u64,
+}
+
+struct InboundSession {
+    started_ms: u64,
+    ephemeral: [u8; 32],
+    cipher: ChaCha20Poly1305,
+    replay: ReplayWindow,
+}
+
+/// Payloads sealed and opened by a node
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptionStats {
+    pub sealed: u64,
+    pub opened: u64,
+    pub sessions_started: u64,
+    pub rejected_forged: u64,
+    pub rejected_replayed: u64,
+    pub rejected_plaintext: u64,
+}
+
+/// A node's end-to-end sessions, both directions
+pub struct E2eSessions {
+    local_id: NodeId,
+    secret: StaticSecret,
+    outbound: HashMap<NodeId, OutboundSession>,
+    inbound: HashMap<NodeId, Vec<InboundSession>>,
+    /// Ephemeral keys of anonymous payloads opened recently
+    anonymous_seen: HashSet<[u8; 32]>,
+    anonymous_order: VecDeque<[u8; 32]>,
+    stats: EncryptionStats,
+}
+
+impl E2eSessions {
+    pub fn new(local_id: NodeId, identity: &SigningKey) -> Self {
+        Self {
+            local_id,
+            secret: StaticSecret::from(identity.to_scalar_bytes()),
+            outbound: HashMap::new(),
+            inbound: HashMap::new(),
+            anonymous_seen: HashSet::new(),
+            anonymous_order: VecDeque::new(),
+            stats: EncryptionStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> EncryptionStats {
+        self.stats
+    }
+
+    /// Note a payload dropped for arriving unencrypted
+    pub fn record_plaintext(&mut self) {
+        self.stats.rejected_plaintext += 1;
+    }
+
+    /// Encrypt a payload for `destination`, whose identity key is `key`
+    pub fn seal(
+        &mut self,
+        destination: &NodeId,
+        key: &VerifyingKey,
+        plaintext: &[u8],
+        config: &E2eConfig,
+        now_ms: u64,
+    ) -> Result<Vec<u8>, EncryptionError> {
+        let expired = self
+            .outbound
+            .get(destination)
+            .is_none_or(|s| s.next_counter >= config.rekey_after_messages);
+        if expired {
+            let session = self.start_session(destination, key, now_ms)?;
+            self.outbound.insert(destination.clone(), session);
+            self.stats.sessions_started += 1;
+        }
+        let session = self.outbound.get_mut(destination).ok_or(EncryptionError::Malformed)?;
+
+        let mut envelope = Envelope {
+            version: ENVELOPE_VERSION,
+            session_started_ms: session.started_ms,
+            ephemeral: session.ephemeral,
+            counter: session.next_counter,
+            ciphertext: Vec::new(),
+        };
+        session.next_counter += 1;
+        envelope.ciphertext = session
+            .cipher
+            .encrypt(&envelope.nonce(), Payload { msg: plaintext, aad: &envelope.aad() })
+            .map_err(|_| EncryptionError::Malformed)?;
+        self.stats.sealed += 1;
+        bincode::serialize(&envelope).map_err(|_| EncryptionError::Malformed)
+    }
+
+    fn start_session(&self, destination: &NodeId, key: &VerifyingKey, now_ms: u64) -> Result<OutboundSession, EncryptionError> {
+        let remote = PublicKey::from(key.to_montgomery().to_bytes());
+        let ephemeral = EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+        let ephemeral_public = PublicKey::from(&ephemeral).to_bytes();
+        let es = ephemeral.diffie_hellman(&remote);
+        let ss = self.secret.diffie_hellman(&remote);
+        if !es.was_contributory() || !ss.was_contributory() {
+            return Err(EncryptionError::InvalidKey(destination.clone()));
+        }
+        // Start times must grow across sessions even if the clock does not
+        let started_ms = self
+            .outbound
+            .get(destination)
+            .map_or(now_ms, |previous| now_ms.max(previous.started_ms + 1));
+        Ok(OutboundSession {
+            started_ms,
+            ephemeral: ephemeral_public,
+            cipher: session_cipher(es.as_bytes(), ss.as_bytes(), &ephemeral_public, &self.local_id, destination),
+            next_counter: 0,
+        })
+    }
+
+    /// Decrypt a payload `source`, whose identity key is `key`, sealed for us
+    pub fn open(&mut self, source: &NodeId, key: &VerifyingKey, sealed: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let envelope: Envelope = bincode::deserialize(sealed).map_err(|_| EncryptionError::Malformed)?;
+        if envelope.version != ENVELOPE_VERSION {
+            return Err(EncryptionError::Malformed);
+        }
+
+        let sessions = self.inbound.entry(source.clone()).or_default();
+        let index = match sessions.iter().position(|s| s.ephemeral == envelope.ephemeral) {
+            Some(index) => index,
+            None => {
+                if sessions.len() >= SESSIONS_PER_SOURCE
+                    && sessions.iter().all(|s| s.started_ms >= envelope.session_started_ms)
+                {
+                    return Err(EncryptionError::StaleSession(source.clone()));
+                }
+                let remote = PublicKey::from(key.to_montgomery().to_bytes());
+                let es = self.secret.diffie_hellman(&PublicKey::from(envelope.ephemeral));
+                let ss = self.secret.diffie_hellman(&remote);
+                if !es.was_contributory() || !ss.was_contributory() {
+                    self.stats.rejected_forged += 1;
+                    return Err(EncryptionError::Forged(source.clone()));
+                }
+                let cipher = session_cipher(es.as_bytes(), ss.as_bytes(), &envelope.ephemeral, source, &self.local_id);
+                sessions.push(InboundSession {
+                    started_ms: envelope.session_started_ms,
+                    ephemeral: envelope.ephemeral,
+                    cipher,
+                    replay: ReplayWindow::default(),
+                });
+                sessions.len() - 1
+            }
+        };
+
+        let session = &mut sessions[index];
+        if !session.replay.is_fresh(envelope.counter) {
+            self.stats.rejected_replayed += 1;
+            return Err(EncryptionError::Replayed(source.clone()));
+        }
+        let Ok(plaintext) = session
+            .cipher
+            .decrypt(&envelope.nonce(), Payload { msg: &envelope.ciphertext, aad: &envelope.aad() })
+        else {
+            // A session that never opened anything was made up by the sender
+            if session.replay.highest.is_none() {
+                sessions.remove(index);
+            }
+            self.stats.rejected_forged += 1;
+            return Err(EncryptionError::Forged(source.clone()));
+        };
+        session.replay.mark(envelope.counter);
+
+        if sessions.len() > SESSIONS_PER_SOURCE {
+            sessions.sort_by_key(|s| std::cmp::Reverse(s.started_ms));
+            sessions.truncate(SESSIONS_PER_SOURCE);
+        }
+        self.stats.opened += 1;
+        Ok(plaintext)
+    }
+
+    /// Decrypt a payload sealed for us by `seal_anonymous`
+    pub fn open_anonymous(&mut self, sealed: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let envelope: Envelope = bincode::deserialize(sealed).map_err(|_| EncryptionError::Malformed)?;
+        if envelope.version != ENVELOPE_VERSION {
+            return Err(EncryptionError::Malformed);
+        }
+        let anonymous = NodeId::new("anonymous");
+        if self.anonymous_seen.contains(&envelope.ephemeral) {
+            self.stats.rejected_replayed += 1;
+            return Err(EncryptionError::Replayed(anonymous));
+        }
+        let es = self.secret.diffie_hellman(&PublicKey::from(envelope.ephemeral));
+        let cipher = derive_cipher(es.as_bytes(), &envelope.ephemeral, KDF_INFO_ANONYMOUS, &[&self.local_id]);
+        let Ok(plaintext) = cipher.decrypt(&envelope.nonce(), Payload { msg: &envelope.ciphertext, aad: &envelope.aad() })
+        else {
+            self.stats.rejected_forged += 1;
+            return Err(EncryptionError::Forged(anonymous));
+        };
+
+        self.anonymous_seen.insert(envelope.ephemeral);
+        self.anonymous_order.push_back(envelope.ephemeral);
+        if self.anonymous_order.len() > ANONYMOUS_REPLAY_CACHE {
+            if let Some(oldest) = self.anonymous_order.pop_front() {
+                self.anonymous_seen.remove(&oldest);
+            }
+        }
+        self.stats.opened += 1;
+        Ok(plaintext)
+    }
+}
+
+/// Seal one payload for `destination` without revealing who sealed it
+pub fn seal_anonymous(destination: &NodeId, key: &VerifyingKey, plaintext: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+    let remote = PublicKey::from(key.to_montgomery().to_bytes());
+    let ephemeral = EphemeralSecret::random_from_rng(rand::rngs::OsRng);
+    let ephemeral_public = PublicKey::from(&ephemeral).to_bytes();
+    let es = ephemeral.diffie_hellman(&remote);
+    if !es.was_contributory() {
+        return Err(EncryptionError::InvalidKey(destination.clone()));
+    }
+    let mut envelope = Envelope {
+        version: ENVELOPE_VERSION,
+        session_started_ms: 0,
+        ephemeral: ephemeral_public,
+        counter: 0,
+        ciphertext: Vec::new(),
+    };
+    envelope.ciphertext = derive_cipher(es.as_bytes(), &ephemeral_public, KDF_INFO_ANONYMOUS, &[destination])
+        .encrypt(&envelope.nonce(), Payload { msg: plaintext, aad: &envelope.aad() })
+        .map_err(|_| EncryptionError::Malformed)?;
+    bincode::serialize(&envelope).map_err(|_| EncryptionError::Malformed)
+}
+
+/// AEAD of a session from `source` to `destination`
+fn session_cipher(es: &[u8; 32], ss: &[u8; 32], ephemeral: &[u8; 32], source: &NodeId, destination: &NodeId) -> ChaCha20Poly1305 {
+    let mut ikm = [0u8; 64];
+    ikm[..32].copy_from_slice(es);
+    ikm[32..].copy_from_slice(ss);
+    derive_cipher(&ikm, ephemeral, KDF_INFO, &[source, destination])
+}
+
+/// HKDF-SHA256 keyed AEAD, salted with the ephemeral key and bound to `ids`
+fn derive_cipher(ikm: &[u8], ephemeral: &[u8; 32], label: &[u8], ids: &[&NodeId]) -> ChaCha20Poly1305 {
+    let mut info = label.to_vec();
+    for id in ids {
+        info.extend_from_slice(&(id.0.len() as u32).to_le_bytes());
+        info.extend_from_slice(id.0.as_bytes());
+    }
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(ephemeral), ikm)
+        .expand(&info, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    ChaCha20Poly1305::new(&key.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    #[test]
+    fn test_seal_open_and_replay() {
+        let (alice, bob) = (NodeId::new("alice"), NodeId::new("bob"));
+        let (alice_key, bob_key) = (identity(1), identity(2));
+        let mut sender = E2eSessions::new(alice.clone(), &alice_key);
+        let mut receiver = E2eSessions::new(bob.clone(), &bob_key);
+        let config = E2eConfig { enabled: true, ..Default::default() };
+
+        let sealed = sender.seal(&bob, &bob_key.verifying_key(), b"hello bob", &config, 10).unwrap();
+        assert!(!sealed.windows(9).any(|w| w == b"hello bob"));
+        assert_eq!(receiver.open(&alice, &alice_key.verifying_key(), &sealed).unwrap(), b"hello bob");
+        assert_eq!(receiver.open(&alice, &alice_key.verifying_key(), &sealed), Err(EncryptionError::Replayed(alice.clone())));
+
+        // Out of order within the window is fine, once
+        let second = sender.seal(&bob, &bob_key.verifying_key(), b"2", &config, 11).unwrap();
+        let third = sender.seal(&bob, &bob_key.verifying_key(), b"3", &config, 12).unwrap();
+        assert_eq!(receiver.open(&alice, &alice_key.verifying_key(), &third).unwrap(), b"3");
+        assert_eq!(receiver.open(&alice, &alice_key.verifying_key(), &second).unwrap(), b"2");
+
+        // Only the claimed source could have sealed it
+        let mallory = identity(3);
+        let mut impostor = E2eSessions::new(alice.clone(), &mallory);
+        let forged = impostor.seal(&bob, &bob_key.verifying_key(), b"hi", &config, 13).unwrap();
+        assert_eq!(receiver.open(&alice, &alice_key.verifying_key(), &forged), Err(EncryptionError::Forged(alice.clone())));
+        // Nor can anyone else read it
+        let mut eve = E2eSessions::new(bob.clone(), &mallory);
+        let fresh = sender.seal(&bob, &bob_key.verifying_key(), b"secret", &config, 14).unwrap();
+        assert!(eve.open(&alice, &alice_key.verifying_key(), &fresh).is_err());
+
+        let stats = receiver.stats();
+        assert_eq!((stats.opened, stats.rejected_replayed, stats.rejected_forged), (3, 1, 1));
+    }
+
+    #[test]
+    fn test_rekey_rejects_expired_sessions() {
+        let (alice, bob) = (NodeId::new("alice"), NodeId::new("bob"));
+        let (alice_key, bob_key) = (identity(1), identity(2));
+        let mut sender = E2eSessions::new(alice.clone(), &alice_key);
+        let mut receiver = E2eSessions::new(bob.clone(), &bob_key);
+        let config = E2eConfig { enabled: true, rekey_after_messages: 1, ..Default::default() };
+
+        let sealed: Vec<Vec<u8>> = (0..3)
+            .map(|i| sender.seal(&bob, &bob_key.verifying_key(), &[i], &config, 100).unwrap())
+            .collect();
+        assert_eq!(sender.stats().sessions_started, 3);
+        for (i, payload) in sealed.iter().enumerate().skip(1) {
+            assert_eq!(receiver.open(&alice, &alice_key.verifying_key(), payload).unwrap(), vec![i as u8]);
+        }
+        // The first session started before both kept ones
+        assert_eq!(
+            receiver.open(&alice, &alice_key.verifying_key(), &sealed[0]),
+            Err(EncryptionError::StaleSession(alice.clone()))
+        );
+
+        let mut directory = KeyDirectory::new();
+        assert!(directory.insert(alice.clone(), &[0u8; 5]).is_err());
+        directory.insert(alice.clone(), alice_key.verifying_key().as_bytes()).unwrap();
+        assert_eq!(directory.get(&alice).unwrap(), alice_key.verifying_key());
+        assert!(E2eConfig { rekey_after_messages: 0, ..Default::default() }.validate().is_err());
+    }
+}
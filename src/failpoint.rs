@@ -0,0 +1,134 @@
+//! Failure Injection Points
+//!
+//! Some failures are hard to provoke from outside: a crash between writing
+//! a checkpoint and renaming it into place, or a send failing right after
+//! the routing decision. Such places are marked with `fail_point!`. Built
+//! with the `failpoints` feature, a test arms a point by name to make it
+//! fail or panic, every time or for the next few hits, and checks that the
+//! node recovers. Without the feature the macro expands to nothing.
+//!
+//! | Point | Where | On `Error` |
+//! |-------|-------|------------|
+//! | `checkpoint.before_write` | `FsCheckpointStore::put`, before the partial file | put fails |
+//! | `checkpoint.after_write` | `FsCheckpointStore::put`, before the rename | put fails, partial file left |
+//! | `coordinate.commit` | `update_coordinates`, between the node and its discovery | update fails half applied |
+//! | `route.before_send` | after the next hop is chosen, before sending to it | send fails |
+//! | `tz.rebuild` | `TZRoutingTable::build`, after the landmark trees | build fails |
+//!
+//! `Panic` stands in for a crash at the point. Armed points are shared by
+//! the whole process, so tests arming them live in a test binary of their
+//! own and hold a `FailScenario`, which runs them one at a time and disarms
+//! every point when dropped.
+
+/// Make the enclosing function return `$body` when the named point is armed
+/// to fail; `$error` is bound to the injected `std::io::Error`
+#[cfg(feature = "failpoints")]
+macro_rules! fail_point {
+    ($name:expr, |$error:ident| $body:expr) => {
+        if let Some($error) = $crate::failpoint::eval($name) {
+            return $body;
+        }
+    };
+}
+
+#[cfg(not(feature = "failpoints"))]
+macro_rules! fail_point {
+    ($name:expr, |$error:ident| $body:expr) => {};
+}
+
+pub(crate) use fail_point;
+
+#[cfg(feature = "failpoints")]
+pub use armed::{arm, arm_times, disarm, eval, hits, FailAction, FailScenario};
+
+#[cfg(feature = "failpoints")]
+mod armed {
+    use std::collections::HashMap;
+    use std::sync::{Mutex, MutexGuard, OnceLock};
+
+    /// What an armed point does when reached
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum FailAction {
+        /// Fail the operation with an I/O error
+        Error,
+        /// Panic, as if the process crashed there
+        Panic,
+    }
+
+    #[derive(Debug, Default)]
+    struct Point {
+        armed: Option<(FailAction, Option<u32>)>,
+        hits: u64,
+    }
+
+    fn registry() -> MutexGuard<'static, HashMap<String, Point>> {
+        static REGISTRY: OnceLock<Mutex<HashMap<String, Point>>> = OnceLock::new();
+        REGISTRY.get_or_init(Mutex::default).lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Arm a point for every hit until disarmed
+    pub fn arm(name: &str, action: FailAction) {
+        registry().entry(name.to_string()).or_default().armed = Some((action, None));
+    }
+
+    /// Arm a point for its next `times` hits
+    pub fn arm_times(name: &str, action: FailAction, times: u32) {
+        registry().entry(name.to_string()).or_default().armed = Some((action, Some(times)));
+    }
+
+    pub fn disarm(name: &str) {
+        if let Some(point) = registry().get_mut(name) {
+            point.armed = None;
+        }
+    }
+
+    /// Times the point was reached, armed or not, since the scenario began
+    pub fn hits(name: &str) -> u64 {
+        registry().get(name).map_or(0, |point| point.hits)
+    }
+
+    /// Count a hit, and return the error to fail with if the point is armed
+    ///
+    /// Panics if it is armed to.
+    pub fn eval(name: &str) -> Option<std::io::Error> {
+        let action = {
+            let mut registry = registry();
+            let point = registry.entry(name.to_string()).or_default();
+            point.hits += 1;
+            let (action, remaining) = point.armed?;
+            match remaining {
+                Some(1) => point.armed = None,
+                Some(n) => point.armed = Some((action, Some(n - 1))),
+                None => {}
+            }
+            action
+        };
+        match action {
+            FailAction::Error => Some(std::io::Error::other(format!("failpoint {} triggered", name))),
+            FailAction::Panic => panic!("failpoint {} triggered", name),
+        }
+    }
+
+    /// Exclusive use of the failure points for one test
+    ///
+    /// Every point starts disarmed with no hits, and is disarmed again on drop.
+    pub struct FailScenario {
+        _exclusive: MutexGuard<'static, ()>,
+    }
+
+    impl FailScenario {
+        pub fn setup() -> Self {
+            static SCENARIO: Mutex<()> = Mutex::new(());
+            // A test that failed while holding it leaves nothing half done
+            let exclusive = SCENARIO.lock().unwrap_or_else(|e| e.into_inner());
+            registry().clear();
+            Self { _exclusive: exclusive }
+        }
+    }
+
+    impl Drop for FailScenario {
+        fn drop(&mut self) {
+            registry().clear();
+        }
+    }
+}
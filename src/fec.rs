@@ -0,0 +1,614 @@
+//! Forward Error Correction for UDP Links
+//!
+//! Control traffic between neighbors travels over UDP, where a lossy radio
+//! link drops heartbeats and coordinate updates without retransmission.
+//! With FEC enabled, a node groups the datagrams it sends to a peer into
+//! batches and follows each batch with Reed-Solomon parity shards, so the
+//! peer can rebuild up to that many lost datagrams without a round trip.
+//! Datagrams still go out immediately as data shards and are delivered on
+//! arrival; parity only matters when something was lost.
+//!
+//! Nodes advertise the schemes they can decode in discovery packets, and a
+//! sender adds parity only toward peers that advertised one. The parity
+//! count per peer is the smallest that reaches `target_delivery` at the
+//! loss measured on the link, so a clean link carries no overhead.
+//!
+//! FEC frames start with `FEC_FRAME_TAG`, a byte MessagePack never emits,
+//! so receivers tell them apart from plain packets without a handshake.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// First byte of every FEC frame
+pub const FEC_FRAME_TAG: u8 = 0xc1;
+
+/// Largest batch a node sends or accepts
+pub const MAX_DATA_SHARDS: usize = 32;
+
+/// Most parity shards per batch a node sends or accepts
+pub const MAX_PARITY_SHARDS: usize = 16;
+
+/// Incomplete batches kept per peer while waiting for more shards
+const MAX_OPEN_BATCHES: usize = 8;
+
+/// Decoders of peers silent this long are dropped
+const DECODER_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Erasure code applied across a batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FecScheme {
+    ReedSolomon,
+}
+
+/// Schemes this build can decode, advertised to peers
+pub fn supported() -> Vec<FecScheme> {
+    vec![FecScheme::ReedSolomon]
+}
+
+/// FEC failures
+#[derive(Error, Debug)]
+pub enum FecError {
+    #[error("Invalid FEC shard: {0}")]
+    InvalidShard(String),
+
+    #[error("Reed-Solomon: {0}")]
+    Codec(String),
+}
+
+impl FecError {
+    /// Stable identifier for programmatic handling
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidShard(_) => "fec.invalid_shard",
+            Self::Codec(_) => "fec.codec",
+        }
+    }
+}
+
+/// FEC settings of a node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FecConfig {
+    /// Add parity to UDP traffic (decoding is always available)
+    pub enabled: bool,
+    /// Probability with which each datagram should arrive or be rebuilt
+    pub target_delivery: f64,
+    /// Datagrams per batch
+    pub batch_size: usize,
+    /// Most parity shards added to a batch
+    pub max_parity: usize,
+    /// A partial batch gets its parity after this long
+    pub flush_ms: u64,
+}
+
+impl Default for FecConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target_delivery: 0.999,
+            batch_size: 8,
+            max_parity: 4,
+            flush_ms: 100,
+        }
+    }
+}
+
+impl FecConfig {
+    /// Parity shards per batch for a link losing `loss` of its datagrams;
+    /// zero when the link already meets the target
+    pub fn parity_for(&self, loss: f64) -> usize {
+        if !self.enabled {
+            return 0;
+        }
+        (0..=self.max_parity)
+            .find(|&parity| delivery_probability(self.batch_size, parity, loss) >= self.target_delivery)
+            .unwrap_or(self.max_parity)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.target_delivery > 0.0 && self.target_delivery < 1.0) {
+            return Err("fec target_delivery must be between 0 and 1".to_string());
+        }
+        if !(1..=MAX_DATA_SHARDS).contains(&self.batch_size) {
+            return Err(format!("fec batch_size must be between 1 and {}", MAX_DATA_SHARDS));
+        }
+        if !(1..=MAX_PARITY_SHARDS).contains(&self.max_parity) {
+            return Err(format!("fec max_parity must be between 1 and {}", MAX_PARITY_SHARDS));
+        }
+        Ok(())
+    }
+}
+
+/// Probability that a datagram in a batch of `data` with `parity` shards
+/// is delivered when each shard is lost independently with `loss`
+///
+/// The datagram arrives itself, or it is lost and at least `data` of the
+/// other shards arrive to rebuild it.
+pub fn delivery_probability(data: usize, parity: usize, loss: f64) -> f64 {
+    let loss = loss.clamp(0.0, 1.0);
+    let arrive = 1.0 - loss;
+    let others = data + parity - 1;
+    let mut rebuild = 0.0;
+    let mut choose = 1.0;
+    for arrived in 0..=others {
+        if arrived > 0 {
+            choose = choose * (others - arrived + 1) as f64 / arrived as f64;
+        }
+        if arrived >= data {
+            rebuild += choose * arrive.powi(arrived as i32) * loss.powi((others - arrived) as i32);
+        }
+    }
+    arrive + loss * rebuild
+}
+
+/// One shard of a batch as sent on the wire
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FecShard {
+    pub batch: u64,
+    /// Data shards come first, then parity
+    pub index: u8,
+    /// Batch size; zero on data shards, which go out before it is known
+    pub data_shards: u8,
+    pub parity_shards: u8,
+    /// The datagram itself for a data shard, parity bytes otherwise
+    pub bytes: Vec<u8>,
+}
+
+impl FecShard {
+    pub fn is_parity(&self) -> bool {
+        self.data_shards > 0 && self.index >= self.data_shards
+    }
+
+    /// Tagged wire form
+    pub fn to_frame(&self) -> Vec<u8> {
+        let mut frame = vec![FEC_FRAME_TAG];
+        frame.extend(bincode::serialize(self).unwrap_or_default());
+        frame
+    }
+
+    /// Parse a tagged frame
+    pub fn from_frame(frame: &[u8]) -> Result<Self, FecError> {
+        let body = frame
+            .strip_prefix(&[FEC_FRAME_TAG])
+            .ok_or_else(|| FecError::InvalidShard("Missing FEC tag".to_string()))?;
+        let shard: FecShard = bincode::deserialize(body).map_err(|e| FecError::InvalidShard(e.to_string()))?;
+        if shard.data_shards as usize > MAX_DATA_SHARDS || shard.parity_shards as usize > MAX_PARITY_SHARDS {
+            return Err(FecError::InvalidShard(format!(
+                "Batch of {}+{} shards is too large",
+                shard.data_shards, shard.parity_shards
+            )));
+        }
+        if shard.data_shards > 0 && shard.index >= shard.data_shards + shard.parity_shards {
+            return Err(FecError::InvalidShard(format!("Shard index {} out of range", shard.index)));
+        }
+        if shard.data_shards == 0 && shard.index as usize >= MAX_DATA_SHARDS {
+            return Err(FecError::InvalidShard(format!("Data shard index {} out of range", shard.index)));
+        }
+        Ok(shard)
+    }
+
+    /// Whether a datagram is an FEC frame rather than a plain packet
+    pub fn is_frame(datagram: &[u8]) -> bool {
+        datagram.first() == Some(&FEC_FRAME_TAG)
+    }
+}
+
+/// A datagram length-prefixed and zero-padded to `len` bytes, the form
+/// parity is computed over
+fn padded(datagram: &[u8], len: usize) -> Vec<u8> {
+    let mut shard = Vec::with_capacity(len);
+    shard.extend_from_slice(&(datagram.len() as u32).to_be_bytes());
+    shard.extend_from_slice(datagram);
+    shard.resize(len, 0);
+    shard
+}
+
+/// Datagram inside a padded shard
+fn unpadded(shard: &[u8]) -> Option<Vec<u8>> {
+    let len = u32::from_be_bytes(shard.get(..4)?.try_into().ok()?) as usize;
+    shard.get(4..4 + len).map(<[u8]>::to_vec)
+}
+
+/// Batches the datagrams sent to one peer and computes their parity
+#[derive(Debug)]
+pub struct FecEncoder {
+    batch_size: usize,
+    parity: usize,
+    next_batch: u64,
+    pending: Vec<Vec<u8>>,
+    opened: Option<Instant>,
+}
+
+impl FecEncoder {
+    pub fn new(batch_size: usize, parity: usize) -> Self {
+        Self {
+            batch_size: batch_size.clamp(1, MAX_DATA_SHARDS),
+            parity: parity.clamp(1, MAX_PARITY_SHARDS),
+            // A restarted sender must not collide with batches the peer still holds
+            next_batch: rand::random(),
+            pending: Vec::new(),
+            opened: None,
+        }
+    }
+
+    /// Change the shape of batches; the open batch is closed first
+    pub fn reconfigure(&mut self, batch_size: usize, parity: usize) -> Vec<FecShard> {
+        let closed = self.close();
+        self.batch_size = batch_size.clamp(1, MAX_DATA_SHARDS);
+        self.parity = parity.clamp(1, MAX_PARITY_SHARDS);
+        closed
+    }
+
+    pub fn shape(&self) -> (usize, usize) {
+        (self.batch_size, self.parity)
+    }
+
+    /// Add a datagram to the open batch
+    ///
+    /// Returns its data shard, followed by the batch's parity shards if
+    /// this datagram filled the batch.
+    pub fn push(&mut self, datagram: Vec<u8>) -> Vec<FecShard> {
+        let mut shards = vec![FecShard {
+            batch: self.next_batch,
+            index: self.pending.len() as u8,
+            data_shards: 0,
+            parity_shards: 0,
+            bytes: datagram.clone(),
+        }];
+        self.opened.get_or_insert_with(Instant::now);
+        self.pending.push(datagram);
+        if self.pending.len() >= self.batch_size {
+            shards.extend(self.close());
+        }
+        shards
+    }
+
+    /// Close the open batch if it has been open for `max_age`
+    pub fn flush_if_older(&mut self, max_age: Duration) -> Vec<FecShard> {
+        match self.opened {
+            Some(opened) if opened.elapsed() >= max_age => self.close(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Parity shards of the open batch, which is then closed
+    fn close(&mut self) -> Vec<FecShard> {
+        self.opened = None;
+        let data = std::mem::take(&mut self.pending);
+        if data.is_empty() {
+            return Vec::new();
+        }
+        let batch = self.next_batch;
+        self.next_batch = self.next_batch.wrapping_add(1);
+
+        let len = 4 + data.iter().map(Vec::len).max().unwrap_or(0);
+        let mut shards: Vec<Vec<u8>> = data.iter().map(|datagram| padded(datagram, len)).collect();
+        shards.resize(data.len() + self.parity, vec![0; len]);
+        let Ok(codec) = ReedSolomon::new(data.len(), self.parity) else {
+            return Vec::new();
+        };
+        if codec.encode(&mut shards).is_err() {
+            return Vec::new();
+        }
+        shards
+            .into_iter()
+            .enumerate()
+            .skip(data.len())
+            .map(|(index, bytes)| FecShard {
+                batch,
+                index: index as u8,
+                data_shards: data.len() as u8,
+                parity_shards: self.parity as u8,
+                bytes,
+            })
+            .collect()
+    }
+}
+
+/// Shards of one batch received so far
+#[derive(Debug, Default)]
+struct BatchState {
+    data: HashMap<u8, Vec<u8>>,
+    parity: HashMap<u8, Vec<u8>>,
+    /// (data shards, parity shards), known once a parity shard arrives
+    shape: Option<(u8, u8)>,
+    /// Data indices already handed to the receiver
+    delivered: HashSet<u8>,
+}
+
+impl BatchState {
+    fn is_complete(&self) -> bool {
+        self.shape.is_some_and(|(data, _)| self.delivered.len() >= data as usize)
+    }
+
+    /// Datagrams of the batch that were lost
+    fn missing(&self) -> usize {
+        self.shape.map_or(0, |(data, _)| (data as usize).saturating_sub(self.delivered.len()))
+    }
+}
+
+/// Reassembles batches from one peer and rebuilds lost datagrams
+#[derive(Debug)]
+pub struct FecDecoder {
+    batches: BTreeMap<u64, BatchState>,
+    last_used: Instant,
+}
+
+impl Default for FecDecoder {
+    fn default() -> Self {
+        Self {
+            batches: BTreeMap::new(),
+            last_used: Instant::now(),
+        }
+    }
+}
+
+/// What a received shard yielded
+#[derive(Debug, Default, PartialEq)]
+pub struct FecReceived {
+    /// Datagrams ready for the receiver, the shard's own one first
+    pub datagrams: Vec<Vec<u8>>,
+    /// How many of them were rebuilt from parity
+    pub recovered: usize,
+    /// Datagrams given up on because their batch was evicted
+    pub unrecoverable: usize,
+}
+
+impl FecDecoder {
+    /// Accept a shard
+    pub fn receive(&mut self, shard: FecShard) -> Result<FecReceived, FecError> {
+        self.last_used = Instant::now();
+        let mut received = FecReceived::default();
+        if !self.batches.contains_key(&shard.batch) {
+            while self.batches.len() >= MAX_OPEN_BATCHES {
+                let Some((_, evicted)) = self.batches.pop_first() else { break };
+                received.unrecoverable += evicted.missing();
+            }
+        }
+        let state = self.batches.entry(shard.batch).or_default();
+
+        if shard.is_parity() {
+            let shape = (shard.data_shards, shard.parity_shards);
+            if state.shape.is_some_and(|known| known != shape) {
+                return Err(FecError::InvalidShard(format!("Batch {} changed shape", shard.batch)));
+            }
+            state.shape = Some(shape);
+            state.parity.insert(shard.index, shard.bytes);
+        } else if state.delivered.insert(shard.index) {
+            received.datagrams.push(shard.bytes.clone());
+            state.data.insert(shard.index, shard.bytes);
+        }
+
+        if let Some((data, parity)) = state.shape {
+            let (data, parity) = (data as usize, parity as usize);
+            if !state.is_complete() && state.data.len() + state.parity.len() >= data {
+                let rebuilt = Self::reconstruct(state, data, parity)?;
+                received.recovered = rebuilt.len();
+                received.datagrams.extend(rebuilt);
+            }
+        }
+        if state.is_complete() {
+            // Keep the entry so late duplicates are not delivered again
+            state.data.clear();
+            state.parity.clear();
+        }
+        Ok(received)
+    }
+
+    /// Rebuild the missing data shards of a batch
+    fn reconstruct(state: &mut BatchState, data: usize, parity: usize) -> Result<Vec<Vec<u8>>, FecError> {
+        let len = state.parity.values().map(Vec::len).next().unwrap_or(0);
+        if state.data.values().any(|datagram| datagram.len() + 4 > len) {
+            return Err(FecError::InvalidShard("Data shard longer than parity".to_string()));
+        }
+        let mut shards: Vec<Option<Vec<u8>>> = (0..data + parity)
+            .map(|index| {
+                let index = index as u8;
+                match state.data.get(&index) {
+                    Some(datagram) => Some(padded(datagram, len)),
+                    None => state.parity.get(&index).filter(|bytes| bytes.len() == len).cloned(),
+                }
+            })
+            .collect();
+        let codec = ReedSolomon::new(data, parity).map_err(|e| FecError::Codec(format!("{:?}", e)))?;
+        codec
+            .reconstruct_data(&mut shards)
+            .map_err(|e| FecError::Codec(format!("{:?}", e)))?;
+
+        let mut rebuilt = Vec::new();
+        for (index, shard) in shards.into_iter().enumerate().take(data) {
+            let index = index as u8;
+            if !state.delivered.insert(index) {
+                continue;
+            }
+            let datagram = shard
+                .as_deref()
+                .and_then(unpadded)
+                .ok_or_else(|| FecError::InvalidShard("Rebuilt shard is malformed".to_string()))?;
+            rebuilt.push(datagram);
+        }
+        Ok(rebuilt)
+    }
+}
+
+/// FEC counters of a node
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FecStats {
+    /// Peers currently sent parity
+    pub links: usize,
+    pub data_shards_sent: u64,
+    pub parity_shards_sent: u64,
+    pub shards_received: u64,
+    /// Datagrams rebuilt from parity
+    pub recovered: u64,
+    /// Datagrams lost beyond what parity could rebuild
+    pub unrecoverable: u64,
+    /// Frames that failed to parse or decode
+    pub decode_failures: u64,
+}
+
+/// Encoders and decoders for every peer of a UDP socket
+#[derive(Debug, Default)]
+pub struct FecLinks {
+    encoders: HashMap<SocketAddr, FecEncoder>,
+    decoders: HashMap<SocketAddr, FecDecoder>,
+    stats: FecStats,
+}
+
+impl FecLinks {
+    /// Set the batch shape toward a peer; None sends it plain datagrams
+    ///
+    /// Returns parity frames of a batch closed by the change.
+    pub fn configure(&mut self, peer: SocketAddr, shape: Option<(usize, usize)>) -> Vec<Vec<u8>> {
+        let closed = match (shape, self.encoders.get_mut(&peer)) {
+            (Some((batch_size, parity)), Some(encoder)) if encoder.shape() != (batch_size, parity) => {
+                encoder.reconfigure(batch_size, parity)
+            }
+            (Some(_), Some(_)) => Vec::new(),
+            (Some((batch_size, parity)), None) => {
+                self.encoders.insert(peer, FecEncoder::new(batch_size, parity));
+                Vec::new()
+            }
+            (None, _) => self.encoders.remove(&peer).map(|mut encoder| encoder.close()).unwrap_or_default(),
+        };
+        self.frames(closed)
+    }
+
+    /// Stop sending parity to peers not accepted by `keep`
+    pub fn retain(&mut self, keep: impl Fn(&SocketAddr) -> bool) {
+        self.encoders.retain(|peer, _| keep(peer));
+    }
+
+    /// Whether datagrams to `peer` get parity
+    pub fn is_protected(&self, peer: &SocketAddr) -> bool {
+        self.encoders.contains_key(peer)
+    }
+
+    /// Frames to send for a datagram to `peer`: the datagram itself, or
+    /// its data shard plus any parity it completed
+    pub fn encode(&mut self, peer: SocketAddr, datagram: Vec<u8>) -> Vec<Vec<u8>> {
+        match self.encoders.get_mut(&peer) {
+            Some(encoder) => {
+                let shards = encoder.push(datagram);
+                self.frames(shards)
+            }
+            None => vec![datagram],
+        }
+    }
+
+    /// Parity frames of batches open for `max_age`, by peer
+    pub fn flush(&mut self, max_age: Duration) -> Vec<(SocketAddr, Vec<u8>)> {
+        self.decoders.retain(|_, decoder| decoder.last_used.elapsed() < DECODER_IDLE_TIMEOUT);
+        let closed: Vec<(SocketAddr, Vec<FecShard>)> = self
+            .encoders
+            .iter_mut()
+            .map(|(peer, encoder)| (*peer, encoder.flush_if_older(max_age)))
+            .collect();
+        closed
+            .into_iter()
+            .flat_map(|(peer, shards)| {
+                self.frames(shards).into_iter().map(move |frame| (peer, frame))
+            })
+            .collect()
+    }
+
+    /// Accept an FEC frame from `peer`; returns the datagrams it yielded
+    pub fn decode(&mut self, peer: SocketAddr, frame: &[u8]) -> Result<Vec<Vec<u8>>, FecError> {
+        let result = FecShard::from_frame(frame)
+            .and_then(|shard| self.decoders.entry(peer).or_default().receive(shard));
+        match result {
+            Ok(received) => {
+                self.stats.shards_received += 1;
+                self.stats.recovered += received.recovered as u64;
+                self.stats.unrecoverable += received.unrecoverable as u64;
+                Ok(received.datagrams)
+            }
+            Err(e) => {
+                self.stats.decode_failures += 1;
+                Err(e)
+            }
+        }
+    }
+
+    pub fn stats(&self) -> FecStats {
+        FecStats {
+            links: self.encoders.len(),
+            ..self.stats.clone()
+        }
+    }
+
+    /// Count and frame outgoing shards
+    fn frames(&mut self, shards: Vec<FecShard>) -> Vec<Vec<u8>> {
+        for shard in &shards {
+            if shard.is_parity() {
+                self.stats.parity_shards_sent += 1;
+            } else {
+                self.stats.data_shards_sent += 1;
+            }
+        }
+        shards.iter().map(FecShard::to_frame).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parity_meets_target_delivery() {
+        let config = FecConfig { enabled: true, ..Default::default() };
+        assert_eq!(config.parity_for(0.0), 0);
+        let parity = config.parity_for(0.05);
+        assert!(parity > 0 && parity <= config.max_parity);
+        assert!(delivery_probability(config.batch_size, parity, 0.05) >= config.target_delivery);
+        assert!(delivery_probability(config.batch_size, parity - 1, 0.05) < config.target_delivery);
+        assert_eq!(config.parity_for(0.9), config.max_parity);
+        assert_eq!(FecConfig::default().parity_for(0.5), 0);
+        assert!((delivery_probability(4, 0, 0.2) - 0.8).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_lost_datagrams_are_rebuilt() {
+        let peer: SocketAddr = "127.0.0.1:7000".parse().unwrap();
+        let mut sender = FecLinks::default();
+        let mut receiver = FecLinks::default();
+        assert_eq!(sender.encode(peer, b"plain".to_vec()), vec![b"plain".to_vec()]);
+
+        sender.configure(peer, Some((4, 2)));
+        let datagrams: Vec<Vec<u8>> = (0..4).map(|i| vec![i as u8; 10 + i * 7]).collect();
+        let frames: Vec<Vec<u8>> = datagrams.iter().flat_map(|d| sender.encode(peer, d.clone())).collect();
+        assert_eq!(frames.len(), 6);
+        assert!(frames.iter().all(|frame| FecShard::is_frame(frame)));
+
+        // Lose two data shards; the parity rebuilds both
+        let mut delivered = Vec::new();
+        for (i, frame) in frames.iter().enumerate() {
+            if i == 1 || i == 2 {
+                continue;
+            }
+            delivered.extend(receiver.decode(peer, frame).unwrap());
+        }
+        delivered.sort();
+        let mut expected = datagrams.clone();
+        expected.sort();
+        assert_eq!(delivered, expected);
+        assert_eq!(receiver.stats().recovered, 2);
+
+        // A late copy of a rebuilt datagram is not delivered twice
+        assert!(receiver.decode(peer, &frames[1]).unwrap().is_empty());
+
+        // A partial batch gets parity on flush
+        sender.encode(peer, b"tail".to_vec());
+        let flushed = sender.flush(Duration::ZERO);
+        assert_eq!(flushed.len(), 2);
+        assert_eq!(receiver.decode(peer, &flushed[0].1).unwrap(), vec![b"tail".to_vec()]);
+        assert_eq!(sender.stats().parity_shards_sent, 4);
+        assert!(receiver.decode(peer, b"\xc1junk").is_err());
+    }
+}
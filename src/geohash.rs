@@ -0,0 +1,214 @@
+//! Hyperbolic Geohash
+//!
+//! A geohash-style encoding of disk coordinates. The polar angle and the
+//! hyperbolic distance from the origin are bisected alternately, angle
+//! first, and each step appends one bit, so a hash names an annular sector
+//! of the disk and every prefix of it names an enclosing sector. Comparing
+//! prefixes is enough to scope geocast or anycast to a region, and a
+//! truncated hash is a coarse coordinate a node can publish without giving
+//! away its exact position. At full precision a hash takes 9 bytes on the
+//! wire.
+
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{normalize_angle, PoincareDiskPoint};
+
+/// Bits in a full-precision hash
+pub const MAX_PRECISION: u8 = 64;
+
+/// Hyperbolic distance from the origin covered by the radial bits; points
+/// farther out share the outermost ring
+pub const MAX_DISTANCE: f64 = 16.0;
+
+/// A region of the disk: angles in [`theta_min`, `theta_max`) and
+/// distances from the origin in [`distance_min`, `distance_max`)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HashCell {
+    pub theta_min: f64,
+    pub theta_max: f64,
+    pub distance_min: f64,
+    pub distance_max: f64,
+}
+
+impl HashCell {
+    /// Point in the middle of the cell
+    pub fn center(&self) -> PoincareDiskPoint {
+        let theta = (self.theta_min + self.theta_max) / 2.0;
+        let r = ((self.distance_min + self.distance_max) / 4.0).tanh();
+        PoincareDiskPoint { x: r * theta.cos(), y: r * theta.sin() }
+    }
+}
+
+/// Prefix-comparable encoding of a disk coordinate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GeoHash {
+    /// Hash bits, most significant first; bits past `precision` are zero
+    bits: u64,
+    precision: u8,
+}
+
+fn prefix_mask(precision: u8) -> u64 {
+    match precision {
+        0 => 0,
+        p => u64::MAX << (MAX_PRECISION - p),
+    }
+}
+
+impl GeoHash {
+    /// Hash of `point` to `precision` bits, capped at `MAX_PRECISION`
+    pub fn encode(point: &PoincareDiskPoint, precision: u8) -> Self {
+        let precision = precision.min(MAX_PRECISION);
+        let r = point.euclidean_norm().min(1.0 - f64::EPSILON);
+        let distance = (2.0 * r.atanh()).min(MAX_DISTANCE);
+        let theta = normalize_angle(point.angle());
+
+        let (mut theta_range, mut distance_range) = ((0.0, 2.0 * PI), (0.0, MAX_DISTANCE));
+        let mut bits = 0u64;
+        for i in 0..precision {
+            let (range, value) = if i % 2 == 0 {
+                (&mut theta_range, theta)
+            } else {
+                (&mut distance_range, distance)
+            };
+            let mid = (range.0 + range.1) / 2.0;
+            if value >= mid {
+                bits |= 1 << (MAX_PRECISION - 1 - i);
+                range.0 = mid;
+            } else {
+                range.1 = mid;
+            }
+        }
+        Self { bits, precision }
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// Region of the disk this hash names
+    pub fn cell(&self) -> HashCell {
+        let (mut theta_range, mut distance_range) = ((0.0, 2.0 * PI), (0.0, MAX_DISTANCE));
+        for i in 0..self.precision {
+            let range = if i % 2 == 0 { &mut theta_range } else { &mut distance_range };
+            let mid = (range.0 + range.1) / 2.0;
+            if self.bits & (1 << (MAX_PRECISION - 1 - i)) != 0 {
+                range.0 = mid;
+            } else {
+                range.1 = mid;
+            }
+        }
+        HashCell {
+            theta_min: theta_range.0,
+            theta_max: theta_range.1,
+            distance_min: distance_range.0,
+            distance_max: distance_range.1,
+        }
+    }
+
+    /// Center of the cell, the best estimate of the encoded point
+    pub fn decode(&self) -> PoincareDiskPoint {
+        self.cell().center()
+    }
+
+    /// The enclosing hash with `precision` bits; longer precisions leave it unchanged
+    pub fn truncate(&self, precision: u8) -> Self {
+        let precision = precision.min(self.precision);
+        Self { bits: self.bits & prefix_mask(precision), precision }
+    }
+
+    /// Whether `other` lies in this hash's region
+    pub fn contains(&self, other: &GeoHash) -> bool {
+        other.precision >= self.precision && other.truncate(self.precision) == *self
+    }
+
+    /// Whether `point` lies in this hash's region
+    pub fn contains_point(&self, point: &PoincareDiskPoint) -> bool {
+        Self::encode(point, self.precision) == *self
+    }
+
+    /// Length of the prefix two hashes share, i.e. the precision of the
+    /// smallest region containing both
+    pub fn common_prefix(&self, other: &GeoHash) -> u8 {
+        let shared = (self.bits ^ other.bits).leading_zeros().min(MAX_PRECISION as u32) as u8;
+        shared.min(self.precision).min(other.precision)
+    }
+
+    /// Wire form: the precision, then only the bytes that hold hash bits
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let len = self.precision.div_ceil(8) as usize;
+        let mut bytes = Vec::with_capacity(1 + len);
+        bytes.push(self.precision);
+        bytes.extend_from_slice(&self.bits.to_be_bytes()[..len]);
+        bytes
+    }
+
+    /// Parse the wire form; None if it is truncated or malformed
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (&precision, rest) = bytes.split_first()?;
+        if precision > MAX_PRECISION || rest.len() != precision.div_ceil(8) as usize {
+            return None;
+        }
+        let mut buffer = [0u8; 8];
+        buffer[..rest.len()].copy_from_slice(rest);
+        let bits = u64::from_be_bytes(buffer);
+        (bits & !prefix_mask(precision) == 0).then_some(Self { bits, precision })
+    }
+}
+
+impl std::fmt::Display for GeoHash {
+    /// The hash bits as a binary string, e.g. `0110`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for i in 0..self.precision {
+            let bit = self.bits & (1 << (MAX_PRECISION - 1 - i)) != 0;
+            write!(f, "{}", u8::from(bit))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip_and_wire_size() {
+        for (r, theta) in [(0.0, 0.0), (0.3, 1.0), (0.9, -2.5), (0.999, 3.0)] {
+            let point = PoincareDiskPoint::from_polar(r, theta).unwrap();
+            let hash = GeoHash::encode(&point, MAX_PRECISION);
+            assert!(hash.decode().hyperbolic_distance(&point) < 1e-6, "{} decoded to {}", point, hash.decode());
+            assert!(hash.contains_point(&point));
+
+            let bytes = hash.to_bytes();
+            assert_eq!(bytes.len(), 9);
+            assert_eq!(GeoHash::from_bytes(&bytes), Some(hash));
+            assert_eq!(GeoHash::from_bytes(&hash.truncate(20).to_bytes()), Some(hash.truncate(20)));
+        }
+        assert_eq!(GeoHash::from_bytes(&[8]), None);
+        assert_eq!(GeoHash::from_bytes(&[4, 0xff]), None);
+        assert_eq!(GeoHash::from_bytes(&[65; 10]), None);
+    }
+
+    #[test]
+    fn test_prefixes_name_enclosing_regions() {
+        let a = GeoHash::encode(&PoincareDiskPoint::from_polar(0.8, 0.5).unwrap(), 32);
+        let b = GeoHash::encode(&PoincareDiskPoint::from_polar(0.8, 0.51).unwrap(), 32);
+        let far = GeoHash::encode(&PoincareDiskPoint::from_polar(0.8, 3.5).unwrap(), 32);
+
+        // Opposite halves of the disk differ in the first bit
+        assert_eq!(a.common_prefix(&far), 0);
+        let shared = a.common_prefix(&b);
+        assert!(shared > 8 && shared < 32);
+        let region = a.truncate(shared);
+        assert!(region.contains(&a) && region.contains(&b) && !region.contains(&far));
+        assert!(!a.contains(&region));
+
+        // A coarse hash still locates the point within its cell
+        let coarse = a.truncate(12);
+        let cell = coarse.cell();
+        assert!(cell.theta_min <= 0.5 && 0.5 < cell.theta_max);
+        assert!(coarse.contains_point(&coarse.decode()));
+        assert_eq!(coarse.to_string().len(), 12);
+    }
+}
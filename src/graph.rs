@@ -0,0 +1,422 @@
+//! Compact Graph Representation
+//!
+//! Index-based CSR (compressed sparse row) adjacency for preprocessing large
+//! graphs. Node IDs are interned once and traversals work on `u32` indices
+//! with a bit-set visited array. `BfsScratch` buffers are reused across
+//! queries and only the entries touched by the previous traversal are reset,
+//! so running many BFS queries over a 1M-edge graph does not allocate.
+
+use crate::coordinates::NodeId;
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+/// Sentinel for "no node" in index arrays
+const NONE: u32 = u32::MAX;
+
+/// Fixed-size bit set
+#[derive(Debug, Clone)]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    /// Create an empty bit set for indices `0..len`
+    pub fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    /// Insert an index; returns true if it was not already present
+    pub fn insert(&mut self, i: u32) -> bool {
+        let (word, bit) = ((i / 64) as usize, 1u64 << (i % 64));
+        let fresh = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        fresh
+    }
+
+    /// Remove an index
+    pub fn remove(&mut self, i: u32) {
+        self.words[(i / 64) as usize] &= !(1u64 << (i % 64));
+    }
+
+    /// Check whether an index is present
+    pub fn contains(&self, i: u32) -> bool {
+        self.words[(i / 64) as usize] & (1u64 << (i % 64)) != 0
+    }
+}
+
+/// Reusable BFS state
+///
+/// After a traversal it holds the BFS tree rooted at the source: distance,
+/// parent and first hop from the source for every visited node.
+#[derive(Debug, Clone)]
+pub struct BfsScratch {
+    visited: BitSet,
+    dist: Vec<u32>,
+    parent: Vec<u32>,
+    first_hop: Vec<u32>,
+    order: Vec<u32>,
+}
+
+impl BfsScratch {
+    /// Create scratch buffers for a graph with `len` nodes
+    pub fn new(len: usize) -> Self {
+        Self {
+            visited: BitSet::new(len),
+            dist: vec![0; len],
+            parent: vec![NONE; len],
+            first_hop: vec![NONE; len],
+            order: Vec::with_capacity(len),
+        }
+    }
+
+    /// Visited nodes in BFS order (source first)
+    pub fn order(&self) -> &[u32] {
+        &self.order
+    }
+
+    /// Distance from the source, if visited
+    pub fn distance(&self, v: u32) -> Option<u32> {
+        self.visited.contains(v).then(|| self.dist[v as usize])
+    }
+
+    /// BFS tree parent; None for the source and unvisited nodes
+    pub fn parent(&self, v: u32) -> Option<u32> {
+        self.visited.contains(v).then(|| self.parent[v as usize]).filter(|&p| p != NONE)
+    }
+
+    /// First hop from the source toward `v`; None for the source and unvisited nodes
+    pub fn first_hop(&self, v: u32) -> Option<u32> {
+        self.visited.contains(v).then(|| self.first_hop[v as usize]).filter(|&h| h != NONE)
+    }
+
+    fn reset(&mut self) {
+        for &v in &self.order {
+            self.visited.remove(v);
+        }
+        self.order.clear();
+    }
+}
+
+/// Betweenness centrality of a `CsrGraph`
+#[derive(Debug, Clone)]
+pub struct Betweenness {
+    /// Node betweenness by index
+    pub nodes: Vec<f64>,
+    /// Undirected edge betweenness as `((u, v), value)` with `u < v`, sorted by edge
+    pub edges: Vec<((u32, u32), f64)>,
+}
+
+/// Per-thread buffers for betweenness computation
+struct BrandesScratch {
+    bfs: BfsScratch,
+    sigma: Vec<f64>,
+    delta: Vec<f64>,
+}
+
+impl BrandesScratch {
+    fn new(len: usize) -> Self {
+        Self {
+            bfs: BfsScratch::new(len),
+            sigma: vec![0.0; len],
+            delta: vec![0.0; len],
+        }
+    }
+}
+
+/// Graph in compressed sparse row form
+#[derive(Debug, Clone)]
+pub struct CsrGraph {
+    ids: Vec<NodeId>,
+    index: HashMap<NodeId, u32>,
+    offsets: Vec<usize>,
+    targets: Vec<u32>,
+}
+
+impl CsrGraph {
+    /// Build from an adjacency list
+    ///
+    /// Neighbor order is preserved, so traversals visit nodes in the same order
+    /// as a BFS over the adjacency map. Nodes that only appear as neighbors are
+    /// included with no outgoing edges.
+    pub fn from_adjacency(adjacency: &HashMap<NodeId, Vec<NodeId>>) -> Self {
+        let mut ids: Vec<NodeId> = adjacency.keys().cloned().collect();
+        ids.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut index: HashMap<NodeId, u32> = ids.iter().enumerate().map(|(i, id)| (id.clone(), i as u32)).collect();
+        for neighbors in adjacency.values() {
+            for neighbor in neighbors {
+                if !index.contains_key(neighbor) {
+                    index.insert(neighbor.clone(), ids.len() as u32);
+                    ids.push(neighbor.clone());
+                }
+            }
+        }
+
+        let mut offsets = Vec::with_capacity(ids.len() + 1);
+        let mut targets = Vec::with_capacity(adjacency.values().map(|n| n.len()).sum());
+        offsets.push(0);
+        for id in &ids {
+            if let Some(neighbors) = adjacency.get(id) {
+                targets.extend(neighbors.iter().map(|n| index[n]));
+            }
+            offsets.push(targets.len());
+        }
+
+        Self {
+            ids,
+            index,
+            offsets,
+            targets,
+        }
+    }
+
+    /// Number of nodes
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Whether the graph has no nodes
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Number of directed edges
+    pub fn edge_count(&self) -> usize {
+        self.targets.len()
+    }
+
+    /// Node ID at an index
+    pub fn id(&self, i: u32) -> &NodeId {
+        &self.ids[i as usize]
+    }
+
+    /// Index of a node ID
+    pub fn index_of(&self, id: &NodeId) -> Option<u32> {
+        self.index.get(id).copied()
+    }
+
+    /// Neighbors of a node by index
+    pub fn neighbors(&self, i: u32) -> &[u32] {
+        &self.targets[self.offsets[i as usize]..self.offsets[i as usize + 1]]
+    }
+
+    /// Full BFS from `source`
+    pub fn bfs(&self, source: u32, scratch: &mut BfsScratch) {
+        self.bfs_bounded(source, u32::MAX, scratch);
+    }
+
+    /// BFS from `source` visiting only nodes within `max_depth` hops
+    pub fn bfs_bounded(&self, source: u32, max_depth: u32, scratch: &mut BfsScratch) {
+        self.traverse(source, max_depth, None, scratch);
+    }
+
+    /// Hop distance between two nodes, stopping as soon as `target` is reached
+    pub fn distance(&self, source: u32, target: u32, scratch: &mut BfsScratch) -> Option<u32> {
+        self.traverse(source, u32::MAX, Some(target), scratch);
+        scratch.distance(target)
+    }
+
+    /// Connected component label of every node
+    pub fn components(&self) -> Vec<u32> {
+        let mut labels = vec![NONE; self.len()];
+        let mut scratch = BfsScratch::new(self.len());
+        let mut next = 0;
+        for v in 0..self.len() as u32 {
+            if labels[v as usize] != NONE {
+                continue;
+            }
+            self.bfs(v, &mut scratch);
+            for &u in scratch.order() {
+                labels[u as usize] = next;
+            }
+            next += 1;
+        }
+        labels
+    }
+
+    /// Unnormalized betweenness centrality (Brandes) from the given BFS sources
+    ///
+    /// Passing every node gives exact values; a sample of sources gives an
+    /// estimate that preserves the ranking in expectation.
+    pub fn betweenness(&self, sources: &[u32]) -> Betweenness {
+        let n = self.len();
+        let empty = || (vec![0.0; n], vec![0.0; self.targets.len()]);
+
+        let (node, slots) = sources
+            .par_iter()
+            .fold(
+                || (empty(), BrandesScratch::new(n)),
+                |((mut node, mut slots), mut scratch), &s| {
+                    self.accumulate_dependencies(s, &mut scratch, &mut node, &mut slots);
+                    ((node, slots), scratch)
+                },
+            )
+            .map(|(acc, _)| acc)
+            .reduce(empty, |(mut n1, mut e1), (n2, e2)| {
+                n1.iter_mut().zip(n2).for_each(|(a, b)| *a += b);
+                e1.iter_mut().zip(e2).for_each(|(a, b)| *a += b);
+                (n1, e1)
+            });
+
+        let mut edges: HashMap<(u32, u32), f64> = HashMap::new();
+        for u in 0..n as u32 {
+            let start = self.offsets[u as usize];
+            for (k, &v) in self.neighbors(u).iter().enumerate() {
+                if u != v {
+                    *edges.entry((u.min(v), u.max(v))).or_insert(0.0) += slots[start + k];
+                }
+            }
+        }
+        let mut edges: Vec<((u32, u32), f64)> = edges.into_iter().collect();
+        edges.sort_unstable_by_key(|&(e, _)| e);
+
+        Betweenness { nodes: node, edges }
+    }
+
+    /// Single-source dependency accumulation for `betweenness`
+    fn accumulate_dependencies(&self, source: u32, scratch: &mut BrandesScratch, node: &mut [f64], slots: &mut [f64]) {
+        let BrandesScratch { bfs, sigma, delta } = scratch;
+        self.bfs(source, bfs);
+        for &v in bfs.order() {
+            sigma[v as usize] = 0.0;
+            delta[v as usize] = 0.0;
+        }
+        sigma[source as usize] = 1.0;
+        for &v in bfs.order() {
+            let dv = bfs.dist[v as usize];
+            for &w in self.neighbors(v) {
+                if bfs.visited.contains(w) && bfs.dist[w as usize] == dv + 1 {
+                    sigma[w as usize] += sigma[v as usize];
+                }
+            }
+        }
+
+        for &w in bfs.order().iter().rev() {
+            let dw = bfs.dist[w as usize];
+            let start = self.offsets[w as usize];
+            for (k, &v) in self.neighbors(w).iter().enumerate() {
+                if dw > 0 && bfs.visited.contains(v) && bfs.dist[v as usize] + 1 == dw {
+                    let c = sigma[v as usize] / sigma[w as usize] * (1.0 + delta[w as usize]);
+                    delta[v as usize] += c;
+                    slots[start + k] += c;
+                }
+            }
+            if w != source {
+                node[w as usize] += delta[w as usize];
+            }
+        }
+    }
+
+    fn traverse(&self, source: u32, max_depth: u32, target: Option<u32>, scratch: &mut BfsScratch) {
+        scratch.reset();
+        scratch.visited.insert(source);
+        scratch.dist[source as usize] = 0;
+        scratch.parent[source as usize] = NONE;
+        scratch.first_hop[source as usize] = NONE;
+        scratch.order.push(source);
+
+        let mut head = 0;
+        while head < scratch.order.len() {
+            let current = scratch.order[head];
+            head += 1;
+            if Some(current) == target {
+                return;
+            }
+
+            let depth = scratch.dist[current as usize];
+            if depth >= max_depth {
+                continue;
+            }
+            for &neighbor in self.neighbors(current) {
+                if scratch.visited.insert(neighbor) {
+                    let n = neighbor as usize;
+                    scratch.dist[n] = depth + 1;
+                    scratch.parent[n] = current;
+                    scratch.first_hop[n] = if current == source {
+                        neighbor
+                    } else {
+                        scratch.first_hop[current as usize]
+                    };
+                    scratch.order.push(neighbor);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cycle_graph(n: usize) -> HashMap<NodeId, Vec<NodeId>> {
+        let id = |i: usize| NodeId::new(format!("{}", i));
+        (0..n)
+            .map(|i| (id(i), vec![id((i + 1) % n), id((i + n - 1) % n)]))
+            .collect()
+    }
+
+    #[test]
+    fn test_bfs_distances_and_first_hops() {
+        let graph = CsrGraph::from_adjacency(&cycle_graph(8));
+        let mut scratch = BfsScratch::new(graph.len());
+        let idx = |s: &str| graph.index_of(&NodeId::new(s)).unwrap();
+
+        graph.bfs(idx("0"), &mut scratch);
+        assert_eq!(scratch.order().len(), 8);
+        assert_eq!(scratch.distance(idx("4")), Some(4));
+        assert_eq!(scratch.distance(idx("6")), Some(2));
+        assert_eq!(scratch.first_hop(idx("2")), Some(idx("1")));
+        assert_eq!(scratch.first_hop(idx("6")), Some(idx("7")));
+        assert_eq!(scratch.parent(idx("0")), None);
+
+        // Scratch reuse resets previously visited state
+        graph.bfs_bounded(idx("4"), 1, &mut scratch);
+        assert_eq!(scratch.order().len(), 3);
+        assert_eq!(scratch.distance(idx("0")), None);
+
+        assert_eq!(graph.distance(idx("1"), idx("5"), &mut scratch), Some(4));
+    }
+
+    #[test]
+    fn test_dangling_neighbors_and_bitset() {
+        let mut adj = HashMap::new();
+        adj.insert(NodeId::new("a"), vec![NodeId::new("b")]);
+        let graph = CsrGraph::from_adjacency(&adj);
+        assert_eq!(graph.len(), 2);
+        assert_eq!(graph.edge_count(), 1);
+        assert!(graph.neighbors(graph.index_of(&NodeId::new("b")).unwrap()).is_empty());
+
+        let mut bits = BitSet::new(130);
+        assert!(bits.insert(129));
+        assert!(!bits.insert(129));
+        assert!(bits.contains(129) && !bits.contains(128));
+        bits.remove(129);
+        assert!(!bits.contains(129));
+    }
+
+    #[test]
+    fn test_betweenness_and_components() {
+        // Path 0-1-2-3 plus isolated edge 4-5
+        let id = |i: usize| NodeId::new(format!("{}", i));
+        let mut adj = HashMap::new();
+        for (a, b) in [(0, 1), (1, 2), (2, 3), (4, 5)] {
+            adj.entry(id(a)).or_insert_with(Vec::new).push(id(b));
+            adj.entry(id(b)).or_insert_with(Vec::new).push(id(a));
+        }
+        let graph = CsrGraph::from_adjacency(&adj);
+        let idx = |i: usize| graph.index_of(&id(i)).unwrap();
+
+        let all: Vec<u32> = (0..graph.len() as u32).collect();
+        let b = graph.betweenness(&all);
+        // Ordered pairs through node 1: (0,2), (0,3) and reverses
+        assert_eq!(b.nodes[idx(1) as usize], 4.0);
+        assert_eq!(b.nodes[idx(0) as usize], 0.0);
+        let middle = b.edges.iter().find(|(e, _)| *e == (idx(1).min(idx(2)), idx(1).max(idx(2)))).unwrap();
+        assert_eq!(middle.1, 8.0);
+
+        let labels = graph.components();
+        assert_eq!(labels[idx(0) as usize], labels[idx(3) as usize]);
+        assert_ne!(labels[idx(0) as usize], labels[idx(4) as usize]);
+    }
+}
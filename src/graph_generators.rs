@@ -0,0 +1,493 @@
+//! Hyperbolic Random Graph Generators
+//!
+//! Graphs with ground-truth hyperbolic geometry, for evaluating embeddings
+//! against the coordinates that produced the graph:
+//!
+//! - **HRG** (Krioukov et al. 2010): `n` points are placed in a disk of
+//!   radius `R` with radial density `α sinh(αr)` and uniform angles. A pair
+//!   at distance `x` is linked with probability `1 / (1 + e^((x - R) / 2T))`,
+//!   or iff `x <= R` at temperature 0.
+//! - **PSO** (Papadopoulos et al. 2012): nodes arrive one at a time at
+//!   radius `2 ln t`, older nodes drift outward (popularity fading), and each
+//!   newcomer links to `m` existing nodes: the `m` hyperbolically closest at
+//!   temperature 0, otherwise `m` sampled by connection probability.
+//!
+//! Both produce power-law degrees with exponent `gamma` and clustering that
+//! falls as temperature rises. Coordinates are native polar: `radii[i]` is
+//! the hyperbolic distance from the origin. HRG link generation compares
+//! every pair, so it suits graphs up to a few tens of thousands of nodes.
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::coordinates::NodeId;
+use crate::PoincareDiskPoint;
+
+/// Invalid generator settings
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum GraphGeneratorError {
+    #[error("A graph needs at least 2 nodes, got {0}")]
+    TooFewNodes(usize),
+    #[error("avg_degree {0} must be in (0, nodes)")]
+    InvalidAvgDegree(f64),
+    #[error("m must be positive")]
+    NoLinks,
+    #[error("gamma {0} must be greater than 2")]
+    InvalidGamma(f64),
+    #[error("temperature {0} must be in [0, 1)")]
+    InvalidTemperature(f64),
+}
+
+impl GraphGeneratorError {
+    /// Stable identifier for programmatic handling
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::TooFewNodes(_) => "graph_generator.too_few_nodes",
+            Self::InvalidAvgDegree(_) => "graph_generator.invalid_avg_degree",
+            Self::NoLinks => "graph_generator.no_links",
+            Self::InvalidGamma(_) => "graph_generator.invalid_gamma",
+            Self::InvalidTemperature(_) => "graph_generator.invalid_temperature",
+        }
+    }
+}
+
+/// Settings for a temperature-controlled hyperbolic random graph
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HrgConfig {
+    pub nodes: usize,
+    /// Target mean degree; sets the disk radius
+    pub avg_degree: f64,
+    /// Power-law exponent of the degree distribution (> 2)
+    pub gamma: f64,
+    /// 0 links exactly the pairs within `R`; up to 1 adds noise
+    pub temperature: f64,
+    pub seed: u64,
+}
+
+impl Default for HrgConfig {
+    fn default() -> Self {
+        Self {
+            nodes: 1000,
+            avg_degree: 10.0,
+            gamma: 2.5,
+            temperature: 0.0,
+            seed: 42,
+        }
+    }
+}
+
+impl HrgConfig {
+    pub fn validate(&self) -> Result<(), GraphGeneratorError> {
+        if self.nodes < 2 {
+            return Err(GraphGeneratorError::TooFewNodes(self.nodes));
+        }
+        if !(self.avg_degree > 0.0 && self.avg_degree < self.nodes as f64) {
+            return Err(GraphGeneratorError::InvalidAvgDegree(self.avg_degree));
+        }
+        validate_shape(self.gamma, self.temperature)
+    }
+
+    /// Disk radius giving roughly `avg_degree` in the large-graph limit
+    pub fn radius(&self) -> f64 {
+        let alpha = (self.gamma - 1.0) / 2.0;
+        let xi = alpha / (alpha - 0.5);
+        2.0 * (2.0 * self.nodes as f64 * xi * xi * temperature_factor(self.temperature) / (PI * self.avg_degree)).ln()
+    }
+}
+
+/// Settings for a popularity-similarity optimization graph
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PsoConfig {
+    pub nodes: usize,
+    /// Links each newcomer makes; the mean degree is about `2m`
+    pub m: usize,
+    /// Power-law exponent of the degree distribution (> 2)
+    pub gamma: f64,
+    /// 0 links to the closest nodes; up to 1 adds noise
+    pub temperature: f64,
+    pub seed: u64,
+}
+
+impl Default for PsoConfig {
+    fn default() -> Self {
+        Self {
+            nodes: 1000,
+            m: 4,
+            gamma: 2.5,
+            temperature: 0.0,
+            seed: 42,
+        }
+    }
+}
+
+impl PsoConfig {
+    pub fn validate(&self) -> Result<(), GraphGeneratorError> {
+        if self.nodes < 2 {
+            return Err(GraphGeneratorError::TooFewNodes(self.nodes));
+        }
+        if self.m == 0 {
+            return Err(GraphGeneratorError::NoLinks);
+        }
+        validate_shape(self.gamma, self.temperature)
+    }
+}
+
+fn validate_shape(gamma: f64, temperature: f64) -> Result<(), GraphGeneratorError> {
+    if gamma.is_nan() || gamma <= 2.0 {
+        return Err(GraphGeneratorError::InvalidGamma(gamma));
+    }
+    if !(0.0..1.0).contains(&temperature) {
+        return Err(GraphGeneratorError::InvalidTemperature(temperature));
+    }
+    Ok(())
+}
+
+/// `πT / sin(πT)`, which tends to 1 as T tends to 0
+fn temperature_factor(temperature: f64) -> f64 {
+    if temperature < 1e-9 {
+        1.0
+    } else {
+        PI * temperature / (PI * temperature).sin()
+    }
+}
+
+/// Link probability at distance `x` for threshold `radius`
+fn link_probability(x: f64, radius: f64, temperature: f64) -> f64 {
+    if temperature < 1e-9 {
+        if x <= radius { 1.0 } else { 0.0 }
+    } else {
+        1.0 / (1.0 + ((x - radius) / (2.0 * temperature)).exp())
+    }
+}
+
+/// Hyperbolic distance between native polar coordinates
+pub fn native_distance(r1: f64, theta1: f64, r2: f64, theta2: f64) -> f64 {
+    let delta = PI - (PI - (theta1 - theta2).abs()).abs();
+    let arg = r1.cosh() * r2.cosh() - r1.sinh() * r2.sinh() * delta.cos();
+    arg.max(1.0).acosh()
+}
+
+/// Generated graph with the coordinates that produced it
+#[derive(Debug, Clone)]
+pub struct HyperbolicGraph {
+    pub nodes: Vec<NodeId>,
+    pub adjacency_idx: Vec<Vec<usize>>,
+    /// Hyperbolic distance of each node from the origin
+    pub radii: Vec<f64>,
+    /// Angle of each node in [0, 2π)
+    pub angles: Vec<f64>,
+}
+
+impl HyperbolicGraph {
+    fn empty(prefix: &str, n: usize) -> Self {
+        Self {
+            nodes: (0..n).map(|i| NodeId::new(format!("{}_{}", prefix, i))).collect(),
+            adjacency_idx: vec![Vec::new(); n],
+            radii: Vec::with_capacity(n),
+            angles: Vec::with_capacity(n),
+        }
+    }
+
+    fn link(&mut self, a: usize, b: usize) {
+        self.adjacency_idx[a].push(b);
+        self.adjacency_idx[b].push(a);
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.adjacency_idx.iter().map(Vec::len).sum::<usize>() / 2
+    }
+
+    pub fn avg_degree(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        2.0 * self.edge_count() as f64 / self.len() as f64
+    }
+
+    /// Adjacency keyed by node ID
+    pub fn adjacency(&self) -> HashMap<NodeId, Vec<NodeId>> {
+        self.nodes
+            .iter()
+            .zip(&self.adjacency_idx)
+            .map(|(id, neighbors)| (id.clone(), neighbors.iter().map(|&j| self.nodes[j].clone()).collect()))
+            .collect()
+    }
+
+    /// True hyperbolic distance between two nodes
+    pub fn distance(&self, a: usize, b: usize) -> f64 {
+        native_distance(self.radii[a], self.angles[a], self.radii[b], self.angles[b])
+    }
+
+    /// True position of a node in the Poincaré disk
+    ///
+    /// Radii beyond about 36 round to the boundary in f64 and yield None.
+    pub fn disk_point(&self, i: usize) -> Option<PoincareDiskPoint> {
+        PoincareDiskPoint::from_polar((self.radii[i] / 2.0).tanh(), self.angles[i])
+    }
+
+    /// True positions keyed by node ID
+    pub fn true_coordinates(&self) -> HashMap<NodeId, PoincareDiskPoint> {
+        (0..self.len()).filter_map(|i| Some((self.nodes[i].clone(), self.disk_point(i)?))).collect()
+    }
+
+    /// Compare inferred coordinates against the true geometry
+    ///
+    /// Embeddings are only defined up to isometry and scale, so this
+    /// correlates pairwise distances rather than positions. Up to
+    /// `max_pairs` random pairs of nodes with inferred coordinates are
+    /// sampled. Returns None with fewer than two usable pairs.
+    pub fn compare(
+        &self,
+        inferred: &HashMap<NodeId, PoincareDiskPoint>,
+        max_pairs: usize,
+        seed: u64,
+    ) -> Option<CoordinateComparison> {
+        let known: Vec<usize> = (0..self.len()).filter(|&i| inferred.contains_key(&self.nodes[i])).collect();
+        let all_pairs = known.len() * known.len().saturating_sub(1) / 2;
+        let mut pairs = Vec::new();
+        if all_pairs <= max_pairs {
+            for (k, &a) in known.iter().enumerate() {
+                pairs.extend(known[k + 1..].iter().map(|&b| (a, b)));
+            }
+        } else {
+            let mut rng = StdRng::seed_from_u64(seed);
+            while pairs.len() < max_pairs {
+                let a = known[rng.gen_range(0..known.len())];
+                let b = known[rng.gen_range(0..known.len())];
+                if a != b {
+                    pairs.push((a, b));
+                }
+            }
+        }
+
+        let (truth, estimate): (Vec<f64>, Vec<f64>) = pairs
+            .iter()
+            .map(|&(a, b)| {
+                let inferred_distance = inferred[&self.nodes[a]].hyperbolic_distance(&inferred[&self.nodes[b]]);
+                (self.distance(a, b), inferred_distance)
+            })
+            .filter(|(t, e)| t.is_finite() && e.is_finite())
+            .unzip();
+        if truth.len() < 2 {
+            return None;
+        }
+        Some(CoordinateComparison {
+            pairs: truth.len(),
+            distance_correlation: pearson(&truth, &estimate),
+            rank_correlation: pearson(&ranks(&truth), &ranks(&estimate)),
+        })
+    }
+}
+
+/// Agreement between inferred and true pairwise distances
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CoordinateComparison {
+    pub pairs: usize,
+    /// Pearson correlation of the distances
+    pub distance_correlation: f64,
+    /// Spearman correlation of the distances
+    pub rank_correlation: f64,
+}
+
+fn pearson(xs: &[f64], ys: &[f64]) -> f64 {
+    let n = xs.len() as f64;
+    let (mx, my) = (xs.iter().sum::<f64>() / n, ys.iter().sum::<f64>() / n);
+    let (mut cov, mut vx, mut vy) = (0.0, 0.0, 0.0);
+    for (x, y) in xs.iter().zip(ys) {
+        cov += (x - mx) * (y - my);
+        vx += (x - mx) * (x - mx);
+        vy += (y - my) * (y - my);
+    }
+    if vx == 0.0 || vy == 0.0 {
+        return 0.0;
+    }
+    cov / (vx * vy).sqrt()
+}
+
+/// Ranks with ties averaged
+fn ranks(values: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        let rank = (start + end - 1) as f64 / 2.0;
+        for &i in &order[start..end] {
+            ranks[i] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+/// Generate a hyperbolic random graph
+pub fn hyperbolic_random_graph(config: &HrgConfig) -> Result<HyperbolicGraph, GraphGeneratorError> {
+    config.validate()?;
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let n = config.nodes;
+    let alpha = (config.gamma - 1.0) / 2.0;
+    let radius = config.radius();
+
+    let mut graph = HyperbolicGraph::empty("hrg", n);
+    // Inverse CDF of the radial density α sinh(αr) / (cosh(αR) - 1)
+    let spread = (alpha * radius).cosh() - 1.0;
+    for _ in 0..n {
+        let u: f64 = rng.gen();
+        graph.radii.push((1.0 + spread * u).acosh() / alpha);
+        graph.angles.push(rng.gen_range(0.0..2.0 * PI));
+    }
+
+    for a in 0..n {
+        for b in a + 1..n {
+            let p = link_probability(graph.distance(a, b), radius, config.temperature);
+            if p >= 1.0 || (p > 0.0 && rng.gen::<f64>() < p) {
+                graph.link(a, b);
+            }
+        }
+    }
+    Ok(graph)
+}
+
+/// Generate a popularity-similarity optimization graph
+pub fn popularity_similarity_graph(config: &PsoConfig) -> Result<HyperbolicGraph, GraphGeneratorError> {
+    config.validate()?;
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let n = config.nodes;
+    let beta = 1.0 / (config.gamma - 1.0);
+    let temperature = config.temperature;
+
+    let mut graph = HyperbolicGraph::empty("pso", n);
+    // Birth radius of each node; current radii follow from it and the time
+    let mut birth = Vec::with_capacity(n);
+    for t in 1..=n {
+        let r_t = 2.0 * (t as f64).ln();
+        let theta_t = rng.gen_range(0.0..2.0 * PI);
+        let i = t - 1;
+
+        // Popularity fading: older nodes drift toward the newcomer's radius
+        let current = |s: usize, birth: &[f64]| beta * birth[s] + (1.0 - beta) * r_t;
+        let mut candidates: Vec<(usize, f64)> =
+            (0..i).map(|s| (s, native_distance(current(s, &birth), graph.angles[s], r_t, theta_t))).collect();
+
+        let links = config.m.min(i);
+        if temperature < 1e-9 || links == i {
+            candidates.sort_by(|x, y| x.1.total_cmp(&y.1));
+            candidates.truncate(links);
+        } else {
+            // Link radius that gives `m` expected links to the newcomer
+            let ln_t = (t as f64).ln();
+            let fading = if beta < 1.0 { (1.0 - (-(1.0 - beta) * ln_t).exp()) / (1.0 - beta) } else { ln_t };
+            let link_radius = r_t - 2.0 * (2.0 * temperature * fading / ((PI * temperature).sin() * config.m as f64)).ln();
+            candidates = sample_weighted(
+                candidates.into_iter().map(|(s, x)| (s, link_probability(x, link_radius, temperature))).collect(),
+                links,
+                &mut rng,
+            );
+        }
+        birth.push(r_t);
+        graph.angles.push(theta_t);
+        for (s, _) in candidates {
+            graph.link(i, s);
+        }
+    }
+
+    let r_n = 2.0 * (n as f64).ln();
+    graph.radii = birth.iter().map(|r| beta * r + (1.0 - beta) * r_n).collect();
+    Ok(graph)
+}
+
+/// Draw `k` entries without replacement, proportionally to their weights
+///
+/// Uses exponential keys (Efraimidis-Spirakis); zero weights come last.
+fn sample_weighted(weighted: Vec<(usize, f64)>, k: usize, rng: &mut StdRng) -> Vec<(usize, f64)> {
+    let mut keyed: Vec<(f64, (usize, f64))> = weighted
+        .into_iter()
+        .map(|(s, w)| {
+            let u: f64 = rng.gen_range(f64::MIN_POSITIVE..1.0);
+            let key = if w > 0.0 { u.ln() / w } else { f64::NEG_INFINITY };
+            (key, (s, w))
+        })
+        .collect();
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+    keyed.into_iter().map(|(_, e)| e).take(k).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hrg_degree_and_determinism() {
+        let config = HrgConfig { nodes: 1500, avg_degree: 8.0, ..Default::default() };
+        let graph = hyperbolic_random_graph(&config).unwrap();
+        assert_eq!(graph.len(), 1500);
+        // The radius formula is asymptotic; finite graphs land near the target
+        assert!((4.0..16.0).contains(&graph.avg_degree()), "avg degree {}", graph.avg_degree());
+        assert!(graph.radii.iter().all(|r| *r <= config.radius() + 1e-9));
+
+        // At temperature 0 links are exactly the pairs within R
+        let radius = config.radius();
+        for (a, neighbors) in graph.adjacency_idx.iter().enumerate().take(50) {
+            for &b in neighbors {
+                assert!(graph.distance(a, b) <= radius);
+            }
+        }
+
+        let again = hyperbolic_random_graph(&config).unwrap();
+        assert_eq!(graph.adjacency_idx, again.adjacency_idx);
+        let err = hyperbolic_random_graph(&HrgConfig { gamma: 1.5, ..Default::default() }).unwrap_err();
+        assert_eq!(err.code(), "graph_generator.invalid_gamma");
+        let err = popularity_similarity_graph(&PsoConfig { m: 0, ..Default::default() }).unwrap_err();
+        assert_eq!(err.code(), "graph_generator.no_links");
+    }
+
+    #[test]
+    fn test_pso_links_and_hubs() {
+        for temperature in [0.0, 0.5] {
+            let config = PsoConfig { nodes: 800, m: 3, temperature, ..Default::default() };
+            let graph = popularity_similarity_graph(&config).unwrap();
+            // Each newcomer after the first m links exactly min(m, t - 1) times
+            assert_eq!(graph.edge_count(), 3 * 800 - 6);
+            let max_degree = graph.adjacency_idx.iter().map(Vec::len).max().unwrap();
+            assert!(max_degree > 30, "no hubs at T={}: max degree {}", temperature, max_degree);
+            // Older nodes stay closer to the origin
+            assert!(graph.radii[0] < graph.radii[799]);
+        }
+    }
+
+    #[test]
+    fn test_compare_true_and_perturbed_coordinates() {
+        let graph = popularity_similarity_graph(&PsoConfig { nodes: 300, ..Default::default() }).unwrap();
+        let truth = graph.true_coordinates();
+        let exact = graph.compare(&truth, 5000, 1).unwrap();
+        assert!(exact.distance_correlation > 0.999);
+        assert!(exact.rank_correlation > 0.999);
+
+        let scrambled: HashMap<NodeId, PoincareDiskPoint> = graph
+            .nodes
+            .iter()
+            .zip(graph.nodes.iter().rev())
+            .map(|(id, other)| (id.clone(), truth[other]))
+            .collect();
+        let mismatched = graph.compare(&scrambled, 5000, 1).unwrap();
+        assert!(mismatched.rank_correlation < exact.rank_correlation);
+        assert!(graph.compare(&HashMap::new(), 100, 1).is_none());
+    }
+}
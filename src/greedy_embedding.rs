@@ -6,10 +6,42 @@
 //! Key insight: By embedding a spanning tree into hyperbolic space where
 //! parent-child relationships are preserved as distance relationships,
 //! greedy forwarding is guaranteed to succeed.
+//!
+//! The root of the spanning tree sits near the origin and every other node's
+//! radius grows with its tree depth, so the root choice decides how deep the
+//! tree is and how evenly nodes spread over the disk. `RootStrategy` selects
+//! it, and `compare_root_strategies` reports the embedding each one yields.
 
 use crate::coordinates::{NodeId, RoutingCoordinate};
 use crate::PoincareDiskPoint;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet, VecDeque};
+use thiserror::Error;
+
+/// Embedding failures
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum EmbeddingError {
+    #[error("Empty graph")]
+    EmptyGraph,
+    #[error("No landmarks selected")]
+    NoLandmarks,
+    #[error("Invalid RTT measurements: {0}")]
+    InvalidMeasurements(String),
+    #[error("Root {0} is not in the graph")]
+    UnknownRoot(NodeId),
+}
+
+impl EmbeddingError {
+    /// Stable identifier for programmatic handling
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::EmptyGraph => "embedding.empty_graph",
+            Self::NoLandmarks => "embedding.no_landmarks",
+            Self::InvalidMeasurements(_) => "embedding.invalid_measurements",
+            Self::UnknownRoot(_) => "embedding.unknown_root",
+        }
+    }
+}
 
 /// Result of the greedy embedding process
 #[derive(Debug, Clone)]
@@ -45,6 +77,178 @@ impl Default for PIEConfig {
     }
 }
 
+/// Multi-restart configuration
+///
+/// On graphs with many non-tree edges the default embedding can end up in a
+/// poor configuration. With restarts enabled, `embed` additionally tries
+/// randomized embeddings (different root among high-degree nodes, shuffled
+/// child order, rotated angle ranges) and keeps the one with the best greedy
+/// success rate.
+#[derive(Debug, Clone)]
+pub struct RestartConfig {
+    /// Number of randomized embeddings tried after the default one
+    pub restarts: usize,
+    /// Source/destination pairs sampled to score a candidate (0 = all pairs)
+    pub score_samples: usize,
+    /// RNG seed for reproducible runs
+    pub seed: u64,
+}
+
+impl Default for RestartConfig {
+    fn default() -> Self {
+        Self {
+            restarts: 4,
+            score_samples: 500,
+            seed: 42,
+        }
+    }
+}
+
+/// How the root of the spanning tree is chosen
+///
+/// Ties are broken by the lowest node ID, so the choice does not depend on
+/// hash map order.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RootStrategy {
+    /// Highest-degree node (hub)
+    #[default]
+    MaxDegree,
+    /// Approximate graph center: midpoint of a longest shortest path found
+    /// by two BFS sweeps, which minimises the tree depth
+    Center,
+    /// Highest betweenness centrality, estimated from `samples` BFS sources
+    /// (0 = all nodes)
+    Betweenness { samples: usize, seed: u64 },
+    /// A given node
+    Fixed { node: NodeId },
+}
+
+impl RootStrategy {
+    /// Short name for reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MaxDegree => "max_degree",
+            Self::Center => "center",
+            Self::Betweenness { .. } => "betweenness",
+            Self::Fixed { .. } => "fixed",
+        }
+    }
+
+    /// Pick the root of `adjacency`
+    pub fn select(&self, adjacency: &HashMap<NodeId, Vec<NodeId>>) -> Result<NodeId, EmbeddingError> {
+        let mut nodes: Vec<&NodeId> = adjacency.keys().collect();
+        nodes.sort();
+        let first = *nodes.first().ok_or(EmbeddingError::EmptyGraph)?;
+        let best_by = |score: &dyn Fn(&NodeId) -> f64| {
+            // `max_by` keeps the last maximum, so walk from the highest ID
+            nodes
+                .iter()
+                .rev()
+                .max_by(|a, b| score(a).total_cmp(&score(b)))
+                .map(|id| (*id).clone())
+                .ok_or(EmbeddingError::EmptyGraph)
+        };
+        match self {
+            Self::MaxDegree => best_by(&|id| adjacency[id].len() as f64),
+            Self::Center => {
+                let (far, _) = bfs(adjacency, first);
+                let a = farthest(&far);
+                let (far, parents) = bfs(adjacency, &a);
+                let b = farthest(&far);
+                let mut path = vec![b];
+                while let Some(Some(parent)) = parents.get(path.last().unwrap_or(&a)) {
+                    path.push(parent.clone());
+                }
+                Ok(path.swap_remove(path.len() / 2))
+            }
+            Self::Betweenness { samples, seed } => {
+                let centrality = sampled_betweenness(adjacency, &nodes, *samples, *seed);
+                best_by(&|id| centrality.get(id).copied().unwrap_or(0.0))
+            }
+            Self::Fixed { node } => match adjacency.contains_key(node) {
+                true => Ok(node.clone()),
+                false => Err(EmbeddingError::UnknownRoot(node.clone())),
+            },
+        }
+    }
+}
+
+/// Hop distances and BFS parents from `source`
+fn bfs(adjacency: &HashMap<NodeId, Vec<NodeId>>, source: &NodeId) -> (HashMap<NodeId, usize>, HashMap<NodeId, Option<NodeId>>) {
+    let mut dist = HashMap::from([(source.clone(), 0)]);
+    let mut parents = HashMap::from([(source.clone(), None)]);
+    let mut queue = VecDeque::from([source.clone()]);
+    while let Some(current) = queue.pop_front() {
+        let d = dist[&current];
+        for neighbor in adjacency.get(&current).into_iter().flatten() {
+            if !dist.contains_key(neighbor) {
+                dist.insert(neighbor.clone(), d + 1);
+                parents.insert(neighbor.clone(), Some(current.clone()));
+                queue.push_back(neighbor.clone());
+            }
+        }
+    }
+    (dist, parents)
+}
+
+/// Node at the largest distance, lowest ID first among ties
+fn farthest(dist: &HashMap<NodeId, usize>) -> NodeId {
+    dist.iter()
+        .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+        .map(|(id, _)| id.clone())
+        .unwrap_or_else(|| NodeId::new(""))
+}
+
+/// Brandes' betweenness accumulated from sampled sources
+fn sampled_betweenness(
+    adjacency: &HashMap<NodeId, Vec<NodeId>>,
+    nodes: &[&NodeId],
+    samples: usize,
+    seed: u64,
+) -> HashMap<NodeId, f64> {
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    let mut sources: Vec<&NodeId> = nodes.to_vec();
+    if samples > 0 && samples < sources.len() {
+        sources.shuffle(&mut rand::rngs::StdRng::seed_from_u64(seed));
+        sources.truncate(samples);
+    }
+    let mut centrality: HashMap<NodeId, f64> = HashMap::new();
+    for source in sources {
+        let mut order = Vec::new();
+        let mut preds: HashMap<&NodeId, Vec<&NodeId>> = HashMap::new();
+        let mut paths: HashMap<&NodeId, f64> = HashMap::from([(source, 1.0)]);
+        let mut dist: HashMap<&NodeId, usize> = HashMap::from([(source, 0)]);
+        let mut queue = VecDeque::from([source]);
+        while let Some(v) = queue.pop_front() {
+            order.push(v);
+            for w in adjacency.get(v).into_iter().flatten() {
+                if !dist.contains_key(w) {
+                    dist.insert(w, dist[v] + 1);
+                    queue.push_back(w);
+                }
+                if dist[w] == dist[v] + 1 {
+                    *paths.entry(w).or_insert(0.0) += paths[v];
+                    preds.entry(w).or_default().push(v);
+                }
+            }
+        }
+        let mut dependency: HashMap<&NodeId, f64> = HashMap::new();
+        for w in order.into_iter().rev() {
+            let delta_w = dependency.get(w).copied().unwrap_or(0.0);
+            for v in preds.get(w).into_iter().flatten() {
+                *dependency.entry(v).or_insert(0.0) += paths[v] / paths[w] * (1.0 + delta_w);
+            }
+            if w != source {
+                *centrality.entry(w.clone()).or_insert(0.0) += delta_w;
+            }
+        }
+    }
+    centrality
+}
+
 /// Greedy Embedding using PIE (Polar Increasing-angle Embedding)
 ///
 /// This algorithm guarantees that for any pair of nodes in the tree,
@@ -52,17 +256,35 @@ impl Default for PIEConfig {
 /// will successfully reach the destination.
 pub struct GreedyEmbedding {
     config: PIEConfig,
+    restart: Option<RestartConfig>,
+    root_strategy: RootStrategy,
 }
 
 impl GreedyEmbedding {
     pub fn new() -> Self {
         Self {
             config: PIEConfig::default(),
+            restart: None,
+            root_strategy: RootStrategy::default(),
         }
     }
 
     pub fn with_config(config: PIEConfig) -> Self {
-        Self { config }
+        Self { config, restart: None, root_strategy: RootStrategy::default() }
+    }
+
+    /// Choose the spanning tree root with `strategy`
+    ///
+    /// Restarts still try randomized roots among high-degree nodes.
+    pub fn with_root_strategy(mut self, strategy: RootStrategy) -> Self {
+        self.root_strategy = strategy;
+        self
+    }
+
+    /// Enable multi-restart embedding with selection by greedy success
+    pub fn with_restarts(mut self, restart: RestartConfig) -> Self {
+        self.restart = Some(restart);
+        self
     }
 
     /// Build a BFS spanning tree from the graph
@@ -137,29 +359,84 @@ impl GreedyEmbedding {
     /// Perform PIE embedding
     ///
     /// Algorithm:
-    /// 1. Build BFS spanning tree from highest-degree node (hub)
+    /// 1. Build BFS spanning tree from the root chosen by the root strategy
     /// 2. Assign root to origin (or small radius)
     /// 3. For each node, assign angle range based on parent's range
     /// 4. Children divide their parent's angle range equally
     /// 5. Radius increases exponentially with depth
+    ///
+    /// If restarts are configured, randomized candidates are also embedded and
+    /// the one with the highest greedy success rate is returned.
     pub fn embed(
         &self,
         adjacency: &HashMap<NodeId, Vec<NodeId>>,
-    ) -> Result<EmbeddingResult, String> {
+    ) -> Result<EmbeddingResult, EmbeddingError> {
         if adjacency.is_empty() {
-            return Err("Empty graph".to_string());
+            return Err(EmbeddingError::EmptyGraph);
         }
 
-        // Find the highest-degree node as root (hub)
-        let root = adjacency
-            .iter()
-            .max_by_key(|(_, neighbors)| neighbors.len())
-            .map(|(id, _)| id.clone())
-            .ok_or("No nodes in graph")?;
+        let root = self.root_strategy.select(adjacency)?;
 
         // Build spanning tree
         let (_parent, children, depths) = self.build_spanning_tree(adjacency, &root);
+        let best = self.layout(root, children, &depths, 0.0);
+
+        let restart = match &self.restart {
+            Some(restart) if restart.restarts > 0 => restart,
+            _ => return Ok(best),
+        };
 
+        use rand::seq::SliceRandom;
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(restart.seed);
+
+        // Candidate roots: the top decile of nodes by degree
+        let mut by_degree: Vec<(&NodeId, usize)> = adjacency
+            .iter()
+            .map(|(id, neighbors)| (id, neighbors.len()))
+            .collect();
+        by_degree.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0 .0.cmp(&b.0 .0)));
+        let candidates = &by_degree[..(by_degree.len() / 10).max(1)];
+
+        let mut best_score = greedy_success_rate(&best.coordinates, adjacency, restart.score_samples, restart.seed);
+        let mut best = best;
+
+        for _ in 0..restart.restarts {
+            if best_score >= 1.0 {
+                break;
+            }
+
+            let root = candidates[rng.gen_range(0..candidates.len())].0.clone();
+            let (_parent, mut children, depths) = self.build_spanning_tree(adjacency, &root);
+            let mut parents: Vec<&NodeId> = children.keys().collect();
+            parents.sort_by(|a, b| a.0.cmp(&b.0));
+            let parents: Vec<NodeId> = parents.into_iter().cloned().collect();
+            for parent in parents {
+                if let Some(list) = children.get_mut(&parent) {
+                    list.shuffle(&mut rng);
+                }
+            }
+            let rotation = rng.gen_range(0.0..2.0 * std::f64::consts::PI);
+
+            let candidate = self.layout(root, children, &depths, rotation);
+            let score = greedy_success_rate(&candidate.coordinates, adjacency, restart.score_samples, restart.seed);
+            if score > best_score {
+                best_score = score;
+                best = candidate;
+            }
+        }
+
+        Ok(best)
+    }
+
+    /// Assign coordinates to a spanning tree, rotating the root's angle range by `rotation`
+    fn layout(
+        &self,
+        root: NodeId,
+        children: HashMap<NodeId, Vec<NodeId>>,
+        depths: &HashMap<NodeId, usize>,
+        rotation: f64,
+    ) -> EmbeddingResult {
         let max_depth = *depths.values().max().unwrap_or(&1);
 
         // Assign coordinates using DFS traversal
@@ -169,7 +446,7 @@ impl GreedyEmbedding {
         let mut angle_ranges: HashMap<NodeId, (f64, f64)> = HashMap::new();
 
         // Root gets the full circle
-        angle_ranges.insert(root.clone(), (0.0, 2.0 * std::f64::consts::PI));
+        angle_ranges.insert(root.clone(), (rotation, rotation + 2.0 * std::f64::consts::PI));
 
         // Process nodes in BFS order (to ensure parents are processed before children)
         let mut queue = VecDeque::new();
@@ -209,19 +486,19 @@ impl GreedyEmbedding {
             }
         }
 
-        Ok(EmbeddingResult {
+        EmbeddingResult {
             coordinates,
             tree_children: children,
             root,
             max_depth,
-        })
+        }
     }
 
     /// Embed graph and return coordinates as RoutingCoordinates
     pub fn embed_as_routing_coords(
         &self,
         adjacency: &HashMap<NodeId, Vec<NodeId>>,
-    ) -> Result<HashMap<NodeId, RoutingCoordinate>, String> {
+    ) -> Result<HashMap<NodeId, RoutingCoordinate>, EmbeddingError> {
         let result = self.embed(adjacency)?;
 
         let coords = result
@@ -319,6 +596,168 @@ impl Default for GreedyEmbedding {
     }
 }
 
+/// Greedy routing success rate over `samples` random pairs (0 = all pairs)
+pub fn greedy_success_rate(
+    coordinates: &HashMap<NodeId, PoincareDiskPoint>,
+    adjacency: &HashMap<NodeId, Vec<NodeId>>,
+    samples: usize,
+    seed: u64,
+) -> f64 {
+    use rand::{Rng, SeedableRng};
+
+    let mut node_ids: Vec<&NodeId> = coordinates.keys().collect();
+    if node_ids.len() < 2 {
+        return 1.0;
+    }
+    node_ids.sort_by(|a, b| a.0.cmp(&b.0));
+
+    if samples == 0 {
+        let (success, total, _) = verify_greedy_property(coordinates, adjacency);
+        return success as f64 / total.max(1) as f64;
+    }
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let mut success = 0;
+    for _ in 0..samples {
+        let source = node_ids[rng.gen_range(0..node_ids.len())];
+        let mut dest = node_ids[rng.gen_range(0..node_ids.len())];
+        while dest == source {
+            dest = node_ids[rng.gen_range(0..node_ids.len())];
+        }
+        if greedy_route_succeeds(coordinates, adjacency, source, dest) {
+            success += 1;
+        }
+    }
+    success as f64 / samples as f64
+}
+
+/// Simulate greedy forwarding from `source` to `dest`
+fn greedy_route_succeeds(
+    coordinates: &HashMap<NodeId, PoincareDiskPoint>,
+    adjacency: &HashMap<NodeId, Vec<NodeId>>,
+    source: &NodeId,
+    dest: &NodeId,
+) -> bool {
+    greedy_hops(coordinates, adjacency, source, dest).is_some()
+}
+
+/// Hops greedy forwarding takes from `source` to `dest`, None if it fails
+fn greedy_hops(
+    coordinates: &HashMap<NodeId, PoincareDiskPoint>,
+    adjacency: &HashMap<NodeId, Vec<NodeId>>,
+    source: &NodeId,
+    dest: &NodeId,
+) -> Option<usize> {
+    let dest_coord = coordinates.get(dest)?;
+    let mut current = source.clone();
+    let mut visited = HashSet::new();
+
+    for hops in 0..1000 {
+        if &current == dest {
+            return Some(hops);
+        }
+        if !visited.insert(current.clone()) {
+            return None;
+        }
+        let current_coord = coordinates.get(&current)?;
+
+        let mut best_neighbor: Option<&NodeId> = None;
+        let mut best_dist = current_coord.hyperbolic_distance(dest_coord);
+        for neighbor in adjacency.get(&current).into_iter().flatten() {
+            if let Some(neighbor_coord) = coordinates.get(neighbor) {
+                let dist = neighbor_coord.hyperbolic_distance(dest_coord);
+                if dist < best_dist {
+                    best_dist = dist;
+                    best_neighbor = Some(neighbor);
+                }
+            }
+        }
+
+        current = best_neighbor?.clone();
+    }
+    None
+}
+
+/// Embedding quality obtained with one root strategy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RootStrategyReport {
+    pub strategy: String,
+    pub root: NodeId,
+    /// Depth of the spanning tree
+    pub max_depth: usize,
+    /// Mean Euclidean radius of the coordinates
+    pub mean_radius: f64,
+    /// Share of nodes at or beyond radius 0.95, where precision runs out
+    pub boundary_fraction: f64,
+    /// Greedy routing success over the sampled pairs
+    pub greedy_success: f64,
+    /// Mean greedy hops over shortest-path hops for the pairs that succeeded
+    pub mean_stretch: f64,
+}
+
+/// Embed `adjacency` once per strategy and report the quality of each
+///
+/// `samples` source/destination pairs are drawn with `seed` (0 = all pairs),
+/// the same pairs for every strategy.
+pub fn compare_root_strategies(
+    adjacency: &HashMap<NodeId, Vec<NodeId>>,
+    strategies: &[RootStrategy],
+    config: &PIEConfig,
+    samples: usize,
+    seed: u64,
+) -> Result<Vec<RootStrategyReport>, EmbeddingError> {
+    use rand::{Rng, SeedableRng};
+
+    let mut nodes: Vec<&NodeId> = adjacency.keys().collect();
+    nodes.sort();
+    let pairs: Vec<(&NodeId, &NodeId)> = if samples == 0 || nodes.len() < 2 {
+        nodes.iter().flat_map(|s| nodes.iter().filter(move |d| *d != s).map(move |d| (*s, *d))).collect()
+    } else {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        (0..samples)
+            .map(|_| {
+                let source = nodes[rng.gen_range(0..nodes.len())];
+                let mut dest = source;
+                while dest == source {
+                    dest = nodes[rng.gen_range(0..nodes.len())];
+                }
+                (source, dest)
+            })
+            .collect()
+    };
+    let mut shortest: HashMap<&NodeId, HashMap<NodeId, usize>> = HashMap::new();
+    for (source, _) in &pairs {
+        shortest.entry(*source).or_insert_with(|| bfs(adjacency, source).0);
+    }
+
+    strategies
+        .iter()
+        .map(|strategy| {
+            let result = GreedyEmbedding::with_config(config.clone())
+                .with_root_strategy(strategy.clone())
+                .embed(adjacency)?;
+            let radii: Vec<f64> = result.coordinates.values().map(|p| p.euclidean_norm()).collect();
+            let (mut delivered, mut stretch) = (0usize, 0.0);
+            for (source, dest) in &pairs {
+                let hops = greedy_hops(&result.coordinates, adjacency, source, dest);
+                if let (Some(hops), Some(&best)) = (hops, shortest[source].get(*dest)) {
+                    delivered += 1;
+                    stretch += hops as f64 / best.max(1) as f64;
+                }
+            }
+            Ok(RootStrategyReport {
+                strategy: strategy.name().to_string(),
+                root: result.root,
+                max_depth: result.max_depth,
+                mean_radius: radii.iter().sum::<f64>() / radii.len().max(1) as f64,
+                boundary_fraction: radii.iter().filter(|r| **r >= 0.95).count() as f64 / radii.len().max(1) as f64,
+                greedy_success: delivered as f64 / pairs.len().max(1) as f64,
+                mean_stretch: if delivered > 0 { stretch / delivered as f64 } else { 0.0 },
+            })
+        })
+        .collect()
+}
+
 /// Verify that the embedding satisfies greedy routing property
 /// Returns (success_count, total_pairs, failure_details)
 pub fn verify_greedy_property(
@@ -497,4 +936,115 @@ mod tests {
         // Should still create valid coordinates
         assert_eq!(result.coordinates.len(), 3);
     }
+
+    /// Share of ordered pairs greedy forwarding delivers, walked here
+    /// rather than through the scoring used to pick among restarts
+    fn delivered_share(coords: &HashMap<NodeId, PoincareDiskPoint>, adj: &HashMap<NodeId, Vec<NodeId>>) -> f64 {
+        let ids: Vec<&NodeId> = coords.keys().collect();
+        let mut delivered = 0;
+        for source in &ids {
+            for dest in &ids {
+                let target = coords[*dest];
+                let mut at = (*source).clone();
+                while &at != *dest {
+                    let here = coords[&at].hyperbolic_distance(&target);
+                    let next = adj[&at]
+                        .iter()
+                        .map(|n| (coords[n].hyperbolic_distance(&target), n))
+                        .min_by(|a, b| a.0.total_cmp(&b.0));
+                    match next {
+                        Some((d, n)) if d < here => at = n.clone(),
+                        _ => break,
+                    }
+                }
+                if &at == *dest && source != dest {
+                    delivered += 1;
+                }
+            }
+        }
+        delivered as f64 / (ids.len() * (ids.len() - 1)) as f64
+    }
+
+    #[test]
+    fn test_multi_restart_improves_on_default() {
+        // Ring of 20 nodes with chords; node 0 is the single hub the default
+        // run roots at, and it embeds poorly from there
+        let n = 20;
+        let mut adj: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+        let mut connect = |a: usize, b: usize| {
+            adj.entry(NodeId::new(a.to_string())).or_default().push(NodeId::new(b.to_string()));
+            adj.entry(NodeId::new(b.to_string())).or_default().push(NodeId::new(a.to_string()));
+        };
+        for i in 0..n {
+            connect(i, (i + 1) % n);
+        }
+        connect(0, 10);
+        connect(0, 7);
+        connect(5, 15);
+        connect(2, 13);
+
+        let default_run = GreedyEmbedding::new().embed(&adj).unwrap();
+        let default_share = delivered_share(&default_run.coordinates, &adj);
+        assert!(default_share < 0.9, "default run already delivers {}", default_share);
+
+        let embedder = GreedyEmbedding::new().with_restarts(RestartConfig {
+            restarts: 8,
+            score_samples: 0,
+            seed: 7,
+        });
+        let result = embedder.embed(&adj).unwrap();
+        let share = delivered_share(&result.coordinates, &adj);
+
+        assert_eq!(result.coordinates.len(), n);
+        assert!(share > default_share + 0.1, "{} vs default {}", share, default_share);
+    }
+
+    /// Path 0-1-...-8 with two extra leaves on node 0
+    fn lollipop() -> HashMap<NodeId, Vec<NodeId>> {
+        let mut adj: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+        let mut connect = |a: String, b: String| {
+            adj.entry(NodeId::new(a.clone())).or_default().push(NodeId::new(b.clone()));
+            adj.entry(NodeId::new(b)).or_default().push(NodeId::new(a));
+        };
+        for i in 0..8 {
+            connect(i.to_string(), (i + 1).to_string());
+        }
+        for leaf in 0..2 {
+            connect("0".to_string(), format!("leaf{}", leaf));
+        }
+        adj
+    }
+
+    #[test]
+    fn test_root_strategies() {
+        let adj = lollipop();
+        let root = |strategy: RootStrategy| strategy.select(&adj).unwrap();
+        assert_eq!(root(RootStrategy::MaxDegree), NodeId::new("0"));
+        // Longest path runs from a leaf to node 8; its midpoint is node 4
+        assert_eq!(root(RootStrategy::Center), NodeId::new("4"));
+        // Node 3 splits the 11 nodes into 5 and 5
+        assert_eq!(root(RootStrategy::Betweenness { samples: 0, seed: 0 }), NodeId::new("3"));
+        assert_eq!(root(RootStrategy::Fixed { node: NodeId::new("7") }), NodeId::new("7"));
+        let missing = RootStrategy::Fixed { node: NodeId::new("x") }.select(&adj).unwrap_err();
+        assert_eq!(missing.code(), "embedding.unknown_root");
+
+        let result = GreedyEmbedding::new().with_root_strategy(RootStrategy::Center).embed(&adj).unwrap();
+        assert_eq!((result.root, result.max_depth), (NodeId::new("4"), 5));
+    }
+
+    #[test]
+    fn test_compare_root_strategies() {
+        let adj = lollipop();
+        let strategies = [RootStrategy::MaxDegree, RootStrategy::Center];
+        let reports = compare_root_strategies(&adj, &strategies, &PIEConfig::default(), 0, 0).unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!((reports[0].strategy.as_str(), reports[0].max_depth), ("max_degree", 8));
+        assert_eq!((reports[1].strategy.as_str(), reports[1].max_depth), ("center", 5));
+        for report in &reports {
+            // The graph is a tree, so delivered packets took the shortest path
+            assert!((report.mean_stretch - 1.0).abs() < 1e-9);
+        }
+        // Radii saturate with depth, so the shallower tree routes better
+        assert!(reports[1].greedy_success > reports[0].greedy_success);
+    }
 }
@@ -3,17 +3,34 @@
 //! This module provides a gRPC API using tonic for high-performance
 //! interaction with DRFE-R nodes. It exposes services for packet sending,
 //! status queries, and streaming topology updates.
+//!
+//! `RoutePackets` is a bidirectional stream for applications that use a
+//! node as a message router: the client streams outbound packets and
+//! receives receipts, destination acks and packets delivered to the node.
+//!
+//! `ReloadConfig` changes runtime tunables like `PUT /api/v1/config`, or
+//! re-reads the node's config file when the update is empty.
+//!
+//! Calls pass the node's API access control (see `api_access`) with the
+//! bearer token from the `authorization` metadata. `SendPacket` and
+//! `RoutePackets` are control calls; a packet stream counts once against the
+//! control limit when it is opened.
 
+use crate::api_access::{AccessError, RequestClass};
+use crate::config::ConfigUpdate;
 use crate::coordinates::NodeId;
-use crate::network::DistributedNode;
-use std::collections::HashMap;
+use crate::network::{DeliveryEvent, DistributedNode};
+use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::sync::{broadcast, RwLock};
-use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
-use tonic::{transport::Server, Request, Response, Status};
+use tokio::sync::{broadcast, mpsc, RwLock};
+use tokio_stream::{
+    wrappers::{BroadcastStream, ReceiverStream},
+    Stream, StreamExt,
+};
+use tonic::{metadata::MetadataMap, transport::Server, Request, Response, Status, Streaming};
 use uuid::Uuid;
 
 // Include generated protobuf code
@@ -23,10 +40,28 @@ pub mod proto {
 
 use proto::{
     routing_service_server::{RoutingService, RoutingServiceServer},
-    GetNodeStatusRequest, HyperbolicPoint, NodeStatus, SendPacketRequest, SendPacketResponse,
+    DeliveredPacket, DeliveryReceipt, GetNodeStatusRequest, HyperbolicPoint, NodeStatus,
+    OutboundPacket, ReceiptStatus, ReloadConfigRequest, ReloadConfigResponse, RouterEvent, router_event, SendPacketRequest, SendPacketResponse,
     TopologyEdge, TopologyNode, TopologyRequest, TopologyUpdate, UpdateType,
 };
 
+/// Events buffered per RoutePackets stream before the sender waits
+const ROUTE_STREAM_BUFFER: usize = 256;
+/// Unacked packets per RoutePackets stream whose acks are still reported
+const ROUTE_STREAM_MAX_PENDING_ACKS: usize = 4096;
+
+/// Check the destination and TTL of a packet to send; returns the TTL to use
+fn validate_packet(destination: &str, ttl: u32) -> Result<u32, &'static str> {
+    if destination.is_empty() {
+        return Err("Destination cannot be empty");
+    }
+    let ttl = if ttl == 0 { 64 } else { ttl };
+    if ttl > 255 {
+        return Err("TTL must be between 1 and 255");
+    }
+    Ok(ttl)
+}
+
 /// Shared gRPC service state
 #[derive(Clone)]
 pub struct GrpcServiceState {
@@ -57,6 +92,32 @@ impl GrpcRoutingService {
     pub fn new(state: GrpcServiceState) -> Self {
         Self { state }
     }
+
+    /// Check a call against the node's API access control
+    async fn authorize(
+        &self,
+        metadata: &MetadataMap,
+        remote_addr: Option<SocketAddr>,
+        class: RequestClass,
+    ) -> Result<(), Status> {
+        let bearer = match metadata.get("authorization") {
+            Some(value) => Some(
+                value
+                    .to_str()
+                    .ok()
+                    .and_then(|v| v.strip_prefix("Bearer "))
+                    .ok_or_else(|| Status::unauthenticated("Malformed authorization metadata"))?,
+            ),
+            None => None,
+        };
+        let client = remote_addr.map(|addr| addr.ip());
+        match self.state.node.authorize_api(bearer, None, client, class).await {
+            Ok(_) => Ok(()),
+            Err(AccessError::Unauthenticated(msg)) => Err(Status::unauthenticated(msg)),
+            Err(AccessError::Forbidden(msg)) => Err(Status::permission_denied(msg)),
+            Err(e @ AccessError::RateLimited(_)) => Err(Status::resource_exhausted(e.to_string())),
+        }
+    }
 }
 
 #[tonic::async_trait]
@@ -66,18 +127,9 @@ impl RoutingService for GrpcRoutingService {
         &self,
         request: Request<SendPacketRequest>,
     ) -> Result<Response<SendPacketResponse>, Status> {
+        self.authorize(request.metadata(), request.remote_addr(), RequestClass::Control).await?;
         let req = request.into_inner();
-
-        // Validate destination
-        if req.destination.is_empty() {
-            return Err(Status::invalid_argument("Destination cannot be empty"));
-        }
-
-        // Validate TTL
-        let ttl = if req.ttl == 0 { 64 } else { req.ttl };
-        if ttl > 255 {
-            return Err(Status::invalid_argument("TTL must be between 1 and 255"));
-        }
+        let ttl = validate_packet(&req.destination, req.ttl).map_err(Status::invalid_argument)?;
 
         // Generate unique packet ID
         let packet_id = Uuid::new_v4().to_string();
@@ -116,6 +168,7 @@ impl RoutingService for GrpcRoutingService {
         &self,
         request: Request<GetNodeStatusRequest>,
     ) -> Result<Response<NodeStatus>, Status> {
+        self.authorize(request.metadata(), request.remote_addr(), RequestClass::Read).await?;
         let req = request.into_inner();
 
         // Check if this is the local node
@@ -168,6 +221,7 @@ impl RoutingService for GrpcRoutingService {
         &self,
         request: Request<TopologyRequest>,
     ) -> Result<Response<Self::StreamTopologyStream>, Status> {
+        self.authorize(request.metadata(), request.remote_addr(), RequestClass::Read).await?;
         let req = request.into_inner();
 
         // Subscribe to topology updates
@@ -193,9 +247,135 @@ impl RoutingService for GrpcRoutingService {
 
     type StreamTopologyStream =
         Pin<Box<dyn Stream<Item = Result<TopologyUpdate, Status>> + Send>>;
+
+    /// Route a stream of packets and report their progress and deliveries
+    async fn route_packets(
+        &self,
+        request: Request<Streaming<OutboundPacket>>,
+    ) -> Result<Response<Self::RoutePacketsStream>, Status> {
+        self.authorize(request.metadata(), request.remote_addr(), RequestClass::Control).await?;
+        let events = self.route_stream(request.into_inner());
+        Ok(Response::new(Box::pin(events) as Self::RoutePacketsStream))
+    }
+
+    type RoutePacketsStream = Pin<Box<dyn Stream<Item = Result<RouterEvent, Status>> + Send>>;
+
+    /// Apply a configuration update, or re-read the node's config file
+    async fn reload_config(
+        &self,
+        request: Request<ReloadConfigRequest>,
+    ) -> Result<Response<ReloadConfigResponse>, Status> {
+        self.authorize(request.metadata(), request.remote_addr(), RequestClass::Control).await?;
+        let req = request.into_inner();
+
+        let config = if req.update_json.is_empty() {
+            self.state.node.reload_config_file().await.map_err(Status::failed_precondition)?
+        } else {
+            let update = ConfigUpdate::from_json(&req.update_json).map_err(Status::invalid_argument)?;
+            self.state.node.apply_config(&update).await.map_err(Status::invalid_argument)?
+        };
+        let config_json = serde_json::to_string(&config).map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(ReloadConfigResponse { config_json }))
+    }
 }
 
 impl GrpcRoutingService {
+    /// Serve one RoutePackets stream
+    ///
+    /// Every outbound packet gets a SENT or FAILED receipt, and an ACKED
+    /// receipt when its destination acknowledges it. Acks are only reported
+    /// on the stream that sent the packet; deliveries go to every stream.
+    /// Deliveries missed while the client lags are dropped. The stream
+    /// stays open after the client stops sending, until it disconnects.
+    fn route_stream<S>(&self, mut inbound: S) -> ReceiverStream<Result<RouterEvent, Status>>
+    where
+        S: Stream<Item = Result<OutboundPacket, Status>> + Send + Unpin + 'static,
+    {
+        let (tx, rx) = mpsc::channel(ROUTE_STREAM_BUFFER);
+        let node = Arc::clone(&self.state.node);
+        let mut deliveries = node.subscribe_deliveries();
+
+        tokio::spawn(async move {
+            // packet_id -> request_id of packets awaiting an ack, oldest first
+            let mut pending: HashMap<String, String> = HashMap::new();
+            let mut pending_order: VecDeque<String> = VecDeque::new();
+            let mut inbound_open = true;
+
+            loop {
+                let event = tokio::select! {
+                    next = inbound.next(), if inbound_open => match next {
+                        Some(Ok(packet)) => {
+                            let receipt = send_outbound(&node, packet).await;
+                            if receipt.status == ReceiptStatus::Sent as i32 {
+                                if pending_order.len() >= ROUTE_STREAM_MAX_PENDING_ACKS {
+                                    if let Some(oldest) = pending_order.pop_front() {
+                                        pending.remove(&oldest);
+                                    }
+                                }
+                                pending.insert(receipt.packet_id.clone(), receipt.request_id.clone());
+                                pending_order.push_back(receipt.packet_id.clone());
+                            }
+                            RouterEvent { event: Some(router_event::Event::Receipt(receipt)) }
+                        }
+                        Some(Err(status)) => {
+                            let _ = tx.send(Err(status)).await;
+                            break;
+                        }
+                        None => {
+                            inbound_open = false;
+                            continue;
+                        }
+                    },
+                    event = deliveries.recv() => match event {
+                        Ok(DeliveryEvent::Delivered { packet_id, source, payload, hops }) => RouterEvent {
+                            event: Some(router_event::Event::Delivered(DeliveredPacket { packet_id, source: source.0, payload, hops })),
+                        },
+                        Ok(DeliveryEvent::Acked { packet_id, .. }) => {
+                            let Some(request_id) = pending.remove(&packet_id) else {
+                                continue;
+                            };
+                            pending_order.retain(|id| *id != packet_id);
+                            RouterEvent {
+                                event: Some(router_event::Event::Receipt(DeliveryReceipt {
+                                    request_id,
+                                    packet_id,
+                                    status: ReceiptStatus::Acked as i32,
+                                    message: String::new(),
+                                })),
+                            }
+                        }
+                        Ok(DeliveryEvent::DeadlineMissed { packet_id, dropped_at, miss, .. }) => {
+                            let Some(request_id) = pending.remove(&packet_id) else {
+                                continue;
+                            };
+                            pending_order.retain(|id| *id != packet_id);
+                            RouterEvent {
+                                event: Some(router_event::Event::Receipt(DeliveryReceipt {
+                                    request_id,
+                                    packet_id,
+                                    status: ReceiptStatus::Failed as i32,
+                                    message: format!(
+                                        "Dropped at {} past its deadline: {} ms left, {} ms needed",
+                                        dropped_at.0, miss.left_ms, miss.estimated_ms
+                                    ),
+                                })),
+                            }
+                        }
+                        Ok(DeliveryEvent::StreamFailed { .. }) => continue,
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                    _ = tx.closed() => break,
+                };
+                if tx.send(Ok(event)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        ReceiverStream::new(rx)
+    }
+
     /// Create a topology snapshot
     async fn create_topology_snapshot(&self) -> TopologyUpdate {
         let local_id = self.state.node.id().0.clone();
@@ -252,6 +432,31 @@ impl GrpcRoutingService {
     }
 }
 
+/// Send one packet from a RoutePackets stream and describe the outcome
+async fn send_outbound(node: &DistributedNode, packet: OutboundPacket) -> DeliveryReceipt {
+    let mut receipt = DeliveryReceipt {
+        request_id: packet.request_id,
+        packet_id: String::new(),
+        status: ReceiptStatus::Failed as i32,
+        message: String::new(),
+    };
+    let ttl = match validate_packet(&packet.destination, packet.ttl) {
+        Ok(ttl) => ttl,
+        Err(message) => {
+            receipt.message = message.to_string();
+            return receipt;
+        }
+    };
+    match node.send_tracked_packet(NodeId::new(&packet.destination), packet.payload, ttl).await {
+        Ok(packet_id) => {
+            receipt.packet_id = packet_id;
+            receipt.status = ReceiptStatus::Sent as i32;
+        }
+        Err(e) => receipt.message = format!("Failed to send packet: {}", e),
+    }
+    receipt
+}
+
 /// Start the gRPC server
 ///
 /// # Arguments
@@ -302,6 +507,13 @@ mod tests {
                 .await
                 .unwrap(),
         );
+        // Tests call control RPCs without a token
+        let access = crate::api_access::ApiAccessConfig {
+            anonymous_role: Some(crate::api_access::ApiRole::Operator),
+            ..Default::default()
+        };
+        let update = crate::config::ConfigUpdate { api_access: Some(access), ..Default::default() };
+        node.apply_config(&update).await.unwrap();
 
         let (topology_tx, _) = broadcast::channel(100);
 
@@ -378,6 +590,73 @@ mod tests {
         assert_eq!(result.unwrap_err().code(), tonic::Code::NotFound);
     }
 
+    #[tokio::test]
+    async fn test_route_stream_receipts_and_deliveries() {
+        let state = create_test_state().await;
+        let node = Arc::clone(&state.node);
+        let service = GrpcRoutingService::new(state);
+
+        let outbound = |request_id: &str, destination: &str| OutboundPacket {
+            request_id: request_id.to_string(),
+            destination: destination.to_string(),
+            payload: b"hi".to_vec(),
+            ttl: 0,
+        };
+        let inbound = tokio_stream::iter(vec![Ok(outbound("r1", "")), Ok(outbound("r2", "elsewhere"))]);
+        let mut events = service.route_stream(inbound);
+
+        // Neither packet can leave: one is invalid, the node has no neighbors
+        for request_id in ["r1", "r2"] {
+            let Some(router_event::Event::Receipt(receipt)) = events.next().await.unwrap().unwrap().event else {
+                panic!("expected a receipt");
+            };
+            assert_eq!(receipt.request_id, request_id);
+            assert_eq!(receipt.status, ReceiptStatus::Failed as i32);
+            assert!(!receipt.message.is_empty());
+        }
+
+        // The stream stays open for deliveries after the client stops sending
+        let target = crate::coordinates::AnchorCoordinate::from_id(node.id()).point;
+        let packet = crate::network::Packet::new_data(NodeId::new("peer"), node.id().clone(), target, b"hello".to_vec(), 8);
+        node.handle_packet(packet.clone(), "127.0.0.1:9".parse().unwrap()).await.unwrap();
+
+        let Some(router_event::Event::Delivered(delivered)) = events.next().await.unwrap().unwrap().event else {
+            panic!("expected a delivered packet");
+        };
+        assert_eq!(delivered.packet_id, packet.header.packet_id);
+        assert_eq!((delivered.source.as_str(), delivered.payload.as_slice()), ("peer", &b"hello"[..]));
+    }
+
+    #[tokio::test]
+    async fn test_reload_config() {
+        let state = create_test_state().await;
+        let node = Arc::clone(&state.node);
+        let service = GrpcRoutingService::new(state);
+
+        let request = Request::new(ReloadConfigRequest { update_json: r#"{"max_neighbors": 5}"#.to_string() });
+        let reply = service.reload_config(request).await.unwrap().into_inner();
+        assert!(reply.config_json.contains(r#""max_neighbors":5"#));
+        assert_eq!(node.config().await.max_neighbors, 5);
+
+        // Without a config file there is nothing to re-read
+        let request = Request::new(ReloadConfigRequest { update_json: String::new() });
+        assert_eq!(service.reload_config(request).await.unwrap_err().code(), tonic::Code::FailedPrecondition);
+
+        // Re-reading picks up edits made to the file since it was set
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), r#"{"max_neighbors": 6}"#).unwrap();
+        node.set_config_file(file.path().to_path_buf()).await.unwrap();
+        assert_eq!(node.config().await.max_neighbors, 6);
+        std::fs::write(file.path(), r#"{"max_neighbors": 7, "heartbeat_interval_ms": 300}"#).unwrap();
+        let request = Request::new(ReloadConfigRequest { update_json: String::new() });
+        service.reload_config(request).await.unwrap();
+        let config = node.config().await;
+        assert_eq!((config.max_neighbors, config.heartbeat_interval_ms), (7, 300));
+
+        let request = Request::new(ReloadConfigRequest { update_json: "not json".to_string() });
+        assert_eq!(service.reload_config(request).await.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+
     #[tokio::test]
     async fn test_create_topology_snapshot() {
         let state = create_test_state().await;
@@ -0,0 +1,311 @@
+//! Routing Header Budgets
+//!
+//! Recovery modes carry their state in the packet header: the visited set,
+//! pressure values and the DFS stack all grow by a node ID per hop, and with
+//! long IDs a packet that wanders in Tree mode can approach `MAX_PACKET_SIZE`
+//! long before its TTL runs out. This module bounds that growth. Each packet
+//! type has a header budget; a header over it switches to a compact
+//! representation that replaces node IDs with 32-bit hashes, and if that is
+//! still too large the oldest recovery state is trimmed. Header and payload
+//! bytes are counted per packet type so the overhead is visible before it
+//! turns into dropped packets.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::network::PacketType;
+use crate::routing::PacketHeader;
+
+/// Header budget of one packet type
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeaderBudgetRule {
+    pub packet_type: PacketType,
+    pub max_bytes: usize,
+}
+
+/// Header size limits
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeaderBudgetConfig {
+    /// Enforce budgets; when off only `MAX_PACKET_SIZE` applies
+    pub enabled: bool,
+    /// Budget of packet types without a rule
+    pub default_max_bytes: usize,
+    /// Per-type overrides
+    pub rules: Vec<HeaderBudgetRule>,
+}
+
+impl Default for HeaderBudgetConfig {
+    fn default() -> Self {
+        Self { enabled: true, default_max_bytes: 4096, rules: Vec::new() }
+    }
+}
+
+/// Smallest budget that still fits a header without recovery state
+const MIN_BUDGET: usize = 512;
+
+impl HeaderBudgetConfig {
+    /// Budget in bytes for headers of `packet_type`
+    pub fn budget_for(&self, packet_type: PacketType) -> usize {
+        self.rules
+            .iter()
+            .find(|rule| rule.packet_type == packet_type)
+            .map_or(self.default_max_bytes, |rule| rule.max_bytes)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let smallest = self.rules.iter().map(|rule| rule.max_bytes).fold(self.default_max_bytes, usize::min);
+        if smallest < MIN_BUDGET {
+            return Err(format!("Header budgets must be at least {} bytes", MIN_BUDGET));
+        }
+        Ok(())
+    }
+}
+
+/// Compact ID of a node in a compacted header
+///
+/// Placeholders produced by `placeholder` map back to the hash they stand
+/// for, so a node that could not resolve a hash passes it on unchanged.
+pub fn compact_id(node: &NodeId) -> u32 {
+    if let Some(hash) = node.0.strip_prefix('#').and_then(|hex| u32::from_str_radix(hex, 16).ok()) {
+        return hash;
+    }
+    // FNV-1a, folded to 32 bits; must agree on every node
+    let hash = node.0.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    (hash ^ (hash >> 32)) as u32
+}
+
+/// Stand-in ID for a hash the current node cannot resolve
+pub fn placeholder(hash: u32) -> NodeId {
+    NodeId::new(format!("#{:08x}", hash))
+}
+
+/// Recovery state with node IDs replaced by their compact IDs
+///
+/// Routing only ever looks up the current node and its neighbors in this
+/// state, so a forwarder resolves hashes against those and leaves the rest
+/// as placeholders.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CompactRecoveryState {
+    /// Sorted compact IDs of visited nodes
+    pub visited: Vec<u32>,
+    /// Pressure per compact ID, sorted by ID
+    pub pressure_values: Vec<(u32, f32)>,
+    /// DFS stack, bottom first
+    pub dfs_stack: Vec<u32>,
+}
+
+impl CompactRecoveryState {
+    /// Compact the recovery state of a routing header
+    pub fn from_routing(header: &PacketHeader) -> Self {
+        Self::encode(header.visited.iter(), header.pressure_values.iter().map(|(n, p)| (n, *p)), header.dfs_stack.iter())
+    }
+
+    pub fn encode<'a>(
+        visited: impl Iterator<Item = &'a NodeId>,
+        pressure_values: impl Iterator<Item = (&'a NodeId, f64)>,
+        dfs_stack: impl Iterator<Item = &'a NodeId>,
+    ) -> Self {
+        let mut visited: Vec<u32> = visited.map(compact_id).collect();
+        visited.sort_unstable();
+        visited.dedup();
+        let mut pressure_values: Vec<(u32, f32)> =
+            pressure_values.map(|(node, pressure)| (compact_id(node), pressure as f32)).collect();
+        pressure_values.sort_by_key(|(id, _)| *id);
+        Self { visited, pressure_values, dfs_stack: dfs_stack.map(compact_id).collect() }
+    }
+
+    /// Expand into `header`, naming the `known` nodes and using placeholders for the rest
+    pub fn decode_into(&self, header: &mut PacketHeader, known: &[NodeId]) {
+        let names: HashMap<u32, &NodeId> = known.iter().map(|node| (compact_id(node), node)).collect();
+        let resolve = |hash: &u32| names.get(hash).map_or_else(|| placeholder(*hash), |node| (*node).clone());
+        header.visited = self.visited.iter().map(resolve).collect::<HashSet<_>>();
+        header.pressure_values = self
+            .pressure_values
+            .iter()
+            .map(|(hash, pressure)| (resolve(hash), *pressure as f64))
+            .collect();
+        header.dfs_stack = self.dfs_stack.iter().map(resolve).collect();
+    }
+}
+
+/// Drop about half of a header's recovery state: the lowest pressures,
+/// else the bottom of the DFS stack, else visited entries
+///
+/// Forgotten visits may be revisited and a cut stack restarts DFS, which
+/// costs hops but not correctness since the TTL still bounds the walk.
+///
+/// # Returns
+/// Whether anything was dropped
+pub fn trim_recovery_state(header: &mut PacketHeader) -> bool {
+    if !header.pressure_values.is_empty() {
+        let mut entries: Vec<(NodeId, f64)> = header.pressure_values.drain().collect();
+        entries.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.0.cmp(&b.0.0)));
+        entries.truncate(entries.len() / 2);
+        header.pressure_values = entries.into_iter().collect();
+        return true;
+    }
+    if !header.dfs_stack.is_empty() {
+        header.dfs_stack.drain(..header.dfs_stack.len().div_ceil(2));
+        return true;
+    }
+    if !header.visited.is_empty() {
+        let mut visited: Vec<NodeId> = header.visited.drain().collect();
+        visited.sort_by(|a, b| a.0.cmp(&b.0));
+        visited.truncate(visited.len() / 2);
+        header.visited = visited.into_iter().collect();
+        return true;
+    }
+    false
+}
+
+/// Result of fitting a header to its budget at one hop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderFit {
+    /// Encoded header size after fitting
+    pub size: usize,
+    /// The header switched to the compact representation at this hop
+    pub compacted: bool,
+    /// Recovery state was trimmed at this hop
+    pub trimmed: bool,
+}
+
+/// Header accounting counters of one packet type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeaderCounters {
+    /// Packets measured before transmission
+    pub packets: u64,
+    pub header_bytes: u64,
+    pub payload_bytes: u64,
+    /// Largest header seen
+    pub max_header_bytes: u64,
+    /// Headers switched to the compact representation
+    pub compacted: u64,
+    /// Headers whose recovery state was trimmed
+    pub trimmed: u64,
+    /// Packets dropped because their header still exceeded the budget
+    pub over_budget: u64,
+}
+
+/// Header accounting of one packet type
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeaderStatsEntry {
+    pub packet_type: PacketType,
+    pub counters: HeaderCounters,
+    /// Header bytes per payload byte
+    pub overhead_ratio: f64,
+    pub mean_header_bytes: f64,
+}
+
+/// Header size telemetry of one node
+#[derive(Debug, Clone, Default)]
+pub struct HeaderStats {
+    counters: HashMap<PacketType, HeaderCounters>,
+}
+
+impl HeaderStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a header measured before transmission
+    pub fn record(&mut self, packet_type: PacketType, fit: &HeaderFit, payload_bytes: usize) {
+        let counters = self.counters.entry(packet_type).or_default();
+        counters.packets += 1;
+        counters.header_bytes += fit.size as u64;
+        counters.payload_bytes += payload_bytes as u64;
+        counters.max_header_bytes = counters.max_header_bytes.max(fit.size as u64);
+        counters.compacted += fit.compacted as u64;
+        counters.trimmed += fit.trimmed as u64;
+    }
+
+    pub fn record_over_budget(&mut self, packet_type: PacketType) {
+        self.counters.entry(packet_type).or_default().over_budget += 1;
+    }
+
+    pub fn entries(&self) -> Vec<HeaderStatsEntry> {
+        self.counters
+            .iter()
+            .map(|(&packet_type, &counters)| HeaderStatsEntry {
+                packet_type,
+                counters,
+                overhead_ratio: if counters.payload_bytes > 0 {
+                    counters.header_bytes as f64 / counters.payload_bytes as f64
+                } else {
+                    0.0
+                },
+                mean_header_bytes: if counters.packets > 0 {
+                    counters.header_bytes as f64 / counters.packets as f64
+                } else {
+                    0.0
+                },
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PoincareDiskPoint;
+
+    #[test]
+    fn test_compact_state_roundtrip() {
+        let mut header = PacketHeader::new(NodeId::new("s"), NodeId::new("d"), PoincareDiskPoint::origin(), 64);
+        for i in 0..6 {
+            header.visited.insert(NodeId::new(format!("node-{}", i)));
+            header.dfs_stack.push(NodeId::new(format!("node-{}", i)));
+        }
+        header.pressure_values.insert(NodeId::new("node-1"), 2.5);
+
+        let compact = CompactRecoveryState::from_routing(&header);
+        let known = [NodeId::new("node-1"), NodeId::new("node-4")];
+        let mut decoded = PacketHeader::new(NodeId::new("s"), NodeId::new("d"), PoincareDiskPoint::origin(), 64);
+        compact.decode_into(&mut decoded, &known);
+
+        assert!(decoded.visited.contains(&known[0]) && decoded.visited.contains(&known[1]));
+        assert_eq!(decoded.pressure_values[&known[0]], 2.5);
+        assert_eq!(decoded.dfs_stack[1], known[0]);
+        // Unresolved nodes survive another round through a placeholder
+        let hidden = placeholder(compact_id(&NodeId::new("node-0")));
+        assert_eq!(decoded.dfs_stack[0], hidden);
+        assert_eq!(CompactRecoveryState::from_routing(&decoded), compact);
+    }
+
+    #[test]
+    fn test_trim_and_stats() {
+        let mut header = PacketHeader::new(NodeId::new("s"), NodeId::new("d"), PoincareDiskPoint::origin(), 64);
+        for (name, pressure) in [("a", 1.0), ("b", 3.0), ("c", 2.0)] {
+            header.pressure_values.insert(NodeId::new(name), pressure);
+            header.visited.insert(NodeId::new(name));
+            header.dfs_stack.push(NodeId::new(name));
+        }
+        header.visited.insert(NodeId::new("d"));
+        assert!(trim_recovery_state(&mut header));
+        assert_eq!(header.pressure_values.keys().collect::<Vec<_>>(), vec![&NodeId::new("b")]);
+        trim_recovery_state(&mut header);
+        assert!(header.pressure_values.is_empty());
+        trim_recovery_state(&mut header);
+        assert_eq!(header.dfs_stack, vec![NodeId::new("c")]);
+        trim_recovery_state(&mut header);
+        trim_recovery_state(&mut header);
+        assert_eq!(header.visited.len(), 2);
+        assert!(header.visited.contains(&NodeId::new("a")));
+        while trim_recovery_state(&mut header) {}
+        assert!(header.visited.is_empty());
+
+        let mut stats = HeaderStats::new();
+        stats.record(PacketType::Data, &HeaderFit { size: 300, compacted: true, trimmed: false }, 100);
+        stats.record(PacketType::Data, &HeaderFit { size: 500, compacted: false, trimmed: true }, 900);
+        stats.record_over_budget(PacketType::Data);
+        let entry = &stats.entries()[0];
+        assert_eq!(entry.counters.max_header_bytes, 500);
+        assert_eq!((entry.counters.compacted, entry.counters.trimmed, entry.counters.over_budget), (1, 1, 1));
+        assert!((entry.overhead_ratio - 0.8).abs() < 1e-9);
+        assert_eq!(entry.mean_header_bytes, 400.0);
+    }
+}
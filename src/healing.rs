@@ -0,0 +1,293 @@
+//! Coordinated Re-embedding After Partition Healing
+//!
+//! When two partitions of the overlay meet again, each side's coordinates
+//! live in a frame of its own. If both sides re-embed at once, each chases
+//! the other's moving coordinates and the overlay can thrash for many
+//! rounds. Instead the sides compare sizes through the node counts in
+//! heartbeat digests: the larger partition keeps its frame and re-embeds as
+//! usual, while the smaller one defers.
+//!
+//! A deferring node snapshots its view. The coordinates it still holds for
+//! nodes of the other side, from before the split, are in its own frame;
+//! as resyncs bring in their current coordinates, in the larger side's
+//! frame, those nodes become anchors pairing the two frames. Once `quorum`
+//! anchors have arrived, the node fits the isometry between the frames with
+//! Procrustes alignment, moves its own coordinate through it, and only then
+//! resumes re-embedding, with a few incremental iterations instead of the
+//! aggressive post-healing run. Without a quorum by `defer_ms`, as when the
+//! partitions never shared nodes, it refines from where it is.
+//!
+//! A node counts every node whose coordinate it holds, so coordinates kept
+//! through a long partition inflate both counts alike. Equal counts are
+//! broken by node ID, so that one side of each link defers.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::procrustes::{self, ProcrustesConfig, ProcrustesFit};
+use crate::PoincareDiskPoint;
+
+/// Coordination of re-embedding between the sides of a healed partition
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealingConfig {
+    /// When off, both sides re-embed aggressively at once
+    pub enabled: bool,
+    /// Anchors the smaller side needs before aligning to the larger's frame
+    pub quorum: usize,
+    /// Longest the smaller side defers re-embedding while waiting for anchors
+    pub defer_ms: u64,
+    /// Ricci flow and coordinate iterations of the refinement after deferring
+    pub refine_flow_iterations: usize,
+    pub refine_coord_iterations: usize,
+}
+
+impl Default for HealingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            quorum: 3,
+            defer_ms: 5_000,
+            refine_flow_iterations: 2,
+            refine_coord_iterations: 5,
+        }
+    }
+}
+
+impl HealingConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.quorum < 2 {
+            return Err("healing quorum must be at least 2, the fewest nodes an alignment needs".to_string());
+        }
+        if self.defer_ms == 0 {
+            return Err("healing defer_ms must be positive".to_string());
+        }
+        if self.refine_flow_iterations == 0 || self.refine_coord_iterations == 0 {
+            return Err("healing refinement needs at least one iteration of each kind".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Part a node plays in re-embedding after its partition healed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealingRole {
+    /// Keep this side's frame and re-embed as usual
+    Keep,
+    /// Defer, then adopt the other side's frame
+    Adopt,
+}
+
+impl HealingRole {
+    /// Role of `local`, counting `local_nodes`, on meeting `remote`, counting `remote_nodes`
+    ///
+    /// A count of zero is unknown, as from a peer that predates node counts,
+    /// and never makes a node defer.
+    pub fn decide(local: &NodeId, local_nodes: u64, remote: &NodeId, remote_nodes: u64) -> Self {
+        let adopt = remote_nodes > 0
+            && (remote_nodes > local_nodes || (remote_nodes == local_nodes && remote.0 < local.0));
+        if adopt {
+            HealingRole::Adopt
+        } else {
+            HealingRole::Keep
+        }
+    }
+}
+
+/// What a node should do about its coordinate after a healing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HealingStep {
+    /// Not deferring; re-embed as usual
+    Idle,
+    /// Still waiting for anchors; do not re-embed
+    Wait,
+    /// Move the local coordinate through this fit, then refine
+    Align(ProcrustesFit),
+    /// No usable fit; refine from the current coordinate
+    Refine,
+}
+
+/// Healings seen by a node and how its deferrals ended
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealingStats {
+    /// Healings where this node kept its frame
+    pub kept: u64,
+    /// Healings where it deferred to the other side
+    pub deferred: u64,
+    /// Deferrals that ended in an alignment
+    pub aligned: u64,
+    /// Deferrals that ended without one
+    pub unaligned: u64,
+}
+
+/// Coordinates known when a deferral began
+#[derive(Debug, Clone)]
+struct Deferral {
+    snapshot: BTreeMap<NodeId, (PoincareDiskPoint, u64)>,
+    deadline_ms: u64,
+}
+
+/// Deferral state of a node's coordinate after partition healing
+#[derive(Debug, Clone, Default)]
+pub struct HealingCoordinator {
+    config: HealingConfig,
+    deferral: Option<Deferral>,
+    stats: HealingStats,
+}
+
+impl HealingCoordinator {
+    pub fn new(config: HealingConfig) -> Self {
+        Self { config, ..Default::default() }
+    }
+
+    pub fn config(&self) -> &HealingConfig {
+        &self.config
+    }
+
+    /// Replace the configuration; disabling ends a deferral in progress
+    pub fn set_config(&mut self, config: HealingConfig) {
+        if !config.enabled {
+            self.deferral = None;
+        }
+        self.config = config;
+    }
+
+    pub fn stats(&self) -> HealingStats {
+        self.stats
+    }
+
+    pub fn is_deferring(&self) -> bool {
+        self.deferral.is_some()
+    }
+
+    /// Record a healing; on `Adopt`, defer with `view` as the snapshot
+    ///
+    /// A healing seen while already deferring keeps the first snapshot,
+    /// whose coordinates are all still in this side's frame.
+    pub fn begin(&mut self, role: HealingRole, view: BTreeMap<NodeId, (PoincareDiskPoint, u64)>, now_ms: u64) {
+        if role == HealingRole::Keep || !self.config.enabled {
+            self.stats.kept += 1;
+            return;
+        }
+        if self.deferral.is_none() {
+            self.stats.deferred += 1;
+            self.deferral = Some(Deferral { snapshot: view, deadline_ms: now_ms + self.config.defer_ms });
+        }
+    }
+
+    /// Snapshot nodes other than `local` whose coordinate `view` holds a newer version of
+    fn anchors(deferral: &Deferral, local: &NodeId, view: &BTreeMap<NodeId, (PoincareDiskPoint, u64)>) -> Vec<NodeId> {
+        deferral
+            .snapshot
+            .iter()
+            .filter(|(node, (_, version))| *node != local && view.get(*node).is_some_and(|(_, v)| v > version))
+            .map(|(node, _)| node.clone())
+            .collect()
+    }
+
+    /// Next step for `local`, given its current `view`
+    ///
+    /// Ends the deferral once the quorum of anchors is reached or the
+    /// deadline passes.
+    pub fn poll(&mut self, local: &NodeId, view: &BTreeMap<NodeId, (PoincareDiskPoint, u64)>, now_ms: u64) -> HealingStep {
+        let Some(deferral) = &self.deferral else {
+            return HealingStep::Idle;
+        };
+        let anchors = Self::anchors(deferral, local, view);
+        let quorum = anchors.len() >= self.config.quorum;
+        if !quorum && now_ms < deferral.deadline_ms {
+            return HealingStep::Wait;
+        }
+
+        let fit = quorum.then(|| {
+            let points = |map: &BTreeMap<NodeId, (PoincareDiskPoint, u64)>| -> HashMap<NodeId, PoincareDiskPoint> {
+                anchors.iter().map(|node| (node.clone(), map[node].0)).collect()
+            };
+            procrustes::align(&points(&deferral.snapshot), &points(view), &ProcrustesConfig::default())
+        });
+        self.deferral = None;
+        match fit.flatten() {
+            Some(fit) => {
+                self.stats.aligned += 1;
+                HealingStep::Align(fit)
+            }
+            None => {
+                self.stats.unaligned += 1;
+                HealingStep::Refine
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::procrustes::MobiusTransform;
+
+    fn view(entries: &[(&str, PoincareDiskPoint, u64)]) -> BTreeMap<NodeId, (PoincareDiskPoint, u64)> {
+        entries.iter().map(|(id, p, v)| (NodeId::new(*id), (*p, *v))).collect()
+    }
+
+    #[test]
+    fn test_smaller_side_adopts() {
+        let (a, b) = (NodeId::new("a"), NodeId::new("b"));
+        assert_eq!(HealingRole::decide(&a, 3, &b, 10), HealingRole::Adopt);
+        assert_eq!(HealingRole::decide(&b, 10, &a, 3), HealingRole::Keep);
+        // Ties defer on exactly one side, and unknown counts never do
+        assert_eq!(HealingRole::decide(&b, 5, &a, 5), HealingRole::Adopt);
+        assert_eq!(HealingRole::decide(&a, 5, &b, 5), HealingRole::Keep);
+        assert_eq!(HealingRole::decide(&a, 0, &b, 0), HealingRole::Keep);
+
+        let mut config = HealingConfig::default();
+        assert!(config.validate().is_ok());
+        config.quorum = 1;
+        assert!(config.validate().is_err());
+
+        let mut healing = HealingCoordinator::new(HealingConfig::default());
+        healing.begin(HealingRole::Keep, BTreeMap::new(), 0);
+        assert!(!healing.is_deferring());
+        healing.begin(HealingRole::Adopt, BTreeMap::new(), 0);
+        assert_eq!(healing.poll(&a, &BTreeMap::new(), 1_000), HealingStep::Wait);
+        // Nothing to align with by the deadline
+        assert_eq!(healing.poll(&a, &BTreeMap::new(), 5_000), HealingStep::Refine);
+        assert_eq!(healing.poll(&a, &BTreeMap::new(), 5_000), HealingStep::Idle);
+        assert_eq!(healing.stats(), HealingStats { kept: 1, deferred: 1, aligned: 0, unaligned: 1 });
+    }
+
+    #[test]
+    fn test_quorum_of_anchors_aligns_frames() {
+        let frame = MobiusTransform { reflect: false, center: (0.1, -0.2), rotation: 0.7, target: (-0.3, 0.1) };
+        let old = [
+            ("x", PoincareDiskPoint::new(0.3, 0.1).unwrap()),
+            ("y", PoincareDiskPoint::new(-0.2, 0.4).unwrap()),
+            ("z", PoincareDiskPoint::new(0.1, -0.5).unwrap()),
+        ];
+        let local = NodeId::new("local");
+        let mut snapshot = view(&[("local", PoincareDiskPoint::new(0.2, 0.2).unwrap(), 4)]);
+        snapshot.extend(view(&old.map(|(id, p)| (id, p, 1))));
+
+        let mut healing = HealingCoordinator::new(HealingConfig::default());
+        healing.begin(HealingRole::Adopt, snapshot.clone(), 0);
+
+        // Two anchors fall short of the quorum of three
+        let mut current = snapshot.clone();
+        for (id, p) in &old[..2] {
+            current.insert(NodeId::new(*id), (frame.apply(p), 2));
+        }
+        assert_eq!(healing.poll(&local, &current, 100), HealingStep::Wait);
+
+        current.insert(NodeId::new("z"), (frame.apply(&old[2].1), 2));
+        let HealingStep::Align(fit) = healing.poll(&local, &current, 200) else {
+            panic!("expected an alignment");
+        };
+        assert_eq!(fit.nodes, 3);
+        assert!(fit.rmsd < 1e-6);
+        let moved = fit.transform.apply(&snapshot[&local].0);
+        assert!(moved.hyperbolic_distance(&frame.apply(&snapshot[&local].0)) < 1e-6);
+        assert!(!healing.is_deferring());
+        assert_eq!(healing.stats().aligned, 1);
+    }
+}
@@ -0,0 +1,446 @@
+//! Node Health Monitoring for DRFE-R
+//!
+//! Aggregates internal signals of a running node into a single health status:
+//! - Background loop liveness (receivers, coordinate updater)
+//! - Router lock contention
+//! - Queue depths (in-flight packet handlers)
+//! - Checkpoint age
+//! - Neighbor count vs expected
+//! - Clock skew toward neighbors
+//!
+//! A watchdog can use `stalled_tasks` to restart background loops that stopped
+//! reporting progress.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+/// Background task name for the UDP receiver loop
+pub const TASK_UDP_RECEIVER: &str = "udp_receiver";
+/// Background task name for the TCP receiver loop
+pub const TASK_TCP_RECEIVER: &str = "tcp_receiver";
+/// Background task name for the coordinate updater loop
+pub const TASK_COORDINATE_UPDATER: &str = "coordinate_updater";
+/// Background task name for the stream, content and FEC retransmission loop
+pub const TASK_STREAM_MAINTENANCE: &str = "stream_maintenance";
+/// Background task name for the neighbor, probe and overlay maintenance loop
+pub const TASK_MAINTENANCE: &str = "maintenance";
+
+/// Overall health status of a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    /// All signals within thresholds
+    Healthy,
+    /// Node works but some signals are outside thresholds
+    Degraded,
+    /// Node cannot be expected to route correctly
+    Unhealthy,
+}
+
+impl std::fmt::Display for HealthStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HealthStatus::Healthy => write!(f, "healthy"),
+            HealthStatus::Degraded => write!(f, "degraded"),
+            HealthStatus::Unhealthy => write!(f, "unhealthy"),
+        }
+    }
+}
+
+/// Thresholds used when evaluating health signals
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthThresholds {
+    /// A task is stalled after missing this many expected beats
+    pub stall_factor: u32,
+    /// Router lock wait above this is reported as degraded (ms)
+    pub lock_wait_degraded_ms: u64,
+    /// Router lock wait above this is reported as unhealthy (ms)
+    pub lock_wait_unhealthy_ms: u64,
+    /// In-flight packet handlers above this is reported as degraded
+    pub queue_depth_degraded: usize,
+    /// In-flight packet handlers above this is reported as unhealthy
+    pub queue_depth_unhealthy: usize,
+    /// Checkpoints older than this are reported as degraded (seconds)
+    pub max_checkpoint_age_secs: u64,
+    /// Number of neighbors a well-connected node is expected to have
+    pub expected_neighbors: usize,
+    /// A neighbor clock offset above this is reported as degraded (ms)
+    pub max_clock_skew_ms: u64,
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        Self {
+            stall_factor: 3,
+            lock_wait_degraded_ms: 100,
+            lock_wait_unhealthy_ms: 1000,
+            queue_depth_degraded: 256,
+            queue_depth_unhealthy: 4096,
+            max_checkpoint_age_secs: 600,
+            expected_neighbors: 3,
+            max_clock_skew_ms: 1000,
+        }
+    }
+}
+
+/// Result of a single health check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthCheck {
+    /// Name of the signal
+    pub name: String,
+    /// Status of this signal
+    pub status: HealthStatus,
+    /// Human-readable detail
+    pub detail: String,
+}
+
+impl HealthCheck {
+    fn new(name: &str, status: HealthStatus, detail: String) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            detail,
+        }
+    }
+}
+
+/// Aggregated health report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthReport {
+    /// Worst status over all checks
+    pub status: HealthStatus,
+    /// Individual check results
+    pub checks: Vec<HealthCheck>,
+    /// Number of watchdog restarts per task
+    pub restarts: HashMap<String, u64>,
+}
+
+/// Liveness state of a background task
+struct TaskState {
+    expected_interval: Duration,
+    last_beat: Instant,
+    restarts: u64,
+}
+
+/// Collects health signals reported by the node's background services
+pub struct HealthMonitor {
+    thresholds: RwLock<HealthThresholds>,
+    tasks: RwLock<HashMap<String, TaskState>>,
+    /// Most recent router lock wait (microseconds)
+    lock_wait_us: AtomicU64,
+    /// Packets currently being handled
+    inflight_packets: AtomicUsize,
+    /// Time of the last successful checkpoint
+    last_checkpoint: RwLock<Option<Instant>>,
+    /// Largest estimated clock offset toward a neighbor (ms), if any is known
+    clock_skew_ms: RwLock<Option<u64>>,
+    /// Whether the watchdog should restart stalled tasks
+    watchdog_enabled: AtomicBool,
+}
+
+impl HealthMonitor {
+    pub fn new(thresholds: HealthThresholds) -> Self {
+        Self {
+            thresholds: RwLock::new(thresholds),
+            tasks: RwLock::new(HashMap::new()),
+            lock_wait_us: AtomicU64::new(0),
+            inflight_packets: AtomicUsize::new(0),
+            last_checkpoint: RwLock::new(None),
+            clock_skew_ms: RwLock::new(None),
+            watchdog_enabled: AtomicBool::new(false),
+        }
+    }
+
+    /// Current thresholds
+    pub fn thresholds(&self) -> HealthThresholds {
+        self.thresholds.read().unwrap().clone()
+    }
+
+    /// Replace the thresholds
+    pub fn set_thresholds(&self, thresholds: HealthThresholds) {
+        *self.thresholds.write().unwrap() = thresholds;
+    }
+
+    /// Enable or disable the watchdog
+    pub fn set_watchdog_enabled(&self, enabled: bool) {
+        self.watchdog_enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Whether the watchdog is enabled
+    pub fn watchdog_enabled(&self) -> bool {
+        self.watchdog_enabled.load(Ordering::Relaxed)
+    }
+
+    /// Register a background task that is expected to beat at least once per interval
+    pub fn register_task(&self, name: &str, expected_interval: Duration) {
+        let mut tasks = self.tasks.write().unwrap();
+        let state = tasks.entry(name.to_string()).or_insert(TaskState {
+            expected_interval,
+            last_beat: Instant::now(),
+            restarts: 0,
+        });
+        state.expected_interval = expected_interval;
+        state.last_beat = Instant::now();
+    }
+
+    /// Record progress of a background task
+    pub fn beat(&self, name: &str) {
+        if let Some(state) = self.tasks.write().unwrap().get_mut(name) {
+            state.last_beat = Instant::now();
+        }
+    }
+
+    /// Record that the watchdog restarted a task
+    pub fn record_restart(&self, name: &str) {
+        if let Some(state) = self.tasks.write().unwrap().get_mut(name) {
+            state.restarts += 1;
+            state.last_beat = Instant::now();
+        }
+    }
+
+    /// Record how long it took to acquire the router lock
+    pub fn record_lock_wait(&self, wait: Duration) {
+        self.lock_wait_us
+            .store(wait.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Mark a packet handler as started
+    pub fn packet_started(&self) {
+        self.inflight_packets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Mark a packet handler as finished
+    pub fn packet_finished(&self) {
+        let _ = self
+            .inflight_packets
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                Some(n.saturating_sub(1))
+            });
+    }
+
+    /// Number of packet handlers currently running
+    pub fn queue_depth(&self) -> usize {
+        self.inflight_packets.load(Ordering::Relaxed)
+    }
+
+    /// Record a successful checkpoint
+    pub fn record_checkpoint(&self) {
+        *self.last_checkpoint.write().unwrap() = Some(Instant::now());
+    }
+
+    /// Record the largest clock offset estimated toward a neighbor
+    pub fn record_clock_skew(&self, skew_ms: Option<u64>) {
+        *self.clock_skew_ms.write().unwrap() = skew_ms;
+    }
+
+    /// Tasks that have not beaten within `stall_factor` expected intervals
+    pub fn stalled_tasks(&self) -> Vec<String> {
+        let factor = self.thresholds.read().unwrap().stall_factor.max(1);
+        let tasks = self.tasks.read().unwrap();
+        let mut stalled: Vec<String> = tasks
+            .iter()
+            .filter(|(_, state)| state.last_beat.elapsed() > state.expected_interval * factor)
+            .map(|(name, _)| name.clone())
+            .collect();
+        stalled.sort();
+        stalled
+    }
+
+    /// Evaluate all signals into a report
+    pub fn evaluate(&self, neighbor_count: usize) -> HealthReport {
+        let thresholds = self.thresholds();
+        let mut checks = Vec::new();
+
+        // Background task liveness
+        {
+            let tasks = self.tasks.read().unwrap();
+            let mut names: Vec<&String> = tasks.keys().collect();
+            names.sort();
+            for name in names {
+                let state = &tasks[name];
+                let since = state.last_beat.elapsed();
+                let limit = state.expected_interval * thresholds.stall_factor.max(1);
+                let status = if since > limit {
+                    HealthStatus::Unhealthy
+                } else {
+                    HealthStatus::Healthy
+                };
+                checks.push(HealthCheck::new(
+                    &format!("task:{}", name),
+                    status,
+                    format!("last beat {}ms ago (limit {}ms)", since.as_millis(), limit.as_millis()),
+                ));
+            }
+        }
+
+        // Router lock contention
+        let wait_ms = self.lock_wait_us.load(Ordering::Relaxed) / 1000;
+        let status = if wait_ms >= thresholds.lock_wait_unhealthy_ms {
+            HealthStatus::Unhealthy
+        } else if wait_ms >= thresholds.lock_wait_degraded_ms {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Healthy
+        };
+        checks.push(HealthCheck::new(
+            "router_lock",
+            status,
+            format!("last wait {}ms", wait_ms),
+        ));
+
+        // Queue depth
+        let depth = self.queue_depth();
+        let status = if depth >= thresholds.queue_depth_unhealthy {
+            HealthStatus::Unhealthy
+        } else if depth >= thresholds.queue_depth_degraded {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Healthy
+        };
+        checks.push(HealthCheck::new(
+            "queue_depth",
+            status,
+            format!("{} packets in flight", depth),
+        ));
+
+        // Checkpoint age
+        let check = match *self.last_checkpoint.read().unwrap() {
+            Some(at) => {
+                let age = at.elapsed().as_secs();
+                let status = if age > thresholds.max_checkpoint_age_secs {
+                    HealthStatus::Degraded
+                } else {
+                    HealthStatus::Healthy
+                };
+                HealthCheck::new("checkpoint_age", status, format!("{}s", age))
+            }
+            None => HealthCheck::new(
+                "checkpoint_age",
+                HealthStatus::Healthy,
+                "no checkpoint recorded".to_string(),
+            ),
+        };
+        checks.push(check);
+
+        // Neighbor count
+        let status = if thresholds.expected_neighbors == 0 || neighbor_count >= thresholds.expected_neighbors {
+            HealthStatus::Healthy
+        } else if neighbor_count == 0 {
+            HealthStatus::Unhealthy
+        } else {
+            HealthStatus::Degraded
+        };
+        checks.push(HealthCheck::new(
+            "neighbors",
+            status,
+            format!("{} of {} expected", neighbor_count, thresholds.expected_neighbors),
+        ));
+
+        // Clock skew; timestamps of a skewed peer risk failing freshness checks
+        let check = match *self.clock_skew_ms.read().unwrap() {
+            Some(skew) => {
+                let status = if skew > thresholds.max_clock_skew_ms {
+                    HealthStatus::Degraded
+                } else {
+                    HealthStatus::Healthy
+                };
+                HealthCheck::new(
+                    "clock_skew",
+                    status,
+                    format!("largest neighbor offset {}ms (limit {}ms)", skew, thresholds.max_clock_skew_ms),
+                )
+            }
+            None => HealthCheck::new("clock_skew", HealthStatus::Healthy, "no offset estimated".to_string()),
+        };
+        checks.push(check);
+
+        let status = checks
+            .iter()
+            .map(|c| c.status)
+            .max()
+            .unwrap_or(HealthStatus::Healthy);
+
+        let restarts = self
+            .tasks
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, state)| (name.clone(), state.restarts))
+            .collect();
+
+        HealthReport {
+            status,
+            checks,
+            restarts,
+        }
+    }
+}
+
+impl Default for HealthMonitor {
+    fn default() -> Self {
+        Self::new(HealthThresholds::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_aggregation() {
+        let monitor = HealthMonitor::default();
+
+        let report = monitor.evaluate(5);
+        assert_eq!(report.status, HealthStatus::Healthy);
+
+        let report = monitor.evaluate(1);
+        assert_eq!(report.status, HealthStatus::Degraded);
+
+        let report = monitor.evaluate(0);
+        assert_eq!(report.status, HealthStatus::Unhealthy);
+    }
+
+    #[test]
+    fn test_lock_contention_and_queue_depth() {
+        let monitor = HealthMonitor::default();
+        monitor.record_lock_wait(Duration::from_millis(150));
+        assert_eq!(monitor.evaluate(5).status, HealthStatus::Degraded);
+
+        monitor.record_lock_wait(Duration::from_millis(0));
+        for _ in 0..300 {
+            monitor.packet_started();
+        }
+        assert_eq!(monitor.evaluate(5).status, HealthStatus::Degraded);
+        for _ in 0..300 {
+            monitor.packet_finished();
+        }
+        assert_eq!(monitor.queue_depth(), 0);
+        assert_eq!(monitor.evaluate(5).status, HealthStatus::Healthy);
+
+        monitor.record_clock_skew(Some(2500));
+        let report = monitor.evaluate(5);
+        assert_eq!(report.status, HealthStatus::Degraded);
+        assert!(report.checks.iter().any(|c| c.name == "clock_skew" && c.detail.contains("2500ms")));
+        monitor.record_clock_skew(Some(200));
+        assert_eq!(monitor.evaluate(5).status, HealthStatus::Healthy);
+    }
+
+    #[test]
+    fn test_stalled_task_detection() {
+        let monitor = HealthMonitor::default();
+        monitor.register_task("fast", Duration::from_millis(1));
+        monitor.register_task("slow", Duration::from_secs(60));
+
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(monitor.stalled_tasks(), vec!["fast".to_string()]);
+        assert_eq!(monitor.evaluate(5).status, HealthStatus::Unhealthy);
+
+        monitor.record_restart("fast");
+        let report = monitor.evaluate(5);
+        assert_eq!(report.restarts["fast"], 1);
+        assert!(monitor.stalled_tasks().is_empty());
+    }
+}
@@ -0,0 +1,374 @@
+//! Adaptive Heartbeats
+//!
+//! A fixed heartbeat interval fits neither extreme: stable overlays pay for
+//! liveness traffic nobody needs, while churning ones notice failures late.
+//! With adaptive heartbeats the interval toward each neighbor shrinks with
+//! recent churn (neighbor joins and failures in a sliding window) and is
+//! floored by a multiple of that neighbor's smoothed RTT, within configured
+//! bounds.
+//!
+//! Heartbeats advertise the sender's current interval so the receiver can
+//! scale its failure deadline to match, and echo the peer's last heartbeat
+//! timestamp so RTT is measured without synchronized clocks. Other traffic
+//! counts as liveness too: packets carry their last hop, which refreshes the
+//! neighbor on the receiving side and postpones the next explicit heartbeat
+//! on the sending side.
+//!
+//! Heartbeats to each neighbor are numbered. Gaps in the numbers a node
+//! receives give the inbound loss of the link, and each heartbeat reports
+//! that figure back, so both ends also learn the outbound loss and can spot
+//! links that only work in one direction.
+//!
+//! Each heartbeat also carries a digest of the sender's view: its own
+//! coordinate version and hashes of the nodes it knows and of their
+//! coordinate versions. A receiver holding an older version of the sender's
+//! coordinate, or whose own digest differs, asks the sender for a resync
+//! instead of waiting for the next periodic coordinate broadcast.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::coordinates::NodeId;
+
+/// Adaptive heartbeat settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdaptiveHeartbeatConfig {
+    /// Adapt intervals; when off, every neighbor gets the fixed interval
+    pub enabled: bool,
+    pub min_interval_ms: u64,
+    pub max_interval_ms: u64,
+    /// Never heartbeat a neighbor more often than this many smoothed RTTs
+    pub rtt_multiplier: f64,
+    /// Window over which neighbor joins and failures count as churn
+    pub churn_window_ms: u64,
+    /// Interval reduction per churn event: max / (1 + weight × events)
+    pub churn_weight: f64,
+    /// Advertised intervals a neighbor may stay silent before it is declared failed
+    pub miss_tolerance: u32,
+}
+
+impl Default for AdaptiveHeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_interval_ms: 250,
+            max_interval_ms: 10_000,
+            rtt_multiplier: 4.0,
+            churn_window_ms: 60_000,
+            churn_weight: 1.0,
+            miss_tolerance: 3,
+        }
+    }
+}
+
+impl AdaptiveHeartbeatConfig {
+    /// Heartbeat interval toward a neighbor with smoothed RTT `srtt`
+    pub fn interval(&self, srtt: Duration, churn_events: usize) -> Duration {
+        let max = self.max_interval_ms as f64;
+        let churned = max / (1.0 + self.churn_weight * churn_events as f64);
+        let floor = (self.rtt_multiplier * srtt.as_secs_f64() * 1000.0).max(self.min_interval_ms as f64);
+        Duration::from_millis(churned.max(floor).min(max) as u64)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_interval_ms == 0 || self.min_interval_ms > self.max_interval_ms {
+            return Err("heartbeat intervals must satisfy 0 < min_interval_ms <= max_interval_ms".to_string());
+        }
+        if self.rtt_multiplier.is_nan() || self.rtt_multiplier < 0.0 || self.churn_weight.is_nan() || self.churn_weight < 0.0 {
+            return Err("rtt_multiplier and churn_weight must be non-negative".to_string());
+        }
+        if self.miss_tolerance == 0 {
+            return Err("miss_tolerance must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Heartbeat fields after the flags byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct HeartbeatInfo {
+    /// Time until the sender's next heartbeat to the receiver
+    pub interval_ms: u64,
+    /// Timestamp of the receiver's last heartbeat seen by the sender, and
+    /// how long the sender held it before this reply
+    pub echo: Option<(u64, u64)>,
+    /// Sequence number of this heartbeat on the sender-to-receiver link
+    pub seq: u64,
+    /// Share of the receiver's heartbeats the sender got, in per mille
+    pub reception: Option<u16>,
+}
+
+/// Summary of the sender's view, after the `HeartbeatInfo` of a heartbeat
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct HeartbeatDigest {
+    /// Version of the sender's own coordinate
+    pub coord_version: u64,
+    /// Hash of the nodes the sender knows, itself included
+    pub partition: u64,
+    /// Hash of those nodes with the coordinate versions the sender holds
+    pub routing: u64,
+    /// Number of those nodes, or zero from senders that predate the count
+    pub nodes: u64,
+}
+
+impl HeartbeatDigest {
+    /// Digest of `view`, the coordinate version held for every known node
+    pub fn of(coord_version: u64, view: &BTreeMap<NodeId, u64>) -> Self {
+        let (mut partition, mut routing) = (Sha256::new(), Sha256::new());
+        for (node, version) in view {
+            for hasher in [&mut partition, &mut routing] {
+                hasher.update(node.0.as_bytes());
+                hasher.update([0]);
+            }
+            routing.update(version.to_le_bytes());
+        }
+        let truncate = |hasher: Sha256| {
+            let digest: [u8; 32] = hasher.finalize().into();
+            u64::from_le_bytes(digest[..8].try_into().unwrap())
+        };
+        Self {
+            coord_version,
+            partition: truncate(partition),
+            routing: truncate(routing),
+            nodes: view.len() as u64,
+        }
+    }
+
+    /// Whether a receiver with digest `local`, holding version `known_version`
+    /// of the sender's coordinate, missed an update or sees a different overlay
+    pub fn diverges(&self, local: &HeartbeatDigest, known_version: u64) -> bool {
+        self.coord_version > known_version || self.partition != local.partition || self.routing != local.routing
+    }
+}
+
+/// Least time between resyncs with one neighbor, in either direction
+pub const MIN_RESYNC_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Sequence numbers covered by the loss estimate
+pub const LOSS_WINDOW: u64 = 64;
+/// Heartbeats needed before a delivery ratio is reported
+pub const MIN_LOSS_SAMPLES: u64 = 8;
+/// Delivery ratios further apart than this make a link asymmetric
+const ASYMMETRY_THRESHOLD: f64 = 0.5;
+/// Expected transmissions above which a link counts as poor
+const POOR_ETX: f64 = 2.0;
+/// Routing distance added per expected retransmission
+const COST_PER_RETRANSMISSION: f64 = 0.5;
+
+/// Which of a neighbor's recent heartbeat sequence numbers arrived
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SequenceWindow {
+    highest: Option<u64>,
+    /// Bit i set if `highest - i` arrived
+    received: u64,
+    /// Sequence numbers covered, up to `LOSS_WINDOW`
+    span: u64,
+}
+
+impl SequenceWindow {
+    pub fn record(&mut self, seq: u64) {
+        match self.highest {
+            Some(highest) if seq > highest => {
+                let shift = seq - highest;
+                self.received = if shift >= LOSS_WINDOW { 0 } else { self.received << shift };
+                self.received |= 1;
+                self.highest = Some(seq);
+                self.span = (self.span + shift).min(LOSS_WINDOW);
+            }
+            // Late or duplicate heartbeat within the window
+            Some(highest) if highest - seq < LOSS_WINDOW => {
+                self.received |= 1 << (highest - seq);
+            }
+            // First heartbeat, or numbering restarted with the neighbor
+            _ => *self = Self { highest: Some(seq), received: 1, span: 1 },
+        }
+    }
+
+    pub fn samples(&self) -> u64 {
+        self.span
+    }
+
+    /// Share of the covered sequence numbers that arrived
+    pub fn delivery_ratio(&self) -> Option<f64> {
+        if self.span < MIN_LOSS_SAMPLES {
+            return None;
+        }
+        let mask = if self.span >= 64 { u64::MAX } else { (1 << self.span) - 1 };
+        Some((self.received & mask).count_ones() as f64 / self.span as f64)
+    }
+}
+
+/// Heartbeat delivery in both directions of a neighbor link
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinkQuality {
+    /// Share of the neighbor's recent heartbeats that reached us
+    pub inbound_delivery: Option<f64>,
+    /// Share of our recent heartbeats the neighbor reports receiving
+    pub outbound_delivery: Option<f64>,
+}
+
+impl LinkQuality {
+    /// Whether either direction has enough heartbeats for an estimate
+    pub fn is_measured(&self) -> bool {
+        self.inbound_delivery.is_some() || self.outbound_delivery.is_some()
+    }
+
+    /// Estimated loss of a packet sent over the link, assuming unknown directions are lossless
+    pub fn loss(&self) -> f64 {
+        1.0 - self.inbound_delivery.unwrap_or(1.0) * self.outbound_delivery.unwrap_or(1.0)
+    }
+
+    /// Difference between the two directions; zero until both are measured
+    pub fn asymmetry(&self) -> f64 {
+        match (self.inbound_delivery, self.outbound_delivery) {
+            (Some(inbound), Some(outbound)) => (inbound - outbound).abs(),
+            _ => 0.0,
+        }
+    }
+
+    /// One direction works much better than the other, e.g. we hear the
+    /// neighbor but it does not hear us
+    pub fn is_asymmetric(&self) -> bool {
+        self.asymmetry() > ASYMMETRY_THRESHOLD
+    }
+
+    /// Expected transmissions per delivered packet (ETX), capped at 100
+    pub fn etx(&self) -> f64 {
+        (1.0 / (1.0 - self.loss()).max(0.01)).min(100.0)
+    }
+
+    /// Link too lossy or one-sided to rely on when there is a choice
+    pub fn is_poor(&self) -> bool {
+        self.etx() > POOR_ETX || self.is_asymmetric()
+    }
+
+    /// Distance penalty for routing over this link
+    pub fn routing_cost(&self) -> f64 {
+        (self.etx() - 1.0) * COST_PER_RETRANSMISSION
+    }
+}
+
+/// Smoothed RTT update with the RFC 6298 gain of 1/8
+pub fn smooth_rtt(srtt: Duration, sample: Duration) -> Duration {
+    if srtt.is_zero() {
+        sample
+    } else {
+        (srtt * 7 + sample) / 8
+    }
+}
+
+/// Neighbor joins and failures in a sliding window
+#[derive(Debug, Default)]
+pub struct ChurnTracker {
+    events: VecDeque<Instant>,
+}
+
+impl ChurnTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, at: Instant) {
+        self.events.push_back(at);
+    }
+
+    /// Events within `window` before `now`, forgetting older ones
+    pub fn count(&mut self, window: Duration, now: Instant) -> usize {
+        while self.events.front().is_some_and(|at| now.duration_since(*at) > window) {
+            self.events.pop_front();
+        }
+        self.events.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interval_adapts_to_churn_and_rtt() {
+        let config = AdaptiveHeartbeatConfig { enabled: true, ..Default::default() };
+        let stable = config.interval(Duration::from_millis(10), 0);
+        let churning = config.interval(Duration::from_millis(10), 9);
+        assert_eq!(stable, Duration::from_millis(10_000));
+        assert_eq!(churning, Duration::from_millis(1_000));
+        // Heavy churn hits the minimum, a slow link raises it
+        assert_eq!(config.interval(Duration::ZERO, 1000), Duration::from_millis(250));
+        assert_eq!(config.interval(Duration::from_millis(500), 1000), Duration::from_millis(2_000));
+
+        assert!(config.validate().is_ok());
+        assert!(AdaptiveHeartbeatConfig { min_interval_ms: 20_000, ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_churn_window_and_rtt_smoothing() {
+        let start = Instant::now();
+        let mut churn = ChurnTracker::new();
+        churn.record(start);
+        churn.record(start + Duration::from_secs(30));
+        assert_eq!(churn.count(Duration::from_secs(60), start + Duration::from_secs(40)), 2);
+        assert_eq!(churn.count(Duration::from_secs(60), start + Duration::from_secs(70)), 1);
+
+        let srtt = smooth_rtt(Duration::ZERO, Duration::from_millis(80));
+        assert_eq!(srtt, Duration::from_millis(80));
+        assert_eq!(smooth_rtt(srtt, Duration::from_millis(160)), Duration::from_millis(90));
+    }
+
+    #[test]
+    fn test_sequence_window_loss_and_asymmetry() {
+        let mut window = SequenceWindow::default();
+        for seq in (0..20).filter(|s| s % 4 != 1) {
+            window.record(seq);
+        }
+        // 15 of 20 arrived; a late arrival fills its gap
+        assert_eq!(window.delivery_ratio(), Some(0.75));
+        window.record(1);
+        assert_eq!(window.delivery_ratio(), Some(0.8));
+
+        // The window slides, and a restarted sender starts over
+        for seq in 20..200 {
+            window.record(seq);
+        }
+        assert_eq!(window.delivery_ratio(), Some(1.0));
+        assert_eq!(window.samples(), LOSS_WINDOW);
+        window.record(0);
+        assert_eq!(window.delivery_ratio(), None);
+
+        let healthy = LinkQuality { inbound_delivery: Some(1.0), outbound_delivery: Some(0.9) };
+        assert!(!healthy.is_poor());
+        assert!((healthy.etx() - 1.0 / 0.9).abs() < 1e-9);
+        // We hear them, they barely hear us
+        let one_way = LinkQuality { inbound_delivery: Some(1.0), outbound_delivery: Some(0.3) };
+        assert!(one_way.is_asymmetric() && one_way.is_poor());
+        assert!(one_way.routing_cost() > healthy.routing_cost());
+        assert_eq!(LinkQuality::default().routing_cost(), 0.0);
+    }
+
+    #[test]
+    fn test_digest_detects_divergence() {
+        let view: BTreeMap<NodeId, u64> = [("a", 3), ("b", 1), ("c", 7)].iter().map(|(n, v)| (NodeId::new(*n), *v)).collect();
+        let digest = HeartbeatDigest::of(3, &view);
+        assert_eq!(digest, HeartbeatDigest::of(3, &view.clone()));
+        assert!(!digest.diverges(&digest, 3));
+        // The receiver missed the sender's latest coordinate
+        assert!(digest.diverges(&digest, 2));
+
+        // A newer version of one node changes only the routing hash
+        let mut newer = view.clone();
+        newer.insert(NodeId::new("b"), 2);
+        let newer = HeartbeatDigest::of(3, &newer);
+        assert_eq!(newer.partition, digest.partition);
+        assert_ne!(newer.routing, digest.routing);
+        assert!(newer.diverges(&digest, 3));
+
+        // A node missing from the view changes both
+        let mut partial = view;
+        partial.remove(&NodeId::new("c"));
+        let partial = HeartbeatDigest::of(3, &partial);
+        assert_ne!(partial.partition, digest.partition);
+        assert!(digest.diverges(&partial, 3));
+    }
+}
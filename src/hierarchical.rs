@@ -6,6 +6,7 @@
 //! - Building a super-graph connecting clusters via gateway nodes
 //! - Two-level routing: inter-cluster then intra-cluster
 
+use crate::clustering::{k_medoids, Clustering, DEFAULT_MAX_ITERATIONS};
 use crate::coordinates::{NodeId, RoutingCoordinate};
 use crate::greedy_embedding::GreedyEmbedding;
 use crate::ricci::{AdaptiveRicciFlow, GraphNode, RicciGraph};
@@ -15,7 +16,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 
 /// Cluster identifier
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct ClusterId(pub String);
 
 impl ClusterId {
@@ -114,8 +115,9 @@ impl LocalCluster {
         
         // Update router coordinates from Ricci graph
         for (id, graph_node) in &self.ricci_graph.nodes {
-            if let Some(router_node) = self.router.get_node_mut(id) {
-                router_node.coord.point = graph_node.coord.point;
+            if let Some(router_node) = self.router.get_node(id) {
+                let coord = RoutingCoordinate::new(graph_node.coord.point, router_node.coord.updated_at);
+                self.router.set_coordinate(id, coord);
             }
         }
         
@@ -166,12 +168,42 @@ impl HierarchicalDRFER {
         edges: Vec<(NodeId, NodeId)>,
         target_cluster_size: usize,
     ) -> Self {
-        let mut system = Self::new(target_cluster_size);
-        
-        // Step 1: Partition nodes into clusters using simple geometric partitioning
+        // Partition nodes into clusters using simple geometric partitioning
         let clusters = Self::partition_nodes(&nodes, target_cluster_size);
-        
-        // Step 2: Create local clusters and assign nodes
+        Self::build_from_partition(nodes, edges, target_cluster_size, clusters)
+    }
+
+    /// Build hierarchical structure with clusters from hyperbolic k-medoids
+    ///
+    /// Unlike angular partitioning this keeps nodes at different depths of
+    /// the disk apart. Pass the clustering of an earlier build as `previous`
+    /// to keep its cluster IDs for clusters that share members.
+    pub fn build_with_hyperbolic_clusters(
+        nodes: Vec<RoutingNode>,
+        edges: Vec<(NodeId, NodeId)>,
+        target_cluster_size: usize,
+        previous: Option<&Clustering>,
+    ) -> (Self, Clustering) {
+        let points: Vec<(NodeId, PoincareDiskPoint)> = nodes.iter().map(|n| (n.id.clone(), n.coord.point)).collect();
+        let k = nodes.len().div_ceil(target_cluster_size.max(1)).max(1);
+        let mut clustering = k_medoids(&points, k, DEFAULT_MAX_ITERATIONS);
+        if let Some(previous) = previous {
+            clustering.relabel(previous);
+        }
+        let system = Self::build_from_partition(nodes, edges, target_cluster_size, clustering.partition());
+        (system, clustering)
+    }
+
+    /// Build hierarchical structure from a given node-to-cluster partition
+    pub fn build_from_partition(
+        nodes: Vec<RoutingNode>,
+        edges: Vec<(NodeId, NodeId)>,
+        target_cluster_size: usize,
+        clusters: HashMap<ClusterId, HashSet<NodeId>>,
+    ) -> Self {
+        let mut system = Self::new(target_cluster_size);
+
+        // Step 1: Create local clusters and assign nodes
         for (cluster_id, node_ids) in clusters {
             let mut cluster = LocalCluster::new(cluster_id.clone());
             
@@ -185,7 +217,7 @@ impl HierarchicalDRFER {
             system.clusters.insert(cluster_id, cluster);
         }
         
-        // Step 3: Add edges to appropriate clusters and identify gateway nodes
+        // Step 2: Add edges to appropriate clusters and identify gateway nodes
         for (u, v) in edges {
             let cluster_u = system.node_cluster_map.get(&u).cloned();
             let cluster_v = system.node_cluster_map.get(&v).cloned();
@@ -212,12 +244,12 @@ impl HierarchicalDRFER {
             }
         }
         
-        // Step 4: Compute cluster centroids
+        // Step 3: Compute cluster centroids
         for cluster in system.clusters.values_mut() {
             cluster.compute_centroid();
         }
         
-        // Step 5: Build super-graph
+        // Step 4: Build super-graph
         system.build_super_graph();
         
         system
@@ -529,4 +561,24 @@ mod tests {
         assert_eq!(stats.num_clusters, 0);
         assert_eq!(stats.total_nodes, 0);
     }
+
+    #[test]
+    fn test_build_with_hyperbolic_clusters() {
+        let nodes: Vec<RoutingNode> = (0..6)
+            .map(|i| {
+                let angle = if i < 3 { 0.0 } else { std::f64::consts::PI } + i as f64 * 0.01;
+                let point = PoincareDiskPoint::from_polar(0.5, angle).unwrap();
+                RoutingNode::new(NodeId::new(format!("n{}", i)), RoutingCoordinate::new(point, 0))
+            })
+            .collect();
+        let edges = vec![(NodeId::new("n0"), NodeId::new("n1")), (NodeId::new("n2"), NodeId::new("n3"))];
+
+        let (system, clustering) = HierarchicalDRFER::build_with_hyperbolic_clusters(nodes.clone(), edges.clone(), 3, None);
+        assert_eq!(system.clusters.len(), 2);
+        assert_eq!(system.inter_cluster_edges.len(), 1);
+        assert_eq!(system.node_cluster_map.get(&NodeId::new("n0")), clustering.cluster_of(&NodeId::new("n0")));
+
+        let (rebuilt, _) = HierarchicalDRFER::build_with_hyperbolic_clusters(nodes, edges, 3, Some(&clustering));
+        assert_eq!(rebuilt.node_cluster_map, system.node_cluster_map);
+    }
 }
@@ -0,0 +1,221 @@
+//! Hybrid Logical Clocks
+//!
+//! Wall clocks on different nodes drift apart, and a node whose clock jumps
+//! back can reuse packet IDs or stamp events out of order. A hybrid logical
+//! clock (HLC) pairs the wall clock with a logical counter: its readings
+//! never go backwards, stay within the clock drift of wall time, and, since
+//! a node merges the readings it receives, order events causally across
+//! nodes.
+//!
+//! A reading packs the physical milliseconds in its upper 48 bits and the
+//! logical counter in its lower 16, so readings compare as plain integers
+//! and fit the `u64` fields that carry versions on the wire. The process
+//! shares one clock, read through `now()`; it stamps packet IDs,
+//! coordinate versions and audit entries.
+//!
+//! A remote reading further ahead of the local wall clock than the
+//! configured drift is not merged, so one peer with a clock set far in the
+//! future cannot drag every node's clock along. Skew between peers is
+//! estimated separately from heartbeat timestamps, see `offset_sample`.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+/// Bits of a reading that hold the logical counter
+const LOGICAL_BITS: u32 = 16;
+const LOGICAL_MASK: u64 = (1 << LOGICAL_BITS) - 1;
+
+/// A hybrid logical clock reading
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Hlc(pub u64);
+
+impl Hlc {
+    pub fn new(physical_ms: u64, logical: u16) -> Self {
+        Self((physical_ms << LOGICAL_BITS) | logical as u64)
+    }
+
+    /// Wall-clock milliseconds of the reading
+    pub fn physical_ms(&self) -> u64 {
+        self.0 >> LOGICAL_BITS
+    }
+
+    /// Events counted within the same millisecond
+    pub fn logical(&self) -> u16 {
+        (self.0 & LOGICAL_MASK) as u16
+    }
+
+    /// Seconds of the reading, a coarse epoch for comparing versions
+    ///
+    /// A legacy counter version, with no physical part, is epoch zero.
+    pub fn epoch_secs(&self) -> u64 {
+        self.physical_ms() / 1000
+    }
+}
+
+impl std::fmt::Display for Hlc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Clock tolerances of a node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClockConfig {
+    /// Furthest a remote reading may be ahead of the wall clock and still be merged
+    pub max_drift_ms: u64,
+    /// Largest estimated peer clock offset the replay freshness check
+    /// compensates for; zero checks timestamps against the local clock only
+    pub max_compensation_ms: u64,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            max_drift_ms: 60_000,
+            max_compensation_ms: 30_000,
+        }
+    }
+}
+
+impl ClockConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_drift_ms == 0 {
+            return Err("clock max_drift_ms must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    /// `offset_ms` limited to the compensation the replay check allows
+    pub fn compensation(&self, offset_ms: i64) -> i64 {
+        let max = self.max_compensation_ms.min(i64::MAX as u64) as i64;
+        offset_ms.clamp(-max, max)
+    }
+}
+
+/// A hybrid logical clock
+#[derive(Debug, Default)]
+pub struct HybridClock {
+    last: AtomicU64,
+}
+
+impl HybridClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Last reading issued or merged
+    pub fn last(&self) -> Hlc {
+        Hlc(self.last.load(Ordering::Acquire))
+    }
+
+    /// Advance the clock to at least `floor` and past its last reading
+    fn advance(&self, floor: u64) -> Hlc {
+        let mut last = self.last.load(Ordering::Acquire);
+        loop {
+            let next = floor.max(last + 1);
+            match self.last.compare_exchange_weak(last, next, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => return Hlc(next),
+                Err(current) => last = current,
+            }
+        }
+    }
+
+    /// Reading for a local event at wall time `wall_ms`
+    pub fn tick(&self, wall_ms: u64) -> Hlc {
+        self.advance(Hlc::new(wall_ms, 0).0)
+    }
+
+    /// Merge a reading received from a peer, returning the local reading after it
+    ///
+    /// A reading more than `max_drift_ms` ahead of `wall_ms` is ignored, and
+    /// the clock only ticks.
+    pub fn observe(&self, remote: Hlc, wall_ms: u64, max_drift_ms: u64) -> Hlc {
+        let local = Hlc::new(wall_ms, 0).0;
+        if remote.physical_ms() > wall_ms.saturating_add(max_drift_ms) {
+            return self.advance(local);
+        }
+        self.advance(local.max(remote.0.saturating_add(1)))
+    }
+}
+
+/// The process-wide clock
+pub fn clock() -> &'static HybridClock {
+    static CLOCK: OnceLock<HybridClock> = OnceLock::new();
+    CLOCK.get_or_init(HybridClock::new)
+}
+
+/// Reading of the process-wide clock for a local event now
+pub fn now() -> Hlc {
+    let wall_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    clock().tick(wall_ms)
+}
+
+/// Offset of a peer's clock from ours, positive if the peer is ahead
+///
+/// `sent_ms` is the peer's wall time when it sent a packet we received at
+/// our wall time `received_ms`; half the round trip `rtt_ms` is taken as
+/// the one-way delay.
+pub fn offset_sample(sent_ms: u64, received_ms: u64, rtt_ms: u64) -> i64 {
+    sent_ms as i64 + (rtt_ms / 2) as i64 - received_ms as i64
+}
+
+/// Fold an offset sample into a running estimate, weighting it by 1/8
+pub fn smooth_offset(estimate: Option<i64>, sample: i64) -> i64 {
+    match estimate {
+        Some(estimate) => estimate + (sample - estimate) / 8,
+        None => sample,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readings_never_go_backwards() {
+        let clock = HybridClock::new();
+        let first = clock.tick(1_000);
+        assert_eq!((first.physical_ms(), first.logical()), (1_000, 0));
+        // Within one millisecond, and when the wall clock steps back, the counter moves on
+        assert_eq!(clock.tick(1_000), Hlc::new(1_000, 1));
+        assert_eq!(clock.tick(900), Hlc::new(1_000, 2));
+        assert_eq!(clock.tick(1_001), Hlc::new(1_001, 0));
+
+        // A peer ahead pulls the clock past its reading
+        let merged = clock.observe(Hlc::new(5_000, 3), 1_002, 60_000);
+        assert_eq!(merged, Hlc::new(5_000, 4));
+        assert!(clock.tick(1_003) > merged);
+        // One beyond the drift bound does not
+        let ticked = clock.observe(Hlc::new(1_000_000, 0), 1_004, 60_000);
+        assert_eq!(ticked.physical_ms(), 5_000);
+        assert_eq!(Hlc::new(5_000, 4).epoch_secs(), 5);
+        assert_eq!(Hlc(7).epoch_secs(), 0);
+    }
+
+    #[test]
+    fn test_offset_estimate() {
+        // The peer sent at its 10_000 ms, we got it at our 9_050 ms over a 100 ms round trip
+        assert_eq!(offset_sample(10_000, 9_050, 100), 1_000);
+        assert_eq!(offset_sample(9_000, 9_050, 100), 0);
+
+        let mut estimate = None;
+        for _ in 0..64 {
+            estimate = Some(smooth_offset(estimate, -2_000));
+        }
+        assert_eq!(estimate, Some(-2_000));
+        assert_eq!(smooth_offset(Some(0), 800), 100);
+
+        let config = ClockConfig::default();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.compensation(-45_000), -30_000);
+        assert_eq!(config.compensation(1_200), 1_200);
+        assert_eq!(ClockConfig { max_compensation_ms: 0, ..config }.compensation(1_200), 0);
+    }
+}
@@ -59,6 +59,7 @@ impl H2Coordinate {
 }
 
 /// HYPER-PRESS routing engine
+#[derive(Clone)]
 pub struct HyperPress {
     /// H^2 coordinates for each node
     coordinates: HashMap<NodeId, H2Coordinate>,
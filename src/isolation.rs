@@ -0,0 +1,127 @@
+//! Overlay Isolation
+//!
+//! Several independent overlays can share hosts, ports and broadcast
+//! domains. Every packet carries the network ID of the overlay it belongs
+//! to, and nodes drop packets from any other overlay, so discovery never
+//! links nodes across overlays.
+//!
+//! A network ID alone only guards against typos. With a key configured,
+//! packets also carry an HMAC-SHA256 tag over the ID, and nodes without the
+//! key cannot join. This prevents accidental merges, for example a test
+//! overlay that reuses a production ID. It does not authenticate
+//! individual packets, because the tag is the same on every packet.
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+
+const TAG_CONTEXT: &[u8] = b"drfe-r/network-id";
+
+/// Packets rejected by overlay isolation
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum IsolationError {
+    #[error("Packet from network {got:?}, expected {expected:?}")]
+    ForeignNetwork { expected: String, got: String },
+
+    #[error("Missing network tag")]
+    MissingTag,
+
+    #[error("Invalid network tag")]
+    InvalidTag,
+}
+
+impl IsolationError {
+    /// Stable identifier for programmatic handling
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::ForeignNetwork { .. } => "isolation.foreign_network",
+            Self::MissingTag => "isolation.missing_tag",
+            Self::InvalidTag => "isolation.invalid_tag",
+        }
+    }
+}
+
+/// Overlay a node belongs to
+///
+/// The default, an empty ID without a key, is the overlay of nodes that
+/// predate network IDs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkIdentity {
+    pub network_id: String,
+    /// Shared secret; when set, packets must carry a matching tag
+    pub key: Option<Vec<u8>>,
+}
+
+impl NetworkIdentity {
+    pub fn new(network_id: impl Into<String>) -> Self {
+        Self {
+            network_id: network_id.into(),
+            key: None,
+        }
+    }
+
+    /// Require a tag keyed with `key` on every packet
+    pub fn with_key(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.key = Some(key.into());
+        self
+    }
+
+    /// Tag to put on outgoing packets, if a key is configured
+    pub fn tag(&self) -> Option<Vec<u8>> {
+        let key = self.key.as_ref()?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+        mac.update(TAG_CONTEXT);
+        mac.update(self.network_id.as_bytes());
+        Some(mac.finalize().into_bytes().to_vec())
+    }
+
+    /// Check the network ID and tag of an incoming packet
+    pub fn admit(&self, network_id: &str, tag: Option<&[u8]>) -> Result<(), IsolationError> {
+        if network_id != self.network_id {
+            return Err(IsolationError::ForeignNetwork {
+                expected: self.network_id.clone(),
+                got: network_id.to_string(),
+            });
+        }
+        let Some(expected) = self.tag() else {
+            return Ok(());
+        };
+        let tag = tag.ok_or(IsolationError::MissingTag)?;
+        if !bool::from(expected.ct_eq(tag)) {
+            return Err(IsolationError::InvalidTag);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_is_hmac_sha256_of_the_network_id() {
+        // HMAC-SHA256("secret", "drfe-r/network-id" || "prod"), as computed by nodes before
+        let tag = NetworkIdentity::new("prod").with_key(b"secret".to_vec()).tag().unwrap();
+        let hex: String = tag.iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(hex, "4450edccf433cbb48ba841bc1ef07441d899e6211502b43bc7ac82d6616efa83");
+    }
+
+    #[test]
+    fn test_admit_checks_network_id_and_tag() {
+        let open = NetworkIdentity::new("staging");
+        assert!(open.admit("staging", None).is_ok());
+        assert_eq!(open.admit("", None).unwrap_err().code(), "isolation.foreign_network");
+
+        let keyed = NetworkIdentity::new("prod").with_key(b"secret".to_vec());
+        let tag = keyed.tag().unwrap();
+        assert!(keyed.admit("prod", Some(&tag)).is_ok());
+        assert_eq!(keyed.admit("prod", None), Err(IsolationError::MissingTag));
+
+        // Same ID, different key: a test overlay accidentally named "prod"
+        let other = NetworkIdentity::new("prod").with_key(b"other".to_vec());
+        assert_eq!(keyed.admit("prod", other.tag().as_deref()), Err(IsolationError::InvalidTag));
+    }
+}
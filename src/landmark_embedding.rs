@@ -10,10 +10,20 @@
 //! 3. Use classical MDS to position landmarks in Euclidean space
 //! 4. Map to Poincaré disk and refine via hyperbolic stress minimization
 //! 5. Triangulate remaining nodes using landmark distances
+//!
+//! On the open Internet the adjacency graph is unknown, but round-trip
+//! times are measurable. `embed_rtt_landmarks` runs steps 3-4 on the
+//! landmarks' pairwise RTT matrix instead of hop counts, and each other
+//! host places itself with `locate_by_rtt` from its own RTTs to the
+//! landmarks, as in GNP. `RttLandmarkFrame::vivaldi_update` then refines a
+//! coordinate from ongoing RTT samples to any peer.
 
 use crate::coordinates::{NodeId, RoutingCoordinate};
+use crate::graph::{BfsScratch, BitSet, CsrGraph};
+use crate::greedy_embedding::EmbeddingError;
 use crate::PoincareDiskPoint;
-use std::collections::{HashMap, HashSet, VecDeque};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// Configuration for Landmark-MDS embedding
 #[derive(Debug, Clone)]
@@ -28,6 +38,8 @@ pub struct LandmarkConfig {
     pub step_size: f64,
     /// Regularization to prevent boundary collapse
     pub boundary_margin: f64,
+    /// Hyperbolic distance assigned to the largest landmark RTT
+    pub rtt_span: f64,
 }
 
 impl Default for LandmarkConfig {
@@ -38,6 +50,7 @@ impl Default for LandmarkConfig {
             triangulation_iterations: 50,
             step_size: 0.1,
             boundary_margin: 0.02,
+            rtt_span: 4.0,
         }
     }
 }
@@ -53,6 +66,127 @@ pub struct LandmarkEmbeddingResult {
     pub covering_radius: u32,
     /// Distance matrix from each node to each landmark
     pub landmark_distances: HashMap<NodeId, Vec<u32>>,
+    /// Graph hops per unit of hyperbolic distance, fitted over landmark pairs
+    pub hop_scale: f64,
+}
+
+/// Estimated hop distance between two nodes
+///
+/// `lower` and `upper` follow from the triangle inequality over the landmark
+/// distance vectors and always bracket the true graph distance; `estimate`
+/// is the scaled hyperbolic distance of the embedded coordinates, clamped to
+/// those bounds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DistanceEstimate {
+    pub estimate: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl DistanceEstimate {
+    /// Whether the bounds pin down the distance
+    pub fn is_exact(&self) -> bool {
+        self.lower == self.upper
+    }
+}
+
+impl LandmarkEmbeddingResult {
+    /// Approximate graph distance between two nodes without routing
+    ///
+    /// Returns infinity for unknown nodes or nodes in different components.
+    pub fn estimate_distance(&self, a: &NodeId, b: &NodeId) -> f64 {
+        self.estimate_distance_bounds(a, b).map_or(f64::INFINITY, |e| e.estimate)
+    }
+
+    /// Approximate graph distance with confidence bounds
+    pub fn estimate_distance_bounds(&self, a: &NodeId, b: &NodeId) -> Option<DistanceEstimate> {
+        let from = self.landmark_distances.get(a)?;
+        let to = self.landmark_distances.get(b)?;
+        if a == b {
+            return Some(DistanceEstimate { estimate: 0.0, lower: 0.0, upper: 0.0 });
+        }
+
+        let mut lower = 0u32;
+        let mut upper = u32::MAX;
+        for (&da, &db) in from.iter().zip(to) {
+            if da == u32::MAX || db == u32::MAX {
+                continue;
+            }
+            lower = lower.max(da.abs_diff(db));
+            upper = upper.min(da.saturating_add(db));
+        }
+        if upper == u32::MAX {
+            return None;
+        }
+        // Distinct nodes are at least one hop apart
+        let (lower, upper) = (lower.max(1) as f64, upper as f64);
+
+        let estimate = match (self.coordinates.get(a), self.coordinates.get(b)) {
+            (Some(pa), Some(pb)) => (self.hop_scale * pa.hyperbolic_distance(pb)).clamp(lower, upper),
+            _ => (lower + upper) / 2.0,
+        };
+        Some(DistanceEstimate { estimate, lower, upper })
+    }
+}
+
+/// Landmark positions fitted to measured round-trip times
+///
+/// Published by the landmarks so that other hosts can place themselves
+/// without knowing the topology.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RttLandmarkFrame {
+    pub landmarks: Vec<NodeId>,
+    /// Coordinates, parallel to `landmarks`
+    pub coordinates: Vec<PoincareDiskPoint>,
+    /// Milliseconds of RTT per unit of hyperbolic distance
+    pub ms_per_unit: f64,
+}
+
+impl RttLandmarkFrame {
+    /// Coordinate of a landmark
+    pub fn coordinate(&self, landmark: &NodeId) -> Option<PoincareDiskPoint> {
+        let i = self.landmarks.iter().position(|l| l == landmark)?;
+        Some(self.coordinates[i])
+    }
+
+    /// Predicted RTT in milliseconds between two coordinates
+    pub fn estimate_rtt(&self, a: &PoincareDiskPoint, b: &PoincareDiskPoint) -> f64 {
+        self.ms_per_unit * a.hyperbolic_distance(b)
+    }
+
+    /// Move `coord` to better match one RTT sample to `peer`, Vivaldi-style
+    ///
+    /// The coordinate slides along the line to the peer by `step` times the
+    /// prediction error, measured in hyperbolic units.
+    pub fn vivaldi_update(
+        &self,
+        coord: &PoincareDiskPoint,
+        peer: &PoincareDiskPoint,
+        rtt_ms: f64,
+        step: f64,
+    ) -> PoincareDiskPoint {
+        if !rtt_ms.is_finite() || rtt_ms < 0.0 {
+            return *coord;
+        }
+        let error = coord.hyperbolic_distance(peer) - rtt_ms / self.ms_per_unit;
+        let (dx, dy) = (peer.x - coord.x, peer.y - coord.y);
+        let eucl_dist = (dx * dx + dy * dy).sqrt();
+        if eucl_dist < 1e-10 {
+            return *coord;
+        }
+
+        // Euclidean length of a hyperbolic step at this radius
+        let r_sq = coord.euclidean_norm_sq();
+        let delta = step * error * (1.0 - r_sq) / 2.0;
+        let (mut x, mut y) = (coord.x + dx / eucl_dist * delta, coord.y + dy / eucl_dist * delta);
+
+        let r = (x * x + y * y).sqrt();
+        if r >= 0.99 {
+            x *= 0.98 / r;
+            y *= 0.98 / r;
+        }
+        PoincareDiskPoint::new(x, y).unwrap_or(*coord)
+    }
 }
 
 /// Landmark-MDS Hyperbolic Embedding
@@ -84,94 +218,48 @@ impl LandmarkEmbedding {
 
     /// Select landmarks using farthest-point sampling
     /// This maximizes the minimum distance from any node to its nearest landmark
-    fn select_landmarks(
-        &self,
-        adjacency: &HashMap<NodeId, Vec<NodeId>>,
-        num_landmarks: usize,
-    ) -> Vec<NodeId> {
-        let nodes: Vec<&NodeId> = adjacency.keys().collect();
-        if nodes.is_empty() || num_landmarks == 0 {
+    fn select_landmarks(&self, graph: &CsrGraph, num_landmarks: usize) -> Vec<NodeId> {
+        if graph.is_empty() || num_landmarks == 0 {
             return Vec::new();
         }
 
         let mut landmarks = Vec::with_capacity(num_landmarks);
-        
-        // Start with highest-degree node (hub)
-        let first_landmark = adjacency
-            .iter()
-            .max_by_key(|(_, neighbors)| neighbors.len())
-            .map(|(id, _)| id.clone())
-            .unwrap();
-        landmarks.push(first_landmark.clone());
+        let mut selected = BitSet::new(graph.len());
+        let mut scratch = BfsScratch::new(graph.len());
 
-        // Distance from each node to nearest landmark
-        let mut min_distances: HashMap<NodeId, u32> = HashMap::new();
+        // Distance from each node to nearest landmark; nodes the first
+        // landmark cannot reach are never candidates
+        let mut min_distances = vec![u32::MAX; graph.len()];
         
-        // Initialize with distances from first landmark
-        let distances_from_first = self.bfs_distances(adjacency, &first_landmark);
-        for (node, dist) in distances_from_first {
-            min_distances.insert(node, dist);
-        }
-
-        // Greedily select remaining landmarks
-        while landmarks.len() < num_landmarks {
-            // Find node with maximum distance to nearest landmark
-            let next_landmark = min_distances
-                .iter()
-                .filter(|(id, _)| !landmarks.contains(id))
-                .max_by_key(|(_, &dist)| dist)
-                .map(|(id, _)| id.clone());
-
-            match next_landmark {
-                Some(landmark) => {
-                    // Update min_distances with new landmark
-                    let new_distances = self.bfs_distances(adjacency, &landmark);
-                    for (node, dist) in new_distances {
-                        let current = min_distances.entry(node).or_insert(u32::MAX);
-                        *current = (*current).min(dist);
-                    }
-                    landmarks.push(landmark);
-                }
-                None => break,
+        // Start with highest-degree node (hub)
+        let mut next_landmark = (0..graph.len() as u32).max_by_key(|&v| graph.neighbors(v).len());
+        while let Some(landmark) = next_landmark {
+            // Update min_distances with new landmark
+            graph.bfs(landmark, &mut scratch);
+            for &v in scratch.order() {
+                let dist = scratch.distance(v).unwrap_or(u32::MAX);
+                min_distances[v as usize] = min_distances[v as usize].min(dist);
             }
-        }
-
-        landmarks
-    }
-
-    /// BFS to compute shortest-path distances from a source node
-    fn bfs_distances(
-        &self,
-        adjacency: &HashMap<NodeId, Vec<NodeId>>,
-        source: &NodeId,
-    ) -> HashMap<NodeId, u32> {
-        let mut distances = HashMap::new();
-        let mut visited = HashSet::new();
-        let mut queue = VecDeque::new();
-
-        distances.insert(source.clone(), 0);
-        visited.insert(source.clone());
-        queue.push_back((source.clone(), 0));
-
-        while let Some((current, dist)) = queue.pop_front() {
-            if let Some(neighbors) = adjacency.get(&current) {
-                for neighbor in neighbors {
-                    if !visited.contains(neighbor) {
-                        visited.insert(neighbor.clone());
-                        distances.insert(neighbor.clone(), dist + 1);
-                        queue.push_back((neighbor.clone(), dist + 1));
-                    }
-                }
+            selected.insert(landmark);
+            landmarks.push(graph.id(landmark).clone());
+            if landmarks.len() >= num_landmarks {
+                break;
             }
+
+            // Greedily pick the node with maximum distance to nearest landmark
+            next_landmark = (0..graph.len() as u32)
+                .filter(|&v| !selected.contains(v) && min_distances[v as usize] != u32::MAX)
+                .max_by_key(|&v| min_distances[v as usize]);
         }
 
-        distances
+        landmarks
     }
 
     /// Compute all landmark distances for all nodes
     fn compute_all_landmark_distances(
         &self,
         adjacency: &HashMap<NodeId, Vec<NodeId>>,
+        graph: &CsrGraph,
         landmarks: &[NodeId],
     ) -> HashMap<NodeId, Vec<u32>> {
         let mut all_distances: HashMap<NodeId, Vec<u32>> = HashMap::new();
@@ -182,11 +270,15 @@ impl LandmarkEmbedding {
         }
 
         // Compute distances from each landmark
+        let mut scratch = BfsScratch::new(graph.len());
         for (i, landmark) in landmarks.iter().enumerate() {
-            let distances = self.bfs_distances(adjacency, landmark);
-            for (node, dist) in distances {
-                if let Some(vec) = all_distances.get_mut(&node) {
-                    vec[i] = dist;
+            let Some(source) = graph.index_of(landmark) else {
+                continue;
+            };
+            graph.bfs(source, &mut scratch);
+            for &v in scratch.order() {
+                if let Some(vec) = all_distances.get_mut(graph.id(v)) {
+                    vec[i] = scratch.distance(v).unwrap_or(u32::MAX);
                 }
             }
         }
@@ -199,7 +291,7 @@ impl LandmarkEmbedding {
     fn classical_mds(
         &self,
         landmarks: &[NodeId],
-        landmark_distances: &HashMap<NodeId, Vec<u32>>,
+        landmark_matrix: &[Vec<f64>],
     ) -> HashMap<NodeId, (f64, f64)> {
         let k = landmarks.len();
         if k < 2 {
@@ -209,17 +301,7 @@ impl LandmarkEmbedding {
                 .collect();
         }
 
-        // Build distance matrix between landmarks
-        let mut dist_matrix = vec![vec![0.0; k]; k];
-        for (i, li) in landmarks.iter().enumerate() {
-            if let Some(dists) = landmark_distances.get(li) {
-                for (j, _lj) in landmarks.iter().enumerate() {
-                    // Distance from landmark i to landmark j
-                    // = distance from li to lj's position = dists[j]
-                    dist_matrix[i][j] = dists[j] as f64;
-                }
-            }
-        }
+        let mut dist_matrix = landmark_matrix.to_vec();
 
         // Symmetrize (in case of any numerical issues)
         for i in 0..k {
@@ -377,105 +459,95 @@ impl LandmarkEmbedding {
         &self,
         landmarks: &[NodeId],
         init_coords: &HashMap<NodeId, (f64, f64)>,
-        landmark_distances: &HashMap<NodeId, Vec<u32>>,
+        landmark_matrix: &[Vec<f64>],
     ) -> HashMap<NodeId, (f64, f64)> {
-        let mut coords = init_coords.clone();
         let k = landmarks.len();
+        let mut coords: Vec<(f64, f64)> = landmarks
+            .iter()
+            .map(|l| init_coords.get(l).copied().unwrap_or((0.0, 0.0)))
+            .collect();
+        if k < 2 {
+            return landmarks.iter().cloned().zip(coords).collect();
+        }
+        // Average over partners so the step does not grow with k
+        let step = self.config.step_size / (k - 1) as f64;
 
         for _ in 0..self.config.landmark_iterations {
-            let mut gradients: HashMap<NodeId, (f64, f64)> = HashMap::new();
-            for l in landmarks {
-                gradients.insert(l.clone(), (0.0, 0.0));
-            }
+            let mut gradients = vec![(0.0, 0.0); k];
 
             // Compute stress gradients between all landmark pairs
             for i in 0..k {
                 for j in (i + 1)..k {
-                    let li = &landmarks[i];
-                    let lj = &landmarks[j];
-
-                    let (xi, yi) = coords.get(li).copied().unwrap_or((0.0, 0.0));
-                    let (xj, yj) = coords.get(lj).copied().unwrap_or((0.0, 0.0));
-
-                    // Target distance (graph distance)
-                    let target_dist = landmark_distances
-                        .get(li)
-                        .map(|d| d[j] as f64)
-                        .unwrap_or(1.0);
-
-                    // Current hyperbolic distance
-                    let point_i = PoincareDiskPoint::new(xi, yi);
-                    let point_j = PoincareDiskPoint::new(xj, yj);
+                    // Target distance (graph distance or scaled RTT)
+                    let target_dist = landmark_matrix[i][j];
+                    if !target_dist.is_finite() || target_dist >= u32::MAX as f64 {
+                        continue;
+                    }
 
-                    let current_dist = match (point_i, point_j) {
-                        (Some(pi), Some(pj)) => pi.hyperbolic_distance(&pj),
-                        _ => continue,
+                    let Some((current_dist, gi)) = Self::distance_gradient(coords[i], coords[j]) else {
+                        continue;
                     };
-
-                    if current_dist < 1e-10 {
+                    let Some((_, gj)) = Self::distance_gradient(coords[j], coords[i]) else {
                         continue;
-                    }
+                    };
 
-                    // Stress and gradient
                     let stress = current_dist - target_dist;
-                    let dx = xj - xi;
-                    let dy = yj - yi;
-                    let eucl_dist = (dx * dx + dy * dy).sqrt().max(1e-10);
-
-                    // Conformal factors
-                    let ri_sq = xi * xi + yi * yi;
-                    let rj_sq = xj * xj + yj * yj;
-                    let conf_i = 2.0 / (1.0 - ri_sq).max(0.01);
-                    let conf_j = 2.0 / (1.0 - rj_sq).max(0.01);
-
-                    let grad_mag = stress * conf_i * conf_j / eucl_dist * self.config.step_size;
-                    let gx = dx / eucl_dist * grad_mag;
-                    let gy = dy / eucl_dist * grad_mag;
-
-                    if let Some(g) = gradients.get_mut(li) {
-                        g.0 += gx;
-                        g.1 += gy;
-                    }
-                    if let Some(g) = gradients.get_mut(lj) {
-                        g.0 -= gx;
-                        g.1 -= gy;
-                    }
+                    gradients[i].0 += stress * gi.0;
+                    gradients[i].1 += stress * gi.1;
+                    gradients[j].0 += stress * gj.0;
+                    gradients[j].1 += stress * gj.1;
                 }
             }
 
             // Apply gradients with Riemannian metric
-            for (id, coord) in coords.iter_mut() {
-                if let Some(&(gx, gy)) = gradients.get(id) {
-                    let (x, y) = *coord;
-                    let r_sq = x * x + y * y;
-                    let metric_scale = ((1.0 - r_sq) * (1.0 - r_sq)) / 4.0;
-                    let metric_scale = metric_scale.max(0.001);
-
-                    let new_x = x - gx * metric_scale;
-                    let new_y = y - gy * metric_scale;
-
-                    // Project back into disk with margin
-                    let new_r_sq = new_x * new_x + new_y * new_y;
-                    let max_r = 1.0 - self.config.boundary_margin;
-                    if new_r_sq >= max_r * max_r {
-                        let scale = (max_r - 0.01) / new_r_sq.sqrt();
-                        coord.0 = new_x * scale;
-                        coord.1 = new_y * scale;
-                    } else {
-                        coord.0 = new_x;
-                        coord.1 = new_y;
-                    }
+            for (coord, &(gx, gy)) in coords.iter_mut().zip(&gradients) {
+                let (x, y) = *coord;
+                let r_sq = x * x + y * y;
+                let metric_scale = ((1.0 - r_sq) * (1.0 - r_sq)) / 4.0;
+
+                let new_x = x - gx * metric_scale * step;
+                let new_y = y - gy * metric_scale * step;
+
+                // Project back into disk with margin
+                let new_r_sq = new_x * new_x + new_y * new_y;
+                let max_r = 1.0 - self.config.boundary_margin;
+                if new_r_sq >= max_r * max_r {
+                    let scale = (max_r - 0.01) / new_r_sq.sqrt();
+                    coord.0 = new_x * scale;
+                    coord.1 = new_y * scale;
+                } else {
+                    coord.0 = new_x;
+                    coord.1 = new_y;
                 }
             }
         }
 
-        coords
+        landmarks.iter().cloned().zip(coords).collect()
+    }
+
+    /// Hyperbolic distance from `u` to `v` and its Euclidean gradient in `u`
+    fn distance_gradient(u: (f64, f64), v: (f64, f64)) -> Option<(f64, (f64, f64))> {
+        let (dx, dy) = (u.0 - v.0, u.1 - v.1);
+        let diff_sq = dx * dx + dy * dy;
+        let alpha = 1.0 - (u.0 * u.0 + u.1 * u.1);
+        let beta = 1.0 - (v.0 * v.0 + v.1 * v.1);
+        if diff_sq < 1e-20 || alpha <= 0.0 || beta <= 0.0 {
+            return None;
+        }
+
+        // d = acosh(1 + delta), delta = 2|u - v|² / (alpha * beta)
+        let delta = 2.0 * diff_sq / (alpha * beta);
+        let dist = (1.0 + delta).acosh();
+        let outer = 1.0 / ((1.0 + delta).powi(2) - 1.0).sqrt();
+        let gx = outer * 2.0 / beta * (2.0 * dx / alpha + 2.0 * diff_sq * u.0 / (alpha * alpha));
+        let gy = outer * 2.0 / beta * (2.0 * dy / alpha + 2.0 * diff_sq * u.1 / (alpha * alpha));
+        Some((dist, (gx, gy)))
     }
 
     /// Triangulate a single node using its distances to landmarks
     fn triangulate_node(
         &self,
-        node_distances: &[u32],
+        node_distances: &[f64],
         landmark_coords: &[(f64, f64)],
     ) -> (f64, f64) {
         if landmark_coords.is_empty() {
@@ -488,12 +560,12 @@ impl LandmarkEmbedding {
         let mut total_weight = 0.0;
 
         for (i, &(lx, ly)) in landmark_coords.iter().enumerate() {
-            let dist = node_distances.get(i).copied().unwrap_or(u32::MAX);
-            if dist == 0 {
+            let dist = node_distances.get(i).copied().unwrap_or(f64::MAX);
+            if dist <= 0.0 {
                 // Node is at this landmark
                 return (lx, ly);
             }
-            let weight = 1.0 / (dist as f64 + 1.0);
+            let weight = 1.0 / (dist + 1.0);
             x += lx * weight;
             y += ly * weight;
             total_weight += weight;
@@ -509,44 +581,27 @@ impl LandmarkEmbedding {
             let mut gx = 0.0;
             let mut gy = 0.0;
 
-            let point = match PoincareDiskPoint::new(x, y) {
-                Some(p) => p,
-                None => break,
-            };
-
-            for (i, &(lx, ly)) in landmark_coords.iter().enumerate() {
-                let target_dist = node_distances.get(i).copied().unwrap_or(1) as f64;
-                
-                let landmark_point = match PoincareDiskPoint::new(lx, ly) {
-                    Some(p) => p,
-                    None => continue,
-                };
+            for (i, &landmark) in landmark_coords.iter().enumerate() {
+                let target_dist = node_distances.get(i).copied().unwrap_or(1.0);
 
-                let current_dist = point.hyperbolic_distance(&landmark_point);
-                if current_dist < 1e-10 {
+                let Some((current_dist, grad)) = Self::distance_gradient((x, y), landmark) else {
                     continue;
-                }
-
-                let stress = current_dist - target_dist;
-                let dx = lx - x;
-                let dy = ly - y;
-                let eucl_dist = (dx * dx + dy * dy).sqrt().max(1e-10);
+                };
 
                 // Weight by inverse target distance (closer landmarks matter more)
                 let weight = 1.0 / (target_dist + 1.0);
-                let grad_mag = stress * weight * self.config.step_size * 0.5;
+                let stress = (current_dist - target_dist) * weight;
 
-                gx += dx / eucl_dist * grad_mag;
-                gy += dy / eucl_dist * grad_mag;
+                gx += stress * grad.0;
+                gy += stress * grad.1;
             }
 
             // Apply gradient with Riemannian metric
             let r_sq = x * x + y * y;
             let metric_scale = ((1.0 - r_sq) * (1.0 - r_sq)) / 4.0;
-            let metric_scale = metric_scale.max(0.001);
 
-            x += gx * metric_scale;
-            y += gy * metric_scale;
+            x -= gx * metric_scale * self.config.step_size;
+            y -= gy * metric_scale * self.config.step_size;
 
             // Project back into disk
             let new_r_sq = x * x + y * y;
@@ -561,26 +616,56 @@ impl LandmarkEmbedding {
         (x, y)
     }
 
+    /// Least-squares ratio of graph hops to hyperbolic distance between landmarks
+    fn fit_hop_scale(
+        landmarks: &[NodeId],
+        landmark_distances: &HashMap<NodeId, Vec<u32>>,
+        coordinates: &HashMap<NodeId, PoincareDiskPoint>,
+    ) -> f64 {
+        let mut cross = 0.0;
+        let mut squares = 0.0;
+        for (i, a) in landmarks.iter().enumerate() {
+            for (j, b) in landmarks.iter().enumerate().skip(i + 1) {
+                let hops = landmark_distances.get(a).map_or(u32::MAX, |d| d[j]);
+                let (Some(pa), Some(pb)) = (coordinates.get(a), coordinates.get(b)) else {
+                    continue;
+                };
+                if hops == u32::MAX {
+                    continue;
+                }
+                let h = pa.hyperbolic_distance(pb);
+                cross += hops as f64 * h;
+                squares += h * h;
+            }
+        }
+        if squares > 0.0 {
+            cross / squares
+        } else {
+            1.0
+        }
+    }
+
     /// Main embedding function
     pub fn embed(
         &self,
         adjacency: &HashMap<NodeId, Vec<NodeId>>,
-    ) -> Result<LandmarkEmbeddingResult, String> {
+    ) -> Result<LandmarkEmbeddingResult, EmbeddingError> {
         let n = adjacency.len();
         if n == 0 {
-            return Err("Empty graph".to_string());
+            return Err(EmbeddingError::EmptyGraph);
         }
 
         // 1. Select landmarks
         let num_landmarks = self.compute_num_landmarks(n);
-        let landmarks = self.select_landmarks(adjacency, num_landmarks);
+        let graph = CsrGraph::from_adjacency(adjacency);
+        let landmarks = self.select_landmarks(&graph, num_landmarks);
         
         if landmarks.is_empty() {
-            return Err("No landmarks selected".to_string());
+            return Err(EmbeddingError::NoLandmarks);
         }
 
         // 2. Compute all landmark distances
-        let landmark_distances = self.compute_all_landmark_distances(adjacency, &landmarks);
+        let landmark_distances = self.compute_all_landmark_distances(adjacency, &graph, &landmarks);
 
         // 3. Compute covering radius
         let covering_radius = landmark_distances
@@ -590,10 +675,14 @@ impl LandmarkEmbedding {
             .unwrap_or(0);
 
         // 4. Classical MDS for initial landmark positions
-        let init_coords = self.classical_mds(&landmarks, &landmark_distances);
+        let landmark_matrix: Vec<Vec<f64>> = landmarks
+            .iter()
+            .map(|l| landmark_distances[l].iter().map(|&d| d as f64).collect())
+            .collect();
+        let init_coords = self.classical_mds(&landmarks, &landmark_matrix);
 
         // 5. Refine landmark coordinates in hyperbolic space
-        let landmark_coords = self.refine_landmark_coords(&landmarks, &init_coords, &landmark_distances);
+        let landmark_coords = self.refine_landmark_coords(&landmarks, &init_coords, &landmark_matrix);
 
         // 6. Convert landmark coords to vector for triangulation
         let landmark_coord_vec: Vec<(f64, f64)> = landmarks
@@ -608,7 +697,8 @@ impl LandmarkEmbedding {
             let (x, y) = if landmarks.contains(node_id) {
                 landmark_coords.get(node_id).copied().unwrap_or((0.0, 0.0))
             } else {
-                self.triangulate_node(dists, &landmark_coord_vec)
+                let dists: Vec<f64> = dists.iter().map(|&d| d as f64).collect();
+                self.triangulate_node(&dists, &landmark_coord_vec)
             };
 
             let point = PoincareDiskPoint::new(x, y)
@@ -616,19 +706,116 @@ impl LandmarkEmbedding {
             coordinates.insert(node_id.clone(), point);
         }
 
+        let hop_scale = Self::fit_hop_scale(&landmarks, &landmark_distances, &coordinates);
+
         Ok(LandmarkEmbeddingResult {
             coordinates,
             landmarks,
             covering_radius,
             landmark_distances,
+            hop_scale,
+        })
+    }
+
+    /// Position landmarks from their pairwise RTT matrix in milliseconds
+    ///
+    /// `rtt_ms[i][j]` is the RTT from `landmarks[i]` to `landmarks[j]`;
+    /// asymmetric measurements are averaged.
+    pub fn embed_rtt_landmarks(
+        &self,
+        landmarks: &[NodeId],
+        rtt_ms: &[Vec<f64>],
+    ) -> Result<RttLandmarkFrame, EmbeddingError> {
+        if landmarks.is_empty() {
+            return Err(EmbeddingError::NoLandmarks);
+        }
+        let k = landmarks.len();
+        if rtt_ms.len() != k || rtt_ms.iter().any(|row| row.len() != k) {
+            return Err(EmbeddingError::InvalidMeasurements(format!(
+                "expected a {k}x{k} RTT matrix"
+            )));
+        }
+        if rtt_ms.iter().flatten().any(|rtt| !rtt.is_finite() || *rtt < 0.0) {
+            return Err(EmbeddingError::InvalidMeasurements(
+                "RTTs must be finite and non-negative".to_string(),
+            ));
+        }
+
+        // Scale so the widest landmark pair spans `rtt_span`
+        let max_rtt = rtt_ms.iter().flatten().copied().fold(0.0f64, f64::max);
+        let initial_scale = if max_rtt > 0.0 { max_rtt / self.config.rtt_span } else { 1.0 };
+        let landmark_matrix: Vec<Vec<f64>> = rtt_ms
+            .iter()
+            .map(|row| row.iter().map(|rtt| rtt / initial_scale).collect())
+            .collect();
+
+        let init_coords = self.classical_mds(landmarks, &landmark_matrix);
+        let refined = self.refine_landmark_coords(landmarks, &init_coords, &landmark_matrix);
+        let coordinates: Vec<PoincareDiskPoint> = landmarks
+            .iter()
+            .map(|l| {
+                let (x, y) = refined.get(l).copied().unwrap_or((0.0, 0.0));
+                PoincareDiskPoint::new(x, y).unwrap_or_else(PoincareDiskPoint::origin)
+            })
+            .collect();
+
+        // Least-squares fit of RTT against the distances actually achieved
+        let mut cross = 0.0;
+        let mut squares = 0.0;
+        for i in 0..k {
+            for j in (i + 1)..k {
+                let h = coordinates[i].hyperbolic_distance(&coordinates[j]);
+                cross += (rtt_ms[i][j] + rtt_ms[j][i]) / 2.0 * h;
+                squares += h * h;
+            }
+        }
+        let ms_per_unit = if squares > 0.0 { cross / squares } else { initial_scale };
+
+        Ok(RttLandmarkFrame {
+            landmarks: landmarks.to_vec(),
+            coordinates,
+            ms_per_unit,
         })
     }
 
+    /// Place a host from its measured RTTs to the frame's landmarks
+    ///
+    /// `rtt_ms` is parallel to `frame.landmarks`; `None` marks a landmark
+    /// that could not be measured.
+    pub fn locate_by_rtt(
+        &self,
+        frame: &RttLandmarkFrame,
+        rtt_ms: &[Option<f64>],
+    ) -> Result<PoincareDiskPoint, EmbeddingError> {
+        if rtt_ms.len() != frame.landmarks.len() {
+            return Err(EmbeddingError::InvalidMeasurements(format!(
+                "expected {} RTTs, got {}",
+                frame.landmarks.len(),
+                rtt_ms.len()
+            )));
+        }
+
+        let (distances, coords): (Vec<f64>, Vec<(f64, f64)>) = rtt_ms
+            .iter()
+            .zip(&frame.coordinates)
+            .filter_map(|(rtt, p)| {
+                rtt.filter(|r| r.is_finite() && *r >= 0.0)
+                    .map(|r| (r / frame.ms_per_unit, (p.x, p.y)))
+            })
+            .unzip();
+        if coords.is_empty() {
+            return Err(EmbeddingError::NoLandmarks);
+        }
+
+        let (x, y) = self.triangulate_node(&distances, &coords);
+        Ok(PoincareDiskPoint::new(x, y).unwrap_or_else(PoincareDiskPoint::origin))
+    }
+
     /// Embed and return as RoutingCoordinates
     pub fn embed_as_routing_coords(
         &self,
         adjacency: &HashMap<NodeId, Vec<NodeId>>,
-    ) -> Result<HashMap<NodeId, RoutingCoordinate>, String> {
+    ) -> Result<HashMap<NodeId, RoutingCoordinate>, EmbeddingError> {
         let result = self.embed(adjacency)?;
 
         let coords = result
@@ -666,21 +853,22 @@ mod tests {
     fn test_landmark_selection() {
         let adj = create_test_adjacency();
         let embedder = LandmarkEmbedding::new();
-        let landmarks = embedder.select_landmarks(&adj, 2);
+        let landmarks = embedder.select_landmarks(&CsrGraph::from_adjacency(&adj), 2);
         
         assert_eq!(landmarks.len(), 2);
         // Should select endpoints (0 and 4) as they maximize coverage
     }
 
     #[test]
-    fn test_bfs_distances() {
+    fn test_landmark_distances() {
         let adj = create_test_adjacency();
         let embedder = LandmarkEmbedding::new();
-        let distances = embedder.bfs_distances(&adj, &NodeId::new("0"));
+        let graph = CsrGraph::from_adjacency(&adj);
+        let distances = embedder.compute_all_landmark_distances(&adj, &graph, &[NodeId::new("0")]);
         
-        assert_eq!(distances.get(&NodeId::new("0")), Some(&0));
-        assert_eq!(distances.get(&NodeId::new("1")), Some(&1));
-        assert_eq!(distances.get(&NodeId::new("4")), Some(&4));
+        assert_eq!(distances.get(&NodeId::new("0")), Some(&vec![0]));
+        assert_eq!(distances.get(&NodeId::new("1")), Some(&vec![1]));
+        assert_eq!(distances.get(&NodeId::new("4")), Some(&vec![4]));
     }
 
     #[test]
@@ -708,4 +896,102 @@ mod tests {
 
         assert_eq!(result.coordinates.len(), 3);
     }
+
+    #[test]
+    fn test_distance_estimate_bounds() {
+        let adj = create_test_adjacency();
+        let result = LandmarkEmbedding::new().embed(&adj).unwrap();
+        assert!(result.hop_scale > 0.0);
+
+        for a in 0..5 {
+            for b in 0..5 {
+                let (na, nb) = (NodeId::new(a.to_string()), NodeId::new(b.to_string()));
+                let est = result.estimate_distance_bounds(&na, &nb).unwrap();
+                let hops = (a as f64 - b as f64).abs();
+                assert!(est.lower <= hops && hops <= est.upper);
+                assert!(est.lower <= est.estimate && est.estimate <= est.upper);
+            }
+        }
+        // An endpoint landmark pins down distances on a path
+        let est = result.estimate_distance_bounds(&NodeId::new("1"), &NodeId::new("3")).unwrap();
+        assert!(est.is_exact());
+        assert_eq!(result.estimate_distance(&NodeId::new("1"), &NodeId::new("3")), 2.0);
+        assert_eq!(result.estimate_distance(&NodeId::new("2"), &NodeId::new("2")), 0.0);
+        assert!(result.estimate_distance(&NodeId::new("0"), &NodeId::new("missing")).is_infinite());
+    }
+
+    fn rtt_ground_truth() -> (Vec<NodeId>, Vec<PoincareDiskPoint>) {
+        let points = [(0.0, 0.0), (0.7, 0.1), (-0.5, 0.6), (-0.3, -0.7), (0.4, -0.6), (0.1, 0.8)];
+        let ids = (0..points.len()).map(|i| NodeId::new(format!("lm{i}"))).collect();
+        let coords = points.iter().map(|&(x, y)| PoincareDiskPoint::new(x, y).unwrap()).collect();
+        (ids, coords)
+    }
+
+    #[test]
+    fn test_rtt_embedding_predicts_host_rtts() {
+        const MS_PER_UNIT: f64 = 25.0;
+        let (landmarks, truth) = rtt_ground_truth();
+        let rtt = |a: &PoincareDiskPoint, b: &PoincareDiskPoint| MS_PER_UNIT * a.hyperbolic_distance(b);
+        let matrix: Vec<Vec<f64>> = truth.iter().map(|a| truth.iter().map(|b| rtt(a, b)).collect()).collect();
+
+        let embedder = LandmarkEmbedding::new();
+        let frame = embedder.embed_rtt_landmarks(&landmarks, &matrix).unwrap();
+        assert_eq!(frame.coordinates.len(), landmarks.len());
+        assert!(frame.ms_per_unit > 0.0);
+
+        // A host that can reach all but one landmark
+        let host = PoincareDiskPoint::new(0.3, 0.3).unwrap();
+        let mut measured: Vec<Option<f64>> = truth.iter().map(|l| Some(rtt(&host, l))).collect();
+        measured[5] = None;
+        let placed = embedder.locate_by_rtt(&frame, &measured).unwrap();
+
+        let mut relative_error = 0.0;
+        for (i, l) in landmarks.iter().enumerate() {
+            let actual = rtt(&host, &truth[i]);
+            let predicted = frame.estimate_rtt(&placed, &frame.coordinate(l).unwrap());
+            relative_error += (predicted - actual).abs() / actual;
+        }
+        let mean_error = relative_error / landmarks.len() as f64;
+        assert!(mean_error < 0.05, "mean relative error {mean_error}");
+    }
+
+    #[test]
+    fn test_rtt_embedding_rejects_bad_input() {
+        let embedder = LandmarkEmbedding::new();
+        let landmarks = vec![NodeId::new("a"), NodeId::new("b")];
+        assert_eq!(embedder.embed_rtt_landmarks(&[], &[]).unwrap_err(), EmbeddingError::NoLandmarks);
+        let err = embedder.embed_rtt_landmarks(&landmarks, &[vec![0.0, 10.0]]).unwrap_err();
+        assert_eq!(err.code(), "embedding.invalid_measurements");
+        let err = embedder
+            .embed_rtt_landmarks(&landmarks, &[vec![0.0, f64::NAN], vec![10.0, 0.0]])
+            .unwrap_err();
+        assert_eq!(err.code(), "embedding.invalid_measurements");
+
+        let frame = embedder
+            .embed_rtt_landmarks(&landmarks, &[vec![0.0, 10.0], vec![10.0, 0.0]])
+            .unwrap();
+        assert_eq!(embedder.locate_by_rtt(&frame, &[None, None]).unwrap_err(), EmbeddingError::NoLandmarks);
+        assert!(embedder.locate_by_rtt(&frame, &[Some(1.0)]).is_err());
+    }
+
+    #[test]
+    fn test_vivaldi_update_reduces_error() {
+        let frame = RttLandmarkFrame {
+            landmarks: vec![NodeId::new("peer")],
+            coordinates: vec![PoincareDiskPoint::origin()],
+            ms_per_unit: 10.0,
+        };
+        let peer = PoincareDiskPoint::origin();
+        let mut coord = PoincareDiskPoint::new(0.1, 0.0).unwrap();
+        for _ in 0..50 {
+            coord = frame.vivaldi_update(&coord, &peer, 20.0, 0.25);
+        }
+        assert!((frame.estimate_rtt(&coord, &peer) - 20.0).abs() < 0.5);
+        assert!(coord.y.abs() < 1e-12);
+
+        // Too far: pulled back towards the peer
+        let far = PoincareDiskPoint::new(0.9, 0.0).unwrap();
+        let pulled = frame.vivaldi_update(&far, &peer, 20.0, 0.25);
+        assert!(pulled.x < far.x);
+    }
 }
@@ -1,8 +1,9 @@
 //! Landmark-guided routing utilities.
 
 use crate::coordinates::NodeId;
+use crate::graph::{BfsScratch, BitSet, CsrGraph};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LandmarkRoutingConfig {
@@ -53,14 +54,15 @@ impl LandmarkRoutingTable {
         }
 
         let num_landmarks = config.effective_landmark_count(adjacency.len());
-        let landmarks = select_landmarks(adjacency, num_landmarks);
+        let graph = CsrGraph::from_adjacency(adjacency);
+        let landmarks = select_landmarks(&graph, num_landmarks);
         if landmarks.is_empty() {
             return Err("No landmarks selected".to_string());
         }
 
-        let distances = compute_all_landmark_distances(adjacency, &landmarks);
+        let distances = compute_all_landmark_distances(adjacency, &graph, &landmarks);
         Ok(Self {
-            landmarks,
+            landmarks: landmarks.iter().map(|&v| graph.id(v).clone()).collect(),
             distances,
         })
     }
@@ -85,49 +87,33 @@ impl LandmarkRoutingTable {
     }
 }
 
-fn select_landmarks(
-    adjacency: &HashMap<NodeId, Vec<NodeId>>,
-    num_landmarks: usize,
-) -> Vec<NodeId> {
-    if adjacency.is_empty() || num_landmarks == 0 {
+fn select_landmarks(graph: &CsrGraph, num_landmarks: usize) -> Vec<u32> {
+    if graph.is_empty() || num_landmarks == 0 {
         return Vec::new();
     }
 
-    let first = adjacency
-        .iter()
-        .max_by_key(|(_, neighbors)| neighbors.len())
-        .map(|(id, _)| id.clone())
-        .unwrap();
-
+    let first = (0..graph.len() as u32).max_by_key(|&v| graph.neighbors(v).len()).unwrap();
     let mut landmarks = Vec::with_capacity(num_landmarks);
-    let mut selected = HashSet::new();
-    landmarks.push(first.clone());
-    selected.insert(first.clone());
-
-    let mut min_distances: HashMap<NodeId, u32> = bfs_distances(adjacency, &first);
-
-    while landmarks.len() < num_landmarks {
-        let next = min_distances
-            .iter()
-            .filter(|(id, _)| !selected.contains(*id))
-            .max_by_key(|(_, dist)| *dist)
-            .map(|(id, _)| id.clone());
-
-        let next = match next {
-            Some(id) => id,
-            None => break,
-        };
-
-        let distances = bfs_distances(adjacency, &next);
-        for (node, dist) in distances {
-            let entry = min_distances.entry(node).or_insert(u32::MAX);
-            if dist < *entry {
-                *entry = dist;
-            }
+    let mut selected = BitSet::new(graph.len());
+    let mut scratch = BfsScratch::new(graph.len());
+    let mut min_distances = vec![u32::MAX; graph.len()];
+
+    // Nodes the first landmark cannot reach are never candidates
+    let mut next = Some(first);
+    while let Some(landmark) = next {
+        graph.bfs(landmark, &mut scratch);
+        for &v in scratch.order() {
+            let dist = scratch.distance(v).unwrap_or(u32::MAX);
+            min_distances[v as usize] = min_distances[v as usize].min(dist);
         }
-
-        selected.insert(next.clone());
-        landmarks.push(next);
+        selected.insert(landmark);
+        landmarks.push(landmark);
+        if landmarks.len() >= num_landmarks {
+            break;
+        }
+        next = (0..graph.len() as u32)
+            .filter(|&v| !selected.contains(v) && min_distances[v as usize] != u32::MAX)
+            .max_by_key(|&v| min_distances[v as usize]);
     }
 
     landmarks
@@ -135,7 +121,8 @@ fn select_landmarks(
 
 fn compute_all_landmark_distances(
     adjacency: &HashMap<NodeId, Vec<NodeId>>,
-    landmarks: &[NodeId],
+    graph: &CsrGraph,
+    landmarks: &[u32],
 ) -> HashMap<NodeId, Vec<u32>> {
     let mut all_distances: HashMap<NodeId, Vec<u32>> = HashMap::new();
 
@@ -143,38 +130,15 @@ fn compute_all_landmark_distances(
         all_distances.insert(node.clone(), vec![u32::MAX; landmarks.len()]);
     }
 
-    for (i, landmark) in landmarks.iter().enumerate() {
-        let distances = bfs_distances(adjacency, landmark);
-        for (node, dist) in distances {
-            if let Some(vec) = all_distances.get_mut(&node) {
-                vec[i] = dist;
+    let mut scratch = BfsScratch::new(graph.len());
+    for (i, &landmark) in landmarks.iter().enumerate() {
+        graph.bfs(landmark, &mut scratch);
+        for &v in scratch.order() {
+            if let Some(vec) = all_distances.get_mut(graph.id(v)) {
+                vec[i] = scratch.distance(v).unwrap_or(u32::MAX);
             }
         }
     }
 
     all_distances
 }
-
-fn bfs_distances(
-    adjacency: &HashMap<NodeId, Vec<NodeId>>,
-    source: &NodeId,
-) -> HashMap<NodeId, u32> {
-    let mut distances = HashMap::new();
-    let mut queue = VecDeque::new();
-    distances.insert(source.clone(), 0);
-    queue.push_back(source.clone());
-
-    while let Some(current) = queue.pop_front() {
-        let current_dist = *distances.get(&current).unwrap_or(&0);
-        if let Some(neighbors) = adjacency.get(&current) {
-            for neighbor in neighbors {
-                if !distances.contains_key(neighbor) {
-                    distances.insert(neighbor.clone(), current_dist + 1);
-                    queue.push_back(neighbor.clone());
-                }
-            }
-        }
-    }
-
-    distances
-}
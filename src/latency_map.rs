@@ -0,0 +1,350 @@
+//! Overlay Latency Map
+//!
+//! Estimates the round-trip time between any two nodes this node knows the
+//! coordinates of, without probing them. The greedy embedding places nodes
+//! that are few hops apart close together, so RTT grows roughly linearly
+//! with hyperbolic distance. The map fits that line,
+//! `base_ms + ms_per_unit * distance`, to the RTTs measured on real links,
+//! and uses it for every other pair.
+//!
+//! A fitted line can be badly off for a single pair, so estimates are
+//! narrowed by triangulation through landmarks, the nodes with the most
+//! measured links. For a landmark `L` the triangle inequality bounds the
+//! RTT between `a` and `b` to `[|rtt(a, L) - rtt(L, b)|, rtt(a, L) +
+//! rtt(L, b)]`. Legs that were measured are used as is, the others come
+//! from the line, and a landmark counts only if at least one of its legs
+//! was measured. The estimate is the line's value clamped to the tightest
+//! bounds over all landmarks. A pair that was measured directly is reported
+//! as measured.
+//!
+//! Applications use this to pick replicas or relays by expected latency.
+//! Like path queries it is an estimate and never sends traffic.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::path_query::DEFAULT_HOP_LATENCY_MS;
+use crate::PoincareDiskPoint;
+
+/// Latency map settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LatencyMapConfig {
+    /// Landmarks used to bound estimates
+    pub landmarks: usize,
+    /// RTT per unit of hyperbolic distance when too few links are measured
+    pub default_ms_per_unit: f64,
+    /// Measured links needed to fit an intercept as well as a slope
+    pub min_samples: usize,
+}
+
+impl Default for LatencyMapConfig {
+    fn default() -> Self {
+        Self {
+            landmarks: 8,
+            default_ms_per_unit: 2.0 * DEFAULT_HOP_LATENCY_MS,
+            min_samples: 3,
+        }
+    }
+}
+
+/// RTT as a function of hyperbolic distance
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LatencyModel {
+    pub base_ms: f64,
+    pub ms_per_unit: f64,
+    /// Measured links the model was fitted to
+    pub samples: usize,
+}
+
+impl LatencyModel {
+    /// Fit to `(distance, rtt_ms)` samples
+    ///
+    /// Least squares when there are enough samples and the fit is sensible,
+    /// otherwise a line through the origin with the mean ratio, otherwise
+    /// the configured default.
+    pub fn fit(samples: &[(f64, f64)], config: &LatencyMapConfig) -> Self {
+        let n = samples.len();
+        let fallback = Self {
+            base_ms: 0.0,
+            ms_per_unit: config.default_ms_per_unit,
+            samples: n,
+        };
+        if n >= config.min_samples.max(2) {
+            let mean_d = samples.iter().map(|(d, _)| d).sum::<f64>() / n as f64;
+            let mean_r = samples.iter().map(|(_, r)| r).sum::<f64>() / n as f64;
+            let var: f64 = samples.iter().map(|(d, _)| (d - mean_d).powi(2)).sum();
+            let cov: f64 = samples.iter().map(|(d, r)| (d - mean_d) * (r - mean_r)).sum();
+            if var > 1e-9 {
+                let slope = cov / var;
+                let base = mean_r - slope * mean_d;
+                if slope > 0.0 && base >= 0.0 {
+                    return Self { base_ms: base, ms_per_unit: slope, samples: n };
+                }
+            }
+        }
+        let ratios: Vec<f64> = samples.iter().filter(|(d, _)| *d > 1e-9).map(|(d, r)| r / d).collect();
+        if ratios.is_empty() {
+            return fallback;
+        }
+        Self {
+            ms_per_unit: ratios.iter().sum::<f64>() / ratios.len() as f64,
+            ..fallback
+        }
+    }
+
+    pub fn predict(&self, distance: f64) -> f64 {
+        self.base_ms + self.ms_per_unit * distance
+    }
+}
+
+/// Estimated RTT between two nodes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LatencyEstimate {
+    pub rtt_ms: f64,
+    /// Triangulated bounds; equal to `rtt_ms` for measured pairs
+    pub lower_ms: f64,
+    pub upper_ms: f64,
+    /// Whether the pair was measured directly
+    pub measured: bool,
+}
+
+/// One pair of the all-pairs map
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatencyEntry {
+    pub a: NodeId,
+    pub b: NodeId,
+    #[serde(flatten)]
+    pub estimate: LatencyEstimate,
+}
+
+/// A candidate ranked by expected RTT
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RankedCandidate {
+    pub node: NodeId,
+    #[serde(flatten)]
+    pub estimate: LatencyEstimate,
+}
+
+/// Serializable view of a whole map
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatencyMapSnapshot {
+    pub model: LatencyModel,
+    pub landmarks: Vec<NodeId>,
+    pub entries: Vec<LatencyEntry>,
+}
+
+/// Estimated all-pairs RTTs over known coordinates
+#[derive(Debug, Clone)]
+pub struct LatencyMap {
+    coords: HashMap<NodeId, PoincareDiskPoint>,
+    measured: HashMap<(NodeId, NodeId), f64>,
+    landmarks: Vec<NodeId>,
+    model: LatencyModel,
+}
+
+fn pair(a: &NodeId, b: &NodeId) -> (NodeId, NodeId) {
+    if a <= b {
+        (a.clone(), b.clone())
+    } else {
+        (b.clone(), a.clone())
+    }
+}
+
+impl LatencyMap {
+    /// Build from node coordinates and measured `(a, b, rtt_ms)` links
+    ///
+    /// Links with an unknown end or a non-positive RTT are ignored; a link
+    /// measured more than once keeps the mean.
+    pub fn build(
+        coords: HashMap<NodeId, PoincareDiskPoint>,
+        links: &[(NodeId, NodeId, f64)],
+        config: &LatencyMapConfig,
+    ) -> Self {
+        let mut sums: HashMap<(NodeId, NodeId), (f64, usize)> = HashMap::new();
+        for (a, b, rtt) in links {
+            if a == b || !rtt.is_finite() || *rtt <= 0.0 || !coords.contains_key(a) || !coords.contains_key(b) {
+                continue;
+            }
+            let entry = sums.entry(pair(a, b)).or_default();
+            entry.0 += rtt;
+            entry.1 += 1;
+        }
+        let measured: HashMap<(NodeId, NodeId), f64> =
+            sums.into_iter().map(|(key, (sum, count))| (key, sum / count as f64)).collect();
+
+        let samples: Vec<(f64, f64)> = measured
+            .iter()
+            .map(|((a, b), rtt)| (coords[a].hyperbolic_distance(&coords[b]), *rtt))
+            .collect();
+        let model = LatencyModel::fit(&samples, config);
+
+        let mut degree: HashMap<&NodeId, usize> = HashMap::new();
+        for (a, b) in measured.keys() {
+            *degree.entry(a).or_default() += 1;
+            *degree.entry(b).or_default() += 1;
+        }
+        let mut ranked: Vec<(&NodeId, usize)> = degree.into_iter().collect();
+        ranked.sort_by(|(a, da), (b, db)| db.cmp(da).then_with(|| a.cmp(b)));
+        let landmarks = ranked.into_iter().take(config.landmarks).map(|(id, _)| id.clone()).collect();
+
+        Self { coords, measured, landmarks, model }
+    }
+
+    pub fn model(&self) -> &LatencyModel {
+        &self.model
+    }
+
+    pub fn landmarks(&self) -> &[NodeId] {
+        &self.landmarks
+    }
+
+    pub fn len(&self) -> usize {
+        self.coords.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.coords.is_empty()
+    }
+
+    /// RTT of a leg, measured if possible, and whether it was
+    fn leg(&self, a: &NodeId, b: &NodeId) -> (f64, bool) {
+        match self.measured.get(&pair(a, b)) {
+            Some(rtt) => (*rtt, true),
+            None => (self.model.predict(self.coords[a].hyperbolic_distance(&self.coords[b])), false),
+        }
+    }
+
+    /// Estimated RTT between `a` and `b`, if both coordinates are known
+    pub fn estimate(&self, a: &NodeId, b: &NodeId) -> Option<LatencyEstimate> {
+        if !self.coords.contains_key(a) || !self.coords.contains_key(b) {
+            return None;
+        }
+        if a == b {
+            return Some(LatencyEstimate { rtt_ms: 0.0, lower_ms: 0.0, upper_ms: 0.0, measured: true });
+        }
+        if let Some(rtt) = self.measured.get(&pair(a, b)) {
+            return Some(LatencyEstimate { rtt_ms: *rtt, lower_ms: *rtt, upper_ms: *rtt, measured: true });
+        }
+
+        let (mut lower, mut upper) = (0.0f64, f64::INFINITY);
+        for landmark in self.landmarks.iter().filter(|l| *l != a && *l != b) {
+            let (to_a, measured_a) = self.leg(a, landmark);
+            let (to_b, measured_b) = self.leg(landmark, b);
+            if measured_a || measured_b {
+                lower = lower.max((to_a - to_b).abs());
+                upper = upper.min(to_a + to_b);
+            }
+        }
+        let model = self.leg(a, b).0;
+        // Inconsistent measurements can cross the bounds; trust the tighter upper one
+        let lower = lower.min(upper);
+        Some(LatencyEstimate {
+            rtt_ms: model.clamp(lower, upper),
+            lower_ms: lower,
+            upper_ms: upper,
+            measured: false,
+        })
+    }
+
+    /// Candidates with known coordinates, nearest first by expected RTT
+    pub fn rank(&self, from: &NodeId, candidates: &[NodeId]) -> Vec<RankedCandidate> {
+        let mut seen = HashSet::new();
+        let mut ranked: Vec<RankedCandidate> = candidates
+            .iter()
+            .filter(|node| seen.insert(*node))
+            .filter_map(|node| {
+                self.estimate(from, node).map(|estimate| RankedCandidate { node: node.clone(), estimate })
+            })
+            .collect();
+        ranked.sort_by(|a, b| a.estimate.rtt_ms.total_cmp(&b.estimate.rtt_ms).then_with(|| a.node.cmp(&b.node)));
+        ranked
+    }
+
+    /// Every unordered pair, sorted by node IDs
+    pub fn snapshot(&self) -> LatencyMapSnapshot {
+        let mut nodes: Vec<&NodeId> = self.coords.keys().collect();
+        nodes.sort();
+        let mut entries = Vec::with_capacity(nodes.len() * nodes.len().saturating_sub(1) / 2);
+        for (i, a) in nodes.iter().enumerate() {
+            for b in &nodes[i + 1..] {
+                if let Some(estimate) = self.estimate(a, b) {
+                    entries.push(LatencyEntry { a: (*a).clone(), b: (*b).clone(), estimate });
+                }
+            }
+        }
+        LatencyMapSnapshot {
+            model: self.model,
+            landmarks: self.landmarks.clone(),
+            entries,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str) -> NodeId {
+        NodeId::new(name)
+    }
+
+    /// Nodes along the real axis, with RTT exactly 5 + 20 * distance
+    fn line() -> (HashMap<NodeId, PoincareDiskPoint>, Vec<(NodeId, NodeId, f64)>) {
+        let xs = [("a", -0.6), ("b", -0.3), ("c", 0.0), ("d", 0.3), ("e", 0.6)];
+        let coords: HashMap<NodeId, PoincareDiskPoint> =
+            xs.iter().map(|(id, x)| (node(id), PoincareDiskPoint::new(*x, 0.0).unwrap())).collect();
+        let rtt = |a: &str, b: &str| 5.0 + 20.0 * coords[&node(a)].hyperbolic_distance(&coords[&node(b)]);
+        let links = [("c", "a"), ("c", "b"), ("c", "d"), ("c", "e")]
+            .iter()
+            .map(|(a, b)| (node(a), node(b), rtt(a, b)))
+            .collect();
+        (coords, links)
+    }
+
+    #[test]
+    fn test_fit_and_triangulate() {
+        let (coords, links) = line();
+        let map = LatencyMap::build(coords.clone(), &links, &LatencyMapConfig::default());
+        assert!((map.model().base_ms - 5.0).abs() < 1e-6);
+        assert!((map.model().ms_per_unit - 20.0).abs() < 1e-6);
+        assert_eq!(map.landmarks()[0], node("c"));
+
+        let measured = map.estimate(&node("a"), &node("c")).unwrap();
+        assert!(measured.measured);
+        let estimate = map.estimate(&node("a"), &node("e")).unwrap();
+        assert!(!estimate.measured);
+        let expected = 5.0 + 20.0 * coords[&node("a")].hyperbolic_distance(&coords[&node("e")]);
+        assert!((estimate.rtt_ms - expected).abs() < 1e-6);
+        assert!(estimate.lower_ms <= estimate.rtt_ms && estimate.rtt_ms <= estimate.upper_ms);
+        assert!(map.estimate(&node("a"), &node("unknown")).is_none());
+
+        // A model far off is pulled back inside the landmark bounds
+        let mut far = coords.clone();
+        far.insert(node("b"), PoincareDiskPoint::new(0.95, 0.0).unwrap());
+        let skewed = LatencyMap::build(far, &links, &LatencyMapConfig::default());
+        let clamped = skewed.estimate(&node("b"), &node("d")).unwrap();
+        assert!(clamped.rtt_ms < skewed.leg(&node("b"), &node("d")).0);
+        assert_eq!(clamped.upper_ms, links[1].2 + links[2].2);
+    }
+
+    #[test]
+    fn test_rank_and_snapshot() {
+        let (coords, links) = line();
+        let map = LatencyMap::build(coords, &links, &LatencyMapConfig::default());
+        let ranked = map.rank(&node("a"), &[node("e"), node("b"), node("d"), node("b"), node("zz")]);
+        let order: Vec<&str> = ranked.iter().map(|r| r.node.0.as_str()).collect();
+        assert_eq!(order, ["b", "d", "e"]);
+
+        let snapshot = map.snapshot();
+        assert_eq!(snapshot.entries.len(), 10);
+        assert_eq!(snapshot.entries.iter().filter(|e| e.estimate.measured).count(), 4);
+
+        // Too few links to fit a line falls back to the mean ratio
+        let sparse = LatencyMap::build(map.coords.clone(), &links[..1], &LatencyMapConfig::default());
+        assert_eq!(sparse.model().base_ms, 0.0);
+        assert_eq!(sparse.model().samples, 1);
+    }
+}
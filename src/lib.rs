@@ -2,36 +2,135 @@
 //!
 //! Core library for hyperbolic geometry operations and distributed routing protocol.
 
+pub mod access_zones;
+pub mod admission;
 pub mod api;
+pub mod api_access;
+#[cfg(feature = "array-backend")]
+pub mod array_backend;
 pub mod audit;
 pub mod baselines;
+pub mod bench;
+pub mod broadcast;
 pub mod byzantine;
+pub mod certificate;
 pub mod chat;
 pub mod chaos;
+pub mod checkpoint_codec;
+pub mod checkpoint_store;
+pub mod clustering;
+pub mod compression;
+pub mod config;
+pub mod conformance;
+pub mod content;
+pub mod congestion;
+pub mod convergence;
+pub mod coordinate_batch;
+pub mod coordinate_control;
+pub mod coordinate_history;
+pub mod coordinate_precision;
+pub mod coordinate_recovery;
+pub mod coordinate_tree;
 pub mod coordinates;
+pub mod coordination;
+pub mod dead_letter;
+pub mod deadline;
+pub mod degradation;
+pub mod e2e_encryption;
+pub mod failpoint;
+pub mod fec;
+pub mod geohash;
+pub mod graph;
+pub mod graph_generators;
 pub mod greedy_embedding;
 pub mod grpc;
+pub mod header_budget;
+pub mod healing;
+pub mod health;
+pub mod heartbeat;
 pub mod hierarchical;
+pub mod hlc;
 pub mod hyperbolic_models;
+pub mod isolation;
 pub mod landmark_embedding;
 pub mod landmark_routing;
+pub mod latency_map;
 pub mod lockfree;
+pub mod memory;
+pub mod memory_transport;
+pub mod mobility;
+pub mod mode_switch;
+pub mod multihoming;
+pub mod multicast;
+pub mod nat;
+pub mod neighbor_cap;
+pub mod neighbor_exchange;
+pub mod neighbor_policy;
 pub mod network;
 pub mod network_tls;
+pub mod onion;
+pub mod path_cache;
+pub mod path_query;
+pub mod path_search;
+pub mod plugins;
+pub mod presence;
+pub mod probing;
+pub mod procrustes;
+pub mod record_placement;
 pub mod rendezvous;
+pub mod replay;
+pub mod reputation;
 pub mod ricci;
+pub mod robustness;
+pub mod route_cache;
+pub mod route_stats;
 pub mod routing;
+pub mod shaping;
 pub mod stability;
+pub mod snapshot;
+pub mod stream;
+pub mod simulation;
 pub mod sybil;
 pub mod telemetry;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod tls;
+pub mod topology_diff;
+pub mod traffic_matrix;
+pub mod tree_repair;
+pub mod ttl_policy;
+pub mod tun;
 pub mod tz_routing;
+pub mod visualization;
+pub mod voronoi;
 pub mod hyper_press;
 
 
 use serde::{Deserialize, Serialize};
 use std::f64::consts::PI;
 
+/// Errors from hyperbolic geometry operations
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum GeometryError {
+    #[error("Non-finite coordinate")]
+    NonFinite,
+    #[error("Point ({x}, {y}) lies outside the open unit disk")]
+    OutsideDisk { x: f64, y: f64 },
+    #[error("Polar radius {0} is outside [0, 1)")]
+    InvalidRadius(f64),
+}
+
+impl GeometryError {
+    /// Stable identifier for programmatic handling
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NonFinite => "geometry.non_finite",
+            Self::OutsideDisk { .. } => "geometry.outside_disk",
+            Self::InvalidRadius(_) => "geometry.invalid_radius",
+        }
+    }
+}
+
 /// A point in the Poincaré disk model of hyperbolic space.
 /// The disk is the unit disk {z ∈ ℂ : |z| < 1}.
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
@@ -54,6 +153,22 @@ impl PoincareDiskPoint {
         }
     }
 
+    /// Like `new`, but reports why the point is invalid
+    pub fn try_new(x: f64, y: f64) -> Result<Self, GeometryError> {
+        if !x.is_finite() || !y.is_finite() {
+            return Err(GeometryError::NonFinite);
+        }
+        Self::new(x, y).ok_or(GeometryError::OutsideDisk { x, y })
+    }
+
+    /// Like `from_polar`, but reports why the point is invalid
+    pub fn try_from_polar(r: f64, theta: f64) -> Result<Self, GeometryError> {
+        if !r.is_finite() || !theta.is_finite() {
+            return Err(GeometryError::NonFinite);
+        }
+        Self::from_polar(r, theta).ok_or(GeometryError::InvalidRadius(r))
+    }
+
     /// Create a point from polar coordinates (r, θ).
     /// r must be in [0, 1).
     pub fn from_polar(r: f64, theta: f64) -> Option<Self> {
@@ -166,6 +281,14 @@ pub fn normalize_angle(theta: f64) -> f64 {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_geometry_errors() {
+        assert_eq!(PoincareDiskPoint::try_new(1.0, 0.0), Err(GeometryError::OutsideDisk { x: 1.0, y: 0.0 }));
+        assert_eq!(PoincareDiskPoint::try_new(f64::NAN, 0.0).unwrap_err().code(), "geometry.non_finite");
+        assert_eq!(PoincareDiskPoint::try_from_polar(1.5, 0.0), Err(GeometryError::InvalidRadius(1.5)));
+        assert!(PoincareDiskPoint::try_from_polar(0.5, 1.0).is_ok());
+    }
+
     #[test]
     fn test_point_creation() {
         assert!(PoincareDiskPoint::new(0.0, 0.0).is_some());
@@ -0,0 +1,124 @@
+//! Memory Accounting
+//!
+//! Relays on small devices need to know what their tables cost before the
+//! overlay grows into them. The major in-memory structures report an entry
+//! count and an approximate size through `MemoryFootprint`, and a node
+//! collects them per subsystem into a `MemoryReport`.
+//!
+//! Sizes are estimates: they count the slots a table has allocated and the
+//! strings and buffers its entries own, but not allocator overhead or
+//! small side fields. They track growth well enough for capacity planning
+//! and are cheap enough to compute on every metrics scrape.
+
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::mem::size_of;
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+
+/// Entries held by a structure and their approximate size
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryUsage {
+    pub entries: usize,
+    pub bytes: usize,
+}
+
+impl MemoryUsage {
+    pub fn new(entries: usize, bytes: usize) -> Self {
+        Self { entries, bytes }
+    }
+}
+
+impl std::ops::Add for MemoryUsage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.entries + other.entries, self.bytes + other.bytes)
+    }
+}
+
+/// A structure that can estimate its own memory use
+pub trait MemoryFootprint {
+    fn memory(&self) -> MemoryUsage;
+}
+
+/// Slots of a hash map with room for `capacity` entries, one control byte each
+pub fn table_bytes<K, V>(capacity: usize) -> usize {
+    capacity * (size_of::<K>() + size_of::<V>() + 1)
+}
+
+/// Buffer of a vector or deque with room for `capacity` elements
+pub fn vec_bytes<T>(capacity: usize) -> usize {
+    capacity * size_of::<T>()
+}
+
+/// Heap bytes owned by a node ID
+pub fn id_bytes(id: &NodeId) -> usize {
+    id.0.capacity()
+}
+
+/// Duplicate suppression cache of message IDs, kept both as a set and in arrival order
+pub fn seen_cache_usage(seen: &HashSet<String>, order: &VecDeque<String>) -> MemoryUsage {
+    let ids: usize = seen.iter().chain(order).map(String::capacity).sum();
+    let bytes = table_bytes::<String, ()>(seen.capacity()) + vec_bytes::<String>(order.capacity()) + ids;
+    MemoryUsage::new(seen.len(), bytes)
+}
+
+/// Memory use of a node, per subsystem
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryReport {
+    pub subsystems: BTreeMap<String, MemoryUsage>,
+    pub total_bytes: usize,
+}
+
+impl MemoryReport {
+    /// Add `usage` to a subsystem, which may collect several structures
+    pub fn record(&mut self, subsystem: &str, usage: MemoryUsage) {
+        let entry = self.subsystems.entry(subsystem.to_string()).or_default();
+        *entry = *entry + usage;
+        self.total_bytes += usage.bytes;
+    }
+
+    pub fn get(&self, subsystem: &str) -> MemoryUsage {
+        self.subsystems.get(subsystem).copied().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_sums_subsystems() {
+        let mut report = MemoryReport::default();
+        report.record("seen_packets", MemoryUsage::new(2, 100));
+        report.record("seen_packets", MemoryUsage::new(3, 50));
+        report.record("dead_letters", MemoryUsage::new(1, 10));
+        assert_eq!(report.get("seen_packets"), MemoryUsage::new(5, 150));
+        assert_eq!(report.get("router"), MemoryUsage::default());
+        assert_eq!(report.total_bytes, 160);
+
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.contains("\"dead_letters\":{\"entries\":1,\"bytes\":10}"));
+    }
+
+    #[test]
+    fn test_estimates_grow_with_contents() {
+        assert_eq!(table_bytes::<u64, u32>(4), 4 * 13);
+        assert_eq!(vec_bytes::<u16>(3), 6);
+        assert_eq!(id_bytes(&NodeId::new("")), 0);
+        assert!(id_bytes(&NodeId::new("node_1")) >= 6);
+
+        let mut seen = HashSet::new();
+        let mut order = VecDeque::new();
+        let empty = seen_cache_usage(&seen, &order);
+        for id in ["a-1", "a-2"] {
+            seen.insert(id.to_string());
+            order.push_back(id.to_string());
+        }
+        let usage = seen_cache_usage(&seen, &order);
+        assert_eq!(usage.entries, 2);
+        assert!(usage.bytes >= empty.bytes + 12);
+    }
+}
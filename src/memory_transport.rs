@@ -0,0 +1,185 @@
+//! In-Memory Transport
+//!
+//! Channel-backed stand-in for the UDP socket and TCP listener of a
+//! `NetworkLayer`, so whole clusters run inside one process without
+//! binding ports. Every layer bound to the same `MemoryNetwork` gets two
+//! loopback-style addresses (one per transport) routed to a single inbox.
+//!
+//! Datagrams behave like UDP: a full inbox or an unknown address drops
+//! them silently. Reliable sends behave like a TCP write: they wait for
+//! room and fail when nobody is bound at the destination.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::mpsc;
+
+/// Frames buffered per inbox before datagrams are dropped
+const INBOX_CAPACITY: usize = 4096;
+/// Ports are handed out from the ephemeral range, like the OS does, so
+/// fixed "nothing listens here" addresses in tests stay unbound
+const FIRST_PORT: u16 = 49152;
+
+/// A frame in flight and the address it came from
+type Frame = (Vec<u8>, SocketAddr);
+
+#[derive(Debug, Default)]
+struct Bindings {
+    inboxes: HashMap<SocketAddr, mpsc::Sender<Frame>>,
+    next_port: u16,
+}
+
+impl Bindings {
+    /// Next port with nothing bound to it
+    fn allocate(&mut self) -> SocketAddr {
+        loop {
+            let port = self.next_port.max(FIRST_PORT);
+            self.next_port = port.checked_add(1).unwrap_or(FIRST_PORT);
+            let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+            if !self.inboxes.contains_key(&addr) {
+                return addr;
+            }
+        }
+    }
+}
+
+/// Address space shared by the in-memory layers of one cluster
+#[derive(Debug, Default)]
+pub struct MemoryNetwork {
+    bindings: Mutex<Bindings>,
+}
+
+impl MemoryNetwork {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Bind a socket with a fresh datagram and stream address
+    pub fn bind(self: &Arc<Self>) -> MemorySocket {
+        let (tx, rx) = mpsc::channel(INBOX_CAPACITY);
+        let mut bindings = self.bindings.lock().unwrap();
+        let udp_addr = bindings.allocate();
+        bindings.inboxes.insert(udp_addr, tx.clone());
+        let tcp_addr = bindings.allocate();
+        bindings.inboxes.insert(tcp_addr, tx);
+        MemorySocket {
+            network: Arc::clone(self),
+            udp_addr,
+            tcp_addr,
+            inbox: tokio::sync::Mutex::new(rx),
+        }
+    }
+
+    /// Number of bound sockets
+    pub fn len(&self) -> usize {
+        self.bindings.lock().unwrap().inboxes.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn inbox(&self, addr: &SocketAddr) -> Option<mpsc::Sender<Frame>> {
+        self.bindings.lock().unwrap().inboxes.get(addr).cloned()
+    }
+}
+
+/// One endpoint on a `MemoryNetwork`; unbinds when dropped
+#[derive(Debug)]
+pub struct MemorySocket {
+    network: Arc<MemoryNetwork>,
+    udp_addr: SocketAddr,
+    tcp_addr: SocketAddr,
+    inbox: tokio::sync::Mutex<mpsc::Receiver<Frame>>,
+}
+
+impl MemorySocket {
+    pub fn udp_addr(&self) -> SocketAddr {
+        self.udp_addr
+    }
+
+    pub fn tcp_addr(&self) -> SocketAddr {
+        self.tcp_addr
+    }
+
+    /// Send a datagram, dropping it if the destination is unbound or full
+    pub fn send_to(&self, frame: &[u8], dest: SocketAddr) -> io::Result<usize> {
+        if let Some(inbox) = self.network.inbox(&dest) {
+            let _ = inbox.try_send((frame.to_vec(), self.udp_addr));
+        }
+        Ok(frame.len())
+    }
+
+    /// Send a frame that must arrive, waiting for room in the destination inbox
+    pub async fn send_reliable(&self, frame: &[u8], dest: SocketAddr) -> io::Result<()> {
+        let inbox = self
+            .network
+            .inbox(&dest)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::ConnectionRefused))?;
+        inbox
+            .send((frame.to_vec(), self.udp_addr))
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::ConnectionReset))
+    }
+
+    /// Receive the next frame into `buffer`, truncating it like a datagram
+    pub async fn recv_from(&self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let (frame, src) = self
+            .inbox
+            .lock()
+            .await
+            .recv()
+            .await
+            .ok_or_else(|| io::Error::from(io::ErrorKind::ConnectionAborted))?;
+        let len = frame.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&frame[..len]);
+        Ok((len, src))
+    }
+}
+
+impl Drop for MemorySocket {
+    fn drop(&mut self) {
+        let mut bindings = self.network.bindings.lock().unwrap();
+        bindings.inboxes.remove(&self.udp_addr);
+        bindings.inboxes.remove(&self.tcp_addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_frames_reach_the_bound_socket() {
+        let network = MemoryNetwork::new();
+        let a = network.bind();
+        let b = network.bind();
+        assert_ne!(a.udp_addr(), b.udp_addr());
+        assert_ne!(b.udp_addr(), b.tcp_addr());
+        assert!(a.udp_addr().port() >= FIRST_PORT);
+
+        let mut buffer = [0u8; 16];
+        a.send_to(b"datagram", b.udp_addr()).unwrap();
+        a.send_reliable(b"stream", b.tcp_addr()).await.unwrap();
+        assert_eq!(b.recv_from(&mut buffer).await.unwrap(), (8, a.udp_addr()));
+        assert_eq!(&buffer[..8], b"datagram");
+        assert_eq!(b.recv_from(&mut buffer).await.unwrap(), (6, a.udp_addr()));
+        assert_eq!(&buffer[..6], b"stream");
+    }
+
+    #[tokio::test]
+    async fn test_unbound_destination() {
+        let network = MemoryNetwork::new();
+        let a = network.bind();
+        let b = network.bind();
+        let gone = b.tcp_addr();
+        drop(b);
+        assert_eq!(network.len(), 1);
+
+        assert!(a.send_to(b"lost", gone).is_ok());
+        let err = a.send_reliable(b"refused", gone).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+    }
+}
@@ -0,0 +1,473 @@
+//! Simulated Node Mobility
+//!
+//! Moves simulated nodes through a rectangular arena to evaluate routing in
+//! MANET and vehicular settings. Three models are provided: random
+//! waypoint, Gauss-Markov, and trace-driven (recorded positions,
+//! interpolated linearly). Links follow a unit-disk radio model: a link
+//! comes up when two nodes are within `range` of each other and goes down
+//! once they are more than `range + hysteresis` apart.
+//!
+//! Each `step` returns the link up/down events of that interval.
+//! `apply_link_events` feeds them to a `GPRouter`, and `run` alternates
+//! steps with batch simulations to show how delivery degrades as the
+//! topology drifts away from the one the coordinates were embedded for.
+
+use std::collections::{HashMap, HashSet};
+
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::routing::GPRouter;
+use crate::simulation::{BatchConfig, BatchSummary, Workload};
+
+/// Rectangular space nodes move in, in meters
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Arena {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Arena {
+    pub fn new(width: f64, height: f64) -> Self {
+        Self { width, height }
+    }
+
+    fn clamp(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (x.clamp(0.0, self.width), y.clamp(0.0, self.height))
+    }
+
+    fn random_point(&self, rng: &mut StdRng) -> (f64, f64) {
+        (rng.gen::<f64>() * self.width, rng.gen::<f64>() * self.height)
+    }
+}
+
+/// Unit-disk radio model
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RadioModel {
+    /// Distance within which a link comes up
+    pub range: f64,
+    /// Extra distance before an existing link goes down, to avoid flapping
+    pub hysteresis: f64,
+}
+
+impl RadioModel {
+    pub fn new(range: f64) -> Self {
+        Self { range, hysteresis: 0.0 }
+    }
+
+    pub fn with_hysteresis(mut self, hysteresis: f64) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    fn linked(&self, distance: f64, currently_up: bool) -> bool {
+        if currently_up {
+            distance <= self.range + self.hysteresis
+        } else {
+            distance <= self.range
+        }
+    }
+}
+
+/// One recorded position of a node
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TracePoint {
+    pub at_ms: u64,
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Recorded node positions over time
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MobilityTrace {
+    /// Points per node, sorted by time
+    pub points: HashMap<NodeId, Vec<TracePoint>>,
+}
+
+impl MobilityTrace {
+    /// Parse `node,at_ms,x,y` lines; blank lines and `#` comments are skipped
+    pub fn from_csv(text: &str) -> Result<Self, String> {
+        let mut trace = Self::default();
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let [node, at_ms, x, y] = fields[..] else {
+                return Err(format!("line {}: expected node,at_ms,x,y", line_no + 1));
+            };
+            let bad = |what: &str| format!("line {}: invalid {}", line_no + 1, what);
+            let point = TracePoint {
+                at_ms: at_ms.parse().map_err(|_| bad("at_ms"))?,
+                x: x.parse().map_err(|_| bad("x"))?,
+                y: y.parse().map_err(|_| bad("y"))?,
+            };
+            trace.points.entry(NodeId::new(node)).or_default().push(point);
+        }
+        for points in trace.points.values_mut() {
+            points.sort_by_key(|p| p.at_ms);
+        }
+        Ok(trace)
+    }
+
+    /// Position of `node` at `at_ms`, held constant before the first and
+    /// after the last point
+    pub fn position(&self, node: &NodeId, at_ms: u64) -> Option<(f64, f64)> {
+        let points = self.points.get(node)?;
+        let next = points.partition_point(|p| p.at_ms <= at_ms);
+        if next == 0 {
+            return points.first().map(|p| (p.x, p.y));
+        }
+        let prev = points[next - 1];
+        let Some(after) = points.get(next) else {
+            return Some((prev.x, prev.y));
+        };
+        let t = (at_ms - prev.at_ms) as f64 / (after.at_ms - prev.at_ms) as f64;
+        Some((prev.x + t * (after.x - prev.x), prev.y + t * (after.y - prev.y)))
+    }
+}
+
+/// How nodes move
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MobilityModel {
+    /// Nodes do not move
+    Static,
+    /// Travel in a straight line to a random point at a random speed,
+    /// pause, repeat
+    RandomWaypoint {
+        min_speed: f64,
+        max_speed: f64,
+        pause_ms: u64,
+    },
+    /// Speed and direction follow a first-order autoregressive process;
+    /// `alpha` near 1 gives smooth, vehicle-like paths, near 0 a random walk
+    GaussMarkov {
+        alpha: f64,
+        mean_speed: f64,
+        speed_std: f64,
+        direction_std: f64,
+        /// Interval between velocity updates
+        update_ms: u64,
+    },
+    /// Replay recorded positions; nodes missing from the trace stay put
+    Trace(MobilityTrace),
+}
+
+/// A link between two nodes came up or went down
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkEvent {
+    pub at_ms: u64,
+    pub a: NodeId,
+    pub b: NodeId,
+    pub up: bool,
+}
+
+#[derive(Debug, Clone)]
+struct MobileNode {
+    position: (f64, f64),
+    speed: f64,
+    direction: f64,
+    waypoint: Option<(f64, f64)>,
+    paused_until_ms: u64,
+    next_update_ms: u64,
+}
+
+/// Result of one `run` step
+#[derive(Debug, Clone)]
+pub struct MobilityStep {
+    pub at_ms: u64,
+    pub links: usize,
+    pub links_up: usize,
+    pub links_down: usize,
+    pub summary: BatchSummary,
+}
+
+/// Moving nodes and the links between them
+#[derive(Debug, Clone)]
+pub struct MobilitySimulation {
+    arena: Arena,
+    radio: RadioModel,
+    model: MobilityModel,
+    rng: StdRng,
+    now_ms: u64,
+    nodes: HashMap<NodeId, MobileNode>,
+    links: HashSet<(NodeId, NodeId)>,
+}
+
+impl MobilitySimulation {
+    pub fn new(arena: Arena, radio: RadioModel, model: MobilityModel, seed: u64) -> Self {
+        Self {
+            arena,
+            radio,
+            model,
+            rng: StdRng::seed_from_u64(seed),
+            now_ms: 0,
+            nodes: HashMap::new(),
+            links: HashSet::new(),
+        }
+    }
+
+    /// Place a node; its links appear in the events of the next step
+    pub fn add_node(&mut self, id: NodeId, position: (f64, f64)) {
+        let position = match &self.model {
+            MobilityModel::Trace(trace) => trace.position(&id, self.now_ms).unwrap_or(position),
+            _ => self.arena.clamp(position),
+        };
+        let direction = self.rng.gen::<f64>() * std::f64::consts::TAU;
+        let speed = match self.model {
+            MobilityModel::GaussMarkov { mean_speed, .. } => mean_speed,
+            _ => 0.0,
+        };
+        self.nodes.insert(
+            id,
+            MobileNode {
+                position,
+                speed,
+                direction,
+                waypoint: None,
+                paused_until_ms: 0,
+                next_update_ms: self.now_ms,
+            },
+        );
+    }
+
+    /// Place `count` nodes named "0", "1", ... uniformly at random
+    pub fn add_random_nodes(&mut self, count: usize) {
+        for i in 0..count {
+            let position = self.arena.random_point(&mut self.rng);
+            self.add_node(NodeId::new(format!("{}", i)), position);
+        }
+    }
+
+    pub fn now_ms(&self) -> u64 {
+        self.now_ms
+    }
+
+    pub fn position(&self, id: &NodeId) -> Option<(f64, f64)> {
+        self.nodes.get(id).map(|n| n.position)
+    }
+
+    /// Current links, each pair ordered by node ID
+    pub fn links(&self) -> impl Iterator<Item = &(NodeId, NodeId)> {
+        self.links.iter()
+    }
+
+    /// Advance time by `dt_ms`, move every node and return link changes
+    pub fn step(&mut self, dt_ms: u64) -> Vec<LinkEvent> {
+        self.now_ms += dt_ms;
+        // Fixed order, so a seed always produces the same movements
+        let mut ids: Vec<NodeId> = self.nodes.keys().cloned().collect();
+        ids.sort_by(|a, b| a.0.cmp(&b.0));
+        for id in &ids {
+            self.move_node(id, dt_ms);
+        }
+        self.update_links()
+    }
+
+    fn move_node(&mut self, id: &NodeId, dt_ms: u64) {
+        let now = self.now_ms;
+        let arena = self.arena;
+        let dt = dt_ms as f64 / 1000.0;
+        let Some(node) = self.nodes.get_mut(id) else {
+            return;
+        };
+        match &self.model {
+            MobilityModel::Static => {}
+            MobilityModel::Trace(trace) => {
+                if let Some(position) = trace.position(id, now) {
+                    node.position = position;
+                }
+            }
+            MobilityModel::RandomWaypoint { min_speed, max_speed, pause_ms } => {
+                if now < node.paused_until_ms {
+                    return;
+                }
+                let waypoint = *node.waypoint.get_or_insert_with(|| arena.random_point(&mut self.rng));
+                if node.speed <= 0.0 {
+                    node.speed = self.rng.gen_range(*min_speed..=max_speed.max(*min_speed));
+                }
+                let (dx, dy) = (waypoint.0 - node.position.0, waypoint.1 - node.position.1);
+                let remaining = (dx * dx + dy * dy).sqrt();
+                let travel = node.speed * dt;
+                if travel >= remaining {
+                    node.position = waypoint;
+                    node.waypoint = None;
+                    node.speed = 0.0;
+                    node.paused_until_ms = now + pause_ms;
+                } else {
+                    node.position.0 += dx / remaining * travel;
+                    node.position.1 += dy / remaining * travel;
+                }
+            }
+            MobilityModel::GaussMarkov { alpha, mean_speed, speed_std, direction_std, update_ms } => {
+                if now >= node.next_update_ms {
+                    let noise = (1.0 - alpha * alpha).max(0.0).sqrt();
+                    let (g1, g2) = gaussian_pair(&mut self.rng);
+                    node.speed = (alpha * node.speed + (1.0 - alpha) * mean_speed + noise * speed_std * g1).max(0.0);
+                    node.direction += noise * direction_std * g2;
+                    node.next_update_ms = now + (*update_ms).max(1);
+                }
+                let (mut x, mut y) = (
+                    node.position.0 + node.speed * node.direction.cos() * dt,
+                    node.position.1 + node.speed * node.direction.sin() * dt,
+                );
+                // Reflect off the arena walls
+                if x < 0.0 || x > arena.width {
+                    node.direction = std::f64::consts::PI - node.direction;
+                    x = if x < 0.0 { -x } else { 2.0 * arena.width - x };
+                }
+                if y < 0.0 || y > arena.height {
+                    node.direction = -node.direction;
+                    y = if y < 0.0 { -y } else { 2.0 * arena.height - y };
+                }
+                node.position = arena.clamp((x, y));
+            }
+        }
+    }
+
+    fn update_links(&mut self) -> Vec<LinkEvent> {
+        let mut nodes: Vec<(&NodeId, (f64, f64))> = self.nodes.iter().map(|(id, n)| (id, n.position)).collect();
+        nodes.sort_by(|a, b| a.0 .0.cmp(&b.0 .0));
+        let mut events = Vec::new();
+        let mut links = HashSet::new();
+        for (i, (a, pa)) in nodes.iter().enumerate() {
+            for (b, pb) in &nodes[i + 1..] {
+                let key = ((*a).clone(), (*b).clone());
+                let up = self.links.contains(&key);
+                let distance = ((pa.0 - pb.0).powi(2) + (pa.1 - pb.1).powi(2)).sqrt();
+                let linked = self.radio.linked(distance, up);
+                if linked != up {
+                    events.push(LinkEvent { at_ms: self.now_ms, a: key.0.clone(), b: key.1.clone(), up: linked });
+                }
+                if linked {
+                    links.insert(key);
+                }
+            }
+        }
+        self.links = links;
+        events
+    }
+
+    /// Step `steps` times, applying link changes to `router` and routing
+    /// `workload` after each step
+    ///
+    /// Coordinates in `router` are left alone, so the results show routing
+    /// over the embedding the router started with.
+    pub fn run(
+        &mut self,
+        router: &mut GPRouter,
+        steps: usize,
+        dt_ms: u64,
+        workload: &Workload,
+        config: &BatchConfig,
+    ) -> Vec<MobilityStep> {
+        (0..steps)
+            .map(|_| {
+                let events = self.step(dt_ms);
+                apply_link_events(router, &events);
+                let links_up = events.iter().filter(|e| e.up).count();
+                MobilityStep {
+                    at_ms: self.now_ms,
+                    links: self.links.len(),
+                    links_up,
+                    links_down: events.len() - links_up,
+                    summary: router.simulate_batch(workload, config).summary,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Add or remove router edges for link events
+pub fn apply_link_events(router: &mut GPRouter, events: &[LinkEvent]) {
+    for event in events {
+        if event.up {
+            router.add_edge(&event.a, &event.b);
+        } else {
+            router.remove_edge(&event.a, &event.b);
+        }
+    }
+}
+
+/// Two independent standard normal samples (Box-Muller)
+fn gaussian_pair(rng: &mut StdRng) -> (f64, f64) {
+    let u1: f64 = rng.gen_range(f64::EPSILON..1.0);
+    let u2: f64 = rng.gen();
+    let r = (-2.0 * u1.ln()).sqrt();
+    let theta = std::f64::consts::TAU * u2;
+    (r * theta.cos(), r * theta.sin())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinates::RoutingCoordinate;
+    use crate::routing::RoutingNode;
+    use crate::PoincareDiskPoint;
+
+    #[test]
+    fn test_trace_drives_link_events() {
+        let trace = MobilityTrace::from_csv("# node,at_ms,x,y\na,0,0,0\nb,0,50,0\nb,1000,150,0\nb,2000,50,0\n").unwrap();
+        assert_eq!(trace.position(&NodeId::new("b"), 500), Some((100.0, 0.0)));
+        assert!(MobilityTrace::from_csv("a,0,0").is_err());
+
+        let radio = RadioModel::new(100.0).with_hysteresis(20.0);
+        let mut sim = MobilitySimulation::new(Arena::new(200.0, 200.0), radio, MobilityModel::Trace(trace), 1);
+        sim.add_node(NodeId::new("a"), (0.0, 0.0));
+        sim.add_node(NodeId::new("b"), (0.0, 0.0));
+
+        let up = sim.step(0);
+        assert_eq!(up.len(), 1);
+        assert!(up[0].up);
+        // 110 m apart: within hysteresis, the link stays up
+        assert!(sim.step(600).is_empty());
+        let down = sim.step(400);
+        assert_eq!((down.len(), down[0].up, down[0].at_ms), (1, false, 1000));
+        // Coming back, the link returns only within the plain range
+        assert!(sim.step(400).is_empty());
+        assert!(sim.step(200).iter().all(|e| e.up));
+    }
+
+    #[test]
+    fn test_models_stay_in_arena_and_drive_router() {
+        let arena = Arena::new(500.0, 300.0);
+        let models = [
+            MobilityModel::RandomWaypoint { min_speed: 5.0, max_speed: 20.0, pause_ms: 500 },
+            MobilityModel::GaussMarkov { alpha: 0.8, mean_speed: 15.0, speed_std: 3.0, direction_std: 0.5, update_ms: 1000 },
+        ];
+        for model in models {
+            let mut sim = MobilitySimulation::new(arena, RadioModel::new(120.0), model, 42);
+            sim.add_random_nodes(20);
+            let start = sim.position(&NodeId::new("0")).unwrap();
+
+            let mut router = GPRouter::new();
+            for i in 0..20 {
+                let angle = i as f64 / 20.0 * std::f64::consts::TAU;
+                let point = PoincareDiskPoint::new(0.5 * angle.cos(), 0.5 * angle.sin()).unwrap();
+                router.add_node(RoutingNode::new(NodeId::new(format!("{}", i)), RoutingCoordinate::new(point, 0)));
+            }
+            let workload = Workload::Random { count: 20, seed: 3 };
+            let steps = sim.run(&mut router, 30, 1000, &workload, &BatchConfig::default());
+            assert_eq!(steps.len(), 30);
+
+            for i in 0..20 {
+                let id = NodeId::new(format!("{}", i));
+                let (x, y) = sim.position(&id).unwrap();
+                assert!((0.0..=arena.width).contains(&x) && (0.0..=arena.height).contains(&y));
+                // The router's edges are exactly the current radio links
+                let mut expected: Vec<&NodeId> = sim
+                    .links()
+                    .filter_map(|(a, b)| if *a == id { Some(b) } else if *b == id { Some(a) } else { None })
+                    .collect();
+                let mut actual: Vec<&NodeId> = router.get_node(&id).unwrap().neighbors.iter().collect();
+                expected.sort_by(|a, b| a.0.cmp(&b.0));
+                actual.sort_by(|a, b| a.0.cmp(&b.0));
+                assert_eq!(actual, expected);
+            }
+            assert_ne!(sim.position(&NodeId::new("0")).unwrap(), start);
+            assert!(steps.iter().map(|s| s.links_up + s.links_down).sum::<usize>() > steps[0].links_up);
+        }
+    }
+}
@@ -0,0 +1,247 @@
+//! Gravity-Pressure Mode Switching Policies
+//!
+//! A packet starts in Gravity mode and escalates when greedy forwarding hits
+//! a local minimum: first to Pressure mode for a bounded number of steps,
+//! then to the guaranteed fallback (Thorup-Zwick if the router has a table,
+//! otherwise Tree DFS). From Pressure and Tree it returns to Gravity once it
+//! is closer to the target than where recovery started. How large the
+//! Pressure budget is, whether Pressure is tried at all and how much closer
+//! a packet must get before returning are decided by a `ModeSwitchPolicy`,
+//! so recovery can be tuned per deployment without touching `GPRouter`.
+//!
+//! `ModeSwitchKind` names the built-in policies for configuration files and
+//! the runtime config API; custom policies implement `ModeSwitchPolicy` and
+//! are installed with `GPRouter::set_mode_switch_policy`.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::routing::RoutingMode;
+
+/// What a policy sees when a packet escalates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EscalationContext {
+    /// Nodes known to the router
+    pub node_count: usize,
+    /// Neighbors of the node at the local minimum
+    pub degree: usize,
+    /// Hops the packet has left
+    pub ttl: u32,
+    /// Mode switches the packet has been through
+    pub recovery_epoch: u32,
+}
+
+/// Decides when a packet escalates and when it returns to Gravity mode
+pub trait ModeSwitchPolicy: Send + Sync + std::fmt::Debug {
+    /// Short name for logs and reports
+    fn name(&self) -> &'static str;
+
+    /// Pressure steps for a packet stuck in a local minimum
+    ///
+    /// None skips Pressure mode and escalates straight to the fallback.
+    fn pressure_budget(&self, ctx: &EscalationContext) -> Option<u32>;
+
+    /// Whether a packet in `mode`, now at `distance` from its target,
+    /// returns to Gravity mode after recovery started at `threshold`
+    fn should_return(&self, mode: RoutingMode, distance: f64, threshold: f64) -> bool {
+        let _ = mode;
+        distance < threshold
+    }
+}
+
+/// Return once closer than the recovery start by more than `hysteresis`
+fn past_threshold(distance: f64, threshold: f64, hysteresis: f64) -> bool {
+    distance < threshold - hysteresis
+}
+
+/// Budget of half the known nodes, returning as soon as the packet is closer
+///
+/// The behavior `GPRouter` always had.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Classic;
+
+impl ModeSwitchPolicy for Classic {
+    fn name(&self) -> &'static str {
+        "classic"
+    }
+
+    fn pressure_budget(&self, ctx: &EscalationContext) -> Option<u32> {
+        Some((ctx.node_count as u32) / 2)
+    }
+}
+
+/// The same Pressure budget however large the network
+#[derive(Debug, Clone, Copy)]
+pub struct FixedBudget {
+    pub steps: u32,
+    pub hysteresis: f64,
+}
+
+impl ModeSwitchPolicy for FixedBudget {
+    fn name(&self) -> &'static str {
+        "fixed_budget"
+    }
+
+    fn pressure_budget(&self, _ctx: &EscalationContext) -> Option<u32> {
+        Some(self.steps)
+    }
+
+    fn should_return(&self, _mode: RoutingMode, distance: f64, threshold: f64) -> bool {
+        past_threshold(distance, threshold, self.hysteresis)
+    }
+}
+
+/// A share of the packet's remaining TTL, leaving the rest for the fallback
+#[derive(Debug, Clone, Copy)]
+pub struct TtlShare {
+    pub share: f64,
+    pub hysteresis: f64,
+}
+
+impl ModeSwitchPolicy for TtlShare {
+    fn name(&self) -> &'static str {
+        "ttl_share"
+    }
+
+    fn pressure_budget(&self, ctx: &EscalationContext) -> Option<u32> {
+        Some((ctx.ttl as f64 * self.share) as u32)
+    }
+
+    fn should_return(&self, _mode: RoutingMode, distance: f64, threshold: f64) -> bool {
+        past_threshold(distance, threshold, self.hysteresis)
+    }
+}
+
+/// Skip Pressure mode; the fallback takes over at the first local minimum
+///
+/// With `sticky`, a packet in the fallback stays there until delivered
+/// instead of returning to Gravity mode.
+#[derive(Debug, Clone, Copy)]
+pub struct Direct {
+    pub sticky: bool,
+}
+
+impl ModeSwitchPolicy for Direct {
+    fn name(&self) -> &'static str {
+        "direct"
+    }
+
+    fn pressure_budget(&self, _ctx: &EscalationContext) -> Option<u32> {
+        None
+    }
+
+    fn should_return(&self, _mode: RoutingMode, distance: f64, threshold: f64) -> bool {
+        !self.sticky && distance < threshold
+    }
+}
+
+/// Built-in policy selection for configuration
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ModeSwitchKind {
+    #[default]
+    Classic,
+    FixedBudget {
+        steps: u32,
+        #[serde(default)]
+        hysteresis: f64,
+    },
+    TtlShare {
+        share: f64,
+        #[serde(default)]
+        hysteresis: f64,
+    },
+    Direct {
+        #[serde(default)]
+        sticky: bool,
+    },
+}
+
+impl ModeSwitchKind {
+    /// Instantiate the policy
+    pub fn build(&self) -> Arc<dyn ModeSwitchPolicy> {
+        match *self {
+            Self::Classic => Arc::new(Classic),
+            Self::FixedBudget { steps, hysteresis } => Arc::new(FixedBudget { steps, hysteresis }),
+            Self::TtlShare { share, hysteresis } => Arc::new(TtlShare { share, hysteresis }),
+            Self::Direct { sticky } => Arc::new(Direct { sticky }),
+        }
+    }
+
+    /// Check that the parameters are usable
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            Self::FixedBudget { hysteresis, .. } | Self::TtlShare { hysteresis, .. }
+                if hysteresis.is_nan() || hysteresis < 0.0 =>
+            {
+                Err("Mode switch hysteresis must be non-negative".to_string())
+            }
+            Self::TtlShare { share, .. } if !(share > 0.0 && share < 1.0) => {
+                Err("ttl_share must be between 0 and 1".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinates::{NodeId, RoutingCoordinate};
+    use crate::routing::{GPRouter, PacketHeader, RoutingDecision, RoutingNode};
+    use crate::PoincareDiskPoint;
+
+    /// Source in a dead end: its only neighbor is farther from the target
+    fn dead_end() -> GPRouter {
+        let mut router = GPRouter::new();
+        let points = [("s", 0.3, 0.0), ("a", 0.7, -0.5), ("b", -0.2, -0.6), ("t", -0.3, 0.0)];
+        for (id, x, y) in points {
+            let coord = RoutingCoordinate::new(PoincareDiskPoint::new(x, y).unwrap(), 0);
+            router.add_node(RoutingNode::new(NodeId::new(id), coord));
+        }
+        router.add_edge(&NodeId::new("s"), &NodeId::new("a"));
+        router.add_edge(&NodeId::new("a"), &NodeId::new("b"));
+        router.add_edge(&NodeId::new("b"), &NodeId::new("t"));
+        router
+    }
+
+    fn first_hop(router: &GPRouter) -> (RoutingDecision, PacketHeader) {
+        let target = router.get_node(&NodeId::new("t")).unwrap().coord.point;
+        let mut packet = PacketHeader::new(NodeId::new("s"), NodeId::new("t"), target, 64);
+        let decision = router.route(&NodeId::new("s"), &mut packet);
+        (decision, packet)
+    }
+
+    #[test]
+    fn test_policies_decide_escalation() {
+        let mut router = dead_end();
+        assert_eq!(router.mode_switch_policy().name(), "classic");
+        let (decision, packet) = first_hop(&router);
+        assert!(matches!(decision, RoutingDecision::Forward { mode: RoutingMode::Pressure, .. }));
+        assert_eq!(packet.pressure_budget, 2);
+
+        router.set_mode_switch_policy(ModeSwitchKind::TtlShare { share: 0.25, hysteresis: 0.0 }.build());
+        assert_eq!(first_hop(&router).1.pressure_budget, 16);
+
+        router.set_mode_switch_policy(ModeSwitchKind::Direct { sticky: false }.build());
+        let (decision, packet) = first_hop(&router);
+        assert!(matches!(decision, RoutingDecision::Forward { mode: RoutingMode::Tree, .. }));
+        assert_eq!(packet.pressure_budget, 0);
+    }
+
+    #[test]
+    fn test_return_conditions_and_validation() {
+        let fixed = FixedBudget { steps: 4, hysteresis: 0.5 };
+        assert!(!fixed.should_return(RoutingMode::Pressure, 1.7, 2.0));
+        assert!(fixed.should_return(RoutingMode::Pressure, 1.4, 2.0));
+        assert!(Classic.should_return(RoutingMode::Tree, 1.9, 2.0));
+        assert!(!Direct { sticky: true }.should_return(RoutingMode::Tree, 0.0, 2.0));
+
+        assert!(ModeSwitchKind::default().validate().is_ok());
+        assert!(ModeSwitchKind::TtlShare { share: 1.5, hysteresis: 0.0 }.validate().is_err());
+        assert!(ModeSwitchKind::FixedBudget { steps: 3, hysteresis: -1.0 }.validate().is_err());
+        let parsed: ModeSwitchKind = serde_json::from_str(r#"{"kind":"fixed_budget","steps":8}"#).unwrap();
+        assert_eq!(parsed, ModeSwitchKind::FixedBudget { steps: 8, hysteresis: 0.0 });
+    }
+}
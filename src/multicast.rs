@@ -0,0 +1,495 @@
+//! Overlay Multicast
+//!
+//! Each group has a rendezvous coordinate derived from its name, exactly like
+//! a node's anchor coordinate. Members join by sending a Join greedily toward
+//! that coordinate; every node the Join passes becomes a forwarder and keeps
+//! the sender as a child, and the node with no neighbor closer to the
+//! coordinate becomes the root. The result is a shared tree built from
+//! reverse-path joins.
+//!
+//! Publishes travel toward the root until they reach a node on the tree and
+//! are then replicated only along tree edges (parent and children, never back
+//! to the link they came from). Tree state is soft: joins are refreshed
+//! periodically, children that stop refreshing expire, and when churn changes
+//! a node's greedy parent it leaves the old one and joins the new one.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::{AnchorCoordinate, NodeId};
+use crate::memory::{self, MemoryUsage};
+use crate::PoincareDiskPoint;
+
+/// Multicast tree maintenance settings
+#[derive(Debug, Clone)]
+pub struct MulticastConfig {
+    /// Joins are re-sent to the parent this often
+    pub refresh_interval_ms: u64,
+    /// Children that have not refreshed for this long are dropped
+    pub child_timeout_ms: u64,
+    /// Recently seen publish IDs kept for duplicate suppression
+    pub dedup_capacity: usize,
+    /// Undelivered messages kept per joined group
+    pub inbox_capacity: usize,
+}
+
+impl Default for MulticastConfig {
+    fn default() -> Self {
+        Self {
+            refresh_interval_ms: 5_000,
+            child_timeout_ms: 15_000,
+            dedup_capacity: 4096,
+            inbox_capacity: 1024,
+        }
+    }
+}
+
+/// Rendezvous coordinate of a group
+pub fn group_coordinate(group: &str) -> PoincareDiskPoint {
+    AnchorCoordinate::from_id(&NodeId::new(format!("group:{}", group))).point
+}
+
+/// Multicast control and data messages, exchanged between neighbors
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MulticastMessage {
+    /// Add the sender as a child in the group's tree
+    Join { group: String },
+    /// Remove the sender from the group's tree
+    Leave { group: String },
+    /// Group payload
+    Publish {
+        group: String,
+        message_id: String,
+        origin: NodeId,
+        payload: Vec<u8>,
+    },
+}
+
+/// A message delivered to a local group member
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupMessage {
+    pub origin: NodeId,
+    pub message_id: String,
+    pub payload: Vec<u8>,
+}
+
+/// Per-group tree state of one node
+#[derive(Debug, Clone, Default)]
+pub struct GroupState {
+    /// This node is a member (not only a forwarder)
+    pub member: bool,
+    /// Next hop toward the rendezvous point; None at the root
+    pub parent: Option<NodeId>,
+    /// Children and the time of their last join
+    pub children: HashMap<NodeId, u64>,
+    /// Time of the last join sent to the parent
+    last_refresh_ms: Option<u64>,
+    inbox: VecDeque<GroupMessage>,
+}
+
+impl GroupState {
+    /// Neither a member nor forwarding for anyone
+    fn is_empty(&self) -> bool {
+        !self.member && self.children.is_empty()
+    }
+}
+
+/// Outgoing multicast messages as (neighbor, message)
+pub type MulticastActions = Vec<(NodeId, MulticastMessage)>;
+
+/// Multicast tree state of one node
+#[derive(Debug, Clone, Default)]
+pub struct MulticastManager {
+    pub config: MulticastConfig,
+    groups: HashMap<String, GroupState>,
+    seen: HashSet<String>,
+    seen_order: VecDeque<String>,
+}
+
+impl MulticastManager {
+    pub fn new(config: MulticastConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Memory held by the seen message IDs
+    pub fn seen_memory(&self) -> MemoryUsage {
+        memory::seen_cache_usage(&self.seen, &self.seen_order)
+    }
+
+    /// Greedy next hop toward a group's rendezvous coordinate
+    ///
+    /// Returns None when no neighbor is strictly closer than this node,
+    /// i.e. this node is the group's root.
+    pub fn parent_toward(
+        group: &str,
+        self_coord: &PoincareDiskPoint,
+        neighbors: &[(NodeId, PoincareDiskPoint)],
+    ) -> Option<NodeId> {
+        let target = group_coordinate(group);
+        let own = self_coord.hyperbolic_distance(&target);
+        neighbors
+            .iter()
+            .map(|(id, coord)| (coord.hyperbolic_distance(&target), id))
+            .filter(|(d, _)| *d < own)
+            .min_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1 .0.cmp(&b.1 .0)))
+            .map(|(_, id)| id.clone())
+    }
+
+    /// Tree state for a group, if this node is on its tree
+    pub fn group(&self, group: &str) -> Option<&GroupState> {
+        self.groups.get(group)
+    }
+
+    /// Groups this node is a member of
+    pub fn joined_groups(&self) -> Vec<String> {
+        self.groups
+            .iter()
+            .filter(|(_, g)| g.member)
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Join a group as a member
+    pub fn join(
+        &mut self,
+        group: &str,
+        self_coord: &PoincareDiskPoint,
+        neighbors: &[(NodeId, PoincareDiskPoint)],
+        now_ms: u64,
+    ) -> MulticastActions {
+        self.groups.entry(group.to_string()).or_default().member = true;
+        self.refresh_group(group, self_coord, neighbors, now_ms, true)
+    }
+
+    /// Leave a group; the node stays a forwarder while it has children
+    pub fn leave(&mut self, group: &str) -> MulticastActions {
+        if let Some(state) = self.groups.get_mut(group) {
+            state.member = false;
+            state.inbox.clear();
+        }
+        self.prune(group)
+    }
+
+    /// Handle a Join from a neighbor
+    pub fn on_join(
+        &mut self,
+        from: &NodeId,
+        group: &str,
+        self_coord: &PoincareDiskPoint,
+        neighbors: &[(NodeId, PoincareDiskPoint)],
+        now_ms: u64,
+    ) -> MulticastActions {
+        let state = self.groups.entry(group.to_string()).or_default();
+        let grafted = state.last_refresh_ms.is_none();
+        state.children.insert(from.clone(), now_ms);
+        // A node newly on the tree joins upstream right away
+        self.refresh_group(group, self_coord, neighbors, now_ms, grafted)
+    }
+
+    /// Handle a Leave from a neighbor
+    pub fn on_leave(&mut self, from: &NodeId, group: &str) -> MulticastActions {
+        if let Some(state) = self.groups.get_mut(group) {
+            state.children.remove(from);
+        }
+        self.prune(group)
+    }
+
+    /// Periodic maintenance: expire children, follow parent changes, refresh joins
+    pub fn maintain(
+        &mut self,
+        self_coord: &PoincareDiskPoint,
+        neighbors: &[(NodeId, PoincareDiskPoint)],
+        now_ms: u64,
+    ) -> MulticastActions {
+        let neighbor_ids: HashSet<&NodeId> = neighbors.iter().map(|(id, _)| id).collect();
+        let timeout = self.config.child_timeout_ms;
+        let groups: Vec<String> = self.groups.keys().cloned().collect();
+
+        let mut actions = Vec::new();
+        for group in groups {
+            if let Some(state) = self.groups.get_mut(&group) {
+                state.children.retain(|child, refreshed| {
+                    neighbor_ids.contains(child) && now_ms.saturating_sub(*refreshed) <= timeout
+                });
+            }
+            let pruned = self.prune(&group);
+            if !pruned.is_empty() || !self.groups.contains_key(&group) {
+                actions.extend(pruned);
+                continue;
+            }
+            actions.extend(self.refresh_group(&group, self_coord, neighbors, now_ms, false));
+        }
+        actions
+    }
+
+    /// Publish to a group from this node
+    pub fn publish(
+        &mut self,
+        self_id: &NodeId,
+        group: &str,
+        message_id: String,
+        payload: Vec<u8>,
+        self_coord: &PoincareDiskPoint,
+        neighbors: &[(NodeId, PoincareDiskPoint)],
+    ) -> MulticastActions {
+        let message = MulticastMessage::Publish {
+            group: group.to_string(),
+            message_id,
+            origin: self_id.clone(),
+            payload,
+        };
+        self.on_publish(None, message, self_coord, neighbors)
+    }
+
+    /// Handle a Publish received from `from` (None for local publishes)
+    ///
+    /// On-tree nodes replicate along tree edges and deliver to their inbox if
+    /// they are members; off-tree nodes pass the message toward the root.
+    pub fn on_publish(
+        &mut self,
+        from: Option<&NodeId>,
+        message: MulticastMessage,
+        self_coord: &PoincareDiskPoint,
+        neighbors: &[(NodeId, PoincareDiskPoint)],
+    ) -> MulticastActions {
+        let MulticastMessage::Publish { group, message_id, origin, payload } = message else {
+            return Vec::new();
+        };
+        if !self.mark_seen(&message_id) {
+            return Vec::new();
+        }
+
+        let next_hops: Vec<NodeId> = match self.groups.get_mut(&group) {
+            Some(state) => {
+                if state.member && from.is_some() {
+                    if state.inbox.len() >= self.config.inbox_capacity {
+                        state.inbox.pop_front();
+                    }
+                    state.inbox.push_back(GroupMessage {
+                        origin: origin.clone(),
+                        message_id: message_id.clone(),
+                        payload: payload.clone(),
+                    });
+                }
+                state.parent.iter().chain(state.children.keys()).cloned().collect()
+            }
+            None => Self::parent_toward(&group, self_coord, neighbors).into_iter().collect(),
+        };
+
+        next_hops
+            .into_iter()
+            .filter(|hop| Some(hop) != from)
+            .map(|hop| {
+                let message = MulticastMessage::Publish {
+                    group: group.clone(),
+                    message_id: message_id.clone(),
+                    origin: origin.clone(),
+                    payload: payload.clone(),
+                };
+                (hop, message)
+            })
+            .collect()
+    }
+
+    /// Take the messages delivered to a joined group
+    pub fn take_messages(&mut self, group: &str) -> Vec<GroupMessage> {
+        self.groups
+            .get_mut(group)
+            .map(|state| state.inbox.drain(..).collect())
+            .unwrap_or_default()
+    }
+
+    /// Recompute the parent and send a join if it changed or is due
+    fn refresh_group(
+        &mut self,
+        group: &str,
+        self_coord: &PoincareDiskPoint,
+        neighbors: &[(NodeId, PoincareDiskPoint)],
+        now_ms: u64,
+        force: bool,
+    ) -> MulticastActions {
+        let parent = Self::parent_toward(group, self_coord, neighbors);
+        let interval = self.config.refresh_interval_ms;
+        let Some(state) = self.groups.get_mut(group) else {
+            return Vec::new();
+        };
+
+        let mut actions = Vec::new();
+        let changed = state.parent != parent;
+        if changed {
+            if let Some(old) = state.parent.take() {
+                actions.push((old, MulticastMessage::Leave { group: group.to_string() }));
+            }
+            // Never keep the new parent as a child, or the tree would loop
+            if let Some(new) = &parent {
+                state.children.remove(new);
+            }
+            state.parent = parent;
+        }
+
+        let due = state
+            .last_refresh_ms
+            .is_none_or(|last| now_ms.saturating_sub(last) >= interval);
+        if force || changed || due {
+            state.last_refresh_ms = Some(now_ms);
+            if let Some(parent) = &state.parent {
+                actions.push((parent.clone(), MulticastMessage::Join { group: group.to_string() }));
+            }
+        }
+        actions
+    }
+
+    /// Drop state for a group nobody needs any more and leave the parent
+    fn prune(&mut self, group: &str) -> MulticastActions {
+        match self.groups.get(group) {
+            Some(state) if state.is_empty() => {
+                let parent = self.groups.remove(group).and_then(|s| s.parent);
+                parent
+                    .map(|p| (p, MulticastMessage::Leave { group: group.to_string() }))
+                    .into_iter()
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Record a publish ID; false if it was already seen
+    fn mark_seen(&mut self, message_id: &str) -> bool {
+        if !self.seen.insert(message_id.to_string()) {
+            return false;
+        }
+        self.seen_order.push_back(message_id.to_string());
+        while self.seen_order.len() > self.config.dedup_capacity {
+            if let Some(old) = self.seen_order.pop_front() {
+                self.seen.remove(&old);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Simulated overlay: nodes on a line, each linked to its two neighbors
+    struct Overlay {
+        ids: Vec<NodeId>,
+        coords: Vec<PoincareDiskPoint>,
+        links: Vec<Vec<usize>>,
+        managers: Vec<MulticastManager>,
+    }
+
+    impl Overlay {
+        fn line(n: usize, group: &str) -> Self {
+            // Place nodes along the ray through the rendezvous coordinate, root at the end
+            let angle = group_coordinate(group).angle();
+            let ids = (0..n).map(|i| NodeId::new(format!("n{}", i))).collect();
+            let coords = (0..n)
+                .map(|i| PoincareDiskPoint::from_polar(0.9 * i as f64 / n as f64, angle).unwrap())
+                .collect();
+            let links = (0..n)
+                .map(|i| [i.wrapping_sub(1), i + 1].into_iter().filter(|&j| j < n).collect())
+                .collect();
+            Self {
+                ids,
+                coords,
+                links,
+                managers: vec![MulticastManager::default(); n],
+            }
+        }
+
+        fn neighbors(&self, i: usize) -> Vec<(NodeId, PoincareDiskPoint)> {
+            self.links[i].iter().map(|&j| (self.ids[j].clone(), self.coords[j])).collect()
+        }
+
+        fn index(&self, id: &NodeId) -> usize {
+            self.ids.iter().position(|x| x == id).unwrap()
+        }
+
+        /// Deliver actions until quiet, returning how many publishes crossed links
+        fn run(&mut self, from: usize, mut actions: MulticastActions, now: u64) -> usize {
+            let mut queue: VecDeque<(usize, NodeId, MulticastMessage)> =
+                actions.drain(..).map(|(to, m)| (from, to, m)).collect();
+            let mut publishes = 0;
+            while let Some((src, to, message)) = queue.pop_front() {
+                let dst = self.index(&to);
+                let neighbors = self.neighbors(dst);
+                let coord = self.coords[dst];
+                let src_id = self.ids[src].clone();
+                let manager = &mut self.managers[dst];
+                let out = match &message {
+                    MulticastMessage::Join { group } => manager.on_join(&src_id, group, &coord, &neighbors, now),
+                    MulticastMessage::Leave { group } => manager.on_leave(&src_id, group),
+                    MulticastMessage::Publish { .. } => {
+                        publishes += 1;
+                        manager.on_publish(Some(&src_id), message, &coord, &neighbors)
+                    }
+                };
+                queue.extend(out.into_iter().map(|(to, m)| (dst, to, m)));
+            }
+            publishes
+        }
+    }
+
+    #[test]
+    fn test_publish_follows_tree_edges_only() {
+        let mut overlay = Overlay::line(6, "g");
+        for member in [0, 2] {
+            let (coord, neighbors) = (overlay.coords[member], overlay.neighbors(member));
+            let actions = overlay.managers[member].join("g", &coord, &neighbors, 0);
+            overlay.run(member, actions, 0);
+        }
+        // Root is the node nearest the rendezvous point; the tree spans 0..=5
+        assert!(overlay.managers[5].group("g").unwrap().parent.is_none());
+        assert_eq!(overlay.managers[3].group("g").unwrap().parent, Some(overlay.ids[4].clone()));
+
+        // Publish from member 2: it reaches 0 and climbs to the root, once per edge
+        let (coord, neighbors) = (overlay.coords[2], overlay.neighbors(2));
+        let id = overlay.ids[2].clone();
+        let actions = overlay.managers[2].publish(&id, "g", "m1".into(), b"hi".to_vec(), &coord, &neighbors);
+        assert_eq!(overlay.run(2, actions, 0), 5);
+
+        let got = overlay.managers[0].take_messages("g");
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].origin, id);
+        assert!(overlay.managers[2].take_messages("g").is_empty());
+        assert!(overlay.managers[4].take_messages("g").is_empty());
+
+        // After the far member leaves, the branch below 2 is pruned
+        let actions = overlay.managers[0].leave("g");
+        overlay.run(0, actions, 0);
+        assert!(overlay.managers[1].group("g").is_none());
+        assert!(overlay.managers[2].group("g").is_some());
+    }
+
+    #[test]
+    fn test_tree_repairs_after_parent_failure() {
+        let mut overlay = Overlay::line(4, "g");
+        let (coord, neighbors) = (overlay.coords[0], overlay.neighbors(0));
+        let actions = overlay.managers[0].join("g", &coord, &neighbors, 0);
+        overlay.run(0, actions, 0);
+        assert_eq!(overlay.managers[1].group("g").unwrap().parent, Some(overlay.ids[2].clone()));
+
+        // Node 2 fails; node 1 becomes the root of what remains and drops nothing
+        overlay.links = vec![vec![1], vec![0], vec![3], vec![2]];
+        for i in [0, 1] {
+            let (coord, neighbors) = (overlay.coords[i], overlay.neighbors(i));
+            let actions = overlay.managers[i].maintain(&coord, &neighbors, 20_000);
+            overlay.run(i, actions, 20_000);
+        }
+        let state = overlay.managers[1].group("g").unwrap();
+        assert!(state.parent.is_none());
+        assert!(state.children.contains_key(&overlay.ids[0]));
+
+        // Children that stop refreshing expire
+        overlay.links = vec![vec![], vec![], vec![3], vec![2]];
+        let (coord, neighbors) = (overlay.coords[1], overlay.neighbors(1));
+        overlay.managers[1].maintain(&coord, &neighbors, 40_000);
+        assert!(overlay.managers[1].group("g").is_none());
+    }
+}
@@ -0,0 +1,300 @@
+//! Multi-Homed Neighbors
+//!
+//! A neighbor may be reachable on several endpoints: UDP and TCP on
+//! different ports, or on different interfaces. Nodes advertise their
+//! endpoints in discovery packets, each with a preference, and keep the
+//! health of every endpoint of a neighbor. Packets to a neighbor pick a
+//! transport by QoS class, so small control traffic goes out as datagrams
+//! and bulk traffic over TCP, and fall over to the next endpoint when a
+//! send fails. An endpoint that failed `failure_threshold` times in a row
+//! is skipped for `retry_after_ms`, unless nothing else is left.
+//!
+//! Neighbors known only by a single address, from older nodes or manual
+//! configuration, get one TCP endpoint at that address, which is how every
+//! neighbor was reached before.
+
+use std::net::{IpAddr, SocketAddr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::memory;
+use crate::ttl_policy::QosClass;
+
+/// Largest packet sent over a UDP endpoint; bigger ones need a stream
+pub const UDP_DATAGRAM_BUDGET: usize = 1200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    Udp,
+    Tcp,
+}
+
+/// One way to reach a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Endpoint {
+    pub transport: Transport,
+    pub addr: SocketAddr,
+    /// Lower is preferred among endpoints of the same transport
+    #[serde(default)]
+    pub preference: u8,
+}
+
+impl Endpoint {
+    pub fn new(transport: Transport, addr: SocketAddr) -> Self {
+        Self { transport, addr, preference: 0 }
+    }
+}
+
+/// Transports tried for each QoS class, in order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransportPolicy {
+    pub control: Vec<Transport>,
+    pub interactive: Vec<Transport>,
+    pub standard: Vec<Transport>,
+    pub bulk: Vec<Transport>,
+}
+
+impl Default for TransportPolicy {
+    fn default() -> Self {
+        Self {
+            control: vec![Transport::Udp, Transport::Tcp],
+            interactive: vec![Transport::Udp, Transport::Tcp],
+            standard: vec![Transport::Tcp, Transport::Udp],
+            bulk: vec![Transport::Tcp],
+        }
+    }
+}
+
+impl TransportPolicy {
+    pub fn order(&self, class: QosClass) -> &[Transport] {
+        match class {
+            QosClass::Control => &self.control,
+            QosClass::Interactive => &self.interactive,
+            QosClass::Standard => &self.standard,
+            QosClass::Bulk => &self.bulk,
+        }
+    }
+}
+
+/// Multi-homing settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MultihomingConfig {
+    pub enabled: bool,
+    /// Endpoints advertised besides the node's own UDP and TCP sockets,
+    /// such as addresses on other interfaces or behind port forwards
+    pub advertise: Vec<Endpoint>,
+    pub transports: TransportPolicy,
+    /// Consecutive send failures after which an endpoint is skipped
+    pub failure_threshold: u32,
+    /// How long a failed endpoint is skipped
+    pub retry_after_ms: u64,
+}
+
+impl Default for MultihomingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            advertise: Vec::new(),
+            transports: TransportPolicy::default(),
+            failure_threshold: 3,
+            retry_after_ms: 5_000,
+        }
+    }
+}
+
+impl MultihomingConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.failure_threshold == 0 {
+            return Err("Multi-homing failure threshold must be positive".to_string());
+        }
+        let policy = &self.transports;
+        if [&policy.control, &policy.interactive, &policy.standard, &policy.bulk].iter().any(|t| t.is_empty()) {
+            return Err("Every QoS class needs at least one transport".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// An endpoint of a neighbor and how sends to it went
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EndpointStatus {
+    pub endpoint: Endpoint,
+    pub sent: u64,
+    pub failed: u64,
+    pub consecutive_failures: u32,
+    /// Skipped until this time after repeated failures
+    pub down_until_ms: Option<u64>,
+}
+
+impl EndpointStatus {
+    fn new(endpoint: Endpoint) -> Self {
+        Self { endpoint, sent: 0, failed: 0, consecutive_failures: 0, down_until_ms: None }
+    }
+
+    pub fn is_up(&self, now_ms: u64) -> bool {
+        self.down_until_ms.is_none_or(|until| now_ms >= until)
+    }
+}
+
+/// Endpoints of one neighbor with their health
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EndpointSet {
+    endpoints: Vec<EndpointStatus>,
+}
+
+impl EndpointSet {
+    /// Heap bytes of the endpoint list
+    pub fn heap_bytes(&self) -> usize {
+        memory::vec_bytes::<EndpointStatus>(self.endpoints.capacity())
+    }
+
+    /// A neighbor known by a single address, reached over TCP
+    pub fn single(addr: SocketAddr) -> Self {
+        Self { endpoints: vec![EndpointStatus::new(Endpoint::new(Transport::Tcp, addr))] }
+    }
+
+    /// Endpoints a neighbor advertised
+    ///
+    /// Unspecified addresses (the node bound to all interfaces) are taken
+    /// to be on `source`, the address the advertisement came from.
+    pub fn advertised(endpoints: &[Endpoint], source: IpAddr) -> Self {
+        let mut set = Self::default();
+        for endpoint in endpoints {
+            let mut endpoint = *endpoint;
+            if endpoint.addr.ip().is_unspecified() {
+                endpoint.addr.set_ip(source);
+            }
+            if !set.endpoints.iter().any(|s| s.endpoint.transport == endpoint.transport && s.endpoint.addr == endpoint.addr) {
+                set.endpoints.push(EndpointStatus::new(endpoint));
+            }
+        }
+        set
+    }
+
+    /// Carry over the health of endpoints this set shares with `previous`
+    pub fn keep_health(&mut self, previous: &EndpointSet) {
+        for status in &mut self.endpoints {
+            let same = |s: &&EndpointStatus| {
+                s.endpoint.transport == status.endpoint.transport && s.endpoint.addr == status.endpoint.addr
+            };
+            if let Some(old) = previous.endpoints.iter().find(same) {
+                let endpoint = status.endpoint;
+                *status = EndpointStatus { endpoint, ..old.clone() };
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.endpoints.is_empty()
+    }
+
+    pub fn endpoints(&self) -> &[EndpointStatus] {
+        &self.endpoints
+    }
+
+    /// Preferred address for `transport`, if one is known
+    pub fn address(&self, transport: Transport) -> Option<SocketAddr> {
+        self.endpoints
+            .iter()
+            .filter(|s| s.endpoint.transport == transport)
+            .min_by_key(|s| s.endpoint.preference)
+            .map(|s| s.endpoint.addr)
+    }
+
+    /// Endpoints to try for a packet, best first
+    ///
+    /// Transports follow `order`, then preference; endpoints that are down
+    /// come last so a send still has somewhere to go. UDP is skipped for
+    /// packets over `UDP_DATAGRAM_BUDGET`.
+    pub fn candidates(&self, order: &[Transport], packet_size: usize, now_ms: u64) -> Vec<Endpoint> {
+        let mut ranked: Vec<(bool, usize, u8, Endpoint)> = self
+            .endpoints
+            .iter()
+            .filter(|s| s.endpoint.transport != Transport::Udp || packet_size <= UDP_DATAGRAM_BUDGET)
+            .filter_map(|s| {
+                let rank = order.iter().position(|t| *t == s.endpoint.transport)?;
+                Some((!s.is_up(now_ms), rank, s.endpoint.preference, s.endpoint))
+            })
+            .collect();
+        ranked.sort_by_key(|(down, rank, preference, _)| (*down, *rank, *preference));
+        ranked.into_iter().map(|(_, _, _, endpoint)| endpoint).collect()
+    }
+
+    /// Record the outcome of a send to `endpoint`
+    pub fn record(&mut self, endpoint: &Endpoint, ok: bool, now_ms: u64, config: &MultihomingConfig) {
+        let Some(status) = self
+            .endpoints
+            .iter_mut()
+            .find(|s| s.endpoint.transport == endpoint.transport && s.endpoint.addr == endpoint.addr)
+        else {
+            return;
+        };
+        status.sent += 1;
+        if ok {
+            status.consecutive_failures = 0;
+            status.down_until_ms = None;
+            return;
+        }
+        status.failed += 1;
+        status.consecutive_failures += 1;
+        if status.consecutive_failures >= config.failure_threshold {
+            status.down_until_ms = Some(now_ms + config.retry_after_ms);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([10, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn test_candidates_follow_qos_and_size() {
+        let source: IpAddr = [192, 168, 1, 5].into();
+        let advertised = [
+            Endpoint::new(Transport::Udp, "0.0.0.0:7000".parse().unwrap()),
+            Endpoint { preference: 1, ..Endpoint::new(Transport::Tcp, addr(7001)) },
+            Endpoint::new(Transport::Tcp, addr(7002)),
+        ];
+        let set = EndpointSet::advertised(&advertised, source);
+        let udp = Endpoint::new(Transport::Udp, SocketAddr::new(source, 7000));
+        let policy = TransportPolicy::default();
+
+        let control = set.candidates(policy.order(QosClass::Control), 100, 0);
+        assert_eq!(control[0], udp);
+        assert_eq!(control[1].addr, addr(7002));
+        assert_eq!(control.len(), 3);
+        // Too big for a datagram
+        assert!(!set.candidates(policy.order(QosClass::Control), 5_000, 0).contains(&udp));
+        let bulk = set.candidates(policy.order(QosClass::Bulk), 100, 0);
+        assert_eq!(bulk.iter().map(|e| e.addr.port()).collect::<Vec<_>>(), vec![7002, 7001]);
+    }
+
+    #[test]
+    fn test_failed_endpoint_skipped_until_retry() {
+        let config = MultihomingConfig { failure_threshold: 2, retry_after_ms: 1_000, ..Default::default() };
+        let tcp = [Transport::Tcp];
+        let primary = Endpoint::new(Transport::Tcp, addr(1));
+        let backup = Endpoint { preference: 5, ..Endpoint::new(Transport::Tcp, addr(2)) };
+        let mut set = EndpointSet::advertised(&[primary, backup], addr(0).ip());
+
+        set.record(&primary, false, 0, &config);
+        assert_eq!(set.candidates(&tcp, 10, 0)[0], primary);
+        set.record(&primary, false, 0, &config);
+        assert_eq!(set.candidates(&tcp, 10, 0), vec![backup, primary]);
+        assert_eq!(set.candidates(&tcp, 10, 1_000)[0], primary);
+
+        // Rediscovery keeps the health of endpoints that are still advertised
+        let mut refreshed = EndpointSet::advertised(&[primary], addr(0).ip());
+        refreshed.keep_health(&set);
+        assert_eq!(refreshed.endpoints()[0].failed, 2);
+        set.record(&primary, true, 1_000, &config);
+        assert!(set.endpoints()[0].is_up(0));
+    }
+}
@@ -0,0 +1,323 @@
+//! NAT and Firewall Reachability Self-Test
+//!
+//! A node behind a NAT or firewall cannot tell from its own sockets whether
+//! peers can reach it. It therefore asks a few neighbors, over UDP and TCP,
+//! which address its probe came from. Each neighbor answers over the same
+//! transport and, for UDP, also asks one of its own neighbors that the
+//! prober does not know to send an unsolicited datagram to that address.
+//! From the answers the node classifies its situation:
+//!
+//! - `open`: the address seen is our own, and unsolicited UDP arrives
+//! - `full_cone`: translated to one address for every peer, and
+//!   unsolicited UDP arrives
+//! - `symmetric`: translated to a different address per peer, so nothing
+//!   can reach us before we sent to it
+//! - `tcp_only`: UDP answers come back but unsolicited UDP is filtered;
+//!   peers reach us over TCP
+//! - `udp_blocked`: no UDP answers at all, only TCP
+//!
+//! The result is advertised in discovery alongside the other capabilities.
+//! A node that cannot take unsolicited UDP stops advertising its UDP
+//! endpoint, one with UDP blocked sends every QoS class over TCP, and onion
+//! relays are picked among neighbors that peers can reach where there are
+//! enough of them.
+//!
+//! Probes are only answered for neighbors, and each one causes at most one
+//! datagram to an address other than the prober's, so the test cannot be
+//! used for amplification.
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::multihoming::{Transport, TransportPolicy};
+
+/// NAT self-test settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NatConfig {
+    /// Run the self-test periodically
+    pub enabled: bool,
+    /// Neighbors asked per test; at least two are needed to spot a symmetric NAT
+    pub peers: usize,
+    /// Time to wait for answers
+    pub timeout_ms: u64,
+    /// Time between tests
+    pub interval_ms: u64,
+}
+
+impl Default for NatConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            peers: 3,
+            timeout_ms: 1_000,
+            interval_ms: 600_000,
+        }
+    }
+}
+
+impl NatConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.peers == 0 || self.timeout_ms == 0 {
+            return Err("NAT self-test needs at least one peer and a positive timeout".to_string());
+        }
+        if self.interval_ms <= self.timeout_ms {
+            return Err("NAT self-test interval must exceed its timeout".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// How peers can reach this node
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NatType {
+    /// Not tested, or no peer answered
+    #[default]
+    Unknown,
+    Open,
+    FullCone,
+    Symmetric,
+    TcpOnly,
+    UdpBlocked,
+}
+
+impl NatType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NatType::Unknown => "unknown",
+            NatType::Open => "open",
+            NatType::FullCone => "full_cone",
+            NatType::Symmetric => "symmetric",
+            NatType::TcpOnly => "tcp_only",
+            NatType::UdpBlocked => "udp_blocked",
+        }
+    }
+
+    /// Whether a peer can send us a datagram we did not ask for
+    ///
+    /// Untested nodes are assumed reachable, as every node was before.
+    pub fn accepts_unsolicited_udp(&self) -> bool {
+        matches!(self, NatType::Unknown | NatType::Open | NatType::FullCone)
+    }
+
+    /// Whether peers can open a path to us, as an onion relay needs
+    pub fn suits_relay(&self) -> bool {
+        !matches!(self, NatType::Symmetric)
+    }
+
+    /// Transports to use per QoS class from behind this NAT
+    pub fn transport_policy(&self, policy: &TransportPolicy) -> TransportPolicy {
+        if *self != NatType::UdpBlocked {
+            return policy.clone();
+        }
+        let tcp = vec![Transport::Tcp];
+        TransportPolicy { control: tcp.clone(), interactive: tcp.clone(), standard: tcp.clone(), bulk: tcp }
+    }
+}
+
+/// Self-test messages between a prober, the peers it asks and their helpers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum NatProbeMessage {
+    /// Report the address this came from, over `transport`; TCP answers go
+    /// to `tcp_addr`, and UDP ones also to a helper outside `avoid`
+    Request { nonce: u64, transport: Transport, tcp_addr: SocketAddr, avoid: Vec<NodeId> },
+    Reply { nonce: u64, transport: Transport, observed: SocketAddr },
+    /// Send `prober` an unsolicited datagram at `target`
+    Bounce { nonce: u64, prober: NodeId, target: SocketAddr },
+    Unsolicited { nonce: u64 },
+}
+
+/// What one peer reported
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProbeObservation {
+    pub peer: NodeId,
+    /// Our address as the peer saw it over UDP, if it answered
+    pub udp_mapped: Option<SocketAddr>,
+    /// The peer's helper reached us unsolicited
+    pub unsolicited_udp: bool,
+    /// The peer answered over a TCP connection of its own
+    pub tcp: bool,
+}
+
+/// Outcome of a self-test
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NatReport {
+    pub nat_type: NatType,
+    pub tested_at_ms: u64,
+    pub observations: Vec<ProbeObservation>,
+}
+
+/// Classify the answers to a self-test of a node whose UDP socket is `local_udp`
+///
+/// A socket bound to all interfaces counts as untranslated when the port
+/// is kept, since the node does not know which of its addresses peers see.
+pub fn classify(local_udp: SocketAddr, observations: &[ProbeObservation]) -> NatType {
+    let mapped: Vec<SocketAddr> = observations.iter().filter_map(|o| o.udp_mapped).collect();
+    if mapped.is_empty() {
+        return if observations.iter().any(|o| o.tcp) { NatType::UdpBlocked } else { NatType::Unknown };
+    }
+    if mapped.iter().any(|m| *m != mapped[0]) {
+        return NatType::Symmetric;
+    }
+    let translated = mapped[0].port() != local_udp.port()
+        || !(local_udp.ip().is_unspecified() || mapped[0].ip() == local_udp.ip());
+    match (observations.iter().any(|o| o.unsolicited_udp), translated) {
+        (true, false) => NatType::Open,
+        (true, true) => NatType::FullCone,
+        (false, _) if observations.iter().any(|o| o.tcp) => NatType::TcpOnly,
+        // Only reachable where we sent first, like a symmetric NAT
+        (false, _) => NatType::Symmetric,
+    }
+}
+
+/// Relay candidates peers can reach, or all of them if fewer than `hops` are
+pub fn prefer_reachable<T>(candidates: Vec<(T, NatType)>, hops: usize) -> Vec<T> {
+    let reachable = candidates.iter().filter(|(_, nat)| nat.suits_relay()).count();
+    candidates
+        .into_iter()
+        .filter(|(_, nat)| reachable < hops || nat.suits_relay())
+        .map(|(candidate, _)| candidate)
+        .collect()
+}
+
+/// Self-test schedule and the answers of the running test
+#[derive(Debug, Default)]
+pub struct NatProber {
+    config: NatConfig,
+    pending: BTreeMap<u64, ProbeObservation>,
+    running: bool,
+    last_started_ms: Option<u64>,
+    report: Option<NatReport>,
+}
+
+impl NatProber {
+    pub fn set_config(&mut self, config: NatConfig) {
+        self.config = config;
+    }
+
+    pub fn config(&self) -> &NatConfig {
+        &self.config
+    }
+
+    /// Whether a periodic test is due
+    pub fn is_due(&self, now_ms: u64) -> bool {
+        self.config.enabled
+            && !self.running
+            && self.last_started_ms.is_none_or(|last| now_ms.saturating_sub(last) >= self.config.interval_ms)
+    }
+
+    /// Start a test asking `peers`, replacing any running one
+    ///
+    /// # Returns
+    /// A fresh nonce for each peer
+    pub fn begin(&mut self, peers: &[NodeId], now_ms: u64) -> Vec<(NodeId, u64)> {
+        self.running = true;
+        self.last_started_ms = Some(now_ms);
+        self.pending = peers
+            .iter()
+            .map(|peer| {
+                let observation = ProbeObservation { peer: peer.clone(), udp_mapped: None, unsolicited_udp: false, tcp: false };
+                (rand::random::<u64>(), observation)
+            })
+            .collect();
+        self.pending.iter().map(|(nonce, o)| (o.peer.clone(), *nonce)).collect()
+    }
+
+    /// Record a peer's answer; unknown nonces are ignored
+    pub fn on_reply(&mut self, nonce: u64, transport: Transport, observed: SocketAddr) {
+        if let Some(observation) = self.pending.get_mut(&nonce) {
+            match transport {
+                Transport::Udp => observation.udp_mapped = Some(observed),
+                Transport::Tcp => observation.tcp = true,
+            }
+        }
+    }
+
+    pub fn on_unsolicited(&mut self, nonce: u64) {
+        if let Some(observation) = self.pending.get_mut(&nonce) {
+            observation.unsolicited_udp = true;
+        }
+    }
+
+    /// Whether every peer answered everything it could
+    pub fn is_complete(&self) -> bool {
+        self.pending.values().all(|o| o.udp_mapped.is_some() && o.unsolicited_udp && o.tcp)
+    }
+
+    /// Classify the running test and keep its report
+    pub fn finish(&mut self, local_udp: SocketAddr, now_ms: u64) -> NatReport {
+        let observations: Vec<ProbeObservation> = std::mem::take(&mut self.pending).into_values().collect();
+        let report = NatReport { nat_type: classify(local_udp, &observations), tested_at_ms: now_ms, observations };
+        self.running = false;
+        self.report = Some(report.clone());
+        report
+    }
+
+    /// Outcome of the last finished test
+    pub fn report(&self) -> Option<&NatReport> {
+        self.report.as_ref()
+    }
+
+    pub fn nat_type(&self) -> NatType {
+        self.report.as_ref().map_or(NatType::Unknown, |r| r.nat_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seen(peer: &str, udp_mapped: Option<&str>, unsolicited_udp: bool, tcp: bool) -> ProbeObservation {
+        ProbeObservation { peer: NodeId::new(peer), udp_mapped: udp_mapped.map(|a| a.parse().unwrap()), unsolicited_udp, tcp }
+    }
+
+    #[test]
+    fn test_classification() {
+        let local: SocketAddr = "10.0.0.2:7000".parse().unwrap();
+        let open = [seen("a", Some("10.0.0.2:7000"), true, true), seen("b", Some("10.0.0.2:7000"), false, true)];
+        assert_eq!(classify(local, &open), NatType::Open);
+        let cone = [seen("a", Some("1.2.3.4:4000"), true, true), seen("b", Some("1.2.3.4:4000"), true, true)];
+        assert_eq!(classify(local, &cone), NatType::FullCone);
+        let symmetric = [seen("a", Some("1.2.3.4:4000"), true, true), seen("b", Some("1.2.3.4:4001"), true, true)];
+        assert_eq!(classify(local, &symmetric), NatType::Symmetric);
+        let filtered = [seen("a", Some("1.2.3.4:4000"), false, true)];
+        assert_eq!(classify(local, &filtered), NatType::TcpOnly);
+        assert_eq!(classify(local, &[seen("a", None, false, true)]), NatType::UdpBlocked);
+        assert_eq!(classify(local, &[seen("a", None, false, false)]), NatType::Unknown);
+        // A wildcard socket is open if the port survives
+        assert_eq!(classify("0.0.0.0:4000".parse().unwrap(), &cone), NatType::Open);
+    }
+
+    #[test]
+    fn test_prober_and_adjustments() {
+        let mut prober = NatProber::default();
+        prober.set_config(NatConfig { enabled: true, ..Default::default() });
+        assert!(prober.is_due(0));
+        let nonces = prober.begin(&[NodeId::new("a"), NodeId::new("b")], 0);
+        assert!(!prober.is_due(0));
+        let mapped: SocketAddr = "1.2.3.4:4000".parse().unwrap();
+        for (_, nonce) in &nonces {
+            prober.on_reply(*nonce, Transport::Udp, mapped);
+            prober.on_reply(*nonce, Transport::Tcp, mapped);
+        }
+        prober.on_unsolicited(nonces[0].1);
+        prober.on_unsolicited(12345);
+        assert!(!prober.is_complete());
+        let report = prober.finish("10.0.0.2:7000".parse().unwrap(), 50);
+        assert_eq!((report.nat_type, prober.nat_type()), (NatType::FullCone, NatType::FullCone));
+        assert!(!prober.is_due(599_999) && prober.is_due(600_000));
+
+        // UDP blocked sends everything over TCP; symmetric nodes are skipped as relays
+        let policy = NatType::UdpBlocked.transport_policy(&TransportPolicy::default());
+        assert_eq!(policy.control, vec![Transport::Tcp]);
+        assert_eq!(NatType::TcpOnly.transport_policy(&TransportPolicy::default()), TransportPolicy::default());
+        let relays = vec![("a", NatType::Symmetric), ("b", NatType::Open), ("c", NatType::Unknown)];
+        assert_eq!(prefer_reachable(relays.clone(), 2), ["b", "c"]);
+        assert_eq!(prefer_reachable(relays, 3), ["a", "b", "c"]);
+        assert!(NatConfig { peers: 0, ..Default::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,241 @@
+//! Adaptive Neighbor Cap
+//!
+//! A fixed `max_neighbors` suits no node in particular: hubs of a
+//! scale-free overlay turn away most of the peers that would route through
+//! them, while a small device holds as many links as a server. With
+//! `enabled`, the cap is instead re-derived every `interval_ms` within the
+//! operator's `min_neighbors..=max_neighbors`, as the lower of:
+//!
+//! - capacity: the bounds interpolated by the node's headroom, the smaller
+//!   of its resource headroom (pressure as `degradation` measures it, up to
+//!   the pressure at which degradation begins) and its bandwidth headroom
+//!   (utilization of the aggregate class caps of the egress shaper, if any);
+//! - demand: the degree asked of the node, its neighbors plus the peers
+//!   left out at the cap since the last evaluation, and at least the median
+//!   degree of its neighbors as their exchanged lists tell.
+//!
+//! A hub thus grows toward its capacity while peers keep being turned away,
+//! and a node keeps pace with a dense neighborhood before any are. The cap
+//! rises by at most `max_step` per evaluation, so a burst of discoveries
+//! does not fill it at once, and falls straight to a lower target. When it
+//! falls below the neighbor count, the neighbor selection policy picks
+//! which neighbors to keep, poor links last, as it does at admission.
+
+use serde::{Deserialize, Serialize};
+
+/// Adaptive neighbor cap settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NeighborCapConfig {
+    /// Derive the cap from capacity and demand; off keeps `max_neighbors`
+    pub enabled: bool,
+    /// Lowest cap, kept under any pressure
+    pub min_neighbors: usize,
+    /// Highest cap, reached with full headroom
+    pub max_neighbors: usize,
+    /// Largest rise of the cap per evaluation
+    pub max_step: usize,
+    pub interval_ms: u64,
+}
+
+impl Default for NeighborCapConfig {
+    fn default() -> Self {
+        Self { enabled: false, min_neighbors: 4, max_neighbors: 64, max_step: 4, interval_ms: 5_000 }
+    }
+}
+
+impl NeighborCapConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_neighbors == 0 || self.min_neighbors > self.max_neighbors {
+            return Err("Neighbor cap bounds must satisfy 0 < min_neighbors <= max_neighbors".to_string());
+        }
+        if self.max_step == 0 || self.interval_ms == 0 {
+            return Err("Neighbor cap step and interval must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    /// `cap` within the bounds
+    pub fn clamp(&self, cap: usize) -> usize {
+        cap.clamp(self.min_neighbors, self.max_neighbors)
+    }
+
+    /// Cap the node can afford with `headroom` in [0, 1]
+    pub fn capacity(&self, headroom: f64) -> usize {
+        let span = (self.max_neighbors - self.min_neighbors) as f64;
+        self.min_neighbors + (span * headroom.clamp(0.0, 1.0)).floor() as usize
+    }
+}
+
+/// What the cap is derived from at one evaluation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CapSignals {
+    /// Current neighbor count
+    pub held: usize,
+    /// Peers turned away or evicted at the cap since the last evaluation
+    pub left_out: usize,
+    /// Neighbor counts of the neighbors whose lists we hold
+    pub neighbor_degrees: Vec<usize>,
+    /// Resource pressure, see `degradation`
+    pub pressure: f64,
+    /// Pressure at which degradation begins
+    pub pressure_limit: f64,
+    /// Highest utilization of an aggregate egress cap, if any is set
+    pub bandwidth_utilization: Option<f64>,
+}
+
+impl CapSignals {
+    /// Share of the node's resources and bandwidth still free, in [0, 1]
+    pub fn headroom(&self) -> f64 {
+        let resources = if self.pressure_limit > 0.0 { 1.0 - self.pressure / self.pressure_limit } else { 1.0 };
+        let bandwidth = self.bandwidth_utilization.map_or(1.0, |used| 1.0 - used);
+        resources.min(bandwidth).clamp(0.0, 1.0)
+    }
+
+    /// Degree asked of the node
+    pub fn demand(&self) -> usize {
+        let mut degrees = self.neighbor_degrees.clone();
+        degrees.sort_unstable();
+        let median = degrees.get(degrees.len() / 2).copied().unwrap_or(0);
+        (self.held + self.left_out).max(median)
+    }
+}
+
+/// Current cap and how it was derived
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct NeighborCapStatus {
+    pub cap: usize,
+    pub capacity: usize,
+    pub demand: usize,
+    pub headroom: f64,
+    pub evaluations: u64,
+    /// Times the cap changed
+    pub changes: u64,
+    /// Neighbors dropped because the cap fell below their count
+    pub evicted: u64,
+}
+
+/// Adaptive cap of one node
+#[derive(Debug, Clone, Default)]
+pub struct NeighborCap {
+    config: NeighborCapConfig,
+    status: NeighborCapStatus,
+    last_ms: Option<u64>,
+}
+
+impl NeighborCap {
+    /// Start from `cap`, the configured fixed cap, within the bounds
+    pub fn new(config: NeighborCapConfig, cap: usize) -> Self {
+        let status = NeighborCapStatus { cap: config.clamp(cap), ..NeighborCapStatus::default() };
+        Self { config, status, last_ms: None }
+    }
+
+    pub fn config(&self) -> &NeighborCapConfig {
+        &self.config
+    }
+
+    /// Replace the configuration, keeping the cap within the new bounds
+    pub fn set_config(&mut self, config: NeighborCapConfig) {
+        self.status.cap = config.clamp(self.status.cap);
+        self.config = config;
+    }
+
+    pub fn status(&self) -> NeighborCapStatus {
+        self.status
+    }
+
+    pub fn cap(&self) -> usize {
+        self.status.cap
+    }
+
+    /// Whether an evaluation is due
+    pub fn due(&self, now_ms: u64) -> bool {
+        self.config.enabled && self.last_ms.is_none_or(|last| now_ms.saturating_sub(last) >= self.config.interval_ms)
+    }
+
+    /// Derive the cap from `signals`, returning it if it changed
+    pub fn evaluate(&mut self, signals: &CapSignals, now_ms: u64) -> Option<usize> {
+        self.last_ms = Some(now_ms);
+        let headroom = signals.headroom();
+        let capacity = self.config.capacity(headroom);
+        let demand = signals.demand();
+        let target = self.config.clamp(capacity.min(demand));
+        let current = self.status.cap;
+        let next = if target > current { target.min(current + self.config.max_step) } else { target };
+
+        self.status.capacity = capacity;
+        self.status.demand = demand;
+        self.status.headroom = headroom;
+        self.status.evaluations += 1;
+        if next == current {
+            return None;
+        }
+        self.status.cap = next;
+        self.status.changes += 1;
+        Some(next)
+    }
+
+    /// Count neighbors dropped to fit a lower cap
+    pub fn record_evicted(&mut self, count: usize) {
+        self.status.evicted += count as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> NeighborCapConfig {
+        NeighborCapConfig { enabled: true, min_neighbors: 4, max_neighbors: 40, max_step: 8, interval_ms: 1_000 }
+    }
+
+    fn signals(held: usize, left_out: usize, pressure: f64) -> CapSignals {
+        CapSignals { held, left_out, pressure, pressure_limit: 0.7, ..CapSignals::default() }
+    }
+
+    #[test]
+    fn test_capacity_and_demand() {
+        let config = config();
+        assert!(config.validate().is_ok());
+        assert_eq!((config.capacity(0.0), config.capacity(0.5), config.capacity(1.0)), (4, 22, 40));
+
+        let idle = signals(6, 3, 0.0);
+        assert_eq!((idle.headroom(), idle.demand()), (1.0, 9));
+        // Half way to where degradation begins
+        assert!((signals(6, 0, 0.35).headroom() - 0.5).abs() < 1e-9);
+        assert_eq!(signals(6, 0, 0.9).headroom(), 0.0);
+        let shaped = CapSignals { bandwidth_utilization: Some(0.75), ..idle.clone() };
+        assert_eq!(shaped.headroom(), 0.25);
+        // A dense neighborhood asks for more than we hold
+        let dense = CapSignals { neighbor_degrees: vec![3, 30, 12, 25], ..idle };
+        assert_eq!(dense.demand(), 25);
+
+        assert!(NeighborCapConfig { min_neighbors: 50, ..config.clone() }.validate().is_err());
+        assert!(NeighborCapConfig { max_step: 0, ..config }.validate().is_err());
+    }
+
+    #[test]
+    fn test_cap_grows_in_steps_and_shrinks_at_once() {
+        let mut cap = NeighborCap::new(config(), 10);
+        assert!(cap.due(0));
+        // Peers keep being turned away: a hub grows toward its capacity
+        assert_eq!(cap.evaluate(&signals(10, 30, 0.0), 0), Some(18));
+        assert!(!cap.due(500) && cap.due(1_000));
+        assert_eq!(cap.evaluate(&signals(18, 30, 0.0), 1_000), Some(26));
+        assert_eq!(cap.evaluate(&signals(26, 30, 0.0), 2_000), Some(34));
+        assert_eq!(cap.evaluate(&signals(34, 30, 0.0), 3_000), Some(40));
+        assert_eq!(cap.evaluate(&signals(40, 30, 0.0), 4_000), None);
+
+        // Memory runs short: straight down to what the node can afford
+        assert_eq!(cap.evaluate(&signals(40, 0, 0.56), 5_000), Some(11));
+        assert_eq!(cap.evaluate(&signals(11, 0, 0.8), 6_000), Some(4));
+        let status = cap.status();
+        assert_eq!((status.capacity, status.demand, status.evaluations, status.changes), (4, 11, 7, 6));
+
+        // Narrower bounds apply at once
+        cap.set_config(NeighborCapConfig { min_neighbors: 8, ..config() });
+        assert_eq!(cap.cap(), 8);
+        cap.set_config(NeighborCapConfig { enabled: false, ..config() });
+        assert!(!cap.due(100_000));
+    }
+}
@@ -0,0 +1,386 @@
+//! Neighbor List Exchange with Anti-Entropy Digests
+//!
+//! Nodes share their neighbor lists with their neighbors, which gives each
+//! node a two-hop view of the overlay. Resending whole lists every round
+//! costs control traffic linear in the neighborhood size, so each round a
+//! node sends only a digest: the revision and hash of its own list and of
+//! the copy it holds of the receiver's list. The receiver answers only when
+//! that copy is stale, with the changes since the copy's revision, or with
+//! the full list when its change log no longer reaches back that far.
+//!
+//! The hash covers the sorted (neighbor, coordinate version) pairs. A copy
+//! whose hash does not match after applying a delta is dropped and rebuilt
+//! from a full list on the next round, so periodic digests also repair
+//! copies that diverged through lost packets.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::coordinates::NodeId;
+use crate::network::SerializablePoincareDiskPoint;
+
+/// Neighbor list exchange settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NeighborExchangeConfig {
+    pub enabled: bool,
+    /// Time between digest rounds
+    pub interval_ms: u64,
+    /// Changes kept for building deltas; older peers get the full list
+    pub max_log: usize,
+}
+
+impl Default for NeighborExchangeConfig {
+    fn default() -> Self {
+        Self { enabled: false, interval_ms: 5_000, max_log: 256 }
+    }
+}
+
+impl NeighborExchangeConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_ms == 0 || self.max_log == 0 {
+            return Err("Neighbor exchange interval and log size must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// One neighbor in a shared list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NeighborEntry {
+    pub node: NodeId,
+    pub coord: SerializablePoincareDiskPoint,
+    /// Coordinate version of the neighbor
+    pub version: u64,
+}
+
+/// Revision and hash of a neighbor list
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListDigest {
+    pub revision: u64,
+    pub hash: u64,
+}
+
+/// Changes to the sender's neighbor list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListDelta {
+    /// Revision the changes apply on top of; None replaces the whole list
+    pub base: Option<u64>,
+    /// Digest of the list after the changes
+    pub digest: ListDigest,
+    pub upserts: Vec<NeighborEntry>,
+    pub removed: Vec<NodeId>,
+}
+
+/// Neighbor exchange packet payload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ExchangeMessage {
+    /// The sender's list, and the sender's copy of the receiver's list
+    Digest { own: ListDigest, yours: Option<ListDigest> },
+    Delta(ListDelta),
+}
+
+/// Exchange counters since startup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExchangeStats {
+    pub digests_sent: u64,
+    pub deltas_sent: u64,
+    pub full_lists_sent: u64,
+    /// Entries and removals carried by deltas and full lists
+    pub entries_sent: u64,
+    /// Copies dropped because their hash did not match after a delta
+    pub mismatches: u64,
+}
+
+fn list_hash<'a>(entries: impl Iterator<Item = &'a NeighborEntry>) -> u64 {
+    let mut hasher = Sha256::new();
+    for entry in entries {
+        hasher.update(entry.node.0.as_bytes());
+        hasher.update([0]);
+        hasher.update(entry.version.to_le_bytes());
+    }
+    let digest: [u8; 32] = hasher.finalize().into();
+    u64::from_le_bytes(digest[..8].try_into().unwrap())
+}
+
+/// A neighbor list keyed by node ID, so iteration is in sorted order
+#[derive(Debug, Clone, Default)]
+struct NeighborList {
+    revision: u64,
+    entries: BTreeMap<NodeId, NeighborEntry>,
+}
+
+impl NeighborList {
+    fn digest(&self) -> ListDigest {
+        ListDigest { revision: self.revision, hash: list_hash(self.entries.values()) }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Change {
+    Upsert(NeighborEntry),
+    Remove(NodeId),
+}
+
+/// This node's list with its change log, and copies of neighbors' lists
+#[derive(Debug, Clone, Default)]
+pub struct NeighborExchange {
+    config: NeighborExchangeConfig,
+    local: NeighborList,
+    /// Changes with the revision they produced, oldest first
+    log: VecDeque<(u64, Change)>,
+    /// Oldest revision a delta can be built from
+    log_floor: u64,
+    remote: HashMap<NodeId, NeighborList>,
+    /// Bumped whenever a copy in `remote` changes
+    remote_generation: u64,
+    last_round_ms: Option<u64>,
+    stats: ExchangeStats,
+}
+
+impl NeighborExchange {
+    pub fn new(config: NeighborExchangeConfig) -> Self {
+        Self { config, ..Default::default() }
+    }
+
+    pub fn config(&self) -> &NeighborExchangeConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: NeighborExchangeConfig) {
+        self.config = config;
+        self.trim_log();
+    }
+
+    pub fn stats(&self) -> ExchangeStats {
+        self.stats
+    }
+
+    pub fn local_digest(&self) -> ListDigest {
+        self.local.digest()
+    }
+
+    /// Whether a digest round is due, marking it started if so
+    pub fn start_round(&mut self, now_ms: u64) -> bool {
+        let due = self.config.enabled
+            && self.last_round_ms.is_none_or(|last| now_ms.saturating_sub(last) >= self.config.interval_ms);
+        if due {
+            self.last_round_ms = Some(now_ms);
+        }
+        due
+    }
+
+    /// Replace this node's list, logging what changed
+    ///
+    /// Returns whether anything changed; all changes of one call share a
+    /// revision.
+    pub fn update_local(&mut self, entries: Vec<NeighborEntry>) -> bool {
+        let next: BTreeMap<NodeId, NeighborEntry> = entries.into_iter().map(|e| (e.node.clone(), e)).collect();
+        let mut changes: Vec<Change> = self
+            .local
+            .entries
+            .keys()
+            .filter(|node| !next.contains_key(*node))
+            .map(|node| Change::Remove(node.clone()))
+            .collect();
+        changes.extend(
+            next.values()
+                .filter(|e| self.local.entries.get(&e.node).is_none_or(|old| old.version != e.version))
+                .map(|e| Change::Upsert(e.clone())),
+        );
+        if changes.is_empty() {
+            return false;
+        }
+        self.local.revision += 1;
+        self.local.entries = next;
+        let revision = self.local.revision;
+        self.log.extend(changes.into_iter().map(|change| (revision, change)));
+        self.trim_log();
+        true
+    }
+
+    fn trim_log(&mut self) {
+        while self.log.len() > self.config.max_log {
+            if let Some((revision, _)) = self.log.pop_front() {
+                // Copies older than a trimmed change cannot be caught up by a delta
+                self.log_floor = self.log_floor.max(revision);
+            }
+        }
+    }
+
+    /// Digest to send to `peer` this round
+    pub fn digest_for(&mut self, peer: &NodeId) -> ExchangeMessage {
+        self.stats.digests_sent += 1;
+        ExchangeMessage::Digest { own: self.local.digest(), yours: self.remote.get(peer).map(NeighborList::digest) }
+    }
+
+    /// Handle a message from `peer`, returning the reply to send, if any
+    pub fn on_message(&mut self, peer: &NodeId, message: ExchangeMessage) -> Option<ExchangeMessage> {
+        match message {
+            ExchangeMessage::Digest { yours, .. } => {
+                let own = self.local.digest();
+                if yours == Some(own) {
+                    return None;
+                }
+                let delta = self.delta_since(yours);
+                self.stats.entries_sent += (delta.upserts.len() + delta.removed.len()) as u64;
+                if delta.base.is_some() {
+                    self.stats.deltas_sent += 1;
+                } else {
+                    self.stats.full_lists_sent += 1;
+                }
+                Some(ExchangeMessage::Delta(delta))
+            }
+            ExchangeMessage::Delta(delta) => {
+                self.apply(peer, delta);
+                None
+            }
+        }
+    }
+
+    /// Changes that turn a copy at `base` into the current list
+    fn delta_since(&self, base: Option<ListDigest>) -> ListDelta {
+        let digest = self.local.digest();
+        let revision = base.map(|b| b.revision).filter(|&r| r >= self.log_floor && r < self.local.revision);
+        let Some(revision) = revision else {
+            return ListDelta { base: None, digest, upserts: self.local.entries.values().cloned().collect(), removed: Vec::new() };
+        };
+        // Later changes to a node supersede earlier ones
+        let mut latest: BTreeMap<&NodeId, &Change> = BTreeMap::new();
+        for (_, change) in self.log.iter().filter(|(r, _)| *r > revision) {
+            let node = match change {
+                Change::Upsert(entry) => &entry.node,
+                Change::Remove(node) => node,
+            };
+            latest.insert(node, change);
+        }
+        let mut delta = ListDelta { base: Some(revision), digest, upserts: Vec::new(), removed: Vec::new() };
+        for change in latest.into_values() {
+            match change {
+                Change::Upsert(entry) => delta.upserts.push(entry.clone()),
+                Change::Remove(node) => delta.removed.push(node.clone()),
+            }
+        }
+        delta
+    }
+
+    fn apply(&mut self, peer: &NodeId, delta: ListDelta) {
+        self.remote_generation += 1;
+        let copy = self.remote.entry(peer.clone()).or_default();
+        match delta.base {
+            None => copy.entries.clear(),
+            // Deltas on top of a revision we do not hold are useless; the
+            // next digest round asks again from the copy we have
+            Some(base) if base != copy.revision => return,
+            Some(_) => {}
+        }
+        for node in &delta.removed {
+            copy.entries.remove(node);
+        }
+        for entry in delta.upserts {
+            copy.entries.insert(entry.node.clone(), entry);
+        }
+        copy.revision = delta.digest.revision;
+        if copy.digest() != delta.digest {
+            self.remote.remove(peer);
+            self.stats.mismatches += 1;
+        }
+    }
+
+    /// The copy of `peer`'s neighbor list, in node ID order
+    pub fn list_of(&self, peer: &NodeId) -> Option<Vec<NeighborEntry>> {
+        self.remote.get(peer).map(|list| list.entries.values().cloned().collect())
+    }
+
+    /// Nodes known to be neighbors of this node's neighbors
+    pub fn two_hop_nodes(&self) -> HashSet<NodeId> {
+        self.remote.values().flat_map(|list| list.entries.keys().cloned()).collect()
+    }
+
+    /// Drop copies of lists from nodes that are no longer neighbors
+    pub fn retain_peers(&mut self, mut keep: impl FnMut(&NodeId) -> bool) {
+        let before = self.remote.len();
+        self.remote.retain(|peer, _| keep(peer));
+        if self.remote.len() != before {
+            self.remote_generation += 1;
+        }
+    }
+
+    /// Changes whenever a copy of a neighbor's list may have
+    pub fn remote_generation(&self) -> u64 {
+        self.remote_generation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(node: &str, version: u64) -> NeighborEntry {
+        NeighborEntry { node: NodeId::new(node), coord: SerializablePoincareDiskPoint { x: 0.1, y: 0.0 }, version }
+    }
+
+    /// One digest round from `b` to `a`; returns the reply `a` sent
+    fn round(a: &mut NeighborExchange, b: &mut NeighborExchange) -> Option<ExchangeMessage> {
+        let (id_a, id_b) = (NodeId::new("a"), NodeId::new("b"));
+        let digest = b.digest_for(&id_a);
+        let reply = a.on_message(&id_b, digest);
+        if let Some(reply) = reply.clone() {
+            b.on_message(&id_a, reply);
+        }
+        reply
+    }
+
+    #[test]
+    fn test_digests_trigger_deltas_only_on_change() {
+        let config = NeighborExchangeConfig { enabled: true, ..Default::default() };
+        let (mut a, mut b) = (NeighborExchange::new(config.clone()), NeighborExchange::new(config));
+        a.update_local((0..20).map(|i| entry(&format!("n{}", i), 1)).collect());
+
+        // First contact sends the whole list, later rounds nothing
+        assert!(matches!(round(&mut a, &mut b), Some(ExchangeMessage::Delta(ListDelta { base: None, .. }))));
+        assert_eq!(b.list_of(&NodeId::new("a")).unwrap().len(), 20);
+        assert!(round(&mut a, &mut b).is_none());
+
+        // One neighbor moved and one left: the delta carries just those
+        let mut next: Vec<_> = (1..20).map(|i| entry(&format!("n{}", i), 1)).collect();
+        next[0].version = 2;
+        assert!(a.update_local(next));
+        let Some(ExchangeMessage::Delta(delta)) = round(&mut a, &mut b) else {
+            panic!("expected a delta");
+        };
+        assert_eq!((delta.base, delta.upserts.len(), delta.removed.len()), (Some(1), 1, 1));
+        assert_eq!(b.remote[&NodeId::new("a")].digest(), a.local_digest());
+        assert!(round(&mut a, &mut b).is_none());
+        assert_eq!(b.two_hop_nodes().len(), 19);
+        assert_eq!((a.stats().full_lists_sent, a.stats().deltas_sent, a.stats().entries_sent), (1, 1, 22));
+    }
+
+    #[test]
+    fn test_diverged_copy_is_rebuilt() {
+        let config = NeighborExchangeConfig { enabled: true, max_log: 2, ..Default::default() };
+        let (mut a, mut b) = (NeighborExchange::new(config.clone()), NeighborExchange::new(config));
+        a.update_local(vec![entry("x", 1)]);
+        round(&mut a, &mut b);
+
+        // Changes beyond the log size fall back to a full list
+        for version in 2..6 {
+            a.update_local(vec![entry("x", version), entry(&format!("y{}", version), 1)]);
+        }
+        assert!(matches!(round(&mut a, &mut b), Some(ExchangeMessage::Delta(ListDelta { base: None, .. }))));
+
+        // A copy corrupted by a lost delta is dropped and rebuilt
+        b.remote.get_mut(&NodeId::new("a")).unwrap().entries.insert(NodeId::new("ghost"), entry("ghost", 1));
+        a.update_local(vec![entry("x", 6)]);
+        round(&mut a, &mut b);
+        assert_eq!(b.stats().mismatches, 1);
+        assert!(b.list_of(&NodeId::new("a")).is_none());
+        round(&mut a, &mut b);
+        assert_eq!(b.remote[&NodeId::new("a")].digest(), a.local_digest());
+
+        b.retain_peers(|_| false);
+        assert!(b.two_hop_nodes().is_empty());
+    }
+}
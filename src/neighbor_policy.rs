@@ -0,0 +1,237 @@
+//! Neighbor Selection Policies
+//!
+//! Decides which peers a node keeps once it knows more than `max_neighbors`.
+//! The choice shapes the overlay graph and with it greedy routing success,
+//! so the policy is pluggable: `DiscoveryService` asks its policy to rank the
+//! current neighbors plus a newly discovered peer and keeps the result.
+//!
+//! `NeighborPolicyKind` names the built-in policies for configuration files
+//! and the runtime config API; custom policies implement
+//! `NeighborSelectionPolicy` and are installed with
+//! `DistributedNode::set_neighbor_policy`.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::coordinates::NodeId;
+use crate::network::NeighborInfo;
+use crate::PoincareDiskPoint;
+
+/// Chooses which neighbors to keep when the neighbor set is capped
+pub trait NeighborSelectionPolicy: Send + Sync + std::fmt::Debug {
+    /// Short name for logs and reports
+    fn name(&self) -> &'static str;
+
+    /// IDs of the candidates to keep, at most `max`
+    fn select(&self, local: &PoincareDiskPoint, candidates: &[NeighborInfo], max: usize) -> Vec<NodeId>;
+}
+
+/// Candidates ordered by `key`, ties broken by ID
+fn ranked_by<K: PartialOrd>(candidates: &[NeighborInfo], key: impl Fn(&NeighborInfo) -> K) -> Vec<NodeId> {
+    let mut keyed: Vec<(K, &NodeId)> = candidates.iter().map(|n| (key(n), &n.id)).collect();
+    keyed.sort_by(|a, b| {
+        a.0.partial_cmp(&b.0)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.1 .0.cmp(&b.1 .0))
+    });
+    keyed.into_iter().map(|(_, id)| id.clone()).collect()
+}
+
+/// Keep the closest peers, evicting the hyperbolically farthest
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FarthestEviction;
+
+impl NeighborSelectionPolicy for FarthestEviction {
+    fn name(&self) -> &'static str {
+        "farthest_eviction"
+    }
+
+    fn select(&self, local: &PoincareDiskPoint, candidates: &[NeighborInfo], max: usize) -> Vec<NodeId> {
+        let mut ranked = ranked_by(candidates, |n| local.hyperbolic_distance(&n.coord));
+        ranked.truncate(max);
+        ranked
+    }
+}
+
+/// Keep local Delaunay neighbors first, then the closest others
+///
+/// See `voronoi::select_neighbors`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DelaunayPreserving;
+
+impl NeighborSelectionPolicy for DelaunayPreserving {
+    fn name(&self) -> &'static str {
+        "delaunay"
+    }
+
+    fn select(&self, local: &PoincareDiskPoint, candidates: &[NeighborInfo], max: usize) -> Vec<NodeId> {
+        let sites: Vec<(NodeId, PoincareDiskPoint)> = candidates.iter().map(|n| (n.id.clone(), n.coord)).collect();
+        crate::voronoi::select_neighbors(local, &sites, max)
+    }
+}
+
+/// Keep the `closest` nearest peers plus `random` long-range shortcuts
+///
+/// Shortcuts are picked by a seeded hash of the peer ID rather than a fresh
+/// random draw, so the same peers stay selected across evictions and
+/// experiments are reproducible. Unused capacity is filled by distance.
+#[derive(Debug, Clone, Copy)]
+pub struct SmallWorld {
+    pub closest: usize,
+    pub random: usize,
+    pub seed: u64,
+}
+
+impl SmallWorld {
+    fn shortcut_rank(&self, id: &NodeId) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.seed.to_le_bytes());
+        hasher.update(id.0.as_bytes());
+        hasher.finalize().into()
+    }
+}
+
+impl NeighborSelectionPolicy for SmallWorld {
+    fn name(&self) -> &'static str {
+        "small_world"
+    }
+
+    fn select(&self, local: &PoincareDiskPoint, candidates: &[NeighborInfo], max: usize) -> Vec<NodeId> {
+        let by_distance = ranked_by(candidates, |n| local.hyperbolic_distance(&n.coord));
+        let mut kept: Vec<NodeId> = by_distance.iter().take(self.closest.min(max)).cloned().collect();
+
+        let mut rest: Vec<&NodeId> = by_distance.iter().filter(|id| !kept.contains(id)).collect();
+        rest.sort_by_key(|id| self.shortcut_rank(id));
+        let shortcuts = self.random.min(max - kept.len());
+        kept.extend(rest.into_iter().take(shortcuts).cloned());
+
+        for id in by_distance {
+            if kept.len() >= max {
+                break;
+            }
+            if !kept.contains(&id) {
+                kept.push(id);
+            }
+        }
+        kept
+    }
+}
+
+/// Keep peers with the lowest RTT-inflated distance
+///
+/// Cost is `distance × (1 + rtt / rtt_scale)`: at an RTT of `rtt_scale_ms` a
+/// peer counts as twice as far away. Unmeasured peers (RTT 0) cost their
+/// distance.
+#[derive(Debug, Clone, Copy)]
+pub struct RttWeighted {
+    pub rtt_scale_ms: f64,
+}
+
+impl NeighborSelectionPolicy for RttWeighted {
+    fn name(&self) -> &'static str {
+        "rtt_weighted"
+    }
+
+    fn select(&self, local: &PoincareDiskPoint, candidates: &[NeighborInfo], max: usize) -> Vec<NodeId> {
+        let mut ranked = ranked_by(candidates, |n| {
+            let rtt_ms = n.rtt.as_secs_f64() * 1000.0;
+            local.hyperbolic_distance(&n.coord) * (1.0 + rtt_ms / self.rtt_scale_ms)
+        });
+        ranked.truncate(max);
+        ranked
+    }
+}
+
+/// Built-in policy selection for configuration
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NeighborPolicyKind {
+    FarthestEviction,
+    #[default]
+    Delaunay,
+    SmallWorld { closest: usize, random: usize, seed: u64 },
+    RttWeighted { rtt_scale_ms: f64 },
+}
+
+impl NeighborPolicyKind {
+    /// Instantiate the policy
+    pub fn build(&self) -> Arc<dyn NeighborSelectionPolicy> {
+        match *self {
+            Self::FarthestEviction => Arc::new(FarthestEviction),
+            Self::Delaunay => Arc::new(DelaunayPreserving),
+            Self::SmallWorld { closest, random, seed } => Arc::new(SmallWorld { closest, random, seed }),
+            Self::RttWeighted { rtt_scale_ms } => Arc::new(RttWeighted { rtt_scale_ms }),
+        }
+    }
+
+    /// Check that the parameters are usable
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            Self::SmallWorld { closest, random, .. } if closest + random == 0 => {
+                Err("small_world policy must keep at least one neighbor".to_string())
+            }
+            Self::RttWeighted { rtt_scale_ms } if rtt_scale_ms.is_nan() || rtt_scale_ms <= 0.0 => {
+                Err("rtt_scale_ms must be positive".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn peer(id: &str, x: f64, y: f64, rtt_ms: u64) -> NeighborInfo {
+        let mut info = NeighborInfo::new(
+            NodeId::new(id),
+            PoincareDiskPoint::new(x, y).unwrap(),
+            "127.0.0.1:9000".parse().unwrap(),
+        );
+        info.rtt = Duration::from_millis(rtt_ms);
+        info
+    }
+
+    fn ids(selected: &[NodeId]) -> Vec<&str> {
+        let mut ids: Vec<&str> = selected.iter().map(|id| id.0.as_str()).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_builtin_policies() {
+        let local = PoincareDiskPoint::origin();
+        let peers = vec![
+            peer("near", 0.2, 0.0, 200),
+            peer("behind", 0.3, 0.0, 1),
+            peer("west", -0.5, 0.0, 1),
+            peer("north", 0.0, 0.7, 1),
+        ];
+
+        assert_eq!(ids(&FarthestEviction.select(&local, &peers, 3)), vec!["behind", "near", "west"]);
+        assert_eq!(ids(&DelaunayPreserving.select(&local, &peers, 3)), vec!["near", "north", "west"]);
+        // A slow link makes "near" the most expensive peer
+        let rtt = RttWeighted { rtt_scale_ms: 20.0 };
+        assert_eq!(ids(&rtt.select(&local, &peers, 3)), vec!["behind", "north", "west"]);
+
+        let small_world = SmallWorld { closest: 1, random: 1, seed: 7 };
+        let selected = small_world.select(&local, &peers, 3);
+        assert_eq!(selected.len(), 3);
+        assert_eq!(selected[0], NodeId::new("near"));
+        // Shortcuts are stable for a given seed
+        assert_eq!(small_world.select(&local, &peers, 3), selected);
+    }
+
+    #[test]
+    fn test_policy_kind_config() {
+        let kind: NeighborPolicyKind =
+            serde_json::from_str(r#"{"kind":"small_world","closest":4,"random":2,"seed":1}"#).unwrap();
+        assert_eq!(kind.build().name(), "small_world");
+        assert!(kind.validate().is_ok());
+        assert_eq!(NeighborPolicyKind::default().build().name(), "delaunay");
+        assert!(NeighborPolicyKind::RttWeighted { rtt_scale_ms: 0.0 }.validate().is_err());
+    }
+}
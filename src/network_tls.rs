@@ -1,17 +1,31 @@
 //! TLS-enabled Network Layer for DRFE-R
 //!
 //! This module extends the NetworkLayer with TLS encryption for secure inter-node communication.
+//!
+//! Connections to a known peer resume its previous session when they can;
+//! see [`crate::tls`] for how tickets are issued and rotated.
 
+use crate::coordinates::NodeId;
 use crate::network::{NetworkError, Packet, MAX_PACKET_SIZE};
-use crate::tls::{TlsCertificate, TlsConfig};
+use crate::tls::{peer_server_name, TlsCertificate, TlsConfig, TlsResumptionConfig};
 use rustls::pki_types::ServerName;
+use rustls::HandshakeKind;
+use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
 use tokio_rustls::{TlsAcceptor, TlsConnector};
 
+/// Handshakes by kind, outgoing and incoming together
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumptionStats {
+    pub full_handshakes: u64,
+    pub resumed_handshakes: u64,
+}
+
 /// TLS-enabled network layer for secure communication
 pub struct TlsNetworkLayer {
     /// TCP listener for incoming TLS connections
@@ -24,6 +38,9 @@ pub struct TlsNetworkLayer {
     connection_timeout: Duration,
     /// Local TCP address
     local_tcp_addr: SocketAddr,
+    resumption: TlsResumptionConfig,
+    full_handshakes: AtomicU64,
+    resumed_handshakes: AtomicU64,
 }
 
 impl TlsNetworkLayer {
@@ -36,12 +53,21 @@ impl TlsNetworkLayer {
     /// # Returns
     /// Result containing the TlsNetworkLayer or an error
     pub async fn new(tcp_addr: &str, certificate: TlsCertificate) -> Result<Self, NetworkError> {
+        Self::with_resumption(tcp_addr, certificate, TlsResumptionConfig::default()).await
+    }
+
+    /// Create a TLS-enabled network layer with the given session resumption settings
+    pub async fn with_resumption(
+        tcp_addr: &str,
+        certificate: TlsCertificate,
+        resumption: TlsResumptionConfig,
+    ) -> Result<Self, NetworkError> {
         // Bind TCP listener
         let tcp_listener = TcpListener::bind(tcp_addr).await?;
         let local_tcp_addr = tcp_listener.local_addr()?;
         
         // Create TLS configuration
-        let tls_config = TlsConfig::new(certificate)
+        let tls_config = TlsConfig::with_resumption(certificate, &resumption)
             .map_err(|e| NetworkError::Serialization(e.to_string()))?;
         
         // Create TLS acceptor and connector
@@ -54,8 +80,25 @@ impl TlsNetworkLayer {
             tls_connector,
             connection_timeout: Duration::from_secs(30),
             local_tcp_addr,
+            resumption,
+            full_handshakes: AtomicU64::new(0),
+            resumed_handshakes: AtomicU64::new(0),
         })
     }
+
+    pub fn resumption_stats(&self) -> ResumptionStats {
+        ResumptionStats {
+            full_handshakes: self.full_handshakes.load(Ordering::Relaxed),
+            resumed_handshakes: self.resumed_handshakes.load(Ordering::Relaxed),
+        }
+    }
+
+    fn count_handshake(&self, kind: Option<HandshakeKind>) -> bool {
+        let resumed = kind == Some(HandshakeKind::Resumed);
+        let counter = if resumed { &self.resumed_handshakes } else { &self.full_handshakes };
+        counter.fetch_add(1, Ordering::Relaxed);
+        resumed
+    }
     
     /// Get local TCP address
     pub fn local_tcp_addr(&self) -> SocketAddr {
@@ -71,8 +114,26 @@ impl TlsNetworkLayer {
     /// # Returns
     /// Result indicating success or error
     pub async fn send_tls(&self, packet: &Packet, dest_addr: SocketAddr) -> Result<(), NetworkError> {
-        let bytes = packet.to_msgpack()
-            .map_err(NetworkError::Serialization)?;
+        let server_name = ServerName::try_from("localhost")
+            .map_err(|e| NetworkError::Serialization(format!("Invalid server name: {:?}", e)))?;
+        self.send_with_name(packet, dest_addr, server_name).await
+    }
+
+    /// Send a packet to `peer`, resuming its previous TLS session if possible
+    ///
+    /// Sessions are cached per peer, so reconnecting after a transient drop
+    /// skips the full handshake while the peer's ticket is still valid.
+    pub async fn send_tls_to(&self, packet: &Packet, peer: &NodeId, dest_addr: SocketAddr) -> Result<(), NetworkError> {
+        self.send_with_name(packet, dest_addr, peer_server_name(peer)).await
+    }
+
+    async fn send_with_name(
+        &self,
+        packet: &Packet,
+        dest_addr: SocketAddr,
+        server_name: ServerName<'static>,
+    ) -> Result<(), NetworkError> {
+        let bytes = packet.to_msgpack()?;
         
         // Connect to destination with TLS
         let tcp_stream = tokio::time::timeout(
@@ -83,11 +144,9 @@ impl TlsNetworkLayer {
         .map_err(|_| NetworkError::Timeout)??;
         
         // Perform TLS handshake
-        let server_name = ServerName::try_from("localhost")
-            .map_err(|e| NetworkError::Serialization(format!("Invalid server name: {:?}", e)))?;
-        
         let mut tls_stream = self.tls_connector.connect(server_name, tcp_stream).await
             .map_err(|e| NetworkError::Serialization(format!("TLS handshake failed: {}", e)))?;
+        let resumed = self.count_handshake(tls_stream.get_ref().1.handshake_kind());
         
         // Send length prefix (4 bytes, big-endian)
         let len = bytes.len() as u32;
@@ -96,6 +155,13 @@ impl TlsNetworkLayer {
         tls_stream.write_all(&len_bytes).await?;
         tls_stream.write_all(&bytes).await?;
         tls_stream.flush().await?;
+
+        // TLS 1.3 tickets follow the handshake; read briefly so they are stored
+        if self.resumption.enabled && !resumed {
+            let mut byte = [0u8; 1];
+            let wait = Duration::from_millis(self.resumption.ticket_wait_ms);
+            let _ = tokio::time::timeout(wait, tls_stream.read(&mut byte)).await;
+        }
         
         Ok(())
     }
@@ -126,8 +192,7 @@ impl TlsNetworkLayer {
         stream.read_exact(&mut buffer).await?;
         
         // Deserialize packet
-        let packet = Packet::from_msgpack(&buffer)
-            .map_err(NetworkError::InvalidPacket)?;
+        let packet = Packet::from_msgpack(&buffer)?;
         
         Ok(packet)
     }
@@ -142,6 +207,7 @@ impl TlsNetworkLayer {
         // Perform TLS handshake
         let tls_stream = self.tls_acceptor.accept(stream).await
             .map_err(|e| NetworkError::Serialization(format!("TLS accept failed: {}", e)))?;
+        self.count_handshake(tls_stream.get_ref().1.handshake_kind());
         
         Ok((tls_stream, addr))
     }
@@ -327,6 +393,54 @@ mod tests {
         assert_eq!(received.payload, large_payload);
     }
 
+    #[tokio::test]
+    async fn test_reconnect_resumes_session_per_peer() {
+        let layer1 = TlsNetworkLayer::new("127.0.0.1:0", TlsCertificate::generate_self_signed("node1").unwrap()).await.unwrap();
+        let layer2 = Arc::new(TlsNetworkLayer::new("127.0.0.1:0", TlsCertificate::generate_self_signed("node2").unwrap()).await.unwrap());
+        let addr = layer2.local_tcp_addr();
+        let server = Arc::clone(&layer2);
+        let accept = tokio::spawn(async move {
+            let mut payloads = Vec::new();
+            for _ in 0..3 {
+                let (mut stream, _) = server.accept_tls().await.unwrap();
+                payloads.push(TlsNetworkLayer::recv_tls(&mut stream).await.unwrap().payload);
+            }
+            payloads
+        });
+
+        let packet = |payload: &[u8]| {
+            Packet::new_data(NodeId::new("node1"), NodeId::new("node2"), PoincareDiskPoint::origin(), payload.to_vec(), 64)
+        };
+        // The first connection to node2 is a full handshake, a reconnect resumes it
+        layer1.send_tls_to(&packet(b"first"), &NodeId::new("node2"), addr).await.unwrap();
+        layer1.send_tls_to(&packet(b"again"), &NodeId::new("node2"), addr).await.unwrap();
+        // Sessions are kept per peer, so another NodeId starts over
+        layer1.send_tls_to(&packet(b"other"), &NodeId::new("node3"), addr).await.unwrap();
+
+        assert_eq!(accept.await.unwrap(), [b"first".to_vec(), b"again".to_vec(), b"other".to_vec()]);
+        assert_eq!(layer1.resumption_stats(), ResumptionStats { full_handshakes: 2, resumed_handshakes: 1 });
+        assert_eq!(layer2.resumption_stats(), ResumptionStats { full_handshakes: 2, resumed_handshakes: 1 });
+
+        // Without resumption every connection is a full handshake
+        let disabled = TlsResumptionConfig { enabled: false, ..Default::default() };
+        let layer3 = TlsNetworkLayer::with_resumption("127.0.0.1:0", TlsCertificate::generate_self_signed("node3").unwrap(), disabled)
+            .await
+            .unwrap();
+        let addr = layer3.local_tcp_addr();
+        let accept = tokio::spawn(async move {
+            for _ in 0..2 {
+                let (mut stream, _) = layer3.accept_tls().await.unwrap();
+                TlsNetworkLayer::recv_tls(&mut stream).await.unwrap();
+            }
+            layer3.resumption_stats()
+        });
+        for _ in 0..2 {
+            layer1.send_tls_to(&packet(b"plain"), &NodeId::new("node3"), addr).await.unwrap();
+        }
+        assert_eq!(accept.await.unwrap().resumed_handshakes, 0);
+        assert_eq!(layer1.resumption_stats().resumed_handshakes, 1);
+    }
+
     #[tokio::test]
     async fn test_tls_handshake_with_different_certs() {
         // Test that nodes with different certificates can communicate
@@ -0,0 +1,199 @@
+//! Onion Routing
+//!
+//! End-to-end encryption hides payloads from forwarders, but not who talks
+//! to whom: every forwarder sees the source and destination in the header.
+//! In onion mode the source instead picks `hops` relays and wraps the
+//! payload in one layer per relay. Each layer is sealed anonymously for its
+//! relay and names only the next hop, so the packet on each leg is
+//! addressed from one relay to the next, and a relay learns its predecessor
+//! and successor but neither the source nor the destination (unless it is
+//! next to them). The innermost layer carries the payload sealed end to end
+//! by the source, which the destination authenticates as usual.
+//!
+//! Relays are chosen for coordinate diversity: the first at random among
+//! candidates at least `min_separation` from both ends, each further one as
+//! far as possible from the ends and the relays already chosen, so a
+//! route does not run through a single region of the disk. Candidates are
+//! neighbors that advertised relay capability in discovery and whose
+//! identity key is known.
+
+use ed25519_dalek::VerifyingKey;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::e2e_encryption::{seal_anonymous, E2eSessions, EncryptionError};
+use crate::PoincareDiskPoint;
+
+/// Onion routing settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OnionConfig {
+    /// Advertise relay capability and forward onion layers for others
+    pub relay: bool,
+    /// Relays on routes this node builds
+    pub hops: usize,
+    /// Least hyperbolic distance between relays and the route's ends
+    pub min_separation: f64,
+}
+
+impl Default for OnionConfig {
+    fn default() -> Self {
+        Self {
+            relay: false,
+            hops: 2,
+            min_separation: 0.5,
+        }
+    }
+}
+
+impl OnionConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(2..=3).contains(&self.hops) {
+            return Err("Onion routes need 2 or 3 relays".to_string());
+        }
+        if self.min_separation.is_nan() || self.min_separation < 0.0 {
+            return Err("Onion min_separation must be non-negative".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// What a node finds after opening its layer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum OnionLayer {
+    /// Forward `inner` to `next`
+    Relay { next: NodeId, inner: Vec<u8> },
+    /// Payload for us, sealed end to end by `source`
+    Deliver { source: NodeId, sealed: Vec<u8> },
+}
+
+/// Onion traffic through a node
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OnionStats {
+    pub sent: u64,
+    pub relayed: u64,
+    pub delivered: u64,
+    pub rejected: u64,
+}
+
+/// Pick up to `config.hops` diverse relays between `source` and `destination`
+pub fn select_relays<R: Rng>(
+    source: &PoincareDiskPoint,
+    destination: &PoincareDiskPoint,
+    candidates: &[(NodeId, PoincareDiskPoint)],
+    config: &OnionConfig,
+    rng: &mut R,
+) -> Vec<NodeId> {
+    let mut anchors = vec![*source, *destination];
+    let spread = |p: &PoincareDiskPoint, anchors: &[PoincareDiskPoint]| {
+        anchors.iter().map(|a| a.hyperbolic_distance(p)).fold(f64::INFINITY, f64::min)
+    };
+    let mut remaining: Vec<&(NodeId, PoincareDiskPoint)> = candidates
+        .iter()
+        .filter(|(_, p)| spread(p, &anchors) >= config.min_separation)
+        .collect();
+    let mut chosen = Vec::new();
+
+    remaining.shuffle(rng);
+    while chosen.len() < config.hops && !remaining.is_empty() {
+        let index = if chosen.is_empty() {
+            0
+        } else {
+            (0..remaining.len())
+                .max_by(|&a, &b| spread(&remaining[a].1, &anchors).total_cmp(&spread(&remaining[b].1, &anchors)))
+                .unwrap_or(0)
+        };
+        let (id, point) = remaining.swap_remove(index);
+        anchors.push(*point);
+        chosen.push(id.clone());
+    }
+    chosen
+}
+
+/// Wrap a payload sealed end to end for `destination` in one layer per relay
+///
+/// Returns the first relay, where the onion is sent, and the onion.
+pub fn wrap(
+    source: &NodeId,
+    destination: (&NodeId, &VerifyingKey),
+    relays: &[(NodeId, VerifyingKey)],
+    sealed: Vec<u8>,
+) -> Result<(NodeId, Vec<u8>), EncryptionError> {
+    let encode = |layer: &OnionLayer| bincode::serialize(layer).map_err(|_| EncryptionError::Malformed);
+    let (mut next, key) = destination;
+    let deliver = OnionLayer::Deliver { source: source.clone(), sealed };
+    let mut onion = seal_anonymous(next, key, &encode(&deliver)?)?;
+    for (relay, key) in relays.iter().rev() {
+        let layer = OnionLayer::Relay { next: next.clone(), inner: onion };
+        onion = seal_anonymous(relay, key, &encode(&layer)?)?;
+        next = relay;
+    }
+    Ok((next.clone(), onion))
+}
+
+/// Open the layer sealed for us
+pub fn peel(sessions: &mut E2eSessions, onion: &[u8]) -> Result<OnionLayer, EncryptionError> {
+    let layer = sessions.open_anonymous(onion)?;
+    bincode::deserialize(&layer).map_err(|_| EncryptionError::Malformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::e2e_encryption::E2eConfig;
+    use ed25519_dalek::SigningKey;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_relays_are_spread_out() {
+        let point = |x: f64, y: f64| PoincareDiskPoint::new(x, y).unwrap();
+        let candidates: Vec<(NodeId, PoincareDiskPoint)> = [
+            ("near_source", 0.55, 0.05),
+            ("north", 0.0, 0.7),
+            ("north2", 0.05, 0.68),
+            ("south", 0.0, -0.7),
+        ]
+        .iter()
+        .map(|(id, x, y)| (NodeId::new(*id), point(*x, *y)))
+        .collect();
+        let (source, destination) = (point(0.6, 0.0), point(-0.6, 0.0));
+        let config = OnionConfig::default();
+
+        for seed in 0..20 {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let relays = select_relays(&source, &destination, &candidates, &config, &mut rng);
+            assert_eq!(relays.len(), 2);
+            assert!(!relays.contains(&NodeId::new("near_source")));
+            // Never both relays from the same region
+            let north = relays.iter().filter(|r| r.0.starts_with("north")).count();
+            assert_eq!(north, 1, "{:?}", relays);
+        }
+        assert!(OnionConfig { hops: 1, ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_layers_peel_in_order() {
+        let ids: Vec<NodeId> = ["src", "r1", "r2", "dst"].iter().map(|s| NodeId::new(*s)).collect();
+        let keys: Vec<SigningKey> = (1..=4).map(|i| SigningKey::from_bytes(&[i; 32])).collect();
+        let mut sessions: Vec<E2eSessions> = ids.iter().zip(&keys).map(|(id, k)| E2eSessions::new(id.clone(), k)).collect();
+
+        let config = E2eConfig { enabled: true, ..Default::default() };
+        let sealed = sessions[0].seal(&ids[3], &keys[3].verifying_key(), b"quiet", &config, 0).unwrap();
+        let relays = vec![(ids[1].clone(), keys[1].verifying_key()), (ids[2].clone(), keys[2].verifying_key())];
+        let (first, onion) = wrap(&ids[0], (&ids[3], &keys[3].verifying_key()), &relays, sealed).unwrap();
+        assert_eq!(first, ids[1]);
+        // The second relay cannot open the first relay's layer
+        assert!(peel(&mut sessions[2], &onion).is_err());
+
+        let OnionLayer::Relay { next, inner } = peel(&mut sessions[1], &onion).unwrap() else { panic!("expected a relay layer") };
+        assert_eq!(next, ids[2]);
+        assert!(matches!(peel(&mut sessions[1], &onion), Err(EncryptionError::Replayed(_))));
+        let OnionLayer::Relay { next, inner } = peel(&mut sessions[2], &inner).unwrap() else { panic!("expected a relay layer") };
+        assert_eq!(next, ids[3]);
+        let OnionLayer::Deliver { source, sealed } = peel(&mut sessions[3], &inner).unwrap() else { panic!("expected delivery") };
+        assert_eq!(source, ids[0]);
+        assert_eq!(sessions[3].open(&source, &keys[0].verifying_key(), &sealed).unwrap(), b"quiet");
+    }
+}
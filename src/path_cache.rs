@@ -0,0 +1,215 @@
+//! Validated Path Cache
+//!
+//! The route cache remembers every greedy decision, but only until the
+//! router epoch moves on, and it never spares a packet the recovery modes
+//! when greedy forwarding fails. This cache keeps fewer, better entries:
+//! next hops toward a region of the disk (a geohash cell of the target
+//! coordinate) that an acknowledgement has proven to lead to a destination
+//! in that region. Every node an Ack passes learns that the neighbor it came
+//! from reaches the Ack's source, so hot flows are served from the cache at
+//! each hop of the reverse path.
+//!
+//! An entry is tied to the coordinate versions of this node and of the next
+//! hop when it was validated; either moving invalidates it, as does losing
+//! the neighbor or the entry outliving `ttl_ms`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::geohash::GeoHash;
+use crate::route_cache::LruCache;
+use crate::PoincareDiskPoint;
+
+/// Path cache settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PathCacheConfig {
+    pub enabled: bool,
+    /// Regions whose validated next hop is kept
+    pub capacity: usize,
+    /// Geohash bits naming a region; more bits give smaller regions
+    pub precision: u8,
+    /// Time after validation an entry is trusted without a fresh Ack
+    pub ttl_ms: u64,
+}
+
+impl Default for PathCacheConfig {
+    fn default() -> Self {
+        Self { enabled: false, capacity: 1024, precision: 16, ttl_ms: 30_000 }
+    }
+}
+
+impl PathCacheConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.enabled && (self.capacity == 0 || self.ttl_ms == 0) {
+            return Err("Path cache capacity and TTL must be positive".to_string());
+        }
+        if self.precision == 0 || self.precision > crate::geohash::MAX_PRECISION {
+            return Err(format!("Path cache precision must be in 1..={}", crate::geohash::MAX_PRECISION));
+        }
+        Ok(())
+    }
+}
+
+/// Counters since startup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Acks that added or refreshed an entry
+    pub validations: u64,
+    /// Entries dropped because a coordinate version moved
+    pub epoch_invalidations: u64,
+    /// Entries dropped because their next hop is no longer a neighbor
+    pub neighbor_invalidations: u64,
+    /// Entries dropped because they outlived the TTL
+    pub expirations: u64,
+    pub cached: usize,
+}
+
+#[derive(Debug, Clone)]
+struct ValidatedHop {
+    next_hop: NodeId,
+    /// This node's coordinate version at validation
+    local_version: u64,
+    /// The next hop's coordinate version at validation
+    hop_version: u64,
+    validated_ms: u64,
+}
+
+/// Next hops toward target regions, validated by acknowledgements
+#[derive(Debug, Clone)]
+pub struct PathCache {
+    config: PathCacheConfig,
+    entries: LruCache<GeoHash, ValidatedHop>,
+    stats: PathCacheStats,
+}
+
+impl Default for PathCache {
+    fn default() -> Self {
+        Self::new(PathCacheConfig::default())
+    }
+}
+
+impl PathCache {
+    pub fn new(config: PathCacheConfig) -> Self {
+        Self { entries: LruCache::new(config.capacity), config, stats: PathCacheStats::default() }
+    }
+
+    pub fn config(&self) -> &PathCacheConfig {
+        &self.config
+    }
+
+    /// Apply new settings; disabling the cache or changing the region size empties it
+    pub fn set_config(&mut self, config: PathCacheConfig) {
+        if !config.enabled || config.precision != self.config.precision {
+            self.entries.clear();
+        }
+        self.entries.set_capacity(config.capacity);
+        self.config = config;
+    }
+
+    fn region(&self, target: &PoincareDiskPoint) -> GeoHash {
+        GeoHash::encode(target, self.config.precision)
+    }
+
+    /// Record that `next_hop` delivered an Ack from a node at `target`
+    pub fn validate(&mut self, target: &PoincareDiskPoint, next_hop: NodeId, local_version: u64, hop_version: u64, now_ms: u64) {
+        if !self.config.enabled {
+            return;
+        }
+        self.stats.validations += 1;
+        let hop = ValidatedHop { next_hop, local_version, hop_version, validated_ms: now_ms };
+        self.entries.insert(self.region(target), hop);
+    }
+
+    /// Validated next hop toward `target`
+    ///
+    /// `hop_version` gives the current coordinate version of a neighbor, or
+    /// None if it is no longer a neighbor. Stale entries are dropped.
+    pub fn lookup(
+        &mut self,
+        target: &PoincareDiskPoint,
+        local_version: u64,
+        hop_version: impl Fn(&NodeId) -> Option<u64>,
+        now_ms: u64,
+    ) -> Option<NodeId> {
+        if !self.config.enabled {
+            return None;
+        }
+        let region = self.region(target);
+        let Some(hop) = self.entries.get(&region).cloned() else {
+            self.stats.misses += 1;
+            return None;
+        };
+        let stale = match hop_version(&hop.next_hop) {
+            None => Some(&mut self.stats.neighbor_invalidations),
+            Some(version) if version != hop.hop_version || local_version != hop.local_version => {
+                Some(&mut self.stats.epoch_invalidations)
+            }
+            Some(_) if now_ms.saturating_sub(hop.validated_ms) >= self.config.ttl_ms => Some(&mut self.stats.expirations),
+            Some(_) => None,
+        };
+        if let Some(counter) = stale {
+            *counter += 1;
+            self.stats.misses += 1;
+            self.entries.remove(&region);
+            return None;
+        }
+        self.stats.hits += 1;
+        Some(hop.next_hop)
+    }
+
+    /// Drop entries whose next hop fails `keep`, returning how many
+    pub fn retain_neighbors(&mut self, keep: impl Fn(&NodeId) -> bool) -> usize {
+        let lost: Vec<GeoHash> = self
+            .entries
+            .iter()
+            .filter(|(_, hop)| !keep(&hop.next_hop))
+            .map(|(region, _)| *region)
+            .collect();
+        for region in &lost {
+            self.entries.remove(region);
+        }
+        self.stats.neighbor_invalidations += lost.len() as u64;
+        lost.len()
+    }
+
+    pub fn stats(&self) -> PathCacheStats {
+        PathCacheStats { cached: self.entries.len(), ..self.stats }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validated_hops_invalidate() {
+        let mut cache = PathCache::new(PathCacheConfig { enabled: true, ttl_ms: 1_000, ..Default::default() });
+        let target = PoincareDiskPoint::new(0.6, 0.2).unwrap();
+        let nearby = PoincareDiskPoint::new(0.6001, 0.2).unwrap();
+        let hop = NodeId::new("hop");
+        let version = |v: u64| move |id: &NodeId| (id.0 == "hop").then_some(v);
+
+        assert_eq!(cache.lookup(&target, 1, version(1), 0), None);
+        cache.validate(&target, hop.clone(), 1, 1, 0);
+        // Targets in the same region share the entry
+        assert_eq!(cache.lookup(&nearby, 1, version(1), 10), Some(hop.clone()));
+        assert_eq!(cache.lookup(&PoincareDiskPoint::new(-0.6, 0.0).unwrap(), 1, version(1), 10), None);
+
+        // The next hop moved
+        assert_eq!(cache.lookup(&target, 1, version(2), 20), None);
+        cache.validate(&target, hop.clone(), 1, 2, 20);
+        // This node moved
+        assert_eq!(cache.lookup(&target, 2, version(2), 30), None);
+        cache.validate(&target, hop.clone(), 2, 2, 30);
+        assert_eq!(cache.lookup(&target, 2, version(2), 1_030), None);
+        cache.validate(&target, hop.clone(), 2, 2, 40);
+        assert_eq!(cache.retain_neighbors(|id| id != &hop), 1);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.validations, stats.cached), (1, 4, 0));
+        assert_eq!((stats.epoch_invalidations, stats.expirations, stats.neighbor_invalidations), (2, 1, 1));
+    }
+}
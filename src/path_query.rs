@@ -0,0 +1,135 @@
+//! Control-Plane Path Queries
+//!
+//! Predicts the path a packet to some destination would take from this
+//! node without sending one. A Thorup-Zwick table gives the path directly.
+//! Otherwise the first hop is the router's own decision and the rest is a
+//! greedy walk over the coordinates this node has heard of: each step moves
+//! to the nearest known node that is closer to the target, which is how
+//! greedy forwarding behaves when links are short. If that walk gets stuck
+//! short of the destination, the path is the shortest one over the links
+//! this node knows of: its router's and its neighbors' shared neighbor
+//! lists (see `path_search`). Per-hop latency comes from measured neighbor
+//! RTTs where available. Applications use this for pre-flight checks; it is
+//! an estimate and never touches the data plane.
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::PoincareDiskPoint;
+
+/// One-way latency assumed for a hop when no neighbor RTT has been measured
+pub const DEFAULT_HOP_LATENCY_MS: f64 = 10.0;
+
+/// How a path was computed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PathSource {
+    /// From the local Thorup-Zwick routing table
+    ThorupZwick,
+    /// Router first hop, then greedy steps over known coordinates
+    Greedy,
+    /// Shortest path over known links, where the greedy walk got stuck
+    Search,
+}
+
+/// One hop of a predicted path
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PathHop {
+    pub node: NodeId,
+    /// Coordinate of the node, if known here
+    pub coord: Option<PoincareDiskPoint>,
+    /// Estimated one-way latency of the link into this node
+    pub latency_ms: f64,
+    /// Whether `latency_ms` comes from a measured RTT
+    pub measured: bool,
+}
+
+/// Predicted path from this node to a destination
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PathEstimate {
+    pub destination: NodeId,
+    pub source: PathSource,
+    /// Hops after this node, in order
+    pub hops: Vec<PathHop>,
+    /// Whether the path ends at the destination; if not, it ends at the
+    /// known node closest to the target
+    pub complete: bool,
+    /// Sum of the per-hop latencies
+    pub estimated_latency_ms: f64,
+}
+
+impl PathEstimate {
+    pub fn hop_count(&self) -> usize {
+        self.hops.len()
+    }
+
+    /// Node IDs along the path, excluding this node
+    pub fn nodes(&self) -> Vec<NodeId> {
+        self.hops.iter().map(|hop| hop.node.clone()).collect()
+    }
+}
+
+/// Continue a greedy walk from `start` toward `target` over `known` nodes
+///
+/// Each step goes to the known node nearest the current one among those
+/// strictly closer to the target, so the walk always makes progress and
+/// ends after at most `max_hops` steps. The returned nodes exclude `start`;
+/// the walk stops early on reaching `destination`.
+pub fn greedy_continuation(
+    start: PoincareDiskPoint,
+    target: PoincareDiskPoint,
+    destination: &NodeId,
+    known: &[(NodeId, PoincareDiskPoint)],
+    max_hops: usize,
+) -> Vec<(NodeId, PoincareDiskPoint)> {
+    let mut path: Vec<(NodeId, PoincareDiskPoint)> = Vec::new();
+    let mut current = start;
+    while path.len() < max_hops {
+        let remaining = current.hyperbolic_distance(&target);
+        let next = known
+            .iter()
+            .filter(|(_, coord)| coord.hyperbolic_distance(&target) < remaining)
+            .min_by(|a, b| {
+                current
+                    .hyperbolic_distance(&a.1)
+                    .total_cmp(&current.hyperbolic_distance(&b.1))
+                    .then_with(|| a.0.0.cmp(&b.0.0))
+            });
+        let Some((node, coord)) = next else {
+            break;
+        };
+        path.push((node.clone(), *coord));
+        if node == destination {
+            break;
+        }
+        current = *coord;
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_greedy_continuation_makes_progress() {
+        let point = |x: f64| PoincareDiskPoint::new(x, 0.0).unwrap();
+        let known = vec![
+            (NodeId::new("behind"), point(-0.5)),
+            (NodeId::new("near"), point(0.2)),
+            (NodeId::new("mid"), point(0.5)),
+            (NodeId::new("dest"), point(0.8)),
+            (NodeId::new("past"), point(0.9)),
+        ];
+        let dest = NodeId::new("dest");
+
+        let path = greedy_continuation(point(0.0), point(0.8), &dest, &known, 10);
+        let names: Vec<&str> = path.iter().map(|(n, _)| n.0.as_str()).collect();
+        assert_eq!(names, vec!["near", "mid", "dest"]);
+
+        // Without the destination's coordinate the walk ends at the closest known node
+        let partial: Vec<_> = known.iter().filter(|(n, _)| *n != dest).cloned().collect();
+        let path = greedy_continuation(point(0.0), point(0.8), &dest, &partial, 10);
+        assert_eq!(path.last().unwrap().0, NodeId::new("past"));
+        assert_eq!(greedy_continuation(point(0.0), point(0.8), &dest, &known, 1).len(), 1);
+    }
+}
@@ -0,0 +1,460 @@
+//! Point-to-Point Shortest-Path Search
+//!
+//! Optimal hop counts for stretch measurements, and paths for control-plane
+//! queries, used to come from a BFS from scratch per pair, which on a
+//! small-world graph visits most of the graph before reaching the target.
+//! `PathSearch` answers the same queries over an interned graph with
+//! reusable buffers, three ways:
+//!
+//! - `Bfs`: the reference, stopping once the target is reached.
+//! - `Bidirectional`: BFS from both ends, always growing the smaller
+//!   frontier by a whole level, until the frontiers meet. The shortest of
+//!   the meeting paths seen in that level is a shortest path.
+//! - `AStar`: nodes are expanded by hops so far plus a lower bound on hops
+//!   left, the hyperbolic distance to the target over the longest edge of
+//!   the graph. No hop covers more than that edge, so the bound never
+//!   overestimates and A* returns shortest paths. A `weight` above 1
+//!   inflates the bound: fewer nodes are expanded, and paths may be up to
+//!   `weight` times too long, and one below 1 expands more nodes for
+//!   nothing. Negative and non-finite weights are rejected. Nodes without a
+//!   coordinate get a bound of 0.
+//!
+//! `check` compares a strategy against plain BFS on sample pairs, so the
+//! heuristic can be validated on an embedding before it is trusted.
+//! Answers are cached per pair until the capacity is reached, at which
+//! point the cache starts over; the graph itself is immutable.
+//!
+//! `Bidirectional` is the default. A* scans fewer edges than BFS but pays
+//! for its heap and distance evaluations, and long edges between hubs keep
+//! its bound weak; it pays off on embeddings whose edges are short next to
+//! the distances queried.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::graph::CsrGraph;
+use crate::routing::GPRouter;
+use crate::PoincareDiskPoint;
+
+/// Sentinel for "no node" and "not reached"
+const NONE: u32 = u32::MAX;
+
+/// Cached answers kept before the cache starts over
+pub const DEFAULT_CACHE_CAPACITY: usize = 65_536;
+
+/// How `PathSearch` finds shortest paths
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum SearchStrategy {
+    Bfs,
+    #[default]
+    Bidirectional,
+    /// Exact with a weight of 1; larger weights trade path length for speed
+    AStar { weight: f64 },
+}
+
+impl SearchStrategy {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::AStar { weight } if !(weight.is_finite() && *weight >= 0.0) => {
+                Err(format!("A* weight must be finite and non-negative, got {}", weight))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Query counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchStats {
+    pub queries: u64,
+    pub cache_hits: u64,
+    /// Edges examined, over all searches
+    pub scanned: u64,
+}
+
+/// Outcome of comparing a strategy against plain BFS
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchCheck {
+    pub pairs: usize,
+    /// Pairs with a different hop count or reachability
+    pub mismatches: usize,
+    /// Most hops a path was longer than the shortest one
+    pub max_excess: u32,
+}
+
+/// Distances and parents of the forward and backward searches
+#[derive(Debug, Clone)]
+struct Scratch {
+    dist: [Vec<u32>; 2],
+    parent: [Vec<u32>; 2],
+    touched: Vec<u32>,
+}
+
+impl Scratch {
+    fn new(len: usize) -> Self {
+        Self {
+            dist: [vec![NONE; len], vec![NONE; len]],
+            parent: [vec![NONE; len], vec![NONE; len]],
+            touched: Vec::new(),
+        }
+    }
+
+    fn reach(&mut self, side: usize, v: u32, dist: u32, parent: u32) {
+        if self.dist[0][v as usize] == NONE && self.dist[1][v as usize] == NONE {
+            self.touched.push(v);
+        }
+        self.dist[side][v as usize] = dist;
+        self.parent[side][v as usize] = parent;
+    }
+
+    fn reset(&mut self) {
+        for v in self.touched.drain(..) {
+            for side in 0..2 {
+                self.dist[side][v as usize] = NONE;
+                self.parent[side][v as usize] = NONE;
+            }
+        }
+    }
+
+    /// Path from the root of `side` to `v`, root first
+    fn walk(&self, side: usize, mut v: u32) -> Vec<u32> {
+        let mut path = vec![v];
+        while self.parent[side][v as usize] != NONE {
+            v = self.parent[side][v as usize];
+            path.push(v);
+        }
+        path.reverse();
+        path
+    }
+}
+
+/// Shortest-path queries over a fixed graph
+#[derive(Debug, Clone)]
+pub struct PathSearch {
+    graph: CsrGraph,
+    /// Incoming edges, for the backward search
+    reverse_offsets: Vec<usize>,
+    reverse_targets: Vec<u32>,
+    coords: Vec<Option<PoincareDiskPoint>>,
+    /// Longest hyperbolic edge between nodes with coordinates
+    max_edge: f64,
+    strategy: SearchStrategy,
+    cache: HashMap<(u32, u32), Option<Vec<u32>>>,
+    cache_capacity: usize,
+    scratch: Scratch,
+    stats: SearchStats,
+}
+
+impl PathSearch {
+    /// Index a graph; `coords` only matter to A*
+    pub fn new(adjacency: &HashMap<NodeId, Vec<NodeId>>, coords: &HashMap<NodeId, PoincareDiskPoint>) -> Self {
+        let graph = CsrGraph::from_adjacency(adjacency);
+        let n = graph.len();
+        let coords: Vec<Option<PoincareDiskPoint>> = (0..n as u32).map(|i| coords.get(graph.id(i)).copied()).collect();
+
+        let mut reverse_offsets = vec![0usize; n + 1];
+        let mut max_edge: f64 = 0.0;
+        for u in 0..n as u32 {
+            for &v in graph.neighbors(u) {
+                reverse_offsets[v as usize + 1] += 1;
+                if let (Some(a), Some(b)) = (coords[u as usize], coords[v as usize]) {
+                    max_edge = max_edge.max(a.hyperbolic_distance(&b));
+                }
+            }
+        }
+        for i in 0..n {
+            reverse_offsets[i + 1] += reverse_offsets[i];
+        }
+        let mut fill = reverse_offsets.clone();
+        let mut reverse_targets = vec![0u32; graph.edge_count()];
+        for u in 0..n as u32 {
+            for &v in graph.neighbors(u) {
+                reverse_targets[fill[v as usize]] = u;
+                fill[v as usize] += 1;
+            }
+        }
+
+        Self {
+            graph,
+            reverse_offsets,
+            reverse_targets,
+            coords,
+            max_edge,
+            strategy: SearchStrategy::default(),
+            cache: HashMap::new(),
+            cache_capacity: DEFAULT_CACHE_CAPACITY,
+            scratch: Scratch::new(n),
+            stats: SearchStats::default(),
+        }
+    }
+
+    /// Index a router's topology and coordinates
+    pub fn from_router(router: &GPRouter) -> Self {
+        let adjacency = router.build_adjacency_map();
+        let coords = adjacency
+            .keys()
+            .filter_map(|id| Some((id.clone(), router.get_node(id)?.coord.point)))
+            .collect();
+        Self::new(&adjacency, &coords)
+    }
+
+    /// Search with `strategy` from now on, if it is valid
+    pub fn with_strategy(mut self, strategy: SearchStrategy) -> Result<Self, String> {
+        strategy.validate()?;
+        self.strategy = strategy;
+        self.cache.clear();
+        Ok(self)
+    }
+
+    /// Cache at most `capacity` answers; 0 disables the cache
+    pub fn with_cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache_capacity = capacity;
+        self.cache.clear();
+        self
+    }
+
+    pub fn strategy(&self) -> SearchStrategy {
+        self.strategy
+    }
+
+    pub fn stats(&self) -> SearchStats {
+        self.stats
+    }
+
+    /// Shortest path from `source` to `target`, both included
+    pub fn shortest_path(&mut self, source: &NodeId, target: &NodeId) -> Option<Vec<NodeId>> {
+        let path = self.query(source, target)?;
+        Some(path.iter().map(|&i| self.graph.id(i).clone()).collect())
+    }
+
+    /// Hop count of the shortest path from `source` to `target`
+    pub fn distance(&mut self, source: &NodeId, target: &NodeId) -> Option<u32> {
+        self.query(source, target).map(|path| path.len() as u32 - 1)
+    }
+
+    /// Compare the current strategy against BFS on `pairs`, bypassing the cache
+    pub fn check(&mut self, pairs: &[(NodeId, NodeId)]) -> SearchCheck {
+        let mut check = SearchCheck { pairs: pairs.len(), ..SearchCheck::default() };
+        for (source, target) in pairs {
+            let (Some(s), Some(t)) = (self.graph.index_of(source), self.graph.index_of(target)) else {
+                continue;
+            };
+            let expected = self.search(SearchStrategy::Bfs, s, t).map(|p| p.len());
+            let found = self.search(self.strategy, s, t).map(|p| p.len());
+            match (expected, found) {
+                (Some(e), Some(f)) if f >= e => {
+                    if f > e {
+                        check.mismatches += 1;
+                        check.max_excess = check.max_excess.max((f - e) as u32);
+                    }
+                }
+                (None, None) => {}
+                _ => check.mismatches += 1,
+            }
+        }
+        check
+    }
+
+    fn query(&mut self, source: &NodeId, target: &NodeId) -> Option<Vec<u32>> {
+        let (s, t) = (self.graph.index_of(source)?, self.graph.index_of(target)?);
+        self.stats.queries += 1;
+        if let Some(cached) = self.cache.get(&(s, t)) {
+            self.stats.cache_hits += 1;
+            return cached.clone();
+        }
+        let path = self.search(self.strategy, s, t);
+        if self.cache_capacity > 0 {
+            if self.cache.len() >= self.cache_capacity {
+                self.cache.clear();
+            }
+            self.cache.insert((s, t), path.clone());
+        }
+        path
+    }
+
+    fn search(&mut self, strategy: SearchStrategy, s: u32, t: u32) -> Option<Vec<u32>> {
+        let path = match strategy {
+            SearchStrategy::Bfs => self.bfs(s, t),
+            SearchStrategy::Bidirectional => self.bidirectional(s, t),
+            SearchStrategy::AStar { weight } => self.astar(s, t, weight),
+        };
+        self.scratch.reset();
+        path
+    }
+
+    fn bfs(&mut self, s: u32, t: u32) -> Option<Vec<u32>> {
+        self.scratch.reach(0, s, 0, NONE);
+        let mut queue = std::collections::VecDeque::from([s]);
+        while let Some(u) = queue.pop_front() {
+            if u == t {
+                return Some(self.scratch.walk(0, t));
+            }
+            let next = self.scratch.dist[0][u as usize] + 1;
+            self.stats.scanned += self.graph.neighbors(u).len() as u64;
+            for &v in self.graph.neighbors(u) {
+                if self.scratch.dist[0][v as usize] == NONE {
+                    self.scratch.reach(0, v, next, u);
+                    queue.push_back(v);
+                }
+            }
+        }
+        None
+    }
+
+    fn bidirectional(&mut self, s: u32, t: u32) -> Option<Vec<u32>> {
+        if s == t {
+            return Some(vec![s]);
+        }
+        self.scratch.reach(0, s, 0, NONE);
+        self.scratch.reach(1, t, 0, NONE);
+        let mut frontiers = [vec![s], vec![t]];
+        loop {
+            if frontiers[0].is_empty() || frontiers[1].is_empty() {
+                return None;
+            }
+            let side = usize::from(frontiers[1].len() < frontiers[0].len());
+            let other = 1 - side;
+            // Shortest meeting over an edge (u, v) with v reached from the other end
+            let mut best: Option<(u32, u32, u32)> = None;
+            let mut next = Vec::new();
+            for &u in &frontiers[side] {
+                let du = self.scratch.dist[side][u as usize];
+                let adjacent = match side {
+                    0 => self.graph.neighbors(u),
+                    _ => &self.reverse_targets[self.reverse_offsets[u as usize]..self.reverse_offsets[u as usize + 1]],
+                };
+                self.stats.scanned += adjacent.len() as u64;
+                for &v in adjacent {
+                    let dv = self.scratch.dist[other][v as usize];
+                    if dv != NONE && best.is_none_or(|(len, _, _)| du + 1 + dv < len) {
+                        best = Some((du + 1 + dv, u, v));
+                    }
+                    if self.scratch.dist[side][v as usize] == NONE {
+                        self.scratch.reach(side, v, du + 1, u);
+                        next.push(v);
+                    }
+                }
+            }
+            if let Some((_, u, v)) = best {
+                let mut near = self.scratch.walk(side, u);
+                let mut far = self.scratch.walk(other, v);
+                far.reverse();
+                near.extend(far);
+                if side == 1 {
+                    near.reverse();
+                }
+                return Some(near);
+            }
+            frontiers[side] = next;
+        }
+    }
+
+    /// Lower bound on the hops from `v` to the target at `target`
+    fn hops_left(&self, v: u32, target: Option<PoincareDiskPoint>) -> f64 {
+        let (Some(point), Some(target)) = (self.coords[v as usize], target) else {
+            return 0.0;
+        };
+        let distance = point.hyperbolic_distance(&target);
+        if !(distance.is_finite() && self.max_edge.is_finite() && self.max_edge > 0.0) {
+            return 0.0;
+        }
+        // Shrunk a little so rounding cannot make the bound overestimate
+        distance / self.max_edge * (1.0 - 1e-9)
+    }
+
+    fn astar(&mut self, s: u32, t: u32, weight: f64) -> Option<Vec<u32>> {
+        let target = self.coords[t as usize];
+        // Lowest estimate first, deepest first among equal estimates
+        let key = |g: u32, h: f64| Reverse(((g as f64 + weight * h).to_bits(), Reverse(g)));
+        let mut open = BinaryHeap::new();
+        self.scratch.reach(0, s, 0, NONE);
+        open.push((key(0, self.hops_left(s, target)), s));
+        while let Some((Reverse((_, Reverse(g))), u)) = open.pop() {
+            if u == t {
+                return Some(self.scratch.walk(0, t));
+            }
+            if g > self.scratch.dist[0][u as usize] {
+                continue;
+            }
+            self.stats.scanned += self.graph.neighbors(u).len() as u64;
+            for i in 0..self.graph.neighbors(u).len() {
+                let v = self.graph.neighbors(u)[i];
+                if g + 1 < self.scratch.dist[0][v as usize] {
+                    self.scratch.reach(0, v, g + 1, u);
+                    open.push((key(g + 1, self.hops_left(v, target)), v));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph_generators::{hyperbolic_random_graph, HrgConfig};
+
+    fn ring(n: usize) -> HashMap<NodeId, Vec<NodeId>> {
+        (0..n)
+            .map(|i| (NodeId::new(format!("n{}", i)), vec![NodeId::new(format!("n{}", (i + 1) % n)), NodeId::new(format!("n{}", (i + n - 1) % n))]))
+            .collect()
+    }
+
+    #[test]
+    fn test_strategies_find_shortest_paths() {
+        let graph = hyperbolic_random_graph(&HrgConfig { nodes: 400, seed: 3, ..HrgConfig::default() }).unwrap();
+        let adjacency = graph.adjacency();
+        let coords = graph.true_coordinates();
+        let pairs: Vec<(NodeId, NodeId)> =
+            (0..200).map(|i| (graph.nodes[i].clone(), graph.nodes[(i * 37 + 11) % graph.len()].clone())).collect();
+
+        for strategy in [SearchStrategy::Bidirectional, SearchStrategy::AStar { weight: 1.0 }] {
+            let mut search = PathSearch::new(&adjacency, &coords).with_strategy(strategy).unwrap();
+            assert_eq!(search.check(&pairs), SearchCheck { pairs: 200, ..SearchCheck::default() }, "{:?}", strategy);
+            for (source, target) in &pairs {
+                let Some(path) = search.shortest_path(source, target) else {
+                    continue;
+                };
+                assert_eq!((path.first(), path.last()), (Some(source), Some(target)));
+                assert!(path.windows(2).all(|hop| adjacency[&hop[0]].contains(&hop[1])));
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_astar_weights_rejected() {
+        for weight in [-1.0, f64::NAN, f64::INFINITY] {
+            let search = PathSearch::new(&ring(4), &HashMap::new()).with_strategy(SearchStrategy::AStar { weight });
+            assert!(search.is_err(), "{}", weight);
+        }
+        let search = PathSearch::new(&ring(4), &HashMap::new()).with_strategy(SearchStrategy::AStar { weight: 0.0 }).unwrap();
+        assert_eq!(search.strategy(), SearchStrategy::AStar { weight: 0.0 });
+    }
+
+    #[test]
+    fn test_cache_and_unreachable_targets() {
+        let mut adjacency = ring(10);
+        adjacency.insert(NodeId::new("island"), Vec::new());
+        let mut search = PathSearch::new(&adjacency, &HashMap::new());
+        let (a, b) = (NodeId::new("n0"), NodeId::new("n4"));
+
+        assert_eq!(search.distance(&a, &b), Some(4));
+        assert_eq!(search.distance(&b, &NodeId::new("n7")), Some(3));
+        assert_eq!(search.distance(&a, &a), Some(0));
+        assert_eq!(search.distance(&a, &NodeId::new("island")), None);
+        assert_eq!(search.distance(&a, &NodeId::new("unknown")), None);
+        assert_eq!(search.shortest_path(&a, &b).unwrap().len(), 5);
+        assert_eq!(search.stats().queries, 5);
+        assert_eq!(search.stats().cache_hits, 1);
+
+        // One-way edges are searched backwards along their direction
+        let one_way: HashMap<NodeId, Vec<NodeId>> =
+            [("a", "b"), ("b", "c"), ("c", "a")].iter().map(|(u, v)| (NodeId::new(*u), vec![NodeId::new(*v)])).collect();
+        let mut search = PathSearch::new(&one_way, &HashMap::new()).with_cache_capacity(0);
+        assert_eq!(search.distance(&NodeId::new("b"), &NodeId::new("a")), Some(2));
+        assert_eq!(search.distance(&NodeId::new("a"), &NodeId::new("b")), Some(1));
+    }
+}
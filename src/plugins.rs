@@ -0,0 +1,226 @@
+//! Custom Packet Types
+//!
+//! Applications add their own control messages without forking the crate:
+//! they register a handler for a type code on `DistributedNode`, and
+//! packets of type `PacketType::Custom(code)` addressed to the node are
+//! passed to that handler.
+//!
+//! The forwarding mode is chosen at registration. `Routed` packets travel
+//! like Data, greedily towards the destination's anchor; relays forward
+//! them whether or not they know the code. `SingleHop` packets go straight
+//! to a neighbor and are never forwarded. Handlers run on their own task,
+//! so a slow handler does not stall the receive loop.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::coordinates::NodeId;
+
+/// How packets of a custom type travel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForwardingMode {
+    /// Routed hop by hop to any node, like Data
+    Routed,
+    /// Sent directly to a neighbor only
+    SingleHop,
+}
+
+/// A custom packet delivered to its handler
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomPacket {
+    pub type_code: u16,
+    pub packet_id: String,
+    pub source: NodeId,
+    pub payload: Vec<u8>,
+    pub hops: u32,
+}
+
+/// Boxed future returned by a custom packet handler
+pub type HandlerFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Async handler for one custom type code
+pub type PacketHandler = Arc<dyn Fn(CustomPacket) -> HandlerFuture + Send + Sync>;
+
+/// Custom packet registration errors
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PluginError {
+    #[error("Custom packet type {0} is already registered")]
+    AlreadyRegistered(u16),
+
+    #[error("Custom packet type {0} is not registered")]
+    NotRegistered(u16),
+
+    #[error("Custom packet type {code} is single-hop and {destination} is not a neighbor")]
+    NotANeighbor { code: u16, destination: NodeId },
+}
+
+impl PluginError {
+    /// Stable identifier for programmatic handling
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::AlreadyRegistered(_) => "plugin.already_registered",
+            Self::NotRegistered(_) => "plugin.not_registered",
+            Self::NotANeighbor { .. } => "plugin.not_a_neighbor",
+        }
+    }
+}
+
+/// A registered custom type
+#[derive(Clone)]
+pub struct Registration {
+    pub mode: ForwardingMode,
+    pub handler: PacketHandler,
+}
+
+impl std::fmt::Debug for Registration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Registration").field("mode", &self.mode).finish_non_exhaustive()
+    }
+}
+
+/// Per-type delivery counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomPacketStats {
+    /// Packets passed to the handler
+    pub handled: u64,
+    /// Packets addressed to us with no handler registered
+    pub unhandled: u64,
+}
+
+/// Handlers for custom packet types, keyed by type code
+#[derive(Debug, Default)]
+pub struct PluginRegistry {
+    registrations: HashMap<u16, Registration>,
+    stats: HashMap<u16, CustomPacketStats>,
+}
+
+impl PluginRegistry {
+    /// Register a handler; each code can have one handler at a time
+    pub fn register(&mut self, code: u16, mode: ForwardingMode, handler: PacketHandler) -> Result<(), PluginError> {
+        if self.registrations.contains_key(&code) {
+            return Err(PluginError::AlreadyRegistered(code));
+        }
+        self.registrations.insert(code, Registration { mode, handler });
+        Ok(())
+    }
+
+    /// Remove a handler; later packets of the code are dropped
+    pub fn unregister(&mut self, code: u16) -> Result<(), PluginError> {
+        self.registrations
+            .remove(&code)
+            .map(|_| ())
+            .ok_or(PluginError::NotRegistered(code))
+    }
+
+    pub fn get(&self, code: u16) -> Option<&Registration> {
+        self.registrations.get(&code)
+    }
+
+    /// Forwarding mode of a registered code
+    pub fn mode(&self, code: u16) -> Option<ForwardingMode> {
+        self.registrations.get(&code).map(|r| r.mode)
+    }
+
+    /// Registered codes, ascending
+    pub fn codes(&self) -> Vec<u16> {
+        let mut codes: Vec<u16> = self.registrations.keys().copied().collect();
+        codes.sort_unstable();
+        codes
+    }
+
+    /// Handler for a packet addressed to us, counting the outcome
+    pub fn dispatch(&mut self, code: u16) -> Option<PacketHandler> {
+        let handler = self.registrations.get(&code).map(|r| Arc::clone(&r.handler));
+        let stats = self.stats.entry(code).or_default();
+        match handler {
+            Some(_) => stats.handled += 1,
+            None => stats.unhandled += 1,
+        }
+        handler
+    }
+
+    /// Counters per type code, ascending
+    pub fn stats(&self) -> Vec<(u16, CustomPacketStats)> {
+        let mut stats: Vec<(u16, CustomPacketStats)> = self.stats.iter().map(|(c, s)| (*c, *s)).collect();
+        stats.sort_unstable_by_key(|(code, _)| *code);
+        stats
+    }
+}
+
+/// Wrap an async closure as a `PacketHandler`
+pub fn handler<F, Fut>(f: F) -> PacketHandler
+where
+    F: Fn(CustomPacket) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    Arc::new(move |packet| Box::pin(f(packet)) as HandlerFuture)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noop() -> PacketHandler {
+        handler(|_| async {})
+    }
+
+    #[test]
+    fn test_register_rejects_duplicate_codes() {
+        let mut registry = PluginRegistry::default();
+        registry.register(7, ForwardingMode::Routed, noop()).unwrap();
+        assert_eq!(
+            registry.register(7, ForwardingMode::SingleHop, noop()),
+            Err(PluginError::AlreadyRegistered(7))
+        );
+        assert_eq!(registry.mode(7), Some(ForwardingMode::Routed));
+
+        registry.unregister(7).unwrap();
+        assert_eq!(registry.unregister(7), Err(PluginError::NotRegistered(7)));
+        registry.register(7, ForwardingMode::SingleHop, noop()).unwrap();
+        assert_eq!(registry.mode(7), Some(ForwardingMode::SingleHop));
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_counts_and_runs_handler() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut registry = PluginRegistry::default();
+        registry
+            .register(
+                3,
+                ForwardingMode::Routed,
+                handler(move |packet: CustomPacket| {
+                    let tx = tx.clone();
+                    async move {
+                        let _ = tx.send(packet.payload);
+                    }
+                }),
+            )
+            .unwrap();
+
+        let packet = CustomPacket {
+            type_code: 3,
+            packet_id: "p".to_string(),
+            source: NodeId::new("a"),
+            payload: b"hi".to_vec(),
+            hops: 1,
+        };
+        registry.dispatch(3).unwrap()(packet).await;
+        assert!(registry.dispatch(4).is_none());
+
+        assert_eq!(rx.recv().await, Some(b"hi".to_vec()));
+        assert_eq!(
+            registry.stats(),
+            vec![
+                (3, CustomPacketStats { handled: 1, unhandled: 0 }),
+                (4, CustomPacketStats { handled: 0, unhandled: 1 }),
+            ]
+        );
+        assert_eq!(registry.codes(), vec![3]);
+    }
+}
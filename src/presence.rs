@@ -0,0 +1,592 @@
+//! Presence
+//!
+//! A node announces whether it is online by publishing a presence record to
+//! its rendezvous point, the node closest to its anchor coordinate. Records
+//! are signed with the node's identity key when it has one and carry a TTL:
+//! the publisher refreshes its record while online, and a record not
+//! refreshed in time counts as offline, so a node that crashes goes offline
+//! without having to say so.
+//!
+//! Other nodes subscribe to the presence of specific node IDs at the same
+//! rendezvous point. Publishes and subscriptions travel greedily toward the
+//! anchor one overlay link at a time, like multicast joins, and the node
+//! with no neighbor closer to it keeps them and pushes every change of a
+//! watched node's status to its subscribers, routed like data.
+//! Subscriptions are soft state refreshed by the subscribers, so when churn
+//! moves a rendezvous point, records and subscriptions both reach the new
+//! one within a refresh interval. A draining rendezvous point hands both
+//! to the neighbors closest to each anchor before it leaves, so a planned
+//! departure does not wait for refreshes.
+//!
+//! A rendezvous point pins the key of the first signed record it holds for
+//! a node, and for as long as it holds a record of that node accepts only
+//! records signed by that key.
+
+use std::collections::HashMap;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::coordinates::{AnchorCoordinate, NodeId};
+use crate::PoincareDiskPoint;
+
+/// Domain separation for presence signatures
+const PRESENCE_CONTEXT: &[u8] = b"drfe-r/presence";
+
+/// Whether a node is reachable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresenceStatus {
+    Online,
+    Offline,
+}
+
+/// A node's statement of its status
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresenceRecord {
+    pub node: NodeId,
+    pub status: PresenceStatus,
+    /// HLC reading at publication; a record only replaces older ones
+    pub version: u64,
+    /// How long the record holds without a refresh
+    pub ttl_ms: u64,
+    /// Ed25519 public key and signature, for signed records
+    pub public_key: Option<Vec<u8>>,
+    pub signature: Option<Vec<u8>>,
+}
+
+impl PresenceRecord {
+    pub fn new(node: NodeId, status: PresenceStatus, version: u64, ttl_ms: u64) -> Self {
+        Self { node, status, version, ttl_ms, public_key: None, signature: None }
+    }
+
+    fn signed_bytes(&self) -> Vec<u8> {
+        bincode::serialize(&(PRESENCE_CONTEXT, &self.node, self.status, self.version, self.ttl_ms)).unwrap_or_default()
+    }
+
+    pub fn sign(mut self, key: &SigningKey) -> Self {
+        self.signature = Some(key.sign(&self.signed_bytes()).to_bytes().to_vec());
+        self.public_key = Some(key.verifying_key().to_bytes().to_vec());
+        self
+    }
+
+    pub fn is_signed(&self) -> bool {
+        self.signature.is_some()
+    }
+
+    /// Whether the record is unsigned, or signed by the key it carries
+    pub fn verify(&self) -> bool {
+        let Some(signature) = &self.signature else {
+            return true;
+        };
+        let key = self.public_key.as_deref().and_then(|k| <[u8; 32]>::try_from(k).ok());
+        let Some(key) = key.and_then(|k| VerifyingKey::from_bytes(&k).ok()) else {
+            return false;
+        };
+        let Ok(signature) = Signature::from_slice(signature) else {
+            return false;
+        };
+        key.verify(&self.signed_bytes(), &signature).is_ok()
+    }
+}
+
+/// Presence messages, routed toward the subject's rendezvous point or to a subscriber
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PresenceMessage {
+    /// A record on its way to the publisher's rendezvous point
+    Publish(PresenceRecord),
+    /// `subscriber` asks to be told of changes to `node`'s status
+    Subscribe { node: NodeId, subscriber: NodeId },
+    Unsubscribe { node: NodeId, subscriber: NodeId },
+    /// Status of a watched node, pushed to a subscriber; `expired` when the
+    /// rendezvous point marked it offline because its record lapsed
+    Notify { record: PresenceRecord, expired: bool },
+}
+
+impl PresenceMessage {
+    /// Node whose rendezvous point a publish or subscription travels to
+    pub fn subject(&self) -> &NodeId {
+        match self {
+            PresenceMessage::Publish(record) | PresenceMessage::Notify { record, .. } => &record.node,
+            PresenceMessage::Subscribe { node, .. } | PresenceMessage::Unsubscribe { node, .. } => node,
+        }
+    }
+}
+
+/// Greedy next hop toward `node`'s rendezvous point
+///
+/// Returns None when no neighbor is strictly closer to the node's anchor
+/// than this node, i.e. this node is the rendezvous point.
+pub fn next_hop(node: &NodeId, self_coord: &PoincareDiskPoint, neighbors: &[(NodeId, PoincareDiskPoint)]) -> Option<NodeId> {
+    let own = self_coord.hyperbolic_distance(&AnchorCoordinate::from_id(node).point);
+    closest_to_anchor(node, neighbors).filter(|(d, _)| *d < own).map(|(_, id)| id)
+}
+
+/// Neighbor that takes over as `node`'s rendezvous point when this node
+/// leaves: the one closest to the node's anchor
+pub fn successor(node: &NodeId, neighbors: &[(NodeId, PoincareDiskPoint)]) -> Option<NodeId> {
+    closest_to_anchor(node, neighbors).map(|(_, id)| id)
+}
+
+fn closest_to_anchor(node: &NodeId, neighbors: &[(NodeId, PoincareDiskPoint)]) -> Option<(f64, NodeId)> {
+    let target = AnchorCoordinate::from_id(node).point;
+    neighbors
+        .iter()
+        .map(|(id, coord)| (coord.hyperbolic_distance(&target), id))
+        .min_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1 .0.cmp(&b.1 .0)))
+        .map(|(d, id)| (d, id.clone()))
+}
+
+/// Presence settings of a node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresenceConfig {
+    /// When off, the node neither publishes nor holds records
+    pub enabled: bool,
+    /// TTL of the records this node publishes
+    pub ttl_ms: u64,
+    /// How often records and subscriptions are refreshed
+    pub refresh_ms: u64,
+    /// How long a rendezvous point keeps a subscription without a refresh
+    pub subscription_ttl_ms: u64,
+    /// Most records a rendezvous point holds
+    pub max_records: usize,
+    /// Most subscribers a rendezvous point keeps per watched node
+    pub max_subscribers: usize,
+    /// Refuse unsigned records
+    pub require_signatures: bool,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ttl_ms: 30_000,
+            refresh_ms: 10_000,
+            subscription_ttl_ms: 30_000,
+            max_records: 10_000,
+            max_subscribers: 256,
+            require_signatures: false,
+        }
+    }
+}
+
+impl PresenceConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.refresh_ms == 0 || self.refresh_ms >= self.ttl_ms {
+            return Err("presence refresh_ms must be positive and below ttl_ms".to_string());
+        }
+        if self.subscription_ttl_ms <= self.refresh_ms {
+            return Err("presence subscription_ttl_ms must exceed refresh_ms".to_string());
+        }
+        if self.max_records == 0 || self.max_subscribers == 0 {
+            return Err("presence max_records and max_subscribers must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Records a rendezvous point refuses
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PresenceError {
+    #[error("Unsigned presence record for {0}")]
+    Unsigned(NodeId),
+
+    #[error("Invalid signature on presence record for {0}")]
+    BadSignature(NodeId),
+
+    #[error("Presence record for {0} signed by another key")]
+    KeyMismatch(NodeId),
+
+    #[error("Presence records full")]
+    Full,
+}
+
+impl PresenceError {
+    /// Stable identifier for programmatic handling
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Unsigned(_) => "presence.unsigned",
+            Self::BadSignature(_) => "presence.bad_signature",
+            Self::KeyMismatch(_) => "presence.key_mismatch",
+            Self::Full => "presence.full",
+        }
+    }
+}
+
+/// A change in a watched node's status, as seen by a subscriber
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresenceUpdate {
+    pub node: NodeId,
+    pub status: PresenceStatus,
+    pub version: u64,
+    /// Offline because the node's record lapsed, not because it said so
+    pub expired: bool,
+}
+
+/// Presence counters since startup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresenceStats {
+    /// Records this node published
+    pub published: u64,
+    /// Records accepted as a rendezvous point
+    pub stored: u64,
+    /// Records refused as a rendezvous point
+    pub rejected: u64,
+    /// Records that lapsed without a refresh
+    pub expired: u64,
+    /// Notifications sent to subscribers
+    pub notified: u64,
+}
+
+/// Subscribers to notify and the record to send them
+pub type Notifications = Vec<(NodeId, PresenceMessage)>;
+
+/// A record held at a rendezvous point
+#[derive(Debug, Clone)]
+struct HeldRecord {
+    record: PresenceRecord,
+    expires_ms: u64,
+    expired: bool,
+}
+
+/// Presence state of a node: its own status, the records it holds as a
+/// rendezvous point and the nodes it watches
+#[derive(Debug, Default)]
+pub struct PresenceService {
+    config: PresenceConfig,
+    signing_key: Option<SigningKey>,
+    own: Option<PresenceStatus>,
+    last_refresh_ms: u64,
+    records: HashMap<NodeId, HeldRecord>,
+    subscribers: HashMap<NodeId, HashMap<NodeId, u64>>,
+    watching: HashMap<NodeId, Option<PresenceUpdate>>,
+    stats: PresenceStats,
+}
+
+impl PresenceService {
+    pub fn new(config: PresenceConfig) -> Self {
+        Self { config, ..Default::default() }
+    }
+
+    pub fn config(&self) -> &PresenceConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: PresenceConfig) {
+        self.config = config;
+    }
+
+    /// Sign published records with `key` from now on
+    pub fn set_signing_key(&mut self, key: SigningKey) {
+        self.signing_key = Some(key);
+    }
+
+    pub fn stats(&self) -> PresenceStats {
+        self.stats
+    }
+
+    /// Status this node last published
+    pub fn status(&self) -> Option<PresenceStatus> {
+        self.own
+    }
+
+    /// This node's record for `status` at HLC reading `version`
+    pub fn publish(&mut self, local: &NodeId, status: PresenceStatus, version: u64, now_ms: u64) -> PresenceRecord {
+        self.own = Some(status);
+        self.last_refresh_ms = now_ms;
+        self.stats.published += 1;
+        let record = PresenceRecord::new(local.clone(), status, version, self.config.ttl_ms);
+        match &self.signing_key {
+            Some(key) => record.sign(key),
+            None => record,
+        }
+    }
+
+    /// Start watching `node`; false if already watched
+    pub fn subscribe(&mut self, node: NodeId) -> bool {
+        if self.watching.contains_key(&node) {
+            return false;
+        }
+        self.watching.insert(node, None);
+        true
+    }
+
+    /// Stop watching `node`; false if it was not watched
+    pub fn unsubscribe(&mut self, node: &NodeId) -> bool {
+        self.watching.remove(node).is_some()
+    }
+
+    /// Last status heard for a watched node
+    pub fn status_of(&self, node: &NodeId) -> Option<PresenceUpdate> {
+        self.watching.get(node).cloned().flatten()
+    }
+
+    /// Refreshes due at `now_ms`: the node's record while online, at HLC
+    /// reading `version`, and every subscription
+    pub fn refresh(&mut self, local: &NodeId, version: u64, now_ms: u64) -> Vec<PresenceMessage> {
+        if !self.config.enabled || now_ms < self.last_refresh_ms + self.config.refresh_ms {
+            return Vec::new();
+        }
+        self.last_refresh_ms = now_ms;
+        let mut messages: Vec<PresenceMessage> = self
+            .watching
+            .keys()
+            .map(|node| PresenceMessage::Subscribe { node: node.clone(), subscriber: local.clone() })
+            .collect();
+        if self.own == Some(PresenceStatus::Online) {
+            messages.push(PresenceMessage::Publish(self.publish(local, PresenceStatus::Online, version, now_ms)));
+        }
+        messages
+    }
+
+    fn notify_subscribers(&mut self, record: &PresenceRecord, expired: bool) -> Notifications {
+        let subscribers: Vec<NodeId> = self.subscribers.get(&record.node).map(|s| s.keys().cloned().collect()).unwrap_or_default();
+        self.stats.notified += subscribers.len() as u64;
+        subscribers
+            .into_iter()
+            .map(|subscriber| (subscriber, PresenceMessage::Notify { record: record.clone(), expired }))
+            .collect()
+    }
+
+    /// Accept a record as its node's rendezvous point
+    ///
+    /// `known_key` is the node's identity key, if this node has it; a signed
+    /// record must then be signed by it. Returns the notifications due if
+    /// the node's status changed.
+    pub fn on_publish(&mut self, record: PresenceRecord, known_key: Option<&[u8]>, now_ms: u64) -> Result<Notifications, PresenceError> {
+        let node = record.node.clone();
+        let pinned = self.records.get(&node).and_then(|held| held.record.public_key.clone());
+        let check = if !record.verify() {
+            Err(PresenceError::BadSignature(node.clone()))
+        } else if !record.is_signed() && (self.config.require_signatures || pinned.is_some()) {
+            Err(PresenceError::Unsigned(node.clone()))
+        } else if known_key.or(pinned.as_deref()).is_some_and(|key| record.public_key.as_deref() != Some(key)) {
+            Err(PresenceError::KeyMismatch(node.clone()))
+        } else if !self.records.contains_key(&node) && self.records.len() >= self.config.max_records {
+            Err(PresenceError::Full)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = check {
+            self.stats.rejected += 1;
+            return Err(e);
+        }
+
+        let previous = self.records.get(&node);
+        if previous.is_some_and(|held| held.record.version >= record.version) {
+            return Ok(Vec::new());
+        }
+        let changed = previous.is_none_or(|held| held.expired || held.record.status != record.status);
+        self.stats.stored += 1;
+        let expires_ms = now_ms + record.ttl_ms;
+        self.records.insert(node, HeldRecord { record: record.clone(), expires_ms, expired: false });
+        Ok(if changed { self.notify_subscribers(&record, false) } else { Vec::new() })
+    }
+
+    /// Add a subscription as `node`'s rendezvous point
+    ///
+    /// Returns the notification carrying the node's current status, if a
+    /// record is held, so a new subscriber does not wait for a change.
+    pub fn on_subscribe(&mut self, node: NodeId, subscriber: NodeId, now_ms: u64) -> Option<PresenceMessage> {
+        let subscribers = self.subscribers.entry(node.clone()).or_default();
+        if !subscribers.contains_key(&subscriber) && subscribers.len() >= self.config.max_subscribers {
+            return None;
+        }
+        subscribers.insert(subscriber, now_ms + self.config.subscription_ttl_ms);
+        let held = self.records.get(&node)?;
+        self.stats.notified += 1;
+        Some(PresenceMessage::Notify { record: held.record.clone(), expired: held.expired })
+    }
+
+    pub fn on_unsubscribe(&mut self, node: &NodeId, subscriber: &NodeId) {
+        if let Some(subscribers) = self.subscribers.get_mut(node) {
+            subscribers.remove(subscriber);
+            if subscribers.is_empty() {
+                self.subscribers.remove(node);
+            }
+        }
+    }
+
+    /// Mark lapsed records offline and drop stale subscriptions
+    ///
+    /// A lapsed record is kept, offline, for another TTL so its pinned key
+    /// outlives a brief outage, then dropped. Returns the notifications
+    /// for records that lapsed.
+    pub fn expire(&mut self, now_ms: u64) -> Notifications {
+        let lapsed: Vec<PresenceRecord> = self
+            .records
+            .values_mut()
+            .filter(|held| !held.expired && held.expires_ms <= now_ms)
+            .map(|held| {
+                held.expired = true;
+                held.record.status = PresenceStatus::Offline;
+                held.record.clone()
+            })
+            .collect();
+        self.records.retain(|_, held| !held.expired || held.expires_ms + held.record.ttl_ms > now_ms);
+        self.stats.expired += lapsed.len() as u64;
+        let notifications = lapsed.iter().flat_map(|record| self.notify_subscribers(record, true)).collect();
+
+        for subscribers in self.subscribers.values_mut() {
+            subscribers.retain(|_, expires_ms| *expires_ms > now_ms);
+        }
+        self.subscribers.retain(|_, subscribers| !subscribers.is_empty());
+        notifications
+    }
+
+    /// Give up the records and subscriptions held as a rendezvous point
+    ///
+    /// Returns them as publishes and subscriptions for the successors to
+    /// take over. Lapsed records are dropped: their signature no longer
+    /// covers the offline status they carry.
+    pub fn handoff(&mut self) -> Vec<PresenceMessage> {
+        let records = self.records.drain().filter(|(_, held)| !held.expired).map(|(_, held)| PresenceMessage::Publish(held.record));
+        let subscriptions = self.subscribers.drain().flat_map(|(node, subscribers)| {
+            subscribers.into_keys().map(move |subscriber| PresenceMessage::Subscribe { node: node.clone(), subscriber })
+        });
+        records.chain(subscriptions).collect()
+    }
+
+    /// Take a notification as a subscriber, returning the update if the
+    /// watched node's status changed
+    pub fn on_notify(&mut self, record: PresenceRecord, expired: bool) -> Option<PresenceUpdate> {
+        // A lapsed record's signature covered its online status
+        if !expired && !record.verify() {
+            return None;
+        }
+        let known = self.watching.get_mut(&record.node)?;
+        if known.as_ref().is_some_and(|k| k.version > record.version || (k.version == record.version && k.status == record.status)) {
+            return None;
+        }
+        let update = PresenceUpdate { node: record.node, status: record.status, version: record.version, expired };
+        *known = Some(update.clone());
+        Some(update)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    #[test]
+    fn test_signed_records_and_pinning() {
+        let alice = NodeId::new("alice");
+        let online = PresenceRecord::new(alice.clone(), PresenceStatus::Online, 10, 1_000).sign(&key(1));
+        assert!(online.verify());
+        let mut forged = online.clone();
+        forged.status = PresenceStatus::Offline;
+        assert!(!forged.verify());
+
+        let mut rendezvous = PresenceService::new(PresenceConfig::default());
+        assert_eq!(rendezvous.on_publish(forged, None, 0).unwrap_err().code(), "presence.bad_signature");
+        assert!(rendezvous.on_publish(online, None, 0).unwrap().is_empty());
+        // The first key is pinned against other keys and unsigned records
+        let other = PresenceRecord::new(alice.clone(), PresenceStatus::Offline, 11, 1_000).sign(&key(2));
+        assert!(matches!(rendezvous.on_publish(other, None, 0), Err(PresenceError::KeyMismatch(_))));
+        let unsigned = PresenceRecord::new(alice.clone(), PresenceStatus::Offline, 11, 1_000);
+        assert!(matches!(rendezvous.on_publish(unsigned, None, 0), Err(PresenceError::Unsigned(_))));
+        // As is a directory key over the record's own
+        let bob = PresenceRecord::new(NodeId::new("bob"), PresenceStatus::Online, 1, 1_000).sign(&key(3));
+        let directory = key(4).verifying_key().to_bytes();
+        assert!(matches!(rendezvous.on_publish(bob, Some(&directory), 0), Err(PresenceError::KeyMismatch(_))));
+        assert_eq!(rendezvous.stats().rejected, 4);
+
+        let mut config = PresenceConfig::default();
+        assert!(config.validate().is_ok());
+        config.refresh_ms = config.ttl_ms;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_subscribers_follow_status_and_expiry() {
+        let (alice, carol) = (NodeId::new("alice"), NodeId::new("carol"));
+        let mut rendezvous = PresenceService::new(PresenceConfig::default());
+        let mut publisher = PresenceService::new(PresenceConfig::default());
+        let mut watcher = PresenceService::new(PresenceConfig::default());
+        assert!(watcher.subscribe(alice.clone()));
+        assert!(!watcher.subscribe(alice.clone()));
+
+        // Nothing held yet, so no immediate notification
+        assert_eq!(rendezvous.on_subscribe(alice.clone(), carol.clone(), 0), None);
+        let record = publisher.publish(&alice, PresenceStatus::Online, 100, 0);
+        let notifications = rendezvous.on_publish(record.clone(), None, 0).unwrap();
+        assert_eq!(notifications.len(), 1);
+        let (to, PresenceMessage::Notify { record, expired }) = notifications[0].clone() else {
+            panic!("expected a notification");
+        };
+        assert_eq!(to, carol);
+        let update = watcher.on_notify(record.clone(), expired).unwrap();
+        assert_eq!((update.status, update.expired), (PresenceStatus::Online, false));
+        // The same news twice is not a change
+        assert_eq!(watcher.on_notify(record, false), None);
+
+        // A refresh with the same status notifies nobody
+        let refreshes = publisher.refresh(&alice, 200, 10_000);
+        let PresenceMessage::Publish(refreshed) = refreshes[0].clone() else {
+            panic!("expected a publish");
+        };
+        assert!(rendezvous.on_publish(refreshed, None, 10_000).unwrap().is_empty());
+        assert!(publisher.refresh(&alice, 201, 15_000).is_empty());
+        // A refreshed subscription is answered with the current status
+        let current = rendezvous.on_subscribe(alice.clone(), carol.clone(), 10_000).unwrap();
+        let PresenceMessage::Notify { record, expired } = current else {
+            panic!("expected a notification");
+        };
+        assert_eq!(watcher.on_notify(record, expired).unwrap().version, 200);
+
+        // The publisher goes silent; its record lapses, and then the unrefreshed subscription
+        let lapsed = rendezvous.expire(40_000);
+        assert_eq!(lapsed.len(), 1);
+        let PresenceMessage::Notify { record, expired } = lapsed[0].1.clone() else {
+            panic!("expected a notification");
+        };
+        let update = watcher.on_notify(record, expired).unwrap();
+        assert_eq!((update.status, update.expired), (PresenceStatus::Offline, true));
+        assert_eq!(watcher.status_of(&alice), Some(update));
+        assert!(rendezvous.expire(40_000).is_empty());
+        // The lapsed record is still held, offline, for late subscribers
+        let late = rendezvous.on_subscribe(alice.clone(), carol, 40_000);
+        assert!(matches!(late, Some(PresenceMessage::Notify { expired: true, .. })));
+        assert_eq!(rendezvous.stats(), PresenceStats { published: 0, stored: 2, rejected: 0, expired: 1, notified: 4 });
+    }
+
+    #[test]
+    fn test_handoff_moves_live_records_and_subscriptions() {
+        let (alice, bob, carol) = (NodeId::new("alice"), NodeId::new("bob"), NodeId::new("carol"));
+        let mut rendezvous = PresenceService::new(PresenceConfig::default());
+        rendezvous.on_publish(PresenceRecord::new(alice.clone(), PresenceStatus::Online, 1, 1_000), None, 0).unwrap();
+        rendezvous.on_publish(PresenceRecord::new(bob.clone(), PresenceStatus::Online, 1, 5_000), None, 0).unwrap();
+        rendezvous.on_subscribe(alice.clone(), carol.clone(), 0);
+        // Alice's record lapses before the handoff
+        rendezvous.expire(2_000);
+
+        let mut successor = PresenceService::new(PresenceConfig::default());
+        let messages = rendezvous.handoff();
+        assert_eq!(messages.len(), 2);
+        for message in messages {
+            match message {
+                PresenceMessage::Publish(record) => {
+                    assert_eq!(record.node, bob);
+                    successor.on_publish(record, None, 2_000).unwrap();
+                }
+                PresenceMessage::Subscribe { node, subscriber } => {
+                    assert_eq!((&node, &subscriber), (&alice, &carol));
+                    successor.on_subscribe(node, subscriber, 2_000);
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        assert!(rendezvous.handoff().is_empty());
+
+        // Carol hears of Alice from the successor
+        let record = PresenceRecord::new(alice, PresenceStatus::Online, 2, 1_000);
+        let notifications = successor.on_publish(record, None, 2_000).unwrap();
+        assert_eq!(notifications[0].0, carol);
+    }
+}
@@ -0,0 +1,522 @@
+//! Black-Hole and Gray-Hole Detection by Active Probing
+//!
+//! A node periodically sends traceable probes through each neighbor toward
+//! random destinations. The destination answers with a confirmation signed
+//! by its own key that echoes the probe's nonce, so a neighbor cannot fake
+//! delivery. Neighbors whose probes are lost (black hole: all of them, gray
+//! hole: a share well above normal loss) or arrive only after a long detour
+//! (misrouting) are excluded from next-hop selection for a while, then
+//! probed again.
+
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+
+use ed25519_dalek::SigningKey;
+
+use crate::coordinates::NodeId;
+
+/// Probing settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProbeConfig {
+    /// Probing needs identity keys on the destinations, so it is opt-in
+    pub enabled: bool,
+    /// Time between probing rounds
+    pub interval_ms: u64,
+    /// Probes sent through each neighbor per probing round
+    pub probes_per_neighbor: usize,
+    /// Time to wait for a confirmation before counting a probe as lost
+    pub probe_timeout_ms: u64,
+    /// Probes that must complete before a neighbor is judged
+    pub min_probes: usize,
+    /// Loss rate at or above which a neighbor is a black hole
+    pub black_hole_loss_rate: f64,
+    /// Loss rate at or above which a neighbor is a gray hole
+    pub gray_hole_loss_rate: f64,
+    /// Confirmed probes taking more hops than this count as misrouted
+    pub max_probe_hops: u32,
+    /// Misrouted share at or above which a neighbor is misrouting
+    pub misroute_rate: f64,
+    /// How long a flagged neighbor stays excluded before being re-probed
+    pub exclusion_ms: u64,
+}
+
+impl Default for ProbeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: 10_000,
+            probes_per_neighbor: 1,
+            probe_timeout_ms: 5000,
+            min_probes: 8,
+            black_hole_loss_rate: 0.9,
+            gray_hole_loss_rate: 0.3,
+            max_probe_hops: 64,
+            misroute_rate: 0.3,
+            exclusion_ms: 600_000,
+        }
+    }
+}
+
+impl ProbeConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_ms == 0 || self.probe_timeout_ms == 0 {
+            return Err("probing interval_ms and probe_timeout_ms must be positive".to_string());
+        }
+        let rates = [self.black_hole_loss_rate, self.gray_hole_loss_rate, self.misroute_rate];
+        if rates.iter().any(|r| !(0.0..=1.0).contains(r)) {
+            return Err("probing loss and misroute rates must be in [0, 1]".to_string());
+        }
+        if self.gray_hole_loss_rate > self.black_hole_loss_rate {
+            return Err("probing gray_hole_loss_rate must not exceed black_hole_loss_rate".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Traceable probe sent through a specific neighbor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Probe {
+    pub probe_id: String,
+    pub origin: NodeId,
+    /// Neighbor the probe is handed to first
+    pub via: NodeId,
+    pub destination: NodeId,
+    /// Random value the destination must echo in its confirmation
+    pub nonce: u64,
+    pub sent_at_ms: u64,
+}
+
+impl Probe {
+    /// Build the destination's signed confirmation for this probe
+    pub fn confirm(&self, hops: u32, private_key: &[u8]) -> Result<ProbeConfirmation, String> {
+        let mut confirmation = ProbeConfirmation {
+            probe_id: self.probe_id.clone(),
+            destination: self.destination.clone(),
+            nonce: self.nonce,
+            hops,
+            signature: None,
+        };
+        confirmation.sign(private_key)?;
+        Ok(confirmation)
+    }
+}
+
+/// Destination's signed proof that a probe arrived
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProbeConfirmation {
+    pub probe_id: String,
+    pub destination: NodeId,
+    pub nonce: u64,
+    /// Hops the probe took to arrive
+    pub hops: u32,
+    /// Ed25519 signature by the destination over the fields above
+    pub signature: Option<Vec<u8>>,
+}
+
+impl ProbeConfirmation {
+    /// Sign with the destination's 32-byte Ed25519 private key
+    pub fn sign(&mut self, private_key: &[u8]) -> Result<(), String> {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let key: [u8; 32] = private_key
+            .try_into()
+            .map_err(|_| format!("Invalid private key length: {} (expected 32 bytes)", private_key.len()))?;
+        let message = self.signing_bytes()?;
+        self.signature = Some(SigningKey::from_bytes(&key).sign(&message).to_bytes().to_vec());
+        Ok(())
+    }
+
+    /// Verify against the destination's 32-byte Ed25519 public key
+    pub fn verify_signature(&self, public_key: &[u8]) -> bool {
+        use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+        let (Some(signature), Ok(key)) = (&self.signature, <[u8; 32]>::try_from(public_key)) else {
+            return false;
+        };
+        let (Ok(key), Ok(signature), Ok(message)) = (
+            VerifyingKey::from_bytes(&key),
+            Signature::from_slice(signature),
+            self.signing_bytes(),
+        ) else {
+            return false;
+        };
+        key.verify(&message, &signature).is_ok()
+    }
+
+    fn signing_bytes(&self) -> Result<Vec<u8>, String> {
+        let mut unsigned = self.clone();
+        unsigned.signature = None;
+        rmp_serde::to_vec(&unsigned).map_err(|e| format!("Failed to serialize confirmation for signing: {}", e))
+    }
+}
+
+/// Probe traffic between nodes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ProbeMessage {
+    /// Routed to `probe.destination`, first through `probe.via`
+    Probe(Probe),
+    /// Routed back to the probe's origin
+    Confirmation(ProbeConfirmation),
+}
+
+/// Probe outcomes through one neighbor
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ProbeStats {
+    pub confirmed: usize,
+    pub lost: usize,
+    /// Confirmed, but over `max_probe_hops`
+    pub misrouted: usize,
+}
+
+impl ProbeStats {
+    /// Completed probes (confirmed or lost)
+    pub fn completed(&self) -> usize {
+        self.confirmed + self.lost
+    }
+
+    pub fn loss_rate(&self) -> f64 {
+        if self.completed() == 0 {
+            0.0
+        } else {
+            self.lost as f64 / self.completed() as f64
+        }
+    }
+}
+
+/// Judgement about a neighbor's forwarding behavior
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NeighborVerdict {
+    /// Not enough completed probes yet
+    Unknown,
+    Healthy,
+    /// Drops (nearly) everything
+    BlackHole { loss_rate: f64 },
+    /// Drops a significant share of traffic
+    GrayHole { loss_rate: f64 },
+    /// Delivers, but along implausibly long paths
+    Misrouting { misroute_rate: f64 },
+}
+
+impl NeighborVerdict {
+    /// Whether the neighbor should be excluded from next-hop selection
+    pub fn is_malicious(&self) -> bool {
+        matches!(
+            self,
+            NeighborVerdict::BlackHole { .. } | NeighborVerdict::GrayHole { .. } | NeighborVerdict::Misrouting { .. }
+        )
+    }
+}
+
+/// Sends probes, checks confirmations and keeps the exclusion list
+pub struct ProbeManager {
+    pub config: ProbeConfig,
+    origin: NodeId,
+    /// Signs confirmations for probes addressed to this node
+    signing_key: Option<SigningKey>,
+    last_round_ms: Option<u64>,
+    pending: RwLock<HashMap<String, Probe>>,
+    stats: RwLock<HashMap<NodeId, ProbeStats>>,
+    /// Excluded neighbor -> time the exclusion ends
+    excluded: RwLock<HashMap<NodeId, u64>>,
+}
+
+impl ProbeManager {
+    pub fn new(origin: NodeId, config: ProbeConfig) -> Self {
+        Self {
+            config,
+            origin,
+            signing_key: None,
+            last_round_ms: None,
+            pending: RwLock::new(HashMap::new()),
+            stats: RwLock::new(HashMap::new()),
+            excluded: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_config(&mut self, config: ProbeConfig) {
+        self.config = config;
+    }
+
+    /// Sign confirmations with `key` from now on
+    pub fn set_signing_key(&mut self, key: SigningKey) {
+        self.signing_key = Some(key);
+    }
+
+    /// Whether a probing round is due, marking it started if so
+    pub fn round_due(&mut self, now_ms: u64) -> bool {
+        if self
+            .last_round_ms
+            .is_some_and(|last| now_ms.saturating_sub(last) < self.config.interval_ms)
+        {
+            return false;
+        }
+        self.last_round_ms = Some(now_ms);
+        true
+    }
+
+    /// Confirm a probe that reached this node after `hops` hops
+    pub fn answer(&self, probe: &Probe, hops: u32) -> Result<ProbeConfirmation, String> {
+        if probe.destination != self.origin {
+            return Err(format!("Probe {} is addressed to {}", probe.probe_id, probe.destination));
+        }
+        let key = self.signing_key.as_ref().ok_or("No identity key to confirm probes with")?;
+        probe.confirm(hops, key.as_bytes())
+    }
+
+    /// Create one round of probes through each neighbor toward random destinations
+    ///
+    /// Destinations are drawn from `destinations` excluding this node and the
+    /// neighbor itself. The probes are registered as pending; the caller sends
+    /// each one to its `via` neighbor.
+    pub fn plan_round<R: Rng>(
+        &self,
+        neighbors: &[NodeId],
+        destinations: &[NodeId],
+        now_ms: u64,
+        rng: &mut R,
+    ) -> Vec<Probe> {
+        let mut probes = Vec::new();
+        for via in neighbors {
+            let candidates: Vec<&NodeId> = destinations
+                .iter()
+                .filter(|d| *d != via && **d != self.origin)
+                .collect();
+            for _ in 0..self.config.probes_per_neighbor {
+                let Some(destination) = candidates.choose(rng) else {
+                    break;
+                };
+                probes.push(Probe {
+                    probe_id: uuid::Uuid::new_v4().to_string(),
+                    origin: self.origin.clone(),
+                    via: via.clone(),
+                    destination: (*destination).clone(),
+                    nonce: rng.gen(),
+                    sent_at_ms: now_ms,
+                });
+            }
+        }
+
+        let mut pending = self.pending.write().unwrap();
+        for probe in &probes {
+            pending.insert(probe.probe_id.clone(), probe.clone());
+        }
+        probes
+    }
+
+    /// Accept a confirmation for a pending probe
+    ///
+    /// The confirmation must match the probe's destination and nonce and be
+    /// signed by the destination. Returns the neighbor the probe went through.
+    pub fn confirm(&self, confirmation: &ProbeConfirmation, destination_public_key: &[u8]) -> Result<NodeId, String> {
+        let probe = self
+            .pending
+            .read()
+            .unwrap()
+            .get(&confirmation.probe_id)
+            .cloned()
+            .ok_or_else(|| format!("Unknown or expired probe {}", confirmation.probe_id))?;
+        if confirmation.destination != probe.destination || confirmation.nonce != probe.nonce {
+            return Err(format!("Confirmation does not match probe {}", probe.probe_id));
+        }
+        if !confirmation.verify_signature(destination_public_key) {
+            return Err(format!("Invalid confirmation signature from {}", probe.destination));
+        }
+
+        self.pending.write().unwrap().remove(&probe.probe_id);
+        let mut stats = self.stats.write().unwrap();
+        let entry = stats.entry(probe.via.clone()).or_default();
+        entry.confirmed += 1;
+        if confirmation.hops > self.config.max_probe_hops {
+            entry.misrouted += 1;
+        }
+        Ok(probe.via)
+    }
+
+    /// Count unconfirmed probes older than the timeout as lost and update exclusions
+    ///
+    /// Returns neighbors newly excluded by this call.
+    pub fn expire(&self, now_ms: u64) -> Vec<NodeId> {
+        let timeout = self.config.probe_timeout_ms;
+        let mut lost = Vec::new();
+        self.pending.write().unwrap().retain(|_, probe| {
+            if now_ms.saturating_sub(probe.sent_at_ms) > timeout {
+                lost.push(probe.via.clone());
+                false
+            } else {
+                true
+            }
+        });
+        {
+            let mut stats = self.stats.write().unwrap();
+            for via in lost {
+                stats.entry(via).or_default().lost += 1;
+            }
+        }
+        self.update_exclusions(now_ms)
+    }
+
+    /// Current verdict for a neighbor
+    pub fn verdict(&self, neighbor: &NodeId) -> NeighborVerdict {
+        let stats = self.stats.read().unwrap().get(neighbor).copied().unwrap_or_default();
+        if stats.completed() < self.config.min_probes {
+            return NeighborVerdict::Unknown;
+        }
+        let loss_rate = stats.loss_rate();
+        let misroute_rate = if stats.confirmed > 0 {
+            stats.misrouted as f64 / stats.confirmed as f64
+        } else {
+            0.0
+        };
+        if loss_rate >= self.config.black_hole_loss_rate {
+            NeighborVerdict::BlackHole { loss_rate }
+        } else if loss_rate >= self.config.gray_hole_loss_rate {
+            NeighborVerdict::GrayHole { loss_rate }
+        } else if misroute_rate >= self.config.misroute_rate {
+            NeighborVerdict::Misrouting { misroute_rate }
+        } else {
+            NeighborVerdict::Healthy
+        }
+    }
+
+    /// Probe statistics for a neighbor
+    pub fn stats(&self, neighbor: &NodeId) -> ProbeStats {
+        self.stats.read().unwrap().get(neighbor).copied().unwrap_or_default()
+    }
+
+    /// Neighbors currently excluded, for `GPRouter::set_excluded_nodes`
+    pub fn excluded(&self) -> HashSet<NodeId> {
+        self.excluded.read().unwrap().keys().cloned().collect()
+    }
+
+    /// Whether a neighbor is currently excluded
+    pub fn is_excluded(&self, neighbor: &NodeId) -> bool {
+        self.excluded.read().unwrap().contains_key(neighbor)
+    }
+
+    /// Number of probes awaiting confirmation
+    pub fn pending_count(&self) -> usize {
+        self.pending.read().unwrap().len()
+    }
+
+    fn update_exclusions(&self, now_ms: u64) -> Vec<NodeId> {
+        let mut excluded = self.excluded.write().unwrap();
+
+        // Expired exclusions get a clean slate and are probed again
+        let released: Vec<NodeId> = excluded
+            .iter()
+            .filter(|(_, until)| now_ms >= **until)
+            .map(|(id, _)| id.clone())
+            .collect();
+        {
+            let mut stats = self.stats.write().unwrap();
+            for id in &released {
+                excluded.remove(id);
+                stats.remove(id);
+            }
+        }
+
+        let neighbors: Vec<NodeId> = self.stats.read().unwrap().keys().cloned().collect();
+        let mut newly = Vec::new();
+        for neighbor in neighbors {
+            if !excluded.contains_key(&neighbor) && self.verdict(&neighbor).is_malicious() {
+                excluded.insert(neighbor.clone(), now_ms + self.config.exclusion_ms);
+                newly.push(neighbor);
+            }
+        }
+        newly.sort_by(|a, b| a.0.cmp(&b.0));
+        newly
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinates::RoutingCoordinate;
+    use crate::routing::{GPRouter, RoutingNode};
+    use crate::PoincareDiskPoint;
+    use ed25519_dalek::SigningKey;
+
+    /// Source "s" with two neighbors "a" (black hole) and "b", both linked
+    /// to destinations "d0".."d3"
+    fn network() -> GPRouter {
+        let mut router = GPRouter::new();
+        let nodes = [("s", -0.6, 0.0), ("a", 0.0, 0.1), ("b", 0.0, -0.1)];
+        for (id, x, y) in nodes {
+            let coord = RoutingCoordinate::new(PoincareDiskPoint::new(x, y).unwrap(), 0);
+            router.add_node(RoutingNode::new(NodeId::new(id), coord));
+        }
+        for i in 0..4 {
+            let y = -0.3 + 0.2 * i as f64;
+            let coord = RoutingCoordinate::new(PoincareDiskPoint::new(0.6, y).unwrap(), 0);
+            router.add_node(RoutingNode::new(NodeId::new(format!("d{}", i)), coord));
+            router.add_edge(&NodeId::new("a"), &NodeId::new(format!("d{}", i)));
+            router.add_edge(&NodeId::new("b"), &NodeId::new(format!("d{}", i)));
+        }
+        router.add_edge(&NodeId::new("s"), &NodeId::new("a"));
+        router.add_edge(&NodeId::new("s"), &NodeId::new("b"));
+        router
+    }
+
+    #[test]
+    fn test_black_hole_detected_and_excluded() {
+        let mut router = network();
+        let mut rng = StdRng::seed_from_u64(7);
+        let key = SigningKey::from_bytes(&rng.gen());
+        let manager = ProbeManager::new(NodeId::new("s"), ProbeConfig::default());
+        let neighbors = [NodeId::new("a"), NodeId::new("b")];
+        let destinations: Vec<NodeId> = (0..4).map(|i| NodeId::new(format!("d{}", i))).collect();
+
+        for round in 0..10u64 {
+            let now = round * 10_000;
+            for probe in manager.plan_round(&neighbors, &destinations, now, &mut rng) {
+                // "a" silently drops everything it is handed
+                if probe.via == NodeId::new("a") {
+                    continue;
+                }
+                let coord = router.get_node(&probe.destination).unwrap().coord.point;
+                let result = router.simulate_delivery(&probe.via, &probe.destination, coord, 20);
+                let confirmation = probe.confirm(result.hops + 1, key.as_bytes()).unwrap();
+                assert_eq!(manager.confirm(&confirmation, key.verifying_key().as_bytes()), Ok(NodeId::new("b")));
+            }
+            manager.expire(now + 6_000);
+        }
+
+        assert!(matches!(manager.verdict(&NodeId::new("a")), NeighborVerdict::BlackHole { .. }));
+        assert_eq!(manager.verdict(&NodeId::new("b")), NeighborVerdict::Healthy);
+        assert_eq!(manager.excluded(), HashSet::from([NodeId::new("a")]));
+
+        router.set_excluded_nodes(manager.excluded());
+        for d in &destinations {
+            let coord = router.get_node(d).unwrap().coord.point;
+            let result = router.simulate_delivery(&NodeId::new("s"), d, coord, 20);
+            assert!(result.success);
+            assert!(!result.path.contains(&NodeId::new("a")));
+        }
+    }
+
+    #[test]
+    fn test_forged_confirmation_rejected() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let destination_key = SigningKey::from_bytes(&rng.gen());
+        let forger_key = SigningKey::from_bytes(&rng.gen());
+        let manager = ProbeManager::new(NodeId::new("s"), ProbeConfig::default());
+        let probe = manager
+            .plan_round(&[NodeId::new("a")], &[NodeId::new("d")], 0, &mut rng)
+            .remove(0);
+        let public_key = destination_key.verifying_key();
+
+        let forged = probe.confirm(2, forger_key.as_bytes()).unwrap();
+        assert!(manager.confirm(&forged, public_key.as_bytes()).is_err());
+
+        let mut replayed = probe.confirm(2, destination_key.as_bytes()).unwrap();
+        replayed.nonce ^= 1;
+        assert!(manager.confirm(&replayed, public_key.as_bytes()).is_err());
+
+        let genuine = probe.confirm(2, destination_key.as_bytes()).unwrap();
+        assert!(manager.confirm(&genuine, public_key.as_bytes()).is_ok());
+        assert_eq!(manager.pending_count(), 0);
+    }
+}
@@ -0,0 +1,317 @@
+//! Hyperbolic Procrustes Alignment
+//!
+//! Two embeddings of the same nodes, from different runs, seeds or
+//! clusters, agree at best up to an isometry of the disk: a rotation, a
+//! Möbius translation and possibly a reflection. Comparing them, or merging
+//! them, first needs the isometry that maps one onto the other:
+//!
+//! ```text
+//! T(p) = T_b( R_θ( T_-a( reflect(p) ) ) )
+//! ```
+//!
+//! `T_c` is the Möbius translation moving the origin to `c`, and `R_θ` a
+//! rotation about the origin. `b` is fixed at the hyperbolic centroid of the
+//! target points; `a` starts at the centroid of the source points and `θ` at
+//! the weighted circular mean of the angle differences seen from the two
+//! centroids. A compass search over `(a, θ)` then minimises the sum of
+//! squared hyperbolic distances between mapped and target points, with and
+//! without reflection. Unlike `coordinate_recovery`, nothing is scaled, so
+//! the remaining error measures how differently the two embeddings place
+//! the nodes.
+
+use std::collections::HashMap;
+
+use num_complex::Complex64;
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::PoincareDiskPoint;
+
+/// Compass search stops once every step is below this
+const MIN_STEP: f64 = 1e-10;
+/// Bound on compass search iterations per reflection
+const MAX_ITERATIONS: usize = 4000;
+/// Disk points are kept this far inside the boundary
+const MAX_NORM: f64 = 1.0 - 1e-15;
+
+/// Isometry of the Poincaré disk
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MobiusTransform {
+    /// Points are mirrored (complex conjugate) first
+    pub reflect: bool,
+    /// Point moved to the origin before rotating
+    pub center: (f64, f64),
+    pub rotation: f64,
+    /// Point the origin is moved to after rotating
+    pub target: (f64, f64),
+}
+
+impl Default for MobiusTransform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl MobiusTransform {
+    pub fn identity() -> Self {
+        Self { reflect: false, center: (0.0, 0.0), rotation: 0.0, target: (0.0, 0.0) }
+    }
+
+    fn map(&self, z: Complex64) -> Complex64 {
+        let z = if self.reflect { z.conj() } else { z };
+        let z = translate(-Complex64::new(self.center.0, self.center.1), z);
+        translate(Complex64::new(self.target.0, self.target.1), z * Complex64::from_polar(1.0, self.rotation))
+    }
+
+    pub fn apply(&self, p: &PoincareDiskPoint) -> PoincareDiskPoint {
+        let z = self.map(Complex64::new(p.x, p.y));
+        PoincareDiskPoint { x: z.re, y: z.im }
+    }
+
+    /// Apply to every coordinate of an embedding
+    pub fn apply_all(&self, embedding: &HashMap<NodeId, PoincareDiskPoint>) -> HashMap<NodeId, PoincareDiskPoint> {
+        embedding.iter().map(|(id, p)| (id.clone(), self.apply(p))).collect()
+    }
+
+    /// The transform undoing this one
+    pub fn inverse(&self) -> Self {
+        if self.reflect {
+            // Conjugation turns T_c into T_c̄ and R_θ into R_-θ
+            Self {
+                reflect: true,
+                center: (self.target.0, -self.target.1),
+                rotation: self.rotation,
+                target: (self.center.0, -self.center.1),
+            }
+        } else {
+            Self { reflect: false, center: self.target, rotation: -self.rotation, target: self.center }
+        }
+    }
+}
+
+/// Möbius translation moving the origin to `c`
+fn translate(c: Complex64, z: Complex64) -> Complex64 {
+    let w = (z + c) / (1.0 + c.conj() * z);
+    if w.norm() >= MAX_NORM {
+        w * (MAX_NORM / w.norm())
+    } else {
+        w
+    }
+}
+
+/// Hyperbolic centroid: the Einstein midpoint, taken in the Klein model
+fn centroid(points: &[Complex64]) -> Complex64 {
+    let (mut sum, mut weight) = (Complex64::new(0.0, 0.0), 0.0);
+    for z in points {
+        let klein = 2.0 * z / (1.0 + z.norm_sqr());
+        let gamma = 1.0 / (1.0 - klein.norm_sqr()).max(f64::MIN_POSITIVE).sqrt();
+        sum += klein * gamma;
+        weight += gamma;
+    }
+    let klein = sum / weight;
+    klein / (1.0 + (1.0 - klein.norm_sqr()).max(0.0).sqrt())
+}
+
+/// Alignment settings
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcrustesConfig {
+    /// Also consider mirrored copies of the source
+    pub allow_reflection: bool,
+}
+
+impl Default for ProcrustesConfig {
+    fn default() -> Self {
+        Self { allow_reflection: true }
+    }
+}
+
+/// Best transform of a source embedding onto a target and what remains
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ProcrustesFit {
+    pub transform: MobiusTransform,
+    /// Nodes in both embeddings
+    pub nodes: usize,
+    /// Root mean squared hyperbolic distance after alignment
+    pub rmsd: f64,
+    /// Largest hyperbolic distance after alignment
+    pub max_error: f64,
+}
+
+struct Pairs {
+    source: Vec<Complex64>,
+    target: Vec<Complex64>,
+}
+
+impl Pairs {
+    fn cost(&self, transform: &MobiusTransform) -> f64 {
+        self.errors(transform).map(|d| d * d).sum()
+    }
+
+    fn errors<'a>(&'a self, transform: &'a MobiusTransform) -> impl Iterator<Item = f64> + 'a {
+        self.source.iter().zip(&self.target).map(|(s, t)| {
+            let mapped = transform.map(*s);
+            PoincareDiskPoint { x: mapped.re, y: mapped.im }.hyperbolic_distance(&PoincareDiskPoint { x: t.re, y: t.im })
+        })
+    }
+}
+
+/// Fit the isometry taking `source` onto `target` over their common nodes
+///
+/// Returns None with fewer than two common nodes.
+pub fn align(
+    source: &HashMap<NodeId, PoincareDiskPoint>,
+    target: &HashMap<NodeId, PoincareDiskPoint>,
+    config: &ProcrustesConfig,
+) -> Option<ProcrustesFit> {
+    let mut pairs = Pairs { source: Vec::new(), target: Vec::new() };
+    for (id, s) in source {
+        if let Some(t) = target.get(id) {
+            pairs.source.push(Complex64::new(s.x, s.y));
+            pairs.target.push(Complex64::new(t.x, t.y));
+        }
+    }
+    if pairs.source.len() < 2 {
+        return None;
+    }
+
+    let reflections: &[bool] = if config.allow_reflection { &[false, true] } else { &[false] };
+    let (transform, cost) = reflections
+        .iter()
+        .map(|&reflect| fit(&pairs, reflect))
+        .min_by(|a, b| a.1.total_cmp(&b.1))?;
+    let nodes = pairs.source.len();
+    Some(ProcrustesFit {
+        transform,
+        nodes,
+        rmsd: (cost / nodes as f64).sqrt(),
+        max_error: pairs.errors(&transform).fold(0.0, f64::max),
+    })
+}
+
+/// Closed-form starting point, refined by compass search
+fn fit(pairs: &Pairs, reflect: bool) -> (MobiusTransform, f64) {
+    let mirrored: Vec<Complex64> = pairs.source.iter().map(|z| if reflect { z.conj() } else { *z }).collect();
+    let center = centroid(&mirrored);
+    let target = centroid(&pairs.target);
+
+    // Rotation: circular mean of the angle differences seen from the centroids
+    let turn: Complex64 = mirrored
+        .iter()
+        .zip(&pairs.target)
+        .map(|(s, t)| (translate(-center, *s), translate(-target, *t)))
+        .filter(|(s, t)| s.norm() > 0.0 && t.norm() > 0.0)
+        .map(|(s, t)| Complex64::from_polar(s.norm().min(t.norm()), t.arg() - s.arg()))
+        .sum();
+
+    let mut best = MobiusTransform {
+        reflect,
+        center: (center.re, center.im),
+        rotation: turn.arg(),
+        target: (target.re, target.im),
+    };
+    let mut best_cost = pairs.cost(&best);
+    let mut steps = [0.05, 0.05, 0.1];
+    for _ in 0..MAX_ITERATIONS {
+        if steps.iter().all(|s| *s < MIN_STEP) {
+            break;
+        }
+        let mut improved = false;
+        for (k, step) in steps.iter().enumerate() {
+            for direction in [1.0, -1.0] {
+                let mut candidate = best;
+                match k {
+                    0 => candidate.center.0 += direction * step,
+                    1 => candidate.center.1 += direction * step,
+                    _ => candidate.rotation += direction * step,
+                }
+                if Complex64::new(candidate.center.0, candidate.center.1).norm() >= MAX_NORM {
+                    continue;
+                }
+                let cost = pairs.cost(&candidate);
+                if cost < best_cost {
+                    best = candidate;
+                    best_cost = cost;
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            steps.iter_mut().for_each(|s| *s /= 2.0);
+        }
+    }
+    (best, best_cost)
+}
+
+/// Merge `other` into `base`, aligned on the nodes they share
+///
+/// Shared nodes keep their position in `base`; nodes only in `other` are
+/// mapped by the fitted transform. Returns None with fewer than two shared
+/// nodes.
+pub fn stitch(
+    base: &HashMap<NodeId, PoincareDiskPoint>,
+    other: &HashMap<NodeId, PoincareDiskPoint>,
+    config: &ProcrustesConfig,
+) -> Option<(HashMap<NodeId, PoincareDiskPoint>, ProcrustesFit)> {
+    let fit = align(other, base, config)?;
+    let mut merged = base.clone();
+    for (id, p) in other {
+        merged.entry(id.clone()).or_insert_with(|| fit.transform.apply(p));
+    }
+    Some((merged, fit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embedding(n: usize) -> HashMap<NodeId, PoincareDiskPoint> {
+        (0..n)
+            .map(|i| {
+                let r = 0.1 + 0.8 * ((i as f64 * 0.618).fract());
+                let p = PoincareDiskPoint::from_polar(r, i as f64 * 2.4).unwrap();
+                (NodeId::new(format!("n{}", i)), p)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_recovers_isometry_with_reflection() {
+        let source = embedding(60);
+        let moved = MobiusTransform { reflect: true, center: (0.3, -0.2), rotation: 1.1, target: (-0.1, 0.25) };
+        let target = moved.apply_all(&source);
+
+        let fit = align(&source, &target, &ProcrustesConfig::default()).unwrap();
+        assert_eq!(fit.nodes, 60);
+        assert!(fit.transform.reflect);
+        assert!(fit.rmsd < 1e-6, "rmsd {}", fit.rmsd);
+        assert!(fit.max_error < 1e-5);
+
+        // The inverse takes the target back
+        let back = fit.transform.inverse().apply_all(&target);
+        for (id, p) in &source {
+            assert!(back[id].hyperbolic_distance(p) < 1e-5);
+        }
+
+        // Without reflection the mirrored copy cannot be matched
+        let rigid = align(&source, &target, &ProcrustesConfig { allow_reflection: false }).unwrap();
+        assert!(rigid.rmsd > 0.1);
+        assert!(align(&source, &HashMap::new(), &ProcrustesConfig::default()).is_none());
+    }
+
+    #[test]
+    fn test_stitch_overlapping_clusters() {
+        let truth = embedding(40);
+        let ids: Vec<NodeId> = (0..40).map(|i| NodeId::new(format!("n{}", i))).collect();
+        // Two clusters sharing nodes 15..25, the second in its own frame
+        let base: HashMap<_, _> = ids[..25].iter().map(|id| (id.clone(), truth[id])).collect();
+        let frame = MobiusTransform { reflect: false, center: (-0.4, 0.1), rotation: -2.0, target: (0.0, 0.0) };
+        let other: HashMap<_, _> = ids[15..].iter().map(|id| (id.clone(), frame.apply(&truth[id]))).collect();
+
+        let (merged, fit) = stitch(&base, &other, &ProcrustesConfig::default()).unwrap();
+        assert_eq!((merged.len(), fit.nodes), (40, 10));
+        for id in &ids {
+            assert!(merged[id].hyperbolic_distance(&truth[id]) < 1e-5, "{} misplaced", id.0);
+        }
+    }
+}
@@ -0,0 +1,481 @@
+//! Congestion-Aware Rendezvous Record Placement
+//!
+//! By default a rendezvous record lives on whichever node happens to be
+//! closest to the target's anchor, however loaded or unstable that node is.
+//! Placement instead scores the nodes near the anchor by distance, load
+//! and coordinate stability, skips overloaded or full ones, and stores the
+//! record on the best few as replicas. A periodic rebalance moves a replica
+//! off its node when the node becomes overloaded or its coordinate drifts
+//! away from the key point. Moves use a two-step hand-off: the record is
+//! copied to the new node first and the old replica is only released once
+//! both copies match the current registration, so a lookup never finds the
+//! record missing or stale. A draining node moves all of its replicas the
+//! same way before it leaves.
+
+use std::collections::HashMap;
+
+use crate::coordinates::{HomeNodeRegistry, NodeId};
+
+/// Placement tuning
+#[derive(Debug, Clone)]
+pub struct PlacementConfig {
+    /// Replicas kept per record; the first is the primary
+    pub replicas: usize,
+    /// Nodes nearest the key point considered for each record
+    pub candidates: usize,
+    /// Score penalty per unit of utilization
+    pub load_weight: f64,
+    /// Score penalty per unit of instability
+    pub stability_weight: f64,
+    /// Utilization at or above which a node takes no records
+    pub overload_threshold: f64,
+    /// Hyperbolic distance a replica may drift beyond where it was placed
+    /// before it is moved
+    pub drift_margin: f64,
+}
+
+impl Default for PlacementConfig {
+    fn default() -> Self {
+        Self {
+            replicas: 3,
+            candidates: 8,
+            load_weight: 2.0,
+            stability_weight: 1.0,
+            overload_threshold: 0.9,
+            drift_margin: 1.0,
+        }
+    }
+}
+
+/// Load a node reports for placement
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NodeLoad {
+    /// Records the node is willing to hold
+    pub capacity: usize,
+    /// Current utilization in [0, 1], e.g. queue occupancy
+    pub utilization: f64,
+    /// Stability score in [0, 1], see `DriftTracker::stability_score`
+    pub stability: f64,
+}
+
+impl Default for NodeLoad {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            utilization: 0.0,
+            stability: 1.0,
+        }
+    }
+}
+
+/// Why a replica is being moved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationReason {
+    /// The holder is overloaded or over capacity
+    Overloaded,
+    /// The holder's coordinate moved away from the key point
+    Drifted,
+    /// The holder is draining and about to leave
+    Draining,
+}
+
+/// A replica move from one node to another
+#[derive(Debug, Clone, PartialEq)]
+pub struct Migration {
+    pub target: NodeId,
+    pub from: NodeId,
+    pub to: NodeId,
+    pub reason: MigrationReason,
+}
+
+/// One copy of a record
+#[derive(Debug, Clone)]
+struct Replica {
+    node: NodeId,
+    /// `updated_at` of the registration this copy holds
+    version: u64,
+    /// Distance from the holder to the key point when placed
+    placed_distance: f64,
+}
+
+/// Replica placement for rendezvous records
+#[derive(Debug, Clone, Default)]
+pub struct RecordPlacement {
+    config: PlacementConfig,
+    loads: HashMap<NodeId, NodeLoad>,
+    /// Replicas per target, best first
+    records: HashMap<NodeId, Vec<Replica>>,
+    /// Copies sent by a hand-off that has not committed, by (target, to)
+    pending: HashMap<(NodeId, NodeId), Replica>,
+}
+
+impl RecordPlacement {
+    pub fn new(config: PlacementConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    pub fn config(&self) -> &PlacementConfig {
+        &self.config
+    }
+
+    /// Update the load a node reports
+    pub fn set_load(&mut self, node: NodeId, load: NodeLoad) {
+        self.loads.insert(node, load);
+    }
+
+    /// Load of a node; nodes that never reported are idle and stable
+    pub fn load(&self, node: &NodeId) -> NodeLoad {
+        self.loads.get(node).copied().unwrap_or_default()
+    }
+
+    /// Number of replicas a node holds
+    pub fn held(&self, node: &NodeId) -> usize {
+        self.records
+            .values()
+            .filter(|replicas| replicas.iter().any(|r| &r.node == node))
+            .count()
+    }
+
+    /// Whether a node is too busy or too full to hold its records
+    pub fn is_overloaded(&self, node: &NodeId) -> bool {
+        let load = self.load(node);
+        load.utilization >= self.config.overload_threshold || self.held(node) > load.capacity
+    }
+
+    /// Placement score of a node at `distance` from the key point; lower is
+    /// better. None if the node cannot take another record.
+    pub fn score(&self, node: &NodeId, distance: f64) -> Option<f64> {
+        let load = self.load(node);
+        if load.utilization >= self.config.overload_threshold || self.held(node) >= load.capacity {
+            return None;
+        }
+        Some(
+            distance
+                + self.config.load_weight * load.utilization
+                + self.config.stability_weight * (1.0 - load.stability.clamp(0.0, 1.0)),
+        )
+    }
+
+    /// Eligible candidates for `target` accepted by `eligible`, best first,
+    /// with their distances to the key point
+    fn ranked_candidates(
+        &self,
+        target: &NodeId,
+        registry: &HomeNodeRegistry,
+        eligible: impl Fn(&NodeId) -> bool,
+    ) -> Vec<(NodeId, f64)> {
+        let key = registry.get_anchor(target).point;
+        let mut scored: Vec<(NodeId, f64, f64)> = registry
+            .nodes_near(&key, self.config.candidates)
+            .into_iter()
+            .filter(|(node, _)| eligible(node))
+            .filter_map(|(node, distance)| {
+                let score = self.score(&node, distance)?;
+                Some((node, distance, score))
+            })
+            .collect();
+        scored.sort_by(|a, b| a.2.total_cmp(&b.2).then_with(|| a.0 .0.cmp(&b.0 .0)));
+        scored.into_iter().map(|(node, distance, _)| (node, distance)).collect()
+    }
+
+    /// Place the record for `target` at `version` on the best candidates
+    ///
+    /// A record that is already placed keeps its replicas and only has its
+    /// version refreshed; moving it is left to rebalancing. Returns the
+    /// replica holders, primary first; empty if no candidate is eligible.
+    pub fn place(
+        &mut self,
+        target: &NodeId,
+        registry: &HomeNodeRegistry,
+        version: u64,
+        eligible: impl Fn(&NodeId) -> bool,
+    ) -> Vec<NodeId> {
+        if self.records.contains_key(target) {
+            self.record_update(target, version);
+            return self.replicas(target);
+        }
+        let replicas: Vec<Replica> = self
+            .ranked_candidates(target, registry, eligible)
+            .into_iter()
+            .take(self.config.replicas)
+            .map(|(node, placed_distance)| Replica { node, version, placed_distance })
+            .collect();
+        if replicas.is_empty() {
+            return Vec::new();
+        }
+        self.records.insert(target.clone(), replicas);
+        self.replicas(target)
+    }
+
+    /// Replica holders of a record, primary first
+    pub fn replicas(&self, target: &NodeId) -> Vec<NodeId> {
+        self.records
+            .get(target)
+            .map(|replicas| replicas.iter().map(|r| r.node.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Primary replica holder of a record
+    pub fn primary(&self, target: &NodeId) -> Option<&NodeId> {
+        self.records.get(target)?.first().map(|r| &r.node)
+    }
+
+    /// Propagate a refreshed registration to every replica
+    pub fn record_update(&mut self, target: &NodeId, version: u64) {
+        for replica in self.records.get_mut(target).into_iter().flatten() {
+            replica.version = version;
+        }
+    }
+
+    /// Forget a record and any hand-off in flight for it
+    pub fn remove(&mut self, target: &NodeId) {
+        self.records.remove(target);
+        self.pending.retain(|(pending, _), _| pending != target);
+    }
+
+    /// Replicas that should move, with where to
+    ///
+    /// A replica moves when its holder is overloaded, or when the holder
+    /// has drifted more than `drift_margin` farther from the key point than
+    /// it was when placed and a better-scoring node is available.
+    pub fn plan_migrations(&self, registry: &HomeNodeRegistry) -> Vec<Migration> {
+        let mut targets: Vec<&NodeId> = self.records.keys().collect();
+        targets.sort();
+
+        let mut migrations = Vec::new();
+        for target in targets {
+            let replicas = &self.records[target];
+            let key = registry.get_anchor(target).point;
+            let mut candidates = self
+                .ranked_candidates(target, registry, |node| !replicas.iter().any(|r| &r.node == node))
+                .into_iter();
+            for replica in replicas {
+                let distance = registry
+                    .get_routing(&replica.node)
+                    .map_or(f64::INFINITY, |coord| coord.point.hyperbolic_distance(&key));
+                let reason = if self.is_overloaded(&replica.node) {
+                    MigrationReason::Overloaded
+                } else if distance - replica.placed_distance > self.config.drift_margin {
+                    MigrationReason::Drifted
+                } else {
+                    continue;
+                };
+                let Some((to, to_distance)) = candidates.next() else { break };
+                let improves = match self.score(&replica.node, distance) {
+                    Some(current) => self.score(&to, to_distance).is_some_and(|score| score < current),
+                    None => true,
+                };
+                if reason == MigrationReason::Overloaded || improves {
+                    migrations.push(Migration {
+                        target: target.clone(),
+                        from: replica.node.clone(),
+                        to,
+                        reason,
+                    });
+                }
+            }
+        }
+        migrations
+    }
+
+    /// Moves for every replica a draining node holds
+    ///
+    /// Each replica goes to the best candidate that holds no copy of the
+    /// record yet; the moves use the same two-step hand-off as rebalancing.
+    pub fn plan_drain(&self, node: &NodeId, registry: &HomeNodeRegistry) -> Vec<Migration> {
+        let mut targets: Vec<&NodeId> = self
+            .records
+            .iter()
+            .filter(|(_, replicas)| replicas.iter().any(|r| &r.node == node))
+            .map(|(target, _)| target)
+            .collect();
+        targets.sort();
+
+        targets
+            .into_iter()
+            .filter_map(|target| {
+                let replicas = &self.records[target];
+                let (to, _) = self
+                    .ranked_candidates(target, registry, |candidate| {
+                        candidate != node && !replicas.iter().any(|r| &r.node == candidate)
+                    })
+                    .into_iter()
+                    .next()?;
+                Some(Migration {
+                    target: target.clone(),
+                    from: node.clone(),
+                    to,
+                    reason: MigrationReason::Draining,
+                })
+            })
+            .collect()
+    }
+
+    /// First step of a hand-off: copy the record to the new holder
+    ///
+    /// The old replica keeps serving until `commit_handoff`.
+    pub fn begin_handoff(&mut self, migration: &Migration, registry: &HomeNodeRegistry) -> Result<(), String> {
+        let replicas = self
+            .records
+            .get(&migration.target)
+            .ok_or_else(|| format!("No record for {}", migration.target))?;
+        let source = replicas
+            .iter()
+            .find(|r| r.node == migration.from)
+            .ok_or_else(|| format!("{} holds no replica of {}", migration.from, migration.target))?;
+        if replicas.iter().any(|r| r.node == migration.to) {
+            return Err(format!("{} already holds a replica of {}", migration.to, migration.target));
+        }
+        let placed_distance = registry
+            .get_routing(&migration.to)
+            .ok_or_else(|| format!("Unknown node {}", migration.to))?
+            .point
+            .hyperbolic_distance(&registry.get_anchor(&migration.target).point);
+        if self.score(&migration.to, placed_distance).is_none() {
+            return Err(format!("{} cannot take more records", migration.to));
+        }
+        let copy = Replica {
+            node: migration.to.clone(),
+            version: source.version,
+            placed_distance,
+        };
+        self.pending.insert((migration.target.clone(), migration.to.clone()), copy);
+        Ok(())
+    }
+
+    /// Second step of a hand-off: swap the new holder in for the old one
+    ///
+    /// Both the copy and the source replica must hold `registered_version`,
+    /// the version of the current registration. If the record changed
+    /// while the copy was in flight the hand-off is aborted and the old
+    /// replica stays in place.
+    pub fn commit_handoff(&mut self, migration: &Migration, registered_version: u64) -> Result<(), String> {
+        let copy = self
+            .pending
+            .remove(&(migration.target.clone(), migration.to.clone()))
+            .ok_or_else(|| format!("No hand-off of {} to {} in progress", migration.target, migration.to))?;
+        let replicas = self
+            .records
+            .get_mut(&migration.target)
+            .ok_or_else(|| format!("No record for {}", migration.target))?;
+        let source = replicas
+            .iter_mut()
+            .find(|r| r.node == migration.from)
+            .ok_or_else(|| format!("{} holds no replica of {}", migration.from, migration.target))?;
+        if copy.version != source.version || source.version != registered_version {
+            return Err(format!(
+                "Inconsistent hand-off of {}: copy v{}, source v{}, registered v{}",
+                migration.target, copy.version, source.version, registered_version
+            ));
+        }
+        *source = copy;
+        Ok(())
+    }
+
+    /// Drop a hand-off in flight
+    pub fn abort_handoff(&mut self, migration: &Migration) {
+        self.pending.remove(&(migration.target.clone(), migration.to.clone()));
+    }
+
+    /// Replica holders whose copy is not at `registered_version`
+    pub fn check_consistency(&self, target: &NodeId, registered_version: u64) -> Vec<NodeId> {
+        self.records
+            .get(target)
+            .into_iter()
+            .flatten()
+            .filter(|r| r.version != registered_version)
+            .map(|r| r.node.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinates::RoutingCoordinate;
+
+    fn registry_around(target: &NodeId, count: usize) -> (HomeNodeRegistry, Vec<NodeId>) {
+        let mut registry = HomeNodeRegistry::new();
+        let key = registry.get_anchor(target).point;
+        let nodes: Vec<NodeId> = (0..count).map(|i| NodeId::new(format!("n{}", i))).collect();
+        for (i, node) in nodes.iter().enumerate() {
+            // n0 is nearest the key point, each next one a little farther
+            let point = crate::PoincareDiskPoint::new(key.x * (1.0 - 0.05 * i as f64), key.y * (1.0 - 0.05 * i as f64)).unwrap();
+            registry.register_node(node.clone(), RoutingCoordinate::new(point, 0));
+        }
+        (registry, nodes)
+    }
+
+    #[test]
+    fn test_placement_avoids_loaded_nodes() {
+        let target = NodeId::new("target");
+        let (registry, nodes) = registry_around(&target, 5);
+        let mut placement = RecordPlacement::new(PlacementConfig { replicas: 2, ..Default::default() });
+        placement.set_load(nodes[0].clone(), NodeLoad { utilization: 0.95, ..Default::default() });
+        placement.set_load(nodes[1].clone(), NodeLoad { capacity: 0, ..Default::default() });
+
+        assert_eq!(placement.place(&target, &registry, 1, |_| true), vec![nodes[2].clone(), nodes[3].clone()]);
+        assert_eq!(placement.primary(&target), Some(&nodes[2]));
+        assert!(placement.place(&NodeId::new("other"), &registry, 1, |_| false).is_empty());
+    }
+
+    #[test]
+    fn test_overload_migrates_with_consistent_handoff() {
+        let target = NodeId::new("target");
+        let (registry, nodes) = registry_around(&target, 4);
+        let mut placement = RecordPlacement::new(PlacementConfig { replicas: 2, ..Default::default() });
+        placement.place(&target, &registry, 1, |_| true);
+        assert!(placement.plan_migrations(&registry).is_empty());
+
+        placement.set_load(nodes[0].clone(), NodeLoad { utilization: 1.0, ..Default::default() });
+        let migrations = placement.plan_migrations(&registry);
+        assert_eq!(
+            migrations,
+            vec![Migration {
+                target: target.clone(),
+                from: nodes[0].clone(),
+                to: nodes[2].clone(),
+                reason: MigrationReason::Overloaded,
+            }]
+        );
+
+        // A registration refresh during the hand-off makes the copy stale
+        let migration = &migrations[0];
+        placement.begin_handoff(migration, &registry).unwrap();
+        placement.record_update(&target, 2);
+        assert!(placement.commit_handoff(migration, 2).is_err());
+        assert_eq!(placement.replicas(&target), vec![nodes[0].clone(), nodes[1].clone()]);
+
+        placement.begin_handoff(migration, &registry).unwrap();
+        placement.commit_handoff(migration, 2).unwrap();
+        assert_eq!(placement.replicas(&target), vec![nodes[2].clone(), nodes[1].clone()]);
+        assert!(placement.check_consistency(&target, 2).is_empty());
+        assert_eq!(placement.check_consistency(&target, 3).len(), 2);
+    }
+
+    #[test]
+    fn test_drain_moves_every_replica() {
+        let target = NodeId::new("target");
+        let (registry, nodes) = registry_around(&target, 4);
+        let mut placement = RecordPlacement::new(PlacementConfig { replicas: 2, ..Default::default() });
+        placement.place(&target, &registry, 1, |_| true);
+        assert!(placement.plan_drain(&nodes[3], &registry).is_empty());
+
+        let migrations = placement.plan_drain(&nodes[0], &registry);
+        assert_eq!(
+            migrations,
+            vec![Migration {
+                target: target.clone(),
+                from: nodes[0].clone(),
+                to: nodes[2].clone(),
+                reason: MigrationReason::Draining,
+            }]
+        );
+        placement.begin_handoff(&migrations[0], &registry).unwrap();
+        placement.commit_handoff(&migrations[0], 1).unwrap();
+        assert_eq!(placement.held(&nodes[0]), 0);
+    }
+}
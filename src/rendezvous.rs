@@ -2,8 +2,25 @@
 //!
 //! Implements the distributed protocol for resolving node coordinates
 //! when only the destination ID is known.
+//!
+//! During a partition a node's home may be on the other side. Registration
+//! then falls back to the closest reachable node as a temporary home, and
+//! resolution at a local minimum consults temporary records when the real
+//! home is unreachable. Once the partition heals,
+//! `RendezvousController::reconcile_after_healing` merges the temporary
+//! records into the real homes.
+//!
+//! With record placement enabled, a record is held by replicas chosen for
+//! their load and stability rather than by the closest node alone. The
+//! closest node then redirects lookups to the primary replica, and
+//! `RendezvousController::rebalance` moves replicas off nodes that became
+//! overloaded or drifted away.
 
+use std::collections::{HashSet, VecDeque};
+
+use crate::certificate::{CertificateStore, CertificateVerifier, CoordinateCertificate};
 use crate::coordinates::{AnchorCoordinate, HomeNodeRegistry, NodeId, RoutingCoordinate};
+use crate::record_placement::{Migration, NodeLoad, PlacementConfig, RecordPlacement};
 use crate::routing::{GPRouter, RoutingNode};
 use crate::PoincareDiskPoint;
 
@@ -66,6 +83,8 @@ pub struct RegistrationMessage {
     pub ttl: u64,
     /// Timestamp
     pub timestamp: u64,
+    /// Certificate attesting `routing_coord`
+    pub certificate: Option<CoordinateCertificate>,
 }
 
 impl RegistrationMessage {
@@ -75,8 +94,15 @@ impl RegistrationMessage {
             routing_coord: coord,
             ttl,
             timestamp,
+            certificate: None,
         }
     }
+
+    /// Attach a coordinate certificate
+    pub fn with_certificate(mut self, certificate: CoordinateCertificate) -> Self {
+        self.certificate = Some(certificate);
+        self
+    }
 }
 
 /// Rendezvous controller managing the two-phase routing protocol
@@ -90,6 +116,12 @@ pub struct RendezvousController {
     /// Registration interval
     #[allow(dead_code)]
     registration_interval: u64,
+    /// Certificates received with registrations
+    certificates: CertificateStore,
+    /// When set, home nodes only hand out certified coordinates
+    verifier: Option<CertificateVerifier>,
+    /// Replica placement for records; None keeps them at the closest node
+    placement: Option<RecordPlacement>,
 }
 
 impl RendezvousController {
@@ -99,7 +131,77 @@ impl RendezvousController {
             router: GPRouter::new(),
             registration_ttl,
             registration_interval,
+            certificates: CertificateStore::new(),
+            verifier: None,
+            placement: None,
+        }
+    }
+
+    /// Place records by load and stability from now on
+    pub fn enable_placement(&mut self, config: PlacementConfig) {
+        self.placement = Some(RecordPlacement::new(config));
+    }
+
+    /// Record placement, if enabled
+    pub fn placement(&self) -> Option<&RecordPlacement> {
+        self.placement.as_ref()
+    }
+
+    /// Update the load a node reports; ignored unless placement is enabled
+    pub fn set_node_load(&mut self, node: NodeId, load: NodeLoad) {
+        if let Some(placement) = &mut self.placement {
+            placement.set_load(node, load);
+        }
+    }
+
+    /// Node that serves lookups for `target`: its primary replica when
+    /// placement holds it, otherwise its home node
+    pub fn home_of(&self, target: &NodeId) -> Option<NodeId> {
+        self.placement
+            .as_ref()
+            .and_then(|placement| placement.primary(target).cloned())
+            .or_else(|| self.registry.find_home_node(target))
+    }
+
+    /// Require certified coordinates at home nodes (None disables checks)
+    pub fn set_certificate_verifier(&mut self, verifier: Option<CertificateVerifier>) {
+        self.verifier = verifier;
+    }
+
+    /// Process a registration carrying a coordinate certificate
+    ///
+    /// The certificate must verify and attest the registered coordinate;
+    /// otherwise the registration is rejected.
+    pub fn process_certified_registration(
+        &mut self,
+        msg: RegistrationMessage,
+        current_time: u64,
+    ) -> Result<(), String> {
+        let verifier = self
+            .verifier
+            .as_ref()
+            .ok_or_else(|| "No certificate verifier configured".to_string())?;
+        let cert = msg
+            .certificate
+            .clone()
+            .ok_or_else(|| format!("Registration of {} carries no certificate", msg.node_id))?;
+        if cert.node_id() != &msg.node_id {
+            return Err(format!("Certificate subject {} does not match {}", cert.node_id(), msg.node_id));
         }
+        verifier.verify_coordinate(&cert, &msg.routing_coord.point, current_time)?;
+        self.certificates.insert(cert, verifier, current_time)?;
+        self.process_registration(msg, current_time);
+        Ok(())
+    }
+
+    /// Certificate stored for a registered node
+    pub fn lookup_certificate(&self, node_id: &NodeId) -> Option<&CoordinateCertificate> {
+        self.certificates.get(node_id)
+    }
+
+    /// Identity key of a node, as known to the certificate verifier
+    pub fn lookup_identity_key(&self, node_id: &NodeId) -> Option<&[u8]> {
+        self.verifier.as_ref()?.key(node_id)
     }
 
     /// Add a node to the network
@@ -120,6 +222,9 @@ impl RendezvousController {
         msg: RegistrationMessage,
         current_time: u64,
     ) {
+        if let Some(placement) = &mut self.placement {
+            placement.record_update(&msg.node_id, msg.routing_coord.updated_at);
+        }
         self.registry.register_at_home(
             &msg.node_id,
             msg.routing_coord,
@@ -128,7 +233,27 @@ impl RendezvousController {
         );
     }
 
+    /// Nodes reachable from `from` over the current topology
+    pub fn reachable_from(&self, from: &NodeId) -> HashSet<NodeId> {
+        let mut reachable = HashSet::from([from.clone()]);
+        let mut queue = VecDeque::from([from.clone()]);
+        while let Some(id) = queue.pop_front() {
+            let Some(node) = self.router.get_node(&id) else { continue };
+            for neighbor in &node.neighbors {
+                if reachable.insert(neighbor.clone()) {
+                    queue.push_back(neighbor.clone());
+                }
+            }
+        }
+        reachable
+    }
+
     /// Simulate registration of a node to its home node
+    ///
+    /// If the home node is partitioned away, the closest reachable node to
+    /// the anchor becomes a temporary home and is returned instead. With
+    /// placement enabled the record is also placed on reachable replicas
+    /// and the primary is returned.
     pub fn register_node_to_home(
         &mut self,
         node_id: &NodeId,
@@ -138,6 +263,18 @@ impl RendezvousController {
         
         // Find home node
         let home = self.registry.find_home_node(node_id)?;
+        let reachable = self.reachable_from(node_id);
+        if !reachable.contains(&home) {
+            let temporary = self.registry.find_home_node_where(node_id, |id| reachable.contains(id))?;
+            self.registry.register_at_temporary_home(
+                node_id,
+                &temporary,
+                coord,
+                self.registration_ttl,
+                current_time,
+            );
+            return Some(temporary);
+        }
 
         // Register at home node
         self.registry.register_at_home(
@@ -147,9 +284,54 @@ impl RendezvousController {
             current_time,
         );
 
+        if let Some(placement) = &mut self.placement {
+            let replicas = placement.place(node_id, &self.registry, coord.updated_at, |id| reachable.contains(id));
+            if let Some(primary) = replicas.into_iter().next() {
+                return Some(primary);
+            }
+        }
+
         Some(home)
     }
 
+    /// Move replicas off overloaded or drifted nodes
+    ///
+    /// Meant to run periodically. Each move is a hand-off: the record is
+    /// copied to a node reachable from the old holder, checked against the
+    /// current registration, and only then released by the old holder.
+    /// Moves that fail a check are aborted and retried on a later run.
+    ///
+    /// Returns the migrations that committed.
+    pub fn rebalance(&mut self, current_time: u64) -> Vec<Migration> {
+        let Some(placement) = &self.placement else {
+            return Vec::new();
+        };
+        let planned = placement.plan_migrations(&self.registry);
+
+        let mut committed = Vec::new();
+        for migration in planned {
+            let Some(version) = self
+                .registry
+                .lookup_registration(&migration.target, current_time)
+                .map(|coord| coord.updated_at)
+            else {
+                continue;
+            };
+            if !self.reachable_from(&migration.from).contains(&migration.to) {
+                continue;
+            }
+            let Some(placement) = &mut self.placement else { break };
+            let moved = placement
+                .begin_handoff(&migration, &self.registry)
+                .and_then(|()| placement.commit_handoff(&migration, version));
+            match moved {
+                Ok(()) => committed.push(migration),
+                Err(_) => placement.abort_handoff(&migration),
+            }
+        }
+        committed
+    }
+
     /// Route a rendezvous packet
     pub fn route_packet(
         &self,
@@ -172,12 +354,18 @@ impl RendezvousController {
         match packet.phase {
             RendezvousPhase::TowardAnchor => {
                 // Check if current node is the home node
-                if let Some(home) = self.registry.find_home_node(&packet.destination) {
+                if let Some(home) = self.home_of(&packet.destination) {
                     if &home == current_node {
                         // We're at home node - lookup destination coordinate
                         if let Some(dest_coord) =
                             self.registry.lookup_registration(&packet.destination, current_time)
                         {
+                            if let Err(reason) = self.verify(&packet.destination, dest_coord, current_time) {
+                                return RendezvousRoutingResult::Failed {
+                                    reason: format!("Unverified coordinate: {}", reason),
+                                };
+                            }
+
                             // Switch to Phase 2
                             packet.switch_to_destination(dest_coord.point);
                             return self.route_toward_destination(packet, current_node);
@@ -194,7 +382,17 @@ impl RendezvousController {
                 }
 
                 // Continue routing toward anchor
-                self.route_toward_anchor(packet, current_node)
+                match self.route_toward_anchor(packet, current_node) {
+                    RendezvousRoutingResult::AtHomeNode { .. } => {
+                        // The closest node redirects to the primary replica, if elsewhere
+                        if self.redirect_to_primary(packet) {
+                            self.route_packet(packet, current_node, current_time)
+                        } else {
+                            self.resolve_partitioned(packet, current_node, current_time)
+                        }
+                    }
+                    result => result,
+                }
             }
             RendezvousPhase::TowardDestination => {
                 self.route_toward_destination(packet, current_node)
@@ -202,6 +400,90 @@ impl RendezvousController {
         }
     }
 
+    /// Retarget a packet that reached the anchor at the primary replica
+    ///
+    /// Returns false if placement holds no replica elsewhere or the packet
+    /// already heads for it.
+    fn redirect_to_primary(&self, packet: &mut RendezvousPacket) -> bool {
+        let Some(primary) = self.placement.as_ref().and_then(|p| p.primary(&packet.destination)) else {
+            return false;
+        };
+        let Some(coord) = self.registry.get_routing(primary) else {
+            return false;
+        };
+        if packet.target_coord == coord.point {
+            return false;
+        }
+        packet.target_coord = coord.point;
+        true
+    }
+
+    /// Resolve at a local minimum that is not the home node
+    ///
+    /// If the home node is reachable this is just a greedy dead end. If it
+    /// is partitioned away, a temporary registration held on this side
+    /// stands in for it.
+    fn resolve_partitioned(
+        &self,
+        packet: &mut RendezvousPacket,
+        current_node: &NodeId,
+        current_time: u64,
+    ) -> RendezvousRoutingResult {
+        let Some(home) = self.registry.find_home_node(&packet.destination) else {
+            return RendezvousRoutingResult::AtHomeNode { home_node: current_node.clone() };
+        };
+        let reachable = self.reachable_from(current_node);
+        if reachable.contains(&home) {
+            return RendezvousRoutingResult::AtHomeNode { home_node: current_node.clone() };
+        }
+        match self.registry.lookup_temporary(&packet.destination, current_time, |id| reachable.contains(id)) {
+            Some(dest_coord) => {
+                if let Err(reason) = self.verify(&packet.destination, dest_coord, current_time) {
+                    return RendezvousRoutingResult::Failed {
+                        reason: format!("Unverified coordinate: {}", reason),
+                    };
+                }
+                packet.switch_to_destination(dest_coord.point);
+                self.route_toward_destination(packet, current_node)
+            }
+            None => RendezvousRoutingResult::Failed {
+                reason: format!(
+                    "Home node {} of {} is unreachable and no temporary registration exists",
+                    home, packet.destination
+                ),
+            },
+        }
+    }
+
+    /// Check a resolved coordinate against its certificate, if certificates are required
+    fn verify(&self, destination: &NodeId, coord: &RoutingCoordinate, current_time: u64) -> Result<(), String> {
+        let Some(verifier) = &self.verifier else {
+            return Ok(());
+        };
+        self.certificates
+            .get(destination)
+            .ok_or_else(|| format!("No certificate for {}", destination))
+            .and_then(|cert| verifier.verify_coordinate(cert, &coord.point, current_time))
+    }
+
+    /// Merge temporary registrations into their real home nodes
+    ///
+    /// Run after a partition heals, alongside the node's routing table
+    /// merge. Records whose home is still unreachable from their temporary
+    /// home stay where they are.
+    ///
+    /// Returns (target, home) for each merged record.
+    pub fn reconcile_after_healing(&mut self) -> Vec<(NodeId, NodeId)> {
+        let mut merged = Vec::new();
+        for (target, temporary) in self.registry.temporary_registrations() {
+            let Some(home) = self.registry.find_home_node(&target) else { continue };
+            if self.reachable_from(&temporary).contains(&home) && self.registry.merge_temporary(&target) {
+                merged.push((target, home));
+            }
+        }
+        merged
+    }
+
     fn route_toward_anchor(
         &self,
         packet: &RendezvousPacket,
@@ -346,6 +628,161 @@ mod tests {
         assert_eq!(msg.ttl, 100);
     }
 
+    #[test]
+    fn test_certified_registration_required() {
+        use ed25519_dalek::SigningKey;
+
+        let mut controller = RendezvousController::new(1000, 100);
+        let ids: Vec<NodeId> = (0..4).map(|i| NodeId::new(format!("n{}", i))).collect();
+        for (i, id) in ids.iter().enumerate() {
+            let point = PoincareDiskPoint::from_polar(0.5, i as f64 * std::f64::consts::FRAC_PI_2).unwrap();
+            controller.add_node(id.clone(), RoutingCoordinate::new(point, 0));
+        }
+        for i in 0..4 {
+            controller.add_edge(&ids[i], &ids[(i + 1) % 4]);
+            controller.add_edge(&ids[(i + 1) % 4], &ids[i]);
+        }
+
+        let dest = ids[2].clone();
+        let coord = *controller.registry().get_routing(&dest).unwrap();
+        let secret = [9u8; 32];
+        let mut verifier = CertificateVerifier::default();
+        verifier.add_key(dest.clone(), SigningKey::from_bytes(&secret).verifying_key().to_bytes().to_vec());
+        controller.set_certificate_verifier(Some(verifier));
+
+        // Uncertified registrations are stored but not handed out
+        controller.register_node_to_home(&dest, 0);
+        let home = controller.registry().find_home_node(&dest).unwrap();
+        let mut packet = RendezvousPacket::new(ids[0].clone(), dest.clone(), 10, vec![]);
+        assert!(matches!(
+            controller.route_packet(&mut packet, &home, 1),
+            RendezvousRoutingResult::Failed { .. }
+        ));
+
+        // A certificate for a different coordinate is rejected
+        let elsewhere = PoincareDiskPoint::new(0.1, 0.0).unwrap();
+        let mut forged = CoordinateCertificate::new(dest.clone(), elsewhere, 1, 0, 1000);
+        forged.sign(&secret).unwrap();
+        let msg = RegistrationMessage::new(dest.clone(), coord, 1000, 0).with_certificate(forged);
+        assert!(controller.process_certified_registration(msg, 1).is_err());
+
+        let mut cert = CoordinateCertificate::new(dest.clone(), coord.point, 1, 0, 1000);
+        cert.sign(&secret).unwrap();
+        let msg = RegistrationMessage::new(dest.clone(), coord, 1000, 0).with_certificate(cert);
+        controller.process_certified_registration(msg, 1).unwrap();
+        assert!(controller.lookup_certificate(&dest).is_some());
+
+        let mut packet = RendezvousPacket::new(ids[0].clone(), dest.clone(), 10, vec![]);
+        let result = controller.route_packet(&mut packet, &home, 2);
+        assert!(!matches!(result, RendezvousRoutingResult::Failed { .. }));
+        assert_eq!(packet.phase, RendezvousPhase::TowardDestination);
+    }
+
+    #[test]
+    fn test_partitioned_home_falls_back_and_reconciles() {
+        let mut controller = RendezvousController::new(1000, 100);
+        // Ring "a" surrounds every anchor; "b" sits at the center, cut off
+        let a: Vec<NodeId> = (0..8).map(|i| NodeId::new(format!("a{}", i))).collect();
+        for (i, id) in a.iter().enumerate() {
+            let point = PoincareDiskPoint::from_polar(0.8, i as f64 * std::f64::consts::FRAC_PI_4).unwrap();
+            controller.add_node(id.clone(), RoutingCoordinate::new(point, 0));
+        }
+        // "b3" is isolated from the rest of "b" as well
+        let b: Vec<NodeId> = (0..4).map(|i| NodeId::new(format!("b{}", i))).collect();
+        for (id, (x, y)) in b.iter().zip([(0.0, 0.0), (0.1, 0.0), (0.0, 0.1), (-0.1, 0.0)]) {
+            controller.add_node(id.clone(), RoutingCoordinate::new(PoincareDiskPoint::new(x, y).unwrap(), 0));
+        }
+        let link = |controller: &mut RendezvousController, u: &NodeId, v: &NodeId| {
+            controller.add_edge(u, v);
+            controller.add_edge(v, u);
+        };
+        for i in 0..8 {
+            link(&mut controller, &a[i], &a[(i + 1) % 8]);
+        }
+        link(&mut controller, &b[0], &b[1]);
+        link(&mut controller, &b[0], &b[2]);
+
+        let home = controller.registry().find_home_node(&b[1]).unwrap();
+        let temporary = controller.register_node_to_home(&b[1], 0).unwrap();
+        assert!(a.contains(&home) && b.contains(&temporary));
+        assert!(controller.registry().lookup_registration(&b[1], 1).is_none());
+
+        let deliver = |controller: &RendezvousController, src: &NodeId, dest: &NodeId| {
+            let mut packet = RendezvousPacket::new(src.clone(), dest.clone(), 10, vec![]);
+            let mut current = src.clone();
+            for _ in 0..10 {
+                match controller.route_packet(&mut packet, &current, 1) {
+                    RendezvousRoutingResult::Forward { next_hop, .. } => current = next_hop,
+                    other => return other,
+                }
+            }
+            RendezvousRoutingResult::Failed { reason: "hop limit".to_string() }
+        };
+        assert!(matches!(deliver(&controller, &b[2], &b[1]), RendezvousRoutingResult::Delivered));
+        // The temporary record is out of reach from the isolated node
+        match deliver(&controller, &b[3], &b[1]) {
+            RendezvousRoutingResult::Failed { reason } => assert!(reason.contains("unreachable")),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // Nothing merges until the partition heals
+        assert!(controller.reconcile_after_healing().is_empty());
+        link(&mut controller, &a[0], &b[1]);
+        assert_eq!(controller.reconcile_after_healing(), vec![(b[1].clone(), home)]);
+        assert!(controller.registry().lookup_registration(&b[1], 1).is_some());
+        assert!(controller.registry().temporary_registrations().is_empty());
+    }
+
+    #[test]
+    fn test_placement_redirects_and_rebalances() {
+        use crate::record_placement::MigrationReason;
+
+        let mut controller = RendezvousController::new(1000, 100);
+        let ids: Vec<NodeId> = (0..8).map(|i| NodeId::new(format!("n{}", i))).collect();
+        for (i, id) in ids.iter().enumerate() {
+            let point = PoincareDiskPoint::from_polar(0.8, i as f64 * std::f64::consts::FRAC_PI_4).unwrap();
+            controller.add_node(id.clone(), RoutingCoordinate::new(point, 0));
+        }
+        for i in 0..8 {
+            controller.add_edge(&ids[i], &ids[(i + 1) % 8]);
+            controller.add_edge(&ids[(i + 1) % 8], &ids[i]);
+        }
+        controller.enable_placement(PlacementConfig { replicas: 1, ..Default::default() });
+
+        let dest = ids[0].clone();
+        let home = controller.registry().find_home_node(&dest).unwrap();
+        controller.set_node_load(home.clone(), NodeLoad { utilization: 0.95, ..Default::default() });
+        let primary = controller.register_node_to_home(&dest, 0).unwrap();
+        assert_ne!(primary, home);
+        assert_eq!(controller.home_of(&dest), Some(primary.clone()));
+
+        let deliver = |controller: &RendezvousController, src: &NodeId| {
+            let mut packet = RendezvousPacket::new(src.clone(), dest.clone(), 20, vec![]);
+            let mut current = src.clone();
+            for _ in 0..20 {
+                match controller.route_packet(&mut packet, &current, 1) {
+                    RendezvousRoutingResult::Forward { next_hop, .. } => current = next_hop,
+                    other => return other,
+                }
+            }
+            RendezvousRoutingResult::Failed { reason: "hop limit".to_string() }
+        };
+        let src = ids.iter().find(|id| **id != dest && **id != primary).unwrap();
+        assert!(matches!(deliver(&controller, src), RendezvousRoutingResult::Delivered));
+
+        // The primary becomes overloaded; rebalancing moves the record away
+        assert!(controller.rebalance(1).is_empty());
+        controller.set_node_load(home.clone(), NodeLoad::default());
+        controller.set_node_load(primary.clone(), NodeLoad { utilization: 1.0, ..Default::default() });
+        let moved = controller.rebalance(1);
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].reason, MigrationReason::Overloaded);
+        assert_eq!(moved[0].to, home);
+        assert_eq!(controller.home_of(&dest), Some(home));
+        assert!(controller.placement().unwrap().check_consistency(&dest, 0).is_empty());
+        assert!(matches!(deliver(&controller, src), RendezvousRoutingResult::Delivered));
+    }
+
     #[test]
     fn test_rendezvous_packet_creation() {
         let packet = RendezvousPacket::new(
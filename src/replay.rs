@@ -0,0 +1,296 @@
+//! Replay Protection for Control Packets
+//!
+//! Heartbeat, Discovery and CoordinateUpdate packets carry a per-sender
+//! sequence number and their creation timestamp. A receiver accepts a packet
+//! only if its timestamp is inside the freshness window and its sequence
+//! number was not seen before; a sliding bitmap per sender (as in IPsec
+//! anti-replay) tolerates UDP reordering without accepting duplicates.
+//!
+//! Each sender numbers its control packets from its own counter, one apart,
+//! so packets sent milliseconds apart stay well inside the receiver's
+//! window when they are reordered. The counter starts at the clock in
+//! microseconds, so a restarted node continues above the numbers it used
+//! before and is not mistaken for a replayer.
+//!
+//! Packets whose signature was verified and packets from senders without a
+//! known key are tracked in separate windows: anyone can forge the latter,
+//! so a forged high sequence number only moves the unverified window and
+//! never pushes the verified one past the sender's real packets.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use crate::coordinates::NodeId;
+
+/// Control-packet sequence numbers of one sender
+#[derive(Debug)]
+pub struct ControlSequence {
+    next: AtomicU64,
+}
+
+impl ControlSequence {
+    pub fn new() -> Self {
+        let now_us = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or(0);
+        Self { next: AtomicU64::new(now_us.max(1)) }
+    }
+
+    /// Next sequence number (one above the last, never 0)
+    pub fn next(&self) -> u64 {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+impl Default for ControlSequence {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static CONTROL_SEQUENCE: OnceLock<ControlSequence> = OnceLock::new();
+
+/// Next sequence number from a process-wide counter
+///
+/// Stamped on control packets when they are built; the network layer
+/// renumbers them from the sending node's own counter on the way out.
+pub fn next_control_sequence() -> u64 {
+    CONTROL_SEQUENCE.get_or_init(ControlSequence::new).next()
+}
+
+/// Replay check settings
+#[derive(Debug, Clone)]
+pub struct ReplayConfig {
+    /// Packets older than this are rejected
+    pub max_age_ms: u64,
+    /// Packets timestamped further ahead than this are rejected
+    pub max_future_ms: u64,
+    /// Sequence numbers this far below the highest seen are still accepted once
+    pub window: u64,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        Self {
+            max_age_ms: 30_000,
+            max_future_ms: 5_000,
+            window: 64,
+        }
+    }
+}
+
+/// Why a packet was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayRejection {
+    /// No sequence number
+    Unsequenced,
+    /// Timestamp older than the freshness window
+    Stale,
+    /// Timestamp too far in the future
+    Future,
+    /// Sequence number already accepted
+    Duplicate,
+    /// Sequence number below the sliding window
+    OutsideWindow,
+    /// Not signed by the sender's known identity key
+    BadSignature,
+}
+
+/// Rejection counters
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayStats {
+    pub accepted: u64,
+    pub unsequenced: u64,
+    pub stale: u64,
+    pub future: u64,
+    pub duplicate: u64,
+    pub outside_window: u64,
+    pub bad_signature: u64,
+}
+
+impl ReplayStats {
+    /// All rejected packets
+    pub fn rejected(&self) -> u64 {
+        self.unsequenced + self.stale + self.future + self.duplicate + self.outside_window + self.bad_signature
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct SenderWindow {
+    highest: u64,
+    /// Bit i set: `highest - i` was accepted
+    seen: u128,
+}
+
+/// Per-sender replay cache
+#[derive(Debug, Clone, Default)]
+pub struct ReplayGuard {
+    pub config: ReplayConfig,
+    senders: HashMap<NodeId, SenderWindow>,
+    unverified: HashMap<NodeId, SenderWindow>,
+    stats: ReplayStats,
+    rejections_by_sender: HashMap<NodeId, u64>,
+}
+
+impl ReplayGuard {
+    pub fn new(config: ReplayConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Check a signature-verified packet from `sender` and record it if accepted
+    pub fn check(&mut self, sender: &NodeId, sequence: u64, timestamp_ms: u64, now_ms: u64) -> Result<(), ReplayRejection> {
+        let result = self.evaluate(true, sender, sequence, timestamp_ms, now_ms);
+        self.count(sender, result)
+    }
+
+    /// Check a packet whose signature could not be verified
+    ///
+    /// Uses its own window per sender, so it never moves the window of the
+    /// sender's verified packets.
+    pub fn check_unverified(&mut self, sender: &NodeId, sequence: u64, timestamp_ms: u64, now_ms: u64) -> Result<(), ReplayRejection> {
+        let result = self.evaluate(false, sender, sequence, timestamp_ms, now_ms);
+        self.count(sender, result)
+    }
+
+    fn count(&mut self, sender: &NodeId, result: Result<(), ReplayRejection>) -> Result<(), ReplayRejection> {
+        match result {
+            Ok(()) => self.stats.accepted += 1,
+            Err(reason) => self.reject(sender, reason),
+        }
+        result
+    }
+
+    /// Count a packet from `sender` rejected before its sequence was checked
+    pub fn reject(&mut self, sender: &NodeId, reason: ReplayRejection) {
+        let counter = match reason {
+            ReplayRejection::Unsequenced => &mut self.stats.unsequenced,
+            ReplayRejection::Stale => &mut self.stats.stale,
+            ReplayRejection::Future => &mut self.stats.future,
+            ReplayRejection::Duplicate => &mut self.stats.duplicate,
+            ReplayRejection::OutsideWindow => &mut self.stats.outside_window,
+            ReplayRejection::BadSignature => &mut self.stats.bad_signature,
+        };
+        *counter += 1;
+        *self.rejections_by_sender.entry(sender.clone()).or_default() += 1;
+    }
+
+    fn evaluate(&mut self, verified: bool, sender: &NodeId, sequence: u64, timestamp_ms: u64, now_ms: u64) -> Result<(), ReplayRejection> {
+        if sequence == 0 {
+            return Err(ReplayRejection::Unsequenced);
+        }
+        if now_ms.saturating_sub(timestamp_ms) > self.config.max_age_ms {
+            return Err(ReplayRejection::Stale);
+        }
+        if timestamp_ms.saturating_sub(now_ms) > self.config.max_future_ms {
+            return Err(ReplayRejection::Future);
+        }
+
+        let window = self.config.window.min(128);
+        let windows = if verified { &mut self.senders } else { &mut self.unverified };
+        let state = windows.entry(sender.clone()).or_default();
+        if sequence > state.highest {
+            let shift = sequence - state.highest;
+            state.seen = if shift >= 128 { 0 } else { state.seen << shift };
+            state.seen |= 1;
+            state.highest = sequence;
+            return Ok(());
+        }
+        let offset = state.highest - sequence;
+        if offset >= window {
+            return Err(ReplayRejection::OutsideWindow);
+        }
+        if state.seen & (1 << offset) != 0 {
+            return Err(ReplayRejection::Duplicate);
+        }
+        state.seen |= 1 << offset;
+        Ok(())
+    }
+
+    /// Counters since creation
+    pub fn stats(&self) -> &ReplayStats {
+        &self.stats
+    }
+
+    /// Rejected packets per claimed sender
+    pub fn rejections_by_sender(&self) -> &HashMap<NodeId, u64> {
+        &self.rejections_by_sender
+    }
+
+    /// Forget a sender (e.g. after it was removed as a neighbor)
+    pub fn forget(&mut self, sender: &NodeId) {
+        self.senders.remove(sender);
+        self.unverified.remove(sender);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reordering_accepted_duplicates_rejected() {
+        let mut guard = ReplayGuard::default();
+        let peer = NodeId::new("peer");
+
+        assert_eq!(guard.check(&peer, 10, 1000, 1000), Ok(()));
+        assert_eq!(guard.check(&peer, 12, 1000, 1000), Ok(()));
+        // Late but unseen: accepted once
+        assert_eq!(guard.check(&peer, 11, 1000, 1000), Ok(()));
+        assert_eq!(guard.check(&peer, 11, 1000, 1000), Err(ReplayRejection::Duplicate));
+        assert_eq!(guard.check(&peer, 12, 1000, 1000), Err(ReplayRejection::Duplicate));
+
+        assert_eq!(guard.check(&peer, 500, 1000, 1000), Ok(()));
+        assert_eq!(guard.check(&peer, 13, 1000, 1000), Err(ReplayRejection::OutsideWindow));
+        assert_eq!(guard.check(&peer, 0, 1000, 1000), Err(ReplayRejection::Unsequenced));
+
+        // Senders have independent windows
+        assert_eq!(guard.check(&NodeId::new("other"), 11, 1000, 1000), Ok(()));
+        assert_eq!(guard.stats().accepted, 5);
+        assert_eq!(guard.stats().rejected(), 4);
+        assert_eq!(guard.rejections_by_sender()[&peer], 4);
+    }
+
+    #[test]
+    fn test_unverified_sequence_cannot_move_verified_window() {
+        let mut guard = ReplayGuard::default();
+        let peer = NodeId::new("peer");
+
+        assert_eq!(guard.check(&peer, 10, 1000, 1000), Ok(()));
+        // A forged packet claiming a far higher sequence number
+        assert_eq!(guard.check_unverified(&peer, 1_000_000, 1000, 1000), Ok(()));
+        assert_eq!(guard.check_unverified(&peer, 1_000_000, 1000, 1000), Err(ReplayRejection::Duplicate));
+        assert_eq!(guard.check_unverified(&peer, 11, 1000, 1000), Err(ReplayRejection::OutsideWindow));
+
+        // The sender's verified packets continue where they were
+        assert_eq!(guard.check(&peer, 11, 1000, 1000), Ok(()));
+        assert_eq!(guard.check(&peer, 10, 1000, 1000), Err(ReplayRejection::Duplicate));
+
+        guard.forget(&peer);
+        assert_eq!(guard.check_unverified(&peer, 11, 1000, 1000), Ok(()));
+    }
+
+    #[test]
+    fn test_timestamp_window() {
+        let mut guard = ReplayGuard::default();
+        let peer = NodeId::new("peer");
+        let now = 100_000;
+
+        assert_eq!(guard.check(&peer, 1, now - 31_000, now), Err(ReplayRejection::Stale));
+        assert_eq!(guard.check(&peer, 2, now + 6_000, now), Err(ReplayRejection::Future));
+        assert_eq!(guard.check(&peer, 3, now - 29_000, now), Ok(()));
+        assert_eq!(guard.stats().stale, 1);
+        assert_eq!(guard.stats().future, 1);
+
+        let a = next_control_sequence();
+        let b = next_control_sequence();
+        assert!(b > a && a > 0);
+        let sequence = ControlSequence::new();
+        let first = sequence.next();
+        assert_eq!(sequence.next(), first + 1);
+    }
+}
@@ -0,0 +1,476 @@
+//! Neighbor Reputation for DRFE-R
+//!
+//! Tracks how each neighbor treats the traffic we hand it: whether forwarded
+//! packets are acknowledged downstream, whether its coordinate updates
+//! verify, and whether it drops our packets. Evidence is kept as decaying
+//! good/bad counts (beta reputation), so old behavior fades and an idle
+//! neighbor drifts back toward the neutral prior.
+//!
+//! Scores feed next-hop tie-breaking in `GPRouter` and can be exchanged as
+//! signed reports between trusted peers, so a black-hole node observed by one
+//! peer is avoided by the others before they lose traffic to it themselves.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use ed25519_dalek::SigningKey;
+
+use crate::byzantine::ValidationResult;
+use crate::coordinates::NodeId;
+
+/// Observed neighbor behavior
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReputationEvent {
+    /// A packet we forwarded through the neighbor was acknowledged downstream
+    Acknowledged,
+    /// A packet we forwarded through the neighbor was never acknowledged
+    Dropped,
+    /// The neighbor's coordinate update passed validation
+    CoordinateVerified,
+    /// The neighbor's coordinate update was rejected
+    CoordinateRejected,
+}
+
+/// Reputation tracker settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReputationConfig {
+    /// When off, nothing is tracked, exchanged or used for tie-breaking
+    pub enabled: bool,
+    /// Time for accumulated evidence to lose half its weight
+    pub half_life_ms: u64,
+    /// Time to wait for a downstream acknowledgment before counting a drop
+    pub ack_timeout_ms: u64,
+    /// How often signed reports are sent to trusted neighbors
+    pub report_interval_ms: u64,
+    /// Weight of second-hand evidence relative to our own observations
+    pub exchange_weight: f64,
+    /// Reports are only sent to and merged from peers at or above this score
+    pub trusted_threshold: f64,
+    /// Maximum evidence a single report entry may contribute
+    pub max_report_evidence: f64,
+}
+
+impl Default for ReputationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            half_life_ms: 300_000,
+            ack_timeout_ms: 5000,
+            report_interval_ms: 30_000,
+            exchange_weight: 0.3,
+            trusted_threshold: 0.7,
+            max_report_evidence: 20.0,
+        }
+    }
+}
+
+impl ReputationConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.half_life_ms == 0 || self.ack_timeout_ms == 0 || self.report_interval_ms == 0 {
+            return Err("reputation half_life_ms, ack_timeout_ms and report_interval_ms must be positive".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.exchange_weight) || !(0.0..=1.0).contains(&self.trusted_threshold) {
+            return Err("reputation exchange_weight and trusted_threshold must be in [0, 1]".to_string());
+        }
+        if self.max_report_evidence.is_nan() || self.max_report_evidence < 0.0 {
+            return Err("reputation max_report_evidence must be non-negative".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Decaying evidence about one neighbor
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NeighborReputation {
+    /// Weighted positive observations
+    pub good: f64,
+    /// Weighted negative observations
+    pub bad: f64,
+    /// Time of the last decay step in milliseconds
+    pub updated_at_ms: u64,
+}
+
+impl NeighborReputation {
+    fn new(now_ms: u64) -> Self {
+        Self {
+            good: 0.0,
+            bad: 0.0,
+            updated_at_ms: now_ms,
+        }
+    }
+
+    /// Expected probability of good behavior: (good + 1) / (good + bad + 2)
+    pub fn score(&self) -> f64 {
+        (self.good + 1.0) / (self.good + self.bad + 2.0)
+    }
+
+    /// Total evidence weight
+    pub fn evidence(&self) -> f64 {
+        self.good + self.bad
+    }
+
+    fn decay(&mut self, now_ms: u64, half_life_ms: u64) {
+        if now_ms <= self.updated_at_ms || half_life_ms == 0 {
+            return;
+        }
+        let factor = 0.5f64.powf((now_ms - self.updated_at_ms) as f64 / half_life_ms as f64);
+        self.good *= factor;
+        self.bad *= factor;
+        self.updated_at_ms = now_ms;
+    }
+}
+
+/// One subject's entry in an exchanged report
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReputationEntry {
+    pub node_id: NodeId,
+    pub score: f64,
+    /// Evidence behind the score, so thin opinions carry little weight
+    pub evidence: f64,
+}
+
+/// Signed reputation report exchanged between peers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReputationReport {
+    pub reporter: NodeId,
+    pub entries: Vec<ReputationEntry>,
+    pub timestamp_ms: u64,
+    /// Ed25519 signature over the report without this field
+    pub signature: Option<Vec<u8>>,
+}
+
+impl ReputationReport {
+    /// Sign the report with a 32-byte Ed25519 private key
+    pub fn sign(&mut self, private_key: &[u8]) -> Result<(), String> {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let key: [u8; 32] = private_key
+            .try_into()
+            .map_err(|_| format!("Invalid private key length: {} (expected 32 bytes)", private_key.len()))?;
+        let message = self.signing_bytes()?;
+        self.signature = Some(SigningKey::from_bytes(&key).sign(&message).to_bytes().to_vec());
+        Ok(())
+    }
+
+    /// Verify the report signature with a 32-byte Ed25519 public key
+    pub fn verify_signature(&self, public_key: &[u8]) -> bool {
+        use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+        let (Some(signature), Ok(key)) = (&self.signature, <[u8; 32]>::try_from(public_key)) else {
+            return false;
+        };
+        let (Ok(key), Ok(signature), Ok(message)) = (
+            VerifyingKey::from_bytes(&key),
+            Signature::from_slice(signature),
+            self.signing_bytes(),
+        ) else {
+            return false;
+        };
+        key.verify(&message, &signature).is_ok()
+    }
+
+    fn signing_bytes(&self) -> Result<Vec<u8>, String> {
+        let mut unsigned = self.clone();
+        unsigned.signature = None;
+        rmp_serde::to_vec(&unsigned).map_err(|e| format!("Failed to serialize report for signing: {}", e))
+    }
+}
+
+/// Per-neighbor reputation tracker
+pub struct ReputationTracker {
+    pub config: ReputationConfig,
+    reputations: RwLock<HashMap<NodeId, NeighborReputation>>,
+    /// Forwarded packets awaiting acknowledgment: packet ID -> (next hop, sent at)
+    pending: RwLock<HashMap<String, (NodeId, u64)>>,
+    /// Key our own reports are signed with; none are sent without one
+    signing_key: Option<SigningKey>,
+    last_report_ms: Option<u64>,
+}
+
+impl ReputationTracker {
+    pub fn new(config: ReputationConfig) -> Self {
+        Self {
+            config,
+            reputations: RwLock::new(HashMap::new()),
+            pending: RwLock::new(HashMap::new()),
+            signing_key: None,
+            last_report_ms: None,
+        }
+    }
+
+    /// Replace the settings; evidence gathered so far is kept
+    pub fn set_config(&mut self, config: ReputationConfig) {
+        self.config = config;
+    }
+
+    /// Sign our reports with `key` from now on
+    pub fn set_signing_key(&mut self, key: SigningKey) {
+        self.signing_key = Some(key);
+    }
+
+    /// Record an observation about a neighbor
+    pub fn record(&self, neighbor: &NodeId, event: ReputationEvent) {
+        self.record_at(neighbor, event, Self::now_ms());
+    }
+
+    /// Record an observation about a neighbor at a given time
+    pub fn record_at(&self, neighbor: &NodeId, event: ReputationEvent, now_ms: u64) {
+        let (good, bad) = match event {
+            ReputationEvent::Acknowledged => (1.0, 0.0),
+            ReputationEvent::Dropped => (0.0, 1.0),
+            ReputationEvent::CoordinateVerified => (0.5, 0.0),
+            ReputationEvent::CoordinateRejected => (0.0, 2.0),
+        };
+        self.add_evidence(neighbor, good, bad, now_ms);
+    }
+
+    /// Record the outcome of validating a neighbor's coordinate update
+    ///
+    /// Results without enough data to judge are ignored.
+    pub fn record_validation(&self, neighbor: &NodeId, result: &ValidationResult) {
+        match result {
+            ValidationResult::Valid { .. } => self.record(neighbor, ReputationEvent::CoordinateVerified),
+            ValidationResult::Invalid { .. } | ValidationResult::SuspiciousMovement { .. } => {
+                self.record(neighbor, ReputationEvent::CoordinateRejected)
+            }
+            ValidationResult::InsufficientData { .. } | ValidationResult::InsufficientTrust => {}
+        }
+    }
+
+    /// Note that a packet was handed to `next_hop` and should be acknowledged
+    pub fn expect_ack(&self, packet_id: &str, next_hop: &NodeId) {
+        self.expect_ack_at(packet_id, next_hop, Self::now_ms());
+    }
+
+    /// Note a forwarded packet at a given time
+    pub fn expect_ack_at(&self, packet_id: &str, next_hop: &NodeId, now_ms: u64) {
+        self.pending
+            .write()
+            .unwrap()
+            .insert(packet_id.to_string(), (next_hop.clone(), now_ms));
+    }
+
+    /// Credit the neighbor a packet was forwarded through once it is acknowledged
+    ///
+    /// Returns the credited neighbor, or None for unknown or expired packets.
+    pub fn acknowledge(&self, packet_id: &str) -> Option<NodeId> {
+        let (next_hop, _) = self.pending.write().unwrap().remove(packet_id)?;
+        self.record(&next_hop, ReputationEvent::Acknowledged);
+        Some(next_hop)
+    }
+
+    /// Count every pending packet older than the ack timeout as a drop
+    ///
+    /// Returns the neighbors blamed, one entry per dropped packet.
+    pub fn expire_pending(&self, now_ms: u64) -> Vec<NodeId> {
+        let timeout = self.config.ack_timeout_ms;
+        let mut expired = Vec::new();
+        self.pending.write().unwrap().retain(|_, (next_hop, sent_at)| {
+            if now_ms.saturating_sub(*sent_at) > timeout {
+                expired.push(next_hop.clone());
+                false
+            } else {
+                true
+            }
+        });
+        for neighbor in &expired {
+            self.record_at(neighbor, ReputationEvent::Dropped, now_ms);
+        }
+        expired
+    }
+
+    /// Current score of a neighbor (0.5 if unknown)
+    pub fn score(&self, neighbor: &NodeId) -> f64 {
+        self.score_at(neighbor, Self::now_ms())
+    }
+
+    /// Score of a neighbor at a given time
+    pub fn score_at(&self, neighbor: &NodeId, now_ms: u64) -> f64 {
+        self.reputations.read().unwrap().get(neighbor).map_or(0.5, |r| {
+            let mut r = *r;
+            r.decay(now_ms, self.config.half_life_ms);
+            r.score()
+        })
+    }
+
+    /// Get full reputation data
+    pub fn get_reputation(&self, neighbor: &NodeId) -> Option<NeighborReputation> {
+        self.reputations.read().unwrap().get(neighbor).copied()
+    }
+
+    /// Scores of all tracked neighbors, for `GPRouter::set_reputation_scores`
+    pub fn scores_at(&self, now_ms: u64) -> HashMap<NodeId, f64> {
+        self.reputations
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(id, r)| {
+                let mut r = *r;
+                r.decay(now_ms, self.config.half_life_ms);
+                (id.clone(), r.score())
+            })
+            .collect()
+    }
+
+    /// Build an unsigned report of our own first-hand observations
+    pub fn export_report(&self, reporter: &NodeId, now_ms: u64) -> ReputationReport {
+        let reputations = self.reputations.read().unwrap();
+        let mut entries: Vec<ReputationEntry> = reputations
+            .iter()
+            .map(|(id, r)| {
+                let mut r = *r;
+                r.decay(now_ms, self.config.half_life_ms);
+                ReputationEntry {
+                    node_id: id.clone(),
+                    score: r.score(),
+                    evidence: r.evidence(),
+                }
+            })
+            .filter(|e| e.evidence > 0.0)
+            .collect();
+        entries.sort_by(|a, b| a.node_id.0.cmp(&b.node_id.0));
+
+        ReputationReport {
+            reporter: reporter.clone(),
+            entries,
+            timestamp_ms: now_ms,
+            signature: None,
+        }
+    }
+
+    /// Our signed report, if one is due and we have a signing key
+    ///
+    /// Starts a new report interval whenever it returns a report.
+    pub fn signed_report(&mut self, reporter: &NodeId, now_ms: u64) -> Option<ReputationReport> {
+        let key = self.signing_key.as_ref()?;
+        if self
+            .last_report_ms
+            .is_some_and(|last| now_ms.saturating_sub(last) < self.config.report_interval_ms)
+        {
+            return None;
+        }
+        let mut report = self.export_report(reporter, now_ms);
+        report.sign(key.as_bytes()).ok()?;
+        self.last_report_ms = Some(now_ms);
+        Some(report)
+    }
+
+    /// Merge a peer's signed report as second-hand evidence
+    ///
+    /// The report must verify against the reporter's public key and the
+    /// reporter must itself be trusted. Each entry contributes at most
+    /// `max_report_evidence`, scaled by `exchange_weight` and the reporter's
+    /// score. Entries about the reporter itself are ignored.
+    ///
+    /// Returns the number of entries merged.
+    pub fn merge_report(&self, report: &ReputationReport, reporter_public_key: &[u8], now_ms: u64) -> Result<usize, String> {
+        if !report.verify_signature(reporter_public_key) {
+            return Err(format!("Invalid signature on report from {}", report.reporter));
+        }
+        let reporter_score = self.score_at(&report.reporter, now_ms);
+        if reporter_score < self.config.trusted_threshold {
+            return Err(format!(
+                "Reporter {} is not trusted (score {:.2})",
+                report.reporter, reporter_score
+            ));
+        }
+
+        let weight = self.config.exchange_weight * reporter_score;
+        let mut merged = 0;
+        for entry in &report.entries {
+            if entry.node_id == report.reporter || !(0.0..=1.0).contains(&entry.score) {
+                continue;
+            }
+            let evidence = entry.evidence.clamp(0.0, self.config.max_report_evidence) * weight;
+            self.add_evidence(&entry.node_id, evidence * entry.score, evidence * (1.0 - entry.score), now_ms);
+            merged += 1;
+        }
+        Ok(merged)
+    }
+
+    /// Forget a neighbor
+    pub fn remove(&self, neighbor: &NodeId) {
+        self.reputations.write().unwrap().remove(neighbor);
+    }
+
+    fn add_evidence(&self, neighbor: &NodeId, good: f64, bad: f64, now_ms: u64) {
+        let mut reputations = self.reputations.write().unwrap();
+        let r = reputations
+            .entry(neighbor.clone())
+            .or_insert_with(|| NeighborReputation::new(now_ms));
+        r.decay(now_ms, self.config.half_life_ms);
+        r.good += good;
+        r.bad += bad;
+    }
+
+    fn now_ms() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64
+    }
+}
+
+impl Default for ReputationTracker {
+    fn default() -> Self {
+        Self::new(ReputationConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drops_lower_score_and_decay() {
+        let tracker = ReputationTracker::default();
+        let good = NodeId::new("good");
+        let bad = NodeId::new("bad");
+
+        for i in 0..10 {
+            tracker.expect_ack_at(&format!("g{}", i), &good, 0);
+            tracker.expect_ack_at(&format!("b{}", i), &bad, 0);
+            assert_eq!(tracker.acknowledge(&format!("g{}", i)), Some(good.clone()));
+        }
+        assert_eq!(tracker.expire_pending(10_000).len(), 10);
+
+        assert!(tracker.score_at(&good, 10_000) > 0.9);
+        assert!(tracker.score_at(&bad, 10_000) < 0.1);
+
+        // Evidence fades toward the neutral prior
+        let later = 10_000 + 10 * tracker.config.half_life_ms;
+        assert!((tracker.score_at(&bad, later) - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_signed_report_exchange() {
+        let mut rng = rand::thread_rng();
+        let key = SigningKey::from_bytes(&rand::Rng::gen(&mut rng));
+        let peer = NodeId::new("peer");
+        let black_hole = NodeId::new("black_hole");
+
+        let peer_tracker = ReputationTracker::default();
+        for _ in 0..10 {
+            peer_tracker.record_at(&black_hole, ReputationEvent::Dropped, 0);
+        }
+        let mut report = peer_tracker.export_report(&peer, 0);
+
+        let tracker = ReputationTracker::default();
+        // Unsigned and untrusted reports are rejected
+        assert!(tracker.merge_report(&report, key.verifying_key().as_bytes(), 0).is_err());
+        report.sign(key.as_bytes()).unwrap();
+        assert!(tracker.merge_report(&report, key.verifying_key().as_bytes(), 0).is_err());
+
+        for _ in 0..10 {
+            tracker.record_at(&peer, ReputationEvent::Acknowledged, 0);
+        }
+        assert_eq!(tracker.merge_report(&report, key.verifying_key().as_bytes(), 0), Ok(1));
+        assert!(tracker.score_at(&black_hole, 0) < 0.3);
+
+        // Tampering breaks the signature
+        report.entries[0].score = 1.0;
+        assert!(tracker.merge_report(&report, key.verifying_key().as_bytes(), 0).is_err());
+    }
+}
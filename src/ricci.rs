@@ -322,6 +322,129 @@ impl Default for RicciGraph {
     }
 }
 
+/// Objective minimized when optimizing coordinates toward Ricci flow targets
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OptimizationObjective {
+    /// Plain stress: Σ (d - t)² over edges
+    #[default]
+    Stress,
+    /// Stress weighted by 1/t², emphasizing short edges
+    WeightedStress,
+    /// Stress weighted by min(|d - t| / t, 1)^gamma, focusing on badly violated edges
+    Focal { gamma: f64 },
+    /// Σ max(0, d(u,v) + margin - d(u,w)) over neighbors v and two-hop nodes w of u,
+    /// so only pairs whose embedded order disagrees with graph order are penalized
+    Hinge { margin: f64 },
+}
+
+impl OptimizationObjective {
+    /// Weight of an edge term given its actual and target length
+    pub(crate) fn edge_weight(&self, actual: f64, target: f64) -> f64 {
+        match *self {
+            OptimizationObjective::Stress | OptimizationObjective::Hinge { .. } => 1.0,
+            OptimizationObjective::WeightedStress => 1.0 / target.max(0.1).powi(2),
+            OptimizationObjective::Focal { gamma } => {
+                let relative = (actual - target).abs() / target.max(1e-6);
+                relative.min(1.0).powf(gamma)
+            }
+        }
+    }
+}
+
+/// Compute backend used for coordinate optimization
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ComputeBackend {
+    /// Per-node hash map updates
+    #[default]
+    Scalar,
+    /// Vectorized update over ndarray coordinate matrices (`array-backend` feature).
+    /// Falls back to `Scalar` when the feature is disabled or the objective
+    /// is not supported by the array kernels.
+    Array,
+}
+
+/// (u, v, w) triples where v is a neighbor of u and w is two hops from u
+fn two_hop_triples(graph: &RicciGraph) -> Vec<(NodeId, NodeId, NodeId)> {
+    let mut triples = Vec::new();
+    for (u, node) in &graph.nodes {
+        for v in &node.neighbors {
+            let Some(v_node) = graph.nodes.get(v) else { continue };
+            for w in &v_node.neighbors {
+                if w != u && !node.neighbors.contains(w) {
+                    triples.push((u.clone(), v.clone(), w.clone()));
+                }
+            }
+        }
+    }
+    triples
+}
+
+/// Euclidean gradient on u for a pair force of signed magnitude `stress` between u and v.
+/// Positive stress pulls u toward v.
+pub(crate) fn pair_gradient(u: (f64, f64), v: (f64, f64), stress: f64, step_size: f64) -> (f64, f64) {
+    let (ux, uy) = u;
+    let (vx, vy) = v;
+
+    // Euclidean direction from u to v
+    let dx = vx - ux;
+    let dy = vy - uy;
+    let eucl_dist = (dx * dx + dy * dy).sqrt().max(1e-10);
+
+    // Derivative of hyperbolic distance w.r.t. Euclidean position
+    // d(d_H)/d(z_u) ≈ -2 / ((1-|u|²)(1-|v|²)) * (v-u) / |v-u|
+    // Simplified: gradient points toward/away from other node
+    let r_u_sq = ux * ux + uy * uy;
+    let r_v_sq = vx * vx + vy * vy;
+
+    // Conformal factor at each point
+    let conf_u = 2.0 / (1.0 - r_u_sq).max(0.01);
+    let conf_v = 2.0 / (1.0 - r_v_sq).max(0.01);
+
+    // Gradient magnitude combines stress and metric
+    let grad_magnitude = stress * conf_u * conf_v / eucl_dist * step_size;
+
+    (dx / eucl_dist * grad_magnitude, dy / eucl_dist * grad_magnitude)
+}
+
+/// Simulated annealing schedule for escaping poor local minima
+///
+/// Every `perturbation_interval` flow iterations all coordinates are kicked by a
+/// random offset. The perturbed state is kept if stress improves, or otherwise
+/// with Metropolis probability exp(-Δstress / T); the temperature T cools
+/// geometrically after each perturbation.
+#[derive(Debug, Clone)]
+pub struct AnnealingSchedule {
+    /// Initial temperature
+    pub initial_temperature: f64,
+    /// Multiplicative cooling factor applied after each perturbation
+    pub cooling_rate: f64,
+    /// Flow iterations between perturbations
+    pub perturbation_interval: usize,
+    /// Maximum Euclidean offset of a perturbation at T = initial_temperature
+    pub perturbation_scale: f64,
+    /// RNG seed for reproducible runs
+    pub seed: u64,
+}
+
+impl Default for AnnealingSchedule {
+    fn default() -> Self {
+        Self {
+            initial_temperature: 1.0,
+            cooling_rate: 0.8,
+            perturbation_interval: 5,
+            perturbation_scale: 0.2,
+            seed: 42,
+        }
+    }
+}
+
+impl AnnealingSchedule {
+    /// Temperature after `k` perturbations
+    pub fn temperature(&self, k: usize) -> f64 {
+        self.initial_temperature * self.cooling_rate.powi(k as i32)
+    }
+}
+
 /// Ricci Flow controller for coordinate updates
 pub struct RicciFlow {
     /// Step size for coordinate updates
@@ -330,6 +453,10 @@ pub struct RicciFlow {
     pub target_curvature: f64,
     /// Coordinate update step size
     pub coord_step: f64,
+    /// Optional annealing schedule applied during `run_optimization`
+    pub annealing: Option<AnnealingSchedule>,
+    /// Backend used for the coordinate update step
+    pub backend: ComputeBackend,
 }
 
 impl RicciFlow {
@@ -338,9 +465,23 @@ impl RicciFlow {
             step_size,
             target_curvature: 0.0,
             coord_step: 0.1,
+            annealing: None,
+            backend: ComputeBackend::Scalar,
         }
     }
 
+    /// Select the compute backend for coordinate optimization
+    pub fn with_backend(mut self, backend: ComputeBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Enable simulated annealing perturbations
+    pub fn with_annealing(mut self, schedule: AnnealingSchedule) -> Self {
+        self.annealing = Some(schedule);
+        self
+    }
+
     /// Perform one step of Ricci flow, updating edge weights
     /// and returning the new target distances
     pub fn flow_step(&self, graph: &RicciGraph) -> HashMap<Edge, f64> {
@@ -378,8 +519,31 @@ impl RicciFlow {
         graph: &RicciGraph,
         target_lengths: &HashMap<Edge, f64>,
         iterations: usize,
+    ) -> HashMap<NodeId, crate::PoincareDiskPoint> {
+        self.optimize_coordinates_with_objective(
+            graph,
+            target_lengths,
+            iterations,
+            OptimizationObjective::Stress,
+        )
+    }
+
+    /// Optimize coordinates using the given objective
+    pub fn optimize_coordinates_with_objective(
+        &self,
+        graph: &RicciGraph,
+        target_lengths: &HashMap<Edge, f64>,
+        iterations: usize,
+        objective: OptimizationObjective,
     ) -> HashMap<NodeId, crate::PoincareDiskPoint> {
         use crate::PoincareDiskPoint;
+
+        #[cfg(feature = "array-backend")]
+        if self.backend == ComputeBackend::Array {
+            if let Some(result) = crate::array_backend::optimize_coordinates(self, graph, target_lengths, iterations, objective) {
+                return result;
+            }
+        }
         
         // Collect current coordinates
         let mut coords: HashMap<NodeId, (f64, f64)> = HashMap::new();
@@ -387,75 +551,83 @@ impl RicciFlow {
             coords.insert(id.clone(), (node.coord.point.x, node.coord.point.y));
         }
 
+        let triples = match objective {
+            OptimizationObjective::Hinge { .. } => two_hop_triples(graph),
+            _ => Vec::new(),
+        };
+
         let step_size = self.coord_step * 0.5; // Smaller step for stability
 
-        // Riemannian gradient descent to minimize stress
+        let hyperbolic = |a: (f64, f64), b: (f64, f64)| -> Option<f64> {
+            let point_a = PoincareDiskPoint::new(a.0, a.1)?;
+            let point_b = PoincareDiskPoint::new(b.0, b.1)?;
+            Some(point_a.hyperbolic_distance(&point_b))
+        };
+
+        // Riemannian gradient descent to minimize the objective
         for _ in 0..iterations {
             let mut gradients: HashMap<NodeId, (f64, f64)> = HashMap::new();
             for id in coords.keys() {
                 gradients.insert(id.clone(), (0.0, 0.0));
             }
 
-            // Compute gradients from edge stress using hyperbolic distances
-            for (edge, &target_len) in target_lengths {
-                let (ux, uy) = match coords.get(&edge.u) {
-                    Some(&c) => c,
-                    None => continue,
-                };
-                let (vx, vy) = match coords.get(&edge.v) {
-                    Some(&c) => c,
-                    None => continue,
-                };
-
-                // Compute actual hyperbolic distance
-                let point_u = match PoincareDiskPoint::new(ux, uy) {
-                    Some(p) => p,
-                    None => continue,
-                };
-                let point_v = match PoincareDiskPoint::new(vx, vy) {
-                    Some(p) => p,
-                    None => continue,
-                };
-                
-                let current_hyp_dist = point_u.hyperbolic_distance(&point_v);
-                if current_hyp_dist < 1e-10 {
-                    continue;
-                }
+            if let OptimizationObjective::Hinge { margin } = objective {
+                // Pull u toward its neighbor v and push it away from the two-hop node w
+                // whenever w is embedded closer to u than v (plus margin)
+                for (u, v, w) in &triples {
+                    let (Some(&cu), Some(&cv), Some(&cw)) = (coords.get(u), coords.get(v), coords.get(w)) else {
+                        continue;
+                    };
+                    let (Some(d_uv), Some(d_uw)) = (hyperbolic(cu, cv), hyperbolic(cu, cw)) else {
+                        continue;
+                    };
+                    let violation = d_uv + margin - d_uw;
+                    if violation <= 0.0 || d_uv < 1e-10 || d_uw < 1e-10 {
+                        continue;
+                    }
 
-                // Stress: (d_H(u,v) - target)² 
-                // ∂stress/∂z_u in Euclidean coords
-                let stress = current_hyp_dist - target_len;
-                
-                // Euclidean direction from u to v
-                let dx = vx - ux;
-                let dy = vy - uy;
-                let eucl_dist = (dx * dx + dy * dy).sqrt().max(1e-10);
-                
-                // Derivative of hyperbolic distance w.r.t. Euclidean position
-                // d(d_H)/d(z_u) ≈ -2 / ((1-|u|²)(1-|v|²)) * (v-u) / |v-u|
-                // Simplified: gradient points toward/away from other node
-                let r_u_sq = ux * ux + uy * uy;
-                let r_v_sq = vx * vx + vy * vy;
-                
-                // Conformal factor at each point
-                let conf_u = 2.0 / (1.0 - r_u_sq).max(0.01);
-                let conf_v = 2.0 / (1.0 - r_v_sq).max(0.01);
-                
-                // Gradient magnitude combines stress and metric
-                let grad_magnitude = stress * conf_u * conf_v / eucl_dist * step_size;
-                
-                // Euclidean gradient direction
-                let grad_x = dx / eucl_dist * grad_magnitude;
-                let grad_y = dy / eucl_dist * grad_magnitude;
-
-                // Update gradients: u moves toward v if stress > 0 (too far apart)
-                if let Some(g) = gradients.get_mut(&edge.u) {
-                    g.0 += grad_x;
-                    g.1 += grad_y;
+                    let (ax, ay) = pair_gradient(cu, cv, violation, step_size);
+                    let (rx, ry) = pair_gradient(cu, cw, -violation, step_size);
+                    if let Some(g) = gradients.get_mut(u) {
+                        g.0 += ax + rx;
+                        g.1 += ay + ry;
+                    }
+                    if let Some(g) = gradients.get_mut(v) {
+                        g.0 -= ax;
+                        g.1 -= ay;
+                    }
+                    if let Some(g) = gradients.get_mut(w) {
+                        g.0 -= rx;
+                        g.1 -= ry;
+                    }
                 }
-                if let Some(g) = gradients.get_mut(&edge.v) {
-                    g.0 -= grad_x;
-                    g.1 -= grad_y;
+            } else {
+                // Compute gradients from edge stress using hyperbolic distances
+                for (edge, &target_len) in target_lengths {
+                    let (Some(&cu), Some(&cv)) = (coords.get(&edge.u), coords.get(&edge.v)) else {
+                        continue;
+                    };
+                    let Some(current_hyp_dist) = hyperbolic(cu, cv) else {
+                        continue;
+                    };
+                    if current_hyp_dist < 1e-10 {
+                        continue;
+                    }
+
+                    // Stress: w · (d_H(u,v) - target)²
+                    let weight = objective.edge_weight(current_hyp_dist, target_len);
+                    let stress = weight * (current_hyp_dist - target_len);
+                    let (grad_x, grad_y) = pair_gradient(cu, cv, stress, step_size);
+
+                    // Update gradients: u moves toward v if stress > 0 (too far apart)
+                    if let Some(g) = gradients.get_mut(&edge.u) {
+                        g.0 += grad_x;
+                        g.1 += grad_y;
+                    }
+                    if let Some(g) = gradients.get_mut(&edge.v) {
+                        g.0 -= grad_x;
+                        g.1 -= grad_y;
+                    }
                 }
             }
 
@@ -499,6 +671,40 @@ impl RicciFlow {
         result
     }
 
+    /// Value of `objective` for the graph's current coordinates
+    pub fn objective_value(
+        graph: &RicciGraph,
+        target_lengths: &HashMap<Edge, f64>,
+        objective: OptimizationObjective,
+    ) -> f64 {
+        let distance = |a: &NodeId, b: &NodeId| -> Option<f64> {
+            let (u, v) = (graph.get_node(a)?, graph.get_node(b)?);
+            Some(u.coord.point.hyperbolic_distance(&v.coord.point))
+        };
+
+        match objective {
+            OptimizationObjective::Hinge { margin } => two_hop_triples(graph)
+                .iter()
+                .filter_map(|(u, v, w)| Some((distance(u, v)? + margin - distance(u, w)?).max(0.0)))
+                .sum(),
+            _ => target_lengths
+                .iter()
+                .filter_map(|(edge, &target)| {
+                    let actual = distance(&edge.u, &edge.v)?;
+                    Some(objective.edge_weight(actual, target) * (actual - target).powi(2))
+                })
+                .sum(),
+        }
+    }
+
+    /// Move the nodes of `graph` to `points`
+    fn set_points(graph: &mut RicciGraph, points: &HashMap<NodeId, crate::PoincareDiskPoint>) {
+        for (id, point) in points {
+            if let Some(node) = graph.nodes.get_mut(id) {
+                node.coord.point = *point;
+            }
+        }
+    }
 
     /// Run full Ricci Flow optimization: compute target lengths then optimize coords
     pub fn run_optimization(
@@ -507,28 +713,99 @@ impl RicciFlow {
         flow_iterations: usize,
         coord_iterations: usize,
     ) -> f64 {
+        self.run_optimization_with_objective(
+            graph,
+            flow_iterations,
+            coord_iterations,
+            OptimizationObjective::Stress,
+        )
+    }
+
+    /// Run full Ricci Flow optimization minimizing the given objective.
+    /// Returns the final objective value.
+    pub fn run_optimization_with_objective(
+        &self,
+        graph: &mut RicciGraph,
+        flow_iterations: usize,
+        coord_iterations: usize,
+        objective: OptimizationObjective,
+    ) -> f64 {
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = self
+            .annealing
+            .as_ref()
+            .map(|schedule| rand::rngs::StdRng::seed_from_u64(schedule.seed));
+        let mut perturbations = 0;
         let mut total_stress = 0.0;
 
-        for _ in 0..flow_iterations {
+        for iteration in 0..flow_iterations {
+            // 0. Annealing: occasionally perturb all coordinates
+            let snapshot = match (&self.annealing, rng.as_mut()) {
+                (Some(schedule), Some(rng))
+                    if iteration > 0
+                        && schedule.perturbation_interval > 0
+                        && iteration % schedule.perturbation_interval == 0 =>
+                {
+                    let snapshot: HashMap<NodeId, crate::PoincareDiskPoint> = graph
+                        .nodes
+                        .iter()
+                        .map(|(id, node)| (id.clone(), node.coord.point))
+                        .collect();
+                    let temperature = schedule.temperature(perturbations);
+                    let scale = schedule.perturbation_scale * temperature / schedule.initial_temperature.max(1e-12);
+                    let mut ids: Vec<NodeId> = graph.nodes.keys().cloned().collect();
+                    ids.sort_by(|a, b| a.0.cmp(&b.0));
+                    for id in ids {
+                        let node = match graph.nodes.get_mut(&id) {
+                            Some(node) => node,
+                            None => continue,
+                        };
+                        let p = node.coord.point;
+                        let x = p.x + rng.gen_range(-1.0..=1.0) * scale;
+                        let y = p.y + rng.gen_range(-1.0..=1.0) * scale;
+                        let r = (x * x + y * y).sqrt();
+                        let (x, y) = if r >= 0.98 { (x * 0.95 / r, y * 0.95 / r) } else { (x, y) };
+                        if let Some(point) = crate::PoincareDiskPoint::new(x, y) {
+                            node.coord.point = point;
+                        }
+                    }
+                    Some((snapshot, temperature))
+                }
+                _ => None,
+            };
+
             // 1. Compute target edge lengths via Ricci flow
             let target_lengths = self.flow_step(graph);
 
             // 2. Optimize coordinates to match target lengths
-            let new_coords = self.optimize_coordinates(graph, &target_lengths, coord_iterations);
+            let new_coords = self.optimize_coordinates_with_objective(graph, &target_lengths, coord_iterations, objective);
 
             // 3. Update graph coordinates
-            for (id, point) in &new_coords {
-                if let Some(node) = graph.nodes.get_mut(id) {
-                    node.coord.point = *point;
-                }
-            }
-
-            // 4. Compute residual stress
-            total_stress = 0.0;
-            for (edge, &target) in &target_lengths {
-                if let (Some(u), Some(v)) = (graph.get_node(&edge.u), graph.get_node(&edge.v)) {
-                    let actual = u.coord.point.hyperbolic_distance(&v.coord.point);
-                    total_stress += (actual - target).powi(2);
+            Self::set_points(graph, &new_coords);
+
+            // 4. Compute residual objective
+            total_stress = Self::objective_value(graph, &target_lengths, objective);
+
+            // 5. Annealing: Metropolis acceptance of the perturbed state. The
+            // unperturbed state is optimized toward the same target lengths,
+            // so both are scored under one metric and the loser's
+            // optimization is not what the iteration keeps
+            if let (Some((snapshot, temperature)), Some(rng)) = (snapshot, rng.as_mut()) {
+                perturbations += 1;
+                Self::set_points(graph, &snapshot);
+                let kept_coords =
+                    self.optimize_coordinates_with_objective(graph, &target_lengths, coord_iterations, objective);
+                Self::set_points(graph, &kept_coords);
+                let kept_stress = Self::objective_value(graph, &target_lengths, objective);
+
+                let delta = total_stress - kept_stress;
+                let accept = delta <= 0.0
+                    || rng.gen::<f64>() < (-delta / temperature.max(1e-12)).exp();
+                if accept {
+                    Self::set_points(graph, &new_coords);
+                } else {
+                    total_stress = kept_stress;
                 }
             }
         }
@@ -884,4 +1161,51 @@ mod tests {
             assert!(*length > 0.0);
         }
     }
+
+    #[test]
+    fn test_annealing_is_reproducible() {
+        let schedule = AnnealingSchedule {
+            perturbation_interval: 2,
+            ..AnnealingSchedule::default()
+        };
+        let flow = RicciFlow::new(0.1).with_annealing(schedule);
+
+        let mut g1 = create_test_graph();
+        let mut g2 = create_test_graph();
+        let s1 = flow.run_optimization(&mut g1, 6, 5);
+        let s2 = flow.run_optimization(&mut g2, 6, 5);
+
+        assert!(s1.is_finite());
+        assert!((s1 - s2).abs() < 1e-6);
+        for (id, node) in &g1.nodes {
+            assert!(node.coord.point.euclidean_norm() < 1.0);
+            assert!(node.coord.point.hyperbolic_distance(&g2.nodes[id].coord.point) < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_objectives() {
+        let flow = RicciFlow::new(0.1);
+        let objectives = [
+            OptimizationObjective::Stress,
+            OptimizationObjective::WeightedStress,
+            OptimizationObjective::Focal { gamma: 2.0 },
+            OptimizationObjective::Hinge { margin: 0.1 },
+        ];
+
+        for objective in objectives {
+            let mut graph = create_test_graph();
+            let value = flow.run_optimization_with_objective(&mut graph, 3, 10, objective);
+            assert!(value.is_finite() && value >= 0.0, "{:?}: {}", objective, value);
+            for node in graph.nodes.values() {
+                assert!(node.coord.point.euclidean_norm() < 1.0);
+            }
+        }
+
+        // Two-hop nodes are already farther than neighbors; only a large margin is violated
+        let graph = create_test_graph();
+        let hinge = |margin| RicciFlow::objective_value(&graph, &HashMap::new(), OptimizationObjective::Hinge { margin });
+        assert_eq!(hinge(0.0), 0.0);
+        assert!(hinge(5.0) > 0.0);
+    }
 }
@@ -0,0 +1,307 @@
+//! Failure-Scenario Routing Evaluation
+//!
+//! Removes increasing fractions of nodes or edges from an embedded network,
+//! chosen at random or by attack order (degree, betweenness), and re-runs
+//! routing on the damaged topology without re-embedding. Coordinates, tree
+//! information and any TZ table stay as they were computed on the intact
+//! graph, so the resulting curves show how gracefully routing degrades before
+//! the embedding is repaired.
+
+use crate::coordinates::NodeId;
+use crate::graph::CsrGraph;
+use crate::routing::GPRouter;
+use crate::simulation::{BatchConfig, Workload};
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Which elements fail and in what order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FailureMode {
+    /// Uniformly random node failures
+    RandomNodes,
+    /// Uniformly random link failures
+    RandomEdges,
+    /// Highest-degree nodes first
+    HighDegreeNodes,
+    /// Highest-betweenness nodes first
+    HighBetweennessNodes,
+    /// Highest-betweenness links first
+    HighBetweennessEdges,
+}
+
+impl FailureMode {
+    /// Whether this mode removes nodes (as opposed to edges)
+    pub fn removes_nodes(&self) -> bool {
+        !matches!(self, FailureMode::RandomEdges | FailureMode::HighBetweennessEdges)
+    }
+}
+
+/// Failure evaluation settings
+#[derive(Debug, Clone)]
+pub struct RobustnessConfig {
+    /// Failure selection strategy
+    pub mode: FailureMode,
+    /// Fractions of nodes (or edges) to remove, one curve point each
+    pub fractions: Vec<f64>,
+    /// Routing pairs sampled among surviving nodes per point
+    pub pairs_per_step: usize,
+    /// Seed for failure selection and pair sampling
+    pub seed: u64,
+    /// TTL per packet (default: 20 × original node count)
+    pub max_ttl: Option<u32>,
+    /// BFS sources for betweenness ranking (None = exact)
+    pub betweenness_samples: Option<usize>,
+}
+
+impl Default for RobustnessConfig {
+    fn default() -> Self {
+        Self {
+            mode: FailureMode::RandomNodes,
+            fractions: vec![0.0, 0.05, 0.1, 0.2, 0.3],
+            pairs_per_step: 1000,
+            seed: 42,
+            max_ttl: None,
+            betweenness_samples: Some(256),
+        }
+    }
+}
+
+/// Routing quality after one failure step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DegradationPoint {
+    /// Requested failure fraction
+    pub fraction: f64,
+    pub removed_nodes: usize,
+    pub removed_edges: usize,
+    /// Sampled pairs
+    pub pairs: usize,
+    /// Sampled pairs still connected in the damaged graph
+    pub connected_pairs: usize,
+    /// Delivered / all sampled pairs
+    pub success_rate: f64,
+    /// Delivered / connected pairs (routing's share of the loss)
+    pub connected_success_rate: f64,
+    pub avg_hops: f64,
+    /// Stretch against shortest paths in the damaged graph
+    pub stretch: f64,
+    pub max_stretch: f64,
+}
+
+/// Degradation curve for one failure mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DegradationCurve {
+    pub mode: FailureMode,
+    pub points: Vec<DegradationPoint>,
+}
+
+/// Evaluate routing under increasing failures
+pub fn evaluate_failures(router: &GPRouter, config: &RobustnessConfig) -> DegradationCurve {
+    let graph = CsrGraph::from_adjacency(&router.build_adjacency_map());
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let max_ttl = config.max_ttl.unwrap_or((router.node_count() * 20) as u32);
+
+    let (node_order, edge_order) = if config.mode.removes_nodes() {
+        (node_failure_order(&graph, config, &mut rng), Vec::new())
+    } else {
+        (Vec::new(), edge_failure_order(&graph, config, &mut rng))
+    };
+    let total = if config.mode.removes_nodes() {
+        graph.len()
+    } else {
+        router.edge_count()
+    };
+
+    let points = config
+        .fractions
+        .iter()
+        .enumerate()
+        .map(|(step, &fraction)| {
+            let k = ((fraction.clamp(0.0, 1.0) * total as f64).round() as usize).min(total);
+            let mut damaged = router.clone();
+            let edges_before = damaged.edge_count();
+            for id in node_order.iter().take(k) {
+                damaged.remove_node(id);
+            }
+            for (u, v) in edge_order.iter().take(k) {
+                damaged.remove_edge(u, v);
+            }
+            let removed_nodes = router.node_count() - damaged.node_count();
+            let removed_edges = edges_before - damaged.edge_count();
+
+            let batch = damaged.simulate_batch(
+                &Workload::Random {
+                    count: config.pairs_per_step,
+                    seed: config.seed.wrapping_add(step as u64 + 1),
+                },
+                &BatchConfig {
+                    max_ttl: Some(max_ttl),
+                    compute_stretch: true,
+                },
+            );
+
+            let damaged_graph = CsrGraph::from_adjacency(&damaged.build_adjacency_map());
+            let labels = damaged_graph.components();
+            let component = |id: &NodeId| damaged_graph.index_of(id).map(|i| labels[i as usize]);
+            let connected_pairs = batch
+                .pairs
+                .iter()
+                .filter(|p| component(&p.source).is_some() && component(&p.source) == component(&p.destination))
+                .count();
+
+            let summary = &batch.summary;
+            DegradationPoint {
+                fraction,
+                removed_nodes,
+                removed_edges,
+                pairs: summary.pairs,
+                connected_pairs,
+                success_rate: summary.success_rate,
+                connected_success_rate: if connected_pairs > 0 {
+                    summary.delivered as f64 / connected_pairs as f64
+                } else {
+                    0.0
+                },
+                avg_hops: summary.avg_hops,
+                stretch: summary.stretch,
+                max_stretch: summary.max_stretch,
+            }
+        })
+        .collect();
+
+    DegradationCurve {
+        mode: config.mode,
+        points,
+    }
+}
+
+/// Node removal order for node failure modes
+fn node_failure_order(graph: &CsrGraph, config: &RobustnessConfig, rng: &mut StdRng) -> Vec<NodeId> {
+    let mut order: Vec<u32> = (0..graph.len() as u32).collect();
+    match config.mode {
+        FailureMode::HighDegreeNodes => {
+            order.sort_by_key(|&v| std::cmp::Reverse(graph.neighbors(v).len()));
+        }
+        FailureMode::HighBetweennessNodes => {
+            let node = graph.betweenness(&betweenness_sources(graph, config, rng)).nodes;
+            order.sort_by(|&a, &b| node[b as usize].total_cmp(&node[a as usize]));
+        }
+        _ => order.shuffle(rng),
+    }
+    order.into_iter().map(|v| graph.id(v).clone()).collect()
+}
+
+/// Edge removal order for edge failure modes
+fn edge_failure_order(graph: &CsrGraph, config: &RobustnessConfig, rng: &mut StdRng) -> Vec<(NodeId, NodeId)> {
+    let edges: Vec<(u32, u32)> = match config.mode {
+        FailureMode::HighBetweennessEdges => {
+            let mut ranked = graph.betweenness(&betweenness_sources(graph, config, rng)).edges;
+            ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+            ranked.into_iter().map(|(e, _)| e).collect()
+        }
+        _ => {
+            let mut edges: Vec<(u32, u32)> = (0..graph.len() as u32)
+                .flat_map(|u| graph.neighbors(u).iter().filter(move |&&v| u < v).map(move |&v| (u, v)))
+                .collect();
+            edges.sort_unstable();
+            edges.dedup();
+            edges.shuffle(rng);
+            edges
+        }
+    };
+    edges
+        .into_iter()
+        .map(|(u, v)| (graph.id(u).clone(), graph.id(v).clone()))
+        .collect()
+}
+
+fn betweenness_sources(graph: &CsrGraph, config: &RobustnessConfig, rng: &mut StdRng) -> Vec<u32> {
+    let all: Vec<u32> = (0..graph.len() as u32).collect();
+    match config.betweenness_samples {
+        Some(k) if k < all.len() => all.choose_multiple(rng, k).copied().collect(),
+        _ => all,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinates::RoutingCoordinate;
+    use crate::greedy_embedding::GreedyEmbedding;
+    use crate::routing::RoutingNode;
+    use std::collections::HashMap;
+
+    /// Two 4x4 grids joined by a single bridge edge, greedily embedded
+    fn bridged_grids() -> GPRouter {
+        let id = |g: usize, x: usize, y: usize| NodeId::new(format!("{}_{}_{}", g, x, y));
+        let mut adjacency: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+        let mut link = |a: NodeId, b: NodeId| {
+            adjacency.entry(a.clone()).or_default().push(b.clone());
+            adjacency.entry(b).or_default().push(a);
+        };
+        for g in 0..2 {
+            for x in 0..4 {
+                for y in 0..4 {
+                    if x + 1 < 4 {
+                        link(id(g, x, y), id(g, x + 1, y));
+                    }
+                    if y + 1 < 4 {
+                        link(id(g, x, y), id(g, x, y + 1));
+                    }
+                }
+            }
+        }
+        link(id(0, 3, 3), id(1, 0, 0));
+
+        let embedding = GreedyEmbedding::new().embed(&adjacency).unwrap();
+        let mut router = GPRouter::new();
+        for (node, point) in &embedding.coordinates {
+            router.add_node(RoutingNode::new(node.clone(), RoutingCoordinate::new(*point, 0)));
+        }
+        for (node, neighbors) in &adjacency {
+            for neighbor in neighbors {
+                router.add_edge(node, neighbor);
+            }
+        }
+        router
+    }
+
+    #[test]
+    fn test_bridge_is_first_betweenness_failure() {
+        let router = bridged_grids();
+        let config = RobustnessConfig {
+            mode: FailureMode::HighBetweennessEdges,
+            fractions: vec![0.0, 1.0 / 49.0],
+            pairs_per_step: 200,
+            betweenness_samples: None,
+            ..RobustnessConfig::default()
+        };
+        let curve = evaluate_failures(&router, &config);
+
+        assert_eq!(curve.points[0].removed_edges, 0);
+        assert_eq!(curve.points[0].connected_pairs, 200);
+        assert_eq!(curve.points[0].success_rate, 1.0);
+
+        // Cutting the bridge disconnects about half of all pairs
+        let cut = &curve.points[1];
+        assert_eq!(cut.removed_edges, 1);
+        assert!(cut.connected_pairs < 150);
+        assert!(cut.success_rate <= cut.connected_pairs as f64 / cut.pairs as f64);
+    }
+
+    #[test]
+    fn test_node_failures_shrink_network() {
+        let router = bridged_grids();
+        for mode in [FailureMode::RandomNodes, FailureMode::HighDegreeNodes, FailureMode::HighBetweennessNodes] {
+            let config = RobustnessConfig {
+                mode,
+                fractions: vec![0.25],
+                pairs_per_step: 100,
+                ..RobustnessConfig::default()
+            };
+            let point = &evaluate_failures(&router, &config).points[0];
+            assert_eq!(point.removed_nodes, 8);
+            assert!(point.connected_success_rate <= 1.0);
+            assert!(point.stretch == 0.0 || point.stretch >= 1.0);
+        }
+    }
+}
@@ -0,0 +1,367 @@
+//! Route Lookup Cache
+//!
+//! Busy relays resolve the same few destinations over and over: every send
+//! hashes the destination ID into its anchor coordinate, and every hop runs
+//! a full next-hop selection. This module keeps bounded LRU caches of both.
+//! Cached next hops are tagged with the router epoch they were chosen under
+//! and dropped once it moves on, so coordinate, topology and cost changes
+//! take effect on the next packet.
+//!
+//! Anchors are derived under the algorithm each destination is known to be
+//! addressed under, learned from discovery and packet headers, falling back
+//! to this node's own.
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::{AnchorAlgorithm, AnchorConfig, NodeId};
+use crate::PoincareDiskPoint;
+
+/// Map that evicts the least recently used entry when full
+#[derive(Debug, Clone)]
+pub struct LruCache<K, V> {
+    capacity: usize,
+    entries: HashMap<K, (V, u64)>,
+    /// Keys by last use
+    recency: BTreeMap<u64, K>,
+    tick: u64,
+}
+
+impl<K: Hash + Eq + Clone, V> LruCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, entries: HashMap::new(), recency: BTreeMap::new(), tick: 0 }
+    }
+
+    /// Look up a key, marking it most recently used
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let (_, used) = self.entries.get_mut(key)?;
+        self.recency.remove(used);
+        self.tick += 1;
+        *used = self.tick;
+        self.recency.insert(self.tick, key.clone());
+        self.entries.get(key).map(|(value, _)| value)
+    }
+
+    /// Insert or replace a value, evicting the least recently used entry if full
+    pub fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.remove(&key);
+        if self.entries.len() >= self.capacity {
+            self.evict(self.entries.len() + 1 - self.capacity);
+        }
+        self.tick += 1;
+        self.recency.insert(self.tick, key.clone());
+        self.entries.insert(key, (value, self.tick));
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let (value, used) = self.entries.remove(key)?;
+        self.recency.remove(&used);
+        Some(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Change the capacity, evicting the least recently used entries over it
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict(self.entries.len().saturating_sub(capacity));
+    }
+
+    /// Entries in arbitrary order, without touching recency
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(key, (value, _))| (key, value))
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    fn evict(&mut self, count: usize) {
+        for _ in 0..count {
+            let Some((_, key)) = self.recency.pop_first() else {
+                return;
+            };
+            self.entries.remove(&key);
+        }
+    }
+}
+
+/// Route cache settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouteCacheConfig {
+    pub enabled: bool,
+    /// Destinations whose anchor coordinate is kept
+    pub anchor_capacity: usize,
+    /// (destination, target) pairs whose next hop is kept
+    pub next_hop_capacity: usize,
+}
+
+impl Default for RouteCacheConfig {
+    fn default() -> Self {
+        Self { enabled: true, anchor_capacity: 4096, next_hop_capacity: 1024 }
+    }
+}
+
+impl RouteCacheConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.enabled && (self.anchor_capacity == 0 || self.next_hop_capacity == 0) {
+            return Err("Route cache capacities must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Hit and miss counters since startup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteCacheStats {
+    pub anchor_hits: u64,
+    pub anchor_misses: u64,
+    pub next_hop_hits: u64,
+    pub next_hop_misses: u64,
+    /// Next hops dropped because the router epoch moved on
+    pub next_hop_invalidations: u64,
+    pub anchors_cached: usize,
+    pub next_hops_cached: usize,
+}
+
+#[derive(Debug, Clone)]
+struct CachedHop {
+    next_hop: NodeId,
+    epoch: u64,
+}
+
+/// Destination and exact target coordinate of a routing decision
+type HopKey = (NodeId, u64, u64);
+
+fn hop_key(destination: &NodeId, target: &PoincareDiskPoint) -> HopKey {
+    (destination.clone(), target.x.to_bits(), target.y.to_bits())
+}
+
+/// Nodes whose learned anchor algorithm is kept
+const LEARNED_ALGORITHM_CAPACITY: usize = 4096;
+
+/// Anchor coordinates and recent next hops
+#[derive(Debug, Clone)]
+pub struct RouteCache {
+    config: RouteCacheConfig,
+    anchor_config: AnchorConfig,
+    /// Algorithms nodes advertised for themselves, when accepted
+    algorithms: LruCache<NodeId, AnchorAlgorithm>,
+    anchors: LruCache<NodeId, PoincareDiskPoint>,
+    next_hops: LruCache<HopKey, CachedHop>,
+    stats: RouteCacheStats,
+}
+
+impl Default for RouteCache {
+    fn default() -> Self {
+        Self::new(RouteCacheConfig::default())
+    }
+}
+
+impl RouteCache {
+    pub fn new(config: RouteCacheConfig) -> Self {
+        Self {
+            anchor_config: AnchorConfig::default(),
+            algorithms: LruCache::new(LEARNED_ALGORITHM_CAPACITY),
+            anchors: LruCache::new(config.anchor_capacity),
+            next_hops: LruCache::new(config.next_hop_capacity),
+            config,
+            stats: RouteCacheStats::default(),
+        }
+    }
+
+    pub fn config(&self) -> &RouteCacheConfig {
+        &self.config
+    }
+
+    /// Apply new settings; disabling the cache empties it
+    pub fn set_config(&mut self, config: RouteCacheConfig) {
+        if config.enabled {
+            self.anchors.set_capacity(config.anchor_capacity);
+            self.next_hops.set_capacity(config.next_hop_capacity);
+        } else {
+            self.anchors.clear();
+            self.next_hops.clear();
+        }
+        self.config = config;
+    }
+
+    pub fn anchor_config(&self) -> &AnchorConfig {
+        &self.anchor_config
+    }
+
+    /// Change anchor derivation, forgetting anchors and algorithms it no longer accepts
+    pub fn set_anchor_config(&mut self, anchor_config: AnchorConfig) {
+        let rejected: Vec<NodeId> = self
+            .algorithms
+            .iter()
+            .filter(|(_, algorithm)| !anchor_config.accepts(**algorithm))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in rejected {
+            self.algorithms.remove(&id);
+        }
+        self.anchors.clear();
+        self.next_hops.clear();
+        self.anchor_config = anchor_config;
+    }
+
+    /// Record the algorithm `id` is addressed under; false if it is not accepted
+    pub fn learn_algorithm(&mut self, id: &NodeId, algorithm: AnchorAlgorithm) -> bool {
+        if !self.anchor_config.accepts(algorithm) {
+            return false;
+        }
+        if self.algorithms.get(id) != Some(&algorithm) {
+            self.algorithms.insert(id.clone(), algorithm);
+            self.anchors.remove(id);
+        }
+        true
+    }
+
+    /// Algorithm `id` is addressed under, as far as this node knows
+    pub fn algorithm_for(&mut self, id: &NodeId) -> AnchorAlgorithm {
+        self.algorithms.get(id).copied().unwrap_or(self.anchor_config.algorithm)
+    }
+
+    /// Anchor coordinate of `id`, computed on a miss
+    pub fn anchor(&mut self, id: &NodeId) -> PoincareDiskPoint {
+        if !self.config.enabled {
+            let algorithm = self.algorithm_for(id);
+            return self.anchor_config.anchor(id, algorithm).point;
+        }
+        if let Some(point) = self.anchors.get(id) {
+            self.stats.anchor_hits += 1;
+            return *point;
+        }
+        self.stats.anchor_misses += 1;
+        let algorithm = self.algorithm_for(id);
+        let point = self.anchor_config.anchor(id, algorithm).point;
+        self.anchors.insert(id.clone(), point);
+        point
+    }
+
+    /// Next hop chosen for this destination and target under `epoch`
+    pub fn next_hop(&mut self, destination: &NodeId, target: &PoincareDiskPoint, epoch: u64) -> Option<NodeId> {
+        if !self.config.enabled {
+            return None;
+        }
+        let key = hop_key(destination, target);
+        match self.next_hops.get(&key) {
+            Some(hop) if hop.epoch == epoch => {
+                self.stats.next_hop_hits += 1;
+                return Some(hop.next_hop.clone());
+            }
+            Some(_) => {
+                self.stats.next_hop_invalidations += 1;
+                self.next_hops.remove(&key);
+            }
+            None => {}
+        }
+        self.stats.next_hop_misses += 1;
+        None
+    }
+
+    /// Remember a Gravity-mode next hop chosen under `epoch`
+    pub fn remember_next_hop(&mut self, destination: &NodeId, target: &PoincareDiskPoint, epoch: u64, next_hop: NodeId) {
+        if self.config.enabled {
+            self.next_hops.insert(hop_key(destination, target), CachedHop { next_hop, epoch });
+        }
+    }
+
+    pub fn stats(&self) -> RouteCacheStats {
+        RouteCacheStats {
+            anchors_cached: self.anchors.len(),
+            next_hops_cached: self.next_hops.len(),
+            ..self.stats
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinates::AnchorCoordinate;
+
+    #[test]
+    fn test_lru_evicts_least_recently_used() {
+        let mut cache = LruCache::new(2);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        assert_eq!(cache.get(&"a"), Some(&1));
+        cache.insert("c", 3);
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.len(), 2);
+
+        cache.insert("a", 10);
+        cache.set_capacity(1);
+        assert_eq!(cache.get(&"a"), Some(&10));
+        assert_eq!(cache.get(&"c"), None);
+    }
+
+    #[test]
+    fn test_next_hops_expire_with_epoch() {
+        let mut cache = RouteCache::default();
+        let dest = NodeId::new("d");
+        let target = cache.anchor(&dest);
+        assert_eq!(cache.anchor(&dest), AnchorCoordinate::from_id(&dest).point);
+
+        assert_eq!(cache.next_hop(&dest, &target, 1), None);
+        cache.remember_next_hop(&dest, &target, 1, NodeId::new("n"));
+        assert_eq!(cache.next_hop(&dest, &target, 1), Some(NodeId::new("n")));
+        // A different target for the same destination is a separate entry
+        assert_eq!(cache.next_hop(&dest, &PoincareDiskPoint::origin(), 1), None);
+        assert_eq!(cache.next_hop(&dest, &target, 2), None);
+
+        let stats = cache.stats();
+        assert_eq!((stats.anchor_hits, stats.anchor_misses), (1, 1));
+        assert_eq!((stats.next_hop_hits, stats.next_hop_misses), (1, 3));
+        assert_eq!(stats.next_hop_invalidations, 1);
+        assert_eq!(stats.next_hops_cached, 0);
+
+        cache.set_config(RouteCacheConfig { enabled: false, ..Default::default() });
+        cache.remember_next_hop(&dest, &target, 2, NodeId::new("n"));
+        assert_eq!(cache.next_hop(&dest, &target, 2), None);
+        assert_eq!(cache.stats().anchors_cached, 0);
+    }
+
+    #[test]
+    fn test_anchors_follow_learned_algorithms() {
+        let mut cache = RouteCache::default();
+        let (old, new) = (NodeId::new("old"), NodeId::new("new"));
+        let legacy = cache.anchor(&new);
+        // Algorithms the node does not accept are not learned
+        assert!(!cache.learn_algorithm(&new, AnchorAlgorithm::Sha512));
+        assert_eq!(cache.anchor(&new), legacy);
+
+        cache.set_anchor_config(AnchorConfig {
+            algorithm: AnchorAlgorithm::Sha512,
+            accepted: vec![AnchorAlgorithm::Sha256],
+            key: None,
+        });
+        assert!(cache.learn_algorithm(&old, AnchorAlgorithm::Sha256));
+        assert_eq!(cache.anchor(&old), AnchorCoordinate::from_id(&old).point);
+        // Unknown nodes are assumed to use this node's algorithm
+        assert_eq!(cache.anchor(&new), AnchorCoordinate::derive(&new, AnchorAlgorithm::Sha512, None).point);
+
+        // Finishing the migration forgets nodes still on the old algorithm
+        cache.set_anchor_config(AnchorConfig { algorithm: AnchorAlgorithm::Sha512, ..Default::default() });
+        assert_eq!(cache.algorithm_for(&old), AnchorAlgorithm::Sha512);
+    }
+}
@@ -0,0 +1,401 @@
+//! Persistent Routing Statistics
+//!
+//! A node learns which destinations it reaches reliably, how much stretch
+//! their routes have and which neighbors lose packets, then forgets it all
+//! on restart. `RouteStats` keeps these aggregates and, with the `stats-db`
+//! feature and a configured path, persists them in an embedded sled
+//! database. A rebooted node prices its links by their stored reliability
+//! until heartbeats have measured them again, so it avoids historically bad
+//! next hops from the first packet. Snapshots serialize to JSON for
+//! fleet-wide analysis.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::coordinates::NodeId;
+use crate::heartbeat::LinkQuality;
+
+/// Weight of a new link sample in a neighbor's reliability
+const RELIABILITY_GAIN: f64 = 0.1;
+
+/// Routing statistics settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouteStatsConfig {
+    pub enabled: bool,
+    /// Database directory; statistics are kept in memory only if unset
+    pub path: Option<PathBuf>,
+    /// How often changed entries are written to the database
+    pub flush_interval_ms: u64,
+    /// Destinations tracked before the least recently seen are dropped
+    pub max_destinations: usize,
+}
+
+impl Default for RouteStatsConfig {
+    fn default() -> Self {
+        Self { enabled: false, path: None, flush_interval_ms: 10_000, max_destinations: 10_000 }
+    }
+}
+
+impl RouteStatsConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.enabled && (self.flush_interval_ms == 0 || self.max_destinations == 0) {
+            return Err("Route stats flush_interval_ms and max_destinations must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Routing statistics store errors
+#[derive(Debug, Error)]
+pub enum RouteStatsError {
+    #[error("Route stats database error: {0}")]
+    Database(String),
+
+    #[error("Failed to encode route stats: {0}")]
+    Encode(#[from] bincode::Error),
+
+    #[error("Persistent route stats need the stats-db feature")]
+    Unsupported,
+}
+
+/// Delivery record for packets this node sent to one destination
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DestinationStats {
+    pub sent: u64,
+    pub acked: u64,
+    /// Sum and count of stretch samples taken from the destination's acks
+    pub stretch_sum: f64,
+    pub stretch_samples: u64,
+    pub last_seen_ms: u64,
+}
+
+impl DestinationStats {
+    /// Fraction of sent packets that were acked
+    pub fn success_rate(&self) -> Option<f64> {
+        (self.sent > 0).then(|| (self.acked as f64 / self.sent as f64).min(1.0))
+    }
+
+    pub fn mean_stretch(&self) -> Option<f64> {
+        (self.stretch_samples > 0).then(|| self.stretch_sum / self.stretch_samples as f64)
+    }
+}
+
+/// Long-run reliability of the link to one neighbor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NeighborStats {
+    /// Smoothed round-trip delivery ratio of heartbeats
+    pub reliability: f64,
+    pub samples: u64,
+    /// Sends that failed at the transport
+    pub send_failures: u64,
+    pub last_seen_ms: u64,
+}
+
+impl Default for NeighborStats {
+    fn default() -> Self {
+        Self { reliability: 1.0, samples: 0, send_failures: 0, last_seen_ms: 0 }
+    }
+}
+
+impl NeighborStats {
+    /// Link quality implied by the stored reliability
+    pub fn link_quality(&self) -> LinkQuality {
+        LinkQuality { inbound_delivery: Some(self.reliability), outbound_delivery: None }
+    }
+}
+
+/// Exported statistics
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RouteStatsSnapshot {
+    pub destinations: BTreeMap<NodeId, DestinationStats>,
+    pub neighbors: BTreeMap<NodeId, NeighborStats>,
+}
+
+#[cfg(feature = "stats-db")]
+struct Database {
+    destinations: sled::Tree,
+    neighbors: sled::Tree,
+    db: sled::Db,
+}
+
+#[cfg(feature = "stats-db")]
+impl Database {
+    fn open(path: &std::path::Path) -> Result<Self, RouteStatsError> {
+        let db = sled::open(path).map_err(|e| RouteStatsError::Database(e.to_string()))?;
+        let tree = |name: &str| db.open_tree(name).map_err(|e| RouteStatsError::Database(e.to_string()));
+        Ok(Self { destinations: tree("destinations")?, neighbors: tree("neighbors")?, db })
+    }
+
+    fn load<T: serde::de::DeserializeOwned>(tree: &sled::Tree) -> Result<HashMap<NodeId, T>, RouteStatsError> {
+        let mut entries = HashMap::new();
+        for entry in tree.iter() {
+            let (key, value) = entry.map_err(|e| RouteStatsError::Database(e.to_string()))?;
+            let id = NodeId::new(String::from_utf8_lossy(&key).as_ref());
+            entries.insert(id, bincode::deserialize(&value)?);
+        }
+        Ok(entries)
+    }
+
+    fn store<T: Serialize>(tree: &sled::Tree, id: &NodeId, value: Option<&T>) -> Result<(), RouteStatsError> {
+        let result = match value {
+            Some(value) => tree.insert(id.0.as_bytes(), bincode::serialize(value)?).map(|_| ()),
+            None => tree.remove(id.0.as_bytes()).map(|_| ()),
+        };
+        result.map_err(|e| RouteStatsError::Database(e.to_string()))
+    }
+}
+
+/// Per-destination and per-neighbor routing statistics
+#[derive(Default)]
+pub struct RouteStats {
+    config: RouteStatsConfig,
+    destinations: HashMap<NodeId, DestinationStats>,
+    neighbors: HashMap<NodeId, NeighborStats>,
+    /// Entries changed since the last flush
+    dirty_destinations: HashSet<NodeId>,
+    dirty_neighbors: HashSet<NodeId>,
+    last_flush_ms: u64,
+    #[cfg(feature = "stats-db")]
+    db: Option<Database>,
+}
+
+impl std::fmt::Debug for RouteStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RouteStats")
+            .field("config", &self.config)
+            .field("destinations", &self.destinations.len())
+            .field("neighbors", &self.neighbors.len())
+            .finish()
+    }
+}
+
+impl RouteStats {
+    /// Open the statistics, loading any stored in the configured database
+    pub fn open(config: RouteStatsConfig) -> Result<Self, RouteStatsError> {
+        let path = config.path.clone().filter(|_| config.enabled);
+        let stats = Self { config, ..Default::default() };
+        match path {
+            None => Ok(stats),
+            #[cfg(feature = "stats-db")]
+            Some(path) => stats.attach(&path),
+            #[cfg(not(feature = "stats-db"))]
+            Some(_) => Err(RouteStatsError::Unsupported),
+        }
+    }
+
+    #[cfg(feature = "stats-db")]
+    fn attach(mut self, path: &std::path::Path) -> Result<Self, RouteStatsError> {
+        let db = Database::open(path)?;
+        self.destinations = Database::load(&db.destinations)?;
+        self.neighbors = Database::load(&db.neighbors)?;
+        self.db = Some(db);
+        Ok(self)
+    }
+
+    pub fn config(&self) -> &RouteStatsConfig {
+        &self.config
+    }
+
+    pub fn is_persistent(&self) -> bool {
+        #[cfg(feature = "stats-db")]
+        {
+            self.db.is_some()
+        }
+        #[cfg(not(feature = "stats-db"))]
+        {
+            false
+        }
+    }
+
+    fn destination_entry(&mut self, destination: &NodeId, now_ms: u64) -> &mut DestinationStats {
+        if !self.destinations.contains_key(destination) && self.destinations.len() >= self.config.max_destinations {
+            let oldest = self.destinations.iter().min_by_key(|(_, s)| s.last_seen_ms).map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                self.destinations.remove(&oldest);
+                self.dirty_destinations.insert(oldest);
+            }
+        }
+        self.dirty_destinations.insert(destination.clone());
+        let entry = self.destinations.entry(destination.clone()).or_default();
+        entry.last_seen_ms = now_ms;
+        entry
+    }
+
+    fn neighbor_entry(&mut self, neighbor: &NodeId, now_ms: u64) -> &mut NeighborStats {
+        self.dirty_neighbors.insert(neighbor.clone());
+        let entry = self.neighbors.entry(neighbor.clone()).or_default();
+        entry.last_seen_ms = now_ms;
+        entry
+    }
+
+    /// Count a Data packet sent to `destination`
+    pub fn record_sent(&mut self, destination: &NodeId, now_ms: u64) {
+        if self.config.enabled {
+            self.destination_entry(destination, now_ms).sent += 1;
+        }
+    }
+
+    /// Count an ack from `destination`, with the stretch of its route if known
+    pub fn record_acked(&mut self, destination: &NodeId, stretch: Option<f64>, now_ms: u64) {
+        if !self.config.enabled {
+            return;
+        }
+        let entry = self.destination_entry(destination, now_ms);
+        entry.acked += 1;
+        if let Some(stretch) = stretch {
+            entry.stretch_sum += stretch;
+            entry.stretch_samples += 1;
+        }
+    }
+
+    /// Fold a measured link quality into the neighbor's reliability
+    pub fn record_link(&mut self, neighbor: &NodeId, quality: &LinkQuality, now_ms: u64) {
+        if !self.config.enabled {
+            return;
+        }
+        let delivery = 1.0 - quality.loss();
+        let entry = self.neighbor_entry(neighbor, now_ms);
+        entry.reliability = if entry.samples == 0 {
+            delivery
+        } else {
+            entry.reliability + RELIABILITY_GAIN * (delivery - entry.reliability)
+        };
+        entry.samples += 1;
+    }
+
+    pub fn record_send_failure(&mut self, neighbor: &NodeId, now_ms: u64) {
+        if self.config.enabled {
+            self.neighbor_entry(neighbor, now_ms).send_failures += 1;
+        }
+    }
+
+    /// Routing cost of the link to `neighbor`, using the stored reliability
+    /// until heartbeats have measured the link
+    pub fn link_cost(&self, neighbor: &NodeId, current: &LinkQuality) -> f64 {
+        if current.is_measured() {
+            return current.routing_cost();
+        }
+        match self.neighbors.get(neighbor) {
+            Some(stats) if self.config.enabled && stats.samples > 0 => stats.link_quality().routing_cost(),
+            _ => current.routing_cost(),
+        }
+    }
+
+    pub fn destination(&self, id: &NodeId) -> Option<&DestinationStats> {
+        self.destinations.get(id)
+    }
+
+    pub fn neighbor(&self, id: &NodeId) -> Option<&NeighborStats> {
+        self.neighbors.get(id)
+    }
+
+    pub fn snapshot(&self) -> RouteStatsSnapshot {
+        RouteStatsSnapshot {
+            destinations: self.destinations.iter().map(|(id, s)| (id.clone(), s.clone())).collect(),
+            neighbors: self.neighbors.iter().map(|(id, s)| (id.clone(), s.clone())).collect(),
+        }
+    }
+
+    /// Flush if `flush_interval_ms` has passed since the last flush
+    pub fn maybe_flush(&mut self, now_ms: u64) -> Result<(), RouteStatsError> {
+        if now_ms.saturating_sub(self.last_flush_ms) < self.config.flush_interval_ms {
+            return Ok(());
+        }
+        self.last_flush_ms = now_ms;
+        self.flush()
+    }
+
+    /// Write changed entries to the database, if there is one
+    pub fn flush(&mut self) -> Result<(), RouteStatsError> {
+        #[cfg(feature = "stats-db")]
+        if let Some(db) = &self.db {
+            for id in &self.dirty_destinations {
+                Database::store(&db.destinations, id, self.destinations.get(id))?;
+            }
+            for id in &self.dirty_neighbors {
+                Database::store(&db.neighbors, id, self.neighbors.get(id))?;
+            }
+            db.db.flush().map_err(|e| RouteStatsError::Database(e.to_string()))?;
+        }
+        self.dirty_destinations.clear();
+        self.dirty_neighbors.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled(path: Option<PathBuf>) -> RouteStatsConfig {
+        RouteStatsConfig { enabled: true, path, ..Default::default() }
+    }
+
+    #[test]
+    fn test_records_and_prices_unmeasured_links() {
+        let mut stats = RouteStats::open(enabled(None)).unwrap();
+        let dest = NodeId::new("d");
+        stats.record_sent(&dest, 1);
+        stats.record_sent(&dest, 2);
+        stats.record_acked(&dest, Some(1.5), 3);
+        let record = stats.destination(&dest).unwrap();
+        assert_eq!(record.success_rate(), Some(0.5));
+        assert_eq!(record.mean_stretch(), Some(1.5));
+
+        let lossy = NodeId::new("lossy");
+        let measured = LinkQuality { inbound_delivery: Some(0.5), outbound_delivery: Some(1.0) };
+        stats.record_link(&lossy, &measured, 4);
+        assert_eq!(stats.neighbor(&lossy).unwrap().reliability, 0.5);
+        // Unmeasured links take the stored cost, measured ones their own
+        let unmeasured = LinkQuality::default();
+        assert_eq!(stats.link_cost(&lossy, &unmeasured), measured.routing_cost());
+        assert_eq!(stats.link_cost(&NodeId::new("new"), &unmeasured), 0.0);
+        assert_eq!(stats.link_cost(&lossy, &LinkQuality { inbound_delivery: Some(1.0), outbound_delivery: None }), 0.0);
+
+        let json = serde_json::to_string(&stats.snapshot()).unwrap();
+        assert_eq!(serde_json::from_str::<RouteStatsSnapshot>(&json).unwrap(), stats.snapshot());
+
+        let mut disabled = RouteStats::default();
+        disabled.record_sent(&dest, 1);
+        assert!(disabled.destination(&dest).is_none());
+    }
+
+    #[test]
+    fn test_evicts_least_recently_seen_destination() {
+        let mut stats = RouteStats::open(RouteStatsConfig { max_destinations: 2, ..enabled(None) }).unwrap();
+        for (i, id) in ["a", "b", "c"].into_iter().map(NodeId::new).enumerate() {
+            stats.record_sent(&id, i as u64);
+        }
+        assert!(stats.destination(&NodeId::new("a")).is_none());
+        assert_eq!(stats.snapshot().destinations.len(), 2);
+    }
+
+    #[cfg(not(feature = "stats-db"))]
+    #[test]
+    fn test_persistence_needs_feature() {
+        let result = RouteStats::open(enabled(Some(PathBuf::from("unused"))));
+        assert!(matches!(result, Err(RouteStatsError::Unsupported)));
+    }
+
+    #[cfg(feature = "stats-db")]
+    #[test]
+    fn test_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = enabled(Some(dir.path().join("stats")));
+        let neighbor = NodeId::new("n");
+        {
+            let mut stats = RouteStats::open(config.clone()).unwrap();
+            assert!(stats.is_persistent());
+            stats.record_sent(&NodeId::new("d"), 1);
+            stats.record_link(&neighbor, &LinkQuality { inbound_delivery: Some(0.25), outbound_delivery: None }, 1);
+            stats.flush().unwrap();
+        }
+        let stats = RouteStats::open(config).unwrap();
+        assert_eq!(stats.destination(&NodeId::new("d")).unwrap().sent, 1);
+        assert_eq!(stats.neighbor(&neighbor).unwrap().reliability, 0.25);
+    }
+}
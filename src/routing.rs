@@ -5,12 +5,18 @@
 //!
 //! Reference: Cvetkovski窶鼎rovella (2009)
 
-use crate::coordinates::{NodeId, RoutingCoordinate};
+use crate::access_zones::{PacketAccess, ZoneConfig, ZoneDecision, ZonePolicy};
+use crate::coordinates::{NodeId, RoutingCoordinate, SpatialIndex};
 use crate::hyper_press::HyperPress;
 use crate::landmark_routing::{LandmarkRoutingConfig, LandmarkRoutingTable};
+use crate::memory::{self, MemoryFootprint, MemoryUsage};
+use crate::mode_switch::{Classic, EscalationContext, ModeSwitchPolicy};
+use crate::tree_repair::{self, TreeRepairConfig, TreeRepairStats};
 use crate::PoincareDiskPoint;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
 /// Routing mode for the GP algorithm
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -65,6 +71,15 @@ pub struct PacketHeader {
     pub tz_path: Vec<NodeId>,
     /// TZ routing: current index in the path
     pub tz_path_index: usize,
+    /// Incremented on every mode switch; pressure values and the DFS stack
+    /// only ever hold state from the current epoch
+    pub recovery_epoch: u32,
+    /// Signature and traffic class, for zone rules
+    pub access: PacketAccess,
+    /// Hop budget under a stretch bound, see `GPRouter::stretch_budget`
+    pub stretch: Option<StretchBudget>,
+    /// Hops taken so far, counted against the stretch budget
+    pub hops: u32,
 }
 
 impl PacketHeader {
@@ -87,6 +102,43 @@ impl PacketHeader {
             dfs_stack: Vec::new(),
             tz_path: Vec::new(),
             tz_path_index: 0,
+            recovery_epoch: 0,
+            access: PacketAccess::default(),
+            stretch: None,
+            hops: 0,
+        }
+    }
+
+    /// Start a new recovery epoch after a mode switch
+    ///
+    /// Pressure values are only meaningful in Pressure mode and the DFS stack
+    /// only in Tree mode, so whatever the new mode does not use is dropped
+    /// instead of being picked up again by a later switch back.
+    pub fn begin_recovery_epoch(&mut self) {
+        self.recovery_epoch = self.recovery_epoch.wrapping_add(1);
+        if self.mode != RoutingMode::Pressure {
+            self.pressure_values.clear();
+        }
+        if self.mode != RoutingMode::Tree {
+            self.dfs_stack.clear();
+        }
+    }
+
+    /// Evict recovery state beyond `limits`
+    ///
+    /// The lowest pressures go first (after decay these are also the oldest
+    /// entries), and the DFS stack loses its bottom-most frames. A truncated
+    /// stack only means DFS restarts once it backtracks past the cut.
+    pub fn compact_recovery_state(&mut self, limits: &RecoveryStateLimits) {
+        if self.pressure_values.len() > limits.max_pressure_entries {
+            let mut entries: Vec<(NodeId, f64)> = self.pressure_values.drain().collect();
+            entries.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.0.cmp(&b.0.0)));
+            entries.truncate(limits.max_pressure_entries);
+            self.pressure_values = entries.into_iter().collect();
+        }
+        if self.dfs_stack.len() > limits.max_dfs_stack {
+            let excess = self.dfs_stack.len() - limits.max_dfs_stack;
+            self.dfs_stack.drain(..excess);
         }
     }
 
@@ -158,6 +210,138 @@ impl PacketHeader {
     }
 }
 
+/// Bounds on the recovery state carried in a packet header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecoveryStateLimits {
+    /// Pressure entries kept per packet
+    pub max_pressure_entries: usize,
+    /// DFS backtracking frames kept per packet
+    pub max_dfs_stack: usize,
+}
+
+impl Default for RecoveryStateLimits {
+    fn default() -> Self {
+        Self {
+            max_pressure_entries: 1024,
+            max_dfs_stack: 4096,
+        }
+    }
+}
+
+/// Hop budget of a packet routed under a worst-case stretch bound
+///
+/// Greedy routing is tried first, but a hop is only taken while the TZ
+/// path from it onward still fits the budget; otherwise the packet switches
+/// to its TZ path where it is. It so arrives within `budget` hops whenever
+/// the TZ table matches the live topology.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StretchBudget {
+    /// TZ estimate of the source-destination distance, in hops
+    pub estimate: u32,
+    /// Most hops allowed: the estimate times the bound, rounded down
+    pub budget: u32,
+}
+
+/// Route a stretch-bounded packet achieved, returned to its source in the ack
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StretchReceipt {
+    pub budget: StretchBudget,
+    /// Hops the packet took
+    pub hops: u32,
+}
+
+impl StretchReceipt {
+    /// Hops taken over the estimated distance
+    pub fn stretch(&self) -> f64 {
+        self.hops as f64 / self.budget.estimate.max(1) as f64
+    }
+
+    pub fn within_bound(&self) -> bool {
+        self.hops <= self.budget.budget
+    }
+}
+
+/// Stretch receipts a source collected, for monitoring a stretch SLO
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct StretchStats {
+    /// Acked packets that were sent under a stretch bound
+    pub receipts: u64,
+    /// Of those, packets that took more hops than their budget
+    pub violations: u64,
+    pub max_stretch: f64,
+    pub mean_stretch: f64,
+}
+
+impl StretchStats {
+    pub fn record(&mut self, receipt: &StretchReceipt) {
+        let stretch = receipt.stretch();
+        self.receipts += 1;
+        self.violations += u64::from(!receipt.within_bound());
+        self.max_stretch = self.max_stretch.max(stretch);
+        self.mean_stretch += (stretch - self.mean_stretch) / self.receipts as f64;
+    }
+}
+
+/// How Gravity mode treats next hops with outdated coordinates
+///
+/// A neighbor's lag is how many epochs its `updated_at` trails the
+/// destination's (or, for an unknown destination, the newest epoch around
+/// the current node). Beyond `max_epoch_lag` the neighbor is stale: it is
+/// penalized per excess epoch, or skipped entirely if `avoid_stale` is set.
+/// Pressure and Tree modes ignore staleness so delivery is not put at risk.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StalenessPolicy {
+    /// Epochs a next hop may trail the reference before it counts as stale
+    pub max_epoch_lag: u64,
+    /// Distance penalty per epoch beyond `max_epoch_lag`
+    pub penalty_per_epoch: f64,
+    /// Skip stale next hops in Gravity mode instead of penalizing them
+    pub avoid_stale: bool,
+}
+
+impl Default for StalenessPolicy {
+    fn default() -> Self {
+        Self {
+            max_epoch_lag: 3,
+            penalty_per_epoch: 0.1,
+            avoid_stale: false,
+        }
+    }
+}
+
+/// How far the coordinates in a router trail the newest one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct StalenessStats {
+    /// Newest `updated_at` among all nodes
+    pub newest_epoch: u64,
+    pub nodes: usize,
+    /// Nodes trailing the newest epoch by more than `max_epoch_lag`
+    pub stale_nodes: usize,
+    pub max_lag: u64,
+    pub mean_lag: f64,
+}
+
+/// Repairs of stamped TZ paths whose next entry was dead
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TzPathRepairStats {
+    /// Dead path entries skipped
+    pub dead_entries: u64,
+    /// Repairs that spliced in a recomputed TZ segment
+    pub segments: u64,
+    /// Repairs that went greedy toward the next live path node
+    pub greedy: u64,
+    /// Dead entries with no repair; the packet fell back to Tree mode
+    pub failed: u64,
+}
+
+#[derive(Debug, Default)]
+struct TzPathRepairCounters {
+    dead_entries: AtomicU64,
+    segments: AtomicU64,
+    greedy: AtomicU64,
+    failed: AtomicU64,
+}
+
 /// A node in the routing network
 #[derive(Debug, Clone)]
 pub struct RoutingNode {
@@ -168,6 +352,10 @@ pub struct RoutingNode {
     pub tree_parent: Option<NodeId>,
     /// Tree children (from PIE embedding spanning tree)
     pub tree_children: Vec<NodeId>,
+    /// Distance from the tree root; None outside the tree
+    pub tree_depth: Option<u32>,
+    /// Nodes in the subtree rooted here, this one included
+    pub subtree_size: usize,
 }
 
 impl RoutingNode {
@@ -178,6 +366,8 @@ impl RoutingNode {
             neighbors: Vec::new(),
             tree_parent: None,
             tree_children: Vec::new(),
+            tree_depth: None,
+            subtree_size: 1,
         }
     }
 
@@ -187,6 +377,10 @@ impl RoutingNode {
         }
     }
 
+    pub fn remove_neighbor(&mut self, neighbor: &NodeId) {
+        self.neighbors.retain(|n| n != neighbor);
+    }
+
     /// Set tree structure information
     pub fn set_tree_info(&mut self, parent: Option<NodeId>, children: Vec<NodeId>) {
         self.tree_parent = parent;
@@ -205,6 +399,7 @@ pub struct LandmarkRoutingState {
 }
 
 /// GP Router implementing Gravity-Pressure routing algorithm
+#[derive(Clone)]
 pub struct GPRouter {
     /// All nodes in the network
     nodes: HashMap<NodeId, RoutingNode>,
@@ -218,6 +413,39 @@ pub struct GPRouter {
     landmark_state: Option<LandmarkRoutingState>,
     /// Optional HYPER-PRESS router for H^2 + potential routing
     hyper_press: Option<HyperPress>,
+    /// Per-node suspicion scores (0.0 to 1.0) for avoiding Sybil regions
+    suspicion: HashMap<NodeId, f64>,
+    /// Distance penalty per unit of suspicion when choosing a next hop
+    suspicion_penalty: f64,
+    /// Per-neighbor reputation scores (0.0 to 1.0) used to break ties
+    reputation: HashMap<NodeId, f64>,
+    /// Gravity candidates within this distance of the best count as tied
+    reputation_tie_tolerance: f64,
+    /// Nodes never chosen as next hop unless they are the destination
+    excluded: HashSet<NodeId>,
+    /// Node coordinates indexed for nearest-node queries
+    coord_index: SpatialIndex,
+    /// Bounds on per-packet pressure values and DFS stack
+    recovery_limits: RecoveryStateLimits,
+    /// Distance penalties per (neighbor, destination region) for hot regions
+    region_costs: HashMap<(NodeId, u32), f64>,
+    /// Angular sectors the region costs are keyed by
+    region_sectors: u32,
+    /// Treatment of next hops with outdated coordinates; off when None
+    staleness: Option<StalenessPolicy>,
+    /// Distance penalties per (from, to) link for measured link loss
+    link_costs: HashMap<(NodeId, NodeId), f64>,
+    /// When packets escalate to Pressure and fallback modes and return
+    mode_switch: Arc<dyn ModeSwitchPolicy>,
+    /// Access control zones and their decision log
+    zones: Arc<ZonePolicy>,
+    /// Local spanning tree repair and its counters
+    tree_repair: TreeRepairConfig,
+    tree_repair_stats: TreeRepairStats,
+    /// Repairs of stamped TZ paths at forwarding time
+    tz_repairs: Arc<TzPathRepairCounters>,
+    /// Bumped by every change that can alter a routing decision
+    epoch: u64,
 }
 
 impl GPRouter {
@@ -229,16 +457,46 @@ impl GPRouter {
             tz_table: None,
             landmark_state: None,
             hyper_press: None,
+            suspicion: HashMap::new(),
+            suspicion_penalty: 10.0,
+            reputation: HashMap::new(),
+            reputation_tie_tolerance: 0.05,
+            excluded: HashSet::new(),
+            coord_index: SpatialIndex::new(),
+            recovery_limits: RecoveryStateLimits::default(),
+            region_costs: HashMap::new(),
+            region_sectors: 1,
+            staleness: None,
+            link_costs: HashMap::new(),
+            mode_switch: Arc::new(Classic),
+            zones: Arc::new(ZonePolicy::default()),
+            tree_repair: TreeRepairConfig::default(),
+            tree_repair_stats: TreeRepairStats::default(),
+            tz_repairs: Arc::new(TzPathRepairCounters::default()),
+            epoch: 0,
         }
     }
 
+    /// Counter that changes whenever topology, coordinates or costs change
+    ///
+    /// Next hops cached under one epoch are valid until it moves on.
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
     /// Add a node to the network
     pub fn add_node(&mut self, node: RoutingNode) {
+        self.coord_index.insert(node.id.clone(), node.coord.point);
         self.nodes.insert(node.id.clone(), node);
+        self.epoch += 1;
     }
 
     /// Add a bidirectional edge between two nodes
     pub fn add_edge(&mut self, node1: &NodeId, node2: &NodeId) {
+        if self.has_edge(node1, node2) && self.has_edge(node2, node1) {
+            return;
+        }
+        self.epoch += 1;
         if let Some(n1) = self.nodes.get_mut(node1) {
             n1.add_neighbor(node2.clone());
         }
@@ -247,19 +505,108 @@ impl GPRouter {
         }
     }
 
+    /// Remove a bidirectional edge between two nodes
+    pub fn remove_edge(&mut self, node1: &NodeId, node2: &NodeId) {
+        if !self.has_edge(node1, node2) && !self.has_edge(node2, node1) {
+            return;
+        }
+        self.epoch += 1;
+        if let Some(n1) = self.nodes.get_mut(node1) {
+            n1.remove_neighbor(node2);
+        }
+        if let Some(n2) = self.nodes.get_mut(node2) {
+            n2.remove_neighbor(node1);
+        }
+
+        // A failed tree edge orphans the child's subtree
+        let is_parent = |child: &NodeId, parent: &NodeId| {
+            self.nodes.get(child).is_some_and(|n| n.tree_parent.as_ref() == Some(parent))
+        };
+        let tree_edge = if is_parent(node1, node2) {
+            Some((node1.clone(), node2.clone()))
+        } else if is_parent(node2, node1) {
+            Some((node2.clone(), node1.clone()))
+        } else {
+            None
+        };
+        if let Some((child, parent)) = tree_edge.filter(|_| self.tree_repair.enabled) {
+            let size = self.nodes[&child].subtree_size;
+            self.nodes.get_mut(&parent).unwrap().tree_children.retain(|c| c != &child);
+            tree_repair::shrink_ancestors(&mut self.nodes, &parent, size);
+            self.repair_tree(&[child], false);
+        }
+    }
+
+    /// Remove a node and all edges to it
+    pub fn remove_node(&mut self, id: &NodeId) -> Option<RoutingNode> {
+        let removed = self.nodes.remove(id)?;
+        self.epoch += 1;
+        self.coord_index.remove(id);
+        for neighbor in &removed.neighbors {
+            if let Some(n) = self.nodes.get_mut(neighbor) {
+                n.remove_neighbor(id);
+            }
+        }
+        if let Some(parent) = removed.tree_parent.as_ref().and_then(|p| self.nodes.get_mut(p)) {
+            parent.tree_children.retain(|c| c != id);
+        }
+        if self.tree_repair.enabled && removed.tree_depth.is_some() {
+            if let Some(parent) = &removed.tree_parent {
+                tree_repair::shrink_ancestors(&mut self.nodes, parent, removed.subtree_size);
+            }
+            self.repair_tree(&removed.tree_children, removed.tree_depth == Some(0));
+        }
+        Some(removed)
+    }
+
     /// Get a node by ID
     pub fn get_node(&self, id: &NodeId) -> Option<&RoutingNode> {
         self.nodes.get(id)
     }
 
     /// Get mutable reference to a node
+    ///
+    /// Coordinate changes must go through `set_coordinate` to keep
+    /// nearest-node queries in sync.
     pub fn get_node_mut(&mut self, id: &NodeId) -> Option<&mut RoutingNode> {
+        self.epoch += 1;
         self.nodes.get_mut(id)
     }
 
+    fn has_edge(&self, from: &NodeId, to: &NodeId) -> bool {
+        self.nodes.get(from).is_some_and(|n| n.neighbors.contains(to))
+    }
+
+    /// Move a node to a new routing coordinate
+    ///
+    /// Returns false if the node is unknown.
+    pub fn set_coordinate(&mut self, id: &NodeId, coord: RoutingCoordinate) -> bool {
+        let Some(node) = self.nodes.get_mut(id) else {
+            return false;
+        };
+        if node.coord.point == coord.point && node.coord.updated_at == coord.updated_at {
+            return true;
+        }
+        self.epoch += 1;
+        node.coord = coord;
+        self.coord_index.insert(id.clone(), coord.point);
+        true
+    }
+
+    /// The `k` nodes closest to `point` with their distances, nearest first
+    pub fn nearest_nodes(&self, point: &PoincareDiskPoint, k: usize) -> Vec<(&NodeId, f64)> {
+        self.coord_index.knn(point, k)
+    }
+
+    /// Nodes within hyperbolic distance `radius` of `point`, nearest first
+    pub fn nodes_within(&self, point: &PoincareDiskPoint, radius: f64) -> Vec<(&NodeId, f64)> {
+        self.coord_index.range(point, radius)
+    }
+
     /// Set the Thorup-Zwick routing table for guaranteed stretch 竕､ 3 fallback
     pub fn set_tz_table(&mut self, table: crate::tz_routing::TZRoutingTable) {
         self.tz_table = Some(table);
+        self.epoch += 1;
     }
 
     /// Check if TZ table is available
@@ -272,6 +619,186 @@ impl GPRouter {
         self.tz_table.as_ref()
     }
 
+    /// Hop budget for a packet from `source` to `destination` within stretch `bound`
+    ///
+    /// The TZ path length stands in for the true distance; the budget is
+    /// never below it, so bounds under 1 only ask for the TZ path. None
+    /// without a TZ table or a TZ path between the two.
+    pub fn stretch_budget(&self, source: &NodeId, destination: &NodeId, bound: f64) -> Option<StretchBudget> {
+        let path = self.tz_table.as_ref()?.compute_path(source, destination)?;
+        let estimate = path.len().saturating_sub(1) as u32;
+        let budget = (estimate as f64 * bound).floor() as u32;
+        Some(StretchBudget { estimate, budget: budget.max(estimate) })
+    }
+
+    /// Set suspicion scores used to steer traffic around suspicious nodes
+    ///
+    /// Suspicious neighbors are penalized rather than excluded, so they are
+    /// still used when no other neighbor makes progress. The destination itself
+    /// is never penalized.
+    pub fn set_suspicion_scores(&mut self, scores: HashMap<NodeId, f64>) {
+        self.suspicion = scores;
+        self.epoch += 1;
+    }
+
+    /// Set the distance penalty applied per unit of suspicion
+    pub fn set_suspicion_penalty(&mut self, penalty: f64) {
+        self.suspicion_penalty = penalty;
+        self.epoch += 1;
+    }
+
+    /// Set neighbor reputation scores used for next-hop tie-breaking
+    ///
+    /// Among gravity candidates within the tie tolerance of the best distance,
+    /// the one with the highest reputation is chosen. Unknown nodes score 0.5.
+    pub fn set_reputation_scores(&mut self, scores: HashMap<NodeId, f64>) {
+        self.reputation = scores;
+        self.epoch += 1;
+    }
+
+    /// Set the distance within which gravity candidates count as tied
+    pub fn set_reputation_tie_tolerance(&mut self, tolerance: f64) {
+        self.reputation_tie_tolerance = tolerance;
+        self.epoch += 1;
+    }
+
+    /// Set distance penalties for next hops toward hot destination regions
+    ///
+    /// Keys are (neighbor, region), with regions as in
+    /// [`crate::traffic_matrix::region_of`] over `sectors` sectors. Like
+    /// suspicion, the cost is added to the neighbor's distance, so loaded
+    /// neighbors lose ties and near-ties but are still used when no other
+    /// neighbor makes progress.
+    pub fn set_region_costs(&mut self, costs: HashMap<(NodeId, u32), f64>, sectors: u32) {
+        self.region_costs = costs;
+        self.region_sectors = sectors.max(1);
+        self.epoch += 1;
+    }
+
+    /// Set distance penalties for lossy links
+    ///
+    /// Keys are (from, to) links. The cost is added to the next hop's
+    /// distance like suspicion, so lossy links lose ties and near-ties to
+    /// clean ones. Links into the destination are never penalized.
+    pub fn set_link_costs(&mut self, costs: HashMap<(NodeId, NodeId), f64>) {
+        if self.link_costs != costs {
+            self.link_costs = costs;
+            self.epoch += 1;
+        }
+    }
+
+    /// Set the bounds on recovery state carried in packet headers
+    pub fn set_recovery_limits(&mut self, limits: RecoveryStateLimits) {
+        self.recovery_limits = limits;
+    }
+
+    pub fn recovery_limits(&self) -> RecoveryStateLimits {
+        self.recovery_limits
+    }
+
+    /// Enable or disable staleness-aware next-hop selection
+    pub fn set_staleness_policy(&mut self, policy: Option<StalenessPolicy>) {
+        self.staleness = policy;
+        self.epoch += 1;
+    }
+
+    pub fn staleness_policy(&self) -> Option<StalenessPolicy> {
+        self.staleness
+    }
+
+    /// Install the policy deciding escalation and return to Gravity mode
+    pub fn set_mode_switch_policy(&mut self, policy: Arc<dyn ModeSwitchPolicy>) {
+        self.mode_switch = policy;
+        self.epoch += 1;
+    }
+
+    pub fn mode_switch_policy(&self) -> Arc<dyn ModeSwitchPolicy> {
+        Arc::clone(&self.mode_switch)
+    }
+
+    /// Lag of every coordinate behind the newest one
+    ///
+    /// Stale nodes are counted against the configured policy, or the default
+    /// one when staleness-aware routing is off. A growing count means
+    /// coordinate gossip is not keeping up with updates.
+    pub fn staleness_stats(&self) -> StalenessStats {
+        let max_epoch_lag = self.staleness.unwrap_or_default().max_epoch_lag;
+        let newest_epoch = self.nodes.values().map(|n| n.coord.updated_at).max().unwrap_or(0);
+        let mut stats = StalenessStats { newest_epoch, nodes: self.nodes.len(), ..Default::default() };
+        let mut total_lag = 0u64;
+        for node in self.nodes.values() {
+            let lag = newest_epoch - node.coord.updated_at;
+            if lag > max_epoch_lag {
+                stats.stale_nodes += 1;
+            }
+            stats.max_lag = stats.max_lag.max(lag);
+            total_lag = total_lag.saturating_add(lag);
+        }
+        if stats.nodes > 0 {
+            stats.mean_lag = total_lag as f64 / stats.nodes as f64;
+        }
+        stats
+    }
+
+    /// Exclude nodes (e.g. detected black holes) from next-hop selection
+    ///
+    /// Unlike suspicion scores this is a hard filter for gravity, pressure and
+    /// tree forwarding. Packets addressed to an excluded node are still delivered.
+    pub fn set_excluded_nodes(&mut self, nodes: HashSet<NodeId>) {
+        self.excluded = nodes;
+        self.epoch += 1;
+    }
+
+    /// Re-attach subtrees orphaned by a failure, timing the repair
+    fn repair_tree(&mut self, orphans: &[NodeId], root_lost: bool) {
+        if orphans.is_empty() {
+            return;
+        }
+        let started = std::time::Instant::now();
+        let outcome = tree_repair::repair(&mut self.nodes, orphans, root_lost);
+        let latency_us = started.elapsed().as_micros() as u64;
+        self.tree_repair_stats.record(outcome, latency_us, &self.tree_repair);
+    }
+
+    /// Recompute tree depths and subtree sizes after installing tree links
+    pub fn refresh_tree_metrics(&mut self) {
+        tree_repair::refresh(&mut self.nodes);
+    }
+
+    /// Check the spanning tree for cycles and inconsistent depths
+    pub fn verify_tree(&self) -> Result<(), NodeId> {
+        tree_repair::verify(&self.nodes)
+    }
+
+    pub fn set_tree_repair(&mut self, config: TreeRepairConfig) {
+        self.tree_repair = config;
+    }
+
+    pub fn tree_repair_stats(&self) -> TreeRepairStats {
+        self.tree_repair_stats
+    }
+
+    pub fn tz_path_repair_stats(&self) -> TzPathRepairStats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        TzPathRepairStats {
+            dead_entries: load(&self.tz_repairs.dead_entries),
+            segments: load(&self.tz_repairs.segments),
+            greedy: load(&self.tz_repairs.greedy),
+            failed: load(&self.tz_repairs.failed),
+        }
+    }
+
+    /// Replace the access control zones; the decision log starts empty
+    pub fn set_zones(&mut self, config: ZoneConfig) {
+        self.zones = Arc::new(ZonePolicy::new(config));
+        self.epoch += 1;
+    }
+
+    /// Routing decisions made with a neighbor denied by zone rules, oldest first
+    pub fn zone_decisions(&self) -> Vec<ZoneDecision> {
+        self.zones.decisions()
+    }
+
     /// Enable landmark-guided routing heuristics
     pub fn enable_landmark_routing(
         &mut self,
@@ -279,6 +806,7 @@ impl GPRouter {
         config: LandmarkRoutingConfig,
     ) {
         self.landmark_state = Some(LandmarkRoutingState { config, table });
+        self.epoch += 1;
     }
 
     /// Check if landmark-guided routing is enabled
@@ -297,6 +825,7 @@ impl GPRouter {
         
         hp.build_from_adjacency(&adjacency);
         self.hyper_press = Some(hp);
+        self.epoch += 1;
     }
 
     /// Enable HYPER-PRESS with custom lambda
@@ -310,6 +839,7 @@ impl GPRouter {
         
         hp.build_from_adjacency(&adjacency);
         self.hyper_press = Some(hp);
+        self.epoch += 1;
     }
 
     /// Check if HYPER-PRESS routing is enabled
@@ -322,6 +852,102 @@ impl GPRouter {
         self.hyper_press.as_ref()
     }
 
+    fn is_excluded(&self, at: &NodeId, node_id: &NodeId, packet: &PacketHeader) -> bool {
+        (node_id != &packet.destination && self.excluded.contains(node_id))
+            || self.zone_denial(at, node_id, packet).is_some()
+    }
+
+    /// Index of the zone rule forbidding a hop from `at` to `hop`
+    fn zone_denial(&self, at: &NodeId, hop: &NodeId, packet: &PacketHeader) -> Option<usize> {
+        if self.zones.is_empty() {
+            return None;
+        }
+        let coord_of = |id: &NodeId| self.nodes.get(id).map(|n| n.coord.point);
+        self.zones.denies(&coord_of, &packet.source, &packet.destination, packet.access, at, hop)
+    }
+
+    /// Drop a forward that zone rules deny and log decisions they affected
+    ///
+    /// Next-hop selection already skips denied neighbors; this also catches
+    /// hops chosen from tables or the DFS stack.
+    fn enforce_zones(&self, current_node: &NodeId, packet: &PacketHeader, decision: RoutingDecision) -> RoutingDecision {
+        let Some(current) = self.nodes.get(current_node).filter(|_| !self.zones.is_empty()) else {
+            return decision;
+        };
+        let denied: Vec<(NodeId, usize)> = current
+            .neighbors
+            .iter()
+            .filter_map(|n| self.zone_denial(current_node, n, packet).map(|rule| (n.clone(), rule)))
+            .collect();
+        if denied.is_empty() {
+            return decision;
+        }
+        let decision = match decision {
+            RoutingDecision::Forward { next_hop, .. } if denied.iter().any(|(n, _)| *n == next_hop) => {
+                RoutingDecision::Failed { reason: format!("Zone policy denies forwarding to {}", next_hop) }
+            }
+            RoutingDecision::Delivered => return RoutingDecision::Delivered,
+            other => other,
+        };
+        self.zones.record(ZoneDecision {
+            source: packet.source.clone(),
+            destination: packet.destination.clone(),
+            at: current_node.clone(),
+            denied,
+            next_hop: match &decision {
+                RoutingDecision::Forward { next_hop, .. } => Some(next_hop.clone()),
+                _ => None,
+            },
+        });
+        decision
+    }
+
+    fn suspicion_cost(&self, node_id: &NodeId, packet: &PacketHeader) -> f64 {
+        if node_id == &packet.destination {
+            return 0.0;
+        }
+        self.suspicion.get(node_id).map_or(0.0, |s| s * self.suspicion_penalty)
+    }
+
+    fn region_cost(&self, node_id: &NodeId, packet: &PacketHeader) -> f64 {
+        if self.region_costs.is_empty() || node_id == &packet.destination {
+            return 0.0;
+        }
+        let region = crate::traffic_matrix::region_of(&packet.target_coord, self.region_sectors);
+        self.region_costs.get(&(node_id.clone(), region)).copied().unwrap_or(0.0)
+    }
+
+    fn link_cost(&self, from: &NodeId, to: &NodeId, packet: &PacketHeader) -> f64 {
+        if self.link_costs.is_empty() || to == &packet.destination {
+            return 0.0;
+        }
+        self.link_costs.get(&(from.clone(), to.clone())).copied().unwrap_or(0.0)
+    }
+
+    /// Epoch that next hops are compared against
+    fn reference_epoch(&self, current: &RoutingNode, packet: &PacketHeader) -> u64 {
+        if let Some(dest) = self.nodes.get(&packet.destination) {
+            return dest.coord.updated_at;
+        }
+        current
+            .neighbors
+            .iter()
+            .filter_map(|n| self.nodes.get(n))
+            .map(|n| n.coord.updated_at)
+            .fold(current.coord.updated_at, u64::max)
+    }
+
+    /// Epochs beyond the allowed lag, or None if the node is fresh enough
+    fn excess_lag(&self, node_id: &NodeId, packet: &PacketHeader, reference: u64) -> Option<u64> {
+        let policy = self.staleness?;
+        if node_id == &packet.destination {
+            return None;
+        }
+        let updated_at = self.nodes.get(node_id)?.coord.updated_at;
+        let lag = reference.saturating_sub(updated_at);
+        (lag > policy.max_epoch_lag).then(|| lag - policy.max_epoch_lag)
+    }
+
     fn distance_to_target(&self, node_id: &NodeId, packet: &PacketHeader) -> f64 {
         if let Some(state) = &self.landmark_state {
             if let Some(landmark_dist) = state.table.distance(node_id, &packet.destination) {
@@ -429,9 +1055,84 @@ impl GPRouter {
         None
     }
 
+    /// Switch a stretch-bounded packet to its TZ path if `decision` would overrun the budget
+    ///
+    /// A hop fits if the hops taken, the hop itself and the TZ path from it
+    /// stay within budget. Packets already on their TZ path are left alone.
+    fn enforce_stretch_budget(&self, current_node: &NodeId, packet: &mut PacketHeader, decision: RoutingDecision) -> RoutingDecision {
+        let (Some(stretch), Some(tz_table)) = (packet.stretch, &self.tz_table) else {
+            return decision;
+        };
+        let next_hop = match &decision {
+            RoutingDecision::Forward { next_hop, mode } if *mode != RoutingMode::ThorupZwick => next_hop,
+            _ => return decision,
+        };
+        let remaining = tz_table.compute_path(next_hop, &packet.destination).map(|path| path.len() as u32 - 1);
+        if remaining.is_some_and(|remaining| packet.hops + 1 + remaining <= stretch.budget) {
+            return decision;
+        }
+        packet.mode = RoutingMode::ThorupZwick;
+        packet.tz_path.clear();
+        packet.tz_path_index = 0;
+        let decision = self.route_step(current_node, packet);
+        self.enforce_zones(current_node, packet, decision)
+    }
+
     /// Make routing decision for a packet at the current node
     /// 縲心ticky Recovery縲・ 繝｢繝ｼ繝峨↓蠢懊§縺溷宍譬ｼ縺ｪ蛻ｶ蠕｡繝輔Ο繝ｼ繧貞ｮ溯｣・
+    ///
+    /// A mode switch starts a new recovery epoch, and the recovery state is
+    /// kept within the router's `RecoveryStateLimits`.
     pub fn route(&self, current_node: &NodeId, packet: &mut PacketHeader) -> RoutingDecision {
+        let entry_mode = packet.mode;
+        let decision = self.route_step(current_node, packet);
+        let decision = self.enforce_zones(current_node, packet, decision);
+        let decision = self.enforce_stretch_budget(current_node, packet, decision);
+        if packet.mode != entry_mode {
+            packet.begin_recovery_epoch();
+        }
+        packet.compact_recovery_state(&self.recovery_limits);
+        decision
+    }
+
+    /// Route a packet, reusing and producing cacheable next hops
+    ///
+    /// `hint` is a next hop cached for the packet's destination and target
+    /// under the current epoch; it is taken while the packet is in Gravity
+    /// mode and the hop is still a neighbor. The second value is the chosen
+    /// next hop if plain greedy forwarding picked it, the only kind that is
+    /// safe to cache since recovery modes depend on per-packet state.
+    pub fn route_with_hint(
+        &self,
+        current_node: &NodeId,
+        packet: &mut PacketHeader,
+        hint: Option<&NodeId>,
+    ) -> (RoutingDecision, Option<NodeId>) {
+        // Zone rules depend on the packet's source, which cached hops do not
+        let greedy = packet.mode == RoutingMode::Gravity
+            && packet.ttl > 0
+            && current_node != &packet.destination
+            && self.zones.is_empty()
+            && packet.stretch.is_none();
+        let current = match self.nodes.get(current_node) {
+            Some(current) if greedy => current,
+            _ => return (self.route(current_node, packet), None),
+        };
+        packet.record_visit(current_node.clone());
+        if let Some(next_hop) = hint.filter(|hop| current.neighbors.contains(hop)) {
+            packet.compact_recovery_state(&self.recovery_limits);
+            return (RoutingDecision::Forward { next_hop: next_hop.clone(), mode: RoutingMode::Gravity }, None);
+        }
+        match self.try_gravity_routing(current, packet) {
+            Some(RoutingDecision::Forward { next_hop, mode }) => {
+                packet.compact_recovery_state(&self.recovery_limits);
+                (RoutingDecision::Forward { next_hop: next_hop.clone(), mode }, Some(next_hop))
+            }
+            _ => (self.route(current_node, packet), None),
+        }
+    }
+
+    fn route_step(&self, current_node: &NodeId, packet: &mut PacketHeader) -> RoutingDecision {
         // [BUG FIX] Check destination FIRST (even if TTL=0, arrival should succeed)
         if current_node == &packet.destination {
             return RoutingDecision::Delivered;
@@ -477,20 +1178,29 @@ impl GPRouter {
                 }
 
                 // Local minimum -> Pressure mode (first-line recovery)
+                let ctx = EscalationContext {
+                    node_count: self.node_count(),
+                    degree: current.degree(),
+                    ttl: packet.ttl,
+                    recovery_epoch: packet.recovery_epoch,
+                };
                 packet.mode = RoutingMode::Pressure;
                 packet.recovery_threshold = current_dist;
-                // Budget: proportional to graph size (e.g., N/2)
-                packet.pressure_budget = (self.node_count() as u32) / 2;
                 packet.pressure_values.clear(); // Reset on new local minimum
+                packet.pressure_budget = self.mode_switch.pressure_budget(&ctx).unwrap_or(0);
+                if packet.pressure_budget == 0 {
+                    // The policy skips Pressure mode
+                    return self.escalate_to_fallback(current, packet);
+                }
 
-                return self.pressure_routing(current, packet);
+                self.pressure_routing(current, packet)
             },
             RoutingMode::Tree => {
                 // Tree繝｢繝ｼ繝・(Sticky): 髢ｾ蛟､繧剃ｸ句屓繧九∪縺ｧGreedy蜴ｳ遖・(Graph DFS)
                 
                 // 閼ｱ蜃ｺ蛻､螳・ 迴ｾ蝨ｨ菴咲ｽｮ縺後Μ繧ｫ繝舌Μ髢句ｧ句慍轤ｹ繧医ｊ縲檎｢ｺ螳溘↓縲阪ざ繝ｼ繝ｫ縺ｫ霑代＞縺具ｼ・
                 // 蜴ｳ蟇・↑荳咲ｭ牙捷 (<) 繧剃ｽｿ逕ｨ縲・
-                if current_dist < packet.recovery_threshold {
+                if self.mode_switch.should_return(packet.mode, current_dist, packet.recovery_threshold) {
                     // 閼ｱ蜃ｺ謌仙粥 -> Gravity繝｢繝ｼ繝峨∈蠕ｩ蟶ｰ
                     packet.mode = RoutingMode::Gravity;
                     packet.recovery_threshold = f64::INFINITY;
@@ -531,13 +1241,13 @@ impl GPRouter {
                 }
                 
                 // 譛ｬ蠖薙↓螟ｱ謨暦ｼ医げ繝ｩ繝輔′髱樣｣邨撰ｼ・
-                return RoutingDecision::Failed { reason: "Graph is disconnected".to_string() };
+                RoutingDecision::Failed { reason: "Graph is disconnected".to_string() }
             },
             RoutingMode::Pressure => {
                 // Pressure繝｢繝ｼ繝・ 螻謇逧・↑鄂縺九ｉ縺ｮ閼ｱ蜃ｺ
                 
                 // 1. 閼ｱ蜃ｺ蛻､螳・(Gravity縺ｸ縺ｮ蠕ｩ蟶ｰ)
-                if current_dist < packet.recovery_threshold {
+                if self.mode_switch.should_return(packet.mode, current_dist, packet.recovery_threshold) {
                     packet.mode = RoutingMode::Gravity;
                     packet.recovery_threshold = f64::INFINITY;
                     packet.pressure_budget = 0;
@@ -552,30 +1262,12 @@ impl GPRouter {
 
                 // 2. 莠育ｮ励メ繧ｧ繝・け (Tree縺ｸ縺ｮ譛邨ゅヵ繧ｩ繝ｼ繝ｫ繝舌ャ繧ｯ)
                 if packet.pressure_budget == 0 {
-                    // [IMPROVEMENT] Prefer TZ routing (stretch <= 3)
-                    if self.has_tz_table() {
-                        packet.mode = RoutingMode::ThorupZwick;
-                        packet.tz_path.clear();
-                        packet.tz_path_index = 0;
-                        return self.route(current_node, packet);
-                    }
-                    
-                    // Tree fallback only if TZ unavailable
-                    packet.mode = RoutingMode::Tree;
-                    packet.dfs_stack.clear();
-                    packet.visited.clear();
-                    packet.pressure_values.clear();
-                    packet.visited.insert(current_node.clone());
-                    
-                    if let Some(decision) = self.traverse_graph_dfs(current, packet) {
-                        return decision;
-                    }
-                    return RoutingDecision::Failed { reason: "Graph is disconnected".to_string() };
+                    return self.escalate_to_fallback(current, packet);
                 }
 
                 // 3. Pressure螳溯｡・
                 packet.pressure_budget -= 1;
-                return self.pressure_routing(current, packet);
+                self.pressure_routing(current, packet)
             },
             RoutingMode::ThorupZwick => {
                 // ThorupZwick mode: Follow precomputed TZ path for guaranteed stretch 竕､ 3
@@ -589,40 +1281,51 @@ impl GPRouter {
                         } else {
                             // TZ path computation failed, try DFS fallback
                             packet.mode = RoutingMode::Tree;
-                            return self.route(current_node, packet);
+                            return self.route_step(current_node, packet);
                         }
                     } else {
                         // No TZ table available, fall back to Tree mode
                         packet.mode = RoutingMode::Tree;
-                        return self.route(current_node, packet);
+                        return self.route_step(current_node, packet);
                     }
                 }
 
-                // Find current position in TZ path
-                let current_pos = packet.tz_path.iter().position(|n| n == current_node);
+                // Find current position in TZ path, never moving back along it
+                let current_pos = packet.tz_path.iter().skip(packet.tz_path_index).position(|n| n == current_node);
                 if let Some(pos) = current_pos {
-                    packet.tz_path_index = pos;
+                    packet.tz_path_index += pos;
                 }
 
                 // Get next hop from TZ path
                 let next_index = packet.tz_path_index + 1;
-                if next_index < packet.tz_path.len() {
+                let dead = packet.tz_path.iter().skip(next_index).take_while(|n| self.is_dead_entry(n, packet)).count();
+                if dead > 0 {
+                    if let Some(decision) = self.repair_tz_path(current, packet, next_index, dead) {
+                        return decision;
+                    }
+                } else if next_index < packet.tz_path.len() {
                     let next_hop = packet.tz_path[next_index].clone();
-                    packet.tz_path_index = next_index;
-                    
+
                     // Check if next hop is neighbor
                     if current.neighbors.contains(&next_hop) {
+                        packet.tz_path_index = next_index;
                         return RoutingDecision::Forward {
                             next_hop,
                             mode: RoutingMode::ThorupZwick,
                         };
-                    } else {
-                        // Next hop is not directly reachable, find path to it
-                        // Use local greedy toward next TZ waypoint
-                        if let Some(decision) = self.route_toward_node(current, &next_hop) {
-                            return decision;
+                    }
+                    // Off the path, still detouring around a dead entry
+                    if current_pos.is_none() {
+                        if let Some(next_hop) = self.greedy_toward(current, &next_hop, packet) {
+                            return RoutingDecision::Forward { next_hop, mode: RoutingMode::ThorupZwick };
                         }
                     }
+                    // Next hop is not directly reachable, find path to it
+                    // Use local greedy toward next TZ waypoint
+                    packet.tz_path_index = next_index;
+                    if let Some(decision) = self.route_toward_node(current, &next_hop) {
+                        return decision;
+                    }
                 }
 
                 // Path exhausted but destination not reached - unexpected
@@ -632,7 +1335,7 @@ impl GPRouter {
                 if let Some(decision) = self.traverse_graph_dfs(current, packet) {
                     return decision;
                 }
-                return RoutingDecision::Failed { reason: "TZ routing exhausted".to_string() };
+                RoutingDecision::Failed { reason: "TZ routing exhausted".to_string() }
             },
             RoutingMode::HyperPress => {
                 // HYPER-PRESS mode: Use H^2 coordinates + Laplacian potential
@@ -654,7 +1357,7 @@ impl GPRouter {
                         packet.mode = RoutingMode::ThorupZwick;
                         packet.tz_path.clear();
                         packet.tz_path_index = 0;
-                        return self.route(current_node, packet);
+                        return self.route_step(current_node, packet);
                     }
                     
                     // Final fallback to Tree
@@ -665,16 +1368,127 @@ impl GPRouter {
                     if let Some(decision) = self.traverse_graph_dfs(current, packet) {
                         return decision;
                     }
-                    return RoutingDecision::Failed { reason: "HYPER-PRESS exhausted, graph disconnected".to_string() };
+                    RoutingDecision::Failed { reason: "HYPER-PRESS exhausted, graph disconnected".to_string() }
                 } else {
                     // HYPER-PRESS not enabled, fall back to Gravity
                     packet.mode = RoutingMode::Gravity;
-                    return self.route(current_node, packet);
+                    self.route_step(current_node, packet)
                 }
             }
         }
     }
 
+    /// Whether a stamped path entry can no longer be forwarded through
+    fn is_dead_entry(&self, node_id: &NodeId, packet: &PacketHeader) -> bool {
+        !self.nodes.contains_key(node_id) || (node_id != &packet.destination && self.excluded.contains(node_id))
+    }
+
+    /// Route around `dead` dead entries of the TZ path starting at `next_index`
+    ///
+    /// The next live entry becomes the waypoint. A TZ segment to it is
+    /// recomputed and spliced into the path if it only uses live nodes and
+    /// links; otherwise the dead entries are dropped and the packet goes
+    /// greedy toward the waypoint, rejoining the path there. None means
+    /// neither worked.
+    fn repair_tz_path(
+        &self,
+        current: &RoutingNode,
+        packet: &mut PacketHeader,
+        next_index: usize,
+        dead: usize,
+    ) -> Option<RoutingDecision> {
+        let counters = &self.tz_repairs;
+        counters.dead_entries.fetch_add(dead as u64, Ordering::Relaxed);
+        let Some(waypoint) = packet.tz_path.get(next_index + dead).cloned() else {
+            counters.failed.fetch_add(1, Ordering::Relaxed);
+            return None;
+        };
+
+        let segment = self
+            .tz_table
+            .as_ref()
+            .and_then(|table| table.compute_path(&current.id, &waypoint))
+            .filter(|segment| segment.len() >= 2 && self.is_live_path(segment, packet));
+        if let Some(segment) = segment {
+            let next_hop = segment[1].clone();
+            packet.tz_path.splice(next_index - 1..=next_index + dead, segment);
+            packet.tz_path_index = next_index;
+            counters.segments.fetch_add(1, Ordering::Relaxed);
+            return Some(RoutingDecision::Forward { next_hop, mode: RoutingMode::ThorupZwick });
+        }
+
+        packet.tz_path.drain(next_index..next_index + dead);
+        match self.greedy_toward(current, &waypoint, packet) {
+            Some(next_hop) => {
+                counters.greedy.fetch_add(1, Ordering::Relaxed);
+                Some(RoutingDecision::Forward { next_hop, mode: RoutingMode::ThorupZwick })
+            }
+            None => {
+                counters.failed.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Whether every hop of a path is a current link to a usable node
+    fn is_live_path(&self, path: &[NodeId], packet: &PacketHeader) -> bool {
+        path.windows(2).all(|hop| {
+            self.nodes.get(&hop[0]).is_some_and(|n| n.neighbors.contains(&hop[1]))
+                && !self.is_dead_entry(&hop[1], packet)
+                && !self.is_excluded(&hop[0], &hop[1], packet)
+        })
+    }
+
+    /// Neighbor linked to `target`, else one strictly closer to it than the current node
+    fn greedy_toward(&self, current: &RoutingNode, target: &NodeId, packet: &PacketHeader) -> Option<NodeId> {
+        if current.neighbors.contains(target) && !self.is_excluded(&current.id, target, packet) {
+            return Some(target.clone());
+        }
+        // Bridging a single dead entry takes one hop; greedy can stall short of it
+        let bridge = current.neighbors.iter().find(|n| {
+            !self.is_dead_entry(n, packet)
+                && !self.is_excluded(&current.id, n, packet)
+                && self.nodes.get(*n).is_some_and(|node| node.neighbors.contains(target) && !self.is_excluded(n, target, packet))
+        });
+        if let Some(bridge) = bridge {
+            return Some(bridge.clone());
+        }
+        let point = self.nodes.get(target)?.coord.point;
+        let distance = |id: &NodeId| self.nodes.get(id).map(|n| n.coord.point.hyperbolic_distance(&point));
+        let current_distance = distance(&current.id)?;
+        current
+            .neighbors
+            .iter()
+            .filter(|n| !self.is_dead_entry(n, packet) && !self.is_excluded(&current.id, n, packet))
+            .filter_map(|n| Some((distance(n)?, n)))
+            .filter(|(d, _)| *d < current_distance)
+            .min_by(|a, b| a.0.total_cmp(&b.0).then_with(|| a.1.cmp(b.1)))
+            .map(|(_, n)| n.clone())
+    }
+
+    /// Leave Pressure mode for the guaranteed fallback
+    fn escalate_to_fallback(&self, current: &RoutingNode, packet: &mut PacketHeader) -> RoutingDecision {
+        // [IMPROVEMENT] Prefer TZ routing (stretch <= 3)
+        if self.has_tz_table() {
+            packet.mode = RoutingMode::ThorupZwick;
+            packet.tz_path.clear();
+            packet.tz_path_index = 0;
+            return self.route_step(&current.id, packet);
+        }
+
+        // Tree fallback only if TZ unavailable
+        packet.mode = RoutingMode::Tree;
+        packet.dfs_stack.clear();
+        packet.visited.clear();
+        packet.pressure_values.clear();
+        packet.visited.insert(current.id.clone());
+
+        if let Some(decision) = self.traverse_graph_dfs(current, packet) {
+            return decision;
+        }
+        RoutingDecision::Failed { reason: "Graph is disconnected".to_string() }
+    }
+
         /// Gravity Mode: Greedy forwarding to neighbor closest to target
     fn try_gravity_routing(
         &self,
@@ -685,15 +1499,41 @@ impl GPRouter {
 
         let mut best_neighbor: Option<&NodeId> = None;
         let mut best_distance = current_distance;
-
-        for neighbor_id in &current.neighbors {
-            let distance = self.distance_to_target(neighbor_id, packet);
+        let mut candidates = Vec::new();
+        let reference = self.staleness.map_or(0, |_| self.reference_epoch(current, packet));
+
+        for neighbor_id in current.neighbors.iter().filter(|n| !self.is_excluded(&current.id, n, packet)) {
+            let staleness_cost = match (self.staleness, self.excess_lag(neighbor_id, packet, reference)) {
+                (Some(policy), Some(_)) if policy.avoid_stale => continue,
+                (Some(policy), Some(excess)) => excess as f64 * policy.penalty_per_epoch,
+                _ => 0.0,
+            };
+            let distance = self.distance_to_target(neighbor_id, packet)
+                + self.suspicion_cost(neighbor_id, packet)
+                + self.region_cost(neighbor_id, packet)
+                + self.link_cost(&current.id, neighbor_id, packet)
+                + staleness_cost;
+            if distance < current_distance {
+                candidates.push((neighbor_id, distance));
+            }
             if distance < best_distance {
                 best_distance = distance;
                 best_neighbor = Some(neighbor_id);
             }
         }
 
+        // Break near-ties in favor of neighbors with a better reputation
+        if !self.reputation.is_empty() {
+            let reputation = |id: &NodeId| self.reputation.get(id).copied().unwrap_or(0.5);
+            let mut best_reputation = best_neighbor.map_or(0.0, reputation);
+            for (neighbor_id, distance) in candidates {
+                if distance <= best_distance + self.reputation_tie_tolerance && reputation(neighbor_id) > best_reputation {
+                    best_reputation = reputation(neighbor_id);
+                    best_neighbor = Some(neighbor_id);
+                }
+            }
+        }
+
         best_neighbor.map(|next_hop| RoutingDecision::Forward {
             next_hop: next_hop.clone(),
             mode: RoutingMode::Gravity,
@@ -760,7 +1600,7 @@ impl GPRouter {
         neighbors.sort_by(|a, b| a.0.cmp(&b.0));
 
         for neighbor_id in neighbors {
-            if !packet.visited.contains(neighbor_id) {
+            if !packet.visited.contains(neighbor_id) && !self.is_excluded(&current.id, neighbor_id, packet) {
                 // Push current node to stack before moving forward
                 packet.dfs_stack.push(current.id.clone());
                 return Some(RoutingDecision::Forward {
@@ -789,7 +1629,7 @@ impl GPRouter {
         let mut best_neighbor: Option<NodeId> = None;
         let mut best_score = f64::INFINITY;
 
-        for neighbor_id in &current.neighbors {
+        for neighbor_id in current.neighbors.iter().filter(|n| !self.is_excluded(&current.id, n, packet)) {
             // Base distance (gravity component)
             let distance = self.distance_to_target(neighbor_id, packet);
 
@@ -802,7 +1642,7 @@ impl GPRouter {
 
             // Combined score: lower is better
             // Nodes with high pressure (many visits) get higher scores, making them less attractive
-            let score = distance + pressure;
+            let score = distance + pressure + self.suspicion_cost(neighbor_id, packet) + self.region_cost(neighbor_id, packet);
 
             if score < best_score {
                 best_score = score;
@@ -959,6 +1799,30 @@ pub struct DeliveryResult {
     pub failure_reason: Option<String>,
 }
 
+/// Nodes and their links, with the per-node and per-link penalty tables;
+/// the TZ table is accounted for separately
+impl MemoryFootprint for GPRouter {
+    fn memory(&self) -> MemoryUsage {
+        let nodes: usize = self
+            .nodes
+            .values()
+            .map(|node| {
+                let links = node.neighbors.iter().chain(&node.tree_children).chain(&node.tree_parent);
+                memory::id_bytes(&node.id)
+                    + memory::vec_bytes::<NodeId>(node.neighbors.capacity() + node.tree_children.capacity())
+                    + links.map(memory::id_bytes).sum::<usize>()
+            })
+            .sum();
+        let bytes = memory::table_bytes::<NodeId, RoutingNode>(self.nodes.capacity())
+            + nodes
+            + memory::table_bytes::<NodeId, f64>(self.suspicion.capacity() + self.reputation.capacity())
+            + memory::table_bytes::<NodeId, ()>(self.excluded.capacity())
+            + memory::table_bytes::<(NodeId, u32), f64>(self.region_costs.capacity())
+            + memory::table_bytes::<(NodeId, NodeId), f64>(self.link_costs.capacity());
+        MemoryUsage::new(self.nodes.len(), bytes)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -997,6 +1861,348 @@ mod tests {
         router
     }
 
+    #[test]
+    fn test_suspicious_nodes_avoided() {
+        let mut router = create_test_network();
+        let src = NodeId::new("1");
+        let dest = NodeId::new("4");
+        let dest_coord = router.get_node(&dest).unwrap().coord.point;
+
+        for (suspect, detour) in [("0", "2"), ("2", "0")] {
+            router.set_suspicion_scores(HashMap::from([(NodeId::new(suspect), 1.0)]));
+            let result = router.simulate_delivery(&src, &dest, dest_coord, 20);
+            assert!(result.success);
+            assert_eq!(result.path, vec![src.clone(), NodeId::new(detour), dest.clone()]);
+        }
+
+        // Penalized, not excluded: "2" is the only way to "3"
+        let dest = NodeId::new("3");
+        let dest_coord = router.get_node(&dest).unwrap().coord.point;
+        router.set_suspicion_scores(HashMap::from([(NodeId::new("2"), 1.0)]));
+        assert!(router.simulate_delivery(&NodeId::new("0"), &dest, dest_coord, 20).success);
+    }
+
+    #[test]
+    fn test_reputation_breaks_ties() {
+        let mut router = create_test_network();
+        let src = NodeId::new("1");
+        let dest = NodeId::new("4");
+        let dest_coord = router.get_node(&dest).unwrap().coord.point;
+
+        // "0" and "2" are equally close to "4"; the better-reputed one wins
+        for (distrusted, chosen) in [("0", "2"), ("2", "0")] {
+            router.set_reputation_scores(HashMap::from([(NodeId::new(distrusted), 0.1)]));
+            let result = router.simulate_delivery(&src, &dest, dest_coord, 20);
+            assert_eq!(result.path[1], NodeId::new(chosen));
+        }
+    }
+
+    #[test]
+    fn test_region_costs_steer_around_loaded_next_hop() {
+        let mut router = create_test_network();
+        let src = NodeId::new("1");
+        let dest = NodeId::new("4");
+        let dest_coord = router.get_node(&dest).unwrap().coord.point;
+        let region = crate::traffic_matrix::region_of(&dest_coord, 8);
+
+        for (loaded, chosen) in [("0", "2"), ("2", "0")] {
+            router.set_region_costs(HashMap::from([((NodeId::new(loaded), region), 0.5)]), 8);
+            let result = router.simulate_delivery(&src, &dest, dest_coord, 20);
+            assert!(result.success);
+            assert_eq!(result.path[1], NodeId::new(chosen));
+        }
+
+        // Costs for other regions do not apply
+        router.set_region_costs(HashMap::from([((NodeId::new("0"), (region + 1) % 8), 0.5)]), 8);
+        let result = router.simulate_delivery(&src, &dest, dest_coord, 20);
+        assert!(result.success);
+    }
+
+    #[test]
+    fn test_route_with_hint_follows_epoch() {
+        let mut router = create_test_network();
+        let src = NodeId::new("1");
+        let dest = NodeId::new("4");
+        let dest_coord = router.get_node(&dest).unwrap().coord.point;
+
+        let mut packet = PacketHeader::new(src.clone(), dest.clone(), dest_coord, 10);
+        let (decision, cacheable) = router.route_with_hint(&src, &mut packet, None);
+        let RoutingDecision::Forward { next_hop, .. } = decision else { panic!("not forwarded") };
+        assert_eq!(cacheable, Some(next_hop.clone()));
+        assert!(packet.has_visited(&src));
+
+        // A cached hop is reused without a new choice while it is a neighbor
+        let other = if next_hop == NodeId::new("0") { NodeId::new("2") } else { NodeId::new("0") };
+        let mut packet = PacketHeader::new(src.clone(), dest.clone(), dest_coord, 10);
+        let (decision, cacheable) = router.route_with_hint(&src, &mut packet, Some(&other));
+        assert!(matches!(decision, RoutingDecision::Forward { next_hop, .. } if next_hop == other));
+        assert_eq!(cacheable, None);
+        let (_, cacheable) = router.route_with_hint(&src, &mut packet, Some(&NodeId::new("9")));
+        assert_eq!(cacheable, Some(next_hop.clone()));
+
+        // Only real changes move the epoch
+        let epoch = router.epoch();
+        router.add_edge(&src, &next_hop);
+        let coord = router.get_node(&src).unwrap().coord;
+        router.set_coordinate(&src, coord);
+        assert_eq!(router.epoch(), epoch);
+        router.set_coordinate(&src, RoutingCoordinate::new(coord.point, coord.updated_at + 1));
+        router.remove_edge(&src, &next_hop);
+        assert_eq!(router.epoch(), epoch + 2);
+    }
+
+    #[test]
+    fn test_link_costs_steer_around_lossy_links() {
+        let mut router = create_test_network();
+        let src = NodeId::new("1");
+        let dest = NodeId::new("4");
+        let dest_coord = router.get_node(&dest).unwrap().coord.point;
+
+        for (lossy, chosen) in [("0", "2"), ("2", "0")] {
+            router.set_link_costs(HashMap::from([((src.clone(), NodeId::new(lossy)), 0.5)]));
+            let result = router.simulate_delivery(&src, &dest, dest_coord, 20);
+            assert!(result.success);
+            assert_eq!(result.path[1], NodeId::new(chosen));
+        }
+
+        // Costs on other nodes' links do not apply
+        router.set_link_costs(HashMap::from([((NodeId::new("3"), NodeId::new("0")), 0.5)]));
+        let result = router.simulate_delivery(&src, &dest, dest_coord, 20);
+        assert!(result.success);
+    }
+
+    #[test]
+    fn test_stale_next_hops_discounted_or_avoided() {
+        let mut router = create_test_network();
+        let src = NodeId::new("1");
+        let dest = NodeId::new("4");
+        let dest_coord = router.get_node(&dest).unwrap().coord.point;
+        for (id, epoch) in [("1", 10), ("2", 10), ("4", 10)] {
+            let point = router.get_node(&NodeId::new(id)).unwrap().coord.point;
+            router.set_coordinate(&NodeId::new(id), RoutingCoordinate::new(point, epoch));
+        }
+
+        let stats = router.staleness_stats();
+        assert_eq!(stats.newest_epoch, 10);
+        assert_eq!(stats.stale_nodes, 2);
+        assert_eq!(stats.max_lag, 10);
+        assert!((stats.mean_lag - 4.0).abs() < 1e-9);
+
+        // "0" and "2" are equally close to "4"; "0" trails by 10 epochs
+        router.set_staleness_policy(Some(StalenessPolicy::default()));
+        let result = router.simulate_delivery(&src, &dest, dest_coord, 20);
+        assert_eq!(result.path, vec![src.clone(), NodeId::new("2"), dest.clone()]);
+
+        // Avoided in Gravity, but recovery still reaches "3" behind stale "0"
+        router.remove_edge(&NodeId::new("2"), &NodeId::new("3"));
+        router.add_edge(&NodeId::new("0"), &NodeId::new("3"));
+        router.set_staleness_policy(Some(StalenessPolicy { avoid_stale: true, ..Default::default() }));
+        let result = router.simulate_delivery(&src, &dest, dest_coord, 20);
+        assert_eq!(result.path[1], NodeId::new("2"));
+        let dest = NodeId::new("3");
+        let dest_coord = router.get_node(&dest).unwrap().coord.point;
+        router.set_coordinate(&dest, RoutingCoordinate::new(dest_coord, 10));
+        let result = router.simulate_delivery(&NodeId::new("4"), &dest, dest_coord, 20);
+        assert!(result.success);
+        assert!(result.pressure_hops + result.tree_hops > 0);
+    }
+
+    #[test]
+    fn test_zone_rules_reroute_and_log() {
+        use crate::access_zones::{Zone, ZoneRegion, ZoneRule};
+
+        let mut router = create_test_network();
+        let zone = |name: &str, node: &str| Zone {
+            name: name.to_string(),
+            region: ZoneRegion::Nodes { nodes: vec![NodeId::new(node)] },
+        };
+        router.set_zones(ZoneConfig {
+            zones: vec![zone("hub", "0"), zone("guests", "1"), zone("core", "3")],
+            rules: vec![
+                ZoneRule::NoTransit { from: "guests".into(), through: "hub".into() },
+                ZoneRule::SignedControlOnly { zone: "core".into() },
+            ],
+            ..Default::default()
+        });
+
+        // Guests reach "4" around the hub
+        let (src, dest) = (NodeId::new("1"), NodeId::new("4"));
+        let dest_coord = router.get_node(&dest).unwrap().coord.point;
+        let result = router.simulate_delivery(&src, &dest, dest_coord, 20);
+        assert!(result.success);
+        assert!(!result.path.contains(&NodeId::new("0")));
+        let decision = &router.zone_decisions()[0];
+        assert_eq!((&decision.at, &decision.denied), (&src, &vec![(NodeId::new("0"), 0)]));
+        assert_eq!(decision.next_hop, Some(NodeId::new("2")));
+
+        // Only signed control packets enter the core
+        let core = NodeId::new("3");
+        let core_coord = router.get_node(&core).unwrap().coord.point;
+        let mut data = PacketHeader::new(NodeId::new("2"), core.clone(), core_coord, 10);
+        assert!(!matches!(router.route(&NodeId::new("2"), &mut data), RoutingDecision::Forward { next_hop, .. } if next_hop == core));
+        let mut control = PacketHeader::new(NodeId::new("2"), core.clone(), core_coord, 10);
+        control.access = PacketAccess { signed: true, control: true };
+        assert!(matches!(router.route(&NodeId::new("2"), &mut control), RoutingDecision::Forward { next_hop, .. } if next_hop == core));
+    }
+
+    #[test]
+    fn test_tree_repaired_after_failures() {
+        let network = crate::bench::barabasi_albert(200, 2, 3);
+        let mut router = crate::bench::build_pie_router(&network).unwrap();
+        let in_tree = |router: &GPRouter| router.nodes.values().filter(|n| n.tree_depth.is_some()).count();
+        assert_eq!(in_tree(&router), 200);
+
+        // Losing the root, then the root that replaced it, then a tree edge
+        for _ in 0..2 {
+            let root = router.nodes.values().find(|n| n.tree_depth == Some(0)).unwrap().id.clone();
+            router.remove_node(&root);
+        }
+        let child = router.nodes.values().filter(|n| n.tree_depth == Some(2) && n.neighbors.len() > 2).min_by_key(|n| &n.id).unwrap();
+        let (child, parent) = (child.id.clone(), child.tree_parent.clone().unwrap());
+        router.remove_edge(&child, &parent);
+
+        assert_eq!(router.verify_tree(), Ok(()));
+        let stats = router.tree_repair_stats();
+        assert_eq!(stats.repairs, 3);
+        assert_eq!(stats.new_roots, 2);
+        // The overlay stays connected, so every node is back in one tree
+        assert_eq!(stats.detached, 0);
+        assert_eq!(in_tree(&router), 198);
+        let root = router.nodes.values().find(|n| n.tree_depth == Some(0)).unwrap();
+        assert_eq!(root.subtree_size, 198);
+        let alive: Vec<&NodeId> = network.nodes.iter().filter(|id| router.get_node(id).is_some()).collect();
+        let (src, dest) = (alive[10].clone(), alive[150].clone());
+        let dest_coord = router.get_node(&dest).unwrap().coord.point;
+        assert!(router.simulate_delivery(&src, &dest, dest_coord, 400).success);
+    }
+
+    #[test]
+    fn test_tz_path_repaired_around_dead_entries() {
+        let network = crate::bench::barabasi_albert(120, 2, 5);
+        let mut router = crate::bench::build_pie_router(&network).unwrap();
+        let table = crate::tz_routing::TZRoutingTable::build(&network.adjacency, Default::default()).unwrap();
+        router.set_tz_table(table.clone());
+
+        // Loop-free stamped paths long enough to lose an intermediate node
+        let mut paths = network
+            .nodes
+            .iter()
+            .flat_map(|a| network.nodes.iter().map(move |b| (a, b)))
+            .filter_map(|(a, b)| table.compute_path(a, b))
+            .filter(|path| path.len() >= 5 && path.iter().collect::<HashSet<_>>().len() == path.len());
+        let follow = |router: &GPRouter, path: &[NodeId]| {
+            let destination = path.last().unwrap().clone();
+            let target = router.get_node(&destination).map_or(PoincareDiskPoint::origin(), |n| n.coord.point);
+            let mut header = PacketHeader::new(path[0].clone(), destination, target, 200);
+            header.mode = RoutingMode::ThorupZwick;
+            header.tz_path = path.to_vec();
+            let mut current = path[0].clone();
+            for _ in 0..200 {
+                match router.route(&current, &mut header) {
+                    RoutingDecision::Forward { next_hop, .. } => current = next_hop,
+                    RoutingDecision::Delivered => return true,
+                    RoutingDecision::Failed { .. } => return false,
+                }
+                header.ttl -= 1;
+            }
+            false
+        };
+
+        // A removed node is routed around
+        let path = paths.next().unwrap();
+        router.remove_node(&path[2]);
+        assert!(follow(&router, &path));
+        let stats = router.tz_path_repair_stats();
+        assert_eq!(stats.dead_entries, 1);
+        assert_eq!(stats.segments + stats.greedy, 1);
+
+        // So is an excluded one, but a dead destination cannot be repaired
+        let path = paths.find(|p| p.iter().all(|n| router.get_node(n).is_some())).unwrap();
+        router.set_excluded_nodes(HashSet::from([path[1].clone()]));
+        assert!(follow(&router, &path));
+        assert_eq!(router.tz_path_repair_stats().dead_entries, 2);
+        router.set_excluded_nodes(HashSet::new());
+        router.remove_node(path.last().unwrap());
+        assert!(!follow(&router, &path));
+        assert!(router.tz_path_repair_stats().failed > 0);
+    }
+
+    #[test]
+    fn test_greedy_toward_bridges_to_waypoint() {
+        // "y" is closer to the waypoint "w" but a dead end; "x" links to it
+        let mut router = GPRouter::new();
+        for (id, x, y) in [("c", -0.6, 0.0), ("x", -0.3, -0.5), ("y", 0.4, 0.1), ("w", 0.6, 0.0)] {
+            let coord = RoutingCoordinate::new(PoincareDiskPoint::new(x, y).unwrap(), 0);
+            router.add_node(RoutingNode::new(NodeId::new(id), coord));
+        }
+        for (a, b) in [("c", "x"), ("c", "y"), ("x", "w")] {
+            router.add_edge(&NodeId::new(a), &NodeId::new(b));
+        }
+        let w = NodeId::new("w");
+        let packet = PacketHeader::new(NodeId::new("c"), w.clone(), router.get_node(&w).unwrap().coord.point, 10);
+        let current = router.get_node(&NodeId::new("c")).unwrap();
+        assert_eq!(router.greedy_toward(current, &w, &packet), Some(NodeId::new("x")));
+
+        // Without a bridge it stays greedy
+        router.remove_edge(&NodeId::new("x"), &w);
+        let current = router.get_node(&NodeId::new("c")).unwrap();
+        assert_eq!(router.greedy_toward(current, &w, &packet), Some(NodeId::new("y")));
+    }
+
+    #[test]
+    fn test_stretch_bound_switches_to_tz_path() {
+        // Greedy follows the coordinates the long way round, s-b1-b2-b3-d,
+        // while s-a-d is two hops
+        let mut router = GPRouter::new();
+        for (id, x, y) in [
+            ("s", -0.6, 0.0),
+            ("b1", -0.2, 0.3),
+            ("b2", 0.2, 0.3),
+            ("b3", 0.5, 0.2),
+            ("d", 0.6, 0.0),
+            ("a", 0.0, -0.9),
+        ] {
+            let coord = RoutingCoordinate::new(PoincareDiskPoint::new(x, y).unwrap(), 0);
+            router.add_node(RoutingNode::new(NodeId::new(id), coord));
+        }
+        for (a, b) in [("s", "b1"), ("b1", "b2"), ("b2", "b3"), ("b3", "d"), ("s", "a"), ("a", "d")] {
+            router.add_edge(&NodeId::new(a), &NodeId::new(b));
+        }
+        let (s, d) = (NodeId::new("s"), NodeId::new("d"));
+        assert!(router.stretch_budget(&s, &d, 2.0).is_none(), "no estimate without a TZ table");
+        let table = crate::tz_routing::TZRoutingTable::build(&router.build_adjacency_map(), Default::default()).unwrap();
+        router.set_tz_table(table);
+
+        let deliver = |bound: Option<f64>| {
+            let target = router.get_node(&d).unwrap().coord.point;
+            let mut header = PacketHeader::new(s.clone(), d.clone(), target, 20);
+            header.stretch = bound.and_then(|bound| router.stretch_budget(&s, &d, bound));
+            let mut current = s.clone();
+            loop {
+                match router.route(&current, &mut header) {
+                    RoutingDecision::Forward { next_hop, .. } => current = next_hop,
+                    RoutingDecision::Delivered => return header.hops,
+                    RoutingDecision::Failed { reason } => panic!("{}", reason),
+                }
+                header.ttl -= 1;
+                header.hops += 1;
+            }
+        };
+        assert_eq!(router.stretch_budget(&s, &d, 1.5), Some(StretchBudget { estimate: 2, budget: 3 }));
+        assert_eq!(deliver(None), 4);
+        // A budget of four hops lets greedy finish; three forces the TZ path
+        assert_eq!(deliver(Some(2.0)), 4);
+        assert_eq!(deliver(Some(1.5)), 2);
+
+        let receipt = StretchReceipt { budget: StretchBudget { estimate: 2, budget: 3 }, hops: 4 };
+        assert_eq!(receipt.stretch(), 2.0);
+        assert!(!receipt.within_bound());
+        let mut stats = StretchStats::default();
+        stats.record(&receipt);
+        stats.record(&StretchReceipt { hops: 2, ..receipt });
+        assert_eq!((stats.receipts, stats.violations, stats.max_stretch, stats.mean_stretch), (2, 1, 2.0, 1.5));
+    }
+
     #[test]
     fn test_gravity_routing_success() {
         let router = create_test_network();
@@ -1012,6 +2218,57 @@ mod tests {
         assert_eq!(result.path.last().unwrap(), &dest);
     }
 
+    #[test]
+    fn test_nearest_nodes_follow_coordinate_updates() {
+        let mut router = create_test_network();
+        let target = PoincareDiskPoint::new(-0.6, -0.6).unwrap();
+        let (closest, _) = router.nearest_nodes(&target, 1)[0];
+        let closest = closest.clone();
+
+        let other = router.node_ids().into_iter().find(|id| *id != closest).unwrap();
+        assert!(router.set_coordinate(&other, RoutingCoordinate::new(PoincareDiskPoint::new(-0.61, -0.6).unwrap(), 1)));
+        assert_eq!(router.nearest_nodes(&target, 1)[0].0, &other);
+        assert_eq!(router.nodes_within(&target, 0.1).len(), 1);
+
+        router.remove_node(&other);
+        assert_eq!(router.nearest_nodes(&target, 1)[0].0, &closest);
+        assert!(!router.set_coordinate(&other, RoutingCoordinate::new(target, 2)));
+    }
+
+    #[test]
+    fn test_recovery_state_limits_and_epochs() {
+        let mut packet = PacketHeader::new(NodeId::new("0"), NodeId::new("3"), PoincareDiskPoint::origin(), 10);
+        for i in 0..10 {
+            packet.pressure_values.insert(NodeId::new(format!("n{}", i)), i as f64);
+            packet.dfs_stack.push(NodeId::new(format!("n{}", i)));
+        }
+        packet.compact_recovery_state(&RecoveryStateLimits { max_pressure_entries: 3, max_dfs_stack: 4 });
+        let mut kept: Vec<&str> = packet.pressure_values.keys().map(|n| n.0.as_str()).collect();
+        kept.sort();
+        assert_eq!(kept, vec!["n7", "n8", "n9"]);
+        assert_eq!(packet.dfs_stack.first(), Some(&NodeId::new("n6")));
+        assert_eq!(packet.dfs_stack.len(), 4);
+
+        // Leaving Pressure drops its state, Tree keeps its stack
+        packet.mode = RoutingMode::Tree;
+        packet.begin_recovery_epoch();
+        assert_eq!(packet.recovery_epoch, 1);
+        assert!(packet.pressure_values.is_empty());
+        assert_eq!(packet.dfs_stack.len(), 4);
+
+        // Switching back to Gravity at the router starts another epoch
+        let router = create_test_network();
+        let dest_coord = router.get_node(&NodeId::new("3")).unwrap().coord.point;
+        let mut packet = PacketHeader::new(NodeId::new("0"), NodeId::new("3"), dest_coord, 10);
+        packet.mode = RoutingMode::Tree;
+        packet.recovery_threshold = f64::INFINITY;
+        packet.dfs_stack.push(NodeId::new("stale"));
+        router.route(&NodeId::new("0"), &mut packet);
+        assert_eq!(packet.mode, RoutingMode::Gravity);
+        assert_eq!(packet.recovery_epoch, 1);
+        assert!(packet.dfs_stack.is_empty());
+    }
+
     #[test]
     fn test_routing_self() {
         let router = create_test_network();
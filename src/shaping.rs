@@ -0,0 +1,385 @@
+//! Egress Traffic Shaping
+//!
+//! A relay on a metered or residential uplink needs to bound how much it
+//! forwards. Each neighbor link can get a token bucket with a rate and a
+//! burst, and each QoS class an aggregate bucket across all links. A packet
+//! goes out when both its link and its class have tokens; when they are
+//! short it waits for the deficit to refill, up to `max_delay_ms`, and is
+//! dropped beyond that. Sent, delayed and dropped traffic is counted per
+//! link and class for telemetry.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::ttl_policy::QosClass;
+
+/// Sustained rate and burst of a token bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub rate_bytes_per_sec: u64,
+    pub burst_bytes: u64,
+}
+
+impl RateLimit {
+    fn validate(&self) -> Result<(), String> {
+        if self.rate_bytes_per_sec == 0 || self.burst_bytes == 0 {
+            return Err("Shaping rates and bursts must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Cap of one neighbor link
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerCap {
+    pub peer: NodeId,
+    #[serde(flatten)]
+    pub limit: RateLimit,
+}
+
+/// Aggregate cap of one QoS class
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassCap {
+    pub class: QosClass,
+    #[serde(flatten)]
+    pub limit: RateLimit,
+}
+
+/// Egress shaping settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShapingConfig {
+    pub enabled: bool,
+    /// Cap of links without a rule in `peers`; None leaves them uncapped
+    pub default_peer: Option<RateLimit>,
+    /// Per-neighbor overrides
+    pub peers: Vec<PeerCap>,
+    /// Aggregate caps per QoS class; classes without one are uncapped
+    pub classes: Vec<ClassCap>,
+    /// Control packets skip the link caps so the overlay stays connected
+    pub exempt_control: bool,
+    /// Longest a packet waits for tokens before it is dropped
+    pub max_delay_ms: u64,
+}
+
+impl Default for ShapingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            default_peer: None,
+            peers: Vec::new(),
+            classes: Vec::new(),
+            exempt_control: true,
+            max_delay_ms: 50,
+        }
+    }
+}
+
+impl ShapingConfig {
+    /// Cap of the link to `peer`
+    pub fn peer_limit(&self, peer: &NodeId) -> Option<RateLimit> {
+        self.peers
+            .iter()
+            .find(|cap| &cap.peer == peer)
+            .map(|cap| cap.limit)
+            .or(self.default_peer)
+    }
+
+    /// Aggregate cap of `class`
+    pub fn class_limit(&self, class: QosClass) -> Option<RateLimit> {
+        self.classes.iter().find(|cap| cap.class == class).map(|cap| cap.limit)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.default_peer
+            .iter()
+            .chain(self.peers.iter().map(|cap| &cap.limit))
+            .chain(self.classes.iter().map(|cap| &cap.limit))
+            .try_for_each(RateLimit::validate)
+    }
+}
+
+/// Token bucket with a usage meter
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    limit: RateLimit,
+    /// Negative while packets that waited for the deficit are in flight
+    tokens: f64,
+    last_ms: u64,
+    window_start_ms: u64,
+    window_bytes: u64,
+    /// Bytes per second sent over the last full window
+    usage: f64,
+}
+
+/// Length of the usage measurement window
+const USAGE_WINDOW_MS: u64 = 1000;
+
+impl TokenBucket {
+    fn new(limit: RateLimit, now_ms: u64) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst_bytes as f64,
+            last_ms: now_ms,
+            window_start_ms: now_ms,
+            window_bytes: 0,
+            usage: 0.0,
+        }
+    }
+
+    fn refill(&mut self, now_ms: u64) {
+        let elapsed = now_ms.saturating_sub(self.last_ms) as f64 / 1000.0;
+        self.tokens = (self.tokens + elapsed * self.limit.rate_bytes_per_sec as f64).min(self.limit.burst_bytes as f64);
+        self.last_ms = self.last_ms.max(now_ms);
+    }
+
+    /// Time until `bytes` can be sent
+    fn wait_for(&mut self, bytes: u64, now_ms: u64) -> Duration {
+        self.refill(now_ms);
+        let deficit = (bytes as f64 - self.tokens).max(0.0);
+        Duration::from_secs_f64(deficit / self.limit.rate_bytes_per_sec as f64)
+    }
+
+    fn take(&mut self, bytes: u64, now_ms: u64) {
+        self.tokens -= bytes as f64;
+        if now_ms.saturating_sub(self.window_start_ms) >= USAGE_WINDOW_MS {
+            let elapsed = now_ms - self.window_start_ms;
+            self.usage = self.window_bytes as f64 * 1000.0 / elapsed as f64;
+            self.window_start_ms = now_ms;
+            self.window_bytes = 0;
+        }
+        self.window_bytes += bytes;
+    }
+}
+
+/// Outcome of shaping one packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShapingDecision {
+    Send,
+    /// Send after waiting this long
+    Delay(Duration),
+    /// Over the cap by more than `max_delay_ms`
+    Drop,
+}
+
+/// Traffic through one bucket
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ShapingCounters {
+    pub sent_packets: u64,
+    pub sent_bytes: u64,
+    /// Sent packets that waited for tokens
+    pub delayed_packets: u64,
+    pub dropped_packets: u64,
+    pub dropped_bytes: u64,
+}
+
+impl ShapingCounters {
+    fn record(&mut self, decision: ShapingDecision, bytes: u64) {
+        match decision {
+            ShapingDecision::Send => {}
+            ShapingDecision::Delay(_) => self.delayed_packets += 1,
+            ShapingDecision::Drop => {
+                self.dropped_packets += 1;
+                self.dropped_bytes += bytes;
+                return;
+            }
+        }
+        self.sent_packets += 1;
+        self.sent_bytes += bytes;
+    }
+}
+
+/// State of one capped link or class
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BucketUsage {
+    pub limit: RateLimit,
+    pub counters: ShapingCounters,
+    /// Bytes that could be sent right now without waiting
+    pub available_bytes: f64,
+    /// Bytes per second sent over the last measurement window
+    pub usage_bytes_per_sec: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerUsage {
+    pub peer: NodeId,
+    #[serde(flatten)]
+    pub usage: BucketUsage,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassUsage {
+    pub class: QosClass,
+    #[serde(flatten)]
+    pub usage: BucketUsage,
+}
+
+/// Usage of every capped link and class
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ShapingSnapshot {
+    pub peers: Vec<PeerUsage>,
+    pub classes: Vec<ClassUsage>,
+}
+
+/// Egress shaper of a node
+#[derive(Debug, Clone, Default)]
+pub struct TrafficShaper {
+    config: ShapingConfig,
+    peers: HashMap<NodeId, (TokenBucket, ShapingCounters)>,
+    classes: HashMap<QosClass, (TokenBucket, ShapingCounters)>,
+}
+
+impl TrafficShaper {
+    pub fn new(config: ShapingConfig) -> Self {
+        Self { config, ..Self::default() }
+    }
+
+    pub fn config(&self) -> &ShapingConfig {
+        &self.config
+    }
+
+    /// Replace the configuration; buckets whose limit changed start full
+    pub fn set_config(&mut self, config: ShapingConfig) {
+        self.peers.retain(|peer, (bucket, _)| config.peer_limit(peer) == Some(bucket.limit));
+        self.classes.retain(|class, (bucket, _)| config.class_limit(*class) == Some(bucket.limit));
+        self.config = config;
+    }
+
+    /// Decide whether a packet of `bytes` to `peer` goes out now, later or not at all
+    ///
+    /// Tokens are taken from both buckets when the packet is sent or
+    /// delayed, so a delayed packet holds its place against later ones.
+    pub fn shape(&mut self, peer: &NodeId, class: QosClass, bytes: u64, now_ms: u64) -> ShapingDecision {
+        if !self.config.enabled {
+            return ShapingDecision::Send;
+        }
+        let peer_limit = if class == QosClass::Control && self.config.exempt_control {
+            None
+        } else {
+            self.config.peer_limit(peer)
+        };
+        let class_limit = self.config.class_limit(class);
+
+        let mut peer_bucket = peer_limit.map(|limit| {
+            self.peers
+                .entry(peer.clone())
+                .or_insert_with(|| (TokenBucket::new(limit, now_ms), ShapingCounters::default()))
+        });
+        let mut wait = Duration::ZERO;
+        if let Some((bucket, _)) = peer_bucket.as_deref_mut() {
+            wait = wait.max(bucket.wait_for(bytes, now_ms));
+        }
+        let mut class_bucket = class_limit.map(|limit| {
+            self.classes
+                .entry(class)
+                .or_insert_with(|| (TokenBucket::new(limit, now_ms), ShapingCounters::default()))
+        });
+        if let Some((bucket, _)) = class_bucket.as_deref_mut() {
+            wait = wait.max(bucket.wait_for(bytes, now_ms));
+        }
+
+        let decision = if wait.is_zero() {
+            ShapingDecision::Send
+        } else if wait <= Duration::from_millis(self.config.max_delay_ms) {
+            ShapingDecision::Delay(wait)
+        } else {
+            ShapingDecision::Drop
+        };
+        for (bucket, counters) in peer_bucket.into_iter().chain(class_bucket) {
+            if decision != ShapingDecision::Drop {
+                bucket.take(bytes, now_ms);
+            }
+            counters.record(decision, bytes);
+        }
+        decision
+    }
+
+    /// Forget links to peers not accepted by `keep`
+    pub fn retain_peers(&mut self, keep: impl Fn(&NodeId) -> bool) {
+        self.peers.retain(|peer, _| keep(peer));
+    }
+
+    /// Usage of every capped link and class
+    pub fn snapshot(&self, now_ms: u64) -> ShapingSnapshot {
+        let usage = |bucket: &TokenBucket, counters: &ShapingCounters| {
+            let mut bucket = bucket.clone();
+            bucket.refill(now_ms);
+            BucketUsage {
+                limit: bucket.limit,
+                counters: *counters,
+                available_bytes: bucket.tokens.max(0.0),
+                usage_bytes_per_sec: bucket.usage,
+            }
+        };
+        let mut peers: Vec<PeerUsage> = self
+            .peers
+            .iter()
+            .map(|(peer, (bucket, counters))| PeerUsage { peer: peer.clone(), usage: usage(bucket, counters) })
+            .collect();
+        peers.sort_by(|a, b| a.peer.0.cmp(&b.peer.0));
+        let mut classes: Vec<ClassUsage> = self
+            .classes
+            .iter()
+            .map(|(class, (bucket, counters))| ClassUsage { class: *class, usage: usage(bucket, counters) })
+            .collect();
+        classes.sort_by_key(|entry| entry.class as u8);
+        ShapingSnapshot { peers, classes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(rate: u64, burst: u64) -> RateLimit {
+        RateLimit { rate_bytes_per_sec: rate, burst_bytes: burst }
+    }
+
+    #[test]
+    fn test_token_bucket_delays_then_drops() {
+        let peer = NodeId::new("peer");
+        let mut shaper = TrafficShaper::new(ShapingConfig {
+            enabled: true,
+            default_peer: Some(limit(10_000, 2_000)),
+            max_delay_ms: 100,
+            ..Default::default()
+        });
+
+        // The burst goes out at once, the next 1000 bytes wait 100 ms for tokens
+        assert_eq!(shaper.shape(&peer, QosClass::Standard, 2_000, 0), ShapingDecision::Send);
+        assert_eq!(shaper.shape(&peer, QosClass::Standard, 1_000, 0), ShapingDecision::Delay(Duration::from_millis(100)));
+        assert_eq!(shaper.shape(&peer, QosClass::Standard, 500, 0), ShapingDecision::Drop);
+        // Control traffic is exempt from link caps
+        assert_eq!(shaper.shape(&peer, QosClass::Control, 5_000, 0), ShapingDecision::Send);
+        // After refilling, traffic flows again
+        assert_eq!(shaper.shape(&peer, QosClass::Standard, 500, 300), ShapingDecision::Send);
+
+        let snapshot = shaper.snapshot(300);
+        assert_eq!(snapshot.peers.len(), 1);
+        let counters = snapshot.peers[0].usage.counters;
+        assert_eq!((counters.sent_packets, counters.delayed_packets, counters.dropped_packets), (3, 1, 1));
+        assert_eq!(counters.sent_bytes, 3_500);
+        assert!(snapshot.classes.is_empty());
+    }
+
+    #[test]
+    fn test_class_cap_spans_links() {
+        let mut shaper = TrafficShaper::new(ShapingConfig {
+            enabled: true,
+            classes: vec![ClassCap { class: QosClass::Bulk, limit: limit(1_000, 1_000) }],
+            max_delay_ms: 0,
+            ..Default::default()
+        });
+        assert_eq!(shaper.shape(&NodeId::new("a"), QosClass::Bulk, 800, 0), ShapingDecision::Send);
+        assert_eq!(shaper.shape(&NodeId::new("b"), QosClass::Bulk, 800, 0), ShapingDecision::Drop);
+        assert_eq!(shaper.shape(&NodeId::new("b"), QosClass::Standard, 800, 0), ShapingDecision::Send);
+        assert_eq!(shaper.shape(&NodeId::new("b"), QosClass::Bulk, 800, 1_000), ShapingDecision::Send);
+        assert!(shaper.snapshot(1_000).peers.is_empty());
+        assert_eq!(shaper.snapshot(1_000).classes[0].usage.counters.sent_packets, 2);
+
+        assert!(ShapingConfig { default_peer: Some(limit(0, 10)), ..Default::default() }.validate().is_err());
+    }
+}
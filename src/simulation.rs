@@ -0,0 +1,282 @@
+//! Batch Routing Simulation
+//!
+//! Runs a whole routing workload (list of pairs, traffic matrix or random
+//! sample) against an immutable router in parallel and aggregates the
+//! results. Benchmarks describe the workload once instead of each carrying
+//! its own sampling, delivery and stretch bookkeeping loop.
+
+use crate::coordinates::NodeId;
+use crate::graph::{BfsScratch, CsrGraph};
+use crate::routing::{DeliveryResult, GPRouter};
+use rand::prelude::*;
+use rayon::prelude::*;
+
+/// A set of source-destination pairs to route
+#[derive(Debug, Clone)]
+pub enum Workload {
+    /// Explicit pairs, each routed once
+    Pairs(Vec<(NodeId, NodeId)>),
+    /// Traffic matrix entries: (source, destination, packet count)
+    ///
+    /// Routing is deterministic, so each entry is simulated once and weighted
+    /// by its packet count in the summary.
+    Matrix(Vec<(NodeId, NodeId, u32)>),
+    /// `count` uniformly random pairs of distinct nodes
+    Random { count: usize, seed: u64 },
+}
+
+impl Workload {
+    /// Resolve the workload into weighted pairs for `router`
+    fn resolve(&self, router: &GPRouter) -> Vec<(NodeId, NodeId, u32)> {
+        match self {
+            Workload::Pairs(pairs) => pairs.iter().map(|(s, d)| (s.clone(), d.clone(), 1)).collect(),
+            Workload::Matrix(entries) => entries.iter().filter(|e| e.2 > 0).cloned().collect(),
+            Workload::Random { count, seed } => {
+                let mut nodes = router.node_ids();
+                nodes.sort_by(|a, b| a.0.cmp(&b.0));
+                if nodes.len() < 2 {
+                    return Vec::new();
+                }
+                let mut rng = StdRng::seed_from_u64(*seed);
+                (0..*count)
+                    .map(|_| {
+                        let src = rng.gen_range(0..nodes.len());
+                        let mut dst = rng.gen_range(0..nodes.len());
+                        while dst == src {
+                            dst = rng.gen_range(0..nodes.len());
+                        }
+                        (nodes[src].clone(), nodes[dst].clone(), 1)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Batch simulation settings
+#[derive(Debug, Clone)]
+pub struct BatchConfig {
+    /// TTL per packet (default: 20 × node count)
+    pub max_ttl: Option<u32>,
+    /// Compute BFS shortest paths for stretch
+    pub compute_stretch: bool,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_ttl: None,
+            compute_stretch: true,
+        }
+    }
+}
+
+/// Result for one source-destination pair
+#[derive(Debug, Clone)]
+pub struct PairResult {
+    pub source: NodeId,
+    pub destination: NodeId,
+    /// Packet count from the workload
+    pub weight: u32,
+    pub delivery: DeliveryResult,
+    /// BFS shortest path length, if computed and reachable
+    pub optimal_hops: Option<u32>,
+}
+
+impl PairResult {
+    /// Hop stretch of a successful delivery
+    pub fn stretch(&self) -> Option<f64> {
+        match self.optimal_hops {
+            Some(opt) if opt > 0 && self.delivery.success => Some(self.delivery.hops as f64 / opt as f64),
+            _ => None,
+        }
+    }
+}
+
+/// Aggregate statistics over a batch, weighted by packet count
+#[derive(Debug, Clone, Default)]
+pub struct BatchSummary {
+    /// Simulated pairs
+    pub pairs: usize,
+    /// Packets (sum of weights)
+    pub packets: u64,
+    /// Delivered packets
+    pub delivered: u64,
+    pub success_rate: f64,
+    /// Average hops of delivered packets
+    pub avg_hops: f64,
+    /// Ratio-of-sums stretch (Σ hops / Σ optimal) over delivered packets
+    pub stretch: f64,
+    pub max_stretch: f64,
+    /// Share of hops per routing mode (percent)
+    pub gravity_pct: f64,
+    pub pressure_pct: f64,
+    pub tree_pct: f64,
+}
+
+/// Result of a batch simulation
+#[derive(Debug, Clone)]
+pub struct BatchResult {
+    pub summary: BatchSummary,
+    pub pairs: Vec<PairResult>,
+}
+
+impl GPRouter {
+    /// Simulate a whole workload in parallel
+    ///
+    /// The router is only read, so all pairs run against the same snapshot.
+    /// Target coordinates are the destinations' current routing coordinates;
+    /// pairs whose destination is unknown are reported as failed.
+    pub fn simulate_batch(&self, workload: &Workload, config: &BatchConfig) -> BatchResult {
+        let pairs = workload.resolve(self);
+        let max_ttl = config.max_ttl.unwrap_or((self.node_count() * 20) as u32);
+        let graph = config
+            .compute_stretch
+            .then(|| CsrGraph::from_adjacency(&self.build_adjacency_map()));
+
+        let results: Vec<PairResult> = pairs
+            .into_par_iter()
+            .map_init(
+                || graph.as_ref().map(|g| BfsScratch::new(g.len())),
+                |scratch, (source, destination, weight)| {
+                    let delivery = match self.get_node(&destination) {
+                        Some(dest) => self.simulate_delivery(&source, &destination, dest.coord.point, max_ttl),
+                        None => DeliveryResult {
+                            success: false,
+                            hops: 0,
+                            gravity_hops: 0,
+                            pressure_hops: 0,
+                            tree_hops: 0,
+                            path: vec![source.clone()],
+                            failure_reason: Some(format!("Unknown destination {}", destination)),
+                        },
+                    };
+
+                    let optimal_hops = match (graph.as_ref(), scratch.as_mut()) {
+                        (Some(g), Some(scratch)) if delivery.success => {
+                            match (g.index_of(&source), g.index_of(&destination)) {
+                                (Some(s), Some(d)) => g.distance(s, d, scratch),
+                                _ => None,
+                            }
+                        }
+                        _ => None,
+                    };
+
+                    PairResult {
+                        source,
+                        destination,
+                        weight,
+                        delivery,
+                        optimal_hops,
+                    }
+                },
+            )
+            .collect();
+
+        BatchResult {
+            summary: summarize(&results),
+            pairs: results,
+        }
+    }
+}
+
+fn summarize(results: &[PairResult]) -> BatchSummary {
+    let mut summary = BatchSummary {
+        pairs: results.len(),
+        ..BatchSummary::default()
+    };
+    let (mut hops, mut optimal, mut stretch_hops) = (0u64, 0u64, 0u64);
+    let (mut gravity, mut pressure, mut tree) = (0u64, 0u64, 0u64);
+
+    for r in results {
+        let w = r.weight as u64;
+        summary.packets += w;
+        if !r.delivery.success {
+            continue;
+        }
+        summary.delivered += w;
+        hops += w * r.delivery.hops as u64;
+        gravity += w * r.delivery.gravity_hops as u64;
+        pressure += w * r.delivery.pressure_hops as u64;
+        tree += w * r.delivery.tree_hops as u64;
+        if let Some(opt) = r.optimal_hops {
+            optimal += w * opt as u64;
+            stretch_hops += w * r.delivery.hops as u64;
+        }
+        if let Some(s) = r.stretch() {
+            summary.max_stretch = summary.max_stretch.max(s);
+        }
+    }
+
+    let pct = |x: u64| if hops > 0 { x as f64 / hops as f64 * 100.0 } else { 0.0 };
+    summary.success_rate = if summary.packets > 0 {
+        summary.delivered as f64 / summary.packets as f64
+    } else {
+        0.0
+    };
+    summary.avg_hops = if summary.delivered > 0 {
+        hops as f64 / summary.delivered as f64
+    } else {
+        0.0
+    };
+    summary.stretch = if optimal > 0 {
+        stretch_hops as f64 / optimal as f64
+    } else {
+        0.0
+    };
+    summary.gravity_pct = pct(gravity);
+    summary.pressure_pct = pct(pressure);
+    summary.tree_pct = pct(tree);
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinates::RoutingCoordinate;
+    use crate::routing::RoutingNode;
+    use crate::PoincareDiskPoint;
+
+    fn line_router(n: usize) -> GPRouter {
+        let mut router = GPRouter::new();
+        for i in 0..n {
+            let x = -0.8 + 1.6 * i as f64 / (n - 1) as f64;
+            let coord = RoutingCoordinate::new(PoincareDiskPoint::new(x, 0.0).unwrap(), 0);
+            router.add_node(RoutingNode::new(NodeId::new(format!("{}", i)), coord));
+        }
+        for i in 1..n {
+            router.add_edge(&NodeId::new(format!("{}", i - 1)), &NodeId::new(format!("{}", i)));
+        }
+        router
+    }
+
+    #[test]
+    fn test_batch_matches_single_delivery() {
+        let router = line_router(6);
+        let result = router.simulate_batch(&Workload::Random { count: 50, seed: 7 }, &BatchConfig::default());
+
+        assert_eq!(result.pairs.len(), 50);
+        for pair in &result.pairs {
+            let dest = router.get_node(&pair.destination).unwrap().coord.point;
+            let single = router.simulate_delivery(&pair.source, &pair.destination, dest, 120);
+            assert_eq!(single.success, pair.delivery.success);
+            assert_eq!(single.path, pair.delivery.path);
+        }
+        // Greedy routing on a line is optimal
+        assert_eq!(result.summary.success_rate, 1.0);
+        assert!((result.summary.stretch - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_traffic_matrix_weights() {
+        let router = line_router(4);
+        let id = |i: u32| NodeId::new(format!("{}", i));
+        let workload = Workload::Matrix(vec![(id(0), id(3), 3), (id(1), id(2), 1), (id(2), NodeId::new("missing"), 4)]);
+        let result = router.simulate_batch(&workload, &BatchConfig::default());
+
+        assert_eq!(result.summary.pairs, 3);
+        assert_eq!(result.summary.packets, 8);
+        assert_eq!(result.summary.delivered, 4);
+        assert!((result.summary.avg_hops - 2.5).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,611 @@
+//! Coordinated Cluster Snapshots for DRFE-R
+//!
+//! Independent per-node checkpoints can disagree with each other: node A may
+//! have checkpointed a neighbor entry for B that B dropped before its own
+//! checkpoint, or hold a stale coordinate for B. This module implements a
+//! Chandy–Lamport style snapshot over the overlay:
+//!
+//! 1. The initiator checkpoints its own state and sends a marker to every neighbor.
+//! 2. On the first marker for a snapshot, a node checkpoints its state and
+//!    forwards the marker to all its neighbors.
+//! 3. Until a marker arrives from a neighbor, coordinate updates received from
+//!    that neighbor are recorded as channel state.
+//! 4. A node's snapshot is complete once markers arrived from all neighbors.
+//!
+//! Markers travel over UDP with the coordinate updates, so they may be lost
+//! or overtaken. A node resends its marker to the neighbors whose marker it
+//! still waits for every `marker_retransmit_ms`, and gives up on the
+//! snapshot after `completion_timeout_ms`, leaving it incomplete. Each
+//! marker carries the sender's coordinate version at its checkpoint, so an
+//! update sent after the checkpoint that overtakes the marker is dropped from
+//! the channel state once the marker arrives. At most `max_retained`
+//! snapshots are kept per node, the oldest finished ones going first, and a
+//! marker arriving after the completion timeout starts no snapshot.
+//!
+//! `reconcile` turns a set of node snapshots into a mutually consistent set
+//! of checkpoints for cluster restore, keeping the channel state apart: it
+//! is delivered on top of a node's checkpoint when the node is restored.
+
+use crate::network::{CheckpointNeighbor, NodeCheckpoint, SerializablePoincareDiskPoint};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Snapshot marker delivery and retention settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnapshotConfig {
+    /// Interval at which markers are resent to neighbors not yet heard from
+    pub marker_retransmit_ms: u64,
+    /// Time after which an incomplete snapshot is given up
+    pub completion_timeout_ms: u64,
+    /// Snapshots kept per node
+    pub max_retained: usize,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self { marker_retransmit_ms: 500, completion_timeout_ms: 10_000, max_retained: 16 }
+    }
+}
+
+impl SnapshotConfig {
+    /// Whether a marker for a snapshot initiated at `initiated_ms` is too
+    /// late to start recording
+    pub fn is_expired(&self, initiated_ms: u64, now_ms: u64) -> bool {
+        now_ms.saturating_sub(initiated_ms) >= self.completion_timeout_ms
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.marker_retransmit_ms == 0 || self.completion_timeout_ms < self.marker_retransmit_ms {
+            return Err("Snapshot completion timeout must be at least the positive marker retransmit interval".to_string());
+        }
+        if self.max_retained == 0 {
+            return Err("At least one snapshot must be retained".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Marker message flowing through the overlay
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotMarker {
+    /// Snapshot identifier (unique per initiation)
+    pub snapshot_id: String,
+    /// Node that initiated the snapshot
+    pub initiator: String,
+    /// Unix time in milliseconds at which the snapshot was initiated
+    pub initiated_ms: u64,
+    /// Sender's coordinate version at its checkpoint
+    pub sender_version: u64,
+}
+
+/// Coordinate update recorded on an incoming channel during a snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelMessage {
+    /// Sending neighbor
+    pub from: String,
+    /// Announced coordinate
+    pub coord: SerializablePoincareDiskPoint,
+    /// Announced coordinate version
+    pub version: u64,
+}
+
+/// One node's part of a coordinated snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeSnapshot {
+    /// Snapshot identifier
+    pub snapshot_id: String,
+    /// Node that initiated the snapshot
+    pub initiator: String,
+    /// Unix time in milliseconds at which the snapshot was initiated
+    #[serde(default)]
+    pub initiated_ms: u64,
+    /// Address the node receives overlay traffic on
+    pub addr: String,
+    /// Local state recorded when the first marker was seen
+    pub checkpoint: NodeCheckpoint,
+    /// Messages in flight on incoming channels at snapshot time
+    pub channel_messages: Vec<ChannelMessage>,
+    /// Whether markers arrived on all incoming channels
+    pub complete: bool,
+    /// Whether the node gave up waiting for markers
+    #[serde(default)]
+    pub timed_out: bool,
+}
+
+impl NodeSnapshot {
+    /// Serialize snapshot to JSON
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize snapshot: {}", e))
+    }
+
+    /// Deserialize snapshot from JSON
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json)
+            .map_err(|e| format!("Failed to deserialize snapshot: {}", e))
+    }
+
+    /// Save snapshot to file
+    pub fn save_to_file(&self, path: &std::path::Path) -> Result<(), String> {
+        let json = self.to_json()?;
+        std::fs::write(path, json)
+            .map_err(|e| format!("Failed to write snapshot file: {}", e))
+    }
+
+    /// Load snapshot from file
+    pub fn load_from_file(path: &std::path::Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read snapshot file: {}", e))?;
+        Self::from_json(&json)
+    }
+}
+
+/// Per-node state machine for an in-progress snapshot
+#[derive(Debug, Clone)]
+pub struct SnapshotRecorder {
+    snapshot: NodeSnapshot,
+    /// Neighbors whose marker has not arrived yet
+    pending_channels: HashSet<String>,
+    started_ms: u64,
+    last_sent_ms: u64,
+}
+
+impl SnapshotRecorder {
+    /// Start recording after the local checkpoint was taken.
+    ///
+    /// `incoming` are the neighbors to wait for; `marker_from` is the neighbor
+    /// whose marker triggered the snapshot (its channel is empty by definition).
+    /// Markers are taken to be sent to all neighbors at `now_ms`.
+    pub fn new(
+        marker: SnapshotMarker,
+        addr: String,
+        checkpoint: NodeCheckpoint,
+        incoming: impl IntoIterator<Item = String>,
+        marker_from: Option<&str>,
+        now_ms: u64,
+    ) -> Self {
+        let pending_channels: HashSet<String> = incoming
+            .into_iter()
+            .filter(|id| Some(id.as_str()) != marker_from)
+            .collect();
+        let complete = pending_channels.is_empty();
+
+        Self {
+            snapshot: NodeSnapshot {
+                snapshot_id: marker.snapshot_id,
+                initiator: marker.initiator,
+                initiated_ms: marker.initiated_ms,
+                addr,
+                checkpoint,
+                channel_messages: Vec::new(),
+                complete,
+                timed_out: false,
+            },
+            pending_channels,
+            started_ms: now_ms,
+            last_sent_ms: now_ms,
+        }
+    }
+
+    /// Record a message received while the snapshot is in progress
+    pub fn record(&mut self, message: ChannelMessage) {
+        if !self.is_finished() && self.pending_channels.contains(&message.from) {
+            self.snapshot.channel_messages.push(message);
+        }
+    }
+
+    /// Close the channel from `from`, whose coordinate was at `sender_version`
+    /// when it recorded its state. Returns true once the snapshot is complete.
+    ///
+    /// Updates from `from` newer than its checkpoint overtook the marker and
+    /// belong to the next state, not the channel.
+    pub fn marker_received(&mut self, from: &str, sender_version: u64) -> bool {
+        if self.pending_channels.remove(from) {
+            self.snapshot
+                .channel_messages
+                .retain(|m| m.from != from || m.version <= sender_version);
+        }
+        self.snapshot.complete = self.pending_channels.is_empty();
+        self.snapshot.complete
+    }
+
+    /// Whether markers arrived on all channels
+    pub fn is_complete(&self) -> bool {
+        self.snapshot.complete
+    }
+
+    /// Whether the snapshot completed or was given up
+    pub fn is_finished(&self) -> bool {
+        self.snapshot.complete || self.snapshot.timed_out
+    }
+
+    /// When recording started
+    pub fn started_ms(&self) -> u64 {
+        self.started_ms
+    }
+
+    /// Marker this node sends for the snapshot
+    pub fn marker(&self) -> SnapshotMarker {
+        SnapshotMarker {
+            snapshot_id: self.snapshot.snapshot_id.clone(),
+            initiator: self.snapshot.initiator.clone(),
+            initiated_ms: self.snapshot.initiated_ms,
+            sender_version: self.snapshot.checkpoint.coord_version,
+        }
+    }
+
+    /// Neighbors to resend the marker to, if a retransmit is due at `now_ms`
+    ///
+    /// Gives the snapshot up instead once `completion_timeout_ms` passed.
+    pub fn poll(&mut self, config: &SnapshotConfig, now_ms: u64) -> Vec<String> {
+        if self.is_finished() {
+            return Vec::new();
+        }
+        if now_ms.saturating_sub(self.started_ms) >= config.completion_timeout_ms {
+            self.snapshot.timed_out = true;
+            return Vec::new();
+        }
+        if now_ms.saturating_sub(self.last_sent_ms) < config.marker_retransmit_ms {
+            return Vec::new();
+        }
+        self.last_sent_ms = now_ms;
+        self.pending_channels()
+    }
+
+    /// Neighbors whose marker is still outstanding
+    pub fn pending_channels(&self) -> Vec<String> {
+        let mut pending: Vec<String> = self.pending_channels.iter().cloned().collect();
+        pending.sort();
+        pending
+    }
+
+    /// Current (possibly incomplete) snapshot
+    pub fn snapshot(&self) -> &NodeSnapshot {
+        &self.snapshot
+    }
+}
+
+/// Drop the oldest snapshots beyond `max_retained`, finished ones first
+///
+/// # Returns
+/// IDs of the snapshots dropped
+pub fn prune(recorders: &mut HashMap<String, SnapshotRecorder>, max_retained: usize) -> Vec<String> {
+    let excess = recorders.len().saturating_sub(max_retained);
+    let mut by_age: Vec<(bool, u64, String)> = recorders
+        .iter()
+        .map(|(id, r)| (!r.is_finished(), r.started_ms(), id.clone()))
+        .collect();
+    by_age.sort();
+    let dropped: Vec<String> = by_age.into_iter().take(excess).map(|(_, _, id)| id).collect();
+    for id in &dropped {
+        recorders.remove(id);
+    }
+    dropped
+}
+
+/// Result of reconciling a set of node snapshots
+#[derive(Debug, Clone)]
+pub struct ClusterRestorePlan {
+    /// Snapshot identifier
+    pub snapshot_id: String,
+    /// Reconciled checkpoint per node
+    pub checkpoints: HashMap<String, NodeCheckpoint>,
+    /// Messages in flight to each node at snapshot time, from nodes in the snapshot
+    pub channel_state: HashMap<String, Vec<ChannelMessage>>,
+    /// Links removed because they pointed outside the snapshot or could not be made symmetric
+    pub dropped_links: Vec<(String, String)>,
+    /// One-sided links that were completed on the other side
+    pub repaired_links: Vec<(String, String)>,
+    /// Nodes whose snapshot did not receive all markers
+    pub incomplete: Vec<String>,
+}
+
+impl ClusterRestorePlan {
+    /// Checkpoint to restore `node_id` from: its local state with the
+    /// messages that were in flight to it delivered
+    pub fn restored_checkpoint(&self, node_id: &str) -> Option<NodeCheckpoint> {
+        let mut checkpoint = self.checkpoints.get(node_id)?.clone();
+        for message in self.channel_state.get(node_id).into_iter().flatten() {
+            if let Some(n) = checkpoint.neighbors.iter_mut().find(|n| n.id == message.from) {
+                if message.version > n.version {
+                    n.coord = message.coord;
+                    n.version = message.version;
+                }
+            }
+        }
+        Some(checkpoint)
+    }
+}
+
+/// Reconcile node snapshots into mutually consistent checkpoints
+///
+/// - Keeps each node's recorded view of its neighbors, and the channel
+///   state apart from it
+/// - Drops neighbor entries and channel messages for nodes not in the snapshot set
+/// - Makes neighbor lists symmetric (adding the reverse link when the address is known)
+pub fn reconcile(snapshots: Vec<NodeSnapshot>) -> Result<ClusterRestorePlan, String> {
+    let snapshot_id = match snapshots.first() {
+        Some(s) => s.snapshot_id.clone(),
+        None => return Err("No snapshots to reconcile".to_string()),
+    };
+    if let Some(other) = snapshots.iter().find(|s| s.snapshot_id != snapshot_id) {
+        return Err(format!(
+            "Mixed snapshots: {} and {}",
+            snapshot_id, other.snapshot_id
+        ));
+    }
+
+    let mut incomplete: Vec<String> = snapshots
+        .iter()
+        .filter(|s| !s.complete)
+        .map(|s| s.checkpoint.node_id.clone())
+        .collect();
+    incomplete.sort();
+
+    // Own coordinates are authoritative
+    let own: HashMap<String, (SerializablePoincareDiskPoint, u64)> = snapshots
+        .iter()
+        .map(|s| (s.checkpoint.node_id.clone(), (s.checkpoint.coord, s.checkpoint.coord_version)))
+        .collect();
+
+    // Known addresses of each node: self-reported, else as seen by its neighbors
+    let mut addresses: HashMap<String, String> = snapshots
+        .iter()
+        .map(|s| (s.checkpoint.node_id.clone(), s.addr.clone()))
+        .collect();
+    for s in &snapshots {
+        for n in &s.checkpoint.neighbors {
+            addresses.entry(n.id.clone()).or_insert_with(|| n.addr.clone());
+        }
+    }
+
+    let mut checkpoints: HashMap<String, NodeCheckpoint> = HashMap::new();
+    let mut channel_state: HashMap<String, Vec<ChannelMessage>> = HashMap::new();
+    let mut dropped_links = Vec::new();
+
+    for s in snapshots {
+        let mut checkpoint = s.checkpoint;
+        let node_id = checkpoint.node_id.clone();
+
+        let in_flight: Vec<ChannelMessage> = s
+            .channel_messages
+            .into_iter()
+            .filter(|m| own.contains_key(&m.from))
+            .collect();
+        if !in_flight.is_empty() {
+            channel_state.insert(node_id.clone(), in_flight);
+        }
+
+        checkpoint.neighbors.retain(|n| {
+            let keep = own.contains_key(&n.id);
+            if !keep {
+                dropped_links.push((node_id.clone(), n.id.clone()));
+            }
+            keep
+        });
+
+        checkpoints.insert(node_id, checkpoint);
+    }
+
+    // Symmetry: every link a -> b must have b -> a
+    let links: Vec<(String, String)> = checkpoints
+        .values()
+        .flat_map(|c| c.neighbors.iter().map(move |n| (c.node_id.clone(), n.id.clone())))
+        .collect();
+    let mut repaired_links = Vec::new();
+    for (a, b) in links {
+        let has_reverse = checkpoints[&b].neighbors.iter().any(|n| n.id == a);
+        if has_reverse {
+            continue;
+        }
+        match addresses.get(&a) {
+            Some(addr) => {
+                let (coord, version) = own[&a];
+                if let Some(target) = checkpoints.get_mut(&b) {
+                    target.neighbors.push(CheckpointNeighbor {
+                        id: a.clone(),
+                        coord,
+                        addr: addr.clone(),
+                        version,
+                    });
+                }
+                repaired_links.push((b, a));
+            }
+            None => {
+                if let Some(source) = checkpoints.get_mut(&a) {
+                    source.neighbors.retain(|n| n.id != b);
+                }
+                dropped_links.push((a, b));
+            }
+        }
+    }
+
+    dropped_links.sort();
+    repaired_links.sort();
+
+    Ok(ClusterRestorePlan {
+        snapshot_id,
+        checkpoints,
+        channel_state,
+        dropped_links,
+        repaired_links,
+        incomplete,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint(id: &str, version: u64, neighbors: &[(&str, u64)]) -> NodeCheckpoint {
+        NodeCheckpoint {
+            node_id: id.to_string(),
+            coord: SerializablePoincareDiskPoint { x: 0.1, y: 0.0 },
+            coord_version: version,
+            neighbors: neighbors
+                .iter()
+                .map(|(n, v)| CheckpointNeighbor {
+                    id: n.to_string(),
+                    coord: SerializablePoincareDiskPoint { x: 0.0, y: 0.0 },
+                    addr: format!("127.0.0.1:9{:03}", n.len()),
+                    version: *v,
+                })
+                .collect(),
+            timestamp: 0,
+            version: NodeCheckpoint::VERSION,
+        }
+    }
+
+    fn marker() -> SnapshotMarker {
+        SnapshotMarker {
+            snapshot_id: "s1".to_string(),
+            initiator: "a".to_string(),
+            initiated_ms: 0,
+            sender_version: 0,
+        }
+    }
+
+    fn message(from: &str, version: u64) -> ChannelMessage {
+        ChannelMessage {
+            from: from.to_string(),
+            coord: SerializablePoincareDiskPoint { x: 0.1 * version as f64 / 10.0, y: 0.0 },
+            version,
+        }
+    }
+
+    #[test]
+    fn test_recorder_completes_after_all_markers() {
+        let mut recorder = SnapshotRecorder::new(
+            marker(),
+            "127.0.0.1:9002".to_string(),
+            checkpoint("b", 1, &[("a", 0), ("c", 0)]),
+            vec!["a".to_string(), "c".to_string()],
+            Some("a"),
+            0,
+        );
+        assert!(!recorder.is_complete());
+        assert_eq!(recorder.pending_channels(), vec!["c".to_string()]);
+
+        // Messages on closed channels are not part of the snapshot
+        recorder.record(message("a", 5));
+        recorder.record(message("c", 7));
+        // Sent by c after its checkpoint, overtaking c's marker
+        recorder.record(message("c", 9));
+        assert!(recorder.marker_received("c", 8));
+        assert_eq!(recorder.snapshot().channel_messages.len(), 1);
+        assert_eq!(recorder.snapshot().channel_messages[0].from, "c");
+        assert_eq!(recorder.snapshot().channel_messages[0].version, 7);
+    }
+
+    #[test]
+    fn test_markers_resent_until_timeout_and_old_snapshots_pruned() {
+        let config = SnapshotConfig { marker_retransmit_ms: 100, completion_timeout_ms: 1_000, max_retained: 2 };
+        assert!(config.validate().is_ok());
+        let recorder = |id: &str, started: u64| {
+            SnapshotRecorder::new(
+                SnapshotMarker { snapshot_id: id.to_string(), ..marker() },
+                "127.0.0.1:9002".to_string(),
+                checkpoint("b", 1, &[("a", 0), ("c", 0)]),
+                vec!["a".to_string(), "c".to_string()],
+                None,
+                started,
+            )
+        };
+
+        let mut lossy = recorder("s1", 0);
+        assert!(lossy.poll(&config, 50).is_empty());
+        assert_eq!(lossy.poll(&config, 100), vec!["a".to_string(), "c".to_string()]);
+        lossy.marker_received("a", 0);
+        assert!(lossy.poll(&config, 150).is_empty());
+        assert_eq!(lossy.poll(&config, 200), vec!["c".to_string()]);
+        // c never answers: the snapshot is given up, incomplete
+        assert!(lossy.poll(&config, 1_000).is_empty());
+        assert!(lossy.is_finished() && !lossy.is_complete());
+        assert!(lossy.snapshot().timed_out);
+        lossy.record(message("c", 3));
+        assert!(lossy.snapshot().channel_messages.is_empty());
+
+        let mut recorders = HashMap::new();
+        recorders.insert("s1".to_string(), lossy);
+        recorders.insert("s2".to_string(), recorder("s2", 500));
+        recorders.insert("s3".to_string(), recorder("s3", 2_000));
+        assert!(prune(&mut recorders, 3).is_empty());
+        // The finished snapshot goes before older running ones
+        recorders.insert("s0".to_string(), recorder("s0", 0));
+        assert_eq!(prune(&mut recorders, config.max_retained), vec!["s1".to_string(), "s0".to_string()]);
+        assert!(recorders.contains_key("s2") && recorders.contains_key("s3"));
+
+        assert!(!config.is_expired(500, 1_400) && config.is_expired(500, 1_500));
+        assert!(SnapshotConfig { marker_retransmit_ms: 0, ..config.clone() }.validate().is_err());
+        assert!(SnapshotConfig { max_retained: 0, ..config }.validate().is_err());
+    }
+
+    #[test]
+    fn test_reconcile_makes_views_consistent() {
+        let snapshots = vec![
+            NodeSnapshot {
+                snapshot_id: "s1".to_string(),
+                initiator: "a".to_string(),
+                initiated_ms: 0,
+                addr: "127.0.0.1:9001".to_string(),
+                checkpoint: checkpoint("a", 3, &[("b", 1), ("ghost", 1)]),
+                channel_messages: vec![message("b", 4), message("ghost", 2)],
+                complete: true,
+                timed_out: false,
+            },
+            NodeSnapshot {
+                snapshot_id: "s1".to_string(),
+                initiator: "a".to_string(),
+                initiated_ms: 0,
+                addr: "127.0.0.1:9002".to_string(),
+                checkpoint: checkpoint("b", 4, &[]),
+                channel_messages: Vec::new(),
+                complete: false,
+                timed_out: true,
+            },
+        ];
+
+        let plan = reconcile(snapshots).unwrap();
+
+        assert_eq!(plan.dropped_links, vec![("a".to_string(), "ghost".to_string())]);
+        assert_eq!(plan.repaired_links, vec![("b".to_string(), "a".to_string())]);
+        assert_eq!(plan.incomplete, vec!["b".to_string()]);
+
+        // a's own view stays as recorded, the update in flight apart from it
+        let a = &plan.checkpoints["a"];
+        assert_eq!(a.neighbors.len(), 1);
+        assert_eq!(a.neighbors[0].version, 1);
+        assert_eq!(plan.channel_state["a"].len(), 1);
+        assert_eq!(plan.channel_state["a"][0].version, 4);
+        let restored = plan.restored_checkpoint("a").unwrap();
+        assert_eq!(restored.neighbors[0].version, 4);
+        assert_eq!(restored.neighbors[0].coord.x, message("b", 4).coord.x);
+        assert_eq!(plan.restored_checkpoint("b").unwrap().neighbors[0].version, 3);
+        assert!(plan.restored_checkpoint("ghost").is_none());
+        let b = &plan.checkpoints["b"];
+        assert_eq!(b.neighbors.len(), 1);
+        assert_eq!(b.neighbors[0].id, "a");
+        assert_eq!(b.neighbors[0].addr, "127.0.0.1:9001");
+        assert_eq!(b.neighbors[0].version, 3);
+    }
+
+    #[test]
+    fn test_reconcile_rejects_mixed_snapshots() {
+        let mut second = NodeSnapshot {
+            snapshot_id: "s1".to_string(),
+            initiator: "a".to_string(),
+            initiated_ms: 0,
+            addr: "127.0.0.1:9001".to_string(),
+            checkpoint: checkpoint("a", 0, &[]),
+            channel_messages: Vec::new(),
+            complete: true,
+            timed_out: false,
+        };
+        let first = second.clone();
+        second.snapshot_id = "s2".to_string();
+
+        assert!(reconcile(vec![first, second]).is_err());
+        assert!(reconcile(Vec::new()).is_err());
+    }
+}
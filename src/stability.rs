@@ -236,6 +236,17 @@ impl DriftTracker {
         variance.sqrt() > threshold
     }
 
+    /// Stability score in [0, 1]: 1 for a node that has not moved, falling
+    /// as its average drift grows, and halved while it oscillates
+    pub fn stability_score(&self, node_id: &str) -> f64 {
+        let score = 1.0 / (1.0 + self.average_drift(node_id).unwrap_or(0.0));
+        if self.is_oscillating(node_id, 0.01) {
+            score / 2.0
+        } else {
+            score
+        }
+    }
+
     /// Get statistics for all nodes
     pub fn get_stats(&self) -> StabilityStats {
         let mut total_avg_drift = 0.0;
@@ -326,6 +337,10 @@ mod tests {
         let avg = tracker.average_drift("node1");
         assert!(avg.is_some());
         assert!(avg.unwrap() > 0.0);
+
+        let score = tracker.stability_score("node1");
+        assert!(score > 0.0 && score < 1.0);
+        assert_eq!(tracker.stability_score("unknown"), 1.0);
     }
 
     #[test]
@@ -0,0 +1,651 @@
+//! Reliable Ordered Streams
+//!
+//! A lightweight stream layer on top of routed packets. Each segment is
+//! routed independently, so when the overlay path shifts mid-stream segments
+//! may arrive late, out of order or not at all. Senders number segments and
+//! retransmit on timeout; receivers keep a reordering buffer and answer each
+//! segment with a cumulative ack plus selective-ack ranges, so only the
+//! segments that were actually lost are sent again.
+//!
+//! A stream carries bytes in one direction, from the node that opened it to
+//! a peer; a bidirectional conversation uses one stream each way. The state
+//! machines here are transport-agnostic: `DistributedNode` moves the
+//! segments they produce.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+
+/// Stream settings
+#[derive(Debug, Clone)]
+pub struct StreamConfig {
+    /// Payload bytes per segment
+    pub max_segment_size: usize,
+    /// Unacknowledged segments per stream
+    pub window_segments: usize,
+    /// Retransmission timeout before the first RTT sample
+    pub initial_rto_ms: u64,
+    pub min_rto_ms: u64,
+    pub max_rto_ms: u64,
+    /// Retransmissions of one segment before the stream fails
+    pub max_retransmits: u32,
+    /// Selective-ack ranges carried per ack
+    pub max_sack_ranges: usize,
+    /// Incoming streams with no segment for this long are dropped
+    pub idle_timeout_ms: u64,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            max_segment_size: 1024,
+            window_segments: 64,
+            initial_rto_ms: 1000,
+            min_rto_ms: 200,
+            max_rto_ms: 10_000,
+            max_retransmits: 8,
+            max_sack_ranges: 4,
+            idle_timeout_ms: 60_000,
+        }
+    }
+}
+
+/// Wire format of one stream segment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamSegment {
+    /// Stream identifier, unique per opening node
+    pub stream_id: u64,
+    pub kind: SegmentKind,
+}
+
+/// Segment contents
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SegmentKind {
+    /// Stream data from the opening node
+    Data {
+        seq: u64,
+        payload: Vec<u8>,
+        /// Last segment of the stream
+        fin: bool,
+    },
+    /// Acknowledgment from the peer
+    Ack {
+        /// Next sequence number expected in order
+        cumulative: u64,
+        /// Received ranges above `cumulative`, as [start, end)
+        sack: Vec<(u64, u64)>,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct InflightSegment {
+    payload: Vec<u8>,
+    fin: bool,
+    sent_at_ms: u64,
+    retransmits: u32,
+    sacked: bool,
+}
+
+/// Sending half of a stream
+#[derive(Debug, Clone)]
+pub struct StreamSender {
+    stream_id: u64,
+    config: StreamConfig,
+    /// Bytes written but not yet segmented
+    pending: Vec<u8>,
+    closing: bool,
+    fin_sent: bool,
+    next_seq: u64,
+    inflight: BTreeMap<u64, InflightSegment>,
+    srtt_ms: Option<f64>,
+    rttvar_ms: f64,
+    failed: bool,
+    retransmissions: u64,
+}
+
+impl StreamSender {
+    pub fn new(stream_id: u64, config: StreamConfig) -> Self {
+        Self {
+            stream_id,
+            config,
+            pending: Vec::new(),
+            closing: false,
+            fin_sent: false,
+            next_seq: 0,
+            inflight: BTreeMap::new(),
+            srtt_ms: None,
+            rttvar_ms: 0.0,
+            failed: false,
+            retransmissions: 0,
+        }
+    }
+
+    /// Queue bytes for sending
+    pub fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        if self.closing {
+            return Err(format!("Stream {} is closed", self.stream_id));
+        }
+        if self.failed {
+            return Err(format!("Stream {} failed", self.stream_id));
+        }
+        self.pending.extend_from_slice(data);
+        Ok(())
+    }
+
+    /// Finish the stream once all queued bytes are sent
+    pub fn close(&mut self) {
+        self.closing = true;
+    }
+
+    /// Current retransmission timeout
+    pub fn rto_ms(&self) -> u64 {
+        let rto = match self.srtt_ms {
+            Some(srtt) => (srtt + 4.0 * self.rttvar_ms) as u64,
+            None => self.config.initial_rto_ms,
+        };
+        rto.clamp(self.config.min_rto_ms, self.config.max_rto_ms)
+    }
+
+    /// Segments to send now: timed-out retransmissions, then new data
+    pub fn poll_transmit(&mut self, now_ms: u64) -> Vec<StreamSegment> {
+        if self.failed {
+            return Vec::new();
+        }
+        let mut out = Vec::new();
+        let rto = self.rto_ms();
+
+        for (&seq, segment) in self.inflight.iter_mut() {
+            // Exponential backoff per retransmission
+            let timeout = (rto << segment.retransmits.min(16)).min(self.config.max_rto_ms);
+            if segment.sacked || now_ms.saturating_sub(segment.sent_at_ms) < timeout {
+                continue;
+            }
+            if segment.retransmits >= self.config.max_retransmits {
+                self.failed = true;
+                return Vec::new();
+            }
+            segment.retransmits += 1;
+            segment.sent_at_ms = now_ms;
+            self.retransmissions += 1;
+            out.push(StreamSegment {
+                stream_id: self.stream_id,
+                kind: SegmentKind::Data {
+                    seq,
+                    payload: segment.payload.clone(),
+                    fin: segment.fin,
+                },
+            });
+        }
+
+        while self.inflight.len() < self.config.window_segments && !self.fin_sent {
+            if self.pending.is_empty() && !self.closing {
+                break;
+            }
+            let take = self.pending.len().min(self.config.max_segment_size);
+            let payload: Vec<u8> = self.pending.drain(..take).collect();
+            let fin = self.closing && self.pending.is_empty();
+            let seq = self.next_seq;
+            self.next_seq += 1;
+            self.fin_sent = fin;
+            self.inflight.insert(
+                seq,
+                InflightSegment {
+                    payload: payload.clone(),
+                    fin,
+                    sent_at_ms: now_ms,
+                    retransmits: 0,
+                    sacked: false,
+                },
+            );
+            out.push(StreamSegment {
+                stream_id: self.stream_id,
+                kind: SegmentKind::Data { seq, payload, fin },
+            });
+        }
+        out
+    }
+
+    /// Process an ack from the peer
+    pub fn on_ack(&mut self, cumulative: u64, sack: &[(u64, u64)], now_ms: u64) {
+        let acked: Vec<u64> = self.inflight.range(..cumulative).map(|(&seq, _)| seq).collect();
+        for seq in acked {
+            if let Some(segment) = self.inflight.remove(&seq) {
+                // Karn: only unambiguous samples update the RTT estimate
+                if segment.retransmits == 0 && !segment.sacked {
+                    self.sample_rtt(now_ms.saturating_sub(segment.sent_at_ms) as f64);
+                }
+            }
+        }
+        let mut samples = Vec::new();
+        for &(start, end) in sack {
+            for (_, segment) in self.inflight.range_mut(start..end) {
+                if !segment.sacked && segment.retransmits == 0 {
+                    samples.push(now_ms.saturating_sub(segment.sent_at_ms) as f64);
+                }
+                segment.sacked = true;
+            }
+        }
+        for sample in samples {
+            self.sample_rtt(sample);
+        }
+    }
+
+    fn sample_rtt(&mut self, sample: f64) {
+        match self.srtt_ms {
+            None => {
+                self.srtt_ms = Some(sample);
+                self.rttvar_ms = sample / 2.0;
+            }
+            Some(srtt) => {
+                self.rttvar_ms = 0.75 * self.rttvar_ms + 0.25 * (srtt - sample).abs();
+                self.srtt_ms = Some(0.875 * srtt + 0.125 * sample);
+            }
+        }
+    }
+
+    /// All bytes, including the end of stream, were acknowledged
+    pub fn is_finished(&self) -> bool {
+        self.fin_sent && self.inflight.is_empty()
+    }
+
+    /// A segment exceeded the retransmission limit
+    pub fn is_failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Unacknowledged segments
+    pub fn inflight(&self) -> usize {
+        self.inflight.len()
+    }
+
+    /// Segments sent more than once
+    pub fn retransmissions(&self) -> u64 {
+        self.retransmissions
+    }
+}
+
+/// Receiving half of a stream
+#[derive(Debug, Clone, Default)]
+pub struct StreamReceiver {
+    stream_id: u64,
+    max_sack_ranges: usize,
+    /// Segments accepted at or above `next_expected`, the sender's window
+    window: u64,
+    next_expected: u64,
+    /// Out-of-order segments waiting for the gap to fill
+    reorder: BTreeMap<u64, (Vec<u8>, bool)>,
+    /// In-order bytes not yet read by the application
+    readable: Vec<u8>,
+    finished: bool,
+    /// Time the last segment arrived
+    last_seen_ms: u64,
+}
+
+impl StreamReceiver {
+    pub fn new(stream_id: u64, config: &StreamConfig) -> Self {
+        Self {
+            stream_id,
+            max_sack_ranges: config.max_sack_ranges,
+            window: config.window_segments as u64,
+            ..Self::default()
+        }
+    }
+
+    /// Accept a data segment and produce the ack to send back
+    ///
+    /// Segments beyond the window cannot come from a well-behaved sender
+    /// and are dropped rather than buffered.
+    pub fn on_data(&mut self, seq: u64, payload: Vec<u8>, fin: bool) -> StreamSegment {
+        if seq >= self.next_expected && seq - self.next_expected < self.window {
+            self.reorder.entry(seq).or_insert((payload, fin));
+        }
+        while let Some((payload, fin)) = self.reorder.remove(&self.next_expected) {
+            self.readable.extend_from_slice(&payload);
+            self.finished |= fin;
+            self.next_expected += 1;
+        }
+        self.ack()
+    }
+
+    /// Cumulative ack with the lowest selective-ack ranges
+    pub fn ack(&self) -> StreamSegment {
+        let mut sack: Vec<(u64, u64)> = Vec::new();
+        for &seq in self.reorder.keys() {
+            if let Some(range) = sack.last_mut().filter(|r| r.1 == seq) {
+                range.1 += 1;
+            } else if sack.len() == self.max_sack_ranges {
+                break;
+            } else {
+                sack.push((seq, seq + 1));
+            }
+        }
+        StreamSegment {
+            stream_id: self.stream_id,
+            kind: SegmentKind::Ack {
+                cumulative: self.next_expected,
+                sack,
+            },
+        }
+    }
+
+    /// Take all in-order bytes received so far
+    pub fn read(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.readable)
+    }
+
+    /// The sender closed the stream and every byte arrived
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Segments buffered out of order
+    pub fn buffered(&self) -> usize {
+        self.reorder.len()
+    }
+}
+
+/// All streams of one node, keyed by peer and stream ID
+#[derive(Debug, Clone, Default)]
+pub struct StreamManager {
+    pub config: StreamConfig,
+    next_stream_id: u64,
+    /// Streams we opened: (destination, stream ID)
+    outgoing: HashMap<(NodeId, u64), StreamSender>,
+    /// Streams peers opened to us: (source, stream ID)
+    incoming: HashMap<(NodeId, u64), StreamReceiver>,
+    /// Incoming streams finished and read, with their final cumulative ack
+    /// and the time they closed, so late duplicates are still acked
+    closed: HashMap<(NodeId, u64), (u64, u64)>,
+    /// Outgoing streams that failed since the last `take_failed`
+    failed: Vec<(NodeId, u64)>,
+}
+
+impl StreamManager {
+    pub fn new(config: StreamConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Open a stream to `peer`, returning its ID
+    pub fn open(&mut self, peer: &NodeId) -> u64 {
+        let stream_id = self.next_stream_id;
+        self.next_stream_id += 1;
+        self.outgoing
+            .insert((peer.clone(), stream_id), StreamSender::new(stream_id, self.config.clone()));
+        stream_id
+    }
+
+    /// Queue bytes on an outgoing stream
+    pub fn write(&mut self, peer: &NodeId, stream_id: u64, data: &[u8]) -> Result<(), String> {
+        self.sender_mut(peer, stream_id)?.write(data)
+    }
+
+    /// Close an outgoing stream after its queued bytes
+    pub fn close(&mut self, peer: &NodeId, stream_id: u64) -> Result<(), String> {
+        self.sender_mut(peer, stream_id)?.close();
+        Ok(())
+    }
+
+    fn sender_mut(&mut self, peer: &NodeId, stream_id: u64) -> Result<&mut StreamSender, String> {
+        self.outgoing
+            .get_mut(&(peer.clone(), stream_id))
+            .ok_or_else(|| format!("Unknown stream {} to {}", stream_id, peer))
+    }
+
+    /// Outgoing stream state
+    pub fn sender(&self, peer: &NodeId, stream_id: u64) -> Option<&StreamSender> {
+        self.outgoing.get(&(peer.clone(), stream_id))
+    }
+
+    /// Incoming stream state
+    pub fn receiver(&self, peer: &NodeId, stream_id: u64) -> Option<&StreamReceiver> {
+        self.incoming.get(&(peer.clone(), stream_id))
+    }
+
+    /// Incoming streams as (source, stream ID)
+    pub fn incoming_streams(&self) -> Vec<(NodeId, u64)> {
+        self.incoming.keys().cloned().collect()
+    }
+
+    /// Read in-order bytes from an incoming stream
+    ///
+    /// A finished stream is released once its last bytes are read.
+    pub fn read(&mut self, peer: &NodeId, stream_id: u64) -> Vec<u8> {
+        let key = (peer.clone(), stream_id);
+        let Some(receiver) = self.incoming.get_mut(&key) else {
+            return Vec::new();
+        };
+        let data = receiver.read();
+        if receiver.is_finished() {
+            let closed = (receiver.next_expected, receiver.last_seen_ms);
+            self.incoming.remove(&key);
+            self.closed.insert(key, closed);
+        }
+        data
+    }
+
+    /// Segments to send now, with their destination
+    ///
+    /// Finished and failed outgoing streams are dropped; failed ones are
+    /// reported by `take_failed`.
+    pub fn poll_transmit(&mut self, now_ms: u64) -> Vec<(NodeId, StreamSegment)> {
+        let mut out = Vec::new();
+        for ((peer, _), sender) in self.outgoing.iter_mut() {
+            out.extend(sender.poll_transmit(now_ms).into_iter().map(|s| (peer.clone(), s)));
+        }
+        let failed = &mut self.failed;
+        self.outgoing.retain(|key, s| {
+            if s.is_failed() {
+                failed.push(key.clone());
+            }
+            !s.is_finished() && !s.is_failed()
+        });
+        out
+    }
+
+    /// Outgoing streams that failed since the last call, as (destination, stream ID)
+    pub fn take_failed(&mut self) -> Vec<(NodeId, u64)> {
+        std::mem::take(&mut self.failed)
+    }
+
+    /// Drop incoming streams idle for longer than `idle_timeout_ms`
+    ///
+    /// Returns the streams dropped before they finished.
+    pub fn reap_idle(&mut self, now_ms: u64) -> Vec<(NodeId, u64)> {
+        let timeout = self.config.idle_timeout_ms;
+        let mut reaped = Vec::new();
+        self.incoming.retain(|key, r| {
+            let idle = now_ms.saturating_sub(r.last_seen_ms) > timeout;
+            if idle && !r.is_finished() {
+                reaped.push(key.clone());
+            }
+            !idle
+        });
+        self.closed.retain(|_, (_, closed_ms)| now_ms.saturating_sub(*closed_ms) <= timeout);
+        reaped
+    }
+
+    /// Handle a segment from `peer`, returning the ack to send back
+    pub fn on_segment(&mut self, peer: &NodeId, segment: StreamSegment, now_ms: u64) -> Option<StreamSegment> {
+        match segment.kind {
+            SegmentKind::Data { seq, payload, fin } => {
+                let key = (peer.clone(), segment.stream_id);
+                if let Some(&(cumulative, _)) = self.closed.get(&key) {
+                    // Our final ack was lost; repeat it
+                    return Some(StreamSegment {
+                        stream_id: segment.stream_id,
+                        kind: SegmentKind::Ack { cumulative, sack: Vec::new() },
+                    });
+                }
+                let config = &self.config;
+                let receiver = self
+                    .incoming
+                    .entry(key)
+                    .or_insert_with(|| StreamReceiver::new(segment.stream_id, config));
+                receiver.last_seen_ms = now_ms;
+                Some(receiver.on_data(seq, payload, fin))
+            }
+            SegmentKind::Ack { cumulative, sack } => {
+                if let Some(sender) = self.outgoing.get_mut(&(peer.clone(), segment.stream_id)) {
+                    sender.on_ack(cumulative, &sack, now_ms);
+                }
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+
+    fn small_config() -> StreamConfig {
+        StreamConfig {
+            max_segment_size: 4,
+            window_segments: 8,
+            max_retransmits: 32,
+            ..StreamConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_reorder_and_selective_ack() {
+        let mut receiver = StreamReceiver::new(0, &small_config());
+
+        let ack = receiver.on_data(1, b"bbbb".to_vec(), false);
+        assert_eq!(ack.kind, SegmentKind::Ack { cumulative: 0, sack: vec![(1, 2)] });
+        receiver.on_data(3, b"d".to_vec(), true);
+        assert_eq!(receiver.read(), b"");
+        assert_eq!(receiver.buffered(), 2);
+
+        let ack = receiver.on_data(0, b"aaaa".to_vec(), false);
+        assert_eq!(ack.kind, SegmentKind::Ack { cumulative: 2, sack: vec![(3, 4)] });
+        // Duplicates are acked again but not delivered twice
+        receiver.on_data(0, b"aaaa".to_vec(), false);
+        assert_eq!(receiver.read(), b"aaaabbbb");
+        assert!(!receiver.is_finished());
+
+        receiver.on_data(2, b"cccc".to_vec(), false);
+        assert_eq!(receiver.read(), b"ccccd");
+        assert!(receiver.is_finished());
+    }
+
+    #[test]
+    fn test_segments_beyond_window_dropped() {
+        let mut receiver = StreamReceiver::new(0, &small_config());
+
+        let ack = receiver.on_data(8, b"late".to_vec(), false);
+        assert_eq!(ack.kind, SegmentKind::Ack { cumulative: 0, sack: vec![] });
+        receiver.on_data(u64::MAX, b"huge".to_vec(), true);
+        assert_eq!(receiver.buffered(), 0);
+
+        receiver.on_data(7, b"last".to_vec(), false);
+        assert_eq!(receiver.buffered(), 1);
+    }
+
+    #[test]
+    fn test_incoming_streams_released() {
+        let peer = NodeId::new("a");
+        let mut manager = StreamManager::new(small_config());
+        let data = |seq, fin| StreamSegment { stream_id: 0, kind: SegmentKind::Data { seq, payload: b"x".to_vec(), fin } };
+
+        // Finished and read: released, but a late duplicate is still acked
+        manager.on_segment(&peer, data(0, true), 0);
+        assert_eq!(manager.read(&peer, 0), b"x");
+        assert!(manager.incoming_streams().is_empty());
+        let ack = manager.on_segment(&peer, data(0, true), 10).unwrap();
+        assert_eq!(ack.kind, SegmentKind::Ack { cumulative: 1, sack: vec![] });
+        assert!(manager.incoming_streams().is_empty());
+
+        // Abandoned halfway: reaped once idle
+        let abandoned = StreamSegment { stream_id: 1, ..data(0, false) };
+        manager.on_segment(&peer, abandoned, 0);
+        assert!(manager.reap_idle(60_000).is_empty());
+        assert_eq!(manager.reap_idle(60_001), vec![(peer.clone(), 1)]);
+        assert!(manager.incoming_streams().is_empty());
+        assert!(manager.closed.is_empty());
+    }
+
+    #[test]
+    fn test_failed_stream_reported() {
+        let peer = NodeId::new("b");
+        let config = StreamConfig { max_retransmits: 1, ..small_config() };
+        let mut manager = StreamManager::new(config);
+        let id = manager.open(&peer);
+        manager.write(&peer, id, b"lost").unwrap();
+
+        let mut now = 0;
+        while manager.sender(&peer, id).is_some() {
+            manager.poll_transmit(now);
+            now += 1000;
+        }
+        assert_eq!(manager.take_failed(), vec![(peer, id)]);
+        assert!(manager.take_failed().is_empty());
+    }
+
+    #[test]
+    fn test_sacked_segments_not_retransmitted() {
+        let mut sender = StreamSender::new(0, small_config());
+        sender.write(b"aaaabbbbcccc").unwrap();
+        sender.close();
+        assert_eq!(sender.poll_transmit(0).len(), 3);
+
+        // Segment 0 lost, 1 and 2 selectively acked
+        sender.on_ack(0, &[(1, 3)], 50);
+        let rto = sender.rto_ms();
+        let resent = sender.poll_transmit(rto);
+        assert_eq!(resent.len(), 1);
+        assert!(matches!(resent[0].kind, SegmentKind::Data { seq: 0, .. }));
+
+        sender.on_ack(3, &[], rto + 50);
+        assert!(sender.is_finished());
+        assert_eq!(sender.retransmissions(), 1);
+    }
+
+    #[test]
+    fn test_lossy_reordering_channel_delivers_in_order() {
+        let peer_a = NodeId::new("a");
+        let peer_b = NodeId::new("b");
+        let mut a = StreamManager::new(small_config());
+        let mut b = StreamManager::new(small_config());
+        let mut rng = StdRng::seed_from_u64(3);
+
+        let message: Vec<u8> = (0..200u32).map(|i| (i % 251) as u8).collect();
+        let id = a.open(&peer_b);
+        a.write(&peer_b, id, &message).unwrap();
+        a.close(&peer_b, id).unwrap();
+
+        let mut received = Vec::new();
+        let mut now = 0;
+        while a.sender(&peer_b, id).is_some() && now < 600_000 {
+            // Deliver a shuffled subset of segments, dropping 30% each way
+            let mut to_b = a.poll_transmit(now);
+            to_b.shuffle(&mut rng);
+            let mut to_a = Vec::new();
+            for (_, segment) in to_b {
+                if rng.gen_bool(0.3) {
+                    continue;
+                }
+                to_a.extend(b.on_segment(&peer_a, segment, now));
+            }
+            to_a.shuffle(&mut rng);
+            for ack in to_a {
+                if !rng.gen_bool(0.3) {
+                    a.on_segment(&peer_b, ack, now + 20);
+                }
+            }
+            received.extend(b.read(&peer_a, id));
+            now += 100;
+        }
+
+        assert!(a.sender(&peer_b, id).is_none());
+        assert_eq!(received, message);
+        // Finished and fully read, so released
+        assert!(b.incoming_streams().is_empty());
+    }
+}
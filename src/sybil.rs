@@ -1,13 +1,15 @@
 //! Sybil Attack Protection for DRFE-R
 //!
 //! Prevents single entities from creating multiple fake nodes
-//! using Proof-of-Work and trust score mechanisms.
+//! using Proof-of-Work and trust score mechanisms, and detects Sybil
+//! regions from embedding geometry and measured RTTs.
 
 use sha2::{Sha256, Digest};
 use std::collections::HashMap;
 use std::sync::RwLock;
 
 use crate::coordinates::NodeId;
+use crate::PoincareDiskPoint;
 
 /// Proof-of-Work based node ID generator
 pub struct ProofOfWork {
@@ -354,6 +356,308 @@ pub enum RegistrationResult {
     InvalidProof,
 }
 
+/// Sybil region detection settings
+#[derive(Debug, Clone)]
+pub struct SybilDetectorConfig {
+    /// Hyperbolic radius of the neighborhood used for density estimation
+    pub density_radius: f64,
+    /// Robust z-score at which a neighborhood counts as implausibly dense
+    pub density_z_threshold: f64,
+    /// Minimum number of identities in a reported region
+    pub min_region_size: usize,
+    /// Claimed links slower than this multiple of the median link RTT are inconsistent
+    pub rtt_outlier_factor: f64,
+    /// Candidates scoring at or above this are refused admission
+    pub admission_threshold: f64,
+}
+
+impl Default for SybilDetectorConfig {
+    fn default() -> Self {
+        Self {
+            density_radius: 0.3,
+            density_z_threshold: 4.0,
+            min_region_size: 4,
+            rtt_outlier_factor: 4.0,
+            admission_threshold: 0.7,
+        }
+    }
+}
+
+/// Per-node suspicion score
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SuspicionScore {
+    /// Coordinate density anomaly (0.0 to 1.0)
+    pub density: f64,
+    /// Fraction of claimed links inconsistent with measured RTTs
+    pub rtt: f64,
+    /// Combined score: 1 - (1 - density)(1 - rtt)
+    pub score: f64,
+}
+
+impl SuspicionScore {
+    fn new(density: f64, rtt: f64) -> Self {
+        Self {
+            density,
+            rtt,
+            score: 1.0 - (1.0 - density) * (1.0 - rtt),
+        }
+    }
+}
+
+/// Group of identities packed implausibly tightly in the embedding
+#[derive(Debug, Clone)]
+pub struct SybilRegion {
+    pub members: Vec<NodeId>,
+    /// Euclidean centroid of the members' coordinates
+    pub center: PoincareDiskPoint,
+    /// Largest hyperbolic distance from the center to a member
+    pub radius: f64,
+}
+
+/// Result of Sybil region detection
+#[derive(Debug, Clone, Default)]
+pub struct SybilReport {
+    pub scores: HashMap<NodeId, SuspicionScore>,
+    pub regions: Vec<SybilRegion>,
+    /// Median neighborhood size, the density baseline
+    pub density_median: f64,
+    /// Robust spread of neighborhood sizes (scaled MAD, at least 1)
+    pub density_scale: f64,
+    /// Median RTT over measured claimed links in milliseconds
+    pub median_link_rtt_ms: Option<f64>,
+}
+
+impl SybilReport {
+    /// Combined suspicion score of a node (0.0 if unknown)
+    pub fn suspicion(&self, node_id: &NodeId) -> f64 {
+        self.scores.get(node_id).map_or(0.0, |s| s.score)
+    }
+
+    /// Nodes whose score is at or above `threshold`
+    pub fn suspicious(&self, threshold: f64) -> Vec<NodeId> {
+        let mut nodes: Vec<NodeId> = self
+            .scores
+            .iter()
+            .filter(|(_, s)| s.score >= threshold)
+            .map(|(id, _)| id.clone())
+            .collect();
+        nodes.sort_by(|a, b| a.0.cmp(&b.0));
+        nodes
+    }
+
+    /// Combined scores for `GPRouter::set_suspicion_scores`
+    pub fn routing_scores(&self) -> HashMap<NodeId, f64> {
+        self.scores
+            .iter()
+            .filter(|(_, s)| s.score > 0.0)
+            .map(|(id, s)| (id.clone(), s.score))
+            .collect()
+    }
+
+    /// Record a coordinate violation for every node at or above `threshold`
+    pub fn apply_to_trust(&self, trust: &TrustManager, threshold: f64) {
+        for node in self.suspicious(threshold) {
+            trust.record_violation(&node);
+        }
+    }
+}
+
+/// Detects Sybil regions from embedding geometry and RTT measurements
+///
+/// Honest identities spread out across the disk as the embedding follows the
+/// real topology. Identities run by one entity tend to claim links to each
+/// other and end up packed into a small area, or claim adjacency to nodes
+/// that are measurably far away.
+#[derive(Debug, Clone, Default)]
+pub struct SybilDetector {
+    pub config: SybilDetectorConfig,
+    coordinates: HashMap<NodeId, PoincareDiskPoint>,
+    claims: HashMap<NodeId, Vec<NodeId>>,
+    rtts: HashMap<(NodeId, NodeId), f64>,
+}
+
+impl SybilDetector {
+    pub fn new(config: SybilDetectorConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Record a node's routing coordinate
+    pub fn observe_coordinate(&mut self, node_id: NodeId, point: PoincareDiskPoint) {
+        self.coordinates.insert(node_id, point);
+    }
+
+    /// Record the neighbor set a node claims
+    pub fn observe_neighbors(&mut self, node_id: NodeId, neighbors: Vec<NodeId>) {
+        self.claims.insert(node_id, neighbors);
+    }
+
+    /// Record a measured round-trip time between two nodes
+    pub fn observe_rtt(&mut self, a: &NodeId, b: &NodeId, rtt_ms: f64) {
+        self.rtts.insert(Self::link_key(a, b), rtt_ms);
+    }
+
+    /// Score every observed node and group dense clusters into regions
+    ///
+    /// Density is computed pairwise, O(n²) in the number of observed nodes.
+    pub fn analyze(&self) -> SybilReport {
+        let mut ids: Vec<&NodeId> = self.coordinates.keys().collect();
+        ids.sort_by(|a, b| a.0.cmp(&b.0));
+        let r = self.config.density_radius;
+
+        let close: Vec<Vec<usize>> = ids
+            .iter()
+            .enumerate()
+            .map(|(i, a)| {
+                let pa = &self.coordinates[*a];
+                (0..ids.len())
+                    .filter(|&j| j != i && pa.hyperbolic_distance(&self.coordinates[ids[j]]) <= r)
+                    .collect()
+            })
+            .collect();
+
+        let counts: Vec<f64> = close.iter().map(|c| c.len() as f64).collect();
+        let density_median = median(&counts).unwrap_or(0.0);
+        let deviations: Vec<f64> = counts.iter().map(|c| (c - density_median).abs()).collect();
+        let density_scale = (1.4826 * median(&deviations).unwrap_or(0.0)).max(1.0);
+        let z = |count: f64| (count - density_median) / density_scale;
+
+        let link_rtts: Vec<f64> = self
+            .claims
+            .iter()
+            .flat_map(|(node, neighbors)| neighbors.iter().filter_map(|n| self.rtts.get(&Self::link_key(node, n)).copied()))
+            .collect();
+        let median_link_rtt_ms = median(&link_rtts);
+
+        let mut report = SybilReport {
+            density_median,
+            density_scale,
+            median_link_rtt_ms,
+            ..SybilReport::default()
+        };
+
+        // Flagged nodes linked by proximity form candidate regions
+        let flagged: Vec<bool> = counts.iter().map(|&c| z(c) >= self.config.density_z_threshold).collect();
+        let mut in_region = vec![false; ids.len()];
+        let mut seen = vec![false; ids.len()];
+        for start in 0..ids.len() {
+            if !flagged[start] || seen[start] {
+                continue;
+            }
+            seen[start] = true;
+            let mut group = vec![start];
+            let mut head = 0;
+            while head < group.len() {
+                let i = group[head];
+                head += 1;
+                for &j in &close[i] {
+                    if flagged[j] && !seen[j] {
+                        seen[j] = true;
+                        group.push(j);
+                    }
+                }
+            }
+            if group.len() < self.config.min_region_size {
+                continue;
+            }
+
+            let points: Vec<PoincareDiskPoint> = group.iter().map(|&i| self.coordinates[ids[i]]).collect();
+            let (sx, sy) = points.iter().fold((0.0, 0.0), |(x, y), p| (x + p.x, y + p.y));
+            let center = PoincareDiskPoint::new(sx / points.len() as f64, sy / points.len() as f64)
+                .unwrap_or_else(PoincareDiskPoint::origin);
+            let radius = points.iter().map(|p| p.hyperbolic_distance(&center)).fold(0.0, f64::max);
+            for &i in &group {
+                in_region[i] = true;
+            }
+            let mut members: Vec<NodeId> = group.iter().map(|&i| ids[i].clone()).collect();
+            members.sort_by(|a, b| a.0.cmp(&b.0));
+            report.regions.push(SybilRegion { members, center, radius });
+        }
+
+        for (i, id) in ids.iter().enumerate() {
+            let density = if in_region[i] { 1.0 } else { self.density_score(z(counts[i])) };
+            let rtt = self.rtt_score(id, median_link_rtt_ms);
+            report.scores.insert((*id).clone(), SuspicionScore::new(density, rtt));
+        }
+        report
+    }
+
+    /// Score a joining node against the current report without recording it
+    ///
+    /// `link_rtts` holds measured RTTs to the neighbors the candidate claims.
+    pub fn score_candidate(
+        &self,
+        report: &SybilReport,
+        point: &PoincareDiskPoint,
+        link_rtts: &[(NodeId, f64)],
+    ) -> SuspicionScore {
+        let count = self
+            .coordinates
+            .values()
+            .filter(|p| p.hyperbolic_distance(point) <= self.config.density_radius)
+            .count() as f64;
+        let z = (count - report.density_median) / report.density_scale;
+
+        let rtt = match report.median_link_rtt_ms {
+            Some(median) if !link_rtts.is_empty() => {
+                let slow = link_rtts
+                    .iter()
+                    .filter(|(_, rtt)| *rtt > median * self.config.rtt_outlier_factor)
+                    .count();
+                slow as f64 / link_rtts.len() as f64
+            }
+            _ => 0.0,
+        };
+
+        SuspicionScore::new(self.density_score(z), rtt)
+    }
+
+    /// Whether a candidate with this score may join
+    pub fn admits(&self, score: &SuspicionScore) -> bool {
+        score.score < self.config.admission_threshold
+    }
+
+    /// Map a density z-score to 0.0 at half the threshold, 1.0 at the threshold
+    fn density_score(&self, z: f64) -> f64 {
+        let half = self.config.density_z_threshold / 2.0;
+        ((z - half) / half).clamp(0.0, 1.0)
+    }
+
+    fn rtt_score(&self, node: &NodeId, median_link_rtt_ms: Option<f64>) -> f64 {
+        let (Some(median), Some(neighbors)) = (median_link_rtt_ms, self.claims.get(node)) else {
+            return 0.0;
+        };
+        let measured: Vec<f64> = neighbors
+            .iter()
+            .filter_map(|n| self.rtts.get(&Self::link_key(node, n)).copied())
+            .collect();
+        if measured.is_empty() {
+            return 0.0;
+        }
+        let slow = measured.iter().filter(|&&rtt| rtt > median * self.config.rtt_outlier_factor).count();
+        slow as f64 / measured.len() as f64
+    }
+
+    fn link_key(a: &NodeId, b: &NodeId) -> (NodeId, NodeId) {
+        if a.0 <= b.0 {
+            (a.clone(), b.clone())
+        } else {
+            (b.clone(), a.clone())
+        }
+    }
+}
+
+fn median(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    Some(sorted[sorted.len() / 2])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -399,6 +703,63 @@ mod tests {
             panic!("Registration should succeed");
         }
     }
+
+    /// 64 honest nodes on a ring, 8 Sybils packed near (0.4, 0.4)
+    fn sybil_scenario() -> SybilDetector {
+        let mut detector = SybilDetector::new(SybilDetectorConfig::default());
+        for i in 0..64 {
+            let theta = i as f64 / 64.0 * std::f64::consts::TAU;
+            let radius = 0.3 + 0.5 * ((i * 7) % 5) as f64 / 5.0;
+            let point = PoincareDiskPoint::from_polar(radius, theta).unwrap();
+            detector.observe_coordinate(NodeId::new(format!("honest_{}", i)), point);
+        }
+        for i in 0..8 {
+            let point = PoincareDiskPoint::new(0.4 + 0.002 * i as f64, 0.4).unwrap();
+            detector.observe_coordinate(NodeId::new(format!("sybil_{}", i)), point);
+        }
+        detector
+    }
+
+    #[test]
+    fn test_dense_cluster_detected() {
+        let detector = sybil_scenario();
+        let report = detector.analyze();
+
+        assert_eq!(report.regions.len(), 1);
+        let region = &report.regions[0];
+        assert!(region.members.len() >= 8);
+        assert!(region.members.iter().all(|m| m.0.starts_with("sybil") || report.suspicion(m) == 1.0));
+        assert_eq!(report.suspicion(&NodeId::new("sybil_3")), 1.0);
+        assert!(report.suspicion(&NodeId::new("honest_32")) < 0.5);
+
+        // A candidate landing inside the region is refused, one elsewhere is not
+        let inside = detector.score_candidate(&report, &PoincareDiskPoint::new(0.405, 0.4).unwrap(), &[]);
+        let outside = detector.score_candidate(&report, &PoincareDiskPoint::new(-0.5, -0.2).unwrap(), &[]);
+        assert!(!detector.admits(&inside));
+        assert!(detector.admits(&outside));
+    }
+
+    #[test]
+    fn test_rtt_inconsistent_claims() {
+        let mut detector = sybil_scenario();
+        let id = |i: usize| NodeId::new(format!("honest_{}", i));
+        for i in 0..64 {
+            detector.observe_neighbors(id(i), vec![id((i + 1) % 64)]);
+            detector.observe_rtt(&id(i), &id((i + 1) % 64), 10.0 + (i % 3) as f64);
+        }
+        // honest_5 also claims to neighbor honest_40, which is 200 ms away
+        detector.observe_neighbors(id(5), vec![id(6), id(40)]);
+        detector.observe_rtt(&id(5), &id(40), 200.0);
+
+        let report = detector.analyze();
+        assert!((report.scores[&id(5)].rtt - 0.5).abs() < 1e-12);
+        assert_eq!(report.scores[&id(6)].rtt, 0.0);
+
+        let trust = TrustManager::new();
+        report.apply_to_trust(&trust, 0.5);
+        assert!(trust.get_trust(&id(5)) < trust.initial_trust);
+        assert_eq!(trust.get_trust(&id(6)), trust.initial_trust);
+    }
 }
 
 // Hex encoding helper
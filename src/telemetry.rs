@@ -2,10 +2,23 @@
 //!
 //! Provides distributed tracing to visualize packet routing paths
 //! in real-time across the network.
+//!
+//! Metrics can also be pushed: `PushExporter` buffers samples and posts them
+//! in batches to an InfluxDB (line protocol) or OTLP/HTTP (JSON) endpoint on
+//! an interval, for nodes behind NAT that a collector cannot scrape. The
+//! buffer is bounded; samples that do not fit while the collector is
+//! unreachable are dropped and counted.
 
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper_rustls::HttpsConnector;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+
 use crate::coordinates::NodeId;
 use crate::routing::RoutingMode;
 
@@ -309,6 +322,298 @@ pub struct TelemetryStats {
     pub avg_hop_count: f64,
 }
 
+/// One metric value with its labels
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricSample {
+    pub name: String,
+    pub labels: Vec<(String, String)>,
+    pub value: f64,
+    /// Unix timestamp in milliseconds
+    pub timestamp_ms: u64,
+}
+
+impl MetricSample {
+    pub fn new(name: impl Into<String>, value: f64, timestamp_ms: u64) -> Self {
+        Self {
+            name: name.into(),
+            labels: Vec::new(),
+            value,
+            timestamp_ms,
+        }
+    }
+
+    pub fn with_label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.push((key.into(), value.into()));
+        self
+    }
+}
+
+/// Wire format of a push exporter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// InfluxDB line protocol (`/api/v2/write` or `/write`)
+    InfluxLineProtocol,
+    /// OTLP/HTTP with JSON encoding (`/v1/metrics`)
+    OtlpJson,
+}
+
+/// Escape a measurement name or tag for line protocol
+fn escape_influx(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | ' ' | '=') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Encode samples as InfluxDB line protocol, one line per sample
+pub fn encode_influx(samples: &[MetricSample]) -> String {
+    samples
+        .iter()
+        .map(|sample| {
+            let mut line = escape_influx(&sample.name);
+            for (key, value) in &sample.labels {
+                line.push_str(&format!(",{}={}", escape_influx(key), escape_influx(value)));
+            }
+            format!("{} value={} {}\n", line, sample.value, sample.timestamp_ms * 1_000_000)
+        })
+        .collect()
+}
+
+/// Encode samples as an OTLP `ExportMetricsServiceRequest` in JSON
+///
+/// Every sample becomes a gauge data point; samples sharing a name are
+/// grouped into one metric.
+pub fn encode_otlp(samples: &[MetricSample], service_name: &str) -> serde_json::Value {
+    let mut metrics: Vec<(&str, Vec<serde_json::Value>)> = Vec::new();
+    for sample in samples {
+        let point = serde_json::json!({
+            "timeUnixNano": (sample.timestamp_ms * 1_000_000).to_string(),
+            "asDouble": sample.value,
+            "attributes": sample.labels.iter().map(|(k, v)| serde_json::json!({
+                "key": k,
+                "value": { "stringValue": v },
+            })).collect::<Vec<_>>(),
+        });
+        match metrics.iter_mut().find(|(name, _)| *name == sample.name) {
+            Some((_, points)) => points.push(point),
+            None => metrics.push((&sample.name, vec![point])),
+        }
+    }
+
+    serde_json::json!({
+        "resourceMetrics": [{
+            "resource": {
+                "attributes": [{ "key": "service.name", "value": { "stringValue": service_name } }],
+            },
+            "scopeMetrics": [{
+                "scope": { "name": "drfe_r" },
+                "metrics": metrics.into_iter().map(|(name, points)| serde_json::json!({
+                    "name": name,
+                    "gauge": { "dataPoints": points },
+                })).collect::<Vec<_>>(),
+            }],
+        }],
+    })
+}
+
+/// Push exporter settings
+#[derive(Debug, Clone)]
+pub struct PushExporterConfig {
+    /// Collector URL, e.g. `https://influx:8086/api/v2/write?org=o&bucket=b`
+    pub endpoint: String,
+    pub format: ExportFormat,
+    /// Extra request headers (e.g. `Authorization`)
+    pub headers: Vec<(String, String)>,
+    /// Time between pushes
+    pub interval_ms: u64,
+    /// Samples per request
+    pub batch_size: usize,
+    /// Samples buffered while the collector is unreachable
+    pub max_buffered: usize,
+    /// Per-request timeout
+    pub timeout_ms: u64,
+    /// `service.name` resource attribute for OTLP
+    pub service_name: String,
+}
+
+impl PushExporterConfig {
+    pub fn new(endpoint: impl Into<String>, format: ExportFormat) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            format,
+            headers: Vec::new(),
+            interval_ms: 10_000,
+            batch_size: 500,
+            max_buffered: 10_000,
+            timeout_ms: 5_000,
+            service_name: "drfe-r".to_string(),
+        }
+    }
+}
+
+/// Push exporter counters
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PushExporterStats {
+    /// Samples waiting to be sent
+    pub buffered: usize,
+    /// Samples accepted by the collector
+    pub exported: u64,
+    /// Samples dropped because the buffer was full
+    pub dropped: u64,
+    /// Requests that failed or were rejected
+    pub failed_requests: u64,
+}
+
+/// Batching metrics exporter for InfluxDB and OTLP/HTTP collectors
+///
+/// Both `http://` and `https://` endpoints are supported; TLS collectors
+/// are verified against the system's root certificates.
+pub struct PushExporter {
+    config: PushExporterConfig,
+    client: Client<HttpsConnector<HttpConnector>, Full<Bytes>>,
+    buffer: std::sync::Mutex<std::collections::VecDeque<MetricSample>>,
+    exported: std::sync::atomic::AtomicU64,
+    dropped: std::sync::atomic::AtomicU64,
+    failed_requests: std::sync::atomic::AtomicU64,
+}
+
+impl PushExporter {
+    pub fn new(config: PushExporterConfig) -> Self {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+        let tls = rustls::ClientConfig::builder().with_root_certificates(roots).with_no_client_auth();
+        let connector = hyper_rustls::HttpsConnectorBuilder::new().with_tls_config(tls).https_or_http().enable_http1().build();
+        // Pushes are seconds apart, so connections are not kept between them
+        let client = Client::builder(TokioExecutor::new()).pool_max_idle_per_host(0).build(connector);
+        Self {
+            config,
+            client,
+            buffer: std::sync::Mutex::new(std::collections::VecDeque::new()),
+            exported: std::sync::atomic::AtomicU64::new(0),
+            dropped: std::sync::atomic::AtomicU64::new(0),
+            failed_requests: std::sync::atomic::AtomicU64::new(0),
+        }
+    }
+
+    pub fn config(&self) -> &PushExporterConfig {
+        &self.config
+    }
+
+    /// Queue samples, dropping the oldest when the buffer is full
+    pub fn record(&self, samples: impl IntoIterator<Item = MetricSample>) {
+        let mut buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+        for sample in samples {
+            if buffer.len() >= self.config.max_buffered {
+                buffer.pop_front();
+                self.dropped.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }
+            buffer.push_back(sample);
+        }
+    }
+
+    pub fn stats(&self) -> PushExporterStats {
+        use std::sync::atomic::Ordering;
+        PushExporterStats {
+            buffered: self.buffer.lock().map(|b| b.len()).unwrap_or(0),
+            exported: self.exported.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            failed_requests: self.failed_requests.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Send buffered samples in batches until the buffer is empty or a request fails
+    ///
+    /// A failed batch goes back to the front of the buffer for the next push.
+    ///
+    /// # Returns
+    /// Number of samples exported
+    pub async fn flush(&self) -> Result<usize, String> {
+        use std::sync::atomic::Ordering;
+        let mut sent = 0;
+        loop {
+            let batch: Vec<MetricSample> = {
+                let mut buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+                let n = buffer.len().min(self.config.batch_size.max(1));
+                buffer.drain(..n).collect()
+            };
+            if batch.is_empty() {
+                return Ok(sent);
+            }
+
+            if let Err(e) = self.send_batch(&batch).await {
+                self.failed_requests.fetch_add(1, Ordering::Relaxed);
+                let mut buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+                for sample in batch.into_iter().rev() {
+                    if buffer.len() >= self.config.max_buffered {
+                        // Newer samples win over the retried ones
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                    } else {
+                        buffer.push_front(sample);
+                    }
+                }
+                return Err(e);
+            }
+            self.exported.fetch_add(batch.len() as u64, Ordering::Relaxed);
+            sent += batch.len();
+        }
+    }
+
+    /// Push on the configured interval, collecting samples from `source` first
+    pub fn start<F>(self: Arc<Self>, source: F) -> tokio::task::JoinHandle<()>
+    where
+        F: Fn() -> Vec<MetricSample> + Send + Sync + 'static,
+    {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_millis(self.config.interval_ms));
+            loop {
+                interval.tick().await;
+                self.record(source());
+                if let Err(e) = self.flush().await {
+                    tracing::debug!("Metrics push to {} failed: {}", self.config.endpoint, e);
+                }
+            }
+        })
+    }
+
+    async fn send_batch(&self, batch: &[MetricSample]) -> Result<(), String> {
+        let (body, content_type) = match self.config.format {
+            ExportFormat::InfluxLineProtocol => (encode_influx(batch), "text/plain; charset=utf-8"),
+            ExportFormat::OtlpJson => (encode_otlp(batch, &self.config.service_name).to_string(), "application/json"),
+        };
+        let timeout = std::time::Duration::from_millis(self.config.timeout_ms);
+        let status = tokio::time::timeout(timeout, self.post(content_type, body))
+            .await
+            .map_err(|_| "Collector request timed out".to_string())??;
+        if (200..300).contains(&status) {
+            Ok(())
+        } else {
+            Err(format!("Collector responded with status {}", status))
+        }
+    }
+
+    /// POST `body` to the collector, returning the response status
+    async fn post(&self, content_type: &str, body: String) -> Result<u16, String> {
+        let mut request = hyper::Request::post(self.config.endpoint.as_str()).header(hyper::header::CONTENT_TYPE, content_type);
+        for (name, value) in &self.config.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        let request = request
+            .body(Full::new(Bytes::from(body)))
+            .map_err(|e| format!("Invalid collector request to {}: {}", self.config.endpoint, e))?;
+        let response = self
+            .client
+            .request(request)
+            .await
+            .map_err(|e| format!("Collector request to {} failed: {}", self.config.endpoint, e))?;
+        Ok(response.status().as_u16())
+    }
+}
+
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -339,4 +644,82 @@ mod tests {
         assert_eq!(stats.completed_traces, 1);
         assert_eq!(stats.delivered_count, 1);
     }
+
+    #[test]
+    fn test_metric_encoding() {
+        let samples = vec![
+            MetricSample::new("drfe_neighbors", 4.0, 1_000).with_label("node", "a b"),
+            MetricSample::new("drfe_neighbors", 5.0, 2_000).with_label("node", "c"),
+        ];
+        assert_eq!(
+            encode_influx(&samples),
+            "drfe_neighbors,node=a\\ b value=4 1000000000\ndrfe_neighbors,node=c value=5 2000000000\n"
+        );
+
+        let otlp = encode_otlp(&samples, "test");
+        let metrics = &otlp["resourceMetrics"][0]["scopeMetrics"][0]["metrics"];
+        assert_eq!(metrics.as_array().unwrap().len(), 1);
+        assert_eq!(metrics[0]["gauge"]["dataPoints"][1]["asDouble"], 5.0);
+    }
+
+    #[tokio::test]
+    async fn test_push_exporter_batches_and_drops() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let mut bodies = Vec::new();
+            for _ in 0..2 {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = vec![0u8; 64 * 1024];
+                let mut len = 0;
+                // Read until the declared body has arrived
+                loop {
+                    len += socket.read(&mut request[len..]).await.unwrap();
+                    let text = String::from_utf8_lossy(&request[..len]).to_string();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let declared: usize = head
+                            .lines()
+                            .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length: ").map(str::to_string))
+                            .and_then(|v| v.parse().ok())
+                            .unwrap();
+                        if body.len() >= declared {
+                            bodies.push(body.to_string());
+                            break;
+                        }
+                    }
+                }
+                socket.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").await.unwrap();
+            }
+            bodies
+        });
+
+        let mut config = PushExporterConfig::new(format!("http://{}/write", addr), ExportFormat::InfluxLineProtocol);
+        config.batch_size = 2;
+        config.max_buffered = 3;
+        let exporter = PushExporter::new(config);
+        exporter.record((0..4).map(|i| MetricSample::new("m", i as f64, 1)));
+        assert_eq!(exporter.stats().dropped, 1);
+
+        assert_eq!(exporter.flush().await, Ok(3));
+        let bodies = server.await.unwrap();
+        assert_eq!(bodies.len(), 2);
+        assert!(bodies[0].starts_with("m value=1 "));
+
+        // Unreachable collector: the batch stays buffered
+        let unreachable = PushExporter::new(PushExporterConfig::new("http://127.0.0.1:1/write", ExportFormat::OtlpJson));
+        unreachable.record([MetricSample::new("m", 1.0, 1)]);
+        assert!(unreachable.flush().await.is_err());
+        let stats = unreachable.stats();
+        assert_eq!((stats.buffered, stats.failed_requests, stats.exported), (1, 1, 0));
+
+        // TLS and IPv6 collectors are reached, not rejected as URLs
+        for endpoint in ["https://127.0.0.1:1/write", "http://[::1]:1/write"] {
+            let exporter = PushExporter::new(PushExporterConfig::new(endpoint, ExportFormat::OtlpJson));
+            exporter.record([MetricSample::new("m", 1.0, 1)]);
+            let error = exporter.flush().await.unwrap_err();
+            assert!(error.contains("request to"), "{}", error);
+        }
+    }
 }
@@ -0,0 +1,316 @@
+//! Multi-Node Test Clusters
+//!
+//! Builds clusters of started `DistributedNode`s for integration tests, wired
+//! into a chosen topology:
+//!
+//! ```ignore
+//! let cluster = TestCluster::new(4).topology(Topology::Ring).start().await?;
+//! cluster.await_convergence(Duration::from_secs(5)).await?;
+//! cluster.assert_delivery(0, 2).await;
+//! cluster.partition(&[&[0, 1], &[2, 3]]).await?;
+//! cluster.heal().await;
+//! cluster.shutdown().await;
+//! ```
+//!
+//! Nodes share an in-memory transport of their own instead of binding
+//! ports, so clusters in concurrent tests never collide. Discovery
+//! broadcasts are off: links come only from the topology, so partitions
+//! stay put until healed.
+//!
+//! Enabled with the `testing` feature.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use crate::coordinates::NodeId;
+use crate::memory_transport::MemoryNetwork;
+use crate::network::{DeliveryEvent, DistributedNode, NeighborInfo, NetworkError, NetworkLayer};
+
+/// Default wait for a packet to reach its destination
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Poll interval while waiting for convergence
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// Time for the receivers to bind after starting
+const STARTUP_DELAY: Duration = Duration::from_millis(200);
+
+/// Link layout of a test cluster, over node indices
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Topology {
+    /// No links
+    Isolated,
+    /// 0 - 1 - ... - n-1
+    Line,
+    /// A line with n-1 linked back to 0
+    Ring,
+    /// Node 0 linked to every other node
+    Star,
+    /// Every pair linked
+    Full,
+    /// Explicit undirected links
+    Custom(Vec<(usize, usize)>),
+}
+
+impl Topology {
+    /// Undirected links for `n` nodes, each with the lower index first
+    pub fn links(&self, n: usize) -> Vec<(usize, usize)> {
+        let links: Vec<(usize, usize)> = match self {
+            Topology::Isolated => Vec::new(),
+            Topology::Line => (1..n).map(|i| (i - 1, i)).collect(),
+            Topology::Ring if n < 3 => Topology::Line.links(n),
+            Topology::Ring => (1..n).map(|i| (i - 1, i)).chain([(0, n - 1)]).collect(),
+            Topology::Star => (1..n).map(|i| (0, i)).collect(),
+            Topology::Full => (0..n).flat_map(|a| (a + 1..n).map(move |b| (a, b))).collect(),
+            Topology::Custom(links) => links.iter().map(|&(a, b)| (a.min(b), a.max(b))).collect(),
+        };
+        let mut seen = HashSet::new();
+        links.into_iter().filter(|&(a, b)| a != b && b < n && seen.insert((a, b))).collect()
+    }
+}
+
+/// Cluster settings, before any node is started
+#[derive(Debug, Clone)]
+pub struct TestClusterBuilder {
+    size: usize,
+    topology: Topology,
+    prefix: String,
+}
+
+impl TestClusterBuilder {
+    pub fn topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// Node IDs are `<prefix><index>`; `node` by default
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Bind, start and link every node
+    pub async fn start(self) -> Result<TestCluster, NetworkError> {
+        let network = MemoryNetwork::new();
+        let mut nodes = Vec::with_capacity(self.size);
+        let mut handles = Vec::with_capacity(self.size);
+        for i in 0..self.size {
+            let id = NodeId::new(format!("{}{}", self.prefix, i));
+            let node = Arc::new(DistributedNode::with_network(id, NetworkLayer::in_memory(&network)).await?);
+            let n = Arc::clone(&node);
+            handles.push(tokio::spawn(async move { n.start(vec![]).await }));
+            nodes.push(node);
+        }
+        tokio::time::sleep(STARTUP_DELAY).await;
+
+        let cluster = TestCluster {
+            links: self.topology.links(self.size),
+            nodes,
+            handles,
+            cut: tokio::sync::Mutex::new(Vec::new()),
+        };
+        for &(a, b) in &cluster.links {
+            cluster.connect(a, b).await;
+        }
+        Ok(cluster)
+    }
+}
+
+/// Started nodes wired into a topology
+pub struct TestCluster {
+    nodes: Vec<Arc<DistributedNode>>,
+    handles: Vec<JoinHandle<Result<(), NetworkError>>>,
+    links: Vec<(usize, usize)>,
+    /// Links removed by `partition`
+    cut: tokio::sync::Mutex<Vec<(usize, usize)>>,
+}
+
+impl TestCluster {
+    /// Describe a cluster of `size` nodes; nothing runs until `start`
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(size: usize) -> TestClusterBuilder {
+        TestClusterBuilder {
+            size,
+            topology: Topology::Isolated,
+            prefix: "node".to_string(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn node(&self, index: usize) -> &Arc<DistributedNode> {
+        &self.nodes[index]
+    }
+
+    pub fn nodes(&self) -> &[Arc<DistributedNode>] {
+        &self.nodes
+    }
+
+    pub fn id(&self, index: usize) -> NodeId {
+        self.nodes[index].id().clone()
+    }
+
+    /// Links of the topology, including any currently cut
+    pub fn links(&self) -> &[(usize, usize)] {
+        &self.links
+    }
+
+    /// Link two nodes in both directions
+    pub async fn connect(&self, a: usize, b: usize) {
+        for (from, to) in [(a, b), (b, a)] {
+            let peer = &self.nodes[to];
+            let neighbor = NeighborInfo::new(peer.id().clone(), peer.coord().await.point, peer.local_tcp_addr());
+            self.nodes[from].add_neighbor(neighbor).await;
+        }
+    }
+
+    /// Unlink two nodes in both directions
+    pub async fn disconnect(&self, a: usize, b: usize) -> Result<(), NetworkError> {
+        self.nodes[a].handle_neighbor_leave(self.nodes[b].id()).await?;
+        self.nodes[b].handle_neighbor_leave(self.nodes[a].id()).await
+    }
+
+    /// Cut every topology link between different groups
+    ///
+    /// Nodes in no group form a group of their own.
+    pub async fn partition(&self, groups: &[&[usize]]) -> Result<(), NetworkError> {
+        let group_of = |i: usize| groups.iter().position(|g| g.contains(&i));
+        let mut cut = self.cut.lock().await;
+        for &(a, b) in &self.links {
+            if group_of(a) != group_of(b) && !cut.contains(&(a, b)) {
+                self.disconnect(a, b).await?;
+                cut.push((a, b));
+            }
+        }
+        Ok(())
+    }
+
+    /// Restore every link cut by `partition`
+    pub async fn heal(&self) {
+        let cut = std::mem::take(&mut *self.cut.lock().await);
+        for (a, b) in cut {
+            self.connect(a, b).await;
+        }
+    }
+
+    /// Wait until every node's neighbors match the uncut topology
+    pub async fn await_convergence(&self, timeout: Duration) -> Result<(), NetworkError> {
+        let result = tokio::time::timeout(timeout, async {
+            while !self.converged().await {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        })
+        .await;
+        result.map_err(|_| NetworkError::Timeout)
+    }
+
+    async fn converged(&self) -> bool {
+        let cut = self.cut.lock().await.clone();
+        for (i, node) in self.nodes.iter().enumerate() {
+            let expected: HashSet<String> = self
+                .links
+                .iter()
+                .filter(|link| !cut.contains(link))
+                .filter_map(|&(a, b)| match i {
+                    _ if i == a => Some(self.nodes[b].id().0.clone()),
+                    _ if i == b => Some(self.nodes[a].id().0.clone()),
+                    _ => None,
+                })
+                .collect();
+            let actual: HashSet<String> = node.neighbors().await.into_iter().map(|n| n.id.0).collect();
+            if actual != expected {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Send a payload from `src` and wait for `dst` to receive it
+    ///
+    /// Returns the hop count of the delivered packet.
+    pub async fn deliver(&self, src: usize, dst: usize, payload: Vec<u8>, timeout: Duration) -> Result<u32, NetworkError> {
+        let mut deliveries = self.nodes[dst].subscribe_deliveries();
+        let packet_id = self.nodes[src].send_tracked_packet(self.id(dst), payload, 64).await?;
+        let received = tokio::time::timeout(timeout, async {
+            loop {
+                match deliveries.recv().await {
+                    Ok(DeliveryEvent::Delivered { packet_id: id, hops, .. }) if id == packet_id => return Some(hops),
+                    Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+        .await;
+        match received {
+            Ok(Some(hops)) => Ok(hops),
+            Ok(None) => Err(NetworkError::ConnectionClosed),
+            Err(_) => Err(NetworkError::Timeout),
+        }
+    }
+
+    /// Panic unless a packet from `src` reaches `dst`
+    pub async fn assert_delivery(&self, src: usize, dst: usize) -> u32 {
+        let payload = format!("{} -> {}", src, dst).into_bytes();
+        match self.deliver(src, dst, payload, DELIVERY_TIMEOUT).await {
+            Ok(hops) => hops,
+            Err(e) => panic!("packet from {} to {} was not delivered: {}", self.id(src), self.id(dst), e),
+        }
+    }
+
+    /// Panic if a packet from `src` reaches `dst`
+    pub async fn assert_no_delivery(&self, src: usize, dst: usize) {
+        let payload = format!("{} -> {}", src, dst).into_bytes();
+        if let Ok(hops) = self.deliver(src, dst, payload, Duration::from_millis(500)).await {
+            panic!("packet from {} to {} was delivered in {} hops", self.id(src), self.id(dst), hops);
+        }
+    }
+
+    /// Stop every node
+    pub async fn shutdown(self) {
+        for node in &self.nodes {
+            node.shutdown().await;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        for handle in self.handles {
+            handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topology_links() {
+        assert_eq!(Topology::Ring.links(4), vec![(0, 1), (1, 2), (2, 3), (0, 3)]);
+        assert_eq!(Topology::Ring.links(2), vec![(0, 1)]);
+        assert_eq!(Topology::Star.links(3), vec![(0, 1), (0, 2)]);
+        assert_eq!(Topology::Full.links(3).len(), 3);
+        assert_eq!(Topology::Custom(vec![(2, 1), (1, 2), (0, 0), (0, 9)]).links(3), vec![(1, 2)]);
+    }
+
+    #[tokio::test]
+    async fn test_partition_and_heal() {
+        let cluster = TestCluster::new(4).topology(Topology::Ring).start().await.unwrap();
+        cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(cluster.node(0).neighbor_count().await, 2);
+
+        cluster.partition(&[&[0, 1], &[2, 3]]).await.unwrap();
+        cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(cluster.node(0).neighbor_count().await, 1);
+
+        cluster.heal().await;
+        cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(cluster.node(0).neighbor_count().await, 2);
+        cluster.shutdown().await;
+    }
+}
@@ -2,11 +2,26 @@
 //!
 //! This module provides TLS encryption for all inter-node communication using rustls.
 //! It handles certificate generation, validation, and TLS configuration.
+//!
+//! Reconnecting to a peer after a transient drop resumes the previous
+//! session instead of repeating the full handshake. Servers issue TLS 1.3
+//! session tickets (PSKs) sealed under a ticket key that is replaced every
+//! `key_rotation_secs`; tickets under the previous key are still honored, so
+//! a compromised key exposes at most two rotation periods of tickets.
+//! Resumed handshakes still run an ephemeral key exchange, which keeps
+//! forward secrecy for the traffic itself. Clients cache one session per
+//! peer, keyed by the peer's `NodeId` through [`peer_server_name`].
 
-use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use crate::coordinates::NodeId;
+use rustls::client::ClientSessionMemoryCache;
+use rustls::pki_types::{CertificateDer, DnsName, PrivateKeyDer, ServerName};
+use rustls::server::{NoServerSessionStorage, ProducesTickets};
 use rustls::{ClientConfig, ServerConfig};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::io::{BufReader, Cursor};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
 /// TLS-related errors
@@ -108,6 +123,134 @@ impl TlsCertificate {
     }
 }
 
+/// TLS session resumption settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsResumptionConfig {
+    /// Issue and use session tickets; off means a full handshake every time
+    pub enabled: bool,
+    /// How long a session ticket may be used to resume, in seconds
+    pub ticket_lifetime_secs: u32,
+    /// How often servers replace their ticket key, in seconds
+    pub key_rotation_secs: u32,
+    /// Peers a client keeps a resumable session for
+    pub max_cached_peers: usize,
+    /// How long a client waits for tickets after a full handshake, in milliseconds
+    pub ticket_wait_ms: u64,
+}
+
+impl Default for TlsResumptionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ticket_lifetime_secs: 3600,
+            key_rotation_secs: 3600,
+            max_cached_peers: 256,
+            ticket_wait_ms: 50,
+        }
+    }
+}
+
+impl TlsResumptionConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.ticket_lifetime_secs == 0 || self.key_rotation_secs == 0 || self.max_cached_peers == 0 {
+            return Err("TLS resumption lifetimes and cache size must be positive".to_string());
+        }
+        // A key is kept for two periods; longer tickets would outlive it
+        if self.ticket_lifetime_secs > self.key_rotation_secs {
+            return Err("TLS ticket_lifetime_secs must not exceed key_rotation_secs".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Server name under which sessions with `peer` are cached
+///
+/// Certificates are not checked against it, so it only has to be a stable,
+/// valid DNS name per peer.
+pub fn peer_server_name(peer: &NodeId) -> ServerName<'static> {
+    let digest = Sha256::digest(peer.0.as_bytes());
+    let label: String = digest[..12].iter().map(|b| format!("{:02x}", b)).collect();
+    let name = DnsName::try_from(format!("n{}.drfe", label)).expect("hex label is a valid DNS name");
+    ServerName::DnsName(name)
+}
+
+struct TicketKeys {
+    current: Arc<dyn ProducesTickets>,
+    previous: Option<Arc<dyn ProducesTickets>>,
+    rotated_at: Instant,
+}
+
+/// Ticket sealing with a key replaced every rotation period
+///
+/// Tickets sealed under the current or the previous key open; older ones
+/// do not.
+struct RotatingTicketer {
+    lifetime: u32,
+    rotate_every: Duration,
+    keys: Mutex<TicketKeys>,
+}
+
+impl std::fmt::Debug for RotatingTicketer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RotatingTicketer").field("lifetime", &self.lifetime).field("rotate_every", &self.rotate_every).finish()
+    }
+}
+
+impl RotatingTicketer {
+    fn new(config: &TlsResumptionConfig) -> Result<Self, TlsError> {
+        Ok(Self {
+            lifetime: config.ticket_lifetime_secs,
+            rotate_every: Duration::from_secs(u64::from(config.key_rotation_secs)),
+            keys: Mutex::new(TicketKeys { current: new_ticket_key()?, previous: None, rotated_at: Instant::now() }),
+        })
+    }
+
+    /// Replace the current key if its period ended by `now`
+    fn rotate_if_due(&self, now: Instant) -> Result<(), TlsError> {
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        let elapsed = now.saturating_duration_since(keys.rotated_at);
+        if elapsed >= self.rotate_every {
+            // Two periods without traffic leave no key worth keeping
+            let fresh = new_ticket_key()?;
+            keys.previous = (elapsed < self.rotate_every * 2).then(|| Arc::clone(&keys.current));
+            keys.current = fresh;
+            keys.rotated_at = now;
+        }
+        Ok(())
+    }
+
+    fn keys(&self) -> (Arc<dyn ProducesTickets>, Option<Arc<dyn ProducesTickets>>) {
+        // A failed rotation keeps the old key rather than stopping resumption
+        let _ = self.rotate_if_due(Instant::now());
+        let keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        (Arc::clone(&keys.current), keys.previous.clone())
+    }
+}
+
+impl ProducesTickets for RotatingTicketer {
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    fn lifetime(&self) -> u32 {
+        self.lifetime
+    }
+
+    fn encrypt(&self, plain: &[u8]) -> Option<Vec<u8>> {
+        self.keys().0.encrypt(plain)
+    }
+
+    fn decrypt(&self, cipher: &[u8]) -> Option<Vec<u8>> {
+        let (current, previous) = self.keys();
+        current.decrypt(cipher).or_else(|| previous?.decrypt(cipher))
+    }
+}
+
+fn new_ticket_key() -> Result<Arc<dyn ProducesTickets>, TlsError> {
+    rustls::crypto::aws_lc_rs::Ticketer::new().map_err(|e| TlsError::Configuration(e.to_string()))
+}
+
 /// TLS configuration for DRFE-R nodes
 pub struct TlsConfig {
     /// Server configuration (for accepting connections)
@@ -125,15 +268,27 @@ impl TlsConfig {
     /// # Returns
     /// Result containing the TLS configuration
     pub fn new(certificate: TlsCertificate) -> Result<Self, TlsError> {
+        Self::with_resumption(certificate, &TlsResumptionConfig::default())
+    }
+
+    /// Create a TLS configuration with the given session resumption settings
+    pub fn with_resumption(certificate: TlsCertificate, resumption: &TlsResumptionConfig) -> Result<Self, TlsError> {
+        resumption.validate().map_err(TlsError::Configuration)?;
         // Create server config
         let cert_der = CertificateDer::from(certificate.cert.clone());
         let key_der = PrivateKeyDer::try_from(certificate.key.clone())
             .map_err(|e| TlsError::Configuration(format!("Invalid private key: {:?}", e)))?;
         
-        let server_config = ServerConfig::builder()
+        let mut server_config = ServerConfig::builder()
             .with_no_client_auth()
             .with_single_cert(vec![cert_der.clone()], key_der.clone_key())
             .map_err(|e| TlsError::Configuration(e.to_string()))?;
+        if resumption.enabled {
+            server_config.ticketer = Arc::new(RotatingTicketer::new(resumption)?);
+        } else {
+            server_config.session_storage = Arc::new(NoServerSessionStorage {});
+            server_config.send_tls13_tickets = 0;
+        }
         
         // Create client config (accept any certificate for now - in production, use proper CA)
         let mut client_config = ClientConfig::builder()
@@ -141,8 +296,12 @@ impl TlsConfig {
             .with_custom_certificate_verifier(Arc::new(NoVerifier))
             .with_no_client_auth();
         
-        // Enable session resumption
-        client_config.resumption = rustls::client::Resumption::default();
+        // One resumable session per peer, keyed by `peer_server_name`
+        client_config.resumption = if resumption.enabled {
+            rustls::client::Resumption::store(Arc::new(ClientSessionMemoryCache::new(resumption.max_cached_peers)))
+        } else {
+            rustls::client::Resumption::disabled()
+        };
         
         Ok(Self {
             server_config: Arc::new(server_config),
@@ -244,6 +403,24 @@ mod tests {
         assert!(Arc::strong_count(&config.client_config()) >= 1);
     }
 
+    #[test]
+    fn test_ticket_keys_rotate() {
+        let config = TlsResumptionConfig { key_rotation_secs: 60, ticket_lifetime_secs: 60, ..Default::default() };
+        let ticketer = RotatingTicketer::new(&config).unwrap();
+        let ticket = ticketer.encrypt(b"session").unwrap();
+        assert_eq!(ticketer.decrypt(&ticket).unwrap(), b"session");
+
+        // One rotation later the ticket still opens under the previous key, two later it does not
+        let start = ticketer.keys.lock().unwrap().rotated_at;
+        ticketer.rotate_if_due(start + Duration::from_secs(60)).unwrap();
+        assert_eq!(ticketer.decrypt(&ticket).unwrap(), b"session");
+        ticketer.rotate_if_due(start + Duration::from_secs(120)).unwrap();
+        assert!(ticketer.decrypt(&ticket).is_none());
+
+        assert_ne!(peer_server_name(&NodeId::new("a")), peer_server_name(&NodeId::new("b")));
+        assert!(TlsResumptionConfig { ticket_lifetime_secs: 7200, ..Default::default() }.validate().is_err());
+    }
+
     #[test]
     fn test_multiple_certificates() {
         let cert1 = TlsCertificate::generate_self_signed("node1").unwrap();
@@ -0,0 +1,253 @@
+//! Router Topology Diffs
+//!
+//! Each time a node refreshes its router from the neighbor table, the new
+//! router state is compared with the snapshot taken after the previous
+//! refresh. The resulting diff lists nodes and edges that appeared or
+//! disappeared and nodes whose coordinate moved by more than an epsilon in
+//! hyperbolic distance. Consumers that keep derived state (visualizers,
+//! incremental routing tables, audit logs) can apply diffs instead of
+//! rescanning the whole router after every update.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::routing::GPRouter;
+use crate::PoincareDiskPoint;
+
+/// Coordinate moves at or below this hyperbolic distance are not reported
+pub const DEFAULT_MOVE_EPSILON: f64 = 1e-3;
+
+/// Undirected edge with endpoints in ID order
+pub type Edge = (NodeId, NodeId);
+
+fn edge(a: &NodeId, b: &NodeId) -> Edge {
+    if a.0 <= b.0 {
+        (a.clone(), b.clone())
+    } else {
+        (b.clone(), a.clone())
+    }
+}
+
+/// Nodes, coordinates and edges of a router at one point in time
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TopologySnapshot {
+    nodes: BTreeMap<NodeId, PoincareDiskPoint>,
+    edges: BTreeSet<Edge>,
+}
+
+impl TopologySnapshot {
+    /// Capture the current state of a router
+    pub fn capture(router: &GPRouter) -> Self {
+        let mut snapshot = Self::default();
+        for id in router.node_ids() {
+            if let Some(node) = router.get_node(&id) {
+                snapshot.nodes.insert(id.clone(), node.coord.point);
+                for neighbor in &node.neighbors {
+                    snapshot.edges.insert(edge(&id, neighbor));
+                }
+            }
+        }
+        snapshot
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// Changes needed to turn `self` into `next`
+    ///
+    /// Moves are only reported for nodes present in both snapshots whose
+    /// coordinate moved by more than `epsilon`.
+    pub fn diff(&self, next: &TopologySnapshot, epsilon: f64) -> TopologyDiff {
+        let mut diff = TopologyDiff::default();
+        for (id, coord) in &next.nodes {
+            match self.nodes.get(id) {
+                None => diff.nodes_added.push(id.clone()),
+                Some(previous) => {
+                    let distance = previous.hyperbolic_distance(coord);
+                    if distance > epsilon {
+                        diff.moved.push(CoordinateMove { node: id.clone(), from: *previous, to: *coord, distance });
+                    }
+                }
+            }
+        }
+        diff.nodes_removed = self.nodes.keys().filter(|id| !next.nodes.contains_key(*id)).cloned().collect();
+        diff.edges_added = next.edges.difference(&self.edges).cloned().collect();
+        diff.edges_removed = self.edges.difference(&next.edges).cloned().collect();
+        diff
+    }
+}
+
+/// A node whose coordinate moved between two snapshots
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoordinateMove {
+    pub node: NodeId,
+    pub from: PoincareDiskPoint,
+    pub to: PoincareDiskPoint,
+    /// Hyperbolic distance between `from` and `to`
+    pub distance: f64,
+}
+
+/// Difference between two consecutive router snapshots
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TopologyDiff {
+    /// Sequence number of the snapshot this diff leads to; consumers that
+    /// see a gap missed a diff and should rescan the router
+    pub epoch: u64,
+    pub nodes_added: Vec<NodeId>,
+    pub nodes_removed: Vec<NodeId>,
+    pub edges_added: Vec<Edge>,
+    pub edges_removed: Vec<Edge>,
+    pub moved: Vec<CoordinateMove>,
+}
+
+impl TopologyDiff {
+    pub fn is_empty(&self) -> bool {
+        self.nodes_added.is_empty()
+            && self.nodes_removed.is_empty()
+            && self.edges_added.is_empty()
+            && self.edges_removed.is_empty()
+            && self.moved.is_empty()
+    }
+
+    /// Total number of changes in the diff
+    pub fn change_count(&self) -> usize {
+        self.nodes_added.len()
+            + self.nodes_removed.len()
+            + self.edges_added.len()
+            + self.edges_removed.len()
+            + self.moved.len()
+    }
+}
+
+/// Running totals of published topology changes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopologyChangeCounters {
+    pub diffs: u64,
+    pub nodes_added: u64,
+    pub nodes_removed: u64,
+    pub edges_added: u64,
+    pub edges_removed: u64,
+    pub moved: u64,
+}
+
+/// Keeps the last router snapshot and produces diffs against it
+#[derive(Debug, Clone)]
+pub struct TopologyTracker {
+    previous: TopologySnapshot,
+    epoch: u64,
+    epsilon: f64,
+    counters: TopologyChangeCounters,
+}
+
+impl Default for TopologyTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_MOVE_EPSILON)
+    }
+}
+
+impl TopologyTracker {
+    pub fn new(epsilon: f64) -> Self {
+        Self { previous: TopologySnapshot::default(), epoch: 0, epsilon, counters: TopologyChangeCounters::default() }
+    }
+
+    /// Epoch of the last published diff
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    pub fn counters(&self) -> TopologyChangeCounters {
+        self.counters
+    }
+
+    /// The snapshot the next diff will be computed against
+    pub fn snapshot(&self) -> &TopologySnapshot {
+        &self.previous
+    }
+
+    /// Snapshot `router` and return the diff from the previous snapshot
+    ///
+    /// Returns `None` when nothing changed; the epoch only advances when a
+    /// diff is returned. Sub-epsilon moves are kept out of the stored
+    /// snapshot so that slow drift still adds up to a reported move.
+    pub fn observe(&mut self, router: &GPRouter) -> Option<TopologyDiff> {
+        let mut next = TopologySnapshot::capture(router);
+        let mut diff = self.previous.diff(&next, self.epsilon);
+        for (id, coord) in next.nodes.iter_mut() {
+            if let Some(previous) = self.previous.nodes.get(id) {
+                if !diff.moved.iter().any(|m| &m.node == id) {
+                    *coord = *previous;
+                }
+            }
+        }
+        self.previous = next;
+        if diff.is_empty() {
+            return None;
+        }
+        self.epoch += 1;
+        diff.epoch = self.epoch;
+        self.counters.diffs += 1;
+        self.counters.nodes_added += diff.nodes_added.len() as u64;
+        self.counters.nodes_removed += diff.nodes_removed.len() as u64;
+        self.counters.edges_added += diff.edges_added.len() as u64;
+        self.counters.edges_removed += diff.edges_removed.len() as u64;
+        self.counters.moved += diff.moved.len() as u64;
+        Some(diff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinates::RoutingCoordinate;
+    use crate::routing::RoutingNode;
+
+    fn point(x: f64) -> PoincareDiskPoint {
+        PoincareDiskPoint::new(x, 0.0).unwrap()
+    }
+
+    fn add(router: &mut GPRouter, id: &str, x: f64) {
+        router.add_node(RoutingNode::new(NodeId::new(id), RoutingCoordinate::new(point(x), 0)));
+    }
+
+    #[test]
+    fn test_tracker_reports_changes_since_last_snapshot() {
+        let mut router = GPRouter::new();
+        let mut tracker = TopologyTracker::new(0.01);
+        add(&mut router, "a", 0.0);
+        add(&mut router, "b", 0.3);
+        router.add_edge(&NodeId::new("a"), &NodeId::new("b"));
+
+        let first = tracker.observe(&router).unwrap();
+        assert_eq!(first.epoch, 1);
+        assert_eq!(first.nodes_added, vec![NodeId::new("a"), NodeId::new("b")]);
+        assert_eq!(first.edges_added, vec![(NodeId::new("a"), NodeId::new("b"))]);
+        assert!(tracker.observe(&router).is_none());
+        assert_eq!(tracker.epoch(), 1);
+
+        // Drift below epsilon accumulates until it is reported
+        router.set_coordinate(&NodeId::new("b"), RoutingCoordinate::new(point(0.302), 1));
+        assert!(tracker.observe(&router).is_none());
+        router.set_coordinate(&NodeId::new("b"), RoutingCoordinate::new(point(0.306), 2));
+        let moved = tracker.observe(&router).unwrap();
+        assert_eq!(moved.moved.len(), 1);
+        assert_eq!(moved.moved[0].from, point(0.3));
+
+        add(&mut router, "c", -0.3);
+        router.remove_edge(&NodeId::new("a"), &NodeId::new("b"));
+        router.add_edge(&NodeId::new("c"), &NodeId::new("a"));
+        let diff = tracker.observe(&router).unwrap();
+        assert_eq!(diff.epoch, 3);
+        assert_eq!(diff.nodes_added, vec![NodeId::new("c")]);
+        assert_eq!(diff.edges_added, vec![(NodeId::new("a"), NodeId::new("c"))]);
+        assert_eq!(diff.edges_removed, vec![(NodeId::new("a"), NodeId::new("b"))]);
+        assert_eq!(diff.change_count(), 3);
+        assert_eq!(tracker.counters().edges_added, 2);
+    }
+}
@@ -0,0 +1,360 @@
+//! Traffic Matrix Estimation
+//!
+//! Nodes count the packets and bytes they route per (source, destination
+//! region). A region is an angular sector of the disk around the packet's
+//! target coordinate, so nearby destinations share a row however many
+//! nodes there are. Counts live in count-min sketches of fixed size. A
+//! bounded set of the heaviest (source, region) pairs is kept so that
+//! snapshots can list them.
+//!
+//! Counting runs in windows. Each closed window becomes a snapshot, which
+//! telemetry exports. Regions receiving at least `hot_region_share` of the
+//! window's bytes are hot. For a hot region, each next hop is charged a
+//! routing cost in proportion to its share of that region's bytes. The
+//! router adds this cost to its distances, so other neighbors that still
+//! make progress get part of the traffic.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::PoincareDiskPoint;
+
+/// Traffic matrix settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrafficMatrixConfig {
+    pub enabled: bool,
+    /// Counters per sketch row
+    pub sketch_width: usize,
+    /// Sketch rows (independent hashes)
+    pub sketch_depth: usize,
+    /// (source, region) pairs listed in snapshots
+    pub max_tracked_pairs: usize,
+    /// Angular sectors the disk is divided into
+    pub regions: u32,
+    pub window_ms: u64,
+    /// A region is hot when it receives at least this share of the bytes
+    pub hot_region_share: f64,
+    /// Routing cost of a next hop carrying all of a hot region's bytes
+    pub hot_link_penalty: f64,
+}
+
+impl Default for TrafficMatrixConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sketch_width: 512,
+            sketch_depth: 4,
+            max_tracked_pairs: 256,
+            regions: 16,
+            window_ms: 60_000,
+            hot_region_share: 0.25,
+            hot_link_penalty: 0.5,
+        }
+    }
+}
+
+impl TrafficMatrixConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.sketch_width == 0 || self.sketch_depth == 0 || self.regions == 0 {
+            return Err("sketch_width, sketch_depth and regions must be positive".to_string());
+        }
+        if self.window_ms == 0 {
+            return Err("window_ms must be positive".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.hot_region_share) {
+            return Err("hot_region_share must be in [0, 1]".to_string());
+        }
+        if self.hot_link_penalty.is_nan() || self.hot_link_penalty < 0.0 {
+            return Err("hot_link_penalty must be non-negative".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Angular sector of `point` among `regions` equal sectors
+pub fn region_of(point: &PoincareDiskPoint, regions: u32) -> u32 {
+    let angle = point.y.atan2(point.x).rem_euclid(2.0 * PI);
+    ((angle / (2.0 * PI) * regions as f64) as u32).min(regions.saturating_sub(1))
+}
+
+/// Count-min sketch: estimates never undercount, and overcount by at most
+/// a small fraction of the total with high probability
+#[derive(Debug, Clone)]
+pub struct CountMinSketch {
+    width: usize,
+    depth: usize,
+    counters: Vec<u64>,
+}
+
+impl CountMinSketch {
+    pub fn new(width: usize, depth: usize) -> Self {
+        let (width, depth) = (width.max(1), depth.max(1));
+        Self {
+            width,
+            depth,
+            counters: vec![0; width * depth],
+        }
+    }
+
+    fn cell<K: Hash>(&self, row: usize, key: &K) -> usize {
+        let mut hasher = DefaultHasher::new();
+        row.hash(&mut hasher);
+        key.hash(&mut hasher);
+        row * self.width + (hasher.finish() % self.width as u64) as usize
+    }
+
+    pub fn add<K: Hash>(&mut self, key: &K, count: u64) {
+        for row in 0..self.depth {
+            let cell = self.cell(row, key);
+            self.counters[cell] = self.counters[cell].saturating_add(count);
+        }
+    }
+
+    pub fn estimate<K: Hash>(&self, key: &K) -> u64 {
+        (0..self.depth).map(|row| self.counters[self.cell(row, key)]).min().unwrap_or(0)
+    }
+}
+
+/// Estimated traffic from one source toward one region
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrafficEntry {
+    pub source: NodeId,
+    pub region: u32,
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+/// Traffic of one closed window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrafficMatrixSnapshot {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub total_packets: u64,
+    pub total_bytes: u64,
+    /// Exact per-region totals, indexed by region
+    pub region_packets: Vec<u64>,
+    pub region_bytes: Vec<u64>,
+    /// Heaviest (source, region) pairs, by bytes descending
+    pub entries: Vec<TrafficEntry>,
+}
+
+impl TrafficMatrixSnapshot {
+    /// Regions receiving at least `share` of all bytes
+    pub fn hot_regions(&self, share: f64) -> Vec<u32> {
+        if self.total_bytes == 0 {
+            return Vec::new();
+        }
+        let threshold = share * self.total_bytes as f64;
+        (0..self.region_bytes.len() as u32)
+            .filter(|&r| self.region_bytes[r as usize] > 0 && self.region_bytes[r as usize] as f64 >= threshold)
+            .collect()
+    }
+}
+
+/// Windowed traffic counters of a node
+#[derive(Debug, Clone)]
+pub struct TrafficMatrix {
+    config: TrafficMatrixConfig,
+    start_ms: u64,
+    packets: CountMinSketch,
+    bytes: CountMinSketch,
+    /// Candidate heavy pairs with their estimated bytes
+    tracked: HashMap<(NodeId, u32), u64>,
+    region_packets: Vec<u64>,
+    region_bytes: Vec<u64>,
+    /// Bytes per (next hop, region), exact: bounded by neighbors × regions
+    links: HashMap<(NodeId, u32), u64>,
+    last: Option<TrafficMatrixSnapshot>,
+    last_link_costs: HashMap<(NodeId, u32), f64>,
+}
+
+impl TrafficMatrix {
+    pub fn new(config: TrafficMatrixConfig, now_ms: u64) -> Self {
+        Self {
+            start_ms: now_ms,
+            packets: CountMinSketch::new(config.sketch_width, config.sketch_depth),
+            bytes: CountMinSketch::new(config.sketch_width, config.sketch_depth),
+            tracked: HashMap::new(),
+            region_packets: vec![0; config.regions as usize],
+            region_bytes: vec![0; config.regions as usize],
+            links: HashMap::new(),
+            last: None,
+            last_link_costs: HashMap::new(),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &TrafficMatrixConfig {
+        &self.config
+    }
+
+    /// Replace the settings; counting restarts if the sketch or regions change
+    pub fn set_config(&mut self, config: TrafficMatrixConfig, now_ms: u64) {
+        let reshaped = config.sketch_width != self.config.sketch_width
+            || config.sketch_depth != self.config.sketch_depth
+            || config.regions != self.config.regions;
+        if reshaped {
+            *self = Self::new(config, now_ms);
+        } else {
+            self.config = config;
+        }
+    }
+
+    /// Count a packet routed toward `target` via `next_hop`
+    ///
+    /// Returns true if this closed the previous window.
+    pub fn record(&mut self, source: &NodeId, target: &PoincareDiskPoint, next_hop: &NodeId, bytes: u64, now_ms: u64) -> bool {
+        if !self.config.enabled {
+            return false;
+        }
+        let rolled = self.roll(now_ms);
+
+        let region = region_of(target, self.config.regions);
+        let key = (source.clone(), region);
+        self.packets.add(&key, 1);
+        self.bytes.add(&key, bytes);
+        self.region_packets[region as usize] += 1;
+        self.region_bytes[region as usize] += bytes;
+        *self.links.entry((next_hop.clone(), region)).or_default() += bytes;
+
+        let estimate = self.bytes.estimate(&key);
+        if self.tracked.contains_key(&key) || self.tracked.len() < self.config.max_tracked_pairs {
+            self.tracked.insert(key, estimate);
+        } else if let Some((lightest, light_bytes)) =
+            self.tracked.iter().min_by_key(|(_, b)| **b).map(|(k, b)| (k.clone(), *b))
+        {
+            if estimate > light_bytes {
+                self.tracked.remove(&lightest);
+                self.tracked.insert(key, estimate);
+            }
+        }
+        rolled
+    }
+
+    /// Close the current window if it has lasted `window_ms`
+    pub fn roll(&mut self, now_ms: u64) -> bool {
+        if now_ms < self.start_ms.saturating_add(self.config.window_ms) {
+            return false;
+        }
+        let snapshot = self.current(now_ms);
+        self.last_link_costs = self.link_costs_for(&snapshot);
+        self.last = Some(snapshot);
+
+        let config = self.config.clone();
+        let (last, costs) = (self.last.take(), std::mem::take(&mut self.last_link_costs));
+        *self = Self::new(config, now_ms);
+        self.last = last;
+        self.last_link_costs = costs;
+        true
+    }
+
+    /// Counts of the window still in progress
+    pub fn current(&self, now_ms: u64) -> TrafficMatrixSnapshot {
+        let mut entries: Vec<TrafficEntry> = self
+            .tracked
+            .keys()
+            .map(|key| TrafficEntry {
+                source: key.0.clone(),
+                region: key.1,
+                packets: self.packets.estimate(key),
+                bytes: self.bytes.estimate(key),
+            })
+            .collect();
+        entries.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.source.0.cmp(&b.source.0)).then(a.region.cmp(&b.region)));
+
+        TrafficMatrixSnapshot {
+            start_ms: self.start_ms,
+            end_ms: now_ms,
+            total_packets: self.region_packets.iter().sum(),
+            total_bytes: self.region_bytes.iter().sum(),
+            region_packets: self.region_packets.clone(),
+            region_bytes: self.region_bytes.clone(),
+            entries,
+        }
+    }
+
+    /// Last closed window
+    pub fn snapshot(&self) -> Option<&TrafficMatrixSnapshot> {
+        self.last.as_ref()
+    }
+
+    /// Routing costs per (next hop, region) derived from the last window
+    pub fn link_costs(&self) -> &HashMap<(NodeId, u32), f64> {
+        &self.last_link_costs
+    }
+
+    fn link_costs_for(&self, snapshot: &TrafficMatrixSnapshot) -> HashMap<(NodeId, u32), f64> {
+        let mut costs = HashMap::new();
+        if self.config.hot_link_penalty <= 0.0 {
+            return costs;
+        }
+        for region in snapshot.hot_regions(self.config.hot_region_share) {
+            let total = snapshot.region_bytes[region as usize] as f64;
+            for ((hop, r), bytes) in &self.links {
+                if *r == region {
+                    costs.insert((hop.clone(), region), self.config.hot_link_penalty * *bytes as f64 / total);
+                }
+            }
+        }
+        costs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_min_never_undercounts() {
+        let mut sketch = CountMinSketch::new(16, 4);
+        for i in 0..100u64 {
+            sketch.add(&i, i);
+        }
+        for i in 0..100u64 {
+            assert!(sketch.estimate(&i) >= i);
+        }
+        assert_eq!(CountMinSketch::new(16, 4).estimate(&"absent"), 0);
+    }
+
+    #[test]
+    fn test_windows_snapshots_and_hot_region_costs() {
+        let config = TrafficMatrixConfig { window_ms: 1000, regions: 4, max_tracked_pairs: 2, ..Default::default() };
+        let mut matrix = TrafficMatrix::new(config, 0);
+        let east = PoincareDiskPoint::new(0.5, 0.01).unwrap();
+        let west = PoincareDiskPoint::new(-0.5, 0.01).unwrap();
+        assert_eq!(region_of(&east, 4), 0);
+        assert_eq!(region_of(&west, 4), 1);
+
+        let (a, b, c) = (NodeId::new("a"), NodeId::new("b"), NodeId::new("c"));
+        let (hop1, hop2) = (NodeId::new("n1"), NodeId::new("n2"));
+        for _ in 0..30 {
+            matrix.record(&a, &east, &hop1, 100, 10);
+        }
+        matrix.record(&a, &east, &hop2, 1000, 20);
+        matrix.record(&b, &west, &hop2, 100, 30);
+        // Tracking is bounded: c's light pair does not displace heavier ones
+        matrix.record(&c, &west, &hop2, 10, 40);
+        assert!(matrix.snapshot().is_none());
+
+        assert!(matrix.roll(1000));
+        let snapshot = matrix.snapshot().unwrap().clone();
+        assert_eq!(snapshot.total_packets, 33);
+        assert_eq!(snapshot.region_bytes[0], 4000);
+        assert_eq!(snapshot.entries.len(), 2);
+        assert_eq!((snapshot.entries[0].source.0.as_str(), snapshot.entries[0].bytes), ("a", 4000));
+        assert_eq!(snapshot.hot_regions(0.25), vec![0]);
+
+        // n1 carried 3/4 of the hot region's bytes
+        let costs = matrix.link_costs();
+        assert!((costs[&(hop1.clone(), 0)] - 0.375).abs() < 1e-9);
+        assert!((costs[&(hop2.clone(), 0)] - 0.125).abs() < 1e-9);
+        assert!(!costs.contains_key(&(hop2, 1)));
+        assert_eq!(matrix.current(1000).total_packets, 0);
+    }
+}
@@ -0,0 +1,358 @@
+//! Local Spanning Tree Repair
+//!
+//! Tree-mode fallback relies on the PIE spanning tree. When a tree node or
+//! tree edge fails, the children below it are cut off from the root, and
+//! their whole subtrees used to stay without a tree until the next global
+//! embedding. Instead each orphaned subtree is re-attached on the spot:
+//! among the edges leaving the subtree toward nodes still in the tree, it
+//! takes the one that puts the orphan at the least depth, re-rooting the
+//! subtree at the edge's inner end when that is not the orphan itself.
+//!
+//! Depths and subtree sizes are cached on each `RoutingNode` and updated
+//! only along the paths a repair touched. The new parent chain is walked to
+//! the root before the repair counts, so a cycle can never be installed. A
+//! subtree with no edge to the rest of the tree stays detached until the
+//! next rebuild; if the root itself failed, the largest orphaned subtree
+//! becomes the tree and the others join it.
+//!
+//! Repairs are timed against a latency target and counted in
+//! `TreeRepairStats`.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::routing::RoutingNode;
+
+/// Tree repair settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TreeRepairConfig {
+    /// Repair the tree when a tree node or edge fails; when off, orphaned
+    /// subtrees wait for a rebuild
+    pub enabled: bool,
+    /// Repairs slower than this are counted as over target
+    pub target_latency_us: u64,
+}
+
+impl Default for TreeRepairConfig {
+    fn default() -> Self {
+        Self { enabled: true, target_latency_us: 1_000 }
+    }
+}
+
+impl TreeRepairConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.target_latency_us == 0 {
+            return Err("Tree repair target_latency_us must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Tree repair counters since startup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeRepairStats {
+    /// Failures that orphaned at least one subtree
+    pub repairs: u64,
+    /// Orphaned subtrees attached to a new parent
+    pub reattached: u64,
+    /// Orphaned subtrees left without a path to the tree
+    pub detached: u64,
+    /// Orphaned subtrees promoted to be the tree after the root failed
+    pub new_roots: u64,
+    /// Repairs slower than the latency target
+    pub over_target: u64,
+    pub last_latency_us: u64,
+    pub max_latency_us: u64,
+}
+
+impl TreeRepairStats {
+    /// Count a finished repair that took `latency_us`
+    pub fn record(&mut self, outcome: RepairOutcome, latency_us: u64, config: &TreeRepairConfig) {
+        self.repairs += 1;
+        self.reattached += outcome.reattached;
+        self.detached += outcome.detached;
+        self.new_roots += outcome.new_roots;
+        self.over_target += u64::from(latency_us > config.target_latency_us);
+        self.last_latency_us = latency_us;
+        self.max_latency_us = self.max_latency_us.max(latency_us);
+    }
+}
+
+/// What one repair did with its orphaned subtrees
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RepairOutcome {
+    pub reattached: u64,
+    pub detached: u64,
+    pub new_roots: u64,
+}
+
+/// Nodes of the subtree below `root`, with their distance from it, root first
+fn subtree(nodes: &HashMap<NodeId, RoutingNode>, root: &NodeId) -> Vec<(NodeId, u32)> {
+    let mut members = vec![(root.clone(), 0)];
+    let mut seen: HashSet<NodeId> = HashSet::from([root.clone()]);
+    let mut i = 0;
+    while i < members.len() {
+        let (id, dist) = members[i].clone();
+        for child in nodes.get(&id).map(|n| n.tree_children.as_slice()).unwrap_or_default() {
+            if nodes.contains_key(child) && seen.insert(child.clone()) {
+                members.push((child.clone(), dist + 1));
+            }
+        }
+        i += 1;
+    }
+    members
+}
+
+/// Set depths below `root`, which sits at `depth`, and subtree sizes from the children up
+fn settle(nodes: &mut HashMap<NodeId, RoutingNode>, root: &NodeId, depth: Option<u32>) -> usize {
+    let members = subtree(nodes, root);
+    for (id, dist) in &members {
+        if let Some(node) = nodes.get_mut(id) {
+            node.tree_depth = depth.map(|d| d + dist);
+        }
+    }
+    for (id, _) in members.iter().rev() {
+        let size = 1 + nodes[id].tree_children.iter().filter_map(|c| nodes.get(c)).map(|c| c.subtree_size).sum::<usize>();
+        nodes.get_mut(id).unwrap().subtree_size = size;
+    }
+    members.len()
+}
+
+/// Recompute depth and subtree size of every node from the tree links
+///
+/// Roots are nodes with children but no parent; nodes no root reaches are
+/// outside the tree.
+pub fn refresh(nodes: &mut HashMap<NodeId, RoutingNode>) {
+    for node in nodes.values_mut() {
+        node.tree_depth = None;
+        node.subtree_size = 1;
+    }
+    let roots: Vec<NodeId> = nodes
+        .values()
+        .filter(|n| n.tree_parent.is_none() && !n.tree_children.is_empty())
+        .map(|n| n.id.clone())
+        .collect();
+    for root in roots {
+        settle(nodes, &root, Some(0));
+    }
+}
+
+/// Shrink the subtree sizes of `from` and its ancestors by `by`
+pub fn shrink_ancestors(nodes: &mut HashMap<NodeId, RoutingNode>, from: &NodeId, by: usize) {
+    walk_up(nodes, from, |node| node.subtree_size = node.subtree_size.saturating_sub(by));
+}
+
+fn walk_up(nodes: &mut HashMap<NodeId, RoutingNode>, from: &NodeId, mut apply: impl FnMut(&mut RoutingNode)) {
+    let mut current = Some(from.clone());
+    let mut steps = 0;
+    while let Some(id) = current.filter(|_| steps <= nodes.len()) {
+        let Some(node) = nodes.get_mut(&id) else {
+            break;
+        };
+        apply(node);
+        current = node.tree_parent.clone();
+        steps += 1;
+    }
+}
+
+/// Whether the parent chain from `from` ends at a root within the node count
+fn reaches_root(nodes: &HashMap<NodeId, RoutingNode>, from: &NodeId) -> bool {
+    let mut current = from;
+    for _ in 0..=nodes.len() {
+        let Some(node) = nodes.get(current) else {
+            return false;
+        };
+        match &node.tree_parent {
+            Some(parent) => current = parent,
+            None => return node.tree_depth == Some(0),
+        }
+    }
+    false
+}
+
+/// Check that every node in the tree reaches a root without a cycle and
+/// that its cached depth matches its parent's
+///
+/// # Returns
+/// The first offending node, if any
+pub fn verify(nodes: &HashMap<NodeId, RoutingNode>) -> Result<(), NodeId> {
+    for node in nodes.values().filter(|n| n.tree_depth.is_some()) {
+        let parent_depth = node.tree_parent.as_ref().and_then(|p| nodes.get(p)).and_then(|p| p.tree_depth);
+        let consistent = match parent_depth {
+            Some(depth) => node.tree_depth == Some(depth + 1),
+            None => node.tree_parent.is_none() && node.tree_depth == Some(0),
+        };
+        if !consistent || !reaches_root(nodes, &node.id) {
+            return Err(node.id.clone());
+        }
+    }
+    Ok(())
+}
+
+/// Re-attach the subtrees rooted at `orphans`, whose parent link just failed
+///
+/// Ancestor sizes must already exclude the orphans. `root_lost` says the
+/// failed node was the root, so no tree is left to attach to until one of
+/// the orphans takes its place.
+pub fn repair(nodes: &mut HashMap<NodeId, RoutingNode>, orphans: &[NodeId], root_lost: bool) -> RepairOutcome {
+    let mut orphans: Vec<NodeId> = orphans.iter().filter(|id| nodes.contains_key(*id)).cloned().collect();
+    // Orphaned subtrees must not serve as attachment points for each other
+    for orphan in &orphans {
+        nodes.get_mut(orphan).unwrap().tree_parent = None;
+        settle(nodes, orphan, None);
+    }
+    orphans.sort_by(|a, b| nodes[b].subtree_size.cmp(&nodes[a].subtree_size).then_with(|| a.0.cmp(&b.0)));
+
+    let mut outcome = RepairOutcome::default();
+    let mut tree_left = !root_lost;
+    for orphan in &orphans {
+        let members = subtree(nodes, orphan);
+        let inside: HashSet<&NodeId> = members.iter().map(|(id, _)| id).collect();
+        // Least depth for the orphan, then the lowest IDs for determinism
+        let best = members
+            .iter()
+            .flat_map(|(u, dist)| nodes[u].neighbors.iter().map(move |v| (u, *dist, v)))
+            .filter(|(_, _, v)| !inside.contains(v))
+            .filter_map(|(u, dist, v)| nodes.get(v)?.tree_depth.map(|depth| (depth + 1 + dist, v, u)))
+            .min_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1 .0.cmp(&b.1 .0)).then_with(|| a.2 .0.cmp(&b.2 .0)))
+            .map(|(_, v, u)| (v.clone(), u.clone()));
+
+        match best {
+            Some((parent, attach)) => {
+                reroot(nodes, &attach);
+                nodes.get_mut(&attach).unwrap().tree_parent = Some(parent.clone());
+                nodes.get_mut(&parent).unwrap().tree_children.push(attach.clone());
+                let depth = nodes[&parent].tree_depth.map(|d| d + 1);
+                let size = settle(nodes, &attach, depth);
+                if reaches_root(nodes, &attach) {
+                    walk_up(nodes, &parent, |node| node.subtree_size += size);
+                    outcome.reattached += 1;
+                } else {
+                    nodes.get_mut(&parent).unwrap().tree_children.retain(|c| c != &attach);
+                    nodes.get_mut(&attach).unwrap().tree_parent = None;
+                    settle(nodes, &attach, None);
+                    outcome.detached += 1;
+                }
+            }
+            None if !tree_left => {
+                settle(nodes, orphan, Some(0));
+                tree_left = true;
+                outcome.new_roots += 1;
+            }
+            None => outcome.detached += 1,
+        }
+    }
+    outcome
+}
+
+/// Make `node` the root of its subtree by reversing the links above it
+fn reroot(nodes: &mut HashMap<NodeId, RoutingNode>, node: &NodeId) {
+    let mut path = vec![node.clone()];
+    while let Some(parent) = nodes[path.last().unwrap()].tree_parent.clone() {
+        if path.contains(&parent) || path.len() > nodes.len() {
+            break;
+        }
+        path.push(parent);
+    }
+    for pair in path.windows(2) {
+        let (child, parent) = (&pair[0], &pair[1]);
+        nodes.get_mut(parent).unwrap().tree_children.retain(|c| c != child);
+        nodes.get_mut(parent).unwrap().tree_parent = Some(child.clone());
+        nodes.get_mut(child).unwrap().tree_children.push(parent.clone());
+    }
+    nodes.get_mut(node).unwrap().tree_parent = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinates::RoutingCoordinate;
+    use crate::PoincareDiskPoint;
+
+    /// Tree r-(a-(a1, a2), b-(b1)) plus cross edges a1-b1 and a2-b
+    fn network() -> HashMap<NodeId, RoutingNode> {
+        let tree = [("r", None), ("a", Some("r")), ("b", Some("r")), ("a1", Some("a")), ("a2", Some("a")), ("b1", Some("b"))];
+        let mut nodes: HashMap<NodeId, RoutingNode> = tree
+            .iter()
+            .map(|(id, _)| (NodeId::new(*id), RoutingNode::new(NodeId::new(*id), RoutingCoordinate::new(PoincareDiskPoint::origin(), 0))))
+            .collect();
+        let mut link = |x: &str, y: &str| {
+            nodes.get_mut(&NodeId::new(x)).unwrap().add_neighbor(NodeId::new(y));
+            nodes.get_mut(&NodeId::new(y)).unwrap().add_neighbor(NodeId::new(x));
+        };
+        for (child, parent) in tree.iter().filter_map(|(c, p)| Some((*c, (*p)?))) {
+            link(child, parent);
+        }
+        link("a1", "b1");
+        link("a2", "b");
+        for (child, parent) in tree.iter().filter_map(|(c, p)| Some((*c, (*p)?))) {
+            nodes.get_mut(&NodeId::new(child)).unwrap().tree_parent = Some(NodeId::new(parent));
+            nodes.get_mut(&NodeId::new(parent)).unwrap().tree_children.push(NodeId::new(child));
+        }
+        refresh(&mut nodes);
+        nodes
+    }
+
+    fn parent(nodes: &HashMap<NodeId, RoutingNode>, id: &str) -> Option<String> {
+        nodes[&NodeId::new(id)].tree_parent.as_ref().map(|p| p.0.clone())
+    }
+
+    #[test]
+    fn test_orphans_pick_least_depth_parent() {
+        let mut nodes = network();
+        assert_eq!(nodes[&NodeId::new("r")].subtree_size, 6);
+        assert_eq!(nodes[&NodeId::new("a1")].tree_depth, Some(2));
+
+        // "a" fails: a2 joins b directly, a1 joins b1 one level deeper
+        let removed = nodes.remove(&NodeId::new("a")).unwrap();
+        nodes.get_mut(&NodeId::new("r")).unwrap().tree_children.retain(|c| c.0 != "a");
+        shrink_ancestors(&mut nodes, &NodeId::new("r"), removed.subtree_size);
+        let outcome = repair(&mut nodes, &removed.tree_children, false);
+        assert_eq!(outcome, RepairOutcome { reattached: 2, detached: 0, new_roots: 0 });
+        assert_eq!((parent(&nodes, "a2"), parent(&nodes, "a1")), (Some("b".into()), Some("b1".into())));
+        assert_eq!(nodes[&NodeId::new("a1")].tree_depth, Some(3));
+        assert_eq!(nodes[&NodeId::new("r")].subtree_size, 5);
+        assert_eq!(nodes[&NodeId::new("b")].subtree_size, 4);
+        assert_eq!(verify(&nodes), Ok(()));
+    }
+
+    #[test]
+    fn test_reroot_and_root_loss() {
+        // Losing the edge b-r re-roots b's subtree at b1, which reaches a1
+        let mut nodes = network();
+        for (x, y) in [("b", "r"), ("a2", "b")] {
+            nodes.get_mut(&NodeId::new(x)).unwrap().remove_neighbor(&NodeId::new(y));
+            nodes.get_mut(&NodeId::new(y)).unwrap().remove_neighbor(&NodeId::new(x));
+        }
+        nodes.get_mut(&NodeId::new("r")).unwrap().tree_children.retain(|c| c.0 != "b");
+        shrink_ancestors(&mut nodes, &NodeId::new("r"), 2);
+        assert_eq!(repair(&mut nodes, &[NodeId::new("b")], false).reattached, 1);
+        assert_eq!((parent(&nodes, "b1"), parent(&nodes, "b")), (Some("a1".into()), Some("b1".into())));
+        assert_eq!(nodes[&NodeId::new("b")].tree_depth, Some(4));
+        assert_eq!(verify(&nodes), Ok(()));
+
+        // Losing the root promotes the largest orphaned subtree
+        let mut nodes = network();
+        let removed = nodes.remove(&NodeId::new("r")).unwrap();
+        let outcome = repair(&mut nodes, &removed.tree_children, true);
+        assert_eq!(outcome, RepairOutcome { reattached: 1, detached: 0, new_roots: 1 });
+        assert_eq!(nodes[&NodeId::new("a")].tree_depth, Some(0));
+        assert_eq!(parent(&nodes, "b"), Some("a2".into()));
+        assert_eq!(nodes[&NodeId::new("a")].subtree_size, 5);
+        assert_eq!(verify(&nodes), Ok(()));
+
+        // A subtree cut off from everything stays detached
+        let mut nodes = network();
+        nodes.get_mut(&NodeId::new("r")).unwrap().tree_children.retain(|c| c.0 != "b");
+        for (x, y) in [("b", "r"), ("a2", "b"), ("a1", "b1")] {
+            nodes.get_mut(&NodeId::new(x)).unwrap().remove_neighbor(&NodeId::new(y));
+            nodes.get_mut(&NodeId::new(y)).unwrap().remove_neighbor(&NodeId::new(x));
+        }
+        assert_eq!(repair(&mut nodes, &[NodeId::new("b")], false).detached, 1);
+        assert_eq!(nodes[&NodeId::new("b1")].tree_depth, None);
+        assert_eq!(verify(&nodes), Ok(()));
+    }
+}
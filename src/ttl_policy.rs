@@ -0,0 +1,348 @@
+//! TTL and Hop-Limit Policy
+//!
+//! Chooses the TTL of outgoing packets from profiles configured per packet
+//! type and QoS class. Multi-hop traffic gets a TTL estimated from the
+//! hyperbolic distance to the destination's anchor: the expected hop count is
+//! the distance divided by the typical link length, and the profile adds a
+//! proportional and a fixed margin on top. Single-hop control traffic keeps a
+//! fixed TTL of 1.
+//!
+//! `TtlStats` counts deliveries and TTL expiries observed at a node, together
+//! with how much of the TTL delivered packets actually used, so margins can be
+//! tightened or widened from measurements.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::network::{PacketType, MAX_TTL};
+
+/// Traffic class of a packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QosClass {
+    /// Protocol control traffic (acks, tree maintenance)
+    Control,
+    /// Latency-sensitive application traffic
+    Interactive,
+    /// Default application traffic
+    #[default]
+    Standard,
+    /// Throughput traffic that may be dropped early
+    Bulk,
+}
+
+/// How the TTL of one kind of packet is chosen
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TtlProfile {
+    /// Fixed TTL; disables estimation
+    pub fixed: Option<u32>,
+    /// Multiplier on the expected hop count
+    pub margin_factor: f64,
+    /// Hops added after the multiplier
+    pub margin_hops: u32,
+    pub min_ttl: u32,
+    /// Also used when no distance estimate is available
+    pub max_ttl: u32,
+}
+
+impl TtlProfile {
+    /// Profile with a fixed TTL
+    pub fn fixed(ttl: u32) -> Self {
+        Self {
+            fixed: Some(ttl),
+            margin_factor: 1.0,
+            margin_hops: 0,
+            min_ttl: ttl,
+            max_ttl: ttl,
+        }
+    }
+
+    /// Profile estimating TTL as `expected × margin_factor + margin_hops`
+    pub fn estimated(margin_factor: f64, margin_hops: u32, min_ttl: u32, max_ttl: u32) -> Self {
+        Self {
+            fixed: None,
+            margin_factor,
+            margin_hops,
+            min_ttl,
+            max_ttl,
+        }
+    }
+
+    /// TTL for a packet expected to need `expected_hops` hops
+    pub fn ttl_for(&self, expected_hops: Option<f64>) -> u32 {
+        if let Some(ttl) = self.fixed {
+            return ttl;
+        }
+        match expected_hops {
+            Some(hops) if hops.is_finite() => {
+                let ttl = (hops.max(1.0) * self.margin_factor).ceil() as u32 + self.margin_hops;
+                ttl.clamp(self.min_ttl, self.max_ttl)
+            }
+            _ => self.max_ttl,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.fixed == Some(0) {
+            return Err("Fixed TTL must be positive".to_string());
+        }
+        if self.min_ttl == 0 || self.min_ttl > self.max_ttl || self.max_ttl > MAX_TTL {
+            return Err(format!("TTL profile bounds must satisfy 1 <= min <= max <= {}", MAX_TTL));
+        }
+        if self.margin_factor.is_nan() || self.margin_factor < 1.0 {
+            return Err("TTL margin_factor must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Profile override for a packet type, a QoS class, or both
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TtlRule {
+    /// Packet type to match (None = any)
+    pub packet_type: Option<PacketType>,
+    /// QoS class to match (None = any)
+    pub qos_class: Option<QosClass>,
+    pub profile: TtlProfile,
+}
+
+impl TtlRule {
+    /// Specificity of a match, or None if the rule does not apply
+    fn matches(&self, packet_type: PacketType, qos_class: QosClass) -> Option<u8> {
+        let type_ok = self.packet_type.is_none_or(|t| t == packet_type);
+        let class_ok = self.qos_class.is_none_or(|c| c == qos_class);
+        (type_ok && class_ok).then(|| 2 * self.packet_type.is_some() as u8 + self.qos_class.is_some() as u8)
+    }
+}
+
+/// TTL profiles per packet type and QoS class
+///
+/// The most specific matching rule wins: type and class, then type only,
+/// then class only, then the default profile. Earlier rules win ties.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TtlPolicy {
+    pub default: TtlProfile,
+    pub rules: Vec<TtlRule>,
+}
+
+impl Default for TtlPolicy {
+    fn default() -> Self {
+        let single_hop = [
+            PacketType::Heartbeat,
+            PacketType::Discovery,
+            PacketType::CoordinateUpdate,
+            PacketType::CoordinateBatch,
+            PacketType::SnapshotMarker,
+            PacketType::Multicast,
+            PacketType::Broadcast,
+            PacketType::Election,
+            PacketType::NeighborExchange,
+            PacketType::Convergence,
+            PacketType::NatProbe,
+        ];
+        let mut rules: Vec<TtlRule> = single_hop
+            .into_iter()
+            .map(|packet_type| TtlRule {
+                packet_type: Some(packet_type),
+                qos_class: None,
+                profile: TtlProfile::fixed(1),
+            })
+            .collect();
+        let per_class = [
+            (QosClass::Control, TtlProfile::estimated(2.0, 8, 16, MAX_TTL)),
+            (QosClass::Interactive, TtlProfile::estimated(1.5, 4, 8, 128)),
+            (QosClass::Bulk, TtlProfile::estimated(1.25, 2, 8, 64)),
+        ];
+        rules.extend(per_class.into_iter().map(|(class, profile)| TtlRule {
+            packet_type: None,
+            qos_class: Some(class),
+            profile,
+        }));
+
+        Self {
+            default: TtlProfile::estimated(1.5, 4, 8, MAX_TTL),
+            rules,
+        }
+    }
+}
+
+impl TtlPolicy {
+    /// Profile applying to a packet type and class
+    pub fn profile(&self, packet_type: PacketType, qos_class: QosClass) -> &TtlProfile {
+        let mut best: Option<(u8, &TtlProfile)> = None;
+        for rule in &self.rules {
+            if let Some(score) = rule.matches(packet_type, qos_class) {
+                if best.is_none_or(|(s, _)| score > s) {
+                    best = Some((score, &rule.profile));
+                }
+            }
+        }
+        best.map_or(&self.default, |(_, profile)| profile)
+    }
+
+    /// TTL for a packet expected to need `expected_hops` hops
+    pub fn ttl(&self, packet_type: PacketType, qos_class: QosClass, expected_hops: Option<f64>) -> u32 {
+        self.profile(packet_type, qos_class).ttl_for(expected_hops)
+    }
+
+    /// Check that all profiles are usable
+    pub fn validate(&self) -> Result<(), String> {
+        self.default.validate()?;
+        self.rules.iter().try_for_each(|rule| rule.profile.validate())
+    }
+}
+
+/// Expected hop count across a hyperbolic distance
+///
+/// `link_length` is the typical hyperbolic length of one overlay link, e.g.
+/// the mean distance to the node's neighbors. None without a usable length.
+pub fn expected_hops(distance: f64, link_length: f64) -> Option<f64> {
+    (link_length > 0.0 && link_length.is_finite() && distance.is_finite()).then(|| distance / link_length)
+}
+
+/// Counters for one packet type and class
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TtlCounters {
+    /// Packets originated here
+    pub sent: u64,
+    /// Packets delivered here
+    pub delivered: u64,
+    /// Packets dropped here because their TTL ran out
+    pub expired: u64,
+    /// Hops taken by delivered packets
+    pub delivered_hops: u64,
+    /// Initial TTL of delivered packets
+    pub delivered_ttl: u64,
+    /// Largest share of its TTL a delivered packet used
+    pub max_ttl_used: f64,
+}
+
+impl TtlCounters {
+    /// Share of terminated packets that expired instead of arriving
+    ///
+    /// Per node this covers only packets that ended here; summing counters
+    /// across nodes gives the network-wide rate.
+    pub fn expiry_rate(&self) -> f64 {
+        let ended = self.delivered + self.expired;
+        if ended > 0 {
+            self.expired as f64 / ended as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// Average share of its TTL a delivered packet used
+    pub fn avg_ttl_used(&self) -> f64 {
+        if self.delivered_ttl > 0 {
+            self.delivered_hops as f64 / self.delivered_ttl as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+/// TTL statistics entry for reporting
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TtlStatsEntry {
+    pub packet_type: PacketType,
+    pub qos_class: QosClass,
+    pub counters: TtlCounters,
+    pub expiry_rate: f64,
+    pub avg_ttl_used: f64,
+}
+
+/// TTL outcome statistics of one node
+#[derive(Debug, Clone, Default)]
+pub struct TtlStats {
+    counters: HashMap<(PacketType, QosClass), TtlCounters>,
+}
+
+impl TtlStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn entry(&mut self, packet_type: PacketType, qos_class: QosClass) -> &mut TtlCounters {
+        self.counters.entry((packet_type, qos_class)).or_default()
+    }
+
+    pub fn record_sent(&mut self, packet_type: PacketType, qos_class: QosClass) {
+        self.entry(packet_type, qos_class).sent += 1;
+    }
+
+    pub fn record_delivered(&mut self, packet_type: PacketType, qos_class: QosClass, hops: u32, initial_ttl: u32) {
+        let counters = self.entry(packet_type, qos_class);
+        counters.delivered += 1;
+        counters.delivered_hops += hops as u64;
+        counters.delivered_ttl += initial_ttl as u64;
+        if initial_ttl > 0 {
+            counters.max_ttl_used = counters.max_ttl_used.max(hops as f64 / initial_ttl as f64);
+        }
+    }
+
+    pub fn record_expired(&mut self, packet_type: PacketType, qos_class: QosClass) {
+        self.entry(packet_type, qos_class).expired += 1;
+    }
+
+    pub fn counters(&self, packet_type: PacketType, qos_class: QosClass) -> Option<&TtlCounters> {
+        self.counters.get(&(packet_type, qos_class))
+    }
+
+    /// All counters with derived rates
+    pub fn report(&self) -> Vec<TtlStatsEntry> {
+        self.counters
+            .iter()
+            .map(|(&(packet_type, qos_class), counters)| TtlStatsEntry {
+                packet_type,
+                qos_class,
+                counters: counters.clone(),
+                expiry_rate: counters.expiry_rate(),
+                avg_ttl_used: counters.avg_ttl_used(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_most_specific_profile_wins() {
+        let mut policy = TtlPolicy::default();
+        policy.rules.push(TtlRule {
+            packet_type: Some(PacketType::Data),
+            qos_class: Some(QosClass::Bulk),
+            profile: TtlProfile::fixed(7),
+        });
+
+        assert_eq!(policy.ttl(PacketType::Heartbeat, QosClass::Control, Some(50.0)), 1);
+        assert_eq!(policy.ttl(PacketType::Data, QosClass::Bulk, Some(50.0)), 7);
+        // Class rule: 10 expected hops × 1.25 + 2
+        assert_eq!(policy.ttl(PacketType::Stream, QosClass::Bulk, Some(10.0)), 15);
+        // Default profile, clamped at the minimum
+        assert_eq!(policy.ttl(PacketType::Data, QosClass::Standard, Some(1.0)), 8);
+        // No estimate falls back to the profile maximum
+        assert_eq!(policy.ttl(PacketType::Data, QosClass::Interactive, None), 128);
+        assert!(policy.validate().is_ok());
+
+        policy.default.max_ttl = MAX_TTL + 1;
+        assert!(policy.validate().is_err());
+    }
+
+    #[test]
+    fn test_expiry_statistics() {
+        let mut stats = TtlStats::new();
+        stats.record_delivered(PacketType::Data, QosClass::Standard, 3, 12);
+        stats.record_delivered(PacketType::Data, QosClass::Standard, 9, 12);
+        stats.record_expired(PacketType::Data, QosClass::Standard);
+
+        let counters = stats.counters(PacketType::Data, QosClass::Standard).unwrap();
+        assert!((counters.expiry_rate() - 1.0 / 3.0).abs() < 1e-12);
+        assert_eq!(counters.avg_ttl_used(), 0.5);
+        assert_eq!(counters.max_ttl_used, 0.75);
+        assert_eq!(expected_hops(3.0, 0.5), Some(6.0));
+        assert_eq!(expected_hops(3.0, 0.0), None);
+    }
+}
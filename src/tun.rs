@@ -0,0 +1,785 @@
+//! IP Tunneling over the Overlay
+//!
+//! Lets unmodified applications use DRFE-R as a mesh VPN. IP packets read
+//! from a TUN device are looked up in a table of IP prefixes owned by
+//! overlay nodes, split into frames that fit the overlay payload budget and
+//! sent as ordinary Data packets. The receiving node reassembles the frames
+//! and writes the packet to its own TUN device.
+//!
+//! Routing, fragmentation and reassembly are plain data structures here
+//! (`TunTunnel`). The device itself and the loop bridging it to a
+//! `DistributedNode` need the `tun` feature and Linux; the `drfe-tun`
+//! binary wires them up from a JSON config.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::coordinates::NodeId;
+
+/// Marks a Data payload as a tunnel frame
+const FRAME_MAGIC: &[u8; 4] = b"DTUN";
+/// Magic, packet ID, fragment index and fragment count
+const FRAME_HEADER_LEN: usize = 4 + 4 + 2 + 2;
+
+/// Tunnel errors
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TunError {
+    #[error("Invalid route: {0}")]
+    InvalidRoute(String),
+
+    #[error("No overlay node owns {0}")]
+    NoRoute(IpAddr),
+
+    #[error("Malformed packet: {0}")]
+    Malformed(String),
+
+    #[error("Packet of {len} bytes exceeds the MTU of {mtu}")]
+    TooLarge { len: usize, mtu: usize },
+
+    #[error("Device error: {0}")]
+    Device(String),
+}
+
+impl TunError {
+    /// Stable identifier for programmatic handling
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidRoute(_) => "tun.invalid_route",
+            Self::NoRoute(_) => "tun.no_route",
+            Self::Malformed(_) => "tun.malformed",
+            Self::TooLarge { .. } => "tun.too_large",
+            Self::Device(_) => "tun.device",
+        }
+    }
+}
+
+/// An IP prefix owned by an overlay node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IpRoute {
+    pub network: IpAddr,
+    pub prefix_len: u8,
+    pub node: NodeId,
+}
+
+impl IpRoute {
+    /// Parse `10.1.0.0/16`, `fd00::/64` or a bare address (a host route)
+    pub fn parse(prefix: &str, node: NodeId) -> Result<Self, TunError> {
+        let invalid = || TunError::InvalidRoute(prefix.to_string());
+        let (addr, len) = match prefix.split_once('/') {
+            Some((addr, len)) => (addr, Some(len)),
+            None => (prefix, None),
+        };
+        let network: IpAddr = addr.trim().parse().map_err(|_| invalid())?;
+        let max_len = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match len {
+            Some(len) => len.trim().parse::<u8>().map_err(|_| invalid())?,
+            None => max_len,
+        };
+        if prefix_len > max_len {
+            return Err(invalid());
+        }
+        Ok(Self { network: mask(network, prefix_len), prefix_len, node })
+    }
+
+    pub fn contains(&self, addr: &IpAddr) -> bool {
+        addr.is_ipv4() == self.network.is_ipv4() && mask(*addr, self.prefix_len) == self.network
+    }
+}
+
+/// Clear the host bits of an address
+fn mask(addr: IpAddr, prefix_len: u8) -> IpAddr {
+    match addr {
+        IpAddr::V4(v4) => {
+            let bits = u32::from(v4);
+            let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(bits & mask))
+        }
+        IpAddr::V6(v6) => {
+            let bits = u128::from(v6);
+            let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(bits & mask))
+        }
+    }
+}
+
+/// IP prefixes to overlay nodes, longest prefix wins
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteTable {
+    routes: Vec<IpRoute>,
+}
+
+impl RouteTable {
+    /// Add a route, replacing any route for the same prefix
+    pub fn insert(&mut self, route: IpRoute) {
+        self.routes
+            .retain(|r| (r.network, r.prefix_len) != (route.network, route.prefix_len));
+        self.routes.push(route);
+        // Longest prefixes first so the first match wins
+        self.routes.sort_by_key(|r| std::cmp::Reverse(r.prefix_len));
+    }
+
+    /// Remove every route pointing at `node`
+    pub fn remove_node(&mut self, node: &NodeId) -> usize {
+        let before = self.routes.len();
+        self.routes.retain(|r| &r.node != node);
+        before - self.routes.len()
+    }
+
+    pub fn lookup(&self, addr: &IpAddr) -> Option<&NodeId> {
+        self.routes.iter().find(|r| r.contains(addr)).map(|r| &r.node)
+    }
+
+    pub fn routes(&self) -> &[IpRoute] {
+        &self.routes
+    }
+}
+
+/// Destination address of a raw IPv4 or IPv6 packet
+pub fn destination_addr(packet: &[u8]) -> Result<IpAddr, TunError> {
+    header_addr(packet, 16, 24)
+}
+
+/// Source address of a raw IPv4 or IPv6 packet
+pub fn source_addr(packet: &[u8]) -> Result<IpAddr, TunError> {
+    header_addr(packet, 12, 8)
+}
+
+/// Address at the given header offset for each IP version
+fn header_addr(packet: &[u8], v4_offset: usize, v6_offset: usize) -> Result<IpAddr, TunError> {
+    match packet.first().map(|b| b >> 4) {
+        Some(4) if packet.len() >= 20 => {
+            let octets: [u8; 4] = packet[v4_offset..v4_offset + 4].try_into().unwrap();
+            Ok(IpAddr::V4(Ipv4Addr::from(octets)))
+        }
+        Some(6) if packet.len() >= 40 => {
+            let octets: [u8; 16] = packet[v6_offset..v6_offset + 16].try_into().unwrap();
+            Ok(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        Some(v @ (4 | 6)) => Err(TunError::Malformed(format!("truncated IPv{v} header"))),
+        Some(v) => Err(TunError::Malformed(format!("unknown IP version {v}"))),
+        None => Err(TunError::Malformed("empty packet".to_string())),
+    }
+}
+
+/// One fragment of a tunneled IP packet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TunFrame {
+    pub packet_id: u32,
+    pub index: u16,
+    pub count: u16,
+    pub data: Vec<u8>,
+}
+
+impl TunFrame {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(FRAME_HEADER_LEN + self.data.len());
+        bytes.extend_from_slice(FRAME_MAGIC);
+        bytes.extend_from_slice(&self.packet_id.to_be_bytes());
+        bytes.extend_from_slice(&self.index.to_be_bytes());
+        bytes.extend_from_slice(&self.count.to_be_bytes());
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// Parse a Data payload; None if it is not a tunnel frame
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < FRAME_HEADER_LEN || &bytes[..4] != FRAME_MAGIC {
+            return None;
+        }
+        let frame = Self {
+            packet_id: u32::from_be_bytes(bytes[4..8].try_into().unwrap()),
+            index: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            count: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            data: bytes[FRAME_HEADER_LEN..].to_vec(),
+        };
+        (frame.count > 0 && frame.index < frame.count).then_some(frame)
+    }
+}
+
+/// Tunnel settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TunConfig {
+    /// Name of the TUN device to create
+    pub device_name: String,
+    /// MTU of the TUN device; larger packets are dropped
+    pub mtu: usize,
+    /// Largest IP fragment carried in one overlay Data packet
+    pub fragment_size: usize,
+    /// Prefix (`10.1.0.0/16`, `fd00::1`) to owning node ID
+    pub routes: HashMap<String, String>,
+    /// Incomplete packets are dropped after this long
+    pub reassembly_timeout_ms: u64,
+    /// Incomplete packets kept at once; the oldest is dropped beyond this
+    pub max_pending: usize,
+    /// Fragment bytes held for incomplete packets; the oldest packets are
+    /// dropped beyond this
+    pub max_pending_bytes: usize,
+}
+
+impl Default for TunConfig {
+    fn default() -> Self {
+        Self {
+            device_name: "drfe0".to_string(),
+            mtu: 1400,
+            fragment_size: 1200,
+            routes: HashMap::new(),
+            reassembly_timeout_ms: 2000,
+            max_pending: 256,
+            max_pending_bytes: 1 << 20,
+        }
+    }
+}
+
+impl TunConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.device_name.is_empty() || self.device_name.len() >= 16 {
+            return Err("device_name must be 1-15 bytes".to_string());
+        }
+        if !(576..=65535).contains(&self.mtu) {
+            return Err("mtu must be in [576, 65535]".to_string());
+        }
+        if self.fragment_size == 0 {
+            return Err("fragment_size must be positive".to_string());
+        }
+        if self.mtu.div_ceil(self.fragment_size) > u16::MAX as usize {
+            return Err("fragment_size is too small for the mtu".to_string());
+        }
+        if self.max_pending == 0 {
+            return Err("max_pending must be positive".to_string());
+        }
+        if self.max_pending_bytes < self.mtu {
+            return Err("max_pending_bytes must hold at least one mtu".to_string());
+        }
+        self.route_table().map(|_| ()).map_err(|e| e.to_string())
+    }
+
+    /// Build the route table from `routes`
+    pub fn route_table(&self) -> Result<RouteTable, TunError> {
+        let mut table = RouteTable::default();
+        for (prefix, node) in &self.routes {
+            table.insert(IpRoute::parse(prefix, NodeId::new(node.as_str()))?);
+        }
+        Ok(table)
+    }
+}
+
+/// Tunnel counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TunStats {
+    pub packets_sent: u64,
+    pub fragments_sent: u64,
+    pub packets_received: u64,
+    pub fragments_received: u64,
+    /// Outgoing packets with no owning node, or malformed
+    pub dropped_no_route: u64,
+    /// Outgoing packets over the MTU
+    pub dropped_oversize: u64,
+    /// Incomplete packets given up on
+    pub reassembly_dropped: u64,
+    /// Incoming frames with more fragments or bytes than the MTU allows
+    pub dropped_invalid: u64,
+    /// Incoming packets whose source address is not routed to the sending node
+    pub dropped_spoofed: u64,
+}
+
+/// Fragments received so far for one packet
+#[derive(Debug)]
+struct PendingPacket {
+    fragments: Vec<Option<Vec<u8>>>,
+    received: usize,
+    bytes: usize,
+    first_seen_ms: u64,
+}
+
+/// Fragmentation and reassembly between IP packets and overlay payloads
+#[derive(Debug)]
+pub struct TunTunnel {
+    config: TunConfig,
+    routes: RouteTable,
+    next_packet_id: u32,
+    pending: HashMap<(NodeId, u32), PendingPacket>,
+    /// Fragment bytes held in `pending`
+    pending_bytes: usize,
+    stats: TunStats,
+}
+
+impl TunTunnel {
+    pub fn new(config: TunConfig) -> Result<Self, TunError> {
+        let routes = config.route_table()?;
+        Ok(Self {
+            config,
+            routes,
+            next_packet_id: rand::random(),
+            pending: HashMap::new(),
+            pending_bytes: 0,
+            stats: TunStats::default(),
+        })
+    }
+
+    pub fn config(&self) -> &TunConfig {
+        &self.config
+    }
+
+    pub fn routes(&self) -> &RouteTable {
+        &self.routes
+    }
+
+    pub fn routes_mut(&mut self) -> &mut RouteTable {
+        &mut self.routes
+    }
+
+    pub fn stats(&self) -> TunStats {
+        self.stats
+    }
+
+    /// Owning node and encoded frames for an IP packet read from the device
+    pub fn outbound(&mut self, packet: &[u8]) -> Result<(NodeId, Vec<Vec<u8>>), TunError> {
+        if packet.len() > self.config.mtu {
+            self.stats.dropped_oversize += 1;
+            return Err(TunError::TooLarge { len: packet.len(), mtu: self.config.mtu });
+        }
+        let node = match destination_addr(packet).and_then(|addr| {
+            self.routes.lookup(&addr).cloned().ok_or(TunError::NoRoute(addr))
+        }) {
+            Ok(node) => node,
+            Err(e) => {
+                self.stats.dropped_no_route += 1;
+                return Err(e);
+            }
+        };
+
+        let packet_id = self.next_packet_id;
+        self.next_packet_id = self.next_packet_id.wrapping_add(1);
+        let chunks: Vec<&[u8]> = packet.chunks(self.config.fragment_size).collect();
+        let count = chunks.len() as u16;
+        let frames: Vec<Vec<u8>> = chunks
+            .into_iter()
+            .enumerate()
+            .map(|(index, data)| {
+                TunFrame { packet_id, index: index as u16, count, data: data.to_vec() }.encode()
+            })
+            .collect();
+
+        self.stats.packets_sent += 1;
+        self.stats.fragments_sent += frames.len() as u64;
+        Ok((node, frames))
+    }
+
+    /// Accept a Data payload from `source`; returns a packet once complete
+    ///
+    /// Payloads that are not tunnel frames are ignored. Frames the sender
+    /// could not have produced under our MTU and fragment size are dropped,
+    /// and so are packets whose source address is not routed to `source`.
+    pub fn inbound(&mut self, source: &NodeId, payload: &[u8], now_ms: u64) -> Option<Vec<u8>> {
+        let frame = TunFrame::decode(payload)?;
+        self.stats.fragments_received += 1;
+        let max_count = self.config.mtu.div_ceil(self.config.fragment_size);
+        if frame.count as usize > max_count || frame.data.len() > self.config.fragment_size {
+            self.stats.dropped_invalid += 1;
+            return None;
+        }
+        if frame.count == 1 {
+            return self.admit(source, frame.data);
+        }
+
+        self.expire(now_ms);
+        let key = (source.clone(), frame.packet_id);
+        if let Some(pending) = self.pending.get(&key) {
+            if pending.fragments.len() != frame.count as usize {
+                self.stats.dropped_invalid += 1;
+                return None;
+            }
+        } else if self.pending.len() >= self.config.max_pending {
+            self.drop_oldest(&key);
+        }
+        while self.pending_bytes + frame.data.len() > self.config.max_pending_bytes && self.drop_oldest(&key) {}
+
+        let pending = self.pending.entry(key.clone()).or_insert_with(|| PendingPacket {
+            fragments: vec![None; frame.count as usize],
+            received: 0,
+            bytes: 0,
+            first_seen_ms: now_ms,
+        });
+        let slot = &mut pending.fragments[frame.index as usize];
+        if slot.is_none() {
+            pending.received += 1;
+            pending.bytes += frame.data.len();
+            self.pending_bytes += frame.data.len();
+            *slot = Some(frame.data);
+        }
+        if pending.received < pending.fragments.len() {
+            return None;
+        }
+
+        let pending = self.remove_pending(&key)?;
+        let packet: Vec<u8> = pending.fragments.into_iter().flatten().flatten().collect();
+        if packet.len() > self.config.mtu {
+            self.stats.dropped_invalid += 1;
+            return None;
+        }
+        self.admit(source, packet)
+    }
+
+    /// Pass a complete packet on if its source address belongs to `source`
+    fn admit(&mut self, source: &NodeId, packet: Vec<u8>) -> Option<Vec<u8>> {
+        let owner = source_addr(&packet).ok().and_then(|addr| self.routes.lookup(&addr));
+        if owner != Some(source) {
+            self.stats.dropped_spoofed += 1;
+            return None;
+        }
+        self.stats.packets_received += 1;
+        Some(packet)
+    }
+
+    /// Drop incomplete packets older than the reassembly timeout
+    pub fn expire(&mut self, now_ms: u64) {
+        let timeout = self.config.reassembly_timeout_ms;
+        let before = self.pending.len();
+        self.pending
+            .retain(|_, p| now_ms.saturating_sub(p.first_seen_ms) < timeout);
+        self.pending_bytes = self.pending.values().map(|p| p.bytes).sum();
+        self.stats.reassembly_dropped += (before - self.pending.len()) as u64;
+    }
+
+    /// Drop the oldest incomplete packet other than `keep`; false if there is none
+    fn drop_oldest(&mut self, keep: &(NodeId, u32)) -> bool {
+        let oldest = self
+            .pending
+            .iter()
+            .filter(|(k, _)| *k != keep)
+            .min_by_key(|(_, p)| p.first_seen_ms)
+            .map(|(k, _)| k.clone());
+        let Some(key) = oldest else {
+            return false;
+        };
+        self.remove_pending(&key);
+        self.stats.reassembly_dropped += 1;
+        true
+    }
+
+    fn remove_pending(&mut self, key: &(NodeId, u32)) -> Option<PendingPacket> {
+        let pending = self.pending.remove(key)?;
+        self.pending_bytes -= pending.bytes;
+        Some(pending)
+    }
+}
+
+#[cfg(all(feature = "tun", target_os = "linux"))]
+pub use device::{run, TunDevice};
+
+#[cfg(all(feature = "tun", target_os = "linux"))]
+mod device {
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::sync::Arc;
+
+    use tokio::io::unix::AsyncFd;
+    use tokio::sync::{broadcast, Mutex};
+
+    use super::{TunError, TunTunnel};
+    use crate::network::{DeliveryEvent, DistributedNode};
+    use crate::ttl_policy::QosClass;
+
+    /// A Linux TUN device without packet information headers
+    pub struct TunDevice {
+        fd: AsyncFd<OwnedFd>,
+        name: String,
+    }
+
+    fn ifreq(name: &str) -> io::Result<libc::ifreq> {
+        if name.len() >= libc::IFNAMSIZ {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "interface name too long"));
+        }
+        // SAFETY: ifreq is plain old data; all-zero is a valid value
+        let mut req: libc::ifreq = unsafe { std::mem::zeroed() };
+        for (dst, src) in req.ifr_name.iter_mut().zip(name.bytes()) {
+            *dst = src as libc::c_char;
+        }
+        Ok(req)
+    }
+
+    fn check(ret: libc::c_int) -> io::Result<()> {
+        if ret < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    impl TunDevice {
+        /// Create (or attach to) a TUN device, set its MTU and bring it up
+        ///
+        /// Needs CAP_NET_ADMIN. Addresses are assigned outside, e.g. with
+        /// `ip addr add 10.1.0.1/16 dev drfe0`.
+        pub fn open(name: &str, mtu: usize) -> io::Result<Self> {
+            // SAFETY: plain syscalls on descriptors we own, with valid ifreq structs
+            unsafe {
+                let raw = libc::open(c"/dev/net/tun".as_ptr(), libc::O_RDWR | libc::O_NONBLOCK | libc::O_CLOEXEC);
+                check(raw)?;
+                let fd = OwnedFd::from_raw_fd(raw);
+
+                let mut req = ifreq(name)?;
+                req.ifr_ifru.ifru_flags = (libc::IFF_TUN | libc::IFF_NO_PI) as libc::c_short;
+                check(libc::ioctl(fd.as_raw_fd(), libc::TUNSETIFF, &mut req))?;
+                let name: String = req
+                    .ifr_name
+                    .iter()
+                    .take_while(|c| **c != 0)
+                    .map(|c| *c as u8 as char)
+                    .collect();
+
+                let sock = libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+                check(sock)?;
+                let sock = OwnedFd::from_raw_fd(sock);
+                let mut req = ifreq(&name)?;
+                req.ifr_ifru.ifru_mtu = mtu as libc::c_int;
+                check(libc::ioctl(sock.as_raw_fd(), libc::SIOCSIFMTU, &mut req))?;
+                check(libc::ioctl(sock.as_raw_fd(), libc::SIOCGIFFLAGS, &mut req))?;
+                req.ifr_ifru.ifru_flags |= (libc::IFF_UP | libc::IFF_RUNNING) as libc::c_short;
+                check(libc::ioctl(sock.as_raw_fd(), libc::SIOCSIFFLAGS, &mut req))?;
+
+                Ok(Self { fd: AsyncFd::new(fd)?, name })
+            }
+        }
+
+        pub fn name(&self) -> &str {
+            &self.name
+        }
+
+        /// Read one IP packet
+        pub async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+            loop {
+                let mut guard = self.fd.readable().await?;
+                let result = guard.try_io(|fd| {
+                    // SAFETY: buf is valid for writes of buf.len() bytes
+                    let n = unsafe { libc::read(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+                    if n < 0 {
+                        Err(io::Error::last_os_error())
+                    } else {
+                        Ok(n as usize)
+                    }
+                });
+                if let Ok(result) = result {
+                    return result;
+                }
+            }
+        }
+
+        /// Write one IP packet
+        pub async fn send(&self, packet: &[u8]) -> io::Result<usize> {
+            loop {
+                let mut guard = self.fd.writable().await?;
+                let result = guard.try_io(|fd| {
+                    // SAFETY: packet is valid for reads of packet.len() bytes
+                    let n = unsafe { libc::write(fd.as_raw_fd(), packet.as_ptr().cast(), packet.len()) };
+                    if n < 0 {
+                        Err(io::Error::last_os_error())
+                    } else {
+                        Ok(n as usize)
+                    }
+                });
+                if let Ok(result) = result {
+                    return result;
+                }
+            }
+        }
+    }
+
+    /// Bridge a TUN device and a started node until either side fails
+    ///
+    /// Packets read from the device go out as Bulk Data packets; tunnel
+    /// frames delivered to the node are reassembled and written back.
+    pub async fn run(
+        node: Arc<DistributedNode>,
+        device: TunDevice,
+        tunnel: Arc<Mutex<TunTunnel>>,
+    ) -> Result<(), TunError> {
+        let device = Arc::new(device);
+        let mut deliveries = node.subscribe_deliveries();
+
+        let outbound = {
+            let (node, device, tunnel) = (Arc::clone(&node), Arc::clone(&device), Arc::clone(&tunnel));
+            async move {
+                let mut buf = vec![0u8; 65536];
+                loop {
+                    let len = device.recv(&mut buf).await.map_err(|e| TunError::Device(e.to_string()))?;
+                    let Ok((dest, frames)) = tunnel.lock().await.outbound(&buf[..len]) else {
+                        continue;
+                    };
+                    for frame in frames {
+                        // IP tolerates loss; the next retransmission is the application's
+                        let _ = node.send_packet_with_qos(dest.clone(), frame, QosClass::Bulk).await;
+                    }
+                }
+            }
+        };
+
+        let inbound = async move {
+            loop {
+                let (source, payload) = match deliveries.recv().await {
+                    Ok(DeliveryEvent::Delivered { source, payload, .. }) => (source, payload),
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => {
+                        return Err(TunError::Device("node stopped".to_string()));
+                    }
+                };
+                let now_ms = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |d| d.as_millis() as u64);
+                let packet = tunnel.lock().await.inbound(&source, &payload, now_ms);
+                if let Some(packet) = packet {
+                    device.send(&packet).await.map_err(|e| TunError::Device(e.to_string()))?;
+                }
+            }
+        };
+
+        tokio::select! {
+            result = outbound => result,
+            result = inbound => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A packet from the "sender" node's prefix
+    fn ipv4_packet(dst: [u8; 4], len: usize) -> Vec<u8> {
+        let mut packet = vec![0u8; len];
+        packet[0] = 0x45;
+        packet[12..16].copy_from_slice(&[10, 9, 0, 1]);
+        packet[16..20].copy_from_slice(&dst);
+        for (i, b) in packet.iter_mut().enumerate().skip(20) {
+            *b = i as u8;
+        }
+        packet
+    }
+
+    fn config() -> TunConfig {
+        TunConfig {
+            fragment_size: 500,
+            routes: HashMap::from([
+                ("10.1.0.0/16".to_string(), "site-a".to_string()),
+                ("10.1.2.3".to_string(), "laptop".to_string()),
+                ("fd00::/64".to_string(), "site-b".to_string()),
+                ("10.9.0.0/16".to_string(), "sender".to_string()),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_route_table_longest_prefix() {
+        let table = config().route_table().unwrap();
+        let lookup = |addr: &str| table.lookup(&addr.parse().unwrap()).map(|n| n.0.clone());
+        assert_eq!(lookup("10.1.2.3"), Some("laptop".to_string()));
+        assert_eq!(lookup("10.1.9.9"), Some("site-a".to_string()));
+        assert_eq!(lookup("fd00::42"), Some("site-b".to_string()));
+        assert_eq!(lookup("10.2.0.1"), None);
+
+        assert!(IpRoute::parse("10.0.0.0/33", NodeId::new("x")).is_err());
+        assert_eq!(IpRoute::parse("10.1.2.3/8", NodeId::new("x")).unwrap().network, "10.0.0.0".parse::<IpAddr>().unwrap());
+        assert!(TunConfig { mtu: 100, ..config() }.validate().is_err());
+        assert!(TunConfig { max_pending_bytes: 1000, ..config() }.validate().is_err());
+        assert!(config().validate().is_ok());
+    }
+
+    #[test]
+    fn test_fragmentation_round_trip() {
+        let mut sender = TunTunnel::new(config()).unwrap();
+        let mut receiver = TunTunnel::new(config()).unwrap();
+        let packet = ipv4_packet([10, 1, 7, 7], 1300);
+
+        let (dest, mut frames) = sender.outbound(&packet).unwrap();
+        assert_eq!(dest, NodeId::new("site-a"));
+        assert_eq!(frames.len(), 3);
+
+        // Out of order and duplicated, with foreign Data in between
+        frames.reverse();
+        let source = NodeId::new("sender");
+        assert_eq!(receiver.inbound(&source, &frames[0], 0), None);
+        assert_eq!(receiver.inbound(&source, &frames[0], 0), None);
+        assert_eq!(receiver.inbound(&source, b"not a tunnel frame", 0), None);
+        assert_eq!(receiver.inbound(&source, &frames[1], 0), None);
+        assert_eq!(receiver.inbound(&source, &frames[2], 0), Some(packet));
+        assert_eq!(receiver.stats().packets_received, 1);
+
+        assert_eq!(
+            sender.outbound(&ipv4_packet([10, 1, 0, 1], 1500)).unwrap_err().code(),
+            "tun.too_large"
+        );
+        assert_eq!(sender.outbound(&ipv4_packet([8, 8, 8, 8], 100)).unwrap_err().code(), "tun.no_route");
+        assert_eq!(sender.stats(), TunStats {
+            packets_sent: 1,
+            fragments_sent: 3,
+            dropped_no_route: 1,
+            dropped_oversize: 1,
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn test_incomplete_packets_expire() {
+        let mut sender = TunTunnel::new(config()).unwrap();
+        let mut receiver = TunTunnel::new(TunConfig { max_pending: 1, ..config() }).unwrap();
+        let source = NodeId::new("sender");
+
+        let (_, first) = sender.outbound(&ipv4_packet([10, 1, 0, 1], 1000)).unwrap();
+        let (_, second) = sender.outbound(&ipv4_packet([10, 1, 0, 2], 1000)).unwrap();
+        assert_eq!(receiver.inbound(&source, &first[0], 0), None);
+        // Capacity of one: the second packet evicts the first
+        assert_eq!(receiver.inbound(&source, &second[0], 10), None);
+        assert_eq!(receiver.stats().reassembly_dropped, 1);
+
+        // Too late to complete
+        receiver.expire(10_000);
+        assert_eq!(receiver.stats().reassembly_dropped, 2);
+        assert_eq!(receiver.inbound(&source, &second[1], 10_000), None);
+    }
+
+    #[test]
+    fn test_inbound_rejects_spoofed_and_oversize_frames() {
+        let mut sender = TunTunnel::new(config()).unwrap();
+        let mut receiver = TunTunnel::new(config()).unwrap();
+        let source = NodeId::new("sender");
+
+        // Only site-a may send from 10.1.0.0/16
+        let mut spoofed = ipv4_packet([10, 1, 0, 1], 100);
+        spoofed[12..16].copy_from_slice(&[10, 1, 0, 9]);
+        let (_, frames) = sender.outbound(&spoofed).unwrap();
+        assert_eq!(receiver.inbound(&source, &frames[0], 0), None);
+        assert_eq!(receiver.inbound(&NodeId::new("site-a"), &frames[0], 0), Some(spoofed));
+
+        // An MTU of 1400 in 500-byte fragments allows at most 3 of them
+        let frame = |index, count, len| TunFrame { packet_id: 7, index, count, data: vec![0; len] }.encode();
+        assert_eq!(receiver.inbound(&source, &frame(0, 4, 100), 0), None);
+        assert_eq!(receiver.inbound(&source, &frame(0, 1, 501), 0), None);
+        assert_eq!(receiver.inbound(&source, &frame(0, 2, 500), 0), None);
+        assert_eq!(receiver.inbound(&source, &frame(1, 3, 500), 0), None);
+        assert_eq!(receiver.stats().dropped_invalid, 3);
+        assert_eq!(receiver.stats().dropped_spoofed, 1);
+    }
+
+    #[test]
+    fn test_pending_bytes_capped() {
+        let mut sender = TunTunnel::new(config()).unwrap();
+        let mut receiver = TunTunnel::new(TunConfig { max_pending_bytes: 1400, ..config() }).unwrap();
+        let source = NodeId::new("sender");
+
+        let packets: Vec<Vec<Vec<u8>>> =
+            (1..=3).map(|i| sender.outbound(&ipv4_packet([10, 1, 0, i], 1400)).unwrap().1).collect();
+        for (at, frames) in packets.iter().enumerate() {
+            assert_eq!(receiver.inbound(&source, &frames[0], at as u64), None);
+            assert_eq!(receiver.inbound(&source, &frames[1], at as u64), None);
+        }
+        // 1000 bytes held per packet: each new one evicts the previous
+        assert_eq!(receiver.stats().reassembly_dropped, 2);
+        assert_eq!(receiver.inbound(&source, &packets[1][2], 3), None);
+        assert_eq!(receiver.inbound(&source, &packets[2][2], 3), Some(ipv4_packet([10, 1, 0, 3], 1400)));
+    }
+}
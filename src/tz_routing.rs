@@ -14,10 +14,50 @@
 //!    - p(v): closest landmark
 //!    - B(v): "bunch" = nodes closer than p(v)
 //! 3. Routing uses bunch membership or landmark hops
+//!
+//! Bunch size grows super-linearly on skewed topologies, so tables can be
+//! pruned under a per-node memory budget (`enforce_memory_budget`) and stored
+//! delta-encoded (`CompactBunchTable`).
+//!
+//! Tables are shipped and stored in a versioned MessagePack form
+//! (`to_msgpack`) with every map written in sorted order, so the same table
+//! always encodes to the same bytes.
 
 use crate::coordinates::NodeId;
+use crate::failpoint::fail_point;
+use crate::graph::{BfsScratch, CsrGraph};
+use crate::memory::{self, MemoryFootprint, MemoryUsage};
 use rayon::prelude::*;
-use std::collections::{HashMap, HashSet, VecDeque};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+
+/// Current version of the serialized table format
+pub const TZ_TABLE_VERSION: u32 = 1;
+
+/// Errors encoding or decoding a serialized routing table
+#[derive(Debug, Error)]
+pub enum TableCodecError {
+    #[error("Failed to serialize routing table: {0}")]
+    Encode(#[from] rmp_serde::encode::Error),
+
+    #[error("Failed to deserialize routing table: {0}")]
+    Decode(#[from] rmp_serde::decode::Error),
+
+    #[error("Routing table version {found} is not supported (current: {current})")]
+    UnsupportedVersion { found: u32, current: u32 },
+}
+
+impl TableCodecError {
+    /// Stable identifier for programmatic handling
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Encode(_) => "tz_table.encode",
+            Self::Decode(_) => "tz_table.decode",
+            Self::UnsupportedVersion { .. } => "tz_table.unsupported_version",
+        }
+    }
+}
 
 /// Configuration for Thorup-Zwick routing
 #[derive(Debug, Clone)]
@@ -37,6 +77,55 @@ impl Default for TZConfig {
     }
 }
 
+/// Estimated bytes per table entry (NodeId + u32 + NodeId)
+pub const BYTES_PER_ENTRY: usize = 48;
+
+/// Per-node memory budget for bunch pruning
+#[derive(Debug, Clone)]
+pub struct TZMemoryBudget {
+    /// Maximum estimated bytes of bunch entries per node
+    pub per_node_bytes: usize,
+    /// An entry is only pruned if routing to it via landmarks keeps stretch within this bound
+    pub max_stretch: f64,
+}
+
+impl Default for TZMemoryBudget {
+    fn default() -> Self {
+        Self {
+            per_node_bytes: 4096,
+            max_stretch: 3.0,
+        }
+    }
+}
+
+/// Outcome of bunch pruning
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TZPruneStats {
+    /// Bunch entries before pruning
+    pub entries_before: usize,
+    /// Bunch entries after pruning
+    pub entries_after: usize,
+    /// Nodes still above budget because their remaining entries are needed for the stretch bound
+    pub nodes_over_budget: usize,
+}
+
+/// Stretch/memory trade-off achieved by a routing table
+#[derive(Debug, Clone)]
+pub struct TZMemoryReport {
+    /// Total table entries
+    pub entries: usize,
+    /// Estimated bytes with plain hash map storage
+    pub estimated_bytes: usize,
+    /// Estimated bytes with delta-encoded bunch tables
+    pub compact_bytes: usize,
+    /// Average stretch over sampled pairs
+    pub avg_stretch: f64,
+    /// Maximum stretch over sampled pairs
+    pub max_stretch: f64,
+    /// Sampled pairs with stretch > 3
+    pub violations: usize,
+}
+
 /// Precomputed routing information for a single node
 #[derive(Debug, Clone)]
 pub struct TZNodeInfo {
@@ -94,22 +183,21 @@ impl TZRoutingTable {
             return Err("No landmarks selected".to_string());
         }
 
-        // Compute distances from all landmarks (BFS from each) — parallelized with rayon
-        let landmark_results: Vec<(&NodeId, HashMap<NodeId, u32>, HashMap<NodeId, NodeId>)> =
-            landmarks.par_iter()
-                .map(|landmark| {
-                    let (distances, parents) = Self::bfs_with_parents(adjacency, landmark);
-                    (landmark, distances, parents)
-                })
-                .collect();
-
-        let mut landmark_full_distances: HashMap<&NodeId, HashMap<NodeId, u32>> = HashMap::new();
-        let mut landmark_parent: HashMap<&NodeId, HashMap<NodeId, NodeId>> = HashMap::new();
-
-        for (landmark, distances, parents) in landmark_results {
-            landmark_full_distances.insert(landmark, distances);
-            landmark_parent.insert(landmark, parents);
-        }
+        // BFS tree from each landmark over the CSR graph — parallelized with rayon
+        let graph = CsrGraph::from_adjacency(adjacency);
+        let landmark_idx: Vec<u32> = landmarks
+            .iter()
+            .map(|l| graph.index_of(l).expect("landmarks are graph nodes"))
+            .collect();
+        let landmark_trees: Vec<BfsScratch> = landmark_idx
+            .par_iter()
+            .map(|&l| {
+                let mut tree = BfsScratch::new(graph.len());
+                graph.bfs(l, &mut tree);
+                tree
+            })
+            .collect();
+        fail_point!("tz.rebuild", |error| Err(error.to_string()));
 
         // For each node, find closest landmark and compute bunch — parallelized with rayon
         // Optimization: Skip bunch computation for very large graphs
@@ -120,119 +208,104 @@ impl TZRoutingTable {
             eprintln!("  Large graph detected ({} nodes). Skipping bunch computation for efficiency.", n);
         }
 
-        let all_nodes: Vec<&NodeId> = adjacency.keys().collect();
+        let all_nodes: Vec<u32> = adjacency.keys().filter_map(|id| graph.index_of(id)).collect();
 
-        let per_node_results: Vec<(NodeId, TZNodeInfo, Option<(NodeId, NodeId)>)> =
+        let per_node_results: Vec<(NodeId, TZNodeInfo, NodeId)> =
             all_nodes.par_iter()
-                .map(|node| {
+                .map_init(|| BfsScratch::new(graph.len()), |scratch, &node| {
                     // Find closest landmark
-                    let mut closest_landmark = landmarks[0].clone();
+                    let mut closest = 0;
                     let mut min_distance = u32::MAX;
 
-                    for landmark in &landmarks {
-                        if let Some(dists) = landmark_full_distances.get(landmark) {
-                            if let Some(&dist) = dists.get(*node) {
-                                if dist < min_distance {
-                                    min_distance = dist;
-                                    closest_landmark = landmark.clone();
-                                }
+                    for (i, tree) in landmark_trees.iter().enumerate() {
+                        if let Some(dist) = tree.distance(node) {
+                            if dist < min_distance {
+                                min_distance = dist;
+                                closest = i;
                             }
                         }
                     }
 
-                    // Compute bunch only for small graphs
-                    let bunch = if compute_bunches {
-                        let (node_distances, node_parents) = Self::bfs_with_parents(adjacency, node);
-                        let mut bunch: HashMap<NodeId, (u32, NodeId)> = HashMap::new();
-                        for (w, &dist) in &node_distances {
-                            if dist < min_distance {
-                                let next_hop = Self::find_next_hop_from_parents(node, w, &node_parents);
-                                bunch.insert(w.clone(), (dist, next_hop));
-                            }
+                    // Compute bunch only for small graphs: nodes strictly closer than the landmark
+                    let mut bunch: HashMap<NodeId, (u32, NodeId)> = HashMap::new();
+                    if compute_bunches && min_distance > 0 {
+                        graph.bfs_bounded(node, min_distance - 1, scratch);
+                        for &w in scratch.order() {
+                            let next_hop = scratch.first_hop(w).unwrap_or(w);
+                            bunch.insert(
+                                graph.id(w).clone(),
+                                (scratch.distance(w).unwrap_or(0), graph.id(next_hop).clone()),
+                            );
                         }
-                        bunch
-                    } else {
-                        HashMap::new()
-                    };
+                    }
 
-                    // Compute next hop toward closest landmark
-                    let next_hop_entry = landmark_parent.get(&closest_landmark)
-                        .map(|parents| {
-                            let next = Self::find_next_hop_toward_source(node, &closest_landmark, parents);
-                            ((*node).clone(), next)
-                        });
+                    // Next hop toward closest landmark: our parent in its BFS tree
+                    let next_hop = landmark_trees[closest].parent(node).unwrap_or(node);
 
                     let info = TZNodeInfo {
-                        closest_landmark,
+                        closest_landmark: landmarks[closest].clone(),
                         landmark_distance: min_distance,
                         bunch,
                     };
 
-                    ((*node).clone(), info, next_hop_entry)
+                    (graph.id(node).clone(), info, graph.id(next_hop).clone())
                 })
                 .collect();
 
         let mut node_info: HashMap<NodeId, TZNodeInfo> = HashMap::with_capacity(n);
         let mut to_landmark_next_hop: HashMap<NodeId, NodeId> = HashMap::with_capacity(n);
 
-        for (node_id, info, next_hop_entry) in per_node_results {
+        for (node_id, info, next_hop) in per_node_results {
+            to_landmark_next_hop.insert(node_id.clone(), next_hop);
             node_info.insert(node_id, info);
-            if let Some((key, val)) = next_hop_entry {
-                to_landmark_next_hop.insert(key, val);
-            }
         }
 
         // Compute landmark-to-landmark distances and routing
         let mut landmark_distances: HashMap<(NodeId, NodeId), u32> = HashMap::new();
         let mut landmark_next_hop: HashMap<(NodeId, NodeId), NodeId> = HashMap::new();
 
-        for l1 in &landmarks {
-            if let Some(dists) = landmark_full_distances.get(l1) {
-                if let Some(parents) = landmark_parent.get(l1) {
-                    for l2 in &landmarks {
-                        if l1 != l2 {
-                            if let Some(&dist) = dists.get(l2) {
-                                landmark_distances.insert((l1.clone(), l2.clone()), dist);
-                                
-                                // Next hop from l2 toward l1 (using l1's BFS tree)
-                                let next = Self::find_next_hop_toward_source(l2, l1, parents);
-                                landmark_next_hop.insert((l2.clone(), l1.clone()), next);
-                            }
-                        }
-                    }
+        for (i, l1) in landmarks.iter().enumerate() {
+            let tree = &landmark_trees[i];
+            for (j, l2) in landmarks.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                if let Some(dist) = tree.distance(landmark_idx[j]) {
+                    landmark_distances.insert((l1.clone(), l2.clone()), dist);
+
+                    // Next hop from l2 toward l1 (using l1's BFS tree)
+                    let next = tree.parent(landmark_idx[j]).map_or_else(|| l2.clone(), |p| graph.id(p).clone());
+                    landmark_next_hop.insert((l2.clone(), l1.clone()), next);
                 }
             }
         }
 
         // Build from_landmark_next_hop and landmark_bfs_parents for Phase 3 path computation
         // For each landmark, store the BFS parent of every reachable node
-        // and compute next-hop FROM landmark TOWARD each node — parallelized with rayon
-        let all_nodes_for_phase3: Vec<&NodeId> = adjacency.keys().collect();
-
+        // and the next-hop FROM landmark TOWARD each node — parallelized with rayon
         let phase3_results: Vec<(Vec<((NodeId, NodeId), NodeId)>, Vec<((NodeId, NodeId), NodeId)>)> =
             landmarks.par_iter()
-                .filter_map(|landmark| {
-                    landmark_parent.get(landmark).map(|parents| {
-                        let mut bfs_parents_entries = Vec::new();
-                        for (node, parent) in parents.iter() {
+                .zip(landmark_trees.par_iter())
+                .map(|(landmark, tree)| {
+                    let mut bfs_parents_entries = Vec::with_capacity(tree.order().len());
+                    for &node in tree.order() {
+                        if let Some(parent) = tree.parent(node) {
                             bfs_parents_entries.push(
-                                ((landmark.clone(), node.clone()), parent.clone())
+                                ((landmark.clone(), graph.id(node).clone()), graph.id(parent).clone())
                             );
                         }
+                    }
 
-                        let mut from_lm_entries = Vec::new();
-                        for node in &all_nodes_for_phase3 {
-                            if *node == landmark { continue; }
-                            let next = Self::find_next_hop_from_source_bfs(landmark, node, parents);
-                            if let Some(next_hop) = next {
-                                from_lm_entries.push(
-                                    ((landmark.clone(), (*node).clone()), next_hop)
-                                );
-                            }
+                    let mut from_lm_entries = Vec::new();
+                    for &node in &all_nodes {
+                        if let Some(next_hop) = tree.first_hop(node) {
+                            from_lm_entries.push(
+                                ((landmark.clone(), graph.id(node).clone()), graph.id(next_hop).clone())
+                            );
                         }
+                    }
 
-                        (bfs_parents_entries, from_lm_entries)
-                    })
+                    (bfs_parents_entries, from_lm_entries)
                 })
                 .collect();
 
@@ -316,117 +389,6 @@ impl TZRoutingTable {
         landmarks
     }
 
-    /// BFS returning distances and parent pointers
-    fn bfs_with_parents(
-        adjacency: &HashMap<NodeId, Vec<NodeId>>,
-        source: &NodeId,
-    ) -> (HashMap<NodeId, u32>, HashMap<NodeId, NodeId>) {
-        let mut distances = HashMap::new();
-        let mut parents = HashMap::new();
-        let mut queue = VecDeque::new();
-
-        distances.insert(source.clone(), 0);
-        queue.push_back(source.clone());
-
-        while let Some(current) = queue.pop_front() {
-            let current_dist = *distances.get(&current).unwrap();
-            
-            if let Some(neighbors) = adjacency.get(&current) {
-                for neighbor in neighbors {
-                    if !distances.contains_key(neighbor) {
-                        distances.insert(neighbor.clone(), current_dist + 1);
-                        parents.insert(neighbor.clone(), current.clone());
-                        queue.push_back(neighbor.clone());
-                    }
-                }
-            }
-        }
-
-        (distances, parents)
-    }
-
-    /// Find next hop from 'from' toward 'to' using parent map (BFS tree from 'from')
-    fn find_next_hop_from_parents(
-        from: &NodeId,
-        to: &NodeId,
-        parents: &HashMap<NodeId, NodeId>,
-    ) -> NodeId {
-        if from == to {
-            return from.clone();
-        }
-
-        // Walk back from 'to' toward 'from' using parent pointers
-        let mut path = vec![to.clone()];
-        let mut current = to.clone();
-        
-        while let Some(parent) = parents.get(&current) {
-            path.push(parent.clone());
-            if parent == from {
-                break;
-            }
-            current = parent.clone();
-        }
-
-        // The second-to-last element is the next hop from 'from'
-        if path.len() >= 2 {
-            path[path.len() - 2].clone()
-        } else {
-            to.clone()
-        }
-    }
-
-    /// Find next hop toward source using BFS tree rooted at source
-    fn find_next_hop_toward_source(
-        from: &NodeId,
-        source: &NodeId,
-        parent_from_source: &HashMap<NodeId, NodeId>,
-    ) -> NodeId {
-        if from == source {
-            return from.clone();
-        }
-
-        // parent_from_source[v] = parent of v in BFS tree from source
-        // So to go from 'from' toward 'source', we follow parents
-        parent_from_source.get(from).cloned().unwrap_or_else(|| from.clone())
-    }
-
-    /// Find next hop FROM source TOWARD destination using BFS tree rooted at source.
-    /// parent_from_source[v] = parent of v in BFS from source.
-    /// We walk from destination back to source, then return the first child of source on that path.
-    fn find_next_hop_from_source_bfs(
-        source: &NodeId,
-        destination: &NodeId,
-        parent_from_source: &HashMap<NodeId, NodeId>,
-    ) -> Option<NodeId> {
-        if source == destination {
-            return None;
-        }
-        // Walk from destination toward source via parent pointers
-        let mut path_back = vec![destination.clone()];
-        let mut current = destination.clone();
-        let mut steps = 0;
-        let max_steps = parent_from_source.len() + 1;
-        while &current != source {
-            if let Some(parent) = parent_from_source.get(&current) {
-                path_back.push(parent.clone());
-                current = parent.clone();
-                steps += 1;
-                if steps > max_steps {
-                    return None; // cycle safety
-                }
-            } else {
-                return None; // unreachable
-            }
-        }
-        // path_back = [destination, ..., child_of_source, source]
-        // next hop from source = second-to-last element
-        if path_back.len() >= 2 {
-            Some(path_back[path_back.len() - 2].clone())
-        } else {
-            None
-        }
-    }
-
     /// Reconstruct full BFS-tree path from source to destination
     /// parent_from_source[v] = parent of v in BFS from source
     fn reconstruct_bfs_path(
@@ -495,22 +457,20 @@ impl TZRoutingTable {
                 return Some(path);
             }
 
-            match self.get_next_hop(&current, destination) {
-                Some((next, is_dest)) => {
-                    if visited.contains(&next) && !is_dest {
-                        // Cycle detected, try alternative routing
-                        return self.compute_path_via_landmarks(source, destination);
-                    }
+            // Follow bunch entries while they last; the first node without
+            // one commits the rest of the route to the landmark detour
+            let next = self.node_info.get(&current)?.bunch.get(destination).map(|(_, hop)| hop.clone());
+            match next {
+                Some(next) if !visited.contains(&next) => {
                     visited.insert(next.clone());
                     path.push(next.clone());
-                    if is_dest {
-                        return Some(path);
-                    }
                     current = next;
                 }
+                Some(_) => return self.compute_path_via_landmarks(source, destination),
                 None => {
-                    // Fallback to landmark routing
-                    return self.compute_path_via_landmarks(source, destination);
+                    let detour = self.compute_path_via_landmarks(&current, destination)?;
+                    path.extend(detour.into_iter().skip(1));
+                    return Some(path);
                 }
             }
         }
@@ -519,99 +479,39 @@ impl TZRoutingTable {
         self.compute_path_via_landmarks(source, destination)
     }
 
-    /// Compute path via landmarks (guaranteed to work if graph is connected)
-    /// Path: source -> src_landmark -> dst_landmark -> destination
-    /// Each phase follows BFS-tree shortest paths faithfully (no deduplication).
+    /// Compute path via the source's landmark (guaranteed to work if graph is connected)
+    /// Path: source -> src_landmark -> destination, both phases along the
+    /// landmark's BFS tree. A destination outside the source's bunch is at
+    /// least as far as the landmark, so the detour is at most 3x the shortest path.
     fn compute_path_via_landmarks(
         &self,
         source: &NodeId,
         destination: &NodeId,
     ) -> Option<Vec<NodeId>> {
-        let src_info = self.node_info.get(source)?;
-        let dst_info = self.node_info.get(destination)?;
-
-        let src_landmark = &src_info.closest_landmark;
-        let dst_landmark = &dst_info.closest_landmark;
+        let landmark = &self.node_info.get(source)?.closest_landmark;
+        self.node_info.get(destination)?;
 
-        // Phase 1: source -> src_landmark (follow to_landmark_next_hop)
-        let mut path = vec![source.clone()];
-        if source != src_landmark {
-            let mut current = source.clone();
-            let mut visited = HashSet::new();
-            visited.insert(current.clone());
-            
-            while current != *src_landmark {
-                if let Some(next) = self.to_landmark_next_hop.get(&current) {
-                    if visited.contains(next) {
-                        break; // Avoid loop
-                    }
-                    visited.insert(next.clone());
-                    path.push(next.clone());
-                    current = next.clone();
-                } else {
-                    break;
-                }
-            }
-        }
-
-        // Phase 2: src_landmark -> dst_landmark (follow landmark_next_hop)
-        if src_landmark != dst_landmark {
-            let mut current = src_landmark.clone();
-            let mut visited = HashSet::new();
-            visited.insert(current.clone());
-            
-            while current != *dst_landmark {
-                if let Some(next) = self.landmark_next_hop.get(&(current.clone(), dst_landmark.clone())) {
-                    if visited.contains(next) {
-                        break;
-                    }
-                    visited.insert(next.clone());
-                    path.push(next.clone());
-                    current = next.clone();
-                } else {
-                    break;
-                }
-            }
-        }
+        let mut path = self.tree_path(landmark, source)?;
+        path.reverse();
+        let down = self.tree_path(landmark, destination)?;
+        path.extend(down.into_iter().skip(1));
+        Some(path)
+    }
 
-        // Phase 3: dst_landmark -> destination
-        // Use the stored BFS parent map from dst_landmark to reconstruct the path
-        if path.last() != Some(destination) {
-            let landmark_for_dst = dst_landmark;
-            // Reconstruct path from dst_landmark to destination using BFS parents
-            let mut sub_path = vec![destination.clone()];
-            let mut current = destination.clone();
-            let max_steps = self.node_info.len();
-            for _ in 0..max_steps {
-                if &current == landmark_for_dst {
-                    break;
-                }
-                if let Some(parent) = self.landmark_bfs_parents.get(&(landmark_for_dst.clone(), current.clone())) {
-                    sub_path.push(parent.clone());
-                    current = parent.clone();
-                } else {
-                    // Fallback: just append destination
-                    if path.last() != Some(destination) {
-                        path.push(destination.clone());
-                    }
-                    return Some(path);
-                }
-            }
-            // sub_path is [destination, ..., child_of_landmark, dst_landmark(maybe)]
-            // Reverse to get dst_landmark -> ... -> destination
-            sub_path.reverse();
-            // Skip the first element if it's already the last element of path (dst_landmark)
-            let start = if !sub_path.is_empty() && path.last() == Some(&sub_path[0]) {
-                1
-            } else {
-                0
-            };
-            for node in &sub_path[start..] {
-                path.push(node.clone());
+    /// Path from `landmark` down to `node` in the landmark's BFS tree
+    fn tree_path(&self, landmark: &NodeId, node: &NodeId) -> Option<Vec<NodeId>> {
+        let mut path_back = vec![node.clone()];
+        let mut current = node.clone();
+        for _ in 0..=self.node_info.len() {
+            if &current == landmark {
+                path_back.reverse();
+                return Some(path_back);
             }
+            let parent = self.landmark_bfs_parents.get(&(landmark.clone(), current))?;
+            path_back.push(parent.clone());
+            current = parent.clone();
         }
-
-        Some(path)
+        None
     }
 
     /// Get total memory usage estimate (number of entries)
@@ -625,6 +525,91 @@ impl TZRoutingTable {
         bunch_entries + landmark_entries + node_entries + from_landmark_entries + bfs_parent_entries
     }
 
+    /// Length of the landmark detour from `source` to `destination`:
+    /// source -> p(source) -> destination
+    fn landmark_detour(&self, source: &NodeId, destination: &NodeId) -> Option<u32> {
+        let src_info = self.node_info.get(source)?;
+        let down = self.tree_path(&src_info.closest_landmark, destination)?;
+        Some(src_info.landmark_distance + (down.len() as u32 - 1))
+    }
+
+    /// Prune bunches down to a per-node memory budget.
+    ///
+    /// For each node over budget, entries are removed in order of the stretch
+    /// their landmark detour would cause, cheapest first. Entries whose detour
+    /// would exceed `budget.max_stretch` are always kept, so a node may remain
+    /// over budget.
+    pub fn enforce_memory_budget(&mut self, budget: &TZMemoryBudget) -> TZPruneStats {
+        let max_entries = budget.per_node_bytes / BYTES_PER_ENTRY;
+        let entries_before: usize = self.node_info.values().map(|info| info.bunch.len()).sum();
+
+        let removals: Vec<(NodeId, Vec<NodeId>, bool)> = self
+            .node_info
+            .par_iter()
+            .filter(|(_, info)| info.bunch.len() > max_entries)
+            .map(|(node, info)| {
+                let mut candidates: Vec<(f64, &NodeId)> = info
+                    .bunch
+                    .iter()
+                    .filter(|(w, (dist, _))| *dist > 0 && *w != node)
+                    .filter_map(|(w, (dist, _))| {
+                        let stretch = self.landmark_detour(node, w)? as f64 / *dist as f64;
+                        (stretch <= budget.max_stretch).then_some((stretch, w))
+                    })
+                    .collect();
+                candidates.sort_by(|a, b| {
+                    a.0.partial_cmp(&b.0)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                        .then_with(|| a.1 .0.cmp(&b.1 .0))
+                });
+
+                let excess = info.bunch.len() - max_entries;
+                let pruned: Vec<NodeId> = candidates.into_iter().take(excess).map(|(_, w)| w.clone()).collect();
+                let over_budget = pruned.len() < excess;
+                (node.clone(), pruned, over_budget)
+            })
+            .collect();
+
+        let mut nodes_over_budget = 0;
+        for (node, pruned, over_budget) in removals {
+            if let Some(info) = self.node_info.get_mut(&node) {
+                for w in &pruned {
+                    info.bunch.remove(w);
+                }
+            }
+            if over_budget {
+                nodes_over_budget += 1;
+            }
+        }
+
+        TZPruneStats {
+            entries_before,
+            entries_after: self.node_info.values().map(|info| info.bunch.len()).sum(),
+            nodes_over_budget,
+        }
+    }
+
+    /// Report the memory footprint and stretch of this table on `num_samples` pairs
+    pub fn memory_report(
+        &self,
+        adjacency: &HashMap<NodeId, Vec<NodeId>>,
+        num_samples: usize,
+    ) -> TZMemoryReport {
+        let entries = self.memory_usage();
+        let bunch_entries: usize = self.node_info.values().map(|info| info.bunch.len()).sum();
+        let compact = CompactBunchTable::encode(self);
+        let (avg_stretch, max_stretch, violations) = self.verify_stretch(adjacency, num_samples);
+
+        TZMemoryReport {
+            entries,
+            estimated_bytes: entries * BYTES_PER_ENTRY,
+            compact_bytes: (entries - bunch_entries) * BYTES_PER_ENTRY + compact.size_bytes(),
+            avg_stretch,
+            max_stretch,
+            violations,
+        }
+    }
+
     /// Rebuild the TZ table for a subgraph (e.g., after node/edge removal).
     /// This re-selects landmarks from surviving nodes and recomputes all structures.
     /// Much more resilient to targeted attacks than using the stale original table.
@@ -637,7 +622,8 @@ impl TZRoutingTable {
     }
 
     /// Verify stretch guarantee on a sample of pairs
-    /// Returns (avg_stretch, max_stretch, num_violations)
+    /// Returns (avg_stretch, max_stretch, num_violations); a connected pair
+    /// with no TZ path is a violation
     /// Uses ratio-of-sums (Σ tz_hops / Σ optimal) for consistency with other benchmarks
     pub fn verify_stretch(
         &self,
@@ -653,7 +639,8 @@ impl TZRoutingTable {
         let mut total_optimal: u64 = 0;
         let mut max_stretch: f64 = 0.0;
         let mut violations = 0;
-        let mut valid_samples = 0;
+        let graph = CsrGraph::from_adjacency(adjacency);
+        let mut scratch = BfsScratch::new(graph.len());
 
         // Use deterministic sampling
         for i in 0..num_samples.min(nodes.len() * nodes.len()) {
@@ -667,25 +654,28 @@ impl TZRoutingTable {
             let source = nodes[src_idx];
             let destination = nodes[dst_idx];
 
-            // Compute TZ path length
-            if let Some(tz_path) = self.compute_path(source, destination) {
-                let tz_len = (tz_path.len() as u32).saturating_sub(1); // hops
-
-                // Compute optimal path length (BFS)
-                let (distances, _) = Self::bfs_with_parents(adjacency, source);
-                if let Some(&optimal) = distances.get(destination) {
-                    if optimal > 0 {
-                        let stretch = tz_len as f64 / optimal as f64;
-                        total_tz_hops += tz_len as u64;
-                        total_optimal += optimal as u64;
-                        max_stretch = f64::max(max_stretch, stretch);
-                        valid_samples += 1;
-
-                        if stretch > 3.0 {
-                            violations += 1;
-                        }
-                    }
-                }
+            // Compute optimal path length (BFS)
+            let optimal = match (graph.index_of(source), graph.index_of(destination)) {
+                (Some(s), Some(d)) => graph.distance(s, d, &mut scratch),
+                _ => None,
+            };
+            let Some(optimal) = optimal.filter(|&hops| hops > 0) else {
+                continue;
+            };
+
+            // A reachable pair the table cannot route counts against it
+            let Some(tz_path) = self.compute_path(source, destination) else {
+                violations += 1;
+                continue;
+            };
+            let tz_len = (tz_path.len() as u32).saturating_sub(1); // hops
+            let stretch = tz_len as f64 / optimal as f64;
+            total_tz_hops += tz_len as u64;
+            total_optimal += optimal as u64;
+            max_stretch = f64::max(max_stretch, stretch);
+
+            if stretch > 3.0 {
+                violations += 1;
             }
         }
 
@@ -699,6 +689,242 @@ impl TZRoutingTable {
     }
 }
 
+/// Delta-encoded bunch tables
+///
+/// Node IDs are interned once in a shared, sorted list. Each bunch is stored as
+/// its members' indices in ascending order, written as LEB128 varints of
+/// (index delta, distance, next hop index). Bunches of nearby nodes are dense
+/// in index space, so deltas mostly fit in a single byte.
+#[derive(Debug, Clone)]
+pub struct CompactBunchTable {
+    nodes: Vec<NodeId>,
+    index: HashMap<NodeId, u32>,
+    bunches: HashMap<NodeId, Vec<u8>>,
+}
+
+impl CompactBunchTable {
+    /// Encode the bunches of a routing table
+    pub fn encode(table: &TZRoutingTable) -> Self {
+        let mut nodes: Vec<NodeId> = table.node_info.keys().cloned().collect();
+        nodes.sort_by(|a, b| a.0.cmp(&b.0));
+        let index: HashMap<NodeId, u32> = nodes.iter().enumerate().map(|(i, id)| (id.clone(), i as u32)).collect();
+
+        let bunches = table
+            .node_info
+            .iter()
+            .map(|(node, info)| {
+                let mut entries: Vec<(u32, u32, u32)> = info
+                    .bunch
+                    .iter()
+                    .filter_map(|(w, (dist, next))| Some((*index.get(w)?, *dist, *index.get(next)?)))
+                    .collect();
+                entries.sort_unstable();
+
+                let mut buf = Vec::with_capacity(entries.len() * 3);
+                let mut prev = 0;
+                for (w, dist, next) in entries {
+                    write_varint(&mut buf, w - prev);
+                    write_varint(&mut buf, dist);
+                    write_varint(&mut buf, next);
+                    prev = w;
+                }
+                (node.clone(), buf)
+            })
+            .collect();
+
+        Self { nodes, index, bunches }
+    }
+
+    /// Decode the bunch of `node`
+    pub fn bunch(&self, node: &NodeId) -> Option<HashMap<NodeId, (u32, NodeId)>> {
+        let mut bunch = HashMap::new();
+        self.scan(node, |w, dist, next| {
+            bunch.insert(self.nodes[w as usize].clone(), (dist, self.nodes[next as usize].clone()));
+            false
+        })?;
+        Some(bunch)
+    }
+
+    /// Look up a single bunch entry without decoding the whole bunch
+    pub fn lookup(&self, node: &NodeId, destination: &NodeId) -> Option<(u32, NodeId)> {
+        let target = *self.index.get(destination)?;
+        let mut found = None;
+        self.scan(node, |w, dist, next| {
+            if w == target {
+                found = Some((dist, self.nodes[next as usize].clone()));
+            }
+            w >= target
+        })?;
+        found
+    }
+
+    /// Total encoded bunch size in bytes (excluding the shared node list)
+    pub fn size_bytes(&self) -> usize {
+        self.bunches.values().map(|b| b.len()).sum()
+    }
+
+    /// Walk the entries of a bunch in index order until `visit` returns true
+    fn scan(&self, node: &NodeId, mut visit: impl FnMut(u32, u32, u32) -> bool) -> Option<()> {
+        let buf = self.bunches.get(node)?;
+        let mut pos = 0;
+        let mut w = 0;
+        while pos < buf.len() {
+            w += read_varint(buf, &mut pos)?;
+            let dist = read_varint(buf, &mut pos)?;
+            let next = read_varint(buf, &mut pos)?;
+            if visit(w, dist, next) {
+                break;
+            }
+        }
+        Some(())
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn read_varint(buf: &[u8], pos: &mut usize) -> Option<u32> {
+    let mut value = 0u32;
+    let mut shift = 0;
+    loop {
+        let byte = *buf.get(*pos)?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+        if shift > 28 {
+            return None;
+        }
+    }
+}
+
+/// Node, closest landmark, landmark distance and bunch of (member, distance, next hop)
+type NodeWire = (NodeId, NodeId, u32, Vec<(NodeId, u32, NodeId)>);
+
+/// Serialized form of a `TZRoutingTable`, maps flattened to sorted lists
+#[derive(Serialize, Deserialize)]
+struct TableWire {
+    version: u32,
+    num_landmarks: Option<usize>,
+    seed: u64,
+    landmarks: Vec<NodeId>,
+    nodes: Vec<NodeWire>,
+    landmark_distances: Vec<(NodeId, NodeId, u32)>,
+    landmark_next_hop: Vec<(NodeId, NodeId, NodeId)>,
+    to_landmark_next_hop: Vec<(NodeId, NodeId)>,
+    from_landmark_next_hop: Vec<(NodeId, NodeId, NodeId)>,
+    landmark_bfs_parents: Vec<(NodeId, NodeId, NodeId)>,
+}
+
+fn sorted_pairs<V: Clone>(map: &HashMap<(NodeId, NodeId), V>) -> Vec<(NodeId, NodeId, V)> {
+    let mut entries: Vec<_> = map.iter().map(|((a, b), v)| (a.clone(), b.clone(), v.clone())).collect();
+    entries.sort_by(|x, y| (&x.0, &x.1).cmp(&(&y.0, &y.1)));
+    entries
+}
+
+fn pair_map<V>(entries: Vec<(NodeId, NodeId, V)>) -> HashMap<(NodeId, NodeId), V> {
+    entries.into_iter().map(|(a, b, v)| ((a, b), v)).collect()
+}
+
+impl TZRoutingTable {
+    /// Serialize the table to MessagePack, byte for byte the same for equal tables
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, TableCodecError> {
+        let mut nodes: Vec<_> = self
+            .node_info
+            .iter()
+            .map(|(node, info)| {
+                let mut bunch: Vec<_> = info.bunch.iter().map(|(w, (d, next))| (w.clone(), *d, next.clone())).collect();
+                bunch.sort_by(|a, b| a.0.cmp(&b.0));
+                (node.clone(), info.closest_landmark.clone(), info.landmark_distance, bunch)
+            })
+            .collect();
+        nodes.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut to_landmark: Vec<_> = self.to_landmark_next_hop.iter().map(|(a, b)| (a.clone(), b.clone())).collect();
+        to_landmark.sort();
+
+        let wire = TableWire {
+            version: TZ_TABLE_VERSION,
+            num_landmarks: self.config.num_landmarks,
+            seed: self.config.seed,
+            landmarks: self.landmarks.clone(),
+            nodes,
+            landmark_distances: sorted_pairs(&self.landmark_distances),
+            landmark_next_hop: sorted_pairs(&self.landmark_next_hop),
+            to_landmark_next_hop: to_landmark,
+            from_landmark_next_hop: sorted_pairs(&self.from_landmark_next_hop),
+            landmark_bfs_parents: sorted_pairs(&self.landmark_bfs_parents),
+        };
+        Ok(rmp_serde::to_vec(&wire)?)
+    }
+
+    /// Deserialize a table written by `to_msgpack` of this or an older version
+    ///
+    /// Version 1 is the only version so far; a format change bumps
+    /// `TZ_TABLE_VERSION` and adds the conversion from the previous version
+    /// here.
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Self, TableCodecError> {
+        let wire: TableWire = rmp_serde::from_slice(bytes)?;
+        if wire.version != TZ_TABLE_VERSION {
+            return Err(TableCodecError::UnsupportedVersion { found: wire.version, current: TZ_TABLE_VERSION });
+        }
+        let node_info = wire
+            .nodes
+            .into_iter()
+            .map(|(node, closest_landmark, landmark_distance, bunch)| {
+                let bunch = bunch.into_iter().map(|(w, d, next)| (w, (d, next))).collect();
+                (node, TZNodeInfo { closest_landmark, landmark_distance, bunch })
+            })
+            .collect();
+        Ok(Self {
+            config: TZConfig { num_landmarks: wire.num_landmarks, seed: wire.seed },
+            landmarks: wire.landmarks,
+            node_info,
+            landmark_distances: pair_map(wire.landmark_distances),
+            landmark_next_hop: pair_map(wire.landmark_next_hop),
+            to_landmark_next_hop: wire.to_landmark_next_hop.into_iter().collect(),
+            from_landmark_next_hop: pair_map(wire.from_landmark_next_hop),
+            landmark_bfs_parents: pair_map(wire.landmark_bfs_parents),
+        })
+    }
+}
+
+/// Entries are counted as in `memory_usage`; node IDs are costed at the
+/// average length of the IDs in `node_info` rather than one by one, since
+/// the pair tables hold several copies of each
+impl MemoryFootprint for TZRoutingTable {
+    fn memory(&self) -> MemoryUsage {
+        let pairs = self.landmark_next_hop.capacity()
+            + self.from_landmark_next_hop.capacity()
+            + self.landmark_bfs_parents.capacity();
+        let bunch_slots: usize = self.node_info.values().map(|info| info.bunch.capacity()).sum();
+        let bunch_entries: usize = self.node_info.values().map(|info| info.bunch.len()).sum();
+        let ids = self.landmarks.len()
+            + self.node_info.len() * 2
+            + bunch_entries * 2
+            + self.landmark_distances.len() * 2
+            + self.landmark_next_hop.len() * 3
+            + self.to_landmark_next_hop.len() * 2
+            + self.from_landmark_next_hop.len() * 3
+            + self.landmark_bfs_parents.len() * 3;
+        let id_heap = self.node_info.keys().map(memory::id_bytes).sum::<usize>() / self.node_info.len().max(1);
+        let bytes = memory::vec_bytes::<NodeId>(self.landmarks.capacity())
+            + memory::table_bytes::<NodeId, TZNodeInfo>(self.node_info.capacity())
+            + memory::table_bytes::<NodeId, (u32, NodeId)>(bunch_slots)
+            + memory::table_bytes::<(NodeId, NodeId), u32>(self.landmark_distances.capacity())
+            + memory::table_bytes::<(NodeId, NodeId), NodeId>(pairs)
+            + memory::table_bytes::<NodeId, NodeId>(self.to_landmark_next_hop.capacity())
+            + ids * id_heap;
+        MemoryUsage::new(self.memory_usage(), bytes)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -758,6 +984,90 @@ mod tests {
         assert_eq!(violations, 0, "Found {} stretch violations", violations);
     }
 
+    fn create_grid_graph(side: usize) -> HashMap<NodeId, Vec<NodeId>> {
+        let id = |r: usize, c: usize| NodeId::new(format!("{}_{}", r, c));
+        let mut adj = HashMap::new();
+        for r in 0..side {
+            for c in 0..side {
+                let mut neighbors = Vec::new();
+                if r > 0 { neighbors.push(id(r - 1, c)); }
+                if r + 1 < side { neighbors.push(id(r + 1, c)); }
+                if c > 0 { neighbors.push(id(r, c - 1)); }
+                if c + 1 < side { neighbors.push(id(r, c + 1)); }
+                adj.insert(id(r, c), neighbors);
+            }
+        }
+        adj
+    }
+
+    #[test]
+    fn test_memory_budget_pruning() {
+        let adj = create_grid_graph(12);
+        let mut table = TZRoutingTable::build(&adj, TZConfig { num_landmarks: Some(3), seed: 42 }).unwrap();
+        let budget = TZMemoryBudget {
+            per_node_bytes: 4 * BYTES_PER_ENTRY,
+            max_stretch: 3.0,
+        };
+
+        let stats = table.enforce_memory_budget(&budget);
+        assert!(stats.entries_after < stats.entries_before);
+        let max_entries = budget.per_node_bytes / BYTES_PER_ENTRY;
+        let over = table.node_info.values().filter(|info| info.bunch.len() > max_entries).count();
+        assert_eq!(over, stats.nodes_over_budget);
+
+        // Pruned tables still deliver every sampled pair
+        let report = table.memory_report(&adj, 500);
+        assert_eq!(report.violations, 0);
+        assert!(report.avg_stretch >= 1.0);
+        assert!(report.compact_bytes < report.estimated_bytes);
+    }
+
+    #[test]
+    fn test_unpruned_routes() {
+        let adj = create_grid_graph(8);
+        let table = TZRoutingTable::build(&adj, TZConfig { num_landmarks: Some(3), seed: 7 }).unwrap();
+        let mut nodes: Vec<&NodeId> = adj.keys().collect();
+        nodes.sort();
+
+        let mut detours = 0;
+        for source in &nodes {
+            let info = &table.node_info[*source];
+            for destination in nodes.iter().step_by(5) {
+                let path = table.compute_path(source, destination).unwrap();
+                let hops = path.len() as u32 - 1;
+                if source == destination {
+                    assert_eq!(hops, 0);
+                } else if let Some((distance, _)) = info.bunch.get(*destination) {
+                    // Bunch members are reached along a shortest path, as before
+                    assert_eq!(hops, *distance);
+                } else {
+                    // Everything else goes through the source's own landmark
+                    // rather than also through the destination's
+                    assert!(path.contains(&info.closest_landmark), "{:?}", path);
+                    assert_eq!(Some(hops), table.landmark_detour(source, destination));
+                    detours += 1;
+                }
+            }
+        }
+        assert!(detours > 0);
+        assert_eq!(table.verify_stretch(&adj, 500).2, 0);
+    }
+
+    #[test]
+    fn test_compact_bunch_roundtrip() {
+        let adj = create_grid_graph(6);
+        let table = TZRoutingTable::build(&adj, TZConfig::default()).unwrap();
+        let compact = CompactBunchTable::encode(&table);
+
+        for (node, info) in &table.node_info {
+            assert_eq!(compact.bunch(node).unwrap(), info.bunch);
+            for (w, entry) in &info.bunch {
+                assert_eq!(compact.lookup(node, w).as_ref(), Some(entry));
+            }
+        }
+        assert!(compact.lookup(&NodeId::new("0_0"), &NodeId::new("missing")).is_none());
+    }
+
     #[test]
     fn test_bunch_membership() {
         let adj = create_path_graph();
@@ -0,0 +1,333 @@
+//! Poincaré Disk Visualization
+//!
+//! Builds a scene graph of an embedding (nodes at their disk coordinates,
+//! edges as hyperbolic geodesics) from a router, a node checkpoint or an
+//! embedding file, and renders it to SVG or exports it as JSON for d3.
+//!
+//! A geodesic between two points of the disk is either a diameter or an arc
+//! of the circle through both points that meets the unit circle at right
+//! angles. The JSON scene carries the arc parameters so front ends do not
+//! have to solve for them.
+//!
+//! Nodes may carry a traffic value; the SVG renderer colors them on a
+//! blue-to-red scale when the heatmap is enabled.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::coordinates::NodeId;
+use crate::network::NodeCheckpoint;
+use crate::routing::GPRouter;
+use crate::PoincareDiskPoint;
+
+/// Below this determinant the points are treated as collinear with the origin
+const COLLINEAR_EPSILON: f64 = 1e-9;
+
+/// A geodesic segment in disk coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Geodesic {
+    /// Segment of a diameter
+    Line,
+    /// Minor arc of the circle centered at (cx, cy) with radius r
+    Arc { cx: f64, cy: f64, r: f64 },
+}
+
+impl Geodesic {
+    /// Geodesic through two points of the disk
+    pub fn between(a: (f64, f64), b: (f64, f64)) -> Self {
+        // The center c of an orthogonal circle through p satisfies
+        // 2 c·p = |p|² + 1, a linear system in c
+        let det = 2.0 * (a.0 * b.1 - a.1 * b.0);
+        if det.abs() < COLLINEAR_EPSILON {
+            return Geodesic::Line;
+        }
+        let ka = a.0 * a.0 + a.1 * a.1 + 1.0;
+        let kb = b.0 * b.0 + b.1 * b.1 + 1.0;
+        let cx = (ka * b.1 - kb * a.1) / det;
+        let cy = (kb * a.0 - ka * b.0) / det;
+        let r = (cx * cx + cy * cy - 1.0).max(0.0).sqrt();
+        Geodesic::Arc { cx, cy, r }
+    }
+}
+
+/// A node placed in the disk
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneNode {
+    pub id: String,
+    pub x: f64,
+    pub y: f64,
+    /// Traffic through the node, for the heatmap
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traffic: Option<f64>,
+}
+
+/// An edge drawn as a geodesic
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneEdge {
+    pub source: String,
+    pub target: String,
+    /// Filled in by `Scene::with_geodesics`; absent in plain embedding files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geodesic: Option<Geodesic>,
+}
+
+/// Scene graph of an embedding
+///
+/// The JSON form uses the `nodes`/`edges` layout of the files written by
+/// `export_visualization`, so those files load directly.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub nodes: Vec<SceneNode>,
+    pub edges: Vec<SceneEdge>,
+}
+
+/// SVG rendering options
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgOptions {
+    /// Width and height of the image in pixels
+    pub size: u32,
+    pub node_radius: f64,
+    /// Color nodes by traffic instead of a single color
+    pub heatmap: bool,
+    /// Print node IDs next to the nodes
+    pub labels: bool,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            size: 800,
+            node_radius: 3.0,
+            heatmap: false,
+            labels: false,
+        }
+    }
+}
+
+impl Scene {
+    /// Scene of every node and edge known to a router
+    pub fn from_router(router: &GPRouter) -> Self {
+        let mut ids = router.node_ids();
+        ids.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut scene = Scene::default();
+        let mut seen = HashSet::new();
+        for id in &ids {
+            let Some(node) = router.get_node(id) else {
+                continue;
+            };
+            scene.push_node(&id.0, node.coord.point);
+            for neighbor in &node.neighbors {
+                let key = if id.0 < neighbor.0 { (&id.0, &neighbor.0) } else { (&neighbor.0, &id.0) };
+                if seen.insert(key) {
+                    scene.push_edge(key.0, key.1);
+                }
+            }
+        }
+        scene
+    }
+
+    /// Star of a node and its neighbors as recorded in a checkpoint
+    pub fn from_checkpoint(checkpoint: &NodeCheckpoint) -> Self {
+        let mut scene = Scene::default();
+        scene.push_node(&checkpoint.node_id, checkpoint.coord.into());
+        for neighbor in &checkpoint.neighbors {
+            scene.push_node(&neighbor.id, neighbor.coord.into());
+            scene.push_edge(&checkpoint.node_id, &neighbor.id);
+        }
+        scene
+    }
+
+    /// Parse a scene or an embedding file
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse scene: {}", e))
+    }
+
+    /// JSON scene with geodesics, ready for d3
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(&self.clone().with_geodesics())
+            .map_err(|e| format!("Failed to serialize scene: {}", e))
+    }
+
+    fn push_node(&mut self, id: &str, point: PoincareDiskPoint) {
+        self.nodes.push(SceneNode { id: id.to_string(), x: point.x, y: point.y, traffic: None });
+    }
+
+    fn push_edge(&mut self, source: &str, target: &str) {
+        self.edges.push(SceneEdge { source: source.to_string(), target: target.to_string(), geodesic: None });
+    }
+
+    /// Attach traffic values to nodes; nodes not in `traffic` keep theirs
+    pub fn with_traffic(mut self, traffic: &HashMap<NodeId, f64>) -> Self {
+        for node in &mut self.nodes {
+            if let Some(value) = traffic.get(&NodeId::new(&node.id)) {
+                node.traffic = Some(*value);
+            }
+        }
+        self
+    }
+
+    /// Compute the geodesic of every edge whose endpoints are in the scene
+    pub fn with_geodesics(mut self) -> Self {
+        let positions = self.positions();
+        for edge in &mut self.edges {
+            if let (Some(a), Some(b)) = (positions.get(&edge.source), positions.get(&edge.target)) {
+                edge.geodesic = Some(Geodesic::between(*a, *b));
+            }
+        }
+        self
+    }
+
+    fn positions(&self) -> HashMap<String, (f64, f64)> {
+        self.nodes.iter().map(|n| (n.id.clone(), (n.x, n.y))).collect()
+    }
+
+    /// Render the scene as a standalone SVG document
+    pub fn render_svg(&self, options: &SvgOptions) -> String {
+        let half = options.size as f64 / 2.0;
+        let scale = half * 0.95;
+        // Disk y grows upward, SVG y downward
+        let to_screen = |(x, y): (f64, f64)| (half + x * scale, half - y * scale);
+        let positions = self.positions();
+        let max_traffic = self.nodes.iter().filter_map(|n| n.traffic).fold(0.0, f64::max);
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{0}" viewBox="0 0 {0} {0}">"#,
+            options.size
+        );
+        let _ = writeln!(
+            svg,
+            r##"<circle cx="{0:.2}" cy="{0:.2}" r="{1:.2}" fill="#f8f8f8" stroke="#444" stroke-width="1"/>"##,
+            half, scale
+        );
+
+        let _ = writeln!(svg, r##"<g fill="none" stroke="#999" stroke-width="0.6">"##);
+        for edge in &self.edges {
+            let (Some(&a), Some(&b)) = (positions.get(&edge.source), positions.get(&edge.target)) else {
+                continue;
+            };
+            let (sa, sb) = (to_screen(a), to_screen(b));
+            match edge.geodesic.unwrap_or_else(|| Geodesic::between(a, b)) {
+                Geodesic::Line => {
+                    let _ = writeln!(svg, r#"<path d="M {:.2} {:.2} L {:.2} {:.2}"/>"#, sa.0, sa.1, sb.0, sb.1);
+                }
+                Geodesic::Arc { cx, cy, r } => {
+                    // The picture is not mirrored, so a minor arc running
+                    // clockwise around the center (negative cross product)
+                    // is drawn clockwise, which SVG calls sweep 1
+                    let cross = (a.0 - cx) * (b.1 - cy) - (a.1 - cy) * (b.0 - cx);
+                    let sweep = u8::from(cross < 0.0);
+                    let radius = r * scale;
+                    let _ = writeln!(
+                        svg,
+                        r#"<path d="M {:.2} {:.2} A {:.2} {:.2} 0 0 {} {:.2} {:.2}"/>"#,
+                        sa.0, sa.1, radius, radius, sweep, sb.0, sb.1
+                    );
+                }
+            }
+        }
+        let _ = writeln!(svg, "</g>");
+
+        for node in &self.nodes {
+            let (x, y) = to_screen((node.x, node.y));
+            let fill = match node.traffic {
+                Some(traffic) if options.heatmap && max_traffic > 0.0 => heat_color(traffic / max_traffic),
+                _ => "#1f77b4".to_string(),
+            };
+            let _ = writeln!(
+                svg,
+                r#"<circle cx="{:.2}" cy="{:.2}" r="{:.2}" fill="{}"><title>{}</title></circle>"#,
+                x,
+                y,
+                options.node_radius,
+                fill,
+                escape_xml(&node.id)
+            );
+            if options.labels {
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="{:.2}" y="{:.2}" font-size="9">{}</text>"#,
+                    x + options.node_radius + 1.0,
+                    y,
+                    escape_xml(&node.id)
+                );
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// Blue (0.0) to red (1.0)
+fn heat_color(level: f64) -> String {
+    let level = level.clamp(0.0, 1.0);
+    let red = (255.0 * level).round() as u8;
+    let blue = (255.0 * (1.0 - level)).round() as u8;
+    format!("rgb({},64,{})", red, blue)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinates::RoutingCoordinate;
+    use crate::routing::RoutingNode;
+
+    #[test]
+    fn test_geodesic_arcs_are_orthogonal_to_the_boundary() {
+        let a = (0.5, 0.1);
+        let b = (-0.2, 0.6);
+        let Geodesic::Arc { cx, cy, r } = Geodesic::between(a, b) else {
+            panic!("expected an arc");
+        };
+        // Passes through both points and meets the unit circle at right angles
+        for p in [a, b] {
+            assert!((((p.0 - cx).powi(2) + (p.1 - cy).powi(2)).sqrt() - r).abs() < 1e-9);
+        }
+        assert!((cx * cx + cy * cy - (r * r + 1.0)).abs() < 1e-9);
+
+        assert_eq!(Geodesic::between((0.3, 0.3), (-0.5, -0.5)), Geodesic::Line);
+        assert_eq!(Geodesic::between((0.0, 0.0), (0.2, 0.7)), Geodesic::Line);
+    }
+
+    #[test]
+    fn test_scene_from_router_renders_and_round_trips() {
+        let mut router = GPRouter::new();
+        for (id, x, y) in [("a", 0.1, 0.2), ("b", -0.4, 0.3), ("c", 0.5, -0.5)] {
+            let coord = RoutingCoordinate::new(PoincareDiskPoint::new(x, y).unwrap(), 0);
+            router.add_node(RoutingNode::new(NodeId::new(id), coord));
+        }
+        router.add_edge(&NodeId::new("a"), &NodeId::new("b"));
+        router.add_edge(&NodeId::new("b"), &NodeId::new("c"));
+
+        let scene = Scene::from_router(&router).with_traffic(&HashMap::from([(NodeId::new("b"), 10.0)]));
+        assert_eq!(scene.nodes.len(), 3);
+        assert_eq!(scene.edges.len(), 2);
+
+        let svg = scene.render_svg(&SvgOptions { heatmap: true, ..Default::default() });
+        assert_eq!(svg.matches("<path").count(), 2);
+        assert!(svg.contains("rgb(255,64,0)"));
+
+        let parsed = Scene::from_json(&scene.to_json().unwrap()).unwrap();
+        assert!(parsed.edges.iter().all(|e| e.geodesic.is_some()));
+        assert_eq!(parsed.nodes, scene.nodes);
+    }
+
+    #[test]
+    fn test_reads_embedding_files() {
+        let json = r#"{"embedding_type":"PIE","num_nodes":2,
+            "nodes":[{"id":"node_0","x":0.1,"y":0.0,"degree":1},{"id":"node_1","x":0.0,"y":0.3,"degree":1}],
+            "edges":[{"source":"node_0","target":"node_1"}]}"#;
+        let scene = Scene::from_json(json).unwrap().with_geodesics();
+        assert_eq!(scene.nodes.len(), 2);
+        assert!(matches!(scene.edges[0].geodesic, Some(Geodesic::Arc { .. })));
+    }
+}
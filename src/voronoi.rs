@@ -0,0 +1,369 @@
+//! Hyperbolic Voronoi Partitioning
+//!
+//! Assigns every point of the Poincaré disk to the node whose routing
+//! coordinate is closest in hyperbolic distance, so "which node owns p?" has
+//! exactly one answer for a given set of coordinates. Ties are broken by node
+//! ID.
+//!
+//! Hyperbolic Voronoi cells are convex (intersections of half-planes bounded
+//! by perpendicular bisector geodesics), so a node can check ownership
+//! locally against the sites it knows about: if it is closer to `p` than all
+//! of its overlay neighbors it is, in a well-connected overlay, the owner.
+//! `local_owner` computes this nearest-of-k answer from any candidate set.
+//!
+//! The dual of the partition is the hyperbolic Delaunay graph: two nodes are
+//! Delaunay neighbors when their cells share a boundary. Greedy forwarding
+//! over Delaunay edges always finds a neighbor closer to the target, so
+//! `select_neighbors` keeps those links first when the neighbor set is capped.
+
+use crate::coordinates::{HomeNodeRegistry, NodeId, SpatialIndex};
+use crate::PoincareDiskPoint;
+
+/// Largest Euclidean radius used when tracing unbounded cells
+const MAX_SAMPLE_RADIUS: f64 = 0.999;
+
+/// Bisection steps when locating a cell boundary along a ray
+const BOUNDARY_STEPS: usize = 40;
+
+/// Points sampled along a bisector in the Delaunay edge test
+const BISECTOR_SAMPLES: usize = 65;
+
+/// Slack for treating a bisector point as equidistant
+const BISECTOR_EPSILON: f64 = 1e-9;
+
+/// Whether `a` at distance `da` beats `b` at distance `db`
+fn closer(da: f64, a: &NodeId, db: f64, b: &NodeId) -> bool {
+    da < db || (da == db && a.0 < b.0)
+}
+
+/// Nearest of the given candidates to `point`
+///
+/// Used for local ownership checks with only neighbor coordinates at hand.
+pub fn local_owner<'a>(
+    point: &PoincareDiskPoint,
+    candidates: impl IntoIterator<Item = (&'a NodeId, &'a PoincareDiskPoint)>,
+) -> Option<&'a NodeId> {
+    let mut best: Option<(f64, &NodeId)> = None;
+    for (id, coord) in candidates {
+        let d = coord.hyperbolic_distance(point);
+        if best.is_none_or(|(bd, bid)| closer(d, id, bd, bid)) {
+            best = Some((d, id));
+        }
+    }
+    best.map(|(_, id)| id)
+}
+
+/// Whether `a` and `b` have adjacent cells among `sites`
+///
+/// Samples the perpendicular bisector of the geodesic `ab`: the cells touch
+/// if some point on it is no closer to any other site than to `a` and `b`.
+/// The bisector is found by translating `a` to the origin, where it is the
+/// diameter-perpendicular geodesic through the midpoint of `ab`.
+fn delaunay_adjacent(
+    a: &PoincareDiskPoint,
+    b: &PoincareDiskPoint,
+    sites: &[(NodeId, PoincareDiskPoint)],
+) -> bool {
+    let to_origin = PoincareDiskPoint { x: -a.x, y: -a.y };
+    let Some(b_local) = to_origin.mobius_add(b) else {
+        return false;
+    };
+    let half = a.hyperbolic_distance(b) / 2.0;
+    let phi = b_local.angle();
+    // Distance 2·artanh(r) from the origin
+    let Some(mid_local) = PoincareDiskPoint::from_polar((half / 2.0).tanh(), phi) else {
+        return false;
+    };
+
+    (0..BISECTOR_SAMPLES).any(|i| {
+        let t = (2.0 * i as f64 / (BISECTOR_SAMPLES - 1) as f64 - 1.0) * MAX_SAMPLE_RADIUS;
+        let offset = PoincareDiskPoint::from_polar(t.abs(), phi + t.signum() * std::f64::consts::FRAC_PI_2);
+        let Some(point) = offset
+            .and_then(|o| mid_local.mobius_add(&o))
+            .and_then(|p| a.mobius_add(&p))
+        else {
+            return false;
+        };
+        let d = a.hyperbolic_distance(&point);
+        sites
+            .iter()
+            .all(|(_, s)| s.hyperbolic_distance(&point) >= d - BISECTOR_EPSILON)
+    })
+}
+
+/// Delaunay neighbors of `local` among `candidates`
+///
+/// Computed from the local view only, so the result approximates the global
+/// Delaunay graph as well as the candidate set covers the surroundings.
+pub fn delaunay_neighbors(local: &PoincareDiskPoint, candidates: &[(NodeId, PoincareDiskPoint)]) -> Vec<NodeId> {
+    candidates
+        .iter()
+        .filter(|(_, coord)| delaunay_adjacent(local, coord, candidates))
+        .map(|(id, _)| id.clone())
+        .collect()
+}
+
+/// Neighbors to keep, most important first, at most `max`
+///
+/// Delaunay neighbors come first, nearest first; the remaining capacity is
+/// filled with the nearest other candidates.
+pub fn select_neighbors(
+    local: &PoincareDiskPoint,
+    candidates: &[(NodeId, PoincareDiskPoint)],
+    max: usize,
+) -> Vec<NodeId> {
+    let delaunay = delaunay_neighbors(local, candidates);
+    let mut ranked: Vec<(bool, f64, &NodeId)> = candidates
+        .iter()
+        .map(|(id, coord)| (!delaunay.contains(id), local.hyperbolic_distance(coord), id))
+        .collect();
+    ranked.sort_by(|a, b| {
+        a.0.cmp(&b.0)
+            .then(a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .then_with(|| a.2 .0.cmp(&b.2 .0))
+    });
+    ranked.into_iter().take(max).map(|(_, _, id)| id.clone()).collect()
+}
+
+/// A responsibility point moving to a new owner
+#[derive(Debug, Clone, PartialEq)]
+pub struct Handoff {
+    /// Key of the point (e.g. the target ID whose anchor it is)
+    pub key: NodeId,
+    pub from: NodeId,
+    pub to: NodeId,
+}
+
+/// Voronoi partition of the disk over node coordinates
+#[derive(Debug, Clone, Default)]
+pub struct VoronoiPartition {
+    sites: SpatialIndex,
+}
+
+impl VoronoiPartition {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Partition over the routing coordinates of a registry
+    pub fn from_registry(registry: &HomeNodeRegistry) -> Self {
+        let mut sites = SpatialIndex::new();
+        for id in registry.get_all_nodes() {
+            if let Some(coord) = registry.get_routing(id) {
+                sites.insert(id.clone(), coord.point);
+            }
+        }
+        Self { sites }
+    }
+
+    /// Add or move a site
+    pub fn set_site(&mut self, node: NodeId, coord: PoincareDiskPoint) {
+        self.sites.insert(node, coord);
+    }
+
+    pub fn remove_site(&mut self, node: &NodeId) -> Option<PoincareDiskPoint> {
+        self.sites.remove(node)
+    }
+
+    pub fn site(&self, node: &NodeId) -> Option<&PoincareDiskPoint> {
+        self.sites.get(node)
+    }
+
+    pub fn len(&self) -> usize {
+        self.sites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sites.is_empty()
+    }
+
+    /// Node whose cell contains `point`
+    pub fn owner_of(&self, point: &PoincareDiskPoint) -> Option<&NodeId> {
+        self.sites.nearest(point).map(|(id, _)| id)
+    }
+
+    /// Whether `node`'s cell contains `point`
+    pub fn owns(&self, node: &NodeId, point: &PoincareDiskPoint) -> bool {
+        self.owner_of(point) == Some(node)
+    }
+
+    /// Points on the boundary of `node`'s cell, one per direction
+    ///
+    /// Casts `directions` evenly spaced geodesic rays from the site and
+    /// bisects for the point where ownership changes. Rays that never leave
+    /// the cell end at the sampling limit near the ideal boundary.
+    pub fn cell_boundary_sample(&self, node: &NodeId, directions: usize) -> Vec<PoincareDiskPoint> {
+        let Some(site) = self.sites.get(node) else {
+            return Vec::new();
+        };
+
+        (0..directions)
+            .filter_map(|i| {
+                let theta = 2.0 * std::f64::consts::PI * i as f64 / directions as f64;
+                // Geodesic rays from the site are images of diameters under
+                // the Möbius translation taking the origin to the site
+                let along = |r: f64| PoincareDiskPoint::from_polar(r, theta).and_then(|p| site.mobius_add(&p));
+                let inside = |r: f64| along(r).is_some_and(|p| self.owns(node, &p));
+
+                if inside(MAX_SAMPLE_RADIUS) {
+                    return along(MAX_SAMPLE_RADIUS);
+                }
+                let (mut lo, mut hi) = (0.0, MAX_SAMPLE_RADIUS);
+                for _ in 0..BOUNDARY_STEPS {
+                    let mid = 0.5 * (lo + hi);
+                    if inside(mid) {
+                        lo = mid;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                along(lo)
+            })
+            .collect()
+    }
+
+    /// Points whose owner differs between this partition and `next`
+    pub fn handoffs<'a>(
+        &self,
+        next: &VoronoiPartition,
+        points: impl IntoIterator<Item = (&'a NodeId, &'a PoincareDiskPoint)>,
+    ) -> Vec<Handoff> {
+        points
+            .into_iter()
+            .filter_map(|(key, point)| {
+                let from = self.owner_of(point)?;
+                let to = next.owner_of(point)?;
+                (from != to).then(|| Handoff {
+                    key: key.clone(),
+                    from: from.clone(),
+                    to: to.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Handoffs caused by moving `node` to `coord` (or removing it with None)
+    pub fn handoffs_for_move<'a>(
+        &self,
+        node: &NodeId,
+        coord: Option<PoincareDiskPoint>,
+        points: impl IntoIterator<Item = (&'a NodeId, &'a PoincareDiskPoint)>,
+    ) -> Vec<Handoff> {
+        let mut next = self.clone();
+        match coord {
+            Some(coord) => next.set_site(node.clone(), coord),
+            None => {
+                next.remove_site(node);
+            }
+        }
+        self.handoffs(&next, points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn partition() -> VoronoiPartition {
+        let mut partition = VoronoiPartition::new();
+        partition.set_site(NodeId::new("a"), PoincareDiskPoint::new(-0.5, 0.0).unwrap());
+        partition.set_site(NodeId::new("b"), PoincareDiskPoint::new(0.5, 0.0).unwrap());
+        partition.set_site(NodeId::new("c"), PoincareDiskPoint::new(0.0, 0.5).unwrap());
+        partition
+    }
+
+    #[test]
+    fn test_owner_and_boundary() {
+        let partition = partition();
+        let a = NodeId::new("a");
+        let b = NodeId::new("b");
+
+        assert_eq!(partition.owner_of(&PoincareDiskPoint::new(-0.9, 0.0).unwrap()), Some(&a));
+        assert_eq!(partition.owner_of(&PoincareDiskPoint::new(0.6, -0.3).unwrap()), Some(&b));
+        // Equidistant: ties go to the smaller ID
+        assert_eq!(partition.owner_of(&PoincareDiskPoint::new(0.0, -0.5).unwrap()), Some(&a));
+
+        // Local check with a subset agrees when the owner is a candidate
+        let p = PoincareDiskPoint::new(0.2, 0.6).unwrap();
+        let owner = partition.owner_of(&p).cloned();
+        let local: Vec<_> = partition.sites.iter().filter(|(id, _)| id.0 != "a").collect();
+        assert_eq!(local_owner(&p, local.iter().map(|(id, c)| (*id, *c))).cloned(), owner);
+
+        let boundary = partition.cell_boundary_sample(&a, 16);
+        assert_eq!(boundary.len(), 16);
+        for point in &boundary {
+            let site_a = partition.site(&a).unwrap();
+            let da = site_a.hyperbolic_distance(point);
+            if point.euclidean_norm() < MAX_SAMPLE_RADIUS - 1e-3 {
+                // On a bisector: some other site is (almost) as close
+                let nearest_other = ["b", "c"]
+                    .iter()
+                    .map(|id| partition.site(&NodeId::new(*id)).unwrap().hyperbolic_distance(point))
+                    .fold(f64::INFINITY, f64::min);
+                assert!((da - nearest_other).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn test_handoff_on_move() {
+        let partition = partition();
+        let keys: Vec<(NodeId, PoincareDiskPoint)> = (0..8)
+            .map(|i| {
+                let id = NodeId::new(format!("key{}", i));
+                let anchor = crate::coordinates::AnchorCoordinate::from_id(&id).point;
+                (id, anchor)
+            })
+            .collect();
+        let points = || keys.iter().map(|(id, p)| (id, p));
+
+        // Moving c only changes ownership to or from c
+        let c = NodeId::new("c");
+        let moves = partition.handoffs_for_move(&c, Some(PoincareDiskPoint::new(0.0, -0.5).unwrap()), points());
+        for handoff in &moves {
+            assert_ne!(handoff.from, handoff.to);
+            assert!(handoff.from == c || handoff.to == c);
+        }
+
+        // Removing a node hands all its keys to the others
+        let owned_by_c = keys.iter().filter(|(_, p)| partition.owns(&c, p)).count();
+        let removed = partition.handoffs_for_move(&c, None, points());
+        assert_eq!(removed.len(), owned_by_c);
+        assert!(removed.iter().all(|h| h.from == c));
+    }
+
+    #[test]
+    fn test_delaunay_selection_keeps_greedy_progress() {
+        let local = PoincareDiskPoint::origin();
+        let at = |x: f64, y: f64| PoincareDiskPoint::new(x, y).unwrap();
+        // A near and a far peer in the same direction, plus one on the other side
+        let candidates = vec![
+            (NodeId::new("near"), at(0.2, 0.0)),
+            (NodeId::new("far"), at(0.6, 0.0)),
+            (NodeId::new("west"), at(-0.5, 0.0)),
+            (NodeId::new("north"), at(0.0, 0.7)),
+        ];
+
+        let delaunay = delaunay_neighbors(&local, &candidates);
+        assert!(delaunay.contains(&NodeId::new("near")));
+        assert!(delaunay.contains(&NodeId::new("west")));
+        assert!(delaunay.contains(&NodeId::new("north")));
+        // Shadowed by "near"
+        assert!(!delaunay.contains(&NodeId::new("far")));
+
+        // Capped at 3, the farther west/north links survive over "far"
+        let kept = select_neighbors(&local, &candidates, 3);
+        assert_eq!(kept, vec![NodeId::new("near"), NodeId::new("west"), NodeId::new("north")]);
+
+        // Greedy toward any candidate makes progress through a kept neighbor
+        for (_, target) in &candidates {
+            let here = local.hyperbolic_distance(target);
+            let best = candidates
+                .iter()
+                .filter(|(id, _)| kept.contains(id))
+                .map(|(_, c)| c.hyperbolic_distance(target))
+                .fold(f64::INFINITY, f64::min);
+            assert!(best < here);
+        }
+    }
+}
@@ -7,6 +7,8 @@ use axum::{
     http::{Request, StatusCode},
 };
 use drfe_r::api::{create_router, ApiState, SendPacketRequest};
+use drfe_r::api_access::{ApiAccessConfig, ApiRole};
+use drfe_r::config::ConfigUpdate;
 use drfe_r::coordinates::NodeId;
 use drfe_r::network::{DistributedNode, NeighborInfo};
 use drfe_r::PoincareDiskPoint;
@@ -18,13 +20,19 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use tower::ServiceExt; // for `oneshot`
 
-/// Helper to create a test node
+/// Helper to create a test node that lets anonymous clients send packets
 async fn create_test_node(id: &str) -> Arc<DistributedNode> {
-    Arc::new(
+    let node = Arc::new(
         DistributedNode::new(NodeId::new(id), "127.0.0.1:0", "127.0.0.1:0")
             .await
             .unwrap(),
-    )
+    );
+    let update = ConfigUpdate {
+        api_access: Some(ApiAccessConfig { anonymous_role: Some(ApiRole::Operator), ..Default::default() }),
+        ..ConfigUpdate::default()
+    };
+    node.apply_config(&update).await.unwrap();
+    node
 }
 
 /// Helper to create API state
@@ -535,3 +543,42 @@ async fn test_neighbor_info_completeness() {
     assert!(neighbor_json["rtt_ms"].is_number());
     assert!(neighbor_json["last_heartbeat_secs"].is_number());
 }
+
+#[tokio::test]
+async fn test_latency_map_and_rank() {
+    let state = create_api_state("test_node").await;
+    for (id, x, rtt_ms) in [("near", 0.1, 4), ("far", 0.7, 40)] {
+        let mut neighbor = NeighborInfo::new(
+            NodeId::new(id),
+            PoincareDiskPoint::new(x, 0.0).unwrap(),
+            "127.0.0.1:8000".parse().unwrap(),
+        );
+        neighbor.rtt = std::time::Duration::from_millis(rtt_ms);
+        state.node.add_neighbor(neighbor).await;
+    }
+    let app = create_router(state);
+
+    let get = |uri: &str| Request::builder().method("GET").uri(uri).body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(get("/api/v1/latency")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["entries"].as_array().unwrap().len(), 3);
+    assert_eq!(json["landmarks"][0], "test_node");
+
+    let response = app
+        .clone()
+        .oneshot(get("/api/v1/latency/rank?candidates=far,near,unknown"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let ranked: Vec<&str> = json.as_array().unwrap().iter().map(|r| r["node"].as_str().unwrap()).collect();
+    assert_eq!(ranked, ["near", "far"]);
+    assert_eq!(json[0]["rtt_ms"], 4.0);
+    assert_eq!(json[0]["measured"], true);
+
+    let response = app.oneshot(get("/api/v1/latency/rank?candidates=")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -435,3 +435,59 @@ async fn test_combined_auth_and_rate_limiting() {
     let response = app.oneshot(request).await.unwrap();
     assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
 }
+
+#[tokio::test]
+async fn test_token_roles_and_control_limit() {
+    use drfe_r::api_access::{ApiAccessConfig, ApiRole, ApiToken};
+    use drfe_r::config::ConfigUpdate;
+
+    let state = create_auth_api_state("test_node", false).await;
+    let update = ConfigUpdate {
+        api_access: Some(ApiAccessConfig {
+            tokens: vec![
+                ApiToken::new("dashboard", "view-token", ApiRole::ReadOnly),
+                ApiToken::new("operator", "ops-token", ApiRole::Operator),
+            ],
+            anonymous_role: Some(ApiRole::ReadOnly),
+            control_requests_per_minute: Some(1),
+            ..Default::default()
+        }),
+        ..ConfigUpdate::default()
+    };
+    state.node.apply_config(&update).await.unwrap();
+    let app = create_router(state);
+
+    let request = |method: &str, token: Option<&str>| {
+        let mut builder = Request::builder()
+            .method(method)
+            .uri("/api/v1/config")
+            .header("Content-Type", "application/json");
+        if let Some(token) = token {
+            builder = builder.header("Authorization", format!("Bearer {}", token));
+        }
+        builder.body(Body::from("{}")).unwrap()
+    };
+
+    // Anyone may read, but token hashes are not part of the config view
+    let response = app.clone().oneshot(request("GET", None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let config: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(config.get("heartbeat_interval_ms").is_some() && config.get("api_access").is_none());
+    let response = app.clone().oneshot(request("GET", Some("wrong"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Only operators may change the node
+    let response = app.clone().oneshot(request("PUT", None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = app.clone().oneshot(request("PUT", Some("view-token"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = app.clone().oneshot(request("PUT", Some("ops-token"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Control requests have their own, lower limit
+    let response = app.clone().oneshot(request("PUT", Some("ops-token"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let response = app.oneshot(request("GET", Some("ops-token"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
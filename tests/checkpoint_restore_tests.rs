@@ -3,7 +3,7 @@
 //! These tests verify that DistributedNode state can be checkpointed and restored correctly.
 
 use drfe_r::coordinates::NodeId;
-use drfe_r::network::{DistributedNode, NeighborInfo, NodeCheckpoint};
+use drfe_r::network::{CheckpointError, DistributedNode, NeighborInfo, NetworkError, NodeCheckpoint};
 use drfe_r::PoincareDiskPoint;
 use std::sync::Arc;
 use std::time::Duration;
@@ -309,6 +309,31 @@ async fn test_checkpoint_version_compatibility() {
     // Attempting to restore from incompatible checkpoint should fail
     let result = node.restore_from_checkpoint(&incompatible).await;
     assert!(result.is_err());
+    assert!(matches!(
+        result,
+        Err(NetworkError::Checkpoint(CheckpointError::IncompatibleVersion { found: 999, .. }))
+    ));
+}
+
+/// Test typed checkpoint errors
+#[tokio::test]
+async fn test_checkpoint_error_kinds() {
+    let temp_dir = TempDir::new().unwrap();
+    let missing = NodeCheckpoint::load_from_file(&temp_dir.path().join("missing.json")).unwrap_err();
+    assert!(matches!(missing, CheckpointError::Io { .. }));
+    assert_eq!(missing.code(), "checkpoint.io");
+    assert!(std::error::Error::source(&missing).is_some());
+
+    let garbage = NodeCheckpoint::from_json("not json").unwrap_err();
+    assert_eq!(garbage.code(), "checkpoint.decode");
+
+    let node = DistributedNode::new(NodeId::new("test_node"), "127.0.0.1:0", "127.0.0.1:0")
+        .await
+        .unwrap();
+    let mut checkpoint = node.create_checkpoint().await;
+    checkpoint.coord.x = 2.0;
+    let err = node.restore_from_checkpoint(&checkpoint).await.unwrap_err();
+    assert_eq!(err.code(), "geometry.outside_disk");
 }
 
 /// Test checkpoint age calculation
@@ -587,3 +612,31 @@ async fn test_checkpoint_preserves_version() {
     assert_eq!(coord_after.updated_at, coord_before.updated_at);
     assert_eq!(coord_after.updated_at, checkpoint.coord_version);
 }
+
+/// Test saving to and restoring from a checkpoint store
+#[tokio::test]
+async fn test_checkpoint_store_restore() {
+    use drfe_r::checkpoint_store::{CheckpointStore, FsCheckpointStore};
+
+    let temp_dir = TempDir::new().unwrap();
+    let store = FsCheckpointStore::new(temp_dir.path());
+
+    let node = DistributedNode::new(NodeId::new("store_node"), "127.0.0.1:0", "127.0.0.1:0")
+        .await
+        .unwrap();
+    node.update_coordinates(PoincareDiskPoint::new(0.2, -0.5).unwrap()).await.unwrap();
+    let key = node.save_checkpoint_to(&store).await.unwrap();
+    assert!(key.starts_with("checkpoints/store_node/"));
+    assert_eq!(store.list("checkpoints/").await.unwrap(), vec![key]);
+
+    let restarted = DistributedNode::new(NodeId::new("store_node"), "127.0.0.1:0", "127.0.0.1:0")
+        .await
+        .unwrap();
+    assert!(restarted.restore_latest_from(&store).await.unwrap());
+    let coord = restarted.coord().await;
+    assert!((coord.point.x - 0.2).abs() < 1e-10);
+    assert!((coord.point.y + 0.5).abs() < 1e-10);
+
+    // No snapshot has been uploaded yet
+    assert_eq!(restarted.restore_from_cluster_snapshot(&store).await.unwrap(), None);
+}
@@ -0,0 +1,67 @@
+//! Conformance suite tests
+//!
+//! `tests/fixtures/conformance/vectors.json` is the vector document
+//! published for other implementations. This implementation must keep
+//! reproducing it; after an intentional change, bump `VECTORS_VERSION` and
+//! rewrite it with `DRFE_BLESS_FIXTURES=1 cargo test --test conformance_tests`.
+
+use drfe_r::conformance::{probe, CheckOutcome, ProbeConfig, TestVectors, VECTORS_VERSION};
+use drfe_r::coordinates::NodeId;
+use drfe_r::network::DistributedNode;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn vectors_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/conformance/vectors.json")
+}
+
+/// Test that the published vectors verify and match freshly generated ones
+#[test]
+fn test_published_vectors() {
+    let generated = TestVectors::generate();
+    let path = vectors_path();
+    if std::env::var_os("DRFE_BLESS_FIXTURES").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, generated.to_json().unwrap() + "\n").unwrap();
+    }
+    let json = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("missing vectors {}: {}", path.display(), e));
+    let published = TestVectors::from_json(&json).unwrap();
+
+    // Outputs are checked within the tolerance rather than byte for byte,
+    // so float formatting differences do not fail the suite
+    assert_eq!(published.version, VECTORS_VERSION);
+    assert_eq!(published.tolerance, 1e-12);
+    let mismatches: Vec<String> = published.verify().iter().map(ToString::to_string).collect();
+    assert!(mismatches.is_empty(), "{:#?}", mismatches);
+
+    let counts = |v: &TestVectors| {
+        (v.distance.len(), v.mobius.len(), v.anchors.len(), v.packets.len(), v.signatures.len(), v.routing.len())
+    };
+    assert_eq!(counts(&published), counts(&generated), "generated vectors differ from the published ones");
+}
+
+/// Test that a node of this implementation passes every remote check
+#[tokio::test]
+async fn test_probe_scores_local_node() {
+    let node = Arc::new(DistributedNode::new(NodeId::new("conformant"), "127.0.0.1:0", "127.0.0.1:0").await.unwrap());
+    let target = node.local_udp_addr();
+    let running = Arc::clone(&node);
+    let handle = tokio::spawn(async move { running.start(vec![]).await });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let config = ProbeConfig { heartbeat_wait: Duration::from_secs(3), ..ProbeConfig::default() };
+    let report = probe(target, &config).await.unwrap();
+    assert_eq!(report.remote_id, Some(NodeId::new("conformant")));
+    assert_eq!(report.score(), 1.0, "{:#?}", report.checks);
+
+    // Nothing listens on a closed port, so every later check is skipped
+    drop(handle);
+    node.shutdown().await;
+    let silent = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let config = ProbeConfig { timeout: Duration::from_millis(200), ..ProbeConfig::default() };
+    let report = probe(silent.local_addr().unwrap(), &config).await.unwrap();
+    assert!(matches!(report.checks[0].outcome, CheckOutcome::Fail(_)));
+    assert!(report.checks[1..].iter().all(|c| matches!(c.outcome, CheckOutcome::Skipped(_))));
+    assert_eq!(report.score(), 0.0);
+}
@@ -0,0 +1,142 @@
+//! Crash and recovery tests driven by failure injection points
+//!
+//! Armed points are process-wide, so these tests live in their own binary
+//! and each holds a `FailScenario`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use drfe_r::checkpoint_store::{latest_checkpoint, FsCheckpointStore};
+use drfe_r::coordinates::NodeId;
+use drfe_r::failpoint::{self, FailAction, FailScenario};
+use drfe_r::network::{CheckpointError, DistributedNode, NetworkError};
+use drfe_r::testing::{TestCluster, Topology};
+use drfe_r::tz_routing::{TZConfig, TZRoutingTable};
+use drfe_r::PoincareDiskPoint;
+use tempfile::TempDir;
+
+async fn new_node(id: &str) -> Arc<DistributedNode> {
+    Arc::new(DistributedNode::new(NodeId::new(id), "127.0.0.1:0", "127.0.0.1:0").await.unwrap())
+}
+
+fn files_with_extension(dir: &std::path::Path, extension: &str) -> usize {
+    let mut count = 0;
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(dir).unwrap().flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|e| e == extension) {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+/// Test that a crash while storing a checkpoint leaves the previous one in effect
+#[tokio::test]
+async fn test_crash_mid_checkpoint_keeps_previous() {
+    let _scenario = FailScenario::setup();
+    let dir = TempDir::new().unwrap();
+    let store = Arc::new(FsCheckpointStore::new(dir.path()));
+    let node = new_node("ckpt").await;
+    node.update_coordinates(PoincareDiskPoint::new(0.1, 0.0).unwrap()).await.unwrap();
+    node.save_checkpoint_to(store.as_ref()).await.unwrap();
+
+    // A failed write leaves nothing behind
+    failpoint::arm_times("checkpoint.before_write", FailAction::Error, 1);
+    let failed = node.save_checkpoint_to(store.as_ref()).await;
+    assert!(matches!(failed, Err(CheckpointError::Io { .. })));
+    assert_eq!(files_with_extension(dir.path(), "partial"), 0);
+
+    // Crash between writing the new checkpoint and renaming it into place
+    node.update_coordinates(PoincareDiskPoint::new(0.4, 0.0).unwrap()).await.unwrap();
+    failpoint::arm("checkpoint.after_write", FailAction::Panic);
+    let crashing = (Arc::clone(&node), Arc::clone(&store));
+    let crashed = tokio::spawn(async move { crashing.0.save_checkpoint_to(crashing.1.as_ref()).await }).await;
+    assert!(crashed.unwrap_err().is_panic());
+    assert_eq!(files_with_extension(dir.path(), "partial"), 1);
+
+    let restarted = new_node("ckpt").await;
+    assert!(restarted.restore_latest_from(store.as_ref()).await.unwrap());
+    assert!((restarted.coord().await.point.x - 0.1).abs() < 1e-9);
+
+    // The next checkpoint replaces the partial file
+    failpoint::disarm("checkpoint.after_write");
+    tokio::time::sleep(Duration::from_millis(2)).await;
+    node.save_checkpoint_to(store.as_ref()).await.unwrap();
+    let latest = latest_checkpoint(store.as_ref(), "ckpt").await.unwrap().unwrap();
+    assert!((latest.coord.x - 0.4).abs() < 1e-9);
+    assert_eq!(failpoint::hits("checkpoint.after_write"), 3);
+}
+
+/// Test that a crash mid coordinate commit is recovered from the last checkpoint
+#[tokio::test]
+async fn test_crash_mid_coordinate_commit() {
+    let _scenario = FailScenario::setup();
+    let dir = TempDir::new().unwrap();
+    let store = FsCheckpointStore::new(dir.path());
+    let node = new_node("commit").await;
+    node.update_coordinates(PoincareDiskPoint::new(0.2, 0.1).unwrap()).await.unwrap();
+    node.save_checkpoint_to(&store).await.unwrap();
+
+    failpoint::arm_times("coordinate.commit", FailAction::Panic, 1);
+    let crashing = Arc::clone(&node);
+    let moved = PoincareDiskPoint::new(-0.3, 0.2).unwrap();
+    let crashed = tokio::spawn(async move { crashing.update_coordinates(moved).await }).await;
+    assert!(crashed.unwrap_err().is_panic());
+
+    let restarted = new_node("commit").await;
+    assert!(restarted.restore_latest_from(&store).await.unwrap());
+    let coord = restarted.coord().await.point;
+    assert!((coord.x - 0.2).abs() < 1e-9 && (coord.y - 0.1).abs() < 1e-9);
+
+    // Disarmed after one hit: the restored node commits normally
+    restarted.update_coordinates(moved).await.unwrap();
+    assert!((restarted.coord().await.point.x + 0.3).abs() < 1e-9);
+}
+
+/// Test that a send failing after the routing decision is retried, then dead-lettered
+#[tokio::test]
+async fn test_failed_send_is_retried_then_dead_lettered() {
+    let _scenario = FailScenario::setup();
+    let cluster = TestCluster::new(2).topology(Topology::Line).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+
+    failpoint::arm_times("route.before_send", FailAction::Error, 1);
+    cluster.assert_delivery(0, 1).await;
+
+    failpoint::arm("route.before_send", FailAction::Error);
+    let failed = nodes[0].send_tracked_packet(cluster.id(1), b"held".to_vec(), 64).await;
+    assert!(matches!(failed, Err(NetworkError::Io(_))));
+    let dead = nodes[0].dead_letters().await;
+    assert_eq!(dead.len(), 1);
+
+    failpoint::disarm("route.before_send");
+    assert!(matches!(nodes[0].retry_dead_letter(dead[0].id).await, Some(Ok(()))));
+    assert!(nodes[0].dead_letters().await.is_empty());
+
+    cluster.shutdown().await;
+}
+
+/// Test that an interrupted TZ rebuild fails cleanly and the next one succeeds
+#[tokio::test]
+async fn test_interrupted_tz_rebuild() {
+    let _scenario = FailScenario::setup();
+    let mut adjacency: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+    for i in 0..8 {
+        let neighbors = [(i + 1) % 8, (i + 7) % 8].iter().map(|j| NodeId::new(format!("n{}", j))).collect();
+        adjacency.insert(NodeId::new(format!("n{}", i)), neighbors);
+    }
+    let config = TZConfig { num_landmarks: Some(3), seed: 7 };
+
+    failpoint::arm_times("tz.rebuild", FailAction::Error, 1);
+    assert!(TZRoutingTable::build(&adjacency, config.clone()).is_err());
+    let table = TZRoutingTable::build(&adjacency, config).unwrap();
+    assert_eq!(table.landmarks.len(), 3);
+    assert_eq!(failpoint::hits("tz.rebuild"), 2);
+}
@@ -1,12 +1,15 @@
 //! Integration tests for gRPC API
 //!
 //! Tests the gRPC service endpoints including SendPacket, GetNodeStatus,
-//! and StreamTopology.
+//! StreamTopology and RoutePackets.
 
+use drfe_r::api_access::{ApiAccessConfig, ApiRole};
+use drfe_r::config::ConfigUpdate;
 use drfe_r::coordinates::NodeId;
 use drfe_r::grpc::proto::routing_service_client::RoutingServiceClient;
 use drfe_r::grpc::proto::{
-    GetNodeStatusRequest, SendPacketRequest, TopologyRequest, UpdateType,
+    router_event, GetNodeStatusRequest, OutboundPacket, ReceiptStatus, SendPacketRequest, TopologyRequest,
+    UpdateType,
 };
 use drfe_r::grpc::{start_grpc_server, GrpcServiceState, PacketStatusInfo};
 use drfe_r::network::DistributedNode;
@@ -16,16 +19,22 @@ use tokio::sync::{broadcast, RwLock};
 use tokio::time::{sleep, Duration};
 use tonic::Request;
 
-/// Helper function to create a test node
+/// Helper function to create a test node that lets anonymous clients send packets
 async fn create_test_node(id: &str, udp_port: u16, tcp_port: u16) -> Arc<DistributedNode> {
     let udp_addr = format!("127.0.0.1:{}", udp_port);
     let tcp_addr = format!("127.0.0.1:{}", tcp_port);
 
-    Arc::new(
+    let node = Arc::new(
         DistributedNode::new(NodeId::new(id), &udp_addr, &tcp_addr)
             .await
             .unwrap(),
-    )
+    );
+    let update = ConfigUpdate {
+        api_access: Some(ApiAccessConfig { anonymous_role: Some(ApiRole::Operator), ..Default::default() }),
+        ..ConfigUpdate::default()
+    };
+    node.apply_config(&update).await.unwrap();
+    node
 }
 
 /// Helper function to start a gRPC server in the background
@@ -204,6 +213,46 @@ async fn test_stream_topology_rpc() {
     assert!(response.is_ok(), "StreamTopology without snapshot should succeed");
 }
 
+/// Test RoutePackets bidirectional streaming
+#[tokio::test]
+async fn test_route_packets_rpc() {
+    let node = create_test_node("test_node_7", 40013, 40014).await;
+
+    let grpc_port = 50056;
+    let _server_handle = start_test_grpc_server(node.clone(), grpc_port).await;
+    sleep(Duration::from_millis(500)).await;
+
+    let mut client = RoutingServiceClient::connect(format!("http://127.0.0.1:{}", grpc_port))
+        .await
+        .expect("Failed to connect to gRPC server");
+
+    let (tx, rx) = tokio::sync::mpsc::channel(4);
+    let response = client
+        .route_packets(tokio_stream::wrappers::ReceiverStream::new(rx))
+        .await
+        .expect("RoutePackets should succeed");
+    let mut events = response.into_inner();
+
+    // An isolated node cannot route, but each packet gets a receipt
+    for request_id in ["a", "b"] {
+        tx.send(OutboundPacket {
+            request_id: request_id.to_string(),
+            destination: "remote".to_string(),
+            payload: b"payload".to_vec(),
+            ttl: 0,
+        })
+        .await
+        .unwrap();
+
+        let event = events.message().await.unwrap().expect("Stream should stay open");
+        let Some(router_event::Event::Receipt(receipt)) = event.event else {
+            panic!("Should be a receipt");
+        };
+        assert_eq!(receipt.request_id, request_id);
+        assert_eq!(receipt.status, ReceiptStatus::Failed as i32);
+    }
+}
+
 /// Test multiple concurrent gRPC clients
 #[tokio::test]
 async fn test_concurrent_grpc_clients() {
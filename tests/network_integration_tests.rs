@@ -4,6 +4,8 @@
 
 use drfe_r::coordinates::NodeId;
 use drfe_r::network::{NetworkLayer, Packet, MAX_PACKET_SIZE};
+#[cfg(feature = "testing")]
+use drfe_r::testing::{TestCluster, Topology};
 use drfe_r::PoincareDiskPoint;
 use std::sync::Arc;
 use std::time::Duration;
@@ -485,97 +487,18 @@ async fn test_packet_routing_between_two_nodes() {
 }
 
 /// Test packet routing between multiple nodes (3-node chain)
+#[cfg(feature = "testing")]
 #[tokio::test]
 async fn test_packet_routing_three_node_chain() {
-    // Create three nodes
-    let node1 = Arc::new(
-        DistributedNode::new(
-            NodeId::new("node1"),
-            "127.0.0.1:0",
-            "127.0.0.1:0",
-        )
-        .await
-        .unwrap(),
-    );
-
-    let node2 = Arc::new(
-        DistributedNode::new(
-            NodeId::new("node2"),
-            "127.0.0.1:0",
-            "127.0.0.1:0",
-        )
-        .await
-        .unwrap(),
-    );
-
-    let node3 = Arc::new(
-        DistributedNode::new(
-            NodeId::new("node3"),
-            "127.0.0.1:0",
-            "127.0.0.1:0",
-        )
-        .await
-        .unwrap(),
-    );
+    // Chain topology: node0 <-> node1 <-> node2
+    let cluster = TestCluster::new(3).topology(Topology::Line).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
 
-    // Start all nodes
-    let n1 = Arc::clone(&node1);
-    let h1 = tokio::spawn(async move { n1.start(vec![]).await });
+    // Send packet from node0 to node2 (should route through node1)
+    let hops = cluster.assert_delivery(0, 2).await;
+    assert!(hops >= 2);
 
-    let n2 = Arc::clone(&node2);
-    let h2 = tokio::spawn(async move { n2.start(vec![]).await });
-
-    let n3 = Arc::clone(&node3);
-    let h3 = tokio::spawn(async move { n3.start(vec![]).await });
-
-    tokio::time::sleep(Duration::from_millis(200)).await;
-
-    // Set up chain topology: node1 <-> node2 <-> node3
-    {
-        // Node1 knows node2
-        node1.add_neighbor(drfe_r::network::NeighborInfo::new(
-            NodeId::new("node2"),
-            node2.coord().await.point,
-            node2.local_tcp_addr(),
-        )).await;
-
-        // Node2 knows node1 and node3
-        node2.add_neighbor(drfe_r::network::NeighborInfo::new(
-            NodeId::new("node1"),
-            node1.coord().await.point,
-            node1.local_tcp_addr(),
-        )).await;
-        node2.add_neighbor(drfe_r::network::NeighborInfo::new(
-            NodeId::new("node3"),
-            node3.coord().await.point,
-            node3.local_tcp_addr(),
-        )).await;
-
-        // Node3 knows node2
-        node3.add_neighbor(drfe_r::network::NeighborInfo::new(
-            NodeId::new("node2"),
-            node2.coord().await.point,
-            node2.local_tcp_addr(),
-        )).await;
-    }
-
-    tokio::time::sleep(Duration::from_millis(100)).await;
-
-    // Send packet from node1 to node3 (should route through node2)
-    let result = node1
-        .send_packet(NodeId::new("node3"), b"Hello node3".to_vec(), 64)
-        .await;
-
-    assert!(result.is_ok());
-
-    // Cleanup
-    node1.shutdown().await;
-    node2.shutdown().await;
-    node3.shutdown().await;
-    tokio::time::sleep(Duration::from_millis(100)).await;
-    h1.abort();
-    h2.abort();
-    h3.abort();
+    cluster.shutdown().await;
 }
 
 /// Test node coordinate updates
@@ -1349,3 +1272,1119 @@ async fn test_periodic_partition_healing_monitor() {
     // Abort monitor handle
     monitor_handle.abort();
 }
+
+/// Test a reliable stream across a three-node chain
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_stream_delivers_in_order_across_chain() {
+    // Chain topology: node0 <-> node1 <-> node2
+    let cluster = TestCluster::new(3).topology(Topology::Line).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+
+    let node3_id = cluster.id(2);
+    let message: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+    let stream_id = nodes[0].open_stream(&node3_id).await;
+    nodes[0].stream_write(&node3_id, stream_id, &message).await.unwrap();
+    nodes[0].stream_close(&node3_id, stream_id).await.unwrap();
+
+    let node1_id = cluster.id(0);
+    let mut received = Vec::new();
+    let result = timeout(Duration::from_secs(10), async {
+        while received.len() < message.len() {
+            received.extend(nodes[2].stream_read(&node1_id, stream_id).await);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await;
+
+    assert!(result.is_ok(), "stream timed out after {} bytes", received.len());
+    assert_eq!(received, message);
+    // Fully read, so the finished stream was released
+    assert!(nodes[2].incoming_streams().await.is_empty());
+
+    cluster.shutdown().await;
+}
+
+/// Test multicast delivery between a member and a non-member publisher
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_group_publish_reaches_members() {
+    let cluster = TestCluster::new(2).topology(Topology::Line).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+
+    nodes[0].join_group("news").await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(nodes[0].joined_groups().await, vec!["news".to_string()]);
+
+    let message_id = nodes[1].send_to_group("news", b"headline".to_vec()).await;
+
+    let received = timeout(Duration::from_secs(5), async {
+        loop {
+            let messages = nodes[0].group_messages("news").await;
+            if !messages.is_empty() {
+                return messages;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("group message not delivered");
+
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].message_id, message_id);
+    assert_eq!(received[0].origin, cluster.id(1));
+    assert_eq!(received[0].payload, b"headline");
+    // The publisher is not a member and keeps no copy
+    assert!(nodes[1].group_messages("news").await.is_empty());
+
+    cluster.shutdown().await;
+}
+
+/// Test that a partition blocks delivery until it heals
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_delivery_across_partition_and_heal() {
+    let cluster = TestCluster::new(4).topology(Topology::Ring).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    cluster.assert_delivery(0, 2).await;
+
+    cluster.partition(&[&[0, 1], &[2, 3]]).await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    cluster.assert_delivery(0, 1).await;
+    cluster.assert_no_delivery(0, 2).await;
+
+    cluster.heal().await;
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    cluster.assert_delivery(0, 2).await;
+
+    cluster.shutdown().await;
+}
+
+/// Test policy TTLs and TTL statistics across a three-node chain
+#[tokio::test]
+async fn test_ttl_policy_and_expiry_stats() {
+    use drfe_r::network::PacketType;
+    use drfe_r::ttl_policy::QosClass;
+
+    let mut nodes = Vec::new();
+    let mut handles = Vec::new();
+    for name in ["node1", "node2", "node3"] {
+        let node = Arc::new(
+            DistributedNode::new(NodeId::new(name), "127.0.0.1:0", "127.0.0.1:0")
+                .await
+                .unwrap(),
+        );
+        let n = Arc::clone(&node);
+        handles.push(tokio::spawn(async move { n.start(vec![]).await }));
+        nodes.push(node);
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    for (a, b) in [(0, 1), (1, 2)] {
+        nodes[a].add_neighbor(drfe_r::network::NeighborInfo::new(
+            nodes[b].id().clone(),
+            nodes[b].coord().await.point,
+            nodes[b].local_tcp_addr(),
+        )).await;
+        nodes[b].add_neighbor(drfe_r::network::NeighborInfo::new(
+            nodes[a].id().clone(),
+            nodes[a].coord().await.point,
+            nodes[a].local_tcp_addr(),
+        )).await;
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let node3_id = NodeId::new("node3");
+    let ttl = nodes[0].estimate_ttl(PacketType::Data, QosClass::Interactive, &node3_id).await;
+    assert!((8..=128).contains(&ttl));
+    assert_eq!(nodes[0].estimate_ttl(PacketType::Heartbeat, QosClass::Standard, &node3_id).await, 1);
+
+    nodes[0]
+        .send_packet_with_qos(node3_id.clone(), b"policy".to_vec(), QosClass::Interactive)
+        .await
+        .unwrap();
+    // A TTL of one cannot cross the relay
+    nodes[0].send_packet(node3_id.clone(), b"short".to_vec(), 1).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let delivered = nodes[2].ttl_stats().await;
+    let data = delivered
+        .iter()
+        .find(|e| e.packet_type == PacketType::Data && e.qos_class == QosClass::Interactive)
+        .expect("no delivery recorded");
+    assert_eq!(data.counters.delivered, 1);
+    assert_eq!(data.counters.delivered_hops, 2);
+
+    let relay = nodes[1].ttl_stats().await;
+    let expired = relay
+        .iter()
+        .find(|e| e.packet_type == PacketType::Data && e.qos_class == QosClass::Standard)
+        .expect("no expiry recorded");
+    assert_eq!(expired.counters.expired, 1);
+    assert_eq!(expired.expiry_rate, 1.0);
+
+    for node in &nodes {
+        node.shutdown().await;
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    for handle in handles {
+        handle.abort();
+    }
+}
+
+/// Test routed and single-hop custom packets, with a relay that does not know the type
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_custom_packet_handlers() {
+    use drfe_r::plugins::{handler, CustomPacket, ForwardingMode, PluginError};
+
+    let cluster = TestCluster::new(3).topology(Topology::Line).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<CustomPacket>();
+    for code in [100, 101] {
+        let mode = if code == 100 { ForwardingMode::Routed } else { ForwardingMode::SingleHop };
+        let tx = tx.clone();
+        let on_packet = handler(move |packet| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(packet);
+            }
+        });
+        nodes[2].register_packet_handler(code, mode, on_packet).await.unwrap();
+        nodes[0].register_packet_handler(code, mode, handler(|_| async {})).await.unwrap();
+    }
+    assert_eq!(
+        nodes[0].register_packet_handler(100, ForwardingMode::Routed, handler(|_| async {})).await,
+        Err(PluginError::AlreadyRegistered(100))
+    );
+
+    // Routed across the relay, which has no handler for the code
+    let packet_id = nodes[0].send_custom_packet(cluster.id(2), 100, b"ping".to_vec()).await.unwrap();
+    let received = timeout(Duration::from_secs(5), rx.recv()).await.unwrap().unwrap();
+    assert_eq!(received.type_code, 100);
+    assert_eq!(received.packet_id, packet_id);
+    assert_eq!(received.source, cluster.id(0));
+    assert_eq!(received.payload, b"ping");
+    assert_eq!(received.hops, 2);
+
+    // Single-hop packets only reach neighbors
+    assert!(nodes[0].send_custom_packet(cluster.id(2), 101, Vec::new()).await.is_err());
+    nodes[1].register_packet_handler(101, ForwardingMode::SingleHop, handler(|_| async {})).await.unwrap();
+    nodes[1].send_custom_packet(cluster.id(2), 101, b"hop".to_vec()).await.unwrap();
+    let received = timeout(Duration::from_secs(5), rx.recv()).await.unwrap().unwrap();
+    assert_eq!((received.type_code, received.hops), (101, 1));
+
+    let stats = nodes[2].custom_packet_stats().await;
+    assert_eq!(stats.iter().map(|(code, s)| (*code, s.handled)).collect::<Vec<_>>(), vec![(100, 1), (101, 1)]);
+
+    cluster.shutdown().await;
+}
+
+/// Test that a broadcast reaches every node exactly once, flooded and along the tree
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_broadcast_reaches_all_nodes_once() {
+    use drfe_r::broadcast::{BroadcastConfig, BroadcastMode};
+    use drfe_r::config::ConfigUpdate;
+
+    let cluster = TestCluster::new(5).topology(Topology::Ring).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+
+    let collect = |expected: usize| async move {
+        let mut received = Vec::new();
+        let _ = timeout(Duration::from_secs(5), async {
+            while received.len() < expected {
+                for node in &nodes[1..] {
+                    received.extend(node.broadcast_messages().await);
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        for node in &nodes[1..] {
+            received.extend(node.broadcast_messages().await);
+        }
+        received
+    };
+
+    let message_id = nodes[0].broadcast(b"flood".to_vec()).await;
+    let received = collect(4).await;
+    assert_eq!(received.len(), 4);
+    assert!(received.iter().all(|m| m.message_id == message_id && m.origin == cluster.id(0)));
+    assert!(nodes[0].broadcast_messages().await.is_empty());
+    // The ring closes, so some node sees a second copy
+    let duplicates: u64 = futures_util::future::join_all(nodes.iter().map(|n| n.broadcast_stats()))
+        .await
+        .iter()
+        .map(|s| s.duplicates)
+        .sum();
+    assert!(duplicates >= 1);
+
+    let update = ConfigUpdate {
+        broadcast: Some(BroadcastConfig { mode: BroadcastMode::Tree, ..BroadcastConfig::default() }),
+        ..ConfigUpdate::default()
+    };
+    for node in nodes {
+        node.apply_config(&update).await.unwrap();
+    }
+
+    // Duplicates prune the ring down to a spanning tree
+    let flooded: u64 = forwarded(nodes).await;
+    for round in 0..3 {
+        nodes[0].broadcast(format!("tree {}", round).into_bytes()).await;
+        let received = collect(4).await;
+        assert_eq!(received.len(), 4);
+    }
+    let before = forwarded(nodes).await;
+    nodes[0].broadcast(b"pruned".to_vec()).await;
+    let received = collect(4).await;
+    assert_eq!(received.len(), 4);
+    assert!(received.iter().all(|m| m.payload == b"pruned"));
+    // A tree over five nodes has four links
+    assert_eq!(forwarded(nodes).await - before, 4);
+    assert!(flooded > 4);
+
+    cluster.shutdown().await;
+}
+
+/// Test that neighbors share their neighbor lists, giving each node a two-hop view
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_neighbor_lists_exchanged() {
+    use drfe_r::config::ConfigUpdate;
+    use drfe_r::neighbor_exchange::NeighborExchangeConfig;
+
+    let cluster = TestCluster::new(3).topology(Topology::Line).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+
+    let update = ConfigUpdate {
+        neighbor_exchange: Some(NeighborExchangeConfig { enabled: true, interval_ms: 500, ..Default::default() }),
+        ..ConfigUpdate::default()
+    };
+    for node in nodes {
+        node.apply_config(&update).await.unwrap();
+    }
+
+    // The ends of the line learn about each other through the middle node
+    let learned = timeout(Duration::from_secs(10), async {
+        while !nodes[0].two_hop_neighbors().await.contains(&cluster.id(2)) {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await;
+    assert!(learned.is_ok());
+    assert!(!nodes[0].two_hop_neighbors().await.contains(&cluster.id(0)));
+    let stats = nodes[1].neighbor_exchange_stats().await;
+    assert!(stats.digests_sent >= 2);
+    assert!(stats.full_lists_sent >= 1);
+
+    cluster.shutdown().await;
+}
+
+/// Test that acks validate cached next hops and losing the neighbor drops them
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_path_cache_validated_by_acks() {
+    use drfe_r::config::ConfigUpdate;
+    use drfe_r::network::DeliveryEvent;
+    use drfe_r::path_cache::PathCacheConfig;
+
+    let cluster = TestCluster::new(3).topology(Topology::Line).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+
+    let update = ConfigUpdate {
+        path_cache: Some(PathCacheConfig { enabled: true, ..Default::default() }),
+        ..ConfigUpdate::default()
+    };
+    for node in nodes {
+        node.apply_config(&update).await.unwrap();
+    }
+
+    let mut events = nodes[0].subscribe_deliveries();
+    let id = nodes[0].send_tracked_packet(cluster.id(2), b"first".to_vec(), 64).await.unwrap();
+    let acked = timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(DeliveryEvent::Acked { packet_id, .. }) = events.recv().await {
+                if packet_id == id {
+                    break;
+                }
+            }
+        }
+    })
+    .await;
+    assert!(acked.is_ok());
+    let stats = nodes[0].path_cache_stats().await;
+    assert!(stats.validations >= 1);
+    assert_eq!(stats.cached, 1);
+
+    nodes[0].send_packet(cluster.id(2), b"second".to_vec(), 64).await.unwrap();
+    assert_eq!(nodes[0].path_cache_stats().await.hits, 1);
+
+    nodes[0].handle_neighbor_leave(&cluster.id(1)).await.unwrap();
+    let stats = nodes[0].path_cache_stats().await;
+    assert_eq!((stats.cached, stats.neighbor_invalidations), (0, 1));
+
+    cluster.shutdown().await;
+}
+
+/// Test that convergence reports reach every node and coordinate moves show up in them
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_convergence_reports_gossiped() {
+    use drfe_r::config::ConfigUpdate;
+    use drfe_r::convergence::{ConvergenceConfig, ConvergenceStatus};
+
+    let cluster = TestCluster::new(3).topology(Topology::Line).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+
+    let update = ConfigUpdate {
+        convergence: Some(ConvergenceConfig { enabled: true, interval_ms: 500, ..Default::default() }),
+        ..ConfigUpdate::default()
+    };
+    for node in nodes {
+        node.apply_config(&update).await.unwrap();
+        node.trigger_coordinate_update(true).await.unwrap();
+    }
+
+    // The ends of the line hear about each other through the middle node
+    let reported = timeout(Duration::from_secs(10), async {
+        while nodes[0].convergence().await.nodes < 3 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await;
+    assert!(reported.is_ok());
+    let summary = nodes[0].convergence().await;
+    assert_ne!(summary.status, ConvergenceStatus::Unknown);
+    assert!(summary.stress.max >= summary.stress.p50);
+    assert!(summary.displacement.max > 0.0);
+
+    cluster.shutdown().await;
+}
+
+/// Test that sends fall over from a dead endpoint of a neighbor to a working one
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_multihomed_neighbor_failover() {
+    use drfe_r::config::ConfigUpdate;
+    use drfe_r::multihoming::{Endpoint, EndpointSet, MultihomingConfig, Transport};
+
+    let cluster = TestCluster::new(2).topology(Topology::Line).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+    let update = ConfigUpdate {
+        multihoming: Some(MultihomingConfig { enabled: true, failure_threshold: 1, ..Default::default() }),
+        ..ConfigUpdate::default()
+    };
+    nodes[0].apply_config(&update).await.unwrap();
+
+    // The preferred endpoint has nothing listening
+    let dead = Endpoint::new(Transport::Tcp, "127.0.0.1:1".parse().unwrap());
+    let live = Endpoint { preference: 1, ..Endpoint::new(Transport::Tcp, nodes[1].local_tcp_addr()) };
+    let mut neighbor = nodes[0].get_neighbor(&cluster.id(1)).await.unwrap();
+    neighbor.endpoints = EndpointSet::advertised(&[dead, live], nodes[1].local_tcp_addr().ip());
+    nodes[0].add_neighbor(neighbor).await;
+
+    nodes[0].send_packet(cluster.id(1), b"first".to_vec(), 64).await.unwrap();
+    nodes[0].send_packet(cluster.id(1), b"second".to_vec(), 64).await.unwrap();
+
+    let neighbor = nodes[0].get_neighbor(&cluster.id(1)).await.unwrap();
+    let [dead, live] = neighbor.endpoints.endpoints() else {
+        panic!("expected two endpoints");
+    };
+    // The dead endpoint is skipped once it failed
+    assert_eq!((dead.sent, dead.failed), (1, 1));
+    assert!(dead.down_until_ms.is_some());
+    assert_eq!((live.sent, live.failed), (2, 0));
+
+    cluster.shutdown().await;
+}
+
+/// Test that payloads are sealed end to end and unknown destinations are refused
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_end_to_end_encrypted_delivery() {
+    use drfe_r::config::ConfigUpdate;
+    use drfe_r::e2e_encryption::E2eConfig;
+    use drfe_r::network::DeliveryEvent;
+    use ed25519_dalek::SigningKey;
+
+    let cluster = TestCluster::new(3).topology(Topology::Line).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+
+    let keys: Vec<SigningKey> = (0..3).map(|i| SigningKey::from_bytes(&[i as u8 + 1; 32])).collect();
+    let update = ConfigUpdate {
+        e2e: Some(E2eConfig { enabled: true, require_encrypted: true, ..Default::default() }),
+        ..ConfigUpdate::default()
+    };
+    for (node, key) in nodes.iter().zip(&keys) {
+        node.apply_config(&update).await.unwrap();
+        node.set_identity_key(key).await;
+    }
+    nodes[0].add_identity_key(cluster.id(2), keys[2].verifying_key().as_bytes()).await.unwrap();
+    nodes[2].add_identity_key(cluster.id(0), keys[0].verifying_key().as_bytes()).await.unwrap();
+
+    let mut events = nodes[2].subscribe_deliveries();
+    nodes[0].send_packet(cluster.id(2), b"for your eyes only".to_vec(), 64).await.unwrap();
+    let delivered = timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(DeliveryEvent::Delivered { payload, .. }) = events.recv().await {
+                return payload;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(delivered, b"for your eyes only");
+    assert_eq!(nodes[0].encryption_stats().await.sealed, 1);
+    assert_eq!(nodes[2].encryption_stats().await.opened, 1);
+
+    // Node 1 knows no keys, so it cannot seal for node 2
+    let err = nodes[1].send_packet(cluster.id(2), b"hi".to_vec(), 64).await.unwrap_err();
+    assert_eq!(err.code(), "encryption.unknown_key");
+
+    cluster.shutdown().await;
+}
+
+/// Test that an onion packet reaches its destination through two relays
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_onion_routed_delivery() {
+    use drfe_r::config::ConfigUpdate;
+    use drfe_r::multihoming::MultihomingConfig;
+    use drfe_r::network::DeliveryEvent;
+    use drfe_r::onion::OnionConfig;
+    use ed25519_dalek::SigningKey;
+
+    let cluster = TestCluster::new(4).topology(Topology::Full).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+
+    let keys: Vec<SigningKey> = (0..4).map(|i| SigningKey::from_bytes(&[i as u8 + 11; 32])).collect();
+    for (i, node) in nodes.iter().enumerate() {
+        let relay = i == 1 || i == 2;
+        let update = ConfigUpdate {
+            onion: Some(OnionConfig { relay, min_separation: 0.0, ..Default::default() }),
+            // Rediscovery then carries the relays' TCP endpoints
+            multihoming: Some(MultihomingConfig { enabled: true, ..Default::default() }),
+            ..ConfigUpdate::default()
+        };
+        node.apply_config(&update).await.unwrap();
+        node.set_identity_key(&keys[i]).await;
+        for (j, key) in keys.iter().enumerate() {
+            if i != j {
+                node.add_identity_key(cluster.id(j), key.verifying_key().as_bytes()).await.unwrap();
+            }
+        }
+    }
+
+    // Nothing advertised relay capability to node 0 yet
+    let err = nodes[0].send_onion_packet(cluster.id(3), b"x".to_vec()).await.unwrap_err();
+    assert_eq!(err.code(), "encryption.too_few_relays");
+
+    // Rediscovery tells node 0 which neighbors relay
+    let relays = [nodes[1].local_udp_addr(), nodes[2].local_udp_addr()];
+    nodes[0].join_network(&relays, Duration::from_millis(500)).await.unwrap();
+
+    let mut events = nodes[3].subscribe_deliveries();
+    let path = nodes[0].send_onion_packet(cluster.id(3), b"unobserved".to_vec()).await.unwrap();
+    assert_eq!(path.len(), 2);
+    assert!(path.iter().all(|id| *id == cluster.id(1) || *id == cluster.id(2)));
+    let (source, payload) = timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(DeliveryEvent::Delivered { source, payload, .. }) = events.recv().await {
+                return (source, payload);
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!((source, payload), (cluster.id(0), b"unobserved".to_vec()));
+    assert_eq!(nodes[1].onion_stats().await.relayed + nodes[2].onion_stats().await.relayed, 2);
+    assert_eq!(nodes[3].onion_stats().await.delivered, 1);
+
+    cluster.shutdown().await;
+}
+
+/// Test that the NAT self-test finds loopback open and advertises the result
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_nat_self_test_on_open_network() {
+    use drfe_r::config::ConfigUpdate;
+    use drfe_r::multihoming::MultihomingConfig;
+    use drfe_r::nat::{NatConfig, NatType};
+    use drfe_r::network::DistributedNode;
+
+    let cluster = TestCluster::new(5).topology(Topology::Ring).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+    let update = ConfigUpdate {
+        multihoming: Some(MultihomingConfig { enabled: true, ..Default::default() }),
+        nat: Some(NatConfig { peers: 2, ..Default::default() }),
+        ..ConfigUpdate::default()
+    };
+    // Rediscovery over UDP tells ring neighbors each other's endpoints
+    for (i, node) in nodes.iter().enumerate() {
+        node.apply_config(&update).await.unwrap();
+        let ring = [nodes[(i + 1) % 5].local_udp_addr(), nodes[(i + 4) % 5].local_udp_addr()];
+        node.join_network(&ring, Duration::from_millis(300)).await.unwrap();
+    }
+
+    // Nodes 1 and 4 answer, and nodes 2 and 3 send the unsolicited datagrams
+    let report = nodes[0].run_nat_self_test().await.unwrap();
+    assert_eq!(report.nat_type, NatType::Open);
+    assert_eq!(report.observations.len(), 2);
+    for observation in &report.observations {
+        assert_eq!(observation.udp_mapped, Some(nodes[0].local_udp_addr()));
+        assert!(observation.unsolicited_udp && observation.tcp);
+    }
+    assert_eq!(nodes[0].nat_report().await, Some(report));
+
+    nodes[0].join_network(&[nodes[1].local_udp_addr()], Duration::from_millis(300)).await.unwrap();
+    assert_eq!(nodes[1].get_neighbor(&cluster.id(0)).await.unwrap().nat, NatType::Open);
+
+    let alone = DistributedNode::new(NodeId::new("alone"), "127.0.0.1:0", "127.0.0.1:0").await.unwrap();
+    assert_eq!(alone.run_nat_self_test().await.unwrap_err().code(), "network.no_nat_peers");
+
+    cluster.shutdown().await;
+}
+
+/// Test that a large payload is chunked, fetched over streams and reassembled
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_chunked_content_transfer() {
+    use drfe_r::config::ConfigUpdate;
+    use drfe_r::content::{ContentConfig, ContentId};
+    use drfe_r::network::DeliveryEvent;
+
+    let cluster = TestCluster::new(3).topology(Topology::Line).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+    let update = ConfigUpdate {
+        content: Some(ContentConfig { threshold: 8 * 1024, chunk_size: 4 * 1024, ..Default::default() }),
+        ..ConfigUpdate::default()
+    };
+    for node in nodes {
+        node.apply_config(&update).await.unwrap();
+    }
+
+    let payload: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let delivered = |node: &Arc<DistributedNode>| {
+        let mut events = node.subscribe_deliveries();
+        async move {
+            timeout(Duration::from_secs(10), async {
+                loop {
+                    if let Ok(DeliveryEvent::Delivered { packet_id, source, payload, .. }) = events.recv().await {
+                        return (packet_id, source, payload);
+                    }
+                }
+            })
+            .await
+            .unwrap()
+        }
+    };
+
+    let receive = delivered(&nodes[2]);
+    let content_id = nodes[0].send_content(cluster.id(2), payload.clone()).await.unwrap();
+    assert_eq!(content_id, ContentId::of(&payload).to_string());
+    assert_eq!(receive.await, (content_id.clone(), cluster.id(0), payload.clone()));
+    let stats = nodes[2].content_stats().await;
+    assert_eq!((stats.chunks_fetched, stats.completed), (25, 1));
+    assert_eq!(nodes[0].content_stats().await.chunks_served, 25);
+
+    // Node 2 now holds the chunks and serves a manifest naming it
+    let mut manifest = nodes[0].publish_content(&payload).await;
+    manifest.holders = vec![cluster.id(2)];
+    let receive = delivered(&nodes[1]);
+    nodes[1].fetch_content(manifest).await.unwrap();
+    assert_eq!(receive.await, (content_id, cluster.id(2), payload));
+
+    // Small payloads still travel as a single Data packet
+    let receive = delivered(&nodes[2]);
+    let packet_id = nodes[0].send_content(cluster.id(2), b"small".to_vec()).await.unwrap();
+    assert_eq!(receive.await, (packet_id, cluster.id(0), b"small".to_vec()));
+
+    cluster.shutdown().await;
+}
+
+/// Test that a chaos experiment is rolled back when delivery drops below its abort threshold
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_chaos_experiment_aborts_on_delivery_ratio() {
+    use drfe_r::chaos::{AbortCondition, ChaosExperiment, ChaosExperimentConfig, ChaosFault, ExperimentState};
+    use drfe_r::config::ConfigUpdate;
+    use drfe_r::network::DeliveryEvent;
+    use drfe_r::route_stats::RouteStatsConfig;
+
+    let cluster = TestCluster::new(2).topology(Topology::Line).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+    let mut status = nodes[0].subscribe_chaos_experiments();
+    let update = ConfigUpdate {
+        route_stats: Some(RouteStatsConfig { enabled: true, ..Default::default() }),
+        chaos_experiments: Some(ChaosExperimentConfig {
+            experiments: vec![ChaosExperiment {
+                name: "cut-off".into(),
+                targets: vec![cluster.id(1)],
+                fault: ChaosFault::Isolate,
+                duration_ms: 60_000,
+                abort: vec![AbortCondition::DeliveryRatioBelow { threshold: 0.5, min_packets: 3 }],
+            }],
+            ..Default::default()
+        }),
+        ..ConfigUpdate::default()
+    };
+    nodes[0].apply_config(&update).await.unwrap();
+
+    let running = status.wait_for(|s| s.first().is_some_and(|s| s.state == ExperimentState::Running));
+    timeout(Duration::from_secs(5), running).await.unwrap().unwrap();
+    for i in 0..3u8 {
+        nodes[0].send_packet(cluster.id(1), vec![i], 16).await.unwrap();
+    }
+    let aborted = timeout(Duration::from_secs(5), status.wait_for(|s| matches!(s[0].state, ExperimentState::Aborted { .. })))
+        .await
+        .unwrap()
+        .unwrap()
+        .clone();
+    assert!(matches!(&aborted[0].state, ExperimentState::Aborted { reason } if reason.contains("delivery ratio")));
+
+    // The fault is gone as soon as the experiment aborts
+    let mut events = nodes[1].subscribe_deliveries();
+    nodes[0].send_packet(cluster.id(1), b"after".to_vec(), 16).await.unwrap();
+    let payload = timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(DeliveryEvent::Delivered { payload, .. }) = events.recv().await {
+                return payload;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(payload, b"after".to_vec());
+    assert_eq!(nodes[0].chaos_experiments(), aborted);
+
+    cluster.shutdown().await;
+}
+
+#[cfg(feature = "testing")]
+async fn forwarded(nodes: &[Arc<DistributedNode>]) -> u64 {
+    futures_util::future::join_all(nodes.iter().map(|n| n.broadcast_stats()))
+        .await
+        .iter()
+        .map(|s| s.forwarded)
+        .sum()
+}
+
+/// Test that the lowest node ID is elected and a resigning leader is replaced
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_leader_election_and_failover() {
+    use drfe_r::config::ConfigUpdate;
+    use drfe_r::coordination::ElectionConfig;
+
+    let cluster = TestCluster::new(4).topology(Topology::Line).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+
+    let update = ConfigUpdate {
+        election: Some(ElectionConfig { lease_ms: 1_500, renew_interval_ms: 300, stable_after_ms: 0, ..Default::default() }),
+        ..ConfigUpdate::default()
+    };
+    for node in nodes {
+        node.apply_config(&update).await.unwrap();
+    }
+
+    let agree_on = |members: Vec<usize>, expected: NodeId| async move {
+        timeout(Duration::from_secs(5), async {
+            loop {
+                let leaders = futures_util::future::join_all(members.iter().map(|&i| nodes[i].leader())).await;
+                if leaders.iter().all(|l| l.as_ref() == Some(&expected)) {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .is_ok()
+    };
+
+    let mut ids: Vec<NodeId> = (0..4).map(|i| cluster.id(i)).collect();
+    ids.sort();
+    let leader = (0..4).find(|&i| cluster.id(i) == ids[0]).unwrap();
+    let mut watcher = nodes[leader].subscribe_leader();
+    assert!(agree_on(vec![0, 1, 2, 3], ids[0].clone()).await);
+    assert!(nodes[leader].is_leader().await);
+    assert_eq!(*watcher.borrow_and_update(), Some(ids[0].clone()));
+
+    // The leader drains and hands over to the next lowest ID
+    nodes[leader].drain(Duration::from_millis(100), Duration::from_secs(1)).await.unwrap();
+    let rest: Vec<usize> = (0..4).filter(|&i| i != leader).collect();
+    assert!(agree_on(rest.clone(), ids[1].clone()).await);
+    assert!(nodes[rest[0]].election_stats().await.leader_changes >= 2);
+
+    cluster.shutdown().await;
+}
+
+/// Test that a subscriber follows another node's presence across the overlay
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_presence_subscription() {
+    use drfe_r::presence::PresenceStatus;
+    use ed25519_dalek::SigningKey;
+
+    let cluster = TestCluster::new(3).topology(Topology::Line).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+    nodes[0].set_identity_key(&SigningKey::from_bytes(&[7; 32])).await;
+
+    let mut updates = nodes[2].presence_updates();
+    nodes[2].subscribe_presence(cluster.id(0)).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    nodes[0].set_presence(PresenceStatus::Online).await;
+    let online = timeout(Duration::from_secs(5), updates.recv()).await.expect("no presence update").unwrap();
+    assert_eq!((online.node.clone(), online.status, online.expired), (cluster.id(0), PresenceStatus::Online, false));
+
+    nodes[0].set_presence(PresenceStatus::Offline).await;
+    let offline = timeout(Duration::from_secs(5), updates.recv()).await.expect("no presence update").unwrap();
+    assert_eq!(offline.status, PresenceStatus::Offline);
+    assert!(offline.version > online.version);
+    assert_eq!(nodes[2].presence_of(&cluster.id(0)).await, Some(offline));
+
+    let stats = futures_util::future::join_all(nodes.iter().map(|n| n.presence_stats())).await;
+    assert_eq!(stats[0].published, 2);
+    assert_eq!(stats.iter().map(|s| s.stored).sum::<u64>(), 2);
+    assert_eq!(stats.iter().map(|s| s.rejected).sum::<u64>(), 0);
+
+    cluster.shutdown().await;
+}
+
+/// Test that neighbor list exchange gives every node its coordinate tree links
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_coordinate_tree_links_from_neighbor_lists() {
+    use drfe_r::config::ConfigUpdate;
+    use drfe_r::neighbor_exchange::NeighborExchangeConfig;
+
+    let cluster = TestCluster::new(5).topology(Topology::Full).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+
+    // Without neighbor lists no tree is known, so updates are flooded
+    assert!(nodes[0].coordinate_tree_links().await.is_none());
+
+    let update = ConfigUpdate {
+        neighbor_exchange: Some(NeighborExchangeConfig { enabled: true, interval_ms: 500, ..Default::default() }),
+        ..ConfigUpdate::default()
+    };
+    for node in nodes {
+        node.apply_config(&update).await.unwrap();
+    }
+    let links = timeout(Duration::from_secs(10), async {
+        loop {
+            let links = futures_util::future::join_all(nodes.iter().map(|n| n.coordinate_tree_links())).await;
+            if links.iter().all(|l| l.as_ref().is_some_and(|l| l.unknown.is_empty())) {
+                return links.into_iter().flatten().collect::<Vec<_>>();
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .expect("tree links not computed");
+
+    for (node, own) in nodes.iter().zip(&links) {
+        let neighbors: std::collections::HashSet<NodeId> = node.neighbors().await.into_iter().map(|n| n.id).collect();
+        assert!(own.parent.iter().chain(&own.children).all(|peer| neighbors.contains(peer)));
+        assert!(own.parent.as_ref().is_none_or(|parent| !own.children.contains(parent)));
+    }
+
+    cluster.shutdown().await;
+}
+
+/// Test that a packet that cannot meet its deadline is dropped and reported to its source
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_deadline_miss_reported_to_source() {
+    use drfe_r::config::ConfigUpdate;
+    use drfe_r::deadline::DeadlineConfig;
+    use drfe_r::network::{DeliveryEvent, NetworkError};
+
+    let cluster = TestCluster::new(3).topology(Topology::Line).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+
+    let mut events = nodes[0].subscribe_deliveries();
+    let id = nodes[0].send_packet_with_deadline(cluster.id(2), b"in time".to_vec(), Duration::from_secs(10)).await.unwrap();
+    let acked = timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(DeliveryEvent::Acked { packet_id, .. }) = events.recv().await {
+                if packet_id == id {
+                    break;
+                }
+            }
+        }
+    })
+    .await;
+    assert!(acked.is_ok());
+
+    // The source refuses to send a packet that is already late
+    let late = nodes[0].send_packet_with_deadline(cluster.id(2), b"late".to_vec(), Duration::ZERO).await;
+    assert!(matches!(late, Err(NetworkError::DeadlineExceeded(_))));
+    assert!(nodes[0].dead_letters().await.is_empty());
+
+    // With the check off at the source, the next hop drops it and reports back
+    let update = ConfigUpdate {
+        deadline: Some(DeadlineConfig { enabled: false, ..DeadlineConfig::default() }),
+        ..ConfigUpdate::default()
+    };
+    nodes[0].apply_config(&update).await.unwrap();
+    let id = nodes[0].send_packet_with_deadline(cluster.id(2), b"late".to_vec(), Duration::ZERO).await.unwrap();
+    let missed = timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(DeliveryEvent::DeadlineMissed { packet_id, destination, dropped_at, .. }) = events.recv().await {
+                if packet_id == id {
+                    return (destination, dropped_at);
+                }
+            }
+        }
+    })
+    .await
+    .expect("drop not reported");
+    assert_eq!(missed, (cluster.id(2), cluster.id(1)));
+
+    let (source, forwarder) = (nodes[0].deadline_stats().await, nodes[1].deadline_stats().await);
+    assert_eq!((source.dropped, source.notices_received), (1, 1));
+    assert_eq!((forwarder.dropped, forwarder.notices_sent), (1, 1));
+
+    cluster.shutdown().await;
+}
+
+/// Test that an adaptive neighbor cap trims the neighbor set and hands back to the fixed cap
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_adaptive_neighbor_cap_trims_neighbors() {
+    use drfe_r::config::ConfigUpdate;
+    use drfe_r::neighbor_cap::NeighborCapConfig;
+
+    let cluster = TestCluster::new(6).topology(Topology::Full).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+    assert_eq!(nodes[0].neighbor_count().await, 5);
+
+    // Bounds leave no room to grow: the cap lands on 3 whatever the headroom
+    let cap = NeighborCapConfig { enabled: true, min_neighbors: 3, max_neighbors: 3, ..NeighborCapConfig::default() };
+    nodes[0].apply_config(&ConfigUpdate { neighbor_cap: Some(cap), ..ConfigUpdate::default() }).await.unwrap();
+    nodes[0].adapt_neighbor_cap().await;
+    let status = nodes[0].neighbor_cap().await;
+    assert_eq!((status.cap, status.evaluations, status.evicted), (3, 1, 2));
+    assert!(status.demand >= 5);
+    assert_eq!(nodes[0].neighbor_count().await, 3);
+
+    // Peers heard from again compete for the slots rather than add to them
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    assert!(nodes[0].neighbor_count().await <= 3);
+
+    let fixed = NeighborCapConfig { enabled: false, ..NeighborCapConfig::default() };
+    let config = nodes[0].apply_config(&ConfigUpdate { neighbor_cap: Some(fixed), ..ConfigUpdate::default() }).await.unwrap();
+    assert_eq!(config.max_neighbors, 10);
+    nodes[0].adapt_neighbor_cap().await;
+    assert_eq!(nodes[0].neighbor_cap().await.evaluations, 1);
+
+    cluster.shutdown().await;
+}
+
+/// Test that presence records, subscriptions and chunks survive their holder draining
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_drain_hands_off_records_and_chunks() {
+    use drfe_r::coordinates::AnchorCoordinate;
+    use drfe_r::presence::{PresenceMessage, PresenceRecord, PresenceStatus};
+
+    let cluster = TestCluster::new(5).topology(Topology::Full).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+
+    // Presence of a node outside the cluster, held by the node nearest its anchor
+    let subject = NodeId::new("roaming-peer");
+    let anchor = AnchorCoordinate::from_id(&subject).point;
+    let mut distances = Vec::new();
+    for node in nodes {
+        distances.push(node.coord().await.point.hyperbolic_distance(&anchor));
+    }
+    let holder = (0..nodes.len()).min_by(|&a, &b| distances[a].total_cmp(&distances[b])).unwrap();
+    let watcher = (holder + 1) % nodes.len();
+    let publish = |node: usize, status: PresenceStatus, version: u64| {
+        let message = PresenceMessage::Publish(PresenceRecord::new(subject.clone(), status, version, 30_000));
+        let packet = Packet::new_presence(cluster.id(watcher), cluster.id(node), &message);
+        nodes[node].handle_packet(packet, "127.0.0.1:9".parse().unwrap())
+    };
+
+    let mut updates = nodes[watcher].presence_updates();
+    nodes[watcher].subscribe_presence(subject.clone()).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    publish(holder, PresenceStatus::Online, 1).await.unwrap();
+    let online = timeout(Duration::from_secs(5), updates.recv()).await.expect("no presence update").unwrap();
+    assert_eq!(online.status, PresenceStatus::Online);
+
+    let payload: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let manifest = nodes[holder].publish_content(&payload).await;
+    nodes[holder].drain(Duration::from_millis(600), Duration::from_secs(1)).await.unwrap();
+
+    // The subscription moved with the record, well before any refresh
+    publish(watcher, PresenceStatus::Offline, 2).await.unwrap();
+    let offline = timeout(Duration::from_secs(5), updates.recv()).await.expect("no presence update").unwrap();
+    assert_eq!((offline.node, offline.status, offline.version), (subject, PresenceStatus::Offline, 2));
+
+    // Every chunk went to a remaining node
+    let mut fetched = 0;
+    for (i, node) in nodes.iter().enumerate() {
+        if i != holder {
+            fetched += node.content_stats().await.chunks_fetched;
+        }
+    }
+    assert_eq!(fetched, manifest.chunks.len() as u64);
+}
+
+/// Test that a neighbor swallowing acks loses reputation, and that a trusted
+/// peer learns of it from a signed report
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_reputation_reports_spread_black_hole() {
+    use drfe_r::config::{ChaosSettings, ConfigUpdate};
+    use drfe_r::reputation::ReputationConfig;
+    use ed25519_dalek::SigningKey;
+
+    // Node 0 in the middle, node 1 the black hole, node 2 a trusted peer
+    let cluster = TestCluster::new(3).topology(Topology::Star).start().await.unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+
+    let keys: Vec<SigningKey> = (0..3).map(|i| SigningKey::from_bytes(&[i as u8 + 41; 32])).collect();
+    let reputation = ReputationConfig {
+        ack_timeout_ms: 300,
+        report_interval_ms: 500,
+        trusted_threshold: 0.6,
+        ..ReputationConfig::default()
+    };
+    for (i, node) in nodes.iter().enumerate() {
+        node.apply_config(&ConfigUpdate { reputation: Some(reputation.clone()), ..ConfigUpdate::default() })
+            .await
+            .unwrap();
+        node.set_identity_key(&keys[i]).await;
+    }
+    nodes[2].add_identity_key(cluster.id(0), keys[0].verifying_key().as_bytes()).await.unwrap();
+
+    // Acked traffic both ways makes nodes 0 and 2 trust each other
+    for _ in 0..3 {
+        cluster.assert_delivery(0, 2).await;
+        cluster.assert_delivery(2, 0).await;
+    }
+
+    // Node 1 takes packets but nothing it sends gets out, acks included
+    let chaos = ChaosSettings { enabled: true, packet_drop_rate: 1.0, ..ChaosSettings::default() };
+    nodes[1].apply_config(&ConfigUpdate { chaos: Some(chaos), ..ConfigUpdate::default() }).await.unwrap();
+    for _ in 0..5 {
+        cluster.assert_delivery(0, 1).await;
+    }
+
+    let black_hole = cluster.id(1);
+    timeout(Duration::from_secs(5), async {
+        while nodes[0].reputation_score(&black_hole).await >= 0.3 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .expect("unacked packets not blamed on the next hop");
+    assert!(nodes[0].reputation_score(&cluster.id(2)).await > 0.6);
+
+    // Node 2 never sent node 1 anything, yet distrusts it second-hand
+    timeout(Duration::from_secs(5), async {
+        while nodes[2].reputation_score(&black_hole).await >= 0.5 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .expect("signed report not merged");
+
+    cluster.shutdown().await;
+}
+
+/// Test that a neighbor dropping everything it forwards is caught by probing and routed around
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_probing_excludes_black_hole_neighbor() {
+    use drfe_r::chaos::{ChaosExperiment, ChaosExperimentConfig, ChaosFault};
+    use drfe_r::config::ConfigUpdate;
+    use drfe_r::probing::ProbeConfig;
+    use ed25519_dalek::SigningKey;
+
+    // Node 0 reaches node 3 through node 1 (the black hole) or node 2
+    let cluster = TestCluster::new(4)
+        .topology(Topology::Custom(vec![(0, 1), (0, 2), (1, 3), (2, 3)]))
+        .start()
+        .await
+        .unwrap();
+    cluster.await_convergence(Duration::from_secs(5)).await.unwrap();
+    let nodes = cluster.nodes();
+
+    let keys: Vec<SigningKey> = (0..4).map(|i| SigningKey::from_bytes(&[i as u8 + 61; 32])).collect();
+    for (i, node) in nodes.iter().enumerate() {
+        node.set_identity_key(&keys[i]).await;
+    }
+    nodes[0].add_identity_key(cluster.id(3), keys[3].verifying_key().as_bytes()).await.unwrap();
+    cluster.assert_delivery(0, 3).await;
+
+    // Node 1 keeps its links to node 0 but drops everything it hands node 3
+    let cut = ConfigUpdate {
+        chaos_experiments: Some(ChaosExperimentConfig {
+            experiments: vec![ChaosExperiment {
+                name: "black-hole".into(),
+                targets: vec![cluster.id(3)],
+                fault: ChaosFault::Isolate,
+                duration_ms: 60_000,
+                abort: vec![],
+            }],
+            ..Default::default()
+        }),
+        ..ConfigUpdate::default()
+    };
+    nodes[1].apply_config(&cut).await.unwrap();
+
+    let probing = ProbeConfig {
+        enabled: true,
+        interval_ms: 500,
+        probes_per_neighbor: 2,
+        probe_timeout_ms: 800,
+        min_probes: 4,
+        ..ProbeConfig::default()
+    };
+    nodes[0].apply_config(&ConfigUpdate { probing: Some(probing), ..ConfigUpdate::default() }).await.unwrap();
+
+    let black_hole = cluster.id(1);
+    timeout(Duration::from_secs(15), async {
+        while !nodes[0].excluded_neighbors().await.contains(&black_hole) {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .expect("black hole not excluded");
+    assert!(!nodes[0].excluded_neighbors().await.contains(&cluster.id(2)));
+    assert!(nodes[0].probe_stats(&cluster.id(2)).await.confirmed > 0);
+
+    // Traffic for node 3 now goes around node 1
+    for _ in 0..3 {
+        cluster.assert_delivery(0, 3).await;
+    }
+
+    cluster.shutdown().await;
+}
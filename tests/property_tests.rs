@@ -120,8 +120,9 @@ mod routing_properties {
                     adjacency.insert(NodeId::new(&format!("n{}", i)), Vec::new());
                 }
                 
-                // Add edges based on adjacency matrix
-                let node_ids: Vec<NodeId> = adjacency.keys().cloned().collect();
+                // Add edges based on adjacency matrix; indices map to IDs in
+                // a fixed order so a seed always yields the same topology
+                let node_ids: Vec<NodeId> = (0..num_nodes).map(|i| NodeId::new(&format!("n{}", i))).collect();
                 for i in 0..num_nodes {
                     for j in (i+1)..num_nodes {
                         // Use adjacency matrix value and edge probability
@@ -206,7 +207,8 @@ mod routing_properties {
             dest_idx in 0usize..5,
         ) {
             let router = create_router_from_adjacency(&adjacency);
-            let node_ids = router.node_ids();
+            let mut node_ids = router.node_ids();
+            node_ids.sort_by(|a, b| a.0.cmp(&b.0));
             
             if node_ids.len() < 2 {
                 return Ok(());
@@ -249,7 +251,8 @@ mod routing_properties {
             dest_idx in 0usize..5,
         ) {
             let router = create_router_from_adjacency(&adjacency);
-            let node_ids = router.node_ids();
+            let mut node_ids = router.node_ids();
+            node_ids.sort_by(|a, b| a.0.cmp(&b.0));
             
             if node_ids.len() < 2 {
                 return Ok(());
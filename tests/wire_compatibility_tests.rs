@@ -0,0 +1,157 @@
+//! Wire compatibility tests against golden fixtures
+//!
+//! `tests/fixtures/wire/v1` holds packets, checkpoints and a TZ table as
+//! written by protocol version 1. Every later version must still parse them,
+//! and must encode the canonical inputs below to the same bytes for as long
+//! as the format version is unchanged. After an intentional format change,
+//! bump the version, add the upgrade path and write a new fixture directory
+//! with `DRFE_BLESS_FIXTURES=1 cargo test --test wire_compatibility_tests`.
+
+use drfe_r::coordinate_precision::CoordinatePrecision;
+use drfe_r::coordinates::NodeId;
+use drfe_r::network::{CodecError, NeighborInfo, NodeCheckpoint, Packet, PacketType, PROTOCOL_VERSION};
+use drfe_r::tz_routing::{TZConfig, TZRoutingTable};
+use drfe_r::PoincareDiskPoint;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/wire/v1").join(name)
+}
+
+/// Read a fixture, first rewriting it from `bytes` when blessing
+fn golden(name: &str, bytes: &[u8]) -> Vec<u8> {
+    let path = fixture_path(name);
+    if std::env::var_os("DRFE_BLESS_FIXTURES").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, bytes).unwrap();
+    }
+    std::fs::read(&path).unwrap_or_else(|e| panic!("missing fixture {}: {}", path.display(), e))
+}
+
+/// Fix the fields that vary between runs
+fn canonical(mut packet: Packet) -> Packet {
+    packet.header.timestamp = 1_700_000_000_000;
+    packet.header.packet_id = format!("{}-{}-fixture", packet.header.source.0, packet.header.destination.0);
+    packet.header.sequence = 7;
+    packet
+}
+
+fn data_packet() -> Packet {
+    let target = PoincareDiskPoint::new(0.3, -0.2).unwrap();
+    canonical(Packet::new_data(NodeId::new("alice"), NodeId::new("bob"), target, b"hello".to_vec(), 64))
+}
+
+fn canonical_packets() -> Vec<(&'static str, Packet)> {
+    let mut signed = data_packet();
+    signed.header.encrypted = true;
+    signed.sign(&[7u8; 32]).unwrap();
+    let ack = canonical(Packet::new_ack(NodeId::new("bob"), &data_packet().header));
+    let point = PoincareDiskPoint::new(-0.45, 0.6).unwrap();
+    let update = canonical(Packet::new_coordinate_update_at(NodeId::new("alice"), point, 42, CoordinatePrecision::Fixed32));
+    vec![
+        ("data_packet.msgpack", data_packet()),
+        ("signed_data_packet.msgpack", signed),
+        ("ack_packet.msgpack", ack),
+        ("coordinate_update_packet.msgpack", update),
+    ]
+}
+
+fn canonical_checkpoint() -> NodeCheckpoint {
+    let neighbors = vec![
+        NeighborInfo::new(NodeId::new("bob"), PoincareDiskPoint::new(0.1, 0.2).unwrap(), "127.0.0.1:9001".parse().unwrap()),
+        NeighborInfo::new(NodeId::new("carol"), PoincareDiskPoint::new(-0.5, 0.05).unwrap(), "127.0.0.1:9002".parse().unwrap()),
+    ];
+    let mut checkpoint = NodeCheckpoint::new("alice".to_string(), PoincareDiskPoint::new(0.25, -0.125).unwrap(), 3, neighbors);
+    checkpoint.timestamp = 1_700_000_000;
+    checkpoint
+}
+
+/// Ring of 10 nodes with two chords
+fn tz_graph() -> HashMap<NodeId, Vec<NodeId>> {
+    let mut adjacency: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+    let mut connect = |a: usize, b: usize| {
+        adjacency.entry(NodeId::new(format!("n{}", a))).or_default().push(NodeId::new(format!("n{}", b)));
+        adjacency.entry(NodeId::new(format!("n{}", b))).or_default().push(NodeId::new(format!("n{}", a)));
+    };
+    for i in 0..10 {
+        connect(i, (i + 1) % 10);
+    }
+    connect(0, 5);
+    connect(2, 7);
+    adjacency
+}
+
+/// Test that golden packets parse and canonical packets encode identically
+#[test]
+fn test_packet_fixtures() {
+    for (name, packet) in canonical_packets() {
+        let bytes = packet.to_msgpack().unwrap();
+        let fixture = golden(name, &bytes);
+        assert_eq!(bytes, fixture, "{} no longer encodes to the golden bytes", name);
+
+        let parsed = Packet::from_msgpack(&fixture).unwrap();
+        assert_eq!(parsed.header.version, PROTOCOL_VERSION);
+        assert_eq!(parsed.header.packet_type, packet.header.packet_type);
+        assert_eq!(parsed.header.packet_id, packet.header.packet_id);
+        assert_eq!(parsed.payload, packet.payload);
+        assert_eq!(parsed.signature, packet.signature);
+    }
+
+    let signed = Packet::from_msgpack(&std::fs::read(fixture_path("signed_data_packet.msgpack")).unwrap()).unwrap();
+    assert!(signed.header.encrypted);
+    let ack = Packet::from_msgpack(&std::fs::read(fixture_path("ack_packet.msgpack")).unwrap()).unwrap();
+    assert_eq!(ack.header.packet_type, PacketType::Ack);
+    assert_eq!(ack.ack_info(), Some(("alice-bob-fixture".to_string(), false)));
+
+    // A packet from a future protocol version is refused, not misread
+    let mut future = data_packet();
+    future.header.version = PROTOCOL_VERSION + 1;
+    let err = Packet::from_msgpack(&future.to_msgpack().unwrap()).unwrap_err();
+    assert!(matches!(err, CodecError::UnsupportedVersion { .. }));
+    assert_eq!(err.code(), "codec.unsupported_version");
+}
+
+/// Test that golden checkpoints parse and canonical ones encode identically
+#[test]
+fn test_checkpoint_fixtures() {
+    let checkpoint = canonical_checkpoint();
+    let json = golden("checkpoint.json", checkpoint.to_json().unwrap().as_bytes());
+    assert_eq!(checkpoint.to_json().unwrap().as_bytes(), json.as_slice());
+    let msgpack = golden("checkpoint.msgpack", &checkpoint.to_msgpack().unwrap());
+    assert_eq!(checkpoint.to_msgpack().unwrap(), msgpack);
+
+    for parsed in [
+        NodeCheckpoint::from_json(std::str::from_utf8(&json).unwrap()).unwrap(),
+        NodeCheckpoint::from_msgpack(&msgpack).unwrap(),
+    ] {
+        assert!(parsed.is_compatible());
+        assert_eq!((parsed.node_id.as_str(), parsed.coord_version, parsed.timestamp), ("alice", 3, 1_700_000_000));
+        assert_eq!((parsed.coord.x, parsed.coord.y), (0.25, -0.125));
+        let neighbors: Vec<(&str, &str)> = parsed.neighbors.iter().map(|n| (n.id.as_str(), n.addr.as_str())).collect();
+        assert_eq!(neighbors, [("bob", "127.0.0.1:9001"), ("carol", "127.0.0.1:9002")]);
+    }
+}
+
+/// Test that the golden TZ table parses, routes and re-encodes identically
+#[test]
+fn test_tz_table_fixture() {
+    let adjacency = tz_graph();
+    let built = TZRoutingTable::build(&adjacency, TZConfig { num_landmarks: Some(3), seed: 7 }).unwrap();
+    let fixture = golden("tz_table.msgpack", &built.to_msgpack().unwrap());
+
+    let table = TZRoutingTable::from_msgpack(&fixture).unwrap();
+    assert_eq!(table.to_msgpack().unwrap(), fixture);
+    assert_eq!((table.landmarks.len(), table.node_info.len()), (3, 10));
+    assert_eq!(table.config.seed, 7);
+    for source in adjacency.keys() {
+        for destination in adjacency.keys().filter(|d| *d != source) {
+            let path = table.compute_path(source, destination).unwrap();
+            assert_eq!(path.last(), Some(destination));
+            assert!(path.windows(2).all(|hop| adjacency[&hop[0]].contains(&hop[1])));
+        }
+    }
+
+    let err = TZRoutingTable::from_msgpack(b"\x01\x02").unwrap_err();
+    assert_eq!(err.code(), "tz_table.decode");
+}